@@ -0,0 +1,49 @@
+//! Marine benchmarks - one core against all of them
+//!
+//! Run with `cargo bench --bench marine --features parallel`. A minute of
+//! synthetic 96 kHz audio (a chord, a beat and some noise) goes through
+//! `MarineProcessor::process_samples` and `process_samples_parallel`; the
+//! two find exactly the same peaks, so any difference is the thread pool.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use mem8_fs_lite::MarineProcessor;
+
+const SAMPLE_RATE: f64 = 96_000.0;
+
+/// 60 seconds of audio-like samples
+fn minute_of_audio() -> Vec<f64> {
+    let mut seed: u64 = 0x8b15;
+    (0..(60.0 * SAMPLE_RATE) as usize)
+        .map(|i| {
+            let t = i as f64 / SAMPLE_RATE;
+            let chord: f64 = [220.0, 277.2, 329.6].iter().map(|hz| (2.0 * std::f64::consts::PI * hz * t).sin()).sum();
+            let beat = if (t * 2.0).fract() < 0.05 { 0.6 } else { 0.0 };
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let noise = (seed >> 33) as f64 / (1u64 << 31) as f64 - 0.5;
+            0.2 * chord + beat + 0.05 * noise
+        })
+        .collect()
+}
+
+fn bench_parallel(c: &mut Criterion) {
+    let samples = minute_of_audio();
+    let serial = MarineProcessor::for_audio(SAMPLE_RATE).process_samples(&samples).len();
+    let parallel = MarineProcessor::for_audio(SAMPLE_RATE).process_samples_parallel(&samples, 1 << 16).len();
+    assert_eq!(serial, parallel, "the two found different peaks");
+    println!("🌊 {} peaks in a minute at 96 kHz, on {} threads", serial,
+        std::thread::available_parallelism().map_or(1, |n| n.get()));
+    
+    let mut group = c.benchmark_group("marine_minute");
+    group.sample_size(10);
+    group.throughput(Throughput::Elements(samples.len() as u64));
+    group.bench_function("serial", |b| {
+        b.iter(|| MarineProcessor::for_audio(SAMPLE_RATE).process_samples(black_box(&samples)))
+    });
+    group.bench_function("parallel", |b| {
+        b.iter(|| MarineProcessor::for_audio(SAMPLE_RATE).process_samples_parallel(black_box(&samples), 1 << 16))
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_parallel);
+criterion_main!(benches);
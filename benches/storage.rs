@@ -0,0 +1,171 @@
+//! Storage benchmarks - how fast, and how big on disk?
+//!
+//! Run with `cargo bench`. Before timing anything, the disk footprint of a
+//! 1 MiB packet is printed next to its input size (format v2 stores the raw
+//! bytes, so the ratio should sit right around 1.0×). The compression group
+//! ingests a mix of quiet, text-like and noisy payloads at a fixed high level
+//! and adaptively, after printing what each leaves on disk. The cold-retrieve
+//! group reads 64 KiB packets through a zero-size cache, buffered and (with
+//! `--features mmap`) memory-mapped. The small-files group writes 64-byte
+//! files through `Mem8Fs`, to wave storage and inline in the index (which
+//! skips data.m8 altogether, so should take about half as long).
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion, Throughput};
+use mem8_fs_lite::{compress, AdaptivePolicy, CompressionMode, Durability, FrequencyPreset, FsConfig, Mem8Fs, Mem8Lite, ReadMode};
+use tempfile::tempdir;
+
+const PACKET_SIZE: usize = 1 << 20;
+
+fn sample_data() -> Vec<u8> {
+    (0..PACKET_SIZE).map(|i| (i * 31 % 251) as u8).collect()
+}
+
+fn report_disk_usage(data: &[u8]) {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("footprint.m8");
+    let mut storage = Mem8Lite::new(&path, FrequencyPreset::GoldenRatio).unwrap();
+    let sig = storage.store(data, None).unwrap();
+    assert_eq!(storage.retrieve(&sig).unwrap(), data, "round trip changed the data");
+    storage.sync().unwrap();
+    
+    let on_disk = std::fs::metadata(&path).unwrap().len();
+    println!("💾 {} bytes in, {} bytes on disk ({:.3}×)",
+             data.len(), on_disk, on_disk as f64 / data.len() as f64);
+}
+
+/// Quiet (mostly zeros), text-like and noisy payloads, 256 KiB each
+fn mixed_payloads() -> Vec<Vec<u8>> {
+    let size = 256 * 1024;
+    let quiet: Vec<u8> = (0..size).map(|i| if i % 4096 < 8 { i as u8 } else { 0 }).collect();
+    let text: Vec<u8> = b"Hue logged 973 waves at 3:14, Trisha filed them under Q4-2024! "
+        .iter().copied().cycle().take(size).collect();
+    let mut state = 0x2545_F491_4F6C_DD1Du64;
+    let noise: Vec<u8> = (0..size).map(|_| {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state as u8
+    }).collect();
+    vec![quiet, text, noise]
+}
+
+fn report_compression(payloads: &[Vec<u8>], modes: &[(&str, CompressionMode)]) {
+    for (name, mode) in modes {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("footprint.m8");
+        let mut storage = Mem8Lite::new(&path, FrequencyPreset::GoldenRatio).unwrap().with_compression(*mode);
+        for payload in payloads {
+            storage.store(payload, None).unwrap();
+        }
+        let stats = storage.stats();
+        let input: usize = payloads.iter().map(Vec::len).sum();
+        println!("🗜️ {}: {} bytes in, {} bytes on disk ({:.2}×)", name, input, stats.total_size, stats.compression_ratio);
+    }
+}
+
+fn bench_compression(c: &mut Criterion) {
+    let payloads = mixed_payloads();
+    let modes = [
+        ("store_mixed_zstd19", CompressionMode::Zstd(compress::MAX_LEVEL)),
+        ("store_mixed_adaptive", CompressionMode::Adaptive(AdaptivePolicy::default())),
+    ];
+    report_compression(&payloads, &modes);
+    
+    let dir = tempdir().unwrap();
+    let mut group = c.benchmark_group("compression");
+    group.throughput(Throughput::Bytes(payloads.iter().map(|p| p.len() as u64).sum()));
+    for (name, mode) in modes {
+        let path = dir.path().join(format!("{}.m8", name));
+        let mut storage = Mem8Lite::with_cache_limit(path, FrequencyPreset::GoldenRatio, 0).unwrap().with_compression(mode);
+        let mut counter = 0u64;
+        group.bench_function(name, |b| b.iter(|| {
+            counter += 1;
+            for payload in &payloads {
+                storage.store(black_box(payload), Some(counter.to_le_bytes().to_vec())).unwrap();
+            }
+        }));
+    }
+    group.finish();
+}
+
+fn bench_storage(c: &mut Criterion) {
+    let data = sample_data();
+    report_disk_usage(&data);
+    
+    let dir = tempdir().unwrap();
+    let mut group = c.benchmark_group("lite");
+    group.throughput(Throughput::Bytes(PACKET_SIZE as u64));
+    
+    let mut storage = Mem8Lite::with_cache_limit(dir.path().join("bench.m8"), FrequencyPreset::GoldenRatio, 0).unwrap();
+    let mut counter = 0u64;
+    group.bench_function("store_1mib", |b| b.iter(|| {
+        // Fresh metadata so every iteration appends a new packet
+        counter += 1;
+        storage.store(black_box(&data), Some(counter.to_le_bytes().to_vec())).unwrap()
+    }));
+    
+    let sig = storage.store(&data, None).unwrap();
+    group.bench_function("retrieve_1mib_from_disk", |b| b.iter(|| {
+        storage.retrieve(black_box(&sig)).unwrap()
+    }));
+    
+    group.finish();
+}
+
+fn bench_cold_retrieve(c: &mut Criterion) {
+    let dir = tempdir().unwrap();
+    let path = dir.path().join("cold.m8");
+    let sigs: Vec<[u8; 32]> = {
+        let mut storage = Mem8Lite::new(&path, FrequencyPreset::GoldenRatio).unwrap();
+        (0..64u32).map(|i| {
+            let packet: Vec<u8> = (0..64 * 1024u32).map(|j| (i * 7 + j * 31 % 251) as u8).collect();
+            storage.store(&packet, None).unwrap()
+        }).collect()
+    };
+    
+    let modes = [
+        ("buffered", ReadMode::Buffered),
+        #[cfg(feature = "mmap")]
+        ("mmap", ReadMode::Mmap),
+    ];
+    
+    let mut group = c.benchmark_group("cold_retrieve");
+    group.throughput(Throughput::Bytes(64 * 1024));
+    for (name, mode) in modes {
+        // No cache, so every retrieve goes back to the file
+        let storage = Mem8Lite::with_cache_limit(&path, FrequencyPreset::GoldenRatio, 0).unwrap().with_read_mode(mode);
+        let mut next = 0;
+        group.bench_function(name, |b| b.iter(|| {
+            next = (next + 1) % sigs.len();
+            storage.retrieve(black_box(&sigs[next])).unwrap()
+        }));
+    }
+    group.finish();
+}
+
+fn bench_small_files(c: &mut Criterion) {
+    // No fsyncs (not even meta.m8's), so it's the writing itself that's timed
+    let configs = [
+        ("stored", FsConfig::default().with_durability(Durability::None)),
+        ("inline", FsConfig::default().with_durability(Durability::None).with_inline_threshold(256)),
+    ];
+    
+    let mut group = c.benchmark_group("small_files");
+    group.throughput(Throughput::Bytes(64));
+    for (name, config) in configs {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new_with_config(dir.path(), config).unwrap();
+        let mut counter = 0u64;
+        group.bench_function(name, |b| b.iter(|| {
+            // New content every time, so nothing is deduplicated away
+            counter += 1;
+            let mut data = [b'~'; 64];
+            data[..8].copy_from_slice(&counter.to_le_bytes());
+            fs.write("/small.txt", black_box(&data)).unwrap()
+        }));
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_storage, bench_compression, bench_cold_retrieve, bench_small_files);
+criterion_main!(benches);
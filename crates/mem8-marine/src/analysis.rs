@@ -0,0 +1,146 @@
+//! Loudness and tempo - the measures reported next to the Marine peaks
+//!
+//! Everything here works on mono samples in -1.0..=1.0; decoding, channel
+//! mixing and where the results get stored stay with the caller.
+//!
+//! Trisha reads the loudness, Hue taps along to the beat. 🥁
+
+use crate::PeakInfo;
+
+/// How many wonder moments `top_wonder_moments` keeps
+pub const MAX_WONDER_MOMENTS: usize = 64;
+
+/// A high-salience moment in a track
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WonderMoment {
+    /// Frame (mono sample) index of the peak
+    pub frame: usize,
+    
+    /// Marine salience at the peak
+    pub salience: f64,
+}
+
+/// Keep only the best peak per quarter-second window, strongest first
+///
+/// Loud passages produce hundreds of peaks; one per window keeps
+/// distinct moments apart instead of crowding into a single burst.
+pub fn top_wonder_moments(peaks: &[PeakInfo], sample_rate: f64) -> Vec<WonderMoment> {
+    let window = (sample_rate / 4.0).max(1.0) as usize;
+    let mut best: std::collections::BTreeMap<usize, WonderMoment> = std::collections::BTreeMap::new();
+    
+    for peak in peaks {
+        let slot = best.entry(peak.index / window)
+            .or_insert(WonderMoment { frame: peak.index, salience: peak.salience });
+        if peak.salience > slot.salience {
+            *slot = WonderMoment { frame: peak.index, salience: peak.salience };
+        }
+    }
+    
+    let mut moments: Vec<WonderMoment> = best.into_values().collect();
+    moments.sort_by(|a, b| b.salience.partial_cmp(&a.salience).unwrap_or(std::cmp::Ordering::Equal));
+    moments.truncate(MAX_WONDER_MOMENTS);
+    moments
+}
+
+/// Estimate the beat period from energy onsets (10 ms hops)
+pub fn estimate_beat_period(samples: &[f64], sample_rate: f64) -> Option<f64> {
+    let hop = (sample_rate / 100.0).max(1.0) as usize;
+    let energy: Vec<f64> = samples.chunks(hop)
+        .map(|chunk| chunk.iter().map(|s| s * s).sum::<f64>() / chunk.len() as f64)
+        .collect();
+    if energy.len() < 3 {
+        return None;
+    }
+    
+    // Spectral-flux style onset strength (positive energy jumps only)
+    let flux: Vec<f64> = energy.windows(2).map(|w| (w[1] - w[0]).max(0.0)).collect();
+    let mean = flux.iter().sum::<f64>() / flux.len() as f64;
+    let std = (flux.iter().map(|f| (f - mean).powi(2)).sum::<f64>() / flux.len() as f64).sqrt();
+    let threshold = mean + 2.0 * std;
+    
+    let mut onsets: Vec<usize> = Vec::new();
+    for (i, &f) in flux.iter().enumerate() {
+        let is_peak = f > threshold
+            && flux.get(i.wrapping_sub(1)).is_none_or(|&p| f >= p)
+            && flux.get(i + 1).is_none_or(|&n| f >= n);
+        // At least 50 ms between onsets
+        if is_peak && onsets.last().is_none_or(|&last| i - last >= 5) {
+            onsets.push(i);
+        }
+    }
+    if onsets.len() < 3 {
+        return None;
+    }
+    
+    let mut intervals: Vec<usize> = onsets.windows(2).map(|w| w[1] - w[0]).collect();
+    intervals.sort_unstable();
+    Some(intervals[intervals.len() / 2] as f64 * hop as f64 / sample_rate)
+}
+
+/// Calculate RMS (Root Mean Square) level
+pub fn calculate_rms(samples: &[f64]) -> f64 {
+    let sum: f64 = samples.iter().map(|s| s * s).sum();
+    (sum / samples.len() as f64).sqrt()
+}
+
+/// Largest absolute sample
+pub fn peak_level(samples: &[f64]) -> f64 {
+    samples.iter().fold(0.0, |a, &b| a.max(b.abs()))
+}
+
+/// Calculate dynamic range in dB
+pub fn calculate_dynamic_range(samples: &[f64]) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    
+    // Sort samples by absolute value
+    let mut sorted: Vec<f64> = samples.iter().map(|s| s.abs()).collect();
+    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    
+    // Get 95th percentile (loud) and 5th percentile (quiet)
+    let loud_idx = (sorted.len() as f64 * 0.95) as usize;
+    let quiet_idx = (sorted.len() as f64 * 0.05) as usize;
+    
+    let loud = sorted[loud_idx.min(sorted.len() - 1)];
+    let quiet = sorted[quiet_idx].max(0.000001);  // Avoid log(0)
+    
+    // Dynamic range in dB
+    20.0 * (loud / quiet).log10()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::MarineProcessor;
+    
+    #[test]
+    fn test_rms_calculation() {
+        let samples = vec![0.5, -0.5, 0.5, -0.5];
+        let rms = calculate_rms(&samples);
+        assert!((rms - 0.5).abs() < 0.001);
+    }
+    
+    #[test]
+    fn test_beat_period_and_wonder_moments() {
+        // A 5 ms click every half second at 8 kHz
+        let sample_rate = 8000.0;
+        let samples: Vec<f64> = (0..8 * 8000)
+            .map(|i| match i % 4000 {
+                k @ 0..=40 => 0.9 * (1.0 - (k as f64 - 20.0).abs() / 20.0) + 0.01,
+                _ => 0.01,
+            })
+            .collect();
+        let period = estimate_beat_period(&samples, sample_rate).unwrap();
+        assert!((period - 0.5).abs() < 0.02, "period {}", period);
+        assert_eq!(estimate_beat_period(&[0.0; 10], sample_rate), None);
+        assert_eq!(peak_level(&samples), 0.91);
+        
+        let (peaks, _) = MarineProcessor::for_audio(sample_rate).analyze(&samples);
+        let moments = top_wonder_moments(&peaks, sample_rate);
+        assert!(!moments.is_empty() && moments.len() <= MAX_WONDER_MOMENTS);
+        assert!(moments.windows(2).all(|w| w[0].salience >= w[1].salience));
+        let windows: std::collections::HashSet<usize> = moments.iter().map(|m| m.frame / 2000).collect();
+        assert_eq!(windows.len(), moments.len());
+    }
+}
@@ -0,0 +1,1592 @@
+//! Marine Algorithm - salience detection for any signal
+//! 
+//! Implements salience detection and jitter-based information processing
+//! as described in the Marine algorithm paper, plus the loudness and tempo
+//! measures (`analysis`) the audio pipeline reports alongside it. This is
+//! the half of MEM8-FS Lite with no storage in it: `num-complex` is the only
+//! required dependency, so anything that wants to find the wonder in a
+//! signal can use it without FUSE, ed25519, the decoders or the wave store.
+//! The `serde` feature adds Serialize and Deserialize to `PeakInfo` and
+//! `MarineMetadata`; `parallel` adds `MarineProcessor::process_samples_parallel`,
+//! with rayon; `spectral` adds the FFT stage in `spectral`, with rustfft.
+//! `structure` splits a track into sections from its salience timeline.
+//!
+//! `mem8-fs-lite` re-exports all of it as `mem8_fs_lite::marine` and plugs
+//! its wave packets in through `SignalSource` and `MarineSink`.
+//!
+//! Hue, this is where we detect the "wonder" in data - the peaks, the patterns,
+//! the jitter that tells us something interesting is happening!
+//! 
+//! Trisha says this is like finding the melody in the noise! 🎵
+
+use num_complex::Complex64;
+use std::borrow::Cow;
+use std::collections::VecDeque;
+
+pub mod analysis;
+pub mod spectral;
+pub mod structure;
+
+use spectral::{SpectralConfig, SpectralFeatures};
+
+/// Marine processor - finds salience in any signal!
+/// 
+/// Like a lighthouse keeper watching for important waves in the ocean of data.
+/// The algorithm treats jitter as information, not noise - revolutionary!
+pub struct MarineProcessor {
+    /// Clip threshold - determines sensitivity (θ_c in the paper)
+    pub clip_threshold: f64,
+    
+    /// How samples are gated before peak detection (`Hard` uses `clip_threshold`)
+    pub gate: GateMode,
+    
+    /// Which turning points of the gated signal count as peaks
+    pub detection: DetectionMode,
+    
+    /// Where a hysteresis gate was left by the last call
+    gate_state: GateState,
+    
+    /// Where a `process_chunk` stream has got to
+    stream: StreamState,
+    
+    /// Grid tick rate - how often we evaluate (f_g in the paper)
+    pub grid_tick_rate: f64,
+    
+    /// Exponential moving average for timing
+    timing_ema: ExponentialMovingAverage,
+    
+    /// Exponential moving average for amplitude
+    amplitude_ema: ExponentialMovingAverage,
+    
+    /// Recent peaks for harmonic analysis
+    recent_peaks: VecDeque<PeakInfo>,
+    
+    /// Weights for salience scoring
+    pub weights: SalienceWeights,
+    
+    /// Sense of wonder threshold - when things get interesting!
+    pub wonder_threshold: f64,
+    
+    /// Adaptive wonder settings (None = fixed `wonder_threshold`)
+    pub wonder_adaptation: Option<WonderAdaptation>,
+    
+    /// Running quantile of recent salience scores for adaptive mode
+    salience_quantile: RecentQuantile,
+    
+    /// How `analyze` measures the spectrum (None = not at all; nothing
+    /// without the `spectral` feature either)
+    pub spectral: Option<SpectralConfig>,
+}
+
+/// Peaks an adaptive wonder threshold learns from, by default
+pub const WONDER_WINDOW: usize = 256;
+
+/// Adaptive wonder threshold configuration
+///
+/// Instead of a fixed bar, wonder is whatever stands out from the recent
+/// distribution - quiet ambient pieces get their moments too! 🌙
+#[derive(Debug, Clone)]
+pub struct WonderAdaptation {
+    /// Salience percentile that counts as wonder (0.95 = top 5%)
+    pub percentile: f64,
+    
+    /// The effective threshold never drops below this
+    pub floor: f64,
+    
+    /// The effective threshold never rises above this
+    pub ceiling: f64,
+    
+    /// Stop learning from new peaks (for reproducible analysis runs)
+    pub frozen: bool,
+    
+    /// How many recent peaks count: the threshold follows the last
+    /// `window` to `2 × window` of them, so it forgets a quiet intro
+    pub window: usize,
+}
+
+impl WonderAdaptation {
+    /// Adapt to the given percentile with a wide-open floor/ceiling
+    pub fn top_percentile(percentile: f64) -> Self {
+        Self {
+            percentile: percentile.clamp(0.0, 1.0),
+            floor: 0.0,
+            ceiling: f64::INFINITY,
+            frozen: false,
+            window: WONDER_WINDOW,
+        }
+    }
+}
+
+/// Pre-gating before peak detection
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum GateMode {
+    /// Zero every sample below `clip_threshold` (the original behaviour)
+    #[default]
+    Hard,
+    
+    /// Open above one level, close below a lower one - see `HysteresisGate`
+    Hysteresis(HysteresisGate),
+}
+
+/// Which turning points of the (gated) signal count as peaks
+/// 
+/// Gates always go by magnitude, so a negative swing gets through as a
+/// positive one does; these say what's done with it after that.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DetectionMode {
+    /// Local maxima of the signal as it is (the original behaviour): a
+    /// negative-going pulse's lowest point isn't one
+    #[default]
+    PositivePeaks,
+    
+    /// Local maxima of |x|, so both halves of a symmetric wave count
+    AbsolutePeaks,
+    
+    /// Maxima above zero and minima below it, each tagged with its
+    /// `Polarity` - inhale and exhale, say
+    PeaksAndTroughs,
+}
+
+impl DetectionMode {
+    /// Is `x` a peak, coming after `prev` and before `next`?
+    fn detects(self, prev: f64, x: f64, next: f64) -> bool {
+        match self {
+            DetectionMode::PositivePeaks => prev < x && x > next && x != 0.0,
+            DetectionMode::AbsolutePeaks => prev.abs() < x.abs() && x.abs() > next.abs(),
+            DetectionMode::PeaksAndTroughs => (prev < x && x > next && x > 0.0) || (prev > x && x < next && x < 0.0),
+        }
+    }
+}
+
+/// Which way a peak went
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Polarity {
+    #[default]
+    Positive,
+    
+    /// A trough, or a negative-going peak
+    Negative,
+}
+
+/// A gate that stays open through quiet-but-structured passages
+///
+/// It opens when a sample reaches `threshold_high` and closes once the
+/// signal has stayed below `threshold_low` for `min_open` samples in a row,
+/// so it's never open for less than that and a wave's zero crossings don't
+/// shut it. With `smoothing` > 0 the gain ramps over that many samples
+/// instead of jumping, so the gate edges don't make peaks of their own.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HysteresisGate {
+    pub threshold_high: f64,
+    pub threshold_low: f64,
+    
+    /// Samples below `threshold_low` it takes to close
+    pub min_open: usize,
+    
+    /// Attack/release ramp length in samples (0 = instant)
+    pub smoothing: usize,
+}
+
+impl HysteresisGate {
+    pub fn new(threshold_high: f64, threshold_low: f64) -> Self {
+        Self { threshold_high, threshold_low, min_open: 1, smoothing: 0 }
+    }
+    
+    pub fn with_min_open(mut self, samples: usize) -> Self {
+        self.min_open = samples.max(1);
+        self
+    }
+    
+    pub fn with_smoothing(mut self, samples: usize) -> Self {
+        self.smoothing = samples;
+        self
+    }
+}
+
+/// Running state of a hysteresis gate, carried from one chunk to the next
+#[derive(Debug, Clone, Copy, Default)]
+struct GateState {
+    open: bool,
+    
+    /// Samples in a row below `threshold_low`
+    quiet_run: usize,
+    gain: f64,
+}
+
+/// How far a `process_chunk` stream has got, carried from one chunk to the next
+#[derive(Debug, Clone, Default)]
+struct StreamState {
+    /// Absolute index of `tail[0]`
+    start: usize,
+    
+    /// The last gated samples (at most two), the newest not yet judged -
+    /// it needs the sample after it
+    tail: Vec<f64>,
+    
+    /// Absolute index of the last peak found
+    last_peak: usize,
+}
+
+/// Settings for a Marine processor, kept apart from its running state
+///
+/// Handy when several processors need the same setup - e.g. one per worker
+/// thread during a backfill.
+#[derive(Debug, Clone)]
+pub struct MarineConfig {
+    pub clip_threshold: f64,
+    pub gate: GateMode,
+    pub detection: DetectionMode,
+    pub grid_tick_rate: f64,
+    pub wonder_threshold: f64,
+    pub weights: SalienceWeights,
+    pub wonder_adaptation: Option<WonderAdaptation>,
+    
+    /// Smoothing of the timing and amplitude averages (see `set_ema_alpha`;
+    /// `build` panics outside 0 < alpha ≤ 1)
+    pub ema_alpha: (f64, f64),
+    pub spectral: Option<SpectralConfig>,
+}
+
+impl Default for MarineConfig {
+    /// The settings `MarineProcessor::new` uses
+    fn default() -> Self {
+        MarineConfig::from(&MarineProcessor::new())
+    }
+}
+
+impl MarineConfig {
+    /// The settings `MarineProcessor::for_audio` uses
+    pub fn for_audio(sample_rate: f64) -> Self {
+        MarineConfig::from(&MarineProcessor::for_audio(sample_rate))
+    }
+    
+    /// A fresh processor with these settings
+    pub fn build(&self) -> MarineProcessor {
+        let mut processor = MarineProcessor::new();
+        processor.clip_threshold = self.clip_threshold;
+        processor.gate = self.gate.clone();
+        processor.detection = self.detection;
+        processor.grid_tick_rate = self.grid_tick_rate;
+        processor.wonder_threshold = self.wonder_threshold;
+        processor.weights = self.weights.clone();
+        processor.spectral = self.spectral;
+        if let Some(adaptation) = &self.wonder_adaptation {
+            processor.enable_adaptive_wonder(adaptation.clone());
+        }
+        let (timing, amplitude) = self.ema_alpha;
+        if let Err(e) = processor.set_ema_alpha(timing, amplitude) {
+            panic!("MarineConfig::ema_alpha: {}", e);
+        }
+        processor
+    }
+}
+
+impl From<&MarineProcessor> for MarineConfig {
+    fn from(processor: &MarineProcessor) -> Self {
+        Self {
+            clip_threshold: processor.clip_threshold,
+            gate: processor.gate.clone(),
+            detection: processor.detection,
+            grid_tick_rate: processor.grid_tick_rate,
+            wonder_threshold: processor.wonder_threshold,
+            weights: processor.weights.clone(),
+            wonder_adaptation: processor.wonder_adaptation.clone(),
+            ema_alpha: (processor.timing_ema.alpha, processor.amplitude_ema.alpha),
+            spectral: processor.spectral,
+        }
+    }
+}
+
+/// An EMA smoothing factor outside 0 < alpha ≤ 1 (see `MarineProcessor::set_ema_alpha`)
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InvalidAlpha(pub f64);
+
+impl std::fmt::Display for InvalidAlpha {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "EMA alpha {} isn't in (0, 1]", self.0)
+    }
+}
+
+impl std::error::Error for InvalidAlpha {}
+
+/// Information about a detected peak
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PeakInfo {
+    /// Sample index where peak occurred
+    pub index: usize,
+    
+    /// Amplitude of the peak (negative for a trough)
+    pub amplitude: f64,
+    
+    /// Whether it's a peak or a trough (see `DetectionMode`)
+    pub polarity: Polarity,
+    
+    /// Time since last peak
+    pub interval: f64,
+    
+    /// Jitter scores
+    pub timing_jitter: f64,
+    pub amplitude_jitter: f64,
+    
+    /// Final salience score
+    pub salience: f64,
+    
+    /// Does this peak inspire wonder? ✨
+    pub has_wonder: bool,
+}
+
+impl PeakInfo {
+    /// Where the peak is, in seconds, for samples taken at `sample_rate` Hz
+    pub fn time_seconds(&self, sample_rate: f64) -> f64 {
+        self.index as f64 / sample_rate
+    }
+    
+    pub fn index(&self) -> usize {
+        self.index
+    }
+    
+    pub fn amplitude(&self) -> f64 {
+        self.amplitude
+    }
+    
+    pub fn interval(&self) -> f64 {
+        self.interval
+    }
+    
+    /// Timing and amplitude jitter against the running averages
+    pub fn jitter(&self) -> (f64, f64) {
+        (self.timing_jitter, self.amplitude_jitter)
+    }
+    
+    pub fn salience(&self) -> f64 {
+        self.salience
+    }
+    
+    pub fn has_wonder(&self) -> bool {
+        self.has_wonder
+    }
+}
+
+/// Weights for combining different salience factors
+#[derive(Debug, Clone)]
+pub struct SalienceWeights {
+    /// Weight for energy/amplitude (w_e)
+    pub energy: f64,
+    
+    /// Weight for jitter inverse (w_j)  
+    pub jitter: f64,
+    
+    /// Weight for harmonic alignment (w_h)
+    pub harmonic: f64,
+    
+    /// Bonus weight for "sense of wonder"
+    pub wonder: f64,
+}
+
+impl Default for SalienceWeights {
+    fn default() -> Self {
+        Self {
+            energy: 0.4,
+            jitter: 0.3,
+            harmonic: 0.2,
+            wonder: 0.1, // That extra magic! ✨
+        }
+    }
+}
+
+/// Exponential Moving Average tracker
+struct ExponentialMovingAverage {
+    value: f64,
+    alpha: f64, // Smoothing factor
+}
+
+impl ExponentialMovingAverage {
+    fn new(alpha: f64) -> Self {
+        Self { value: 0.0, alpha }
+    }
+    
+    fn reset(&mut self) {
+        self.value = 0.0;
+    }
+    
+    fn update(&mut self, new_value: f64) -> f64 {
+        self.value = self.alpha * new_value + (1.0 - self.alpha) * self.value;
+        self.value
+    }
+}
+
+/// Streaming quantile estimator (the P² algorithm by Jain & Chlamtac)
+///
+/// Tracks a single quantile in O(1) memory with five markers - no need
+/// to keep every salience score around!
+#[derive(Debug, Clone)]
+struct P2Quantile {
+    /// Target quantile (0.0 to 1.0)
+    p: f64,
+    /// Marker heights
+    heights: [f64; 5],
+    /// Actual marker positions
+    positions: [f64; 5],
+    /// Desired marker positions
+    desired: [f64; 5],
+    /// Observations seen so far
+    count: usize,
+}
+
+impl P2Quantile {
+    fn new(p: f64) -> Self {
+        Self {
+            p,
+            heights: [0.0; 5],
+            positions: [0.0, 1.0, 2.0, 3.0, 4.0],
+            desired: [0.0, 2.0 * p, 4.0 * p, 2.0 + 2.0 * p, 4.0],
+            count: 0,
+        }
+    }
+    
+    fn observe(&mut self, x: f64) {
+        // Warm-up: collect the first five observations as marker heights
+        if self.count < 5 {
+            self.heights[self.count] = x;
+            self.count += 1;
+            if self.count == 5 {
+                self.heights.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+            }
+            return;
+        }
+        self.count += 1;
+        
+        // Find the cell containing x, stretching the extremes if needed
+        let k = if x < self.heights[0] {
+            self.heights[0] = x;
+            0
+        } else if x >= self.heights[4] {
+            self.heights[4] = x;
+            3
+        } else {
+            (0..4).find(|&i| x < self.heights[i + 1]).unwrap_or(3)
+        };
+        
+        for position in &mut self.positions[k + 1..] {
+            *position += 1.0;
+        }
+        let increments = [0.0, self.p / 2.0, self.p, (1.0 + self.p) / 2.0, 1.0];
+        for (desired, inc) in self.desired.iter_mut().zip(increments) {
+            *desired += inc;
+        }
+        
+        // Nudge the middle markers towards their desired positions
+        for i in 1..4 {
+            let d = self.desired[i] - self.positions[i];
+            let gap_up = self.positions[i + 1] - self.positions[i];
+            let gap_down = self.positions[i - 1] - self.positions[i];
+            if (d >= 1.0 && gap_up > 1.0) || (d <= -1.0 && gap_down < -1.0) {
+                let d = d.signum();
+                let parabolic = self.parabolic(i, d);
+                self.heights[i] = if self.heights[i - 1] < parabolic && parabolic < self.heights[i + 1] {
+                    parabolic
+                } else {
+                    self.linear(i, d)
+                };
+                self.positions[i] += d;
+            }
+        }
+    }
+    
+    fn parabolic(&self, i: usize, d: f64) -> f64 {
+        let (q, n) = (&self.heights, &self.positions);
+        q[i] + d / (n[i + 1] - n[i - 1]) * (
+            (n[i] - n[i - 1] + d) * (q[i + 1] - q[i]) / (n[i + 1] - n[i]) +
+            (n[i + 1] - n[i] - d) * (q[i] - q[i - 1]) / (n[i] - n[i - 1])
+        )
+    }
+    
+    fn linear(&self, i: usize, d: f64) -> f64 {
+        let j = if d > 0.0 { i + 1 } else { i - 1 };
+        self.heights[i] + d * (self.heights[j] - self.heights[i]) / (self.positions[j] - self.positions[i])
+    }
+    
+    /// Current quantile estimate (None until we've seen anything)
+    fn estimate(&self) -> Option<f64> {
+        match self.count {
+            0 => None,
+            n if n < 5 => {
+                let mut seen = self.heights[..n].to_vec();
+                seen.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+                let idx = ((n - 1) as f64 * self.p).round() as usize;
+                Some(seen[idx])
+            }
+            _ => Some(self.heights[2]),
+        }
+    }
+}
+
+/// A P² quantile of the latest observations only
+///
+/// Two estimators overlap: `older` answers, and `newer` starts once `older`
+/// has seen `window` observations and takes over when it's seen twice that.
+/// So the estimate always covers the last `window` to `2 × window` of them.
+#[derive(Debug, Clone)]
+struct RecentQuantile {
+    window: usize,
+    older: P2Quantile,
+    newer: P2Quantile,
+}
+
+impl RecentQuantile {
+    fn new(p: f64, window: usize) -> Self {
+        Self { window: window.max(5), older: P2Quantile::new(p), newer: P2Quantile::new(p) }
+    }
+    
+    fn observe(&mut self, x: f64) {
+        self.older.observe(x);
+        if self.older.count > self.window {
+            self.newer.observe(x);
+        }
+        if self.older.count == 2 * self.window {
+            let fresh = P2Quantile::new(self.older.p);
+            self.older = std::mem::replace(&mut self.newer, fresh);
+        }
+    }
+    
+    fn estimate(&self) -> Option<f64> {
+        self.older.estimate()
+    }
+}
+
+impl Default for MarineProcessor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MarineProcessor {
+    /// Create a new Marine processor with default settings
+    /// 
+    /// These defaults are tuned for audio at 44.1kHz, but work universally!
+    pub fn new() -> Self {
+        Self {
+            clip_threshold: 0.1,
+            gate: GateMode::Hard,
+            detection: DetectionMode::PositivePeaks,
+            gate_state: GateState::default(),
+            stream: StreamState::default(),
+            grid_tick_rate: 100.0, // 100Hz evaluation rate
+            timing_ema: ExponentialMovingAverage::new(0.125),
+            amplitude_ema: ExponentialMovingAverage::new(0.125),
+            recent_peaks: VecDeque::with_capacity(32),
+            weights: SalienceWeights::default(),
+            wonder_threshold: 0.8, // High salience = wonder!
+            wonder_adaptation: None,
+            salience_quantile: RecentQuantile::new(0.95, WONDER_WINDOW),
+            spectral: None,
+        }
+    }
+    
+    /// Create a processor optimized for audio
+    pub fn for_audio(sample_rate: f64) -> Self {
+        let mut processor = Self::new();
+        processor.grid_tick_rate = sample_rate / 441.0; // 100Hz for 44.1kHz
+        processor.clip_threshold = 0.05; // More sensitive for audio
+        processor.wonder_threshold = 0.7; // Audio has lots of wonder!
+        processor.spectral = Some(SpectralConfig::for_sample_rate(sample_rate));
+        processor
+    }
+    
+    /// Switch to adaptive wonder detection
+    /// 
+    /// The effective threshold follows the configured percentile of recent
+    /// salience scores, so loud and quiet material both get their share of wonder.
+    pub fn enable_adaptive_wonder(&mut self, adaptation: WonderAdaptation) {
+        self.salience_quantile = RecentQuantile::new(adaptation.percentile, adaptation.window);
+        self.wonder_adaptation = Some(adaptation);
+    }
+    
+    /// How quickly the expected timing and amplitude follow new peaks
+    /// 
+    /// Each is the weight a new peak gets in its moving average (0.125 by
+    /// default): 1 forgets everything before the last peak, values near 0
+    /// barely move. Both must be in 0 < alpha ≤ 1, or neither is changed.
+    pub fn set_ema_alpha(&mut self, timing: f64, amplitude: f64) -> Result<(), InvalidAlpha> {
+        for alpha in [timing, amplitude] {
+            if !(alpha > 0.0 && alpha <= 1.0) {
+                return Err(InvalidAlpha(alpha));
+            }
+        }
+        self.timing_ema.alpha = timing;
+        self.amplitude_ema.alpha = amplitude;
+        Ok(())
+    }
+    
+    /// Forget everything heard so far, keeping the settings
+    /// 
+    /// The averages, recent peaks, gate, adaptive threshold and any
+    /// `process_chunk` stream all start over, so the next signal is judged
+    /// as a freshly built processor would judge it - e.g. between songs.
+    pub fn reset(&mut self) {
+        self.timing_ema.reset();
+        self.amplitude_ema.reset();
+        self.recent_peaks.clear();
+        self.gate_state = GateState::default();
+        self.stream = StreamState::default();
+        let (percentile, window) = self.wonder_adaptation.as_ref()
+            .map_or((0.95, WONDER_WINDOW), |adaptation| (adaptation.percentile, adaptation.window));
+        self.salience_quantile = RecentQuantile::new(percentile, window);
+    }
+    
+    /// Freeze (or unfreeze) adaptation so repeated runs use the same threshold
+    pub fn freeze_wonder_adaptation(&mut self, frozen: bool) {
+        if let Some(adaptation) = self.wonder_adaptation.as_mut() {
+            adaptation.frozen = frozen;
+        }
+    }
+    
+    /// The wonder threshold currently in effect
+    /// 
+    /// In fixed mode this is just `wonder_threshold`. In adaptive mode it's the
+    /// percentile of the last `window` or so peaks clamped to the floor/ceiling, falling back to
+    /// `wonder_threshold` until the first peak has been seen.
+    pub fn effective_wonder_threshold(&self) -> f64 {
+        match &self.wonder_adaptation {
+            Some(adaptation) => self.salience_quantile.estimate()
+                .unwrap_or(self.wonder_threshold)
+                .max(adaptation.floor)
+                .min(adaptation.ceiling),
+            None => self.wonder_threshold,
+        }
+    }
+    
+    /// Process raw samples and detect salient peaks
+    /// 
+    /// This is where the magic happens - we find the important moments!
+    pub fn process_samples(&mut self, samples: &[f64]) -> Vec<PeakInfo> {
+        let mut peaks = Vec::new();
+        let mut last_peak_index = 0;
+        
+        // Pre-gating: ignore samples below threshold
+        let gated = self.gate_samples(samples);
+        
+        // Peak detection: x(n-1) < x(n) > x(n+1), or as `detection` says
+        for i in 1..gated.len().saturating_sub(1) {
+            if self.detection.detects(gated[i-1], gated[i], gated[i+1]) {
+                peaks.push(self.judge_peak(i, gated[i], last_peak_index));
+                last_peak_index = i;
+            }
+        }
+        
+        peaks
+    }
+    
+    /// Process the next chunk of a signal too long to hold at once
+    /// 
+    /// Chunk after chunk, this finds the peaks `process_samples` would find
+    /// in the whole signal: indices count from the first sample of the first
+    /// chunk, and intervals, jitter and the gate carry across the joins. A
+    /// peak on a chunk's last sample comes back with the next chunk (it
+    /// takes the sample after it to tell); call `finalize` at the end.
+    pub fn process_chunk(&mut self, samples: &[f64]) -> Vec<PeakInfo> {
+        let mut window = std::mem::take(&mut self.stream.tail);
+        window.extend(self.gate_samples(samples));
+        let start = self.stream.start;
+        
+        let mut peaks = Vec::new();
+        for i in 1..window.len().saturating_sub(1) {
+            if self.detection.detects(window[i-1], window[i], window[i+1]) {
+                let peak = self.judge_peak(start + i, window[i], self.stream.last_peak);
+                self.stream.last_peak = peak.index;
+                peaks.push(peak);
+            }
+        }
+        
+        let keep = window.len().min(2);
+        self.stream.start = start + window.len() - keep;
+        self.stream.tail = window.split_off(window.len() - keep);
+        peaks
+    }
+    
+    /// End a `process_chunk` stream, and start the next one at index 0
+    /// 
+    /// The signal is taken to fall silent after its last sample, so that
+    /// sample counts as a peak if it rose above the one before it (or, for
+    /// troughs, fell below it) - which `process_samples` never counts. The salience state carries on into
+    /// the next stream, as it does between `process_samples` calls.
+    pub fn finalize(&mut self) -> Option<PeakInfo> {
+        let stream = std::mem::take(&mut self.stream);
+        let last = stream.tail.len().checked_sub(1)?;
+        let before = if last > 0 { stream.tail[last - 1] } else { 0.0 };
+        let sample = stream.tail[last];
+        self.detection.detects(before, sample, 0.0)
+            .then(|| self.judge_peak(stream.start + last, sample, stream.last_peak))
+    }
+    
+    /// `process_samples` on every core, for recordings minutes long
+    /// 
+    /// Gating (hard gates only - a hysteresis gate runs in order), finding
+    /// the peaks and their harmonic alignment are split into `chunk_len`
+    /// sample chunks on the rayon pool; each chunk looks one sample past its
+    /// ends, so a peak on a join belongs to the chunk it's in and is found
+    /// once. What depends on the peaks before (the averages, jitter and
+    /// wonder) is then worked out in order, in a pass over the peaks alone,
+    /// so the result is exactly `process_samples`'s.
+    /// 
+    /// # Panics
+    /// 
+    /// If `chunk_len` is 0.
+    #[cfg(feature = "parallel")]
+    pub fn process_samples_parallel(&mut self, samples: &[f64], chunk_len: usize) -> Vec<PeakInfo> {
+        use rayon::prelude::*;
+        assert!(chunk_len > 0, "process_samples_parallel needs chunks of at least one sample");
+        
+        let gated = match self.gate {
+            GateMode::Hard => {
+                let clip = self.clip_threshold;
+                samples.par_chunks(chunk_len)
+                    .flat_map_iter(|chunk| chunk.iter().map(move |&s| if s.abs() < clip { 0.0 } else { s }))
+                    .collect()
+            }
+            GateMode::Hysteresis(_) => self.gate_samples(samples),
+        };
+        let detection = self.detection;
+        let last = gated.len().saturating_sub(1);
+        let found: Vec<usize> = (1..last.max(1)).step_by(chunk_len)
+            .collect::<Vec<_>>()
+            .into_par_iter()
+            .flat_map_iter(|start| (start..(start + chunk_len).min(last))
+                .filter(|&i| detection.detects(gated[i - 1], gated[i], gated[i + 1])))
+            .collect();
+        
+        let intervals: Vec<f64> = found.iter().scan(0, |last_peak, &index| {
+            let interval = (index - *last_peak) as f64;
+            *last_peak = index;
+            Some(interval)
+        }).collect();
+        let harmonics: Vec<f64> = intervals.par_chunks(chunk_len)
+            .flat_map_iter(|chunk| chunk.iter().map(|&interval| self.calculate_harmonic_alignment(interval)))
+            .collect();
+        
+        found.iter().zip(intervals).zip(harmonics)
+            .map(|((&index, interval), harmonic)| self.score_peak(index, gated[index], interval, harmonic))
+            .collect()
+    }
+    
+    /// Salience over time: `samples` in windows of `window_size`, one every `hop` samples
+    /// 
+    /// The peaks come from `process_chunk`, so `samples` can be one chunk of
+    /// a stream after another: `start_sample` counts from the start of the
+    /// stream, and each call covers the full windows that fit in its own
+    /// chunk (a peak on a chunk's last sample isn't in any of them).
+    /// 
+    /// # Panics
+    /// 
+    /// If `window_size` or `hop` is 0.
+    pub fn salience_timeline(&mut self, samples: &[f64], window_size: usize, hop: usize) -> Vec<WindowStats> {
+        assert!(window_size > 0 && hop > 0, "salience_timeline needs a window and a hop of at least one sample");
+        let offset = self.stream.start + self.stream.tail.len();
+        let peaks = self.process_chunk(samples);
+        if samples.len() < window_size {
+            return Vec::new();
+        }
+        
+        (0..=samples.len() - window_size).step_by(hop)
+            .map(|start| {
+                let window = &samples[start..start + window_size];
+                let rms = (window.iter().map(|s| s * s).sum::<f64>() / window_size as f64).sqrt();
+                let range = offset + start..offset + start + window_size;
+                let inside: Vec<&PeakInfo> = peaks.iter().filter(|p| range.contains(&p.index)).collect();
+                WindowStats {
+                    start_sample: range.start,
+                    rms,
+                    peak_count: inside.len(),
+                    mean_salience: inside.iter().map(|p| p.salience).sum::<f64>() / inside.len().max(1) as f64,
+                    wonder_count: inside.iter().filter(|p| p.has_wonder).count(),
+                }
+            })
+            .collect()
+    }
+    
+    /// Score a peak at `index` (`amplitude` high) found after one at `last_peak`
+    fn judge_peak(&mut self, index: usize, amplitude: f64, last_peak: usize) -> PeakInfo {
+        let interval = (index - last_peak) as f64;
+        let harmonic_score = self.calculate_harmonic_alignment(interval);
+        self.score_peak(index, amplitude, interval, harmonic_score)
+    }
+    
+    /// `judge_peak`, its harmonic alignment already worked out
+    fn score_peak(&mut self, index: usize, amplitude: f64, interval: f64, harmonic_score: f64) -> PeakInfo {
+        // Update EMAs
+        let expected_timing = self.timing_ema.update(interval);
+        let expected_amplitude = self.amplitude_ema.update(amplitude.abs());
+        
+        // Calculate jitter (deviation from expected)
+        let timing_jitter = (interval - expected_timing).abs();
+        let amplitude_jitter = (amplitude.abs() - expected_amplitude).abs();
+        
+        // Calculate final salience score
+        let salience = self.calculate_salience(
+            amplitude.abs(),
+            timing_jitter,
+            amplitude_jitter,
+            harmonic_score
+        );
+        
+        // Learn from this peak before judging it (adaptive mode only)
+        if matches!(&self.wonder_adaptation, Some(a) if !a.frozen) {
+            self.salience_quantile.observe(salience);
+        }
+        
+        // Check for wonder! ✨
+        let has_wonder = salience > self.effective_wonder_threshold();
+        
+        let peak = PeakInfo {
+            index,
+            amplitude,
+            polarity: if amplitude < 0.0 { Polarity::Negative } else { Polarity::Positive },
+            interval,
+            timing_jitter,
+            amplitude_jitter,
+            salience,
+            has_wonder,
+        };
+        
+        // Store in recent peaks for harmonic analysis
+        self.recent_peaks.push_back(peak.clone());
+        if self.recent_peaks.len() > 32 {
+            self.recent_peaks.pop_front();
+        }
+        peak
+    }
+    
+    /// Apply the gate, keeping a hysteresis gate's state for the next call
+    fn gate_samples(&mut self, samples: &[f64]) -> Vec<f64> {
+        let gate = match &self.gate {
+            GateMode::Hard => {
+                return samples.iter()
+                    .map(|&s| if s.abs() < self.clip_threshold { 0.0 } else { s })
+                    .collect();
+            }
+            GateMode::Hysteresis(gate) => gate,
+        };
+        let state = &mut self.gate_state;
+        let step = match gate.smoothing {
+            0 => 1.0,
+            n => 1.0 / n as f64,
+        };
+        samples.iter()
+            .map(|&s| {
+                let level = s.abs();
+                if level >= gate.threshold_high {
+                    state.open = true;
+                }
+                state.quiet_run = if level < gate.threshold_low { state.quiet_run + 1 } else { 0 };
+                if state.open && state.quiet_run >= gate.min_open {
+                    state.open = false;
+                }
+                state.gain = match state.open {
+                    true => (state.gain + step).min(1.0),
+                    false => (state.gain - step).max(0.0),
+                };
+                s * state.gain
+            })
+            .collect()
+    }
+    
+    /// Process complex wave data (our MEM8 format!)
+    /// 
+    /// This extracts salience from our wave-encoded data.
+    /// The real and imaginary parts create a richer signal!
+    pub fn process_waves(&mut self, waves: &[Complex64]) -> Vec<PeakInfo> {
+        // Convert complex waves to magnitude signal
+        self.process_samples(&waves.marine_samples())
+    }
+    
+    /// Calculate salience score using the Marine formula
+    /// 
+    /// S = w_e * E + w_j * (1/J) + w_h * H + w_w * W
+    /// 
+    /// Where W is our "wonder factor" - that special something!
+    fn calculate_salience(
+        &self,
+        energy: f64,
+        timing_jitter: f64,
+        amplitude_jitter: f64,
+        harmonic: f64
+    ) -> f64 {
+        // Avoid division by zero
+        let jitter_score = 1.0 / (1.0 + timing_jitter + amplitude_jitter);
+        
+        // Calculate wonder factor based on unexpected patterns
+        let wonder = self.calculate_wonder_factor(energy, jitter_score);
+        
+        // Combine all factors
+        self.weights.energy * energy +
+        self.weights.jitter * jitter_score +
+        self.weights.harmonic * harmonic +
+        self.weights.wonder * wonder
+    }
+    
+    /// Calculate harmonic alignment score
+    /// 
+    /// Checks if the timing interval aligns with common musical ratios.
+    /// This is where we find the rhythm in the data!
+    fn calculate_harmonic_alignment(&self, interval: f64) -> f64 {
+        // Common harmonic ratios (musical intervals)
+        let harmonics = [
+            1.0,    // Unison
+            2.0,    // Octave
+            1.5,    // Perfect fifth
+            1.333,  // Perfect fourth
+            1.25,   // Major third
+            1.618,  // Golden ratio! (Hue's favorite)
+        ];
+        
+        // Find the best harmonic match
+        let mut best_score = 0.0_f64;
+        
+        for &harmonic in &harmonics {
+            // Check if interval is close to a harmonic multiple
+            let ratio = interval / self.grid_tick_rate;
+            let distance = (ratio / harmonic).fract();
+            let score = 1.0 - distance.min(1.0 - distance);
+            best_score = best_score.max(score);
+        }
+        
+        best_score
+    }
+    
+    /// Calculate the wonder factor - that sense of awe in the data!
+    /// 
+    /// This is our special sauce - when patterns surprise us in beautiful ways.
+    /// Trisha says this is what makes data sing! 🎶
+    fn calculate_wonder_factor(&self, energy: f64, jitter_score: f64) -> f64 {
+        // High energy with low jitter = controlled power = wonder!
+        // OR low energy with specific jitter patterns = subtle beauty
+        
+        let power_wonder = energy * jitter_score;
+        
+        // Check for golden ratio patterns in recent peaks
+        let golden_wonder = if self.recent_peaks.len() >= 3 {
+            let intervals: Vec<f64> = self.recent_peaks.iter()
+                .map(|p| p.interval)
+                .collect();
+            
+            // Check if intervals follow golden ratio
+            let mut golden_score = 0.0;
+            for i in 1..intervals.len() {
+                let ratio = intervals[i] / intervals[i-1];
+                let distance = (ratio - 1.618).abs();
+                if distance < 0.1 {
+                    golden_score += 1.0;
+                }
+            }
+            golden_score / intervals.len() as f64
+        } else {
+            0.0
+        };
+        
+        power_wonder + golden_wonder * 0.5
+    }
+    
+    /// Extract metadata from peaks - the story the data tells!
+    pub fn extract_metadata(&self, peaks: &[PeakInfo]) -> MarineMetadata {
+        let wonder_peaks: Vec<_> = peaks.iter()
+            .filter(|p| p.has_wonder)
+            .collect();
+        
+        let avg_salience = peaks.iter()
+            .map(|p| p.salience)
+            .sum::<f64>() / peaks.len().max(1) as f64;
+        
+        let max_salience = peaks.iter()
+            .map(|p| p.salience)
+            .fold(0.0_f64, f64::max);
+        
+        MarineMetadata {
+            total_peaks: peaks.len(),
+            wonder_count: wonder_peaks.len(),
+            average_salience: avg_salience,
+            max_salience,
+            has_rhythm: self.detect_rhythm(peaks),
+            emotional_signature: self.detect_emotion(peaks),
+            adaptive_wonder: self.wonder_adaptation.is_some(),
+            effective_wonder_threshold: self.effective_wonder_threshold(),
+            spectral: None,
+        }
+    }
+    
+    /// The spectral stage on `samples`: None if it's off, compiled out or
+    /// there isn't a whole window of sound
+    pub fn spectral_features(&self, samples: &[f64]) -> Option<SpectralFeatures> {
+        #[cfg(feature = "spectral")]
+        return spectral::analyze(samples, self.spectral.as_ref()?);
+        #[cfg(not(feature = "spectral"))]
+        {
+            let _ = samples;
+            None
+        }
+    }
+    
+    /// Detect if there's a rhythm in the peaks
+    fn detect_rhythm(&self, peaks: &[PeakInfo]) -> bool {
+        if peaks.len() < 4 {
+            return false;
+        }
+        
+        // Check if intervals are regular (low variance)
+        let intervals: Vec<f64> = peaks.windows(2)
+            .map(|w| w[1].index as f64 - w[0].index as f64)
+            .collect();
+        
+        let mean = intervals.iter().sum::<f64>() / intervals.len() as f64;
+        let variance = intervals.iter()
+            .map(|&i| (i - mean).powi(2))
+            .sum::<f64>() / intervals.len() as f64;
+        
+        variance < (mean * 0.2).powi(2) // Low variance = rhythm!
+    }
+    
+    /// Detect emotional signature in the data
+    /// 
+    /// This is pure speculation, but Trisha insists data has feelings! 💝
+    fn detect_emotion(&self, peaks: &[PeakInfo]) -> String {
+        let avg_amplitude = peaks.iter()
+            .map(|p| p.amplitude.abs())
+            .sum::<f64>() / peaks.len().max(1) as f64;
+        
+        let wonder_ratio = peaks.iter()
+            .filter(|p| p.has_wonder)
+            .count() as f64 / peaks.len().max(1) as f64;
+        
+        match (avg_amplitude, wonder_ratio) {
+            (_a, w) if w > 0.5 => "✨ Wondrous".to_string(),
+            (a, _) if a > 0.8 => "🔥 Energetic".to_string(),
+            (a, _) if a < 0.2 => "😌 Peaceful".to_string(),
+            (_, w) if w > 0.3 => "🎵 Musical".to_string(),
+            _ => "🌊 Flowing".to_string(),
+        }
+    }
+}
+
+/// Metadata extracted by Marine processing
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MarineMetadata {
+    /// Total number of peaks detected
+    pub total_peaks: usize,
+    
+    /// Number of peaks with "wonder" (high salience)
+    pub wonder_count: usize,
+    
+    /// Average salience across all peaks
+    pub average_salience: f64,
+    
+    /// Maximum salience found
+    pub max_salience: f64,
+    
+    /// Does the data have rhythm?
+    pub has_rhythm: bool,
+    
+    /// Emotional signature of the data (for fun!)
+    pub emotional_signature: String,
+    
+    /// Was the wonder threshold adapting to the salience distribution?
+    pub adaptive_wonder: bool,
+    
+    /// The wonder threshold that was actually applied
+    pub effective_wonder_threshold: f64,
+    
+    /// The spectrum, when `analyze` ran the spectral stage
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub spectral: Option<SpectralFeatures>,
+}
+
+impl std::fmt::Display for MarineMetadata {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "🌊 Marine Analysis:")?;
+        writeln!(f, "  Peaks: {} (✨ {} with wonder)", self.total_peaks, self.wonder_count)?;
+        writeln!(f, "  Salience: {:.3} avg, {:.3} max", self.average_salience, self.max_salience)?;
+        writeln!(f, "  Rhythm: {}", if self.has_rhythm { "Yes! 🎵" } else { "No" })?;
+        writeln!(f, "  Emotion: {}", self.emotional_signature)?;
+        writeln!(f, "  Wonder threshold: {:.3} ({})", self.effective_wonder_threshold,
+            if self.adaptive_wonder { "adaptive" } else { "fixed" })?;
+        if let Some(spectral) = &self.spectral {
+            writeln!(f, "  Spectrum: centroid {:.0} Hz, flatness {:.2}, rolloff {:.0} Hz (low/mid/high {:.0}/{:.0}/{:.0}%)",
+                spectral.centroid_hz, spectral.flatness, spectral.rolloff_hz,
+                100.0 * spectral.low_ratio, 100.0 * spectral.mid_ratio, 100.0 * spectral.high_ratio)?;
+        }
+        Ok(())
+    }
+}
+
+/// One window of a `MarineProcessor::salience_timeline`
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct WindowStats {
+    /// Where the window starts, counting from the start of the stream
+    pub start_sample: usize,
+    pub rms: f64,
+    pub peak_count: usize,
+    
+    /// Average salience of the window's peaks (0 without any)
+    pub mean_salience: f64,
+    pub wonder_count: usize,
+}
+
+/// Something Marine can listen to
+/// 
+/// Plain samples and complex waves come ready-made; a host crate implements
+/// it for its own types (`mem8-fs-lite` does for `WavePacket`).
+pub trait SignalSource {
+    /// The signal as real samples (complex waves give their magnitude)
+    fn marine_samples(&self) -> Cow<'_, [f64]>;
+}
+
+impl SignalSource for [f64] {
+    fn marine_samples(&self) -> Cow<'_, [f64]> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl SignalSource for Vec<f64> {
+    fn marine_samples(&self) -> Cow<'_, [f64]> {
+        Cow::Borrowed(self)
+    }
+}
+
+impl SignalSource for [Complex64] {
+    fn marine_samples(&self) -> Cow<'_, [f64]> {
+        Cow::Owned(self.iter().map(|w| w.norm()).collect())
+    }
+}
+
+impl SignalSource for Vec<Complex64> {
+    fn marine_samples(&self) -> Cow<'_, [f64]> {
+        self.as_slice().marine_samples()
+    }
+}
+
+/// Somewhere Marine's findings can be written back to
+pub trait MarineSink {
+    type Error;
+    
+    fn attach_marine(&mut self, metadata: &MarineMetadata) -> Result<(), Self::Error>;
+}
+
+impl MarineProcessor {
+    /// Find the peaks in `source` and summarise them, spectrum included if `spectral` is set
+    pub fn analyze<S: SignalSource + ?Sized>(&mut self, source: &S) -> (Vec<PeakInfo>, MarineMetadata) {
+        let samples = source.marine_samples();
+        let peaks = self.process_samples(&samples);
+        let mut metadata = self.extract_metadata(&peaks);
+        metadata.spectral = self.spectral_features(&samples);
+        (peaks, metadata)
+    }
+    
+    /// Analyse `target` and hand it the metadata
+    pub fn enhance<T: SignalSource + MarineSink + ?Sized>(&mut self, target: &mut T) -> Result<MarineMetadata, T::Error> {
+        let (_, metadata) = self.analyze(target);
+        target.attach_marine(&metadata)?;
+        Ok(metadata)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_peak_detection() {
+        let mut processor = MarineProcessor::new();
+        
+        // Create a signal with clear peaks
+        let samples = vec![
+            0.0, 0.5, 1.0, 0.5, 0.0,  // Peak at index 2
+            0.0, 0.3, 0.7, 0.3, 0.0,  // Peak at index 7
+            0.0, 0.4, 0.9, 0.4, 0.0,  // Peak at index 12
+        ];
+        
+        let peaks = processor.process_samples(&samples);
+        assert_eq!(peaks.len(), 3);
+        assert_eq!(peaks[0].index, 2);
+        assert_eq!(peaks[1].index, 7);
+        assert_eq!(peaks[2].index, 12);
+        assert_eq!(peaks[2].time_seconds(4.0), 3.0);
+    }
+    
+    #[test]
+    fn test_rhythm_detection() {
+        let mut processor = MarineProcessor::new();
+        
+        // Create a rhythmic signal (regular intervals)
+        let mut samples = vec![0.0; 100];
+        for i in (10..100).step_by(10) {
+            samples[i] = 1.0; // Peak every 10 samples
+        }
+        
+        let peaks = processor.process_samples(&samples);
+        let metadata = processor.extract_metadata(&peaks);
+        
+        assert!(metadata.has_rhythm);
+    }
+    
+    #[test]
+    fn test_wonder_detection() {
+        let mut processor = MarineProcessor::new();
+        processor.wonder_threshold = 0.5; // Lower threshold for testing
+        
+        // Create a signal with high energy peaks
+        let samples = vec![
+            0.0, 0.1, 0.9, 0.1, 0.0,  // High energy peak
+            0.0, 0.1, 0.2, 0.1, 0.0,  // Low energy peak
+            0.0, 0.1, 0.8, 0.1, 0.0,  // High energy peak
+        ];
+        
+        let peaks = processor.process_samples(&samples);
+        let wonder_count = peaks.iter().filter(|p| p.has_wonder).count();
+        
+        assert!(wonder_count > 0);
+    }
+    
+    /// Loud, then a quiet passage well under the clip threshold, then loud again
+    /// (a sine with a 20-sample period throughout, so every peak is at 5 mod 20)
+    fn quiet_passage_signal() -> Vec<f64> {
+        (0..600)
+            .map(|i| {
+                let amplitude = if (200..400).contains(&i) { 0.06 } else { 1.0 };
+                amplitude * (2.0 * std::f64::consts::PI * i as f64 / 20.0).sin()
+            })
+            .collect()
+    }
+    
+    #[test]
+    fn test_hysteresis_gate_keeps_quiet_peaks() {
+        let samples = quiet_passage_signal();
+        let true_quiet: Vec<usize> = (200..400).filter(|i| i % 20 == 5).collect();
+        let loud_peaks = |peaks: &[PeakInfo]| peaks.iter().map(|p| p.index).filter(|i| !(200..400).contains(i)).collect::<Vec<_>>();
+        let quiet_peaks = |peaks: &[PeakInfo]| peaks.iter().map(|p| p.index).filter(|i| (200..400).contains(i)).collect::<Vec<_>>();
+        
+        // The hard gate (still the default) drops the whole passage
+        let mut hard = MarineProcessor::new();
+        assert_eq!(hard.gate, GateMode::Hard);
+        let hard_peaks = hard.process_samples(&samples);
+        assert!(quiet_peaks(&hard_peaks).is_empty());
+        
+        // Hysteresis stays open through it and finds exactly its peaks
+        let mut hysteresis = MarineProcessor::new();
+        hysteresis.gate = GateMode::Hysteresis(HysteresisGate::new(0.1, 0.01).with_min_open(40));
+        let peaks = hysteresis.process_samples(&samples);
+        assert_eq!(quiet_peaks(&peaks), true_quiet);
+        assert_eq!(loud_peaks(&peaks), loud_peaks(&hard_peaks));
+        assert_eq!(peaks.len(), hard_peaks.len() + true_quiet.len());
+        
+        // Smoothing only softens the first opening; the passage is untouched
+        let config = MarineConfig {
+            gate: GateMode::Hysteresis(HysteresisGate::new(0.1, 0.01).with_min_open(40).with_smoothing(8)),
+            ..MarineConfig::default()
+        };
+        let smoothed = config.build().process_samples(&samples);
+        assert_eq!(quiet_peaks(&smoothed), true_quiet);
+        assert!(smoothed.iter().all(|p| p.index % 20 == 5 || p.index < 20), "{:?}", smoothed.iter().map(|p| p.index).collect::<Vec<_>>());
+        
+        // A silence longer than min_open closes it again
+        let mut closing = MarineProcessor::new();
+        closing.gate = GateMode::Hysteresis(HysteresisGate::new(0.1, 0.01).with_min_open(40));
+        let mut gapped = samples[..200].to_vec();
+        gapped.extend(std::iter::repeat_n(0.0, 50));
+        gapped.extend(&samples[200..400]);
+        assert!(closing.process_samples(&gapped).iter().all(|p| p.index < 200));
+    }
+    
+    /// Spiky signal with a peak every 10 samples and varying heights
+    fn spiky_signal(scale: f64) -> Vec<f64> {
+        let mut samples = vec![0.0; 10_000];
+        let mut seed: u64 = 0x8b15;
+        for i in (10..10_000).step_by(10) {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            let height = 0.2 + 0.8 * ((seed >> 33) as f64 / (1u64 << 31) as f64);
+            samples[i] = height * scale;
+        }
+        samples
+    }
+    
+    fn wonder_ratio(processor: &mut MarineProcessor, samples: &[f64]) -> f64 {
+        let peaks = processor.process_samples(samples);
+        peaks.iter().filter(|p| p.has_wonder).count() as f64 / peaks.len() as f64
+    }
+    
+    #[test]
+    fn test_adaptive_wonder_scale_invariant() {
+        let loud = spiky_signal(1.0);
+        let quiet = spiky_signal(0.1);
+        
+        // Fixed mode: the quiet track barely gets any wonder
+        let mut fixed_loud = MarineProcessor::new();
+        fixed_loud.clip_threshold = 0.001;
+        let mut fixed_quiet = MarineProcessor::new();
+        fixed_quiet.clip_threshold = 0.001;
+        let fixed_diff = (wonder_ratio(&mut fixed_loud, &loud) - wonder_ratio(&mut fixed_quiet, &quiet)).abs();
+        
+        // Adaptive mode: both tracks get their top 10%
+        let mut adaptive_loud = MarineProcessor::new();
+        adaptive_loud.clip_threshold = 0.001;
+        adaptive_loud.enable_adaptive_wonder(WonderAdaptation::top_percentile(0.9));
+        let mut adaptive_quiet = MarineProcessor::new();
+        adaptive_quiet.clip_threshold = 0.001;
+        adaptive_quiet.enable_adaptive_wonder(WonderAdaptation::top_percentile(0.9));
+        let loud_ratio = wonder_ratio(&mut adaptive_loud, &loud);
+        let quiet_ratio = wonder_ratio(&mut adaptive_quiet, &quiet);
+        
+        assert!((loud_ratio - quiet_ratio).abs() < 0.05, "adaptive ratios {} vs {}", loud_ratio, quiet_ratio);
+        assert!(loud_ratio > 0.0 && quiet_ratio > 0.0);
+        assert!(fixed_diff > 0.1, "fixed ratios should differ, got diff {}", fixed_diff);
+    }
+    
+    #[test]
+    fn test_adaptive_wonder_follows_recent_peaks() {
+        // Loud for a while, then a tenth as loud, in one stream
+        let loud = spiky_signal(1.0);
+        let quiet = spiky_signal(0.1);
+        let run = |window: usize| {
+            let mut processor = MarineProcessor::new();
+            processor.clip_threshold = 0.001;
+            processor.enable_adaptive_wonder(WonderAdaptation { window, ..WonderAdaptation::top_percentile(0.9) });
+            processor.process_samples(&loud);
+            let peaks = processor.process_samples(&quiet);
+            let tail = &peaks[peaks.len() / 2..];
+            tail.iter().filter(|p| p.has_wonder).count() as f64 / tail.len() as f64
+        };
+        
+        // Over the last 256 peaks, the quiet half gets its top 10% back
+        let recent = run(WONDER_WINDOW);
+        assert!((recent - 0.1).abs() < 0.05, "recent ratio {}", recent);
+        
+        // Over everything since the start, the loud half still sets the bar
+        let cumulative = run(1 << 20);
+        assert!(cumulative < 0.02, "cumulative ratio {}", cumulative);
+    }
+    
+    #[test]
+    fn test_adaptive_wonder_freeze_and_metadata() {
+        let mut processor = MarineProcessor::new();
+        processor.enable_adaptive_wonder(WonderAdaptation {
+            percentile: 0.9,
+            floor: 0.1,
+            ceiling: 5.0,
+            frozen: false,
+            window: WONDER_WINDOW,
+        });
+        let samples = spiky_signal(1.0);
+        processor.process_samples(&samples);
+        
+        let learned = processor.effective_wonder_threshold();
+        assert!((0.1..=5.0).contains(&learned));
+        
+        // Frozen: feeding a much louder signal doesn't move the threshold
+        processor.freeze_wonder_adaptation(true);
+        let peaks = processor.process_samples(&spiky_signal(3.0));
+        assert_eq!(processor.effective_wonder_threshold(), learned);
+        
+        let metadata = processor.extract_metadata(&peaks);
+        assert!(metadata.adaptive_wonder);
+        assert_eq!(metadata.effective_wonder_threshold, learned);
+        
+        // Fixed mode reports the plain threshold
+        let fixed = MarineProcessor::new();
+        let metadata = fixed.extract_metadata(&[]);
+        assert!(!metadata.adaptive_wonder);
+        assert_eq!(metadata.effective_wonder_threshold, fixed.wonder_threshold);
+    }
+    
+    /// A host type plugged in through the adapter traits
+    struct Reading {
+        waves: Vec<Complex64>,
+        marine: Option<MarineMetadata>,
+    }
+    
+    impl SignalSource for Reading {
+        fn marine_samples(&self) -> Cow<'_, [f64]> {
+            self.waves.marine_samples()
+        }
+    }
+    
+    impl MarineSink for Reading {
+        type Error = String;
+        
+        fn attach_marine(&mut self, metadata: &MarineMetadata) -> Result<(), String> {
+            if self.marine.is_some() {
+                return Err("already analysed".to_string());
+            }
+            self.marine = Some(metadata.clone());
+            Ok(())
+        }
+    }
+    
+    #[test]
+    fn test_adapters_match_process_waves() {
+        let waves: Vec<Complex64> = spiky_signal(1.0).iter()
+            .enumerate()
+            .map(|(i, &s)| Complex64::from_polar(s, i as f64 * 0.1))
+            .collect();
+        let direct = MarineProcessor::new().process_waves(&waves);
+        
+        let mut reading = Reading { waves, marine: None };
+        let metadata = MarineProcessor::new().enhance(&mut reading).unwrap();
+        assert_eq!(metadata.total_peaks, direct.len());
+        assert!(metadata.total_peaks > 0);
+        assert_eq!(reading.marine.as_ref().unwrap().max_salience, metadata.max_salience);
+        assert!(MarineProcessor::new().enhance(&mut reading).is_err());
+        
+        // Peaks read the same through the accessors
+        let (peaks, _) = MarineProcessor::new().analyze(&reading);
+        assert_eq!(peaks.iter().map(PeakInfo::index).collect::<Vec<_>>(),
+            direct.iter().map(PeakInfo::index).collect::<Vec<_>>());
+        assert!(peaks.iter().all(|p| p.salience() > 0.0 && p.amplitude() > 0.0));
+    }
+    
+    #[test]
+    fn test_chunks_find_the_whole_signals_peaks() {
+        let mut samples = spiky_signal(1.0);
+        samples.extend(quiet_passage_signal());
+        samples.extend(spiky_signal(0.5));
+        let config = MarineConfig {
+            gate: GateMode::Hysteresis(HysteresisGate::new(0.1, 0.01).with_min_open(40)),
+            wonder_adaptation: Some(WonderAdaptation::top_percentile(0.9)),
+            ..MarineConfig::default()
+        };
+        let whole = config.build().process_samples(&samples);
+        
+        let mut processor = config.build();
+        let mut chunked: Vec<PeakInfo> = samples.chunks(4096)
+            .flat_map(|chunk| processor.process_chunk(chunk))
+            .collect();
+        assert_eq!(chunked.len(), whole.len());
+        for (a, b) in chunked.iter().zip(&whole) {
+            assert_eq!((a.index, a.interval, a.has_wonder), (b.index, b.interval, b.has_wonder));
+            assert!((a.salience - b.salience).abs() < 1e-9);
+        }
+        assert!(processor.finalize().is_none());
+        
+        // Peaks right on a join, and one left at the very end
+        let mut edges = vec![0.0; 8192];
+        edges[4095] = 0.9;
+        edges[4096 + 10] = 0.5;
+        edges[8191] = 0.7;
+        chunked = edges.chunks(4096).flat_map(|chunk| processor.process_chunk(chunk)).collect();
+        assert_eq!(chunked.iter().map(|p| p.index).collect::<Vec<_>>(), [4095, 4106]);
+        let last = processor.finalize().unwrap();
+        assert_eq!((last.index, last.interval), (8191, 8191.0 - 4106.0));
+        
+        // The next stream starts over
+        assert_eq!(processor.process_chunk(&[0.0, 0.8, 0.0])[0].index, 1);
+        assert!(processor.finalize().is_none());
+    }
+    
+    #[test]
+    fn test_salience_timeline_windows() {
+        // Loud spikes, then silence, then quieter spikes
+        let mut samples = spiky_signal(1.0)[..1000].to_vec();
+        samples.extend(vec![0.0; 1000]);
+        samples.extend(&spiky_signal(0.5)[..1000]);
+        let peaks = MarineProcessor::new().process_samples(&samples);
+        
+        let timeline = MarineProcessor::new().salience_timeline(&samples, 1000, 500);
+        assert_eq!(timeline.iter().map(|w| w.start_sample).collect::<Vec<_>>(), [0, 500, 1000, 1500, 2000]);
+        assert_eq!(timeline.iter().step_by(2).map(|w| w.peak_count).sum::<usize>(), peaks.len());
+        assert_eq!((timeline[2].peak_count, timeline[2].rms, timeline[2].mean_salience), (0, 0.0, 0.0));
+        assert!(timeline[0].rms > timeline[4].rms && timeline[4].rms > 0.0);
+        assert!(timeline[0].mean_salience > 0.0);
+        
+        // Chunk by chunk, the windows pick up where the stream is
+        let mut processor = MarineProcessor::new();
+        let streamed: Vec<WindowStats> = samples.chunks(1000)
+            .flat_map(|chunk| processor.salience_timeline(chunk, 1000, 1000))
+            .collect();
+        assert_eq!(streamed.iter().map(|w| w.start_sample).collect::<Vec<_>>(), [0, 1000, 2000]);
+        for (streamed, whole) in streamed.iter().zip(timeline.iter().step_by(2)) {
+            assert_eq!((streamed.rms, streamed.peak_count), (whole.rms, whole.peak_count));
+        }
+        assert!(processor.salience_timeline(&[0.5; 10], 100, 10).is_empty());
+    }
+    
+    #[test]
+    fn test_reset_forgets_the_last_signal() {
+        let first = spiky_signal(1.0);
+        let second = spiky_signal(0.3);
+        let saliences = |peaks: Vec<PeakInfo>| peaks.iter().map(|p| p.salience).collect::<Vec<_>>();
+        let fresh = saliences(MarineProcessor::new().process_samples(&second));
+        
+        // Reused as it is, the first signal colours the second
+        let mut processor = MarineProcessor::new();
+        processor.process_samples(&first);
+        assert_ne!(saliences(processor.process_samples(&second)), fresh);
+        
+        processor.process_samples(&first);
+        processor.reset();
+        assert_eq!(saliences(processor.process_samples(&second)), fresh);
+        
+        // Smoothing is a setting, kept through reset and config
+        assert_eq!(processor.set_ema_alpha(0.0, 0.5), Err(InvalidAlpha(0.0)));
+        assert_eq!(processor.set_ema_alpha(0.5, f64::NAN).unwrap_err().to_string(), "EMA alpha NaN isn't in (0, 1]");
+        processor.set_ema_alpha(1.0, 0.5).unwrap();
+        processor.reset();
+        assert_eq!(MarineConfig::from(&processor).ema_alpha, (1.0, 0.5));
+        let rebuilt = saliences(MarineConfig::from(&processor).build().process_samples(&second));
+        assert_eq!(saliences(processor.process_samples(&second)), rebuilt);
+        assert_ne!(rebuilt, fresh);
+    }
+    
+    #[test]
+    fn test_detection_modes_find_troughs() {
+        // Breathing: a deep inhale, a shallower exhale, four breaths
+        let breathing: Vec<f64> = (0..2000)
+            .map(|i| {
+                let phase = 2.0 * std::f64::consts::PI * i as f64 / 500.0;
+                if phase.sin() > 0.0 { 0.8 * phase.sin() } else { 0.5 * phase.sin() }
+            })
+            .collect();
+        let inhales: Vec<_> = (0..4).map(|n| (125 + 500 * n, Polarity::Positive)).collect();
+        let exhales: Vec<_> = (0..4).map(|n| (375 + 500 * n, Polarity::Negative)).collect();
+        
+        // Only the inhales by default; both, in order, otherwise
+        assert_eq!(detected(DetectionMode::PositivePeaks, &breathing), inhales);
+        let mut both: Vec<_> = inhales.iter().chain(&exhales).copied().collect();
+        both.sort_by_key(|&(index, _)| index);
+        assert_eq!(detected(DetectionMode::PeaksAndTroughs, &breathing), both);
+        assert_eq!(detected(DetectionMode::AbsolutePeaks, &breathing), both);
+        
+        // A negative-going pulse alone
+        let pulse = [0.0, -0.3, -0.9, -0.3, 0.0];
+        assert!(detected(DetectionMode::PositivePeaks, &pulse).is_empty());
+        assert_eq!(detected(DetectionMode::AbsolutePeaks, &pulse), [(2, Polarity::Negative)]);
+        assert_eq!(detected(DetectionMode::PeaksAndTroughs, &pulse), [(2, Polarity::Negative)]);
+    }
+    
+    fn detected(detection: DetectionMode, samples: &[f64]) -> Vec<(usize, Polarity)> {
+        let mut processor = MarineProcessor::new();
+        processor.detection = detection;
+        processor.process_samples(samples).iter().map(|p| (p.index, p.polarity)).collect()
+    }
+    
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parallel_matches_serial() {
+        let mut samples = spiky_signal(1.0);
+        samples.extend(quiet_passage_signal());
+        samples.extend(spiky_signal(0.4));
+        let summary = |peaks: Vec<PeakInfo>| peaks.iter()
+            .map(|p| (p.index, p.interval, p.salience, p.has_wonder))
+            .collect::<Vec<_>>();
+        
+        // Chunks joining on a peak (every 10 samples), or not, and gates of both kinds
+        for gate in [GateMode::Hard, GateMode::Hysteresis(HysteresisGate::new(0.1, 0.01).with_min_open(40))] {
+            let config = MarineConfig { gate, wonder_adaptation: Some(WonderAdaptation::top_percentile(0.9)), ..MarineConfig::default() };
+            let serial = summary(config.build().process_samples(&samples));
+            assert!(serial.len() > 1000);
+            for chunk_len in [1, 10, 777, 4096, samples.len() * 2] {
+                assert_eq!(summary(config.build().process_samples_parallel(&samples, chunk_len)), serial, "chunks of {}", chunk_len);
+            }
+        }
+        assert!(MarineProcessor::new().process_samples_parallel(&[], 16).is_empty());
+    }
+}
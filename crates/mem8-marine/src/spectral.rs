@@ -0,0 +1,176 @@
+//! Spectral features - what a signal sounds like, not just how loud it is
+//!
+//! Peaks alone can't tell a loud ambient drone from rock: both are loud and
+//! steady. The spectrum can - a drone's energy sits low and in a few
+//! tones, a rock track's is spread bright across the band. With the
+//! `spectral` feature (rustfft), `MarineProcessor::analyze` cuts the signal
+//! into `SpectralConfig::fft_size` windows, measures each one's centroid,
+//! flatness, rolloff and band energies, and averages them into
+//! `MarineMetadata::spectral`. Windows of near silence are left out.
+//!
+//! Hue hears the colour of the sound; Trisha says this one's purple. 🎨
+
+/// Where the low band ends and the high band starts, in Hz
+pub const LOW_BAND_HZ: f64 = 250.0;
+pub const HIGH_BAND_HZ: f64 = 4000.0;
+
+/// The share of a window's energy below its rolloff frequency
+pub const ROLLOFF_SHARE: f64 = 0.85;
+
+/// Power below which a window counts as silence (per sample, squared)
+#[cfg(feature = "spectral")]
+const SILENT_POWER: f64 = 1e-10;
+
+/// How the spectral stage cuts up a signal
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpectralConfig {
+    /// Samples per FFT window (windows don't overlap)
+    pub fft_size: usize,
+    
+    /// What the samples were taken at, to put the bins in Hz
+    pub sample_rate: f64,
+}
+
+impl SpectralConfig {
+    /// A window of roughly 46 ms at `sample_rate` (2048 samples at 44.1 kHz)
+    pub fn for_sample_rate(sample_rate: f64) -> Self {
+        let fft_size = match sample_rate {
+            r if r <= 24_000.0 => 1024,
+            r if r <= 48_000.0 => 2048,
+            r if r <= 96_000.0 => 4096,
+            _ => 8192,
+        };
+        Self { fft_size, sample_rate }
+    }
+}
+
+/// A signal's spectrum, averaged over its windows
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpectralFeatures {
+    /// The spectrum's centre of mass - how bright it sounds
+    pub centroid_hz: f64,
+    
+    /// Geometric over arithmetic mean of the power spectrum: near 0 for
+    /// pure tones, near 1 for white noise
+    pub flatness: f64,
+    
+    /// Below this frequency lies `ROLLOFF_SHARE` of the energy
+    pub rolloff_hz: f64,
+    
+    /// Shares of the energy below `LOW_BAND_HZ`, between the two, and above `HIGH_BAND_HZ`
+    pub low_ratio: f64,
+    pub mid_ratio: f64,
+    pub high_ratio: f64,
+    
+    /// Windows averaged (the silent ones aren't)
+    pub windows: usize,
+}
+
+/// The spectral features of `samples`, or None if there isn't a whole window of sound
+#[cfg(feature = "spectral")]
+pub fn analyze(samples: &[f64], config: &SpectralConfig) -> Option<SpectralFeatures> {
+    use rustfft::num_complex::Complex64;
+    
+    let size = config.fft_size.max(2);
+    let fft = rustfft::FftPlanner::<f64>::new().plan_fft_forward(size);
+    let hann: Vec<f64> = (0..size)
+        .map(|i| 0.5 - 0.5 * (2.0 * std::f64::consts::PI * i as f64 / size as f64).cos())
+        .collect();
+    let bin_hz = config.sample_rate / size as f64;
+    
+    let mut sum = [0.0f64; 6];
+    let mut windows = 0;
+    let mut buffer = vec![Complex64::new(0.0, 0.0); size];
+    for window in samples.chunks_exact(size) {
+        if window.iter().map(|s| s * s).sum::<f64>() / (size as f64) < SILENT_POWER {
+            continue;
+        }
+        for ((slot, &sample), &weight) in buffer.iter_mut().zip(window).zip(&hann) {
+            *slot = Complex64::new(sample * weight, 0.0);
+        }
+        fft.process(&mut buffer);
+        
+        // Up to Nyquist, leaving out DC
+        let power: Vec<f64> = buffer[1..=size / 2].iter().map(|bin| bin.norm_sqr()).collect();
+        let total: f64 = power.iter().sum();
+        if total <= 0.0 {
+            continue;
+        }
+        let hz = |bin: usize| (bin + 1) as f64 * bin_hz;
+        
+        let centroid = power.iter().enumerate().map(|(bin, p)| hz(bin) * p).sum::<f64>() / total;
+        let log_mean = power.iter().map(|p| p.max(f64::MIN_POSITIVE).ln()).sum::<f64>() / power.len() as f64;
+        let flatness = log_mean.exp() / (total / power.len() as f64);
+        let mut below = 0.0;
+        let rolloff = power.iter().enumerate()
+            .find(|(_, p)| {
+                below += *p;
+                below >= ROLLOFF_SHARE * total
+            })
+            .map_or(config.sample_rate / 2.0, |(bin, _)| hz(bin));
+        let band = |range: std::ops::Range<f64>| power.iter().enumerate()
+            .filter(|(bin, _)| range.contains(&hz(*bin)))
+            .map(|(_, p)| p)
+            .sum::<f64>() / total;
+        
+        let features = [
+            centroid,
+            flatness,
+            rolloff,
+            band(0.0..LOW_BAND_HZ),
+            band(LOW_BAND_HZ..HIGH_BAND_HZ),
+            band(HIGH_BAND_HZ..f64::INFINITY),
+        ];
+        for (sum, feature) in sum.iter_mut().zip(features) {
+            *sum += feature;
+        }
+        windows += 1;
+    }
+    
+    if windows == 0 {
+        return None;
+    }
+    let [centroid_hz, flatness, rolloff_hz, low_ratio, mid_ratio, high_ratio] = sum.map(|total| total / windows as f64);
+    Some(SpectralFeatures { centroid_hz, flatness, rolloff_hz, low_ratio, mid_ratio, high_ratio, windows })
+}
+
+#[cfg(all(test, feature = "spectral"))]
+mod tests {
+    use super::*;
+    
+    fn sine(hz: f64, rate: f64, seconds: f64) -> Vec<f64> {
+        (0..(rate * seconds) as usize)
+            .map(|i| 0.8 * (2.0 * std::f64::consts::PI * hz * i as f64 / rate).sin())
+            .collect()
+    }
+    
+    #[test]
+    fn test_tone_and_noise_spectra() {
+        let config = SpectralConfig::for_sample_rate(44_100.0);
+        assert_eq!(config.fft_size, 2048);
+        
+        let tone = analyze(&sine(100.0, 44_100.0, 1.0), &config).unwrap();
+        assert!((tone.centroid_hz - 100.0).abs() < 30.0, "{:?}", tone);
+        assert!(tone.flatness < 0.01 && tone.low_ratio > 0.95, "{:?}", tone);
+        assert!(tone.rolloff_hz < LOW_BAND_HZ);
+        assert_eq!(tone.windows, 21);
+        
+        let mut seed: u64 = 0x8b15;
+        let noise: Vec<f64> = (0..44_100)
+            .map(|_| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                (seed >> 33) as f64 / (1u64 << 31) as f64 - 0.5
+            })
+            .collect();
+        let noise = analyze(&noise, &config).unwrap();
+        assert!(noise.centroid_hz > 9_000.0 && noise.flatness > 0.4, "{:?}", noise);
+        assert!(noise.high_ratio > 0.7);
+        assert!((noise.low_ratio + noise.mid_ratio + noise.high_ratio - 1.0).abs() < 1e-9);
+        
+        // Silence, or less than a window, has no spectrum to speak of
+        assert!(analyze(&[0.0; 44_100], &config).is_none());
+        assert!(analyze(&sine(100.0, 44_100.0, 0.01), &config).is_none());
+    }
+}
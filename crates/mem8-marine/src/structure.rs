@@ -0,0 +1,219 @@
+//! Structure - where a track changes character
+//!
+//! `MarineProcessor::segment` looks for the points in a `salience_timeline`
+//! where the energy (rms) and salience shift, by binary segmentation: split
+//! wherever it most reduces the spread of both around their means, for as
+//! long as a split is worth `SPLIT_PENALTY` per log window. Each boundary
+//! then moves onto the peak near it whose amplitude jumps the most, so
+//! sections start on an onset rather than a window edge, and each section
+//! gets a rough label from its energy against the rest of the track.
+//!
+//! Hue hears the build; Trisha knows the drop is coming. 🎢
+
+use crate::{MarineProcessor, PeakInfo, WindowStats};
+
+/// Windows a section has at least
+pub const MIN_SEGMENT_WINDOWS: usize = 3;
+
+/// What a split has to gain, times ln(windows), in standardised squared units
+pub const SPLIT_PENALTY: f64 = 4.0;
+
+/// Below this share of the track's mean energy, a section is quiet
+const QUIET_SHARE: f64 = 0.5;
+
+/// Within this share of the loudest section's energy, a section is a peak
+const PEAK_SHARE: f64 = 0.9;
+
+/// A rough name for what a section is doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum SegmentLabel {
+    /// Well under the track's mean energy
+    Quiet,
+    
+    /// Louder than the section before
+    Building,
+    
+    /// About as loud as the track gets
+    Peak,
+    
+    /// Quieter than the section before
+    Falling,
+}
+
+impl std::fmt::Display for SegmentLabel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            SegmentLabel::Quiet => "quiet",
+            SegmentLabel::Building => "building",
+            SegmentLabel::Peak => "peak",
+            SegmentLabel::Falling => "falling",
+        })
+    }
+}
+
+/// A stretch of a track with one character
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Segment {
+    pub start_sec: f64,
+    pub end_sec: f64,
+    pub label: SegmentLabel,
+    
+    /// Means over the section's windows
+    pub mean_salience: f64,
+    pub mean_energy: f64,
+}
+
+impl MarineProcessor {
+    /// Split a track into sections where its energy and salience change
+    ///
+    /// `timeline` is the track's `salience_timeline` and `peaks` its peaks
+    /// (from `process_samples`, say: the same sample indices), taken at
+    /// `sample_rate`. The sections cover the timeline end to end; the last
+    /// ends where its last window starts plus one hop.
+    pub fn segment(&self, peaks: &[PeakInfo], timeline: &[WindowStats], sample_rate: f64) -> Vec<Segment> {
+        let Some(first) = timeline.first() else {
+            return Vec::new();
+        };
+        let hop = match timeline {
+            [a, b, ..] => b.start_sample - a.start_sample,
+            _ => 0,
+        };
+        
+        // Boundaries as window indices, then as samples moved onto onsets
+        let features = [
+            standardised(timeline.iter().map(|w| w.rms)),
+            standardised(timeline.iter().map(|w| w.mean_salience)),
+        ];
+        let mut cuts = vec![0, timeline.len()];
+        split(&features, 0, timeline.len(), &mut cuts);
+        cuts.sort_unstable();
+        let samples: Vec<usize> = cuts.iter()
+            .map(|&cut| match cut {
+                0 => first.start_sample,
+                cut if cut == timeline.len() => timeline[cut - 1].start_sample + hop,
+                cut => onset_near(peaks, timeline[cut].start_sample, hop / 2),
+            })
+            .collect();
+        
+        let track_energy = timeline.iter().map(|w| w.rms).sum::<f64>() / timeline.len() as f64;
+        let means: Vec<(f64, f64)> = cuts.windows(2)
+            .map(|pair| {
+                let windows = &timeline[pair[0]..pair[1]];
+                let n = windows.len() as f64;
+                (windows.iter().map(|w| w.mean_salience).sum::<f64>() / n, windows.iter().map(|w| w.rms).sum::<f64>() / n)
+            })
+            .collect();
+        let loudest = means.iter().map(|&(_, energy)| energy).fold(0.0, f64::max);
+        
+        means.iter().enumerate()
+            .map(|(i, &(mean_salience, mean_energy))| {
+                let label = if mean_energy < QUIET_SHARE * track_energy {
+                    SegmentLabel::Quiet
+                } else if mean_energy >= PEAK_SHARE * loudest {
+                    SegmentLabel::Peak
+                } else if i == 0 || mean_energy > means[i - 1].1 {
+                    SegmentLabel::Building
+                } else {
+                    SegmentLabel::Falling
+                };
+                Segment {
+                    start_sec: samples[i] as f64 / sample_rate,
+                    end_sec: samples[i + 1] as f64 / sample_rate,
+                    label,
+                    mean_salience,
+                    mean_energy,
+                }
+            })
+            .collect()
+    }
+}
+
+/// `values` over their standard deviation, around their mean (all zeros if they don't vary)
+fn standardised(values: impl Iterator<Item = f64>) -> Vec<f64> {
+    let values: Vec<f64> = values.collect();
+    let n = values.len().max(1) as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let spread = (values.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / n).sqrt();
+    values.iter()
+        .map(|v| if spread > 0.0 { (v - mean) / spread } else { 0.0 })
+        .collect()
+}
+
+/// The spread of `features[..][start..end]` around their means
+fn cost(features: &[Vec<f64>], start: usize, end: usize) -> f64 {
+    features.iter()
+        .map(|values| {
+            let part = &values[start..end];
+            let mean = part.iter().sum::<f64>() / part.len() as f64;
+            part.iter().map(|v| (v - mean).powi(2)).sum::<f64>()
+        })
+        .sum()
+}
+
+/// Add the cuts worth making in windows `start..end` to `cuts`
+fn split(features: &[Vec<f64>], start: usize, end: usize, cuts: &mut Vec<usize>) {
+    if end - start < 2 * MIN_SEGMENT_WINDOWS {
+        return;
+    }
+    let whole = cost(features, start, end);
+    let (gain, cut) = (start + MIN_SEGMENT_WINDOWS..=end - MIN_SEGMENT_WINDOWS)
+        .map(|cut| (whole - cost(features, start, cut) - cost(features, cut, end), cut))
+        .fold((0.0, start), |best, candidate| if candidate.0 > best.0 { candidate } else { best });
+    let windows = features.first().map_or(0, Vec::len);
+    if gain <= SPLIT_PENALTY * (windows as f64).ln() {
+        return;
+    }
+    cuts.push(cut);
+    split(features, start, cut, cuts);
+    split(features, cut, end, cuts);
+}
+
+/// The peak within `reach` samples of `sample` whose amplitude jumps most
+/// from the peak before it, or `sample` itself if there's none
+fn onset_near(peaks: &[PeakInfo], sample: usize, reach: usize) -> usize {
+    let from = peaks.partition_point(|p| p.index < sample.saturating_sub(reach)).max(1);
+    let to = peaks.partition_point(|p| p.index <= sample + reach);
+    (from..to)
+        .map(|i| ((peaks[i].amplitude - peaks[i - 1].amplitude).abs(), peaks[i].index))
+        .max_by(|a, b| a.0.total_cmp(&b.0))
+        .map_or(sample, |(_, index)| index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// `seconds` of spikes every 10 samples at `level`, at 1 kHz
+    fn section(level: f64, seconds: usize) -> Vec<f64> {
+        (0..seconds * 1000).map(|i| if i % 10 == 5 { level } else { 0.0 }).collect()
+    }
+    
+    #[test]
+    fn test_segments_follow_the_energy() {
+        let mut samples = section(0.15, 8);
+        samples.extend(section(0.5, 8));
+        samples.extend(section(1.0, 8));
+        samples.extend(section(0.15, 8));
+        let mut processor = MarineProcessor::new();
+        let peaks = processor.process_samples(&samples);
+        let timeline = MarineProcessor::new().salience_timeline(&samples, 500, 500);
+        
+        let segments = processor.segment(&peaks, &timeline, 1000.0);
+        let labels: Vec<SegmentLabel> = segments.iter().map(|s| s.label).collect();
+        assert_eq!(labels, [SegmentLabel::Quiet, SegmentLabel::Building, SegmentLabel::Peak, SegmentLabel::Quiet]);
+        
+        // Boundaries land on onsets (peaks at ...5), and the sections cover it all
+        let bounds: Vec<(f64, f64)> = segments.iter().map(|s| (s.start_sec, s.end_sec)).collect();
+        assert_eq!(bounds, [(0.0, 8.005), (8.005, 16.005), (16.005, 24.005), (24.005, 32.0)]);
+        assert!(segments[2].mean_energy > segments[1].mean_energy);
+        assert!(segments[2].mean_salience > segments[0].mean_salience);
+        
+        // Nothing changes, nothing to split; nothing at all, no sections
+        let steady = MarineProcessor::new().salience_timeline(&section(0.5, 20), 500, 500);
+        assert_eq!(processor.segment(&[], &steady, 1000.0).len(), 1);
+        assert!(processor.segment(&[], &[], 1000.0).is_empty());
+    }
+}
@@ -8,7 +8,7 @@
 //! Hue, this is where audio becomes memory with emotion and perspective!
 //! Each listener hears their own truth in the waves. 🎵
 
-use mem8_fs_lite::{Mem8Lite, MarineProcessor, MarineMetadata};
+use mem8_fs_lite::{Mem8Lite, MarineProcessor, MarineMetadata, FrequencyPreset};
 use anyhow::Result;
 use std::f64::consts::PI;
 use num_complex::Complex64;
@@ -109,9 +109,12 @@ fn main() -> Result<()> {
         },
     ];
     
-    // Process and store from each perspective
-    let mut storage = Mem8Lite::new("/tmp/mem8_audio_memories.m8", 1.618)?;
+    // Process and store from each perspective, linked as one memory group
+    let mut storage = Mem8Lite::new("/tmp/mem8_audio_memories.m8", FrequencyPreset::GoldenRatio)?;
     let mut signatures = Vec::new();
+    let moment = format!("emotional-melody-{}", std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)?
+        .as_secs());
     
     for perspective in &perspectives {
         println!("=== {} ===", perspective.metadata_prefix());
@@ -129,19 +132,19 @@ fn main() -> Result<()> {
         let meta_json = create_temporal_metadata(&metadata, &perspective)?;
         
         // Store with perspective metadata
-        let sig = storage.store(&audio_to_bytes(&audio), Some(meta_json))?;
+        let sig = storage.store_linked(&moment, &audio_to_bytes(&audio), Some(meta_json))?;
         signatures.push((perspective.clone(), sig));
         
         println!("Stored with signature: {}\n", hex::encode(&sig[..8]));
     }
     
-    // Now retrieve and compare perspectives
-    println!("\n=== Retrieving Memories ===\n");
+    // Now retrieve every perspective of the moment through its group
+    println!("\n=== Retrieving Memories ({}) ===\n", moment);
     
-    for (perspective, sig) in &signatures {
-        if let Some(metadata) = storage.get_metadata(&sig) {
+    for member in storage.get_memory_group(&moment)? {
+        if let Some(metadata) = storage.get_metadata(&member.signature) {
             let meta_str = String::from_utf8_lossy(&metadata);
-            println!("{}", perspective.metadata_prefix());
+            println!("{} ({})", member.perspective, hex::encode(&member.signature[..8]));
             println!("Metadata: {}\n", meta_str);
         }
     }
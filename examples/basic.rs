@@ -2,7 +2,7 @@
 //! 
 //! Run with: cargo run --example basic
 
-use mem8_fs_lite::{Mem8Fs, Mem8Lite};
+use mem8_fs_lite::{Mem8Error, Mem8Fs, Mem8Lite, FlushPolicy, FrequencyPreset};
 use anyhow::Result;
 use std::time::Instant;
 
@@ -23,7 +23,7 @@ fn main() -> Result<()> {
 }
 
 fn simple_storage_demo() -> Result<()> {
-    let mut storage = Mem8Lite::new("/tmp/mem8_example.m8", 1.618)?;
+    let mut storage = Mem8Lite::new("/tmp/mem8_example.m8", FrequencyPreset::GoldenRatio)?;
     
     // Store some data
     let messages = vec![
@@ -92,11 +92,22 @@ fn filesystem_demo() -> Result<()> {
     println!("  Size: {} bytes", meta.size);
     println!("  Signature: {}", &meta.signature[..16]);
     
+    // Errors are a Mem8Error to match on
+    match fs.read("/documents/missing.txt") {
+        Err(Mem8Error::NotFound(_)) => println!("\n🔍 No missing.txt, as expected"),
+        other => println!("\n🤔 Unexpected: {:?}", other.map(|data| data.len())),
+    }
+    
     Ok(())
 }
 
 fn performance_demo() -> Result<()> {
-    let mut storage = Mem8Lite::new("/tmp/mem8_perf.m8", 2.0)?;
+    // Fresh stores, so reruns don't just hit dedup
+    let dir = tempfile::tempdir()?;
+    let mut storage = Mem8Lite::new(dir.path().join("perf.m8"), 2.0)?;
+    
+    // Durable writes: every packet is fsynced before store returns
+    storage.set_flush_policy(FlushPolicy::EveryWrite);
     
     // Generate test data
     let test_data: Vec<Vec<u8>> = (0..1000)
@@ -117,6 +128,18 @@ fn performance_demo() -> Result<()> {
     println!("✅ Write time: {:?}", write_time);
     println!("   Per entry: {:?}", write_time / 1000);
     
+    // Same durability for a batch: one write, one fsync
+    let mut batched = Mem8Lite::new(dir.path().join("batch.m8"), 2.0)?;
+    batched.set_flush_policy(FlushPolicy::EveryWrite);
+    let items: Vec<(&[u8], Option<Vec<u8>>)> = test_data.iter().map(|data| (data.as_slice(), None)).collect();
+    
+    println!("\n📦 Writing the same 1000 entries as one batch...");
+    let start = Instant::now();
+    batched.store_batch(&items)?;
+    let batch_time = start.elapsed();
+    println!("✅ Batch write time: {:?} ({:.0}× faster)", batch_time,
+             write_time.as_secs_f64() / batch_time.as_secs_f64().max(1e-9));
+    
     // Benchmark reads
     println!("\n📖 Reading 1000 entries...");
     let start = Instant::now();
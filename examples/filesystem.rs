@@ -0,0 +1,23 @@
+//! Mount a MEM8 filesystem with FUSE
+//! 
+//! Run with: cargo run --example filesystem --features fuse-mount -- /mnt/mem8
+//! 
+//! Press Ctrl-C to unmount - the index gets flushed on the way out!
+
+use mem8_fs_lite::{Mem8Fs, mount::Mem8FuseFs};
+use anyhow::Result;
+use std::sync::Arc;
+
+fn main() -> Result<()> {
+    let mountpoint = std::env::args().nth(1).unwrap_or_else(|| "/tmp/mem8_mnt".to_string());
+    std::fs::create_dir_all(&mountpoint)?;
+    
+    let fs = Arc::new(Mem8Fs::new("/tmp/mem8_fuse_data")?);
+    fs.write_string("hello.txt", "Hello from the waves! 🌊\n")?;
+    
+    println!("🚀 Mounted MEM8 at {} (Ctrl-C to unmount)", mountpoint);
+    Mem8FuseFs::new(fs).mount_until_shutdown(&mountpoint)?;
+    println!("👋 Unmounted cleanly");
+    
+    Ok(())
+}
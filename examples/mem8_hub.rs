@@ -0,0 +1,73 @@
+//! mem8-hub - a home sensor hub built from the library pieces
+//! 
+//! Sensors post readings over HTTP, fusion and alerts run on every post,
+//! readings land in the store (pruned to the retention window), and an LLM
+//! talks to the same store over MCP on stdin/stdout.
+//! 
+//! Run with: cargo run --example mem8-hub -- hub.toml
+//! (see `mem8_fs_lite::hub` for the config format)
+//! 
+//! Post a reading:
+//!   curl -d '{"Analog": {"id": "desk_lux", "value": 42, "range": [0, 1000], "unit": "lux", "timestamp": 1700000000}}' \
+//!        http://127.0.0.1:8088/sensors
+
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use anyhow::{Result, anyhow};
+use mem8_fs_lite::hub::{self, HttpIngress, HubConfig, SensorHub};
+use mem8_fs_lite::shutdown;
+
+/// How often old readings are pruned
+const PRUNE_EVERY: Duration = Duration::from_secs(60);
+
+fn main() -> Result<()> {
+    let path = std::env::args().nth(1)
+        .ok_or_else(|| anyhow!("Usage: mem8-hub CONFIG.toml"))?;
+    let config = HubConfig::load(&path)?;
+    shutdown::install_handlers();
+    
+    let hub = Arc::new(Mutex::new(SensorHub::new(config.clone())?));
+    let server = hub.lock().unwrap().server();
+    eprintln!("🏠 mem8-hub: {} sensors, store {}", config.sensors.len(), config.store.display());
+    
+    let ingress = match &config.http_bind {
+        Some(bind) => {
+            let ingress = HttpIngress::start(bind, Arc::clone(&hub))?;
+            eprintln!("📡 Sensor posts: http://{}/sensors", ingress.local_addr());
+            Some(ingress)
+        }
+        None => None,
+    };
+    
+    // MCP on stdio (stdout is the protocol - everything else goes to stderr).
+    // When the client hangs up, the hub shuts down too.
+    std::thread::spawn(move || {
+        let stdin = std::io::stdin();
+        if let Err(e) = server.serve_stdio(stdin.lock(), std::io::stdout()) {
+            eprintln!("⚠️ mem8-hub: MCP transport failed: {}", e);
+        }
+        shutdown::request();
+    });
+    
+    let mut since_prune = Duration::ZERO;
+    while !shutdown::requested() {
+        std::thread::sleep(Duration::from_millis(200));
+        since_prune += Duration::from_millis(200);
+        if since_prune >= PRUNE_EVERY {
+            since_prune = Duration::ZERO;
+            match hub.lock().unwrap().prune(hub::now()) {
+                Ok(0) => {}
+                Ok(pruned) => eprintln!("🧹 Pruned {} readings past retention", pruned),
+                Err(e) => eprintln!("⚠️ mem8-hub: prune failed: {}", e),
+            }
+        }
+    }
+    
+    // Stop taking posts, then flush everything
+    eprintln!("🛑 mem8-hub shutting down...");
+    if let Some(ingress) = ingress {
+        ingress.stop();
+    }
+    hub.lock().unwrap().shutdown()?;
+    Ok(())
+}
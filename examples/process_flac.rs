@@ -88,6 +88,41 @@ fn main() -> Result<()> {
     
     println!("\n{}", metadata);
     
+    // Where the most salient moments are
+    let mut salient: Vec<_> = peaks.iter().collect();
+    salient.sort_by(|a, b| b.salience.total_cmp(&a.salience));
+    if !salient.is_empty() {
+        println!("🏔️ Most salient moments:");
+    }
+    for peak in salient.iter().take(10) {
+        let seconds = peak.time_seconds(loaded.format.sample_rate.as_f64());
+        println!("  {:2}:{:05.2}  salience {:.3}{}", (seconds / 60.0) as u64, seconds % 60.0,
+            peak.salience, if peak.has_wonder { " ✨" } else { "" });
+    }
+    
+    // Salience over time, a character per second
+    let second = loaded.format.sample_rate.as_f64() as usize;
+    let timeline = mem8_fs_lite::marine::MarineConfig::from(&processor).build()
+        .salience_timeline(&mono_samples, second, second);
+    let highest = timeline.iter().map(|w| w.mean_salience).fold(0.0, f64::max);
+    if highest > 0.0 {
+        const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+        let sparkline: String = timeline.iter()
+            .map(|w| BARS[((w.mean_salience / highest) * 7.0).round() as usize])
+            .collect();
+        println!("\n📈 Salience per second:\n  {}", sparkline);
+    }
+    
+    // Where it changes character
+    let segments = processor.segment(&peaks, &timeline, loaded.format.sample_rate.as_f64());
+    if segments.len() > 1 {
+        println!("\n🎢 Sections:");
+        for segment in &segments {
+            println!("  {:6.1}s - {:6.1}s  {:<8}  salience {:.3}", segment.start_sec, segment.end_sec,
+                segment.label.to_string(), segment.mean_salience);
+        }
+    }
+    
     // Analyze specific sections for "An Ending (Ascent)"
     if audio_path.to_lowercase().contains("ending") || 
        audio_path.to_lowercase().contains("ascent") {
@@ -98,7 +133,7 @@ fn main() -> Result<()> {
     println!("\n💾 Storing in MEM8 wave format...");
     
     let storage_path = "/tmp/mem8_flac_storage.m8";
-    let mut storage = Mem8Lite::new(storage_path, loaded.format.sample_rate.wave_frequency())?;
+    let mut storage = Mem8Lite::new(storage_path, loaded.format.sample_rate.frequency_preset())?;
     
     // Create rich metadata
     let meta_json = serde_json::json!({
@@ -108,6 +143,8 @@ fn main() -> Result<()> {
                 mem8_fs_lite::audio_loader::AudioFileFormat::Flac => "FLAC",
                 mem8_fs_lite::audio_loader::AudioFileFormat::Wav => "WAV",
                 mem8_fs_lite::audio_loader::AudioFileFormat::RawPcm(_) => "RAW_PCM",
+                mem8_fs_lite::audio_loader::AudioFileFormat::Mp4 => "MP4",
+                mem8_fs_lite::audio_loader::AudioFileFormat::Matroska => "MATROSKA",
             },
             "sample_rate": loaded.format.sample_rate.as_f64(),
             "channels": loaded.format.channels,
@@ -127,13 +164,16 @@ fn main() -> Result<()> {
             .as_secs(),
     });
     
-    let signature = storage.store(
-        &mono_samples.iter()
-            .flat_map(|&s| {
-                let pcm = (s * 32767.0).max(-32768.0).min(32767.0) as i16;
-                pcm.to_le_bytes()
-            })
-            .collect::<Vec<_>>(),
+    let pcm: Vec<u8> = mono_samples.iter()
+        .flat_map(|&s| {
+            let pcm = (s * 32767.0).clamp(-32768.0, 32767.0) as i16;
+            pcm.to_le_bytes()
+        })
+        .collect();
+    
+    // Streamed in chunks, so the (16× larger) wave encoding never sits in memory whole
+    let signature = storage.store_stream(
+        &pcm[..],
         Some(serde_json::to_vec(&meta_json)?),
     )?;
     
@@ -182,23 +222,3 @@ fn analyze_ascent_sections(samples: &[f64], sample_rate: &f64) -> Result<()> {
     
     Ok(())
 }
-
-/// Get the wave frequency for this sample rate
-trait WaveFrequency {
-    fn wave_frequency(&self) -> f64;
-}
-
-impl WaveFrequency for mem8_fs_lite::audio::SampleRate {
-    fn wave_frequency(&self) -> f64 {
-        use mem8_fs_lite::audio::SampleRate;
-        match self {
-            SampleRate::Phone16k => 0.5,
-            SampleRate::Broadcast22k => 0.8,
-            SampleRate::CD44k => 1.618,  // Golden ratio!
-            SampleRate::DVD48k => 2.0,
-            SampleRate::Studio96k => 3.14159,
-            SampleRate::Audiophile192k => 4.669,
-            SampleRate::Custom(rate) => 1.618 * (rate / 44100.0).sqrt(),
-        }
-    }
-}
\ No newline at end of file
@@ -0,0 +1,320 @@
+//! Listening reports - what worked for each activity, and is it still working?
+//!
+//! Aggregates the mood engine's listening journal (and its mood transitions)
+//! over a time range: tracks and minutes per activity, mean effectiveness,
+//! the best and worst artists, and trends found by comparing the first half
+//! of the range with the second.
+//!
+//! Hue's weekly retro, but for the playlist! 📈
+
+use std::collections::{HashMap, HashSet};
+use serde::{Serialize, Deserialize};
+
+use crate::float_format::{canonical_f64, FloatCategory};
+use crate::mood_engine::{Activity, Genre, ListeningEntry, MoodTransition};
+
+/// Change in mean effectiveness treated as a real trend (not noise)
+pub const TREND_THRESHOLD: f64 = 0.1;
+
+/// Artists listed in each of the top/bottom lists
+const ARTISTS_LISTED: usize = 3;
+
+/// Time window for a report (unix seconds, end exclusive)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReportRange {
+    pub start: u64,
+    pub end: u64,
+}
+
+impl ReportRange {
+    /// The last `days` days up to now
+    pub fn last_days(days: u64) -> Self {
+        let end = chrono::Utc::now().timestamp() as u64 + 1;
+        Self { start: end.saturating_sub(days * 24 * 60 * 60), end }
+    }
+    
+    pub fn contains(&self, timestamp: u64) -> bool {
+        timestamp >= self.start && timestamp < self.end
+    }
+    
+    /// Where the first half ends and the second begins
+    pub fn midpoint(&self) -> u64 {
+        self.start + (self.end - self.start) / 2
+    }
+}
+
+/// Which way effectiveness is heading
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TrendDirection {
+    Rising,
+    Steady,
+    Declining,
+}
+
+/// First-half vs second-half effectiveness for one artist or genre
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Trend {
+    /// Artist name, or genre (e.g. "Electronic")
+    pub subject: String,
+    
+    /// "artist" or "genre"
+    pub kind: String,
+    
+    pub first_half: f64,
+    pub second_half: f64,
+    pub direction: TrendDirection,
+}
+
+/// How well one artist worked for an activity
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ArtistEffect {
+    pub artist: String,
+    pub listens: usize,
+    pub mean_effectiveness: f64,
+}
+
+/// Everything about one activity in the range
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActivitySummary {
+    pub activity: Activity,
+    
+    /// Distinct tracks heard
+    pub tracks: usize,
+    
+    /// Total listens (repeats included)
+    pub listens: usize,
+    
+    pub minutes: f64,
+    pub mean_effectiveness: f64,
+    
+    /// Best artists first
+    pub top_artists: Vec<ArtistEffect>,
+    
+    /// Worst artists first
+    pub bottom_artists: Vec<ArtistEffect>,
+    
+    /// Artists and genres heard in both halves of the range
+    pub trends: Vec<Trend>,
+}
+
+/// Per-activity listening report
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ActivityReport {
+    pub range: ReportRange,
+    pub activities: Vec<ActivitySummary>,
+    
+    /// Mood transitions recorded in the range
+    pub transitions: usize,
+    
+    /// Mean effectiveness of those transitions
+    pub transition_effectiveness: Option<f64>,
+}
+
+impl ActivityReport {
+    /// Aggregate a journal and transition history over `range`
+    pub fn build(journal: &[ListeningEntry], transitions: &[MoodTransition], range: ReportRange) -> Self {
+        let mut by_activity: HashMap<&Activity, Vec<&ListeningEntry>> = HashMap::new();
+        for entry in journal.iter().filter(|e| range.contains(e.timestamp)) {
+            by_activity.entry(&entry.activity).or_default().push(entry);
+        }
+        
+        let mut activities: Vec<ActivitySummary> = by_activity.into_iter()
+            .map(|(activity, entries)| summarize(activity, &entries, range))
+            .collect();
+        activities.sort_by(|a, b| b.minutes.partial_cmp(&a.minutes).unwrap_or(std::cmp::Ordering::Equal));
+        
+        let in_range: Vec<f64> = transitions.iter()
+            .filter(|t| range.contains(t.timestamp))
+            .map(|t| t.effectiveness)
+            .collect();
+        
+        Self {
+            range,
+            activities,
+            transitions: in_range.len(),
+            transition_effectiveness: mean(&in_range),
+        }
+    }
+    
+    /// Trends pointing down across all activities
+    pub fn declining(&self) -> Vec<(&Activity, &Trend)> {
+        self.activities.iter()
+            .flat_map(|a| a.trends.iter().map(move |t| (&a.activity, t)))
+            .filter(|(_, t)| t.direction == TrendDirection::Declining)
+            .collect()
+    }
+}
+
+fn summarize(activity: &Activity, entries: &[&ListeningEntry], range: ReportRange) -> ActivitySummary {
+    let tracks: HashSet<(&str, &str)> = entries.iter()
+        .map(|e| (e.artist.as_str(), e.title.as_str()))
+        .collect();
+    let effectiveness: Vec<f64> = entries.iter().map(|e| e.effectiveness).collect();
+    
+    // Artists ranked by mean effectiveness
+    let mut per_artist: HashMap<&str, Vec<f64>> = HashMap::new();
+    for entry in entries {
+        per_artist.entry(entry.artist.as_str()).or_default().push(entry.effectiveness);
+    }
+    let mut artists: Vec<ArtistEffect> = per_artist.iter()
+        .map(|(artist, values)| ArtistEffect {
+            artist: artist.to_string(),
+            listens: values.len(),
+            mean_effectiveness: rounded(mean(values).unwrap_or(0.0)),
+        })
+        .collect();
+    artists.sort_by(|a, b| b.mean_effectiveness.partial_cmp(&a.mean_effectiveness)
+        .unwrap_or(std::cmp::Ordering::Equal)
+        .then_with(|| a.artist.cmp(&b.artist)));
+    let top_artists = artists.iter().take(ARTISTS_LISTED).cloned().collect();
+    let bottom_artists = artists.iter().rev().take(ARTISTS_LISTED).cloned().collect();
+    
+    let mut trends = trends_by(entries, range, "artist", |e| Some(e.artist.clone()));
+    trends.extend(trends_by(entries, range, "genre", |e| e.genre.as_ref().map(genre_name)));
+    
+    ActivitySummary {
+        activity: activity.clone(),
+        tracks: tracks.len(),
+        listens: entries.len(),
+        minutes: rounded(entries.iter().map(|e| e.minutes).sum()),
+        mean_effectiveness: rounded(mean(&effectiveness).unwrap_or(0.0)),
+        top_artists,
+        bottom_artists,
+        trends,
+    }
+}
+
+/// Compare each subject's mean effectiveness in the two halves of the range
+fn trends_by<F>(entries: &[&ListeningEntry], range: ReportRange, kind: &str, subject: F) -> Vec<Trend>
+where F: Fn(&ListeningEntry) -> Option<String> {
+    let midpoint = range.midpoint();
+    let mut halves: HashMap<String, (Vec<f64>, Vec<f64>)> = HashMap::new();
+    for entry in entries {
+        if let Some(name) = subject(entry) {
+            let (first, second) = halves.entry(name).or_default();
+            if entry.timestamp < midpoint {
+                first.push(entry.effectiveness);
+            } else {
+                second.push(entry.effectiveness);
+            }
+        }
+    }
+    
+    let mut trends: Vec<Trend> = halves.into_iter()
+        .filter_map(|(name, (first, second))| {
+            let (first, second) = (mean(&first)?, mean(&second)?);
+            let delta = second - first;
+            Some(Trend {
+                subject: name,
+                kind: kind.to_string(),
+                first_half: rounded(first),
+                second_half: rounded(second),
+                direction: if delta >= TREND_THRESHOLD {
+                    TrendDirection::Rising
+                } else if delta <= -TREND_THRESHOLD {
+                    TrendDirection::Declining
+                } else {
+                    TrendDirection::Steady
+                },
+            })
+        })
+        .collect();
+    trends.sort_by(|a, b| a.subject.cmp(&b.subject));
+    trends
+}
+
+fn genre_name(genre: &Genre) -> String {
+    format!("{:?}", genre)
+}
+
+fn mean(values: &[f64]) -> Option<f64> {
+    if values.is_empty() {
+        None
+    } else {
+        Some(values.iter().sum::<f64>() / values.len() as f64)
+    }
+}
+
+fn rounded(value: f64) -> f64 {
+    canonical_f64(value, FloatCategory::Level)
+}
+
+impl std::fmt::Display for ActivityReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "📊 Listening Report ({} days):", (self.range.end - self.range.start) / 86_400)?;
+        if self.activities.is_empty() {
+            writeln!(f, "  Nothing listened to in this range 🤫")?;
+        }
+        for summary in &self.activities {
+            write!(f, "\n  {}: {} tracks, {:.0} min, {:.0}% effective\n",
+                   summary.activity, summary.tracks, summary.minutes, summary.mean_effectiveness * 100.0)?;
+            if let Some(best) = summary.top_artists.first() {
+                writeln!(f, "    🏆 Best: {} ({:.0}%)", best.artist, best.mean_effectiveness * 100.0)?;
+            }
+            if let Some(worst) = summary.bottom_artists.first() {
+                writeln!(f, "    👎 Worst: {} ({:.0}%)", worst.artist, worst.mean_effectiveness * 100.0)?;
+            }
+            for trend in summary.trends.iter().filter(|t| t.direction != TrendDirection::Steady) {
+                let arrow = if trend.direction == TrendDirection::Rising { "📈" } else { "📉" };
+                writeln!(f, "    {} {} {}: {:.0}% → {:.0}%", arrow, trend.kind, trend.subject,
+                       trend.first_half * 100.0, trend.second_half * 100.0)?;
+            }
+        }
+        if let Some(effectiveness) = self.transition_effectiveness {
+            write!(f, "\n  Mood transitions: {} ({:.0}% effective)\n", self.transitions, effectiveness * 100.0)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mood_engine::MoodEngine;
+    
+    const DAY: u64 = 24 * 60 * 60;
+    
+    fn listen(day: u64, artist: &str, genre: Genre, effectiveness: f64) -> ListeningEntry {
+        ListeningEntry {
+            timestamp: 1_700_000_000 + day * DAY + 3_600,
+            activity: Activity::Programming,
+            artist: artist.to_string(),
+            title: format!("{} track {}", artist, day % 3),
+            genre: Some(genre),
+            minutes: 30.0,
+            effectiveness,
+        }
+    }
+    
+    #[test]
+    fn test_report_flags_declining_artist() {
+        let mut engine = MoodEngine::create_hue_profile();
+        for day in 0..14 {
+            // Orbital keeps working; the other one wears thin in week two
+            engine.record_listening(listen(day, "Orbital", Genre::Electronic, 0.9));
+            let fading = if day < 7 { 0.85 } else { 0.35 };
+            engine.record_listening(listen(day, "Overplayed Band", Genre::Crossover, fading));
+        }
+        
+        let range = ReportRange { start: 1_700_000_000, end: 1_700_000_000 + 14 * DAY };
+        let report = engine.activity_report(range);
+        assert_eq!(report.activities.len(), 1);
+        
+        let programming = &report.activities[0];
+        assert_eq!(programming.listens, 28);
+        assert_eq!(programming.minutes, 840.0);
+        assert_eq!(programming.top_artists[0].artist, "Orbital");
+        assert_eq!(programming.bottom_artists[0].artist, "Overplayed Band");
+        
+        let declining: Vec<&str> = report.declining().iter().map(|(_, t)| t.subject.as_str()).collect();
+        assert_eq!(declining, vec!["Overplayed Band", "Crossover"]); // Artists, then genres
+        let orbital = programming.trends.iter().find(|t| t.subject == "Orbital").unwrap();
+        assert_eq!(orbital.direction, TrendDirection::Steady);
+        
+        // Renders both ways
+        assert!(report.to_string().contains("📉 artist Overplayed Band"));
+        let json = serde_json::to_value(&report).unwrap();
+        assert_eq!(json["activities"][0]["activity"], "Programming");
+    }
+}
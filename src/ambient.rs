@@ -0,0 +1,523 @@
+//! Ambient pads that breathe with you - for meditation sessions
+//!
+//! `AmbientGenerator` renders a slowly evolving pad, one buffer at a time.
+//! Its swell (the slow rise and fall in loudness) follows the live breathing
+//! sensor: `feed` a `SensorData::Breathing` reading and the swell period
+//! drifts toward one breath, the loudness toward the breath's depth. Both
+//! move smoothly, so a jumpy sensor never makes the pad lurch.
+//!
+//! The pad is meant to sit under real music, not on top of it: `fill_under`
+//! mixes it beneath a playing track and ducks it while the track is audible.
+//! `AmbientSession` keeps what was generated along with the breathing that
+//! drove it, and `capture` stores both as one memory group.
+//!
+//! Trisha's 4-7-8 breathing finally has a soundtrack. 🌬️
+
+use std::f64::consts::PI;
+use std::time::Duration;
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+use anyhow::Result;
+
+use crate::audio::SampleRate;
+use crate::float_format::{json_f64, FloatCategory};
+use crate::lite::Mem8Lite;
+use crate::sensor_ingress::SensorData;
+
+/// Breaths per minute the swell starts at, before any reading arrives
+pub const DEFAULT_BREATHING_RATE: f64 = 6.0;
+
+/// Breathing rates outside this range (breaths per minute) are clamped
+pub const BREATHING_RATE_RANGE: (f64, f64) = (2.0, 40.0);
+
+/// How long the swell takes to settle on a new reading (time constant)
+pub const DEFAULT_SMOOTHING: Duration = Duration::from_secs(3);
+
+/// Share of the loudness left at the bottom of a swell
+const SWELL_FLOOR: f64 = 0.2;
+
+/// Loudness for the shallowest breath (the deepest gets 1.0)
+const MIN_AMPLITUDE: f64 = 0.3;
+
+/// Peak output level, leaving headroom for the track it's mixed under
+const HEADROOM: f64 = 0.5;
+
+/// Track level above which the pad ducks
+const DUCK_THRESHOLD: f64 = 0.01;
+
+/// How loud the pad stays while ducked
+const DUCK_GAIN: f64 = 0.15;
+
+/// Track level follower attack and release, in seconds
+const LEVEL_ATTACK: f64 = 0.01;
+const LEVEL_RELEASE: f64 = 0.5;
+
+/// How fast the duck gain moves, in seconds
+const DUCK_SMOOTHING: f64 = 0.2;
+
+/// Period of the slow drift in the pad's brightness, in seconds
+const DRIFT_PERIOD: f64 = 37.0;
+
+/// The character of the pad: which partials sound over the base frequency
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Texture {
+    /// Fundamental, fifth and a couple of soft harmonics
+    Warm,
+    
+    /// Thin and high - upper harmonics over a light fundamental
+    Airy,
+    
+    /// An octave down, with the fundamental and fifth above it
+    Deep,
+}
+
+impl Texture {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Texture::Warm => "warm",
+            Texture::Airy => "airy",
+            Texture::Deep => "deep",
+        }
+    }
+    
+    /// (frequency ratio, weight, detune in cents) of each partial
+    fn partials(&self) -> &'static [(f64, f64, f64)] {
+        match self {
+            Texture::Warm => &[(1.0, 1.0, 0.0), (1.0, 0.25, 4.0), (1.5, 0.35, -3.0), (2.0, 0.3, 2.0), (3.0, 0.12, -5.0)],
+            Texture::Airy => &[(1.0, 0.5, 0.0), (2.0, 0.6, 3.0), (4.0, 0.4, -4.0), (6.0, 0.2, 6.0)],
+            Texture::Deep => &[(0.5, 1.0, 0.0), (0.5, 0.25, -5.0), (1.0, 0.6, 3.0), (1.5, 0.25, -2.0)],
+        }
+    }
+}
+
+/// A value that eases toward its target instead of jumping
+#[derive(Debug, Clone, Copy)]
+struct Smoothed {
+    current: f64,
+    target: f64,
+}
+
+impl Smoothed {
+    fn new(value: f64) -> Self {
+        Self { current: value, target: value }
+    }
+    
+    fn step(&mut self, alpha: f64) -> f64 {
+        self.current += (self.target - self.current) * alpha;
+        self.current
+    }
+}
+
+/// One-pole smoothing coefficient for a time constant of `seconds`
+fn alpha(seconds: f64, sample_rate: f64) -> f64 {
+    if seconds <= 0.0 {
+        1.0
+    } else {
+        1.0 - (-1.0 / (seconds * sample_rate)).exp()
+    }
+}
+
+/// Generates the ambient pad, buffer by buffer (mono, -1.0 to 1.0)
+#[derive(Debug, Clone)]
+pub struct AmbientGenerator {
+    base_frequency: f64,
+    texture: Texture,
+    sample_rate: SampleRate,
+    smoothing: Duration,
+    
+    /// Swell period in seconds
+    period: Smoothed,
+    
+    /// Swell peak loudness, 0.0 to 1.0
+    amplitude: Smoothed,
+    
+    /// Oscillator phase of each partial, in cycles
+    phases: Vec<f64>,
+    
+    /// Position in the current swell, in cycles (0.0 = quietest)
+    swell_phase: f64,
+    
+    /// Position in the brightness drift, in cycles
+    drift_phase: f64,
+    
+    /// Envelope of the last sample generated
+    envelope: f64,
+    
+    /// Level of the track being mixed over the pad (see `fill_under`)
+    track_level: f64,
+    
+    /// Current gain of the pad under that track
+    duck_gain: f64,
+}
+
+impl AmbientGenerator {
+    /// A pad on `base_frequency` Hz, swelling at `DEFAULT_BREATHING_RATE` until fed
+    pub fn new(base_frequency: f64, texture: Texture) -> Self {
+        Self {
+            base_frequency,
+            texture,
+            sample_rate: SampleRate::CD44k,
+            smoothing: DEFAULT_SMOOTHING,
+            period: Smoothed::new(60.0 / DEFAULT_BREATHING_RATE),
+            amplitude: Smoothed::new(MIN_AMPLITUDE + (1.0 - MIN_AMPLITUDE) * 0.5),
+            phases: vec![0.0; texture.partials().len()],
+            swell_phase: 0.0,
+            drift_phase: 0.0,
+            envelope: 0.0,
+            track_level: 0.0,
+            duck_gain: 1.0,
+        }
+    }
+    
+    pub fn with_sample_rate(mut self, sample_rate: SampleRate) -> Self {
+        self.sample_rate = sample_rate;
+        self
+    }
+    
+    /// How quickly the swell follows the breathing (time constant; zero to follow instantly)
+    pub fn with_smoothing(mut self, smoothing: Duration) -> Self {
+        self.smoothing = smoothing;
+        self
+    }
+    
+    pub fn base_frequency(&self) -> f64 {
+        self.base_frequency
+    }
+    
+    pub fn texture(&self) -> Texture {
+        self.texture
+    }
+    
+    pub fn sample_rate(&self) -> SampleRate {
+        self.sample_rate
+    }
+    
+    /// Steer the swell from a breathing reading; anything else is ignored
+    ///
+    /// The rate (clamped to `BREATHING_RATE_RANGE`) sets the swell period -
+    /// one swell per breath - and the depth sets how loud the swell peaks.
+    /// Returns whether the reading was used.
+    pub fn feed(&mut self, reading: &SensorData) -> bool {
+        let SensorData::Breathing { rate, depth, .. } = reading else {
+            return false;
+        };
+        if !rate.is_finite() || !depth.is_finite() {
+            return false;
+        }
+        let rate = rate.clamp(BREATHING_RATE_RANGE.0, BREATHING_RATE_RANGE.1);
+        self.period.target = 60.0 / rate;
+        self.amplitude.target = MIN_AMPLITUDE + (1.0 - MIN_AMPLITUDE) * depth.clamp(0.0, 1.0);
+        true
+    }
+    
+    /// The swell period right now, in seconds (still easing toward the last reading)
+    pub fn swell_period(&self) -> f64 {
+        self.period.current
+    }
+    
+    /// The envelope of the last sample generated (0.0 to 1.0)
+    pub fn envelope(&self) -> f64 {
+        self.envelope
+    }
+    
+    /// The pad's gain under the track from the last `fill_under` (1.0 = not ducked)
+    pub fn duck_gain(&self) -> f64 {
+        self.duck_gain
+    }
+    
+    /// The next `count` samples
+    pub fn frames(&mut self, count: usize) -> Vec<f64> {
+        let mut out = vec![0.0; count];
+        self.fill(&mut out);
+        out
+    }
+    
+    /// Overwrite `out` with the next samples
+    pub fn fill(&mut self, out: &mut [f64]) {
+        let rate = self.sample_rate.as_f64();
+        let follow = alpha(self.smoothing.as_secs_f64(), rate);
+        let partials = self.texture.partials();
+        let total_weight: f64 = partials.iter().map(|&(_, weight, _)| weight).sum();
+        
+        for sample in out.iter_mut() {
+            let period = self.period.step(follow);
+            let amplitude = self.amplitude.step(follow);
+            
+            // Raised cosine swell that never quite goes silent
+            let swell = 0.5 - 0.5 * (2.0 * PI * self.swell_phase).cos();
+            self.envelope = amplitude * (SWELL_FLOOR + (1.0 - SWELL_FLOOR) * swell);
+            self.swell_phase = (self.swell_phase + 1.0 / (period * rate)).fract();
+            
+            // Upper partials brighten and fade over the slow drift
+            let drift = 0.5 - 0.5 * (2.0 * PI * self.drift_phase).cos();
+            self.drift_phase = (self.drift_phase + 1.0 / (DRIFT_PERIOD * rate)).fract();
+            
+            let mut tone = 0.0;
+            for (phase, &(ratio, weight, cents)) in self.phases.iter_mut().zip(partials) {
+                let brightness = if ratio > 1.0 { 0.6 + 0.4 * drift } else { 1.0 };
+                tone += weight * brightness * (2.0 * PI * *phase).sin();
+                let frequency = self.base_frequency * ratio * 2f64.powf(cents / 1200.0);
+                *phase = (*phase + frequency / rate).fract();
+            }
+            *sample = HEADROOM * self.envelope * tone / total_weight;
+        }
+    }
+    
+    /// Mix the pad under `track` into `out`, ducking it while the track plays
+    ///
+    /// `out` gets `track` plus the pad (only as many samples as both have);
+    /// pass silence when nothing is playing and the pad comes back up. The
+    /// duck is smoothed both ways so the pad fades rather than cuts.
+    pub fn fill_under(&mut self, track: &[f64], out: &mut [f64]) {
+        let len = track.len().min(out.len());
+        self.fill(&mut out[..len]);
+        
+        let rate = self.sample_rate.as_f64();
+        let (attack, release) = (alpha(LEVEL_ATTACK, rate), alpha(LEVEL_RELEASE, rate));
+        let duck = alpha(DUCK_SMOOTHING, rate);
+        for (sample, &playing) in out[..len].iter_mut().zip(track) {
+            let level = playing.abs();
+            self.track_level += (level - self.track_level) * if level > self.track_level { attack } else { release };
+            let target = if self.track_level > DUCK_THRESHOLD { DUCK_GAIN } else { 1.0 };
+            self.duck_gain += (target - self.duck_gain) * duck;
+            *sample = playing + *sample * self.duck_gain;
+        }
+    }
+}
+
+/// An ambient session: the generator plus a record of what it played and why
+///
+/// Breathing readings fed to the session steer the generator and are kept;
+/// everything it generates is kept as 16-bit PCM for `capture`.
+#[derive(Debug, Clone)]
+pub struct AmbientSession {
+    generator: AmbientGenerator,
+    breathing: Vec<SensorData>,
+    pcm: Vec<u8>,
+    started: u64,
+}
+
+/// What `AmbientSession::capture` stored
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AmbientCapture {
+    /// The generated audio
+    pub audio: [u8; 32],
+    
+    /// The breathing readings that drove it, in the order they arrived
+    pub breathing: Vec<[u8; 32]>,
+}
+
+impl AmbientSession {
+    pub fn new(generator: AmbientGenerator) -> Self {
+        Self {
+            generator,
+            breathing: Vec::new(),
+            pcm: Vec::new(),
+            started: chrono::Utc::now().timestamp() as u64,
+        }
+    }
+    
+    pub fn generator(&self) -> &AmbientGenerator {
+        &self.generator
+    }
+    
+    /// `AmbientGenerator::feed`, keeping the reading if it was used
+    pub fn feed(&mut self, reading: &SensorData) -> bool {
+        let used = self.generator.feed(reading);
+        if used {
+            self.breathing.push(reading.clone());
+        }
+        used
+    }
+    
+    /// `AmbientGenerator::fill`, recording what was generated
+    pub fn fill(&mut self, out: &mut [f64]) {
+        self.generator.fill(out);
+        self.record(out);
+    }
+    
+    /// `AmbientGenerator::fill_under`, recording the pad as mixed (without the track)
+    pub fn fill_under(&mut self, track: &[f64], out: &mut [f64]) {
+        self.generator.fill_under(track, out);
+        let pad: Vec<f64> = out.iter().zip(track).map(|(mixed, playing)| mixed - playing).collect();
+        self.record(&pad);
+    }
+    
+    /// Seconds of audio generated so far
+    pub fn duration(&self) -> f64 {
+        (self.pcm.len() / 2) as f64 / self.generator.sample_rate.as_f64()
+    }
+    
+    /// Store the generated audio and the breathing readings as memory group `group_id`
+    ///
+    /// The audio is stored as mono 16-bit PCM with the same `format` block
+    /// `AudioProcessor::store_audio` writes, so backfill and highlights treat
+    /// it like any other track; each reading is stored as its JSON.
+    pub fn capture(self, storage: &mut Mem8Lite, group_id: &str) -> Result<AmbientCapture> {
+        let generator = &self.generator;
+        let metadata = json!({
+            "name": format!("ambient-{}", generator.texture.name()),
+            "perspective": "ambient",
+            "format": {
+                "sample_rate": json_f64(generator.sample_rate.as_f64(), FloatCategory::Frequency),
+                "channels": 1,
+                "bit_depth": 16,
+                "is_float": false,
+                "wave_frequency": generator.sample_rate.frequency_preset(),
+            },
+            "ambient": {
+                "texture": generator.texture,
+                "base_frequency": json_f64(generator.base_frequency, FloatCategory::Frequency),
+                "duration": json_f64(self.duration(), FloatCategory::Duration),
+            },
+            "timestamp": self.started,
+        });
+        let audio = storage.store_linked(group_id, &self.pcm, Some(serde_json::to_vec(&metadata)?))?;
+        
+        let mut breathing = Vec::with_capacity(self.breathing.len());
+        for reading in &self.breathing {
+            let metadata = json!({
+                "perspective": "breathing",
+                "sensor": reading.id(),
+                "timestamp": reading.timestamp(),
+            });
+            breathing.push(storage.store_linked(group_id, &serde_json::to_vec(reading)?, Some(serde_json::to_vec(&metadata)?))?);
+        }
+        Ok(AmbientCapture { audio, breathing })
+    }
+    
+    fn record(&mut self, samples: &[f64]) {
+        self.pcm.reserve(samples.len() * 2);
+        for &sample in samples {
+            let value = (sample.clamp(-1.0, 1.0) * i16::MAX as f64).round() as i16;
+            self.pcm.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    
+    const RATE: f64 = 2000.0;
+    
+    fn breath(rate: f64, depth: f64, timestamp: u64) -> SensorData {
+        SensorData::Breathing {
+            id: "chest-radar".to_string(),
+            rate,
+            depth,
+            regularity: 0.9,
+            phase: 0.0,
+            timestamp,
+        }
+    }
+    
+    #[test]
+    fn test_swell_period_tracks_a_breathing_ramp() {
+        let mut pad = AmbientGenerator::new(110.0, Texture::Warm)
+            .with_sample_rate(SampleRate::Custom(RATE));
+        assert!(!pad.feed(&SensorData::Binary { id: "switch".to_string(), state: true, timestamp: 0 }));
+        
+        // 6 -> 12 breaths per minute over four minutes, a reading every second
+        let ramp = |second: f64| 6.0 + 6.0 * (second / 240.0).min(1.0);
+        let mut envelope = Vec::new();
+        for second in 0..300 {
+            assert!(pad.feed(&breath(ramp(second as f64), 0.8, second)));
+            for _ in 0..20 {
+                envelope.push(pad.envelope());
+                pad.frames((RATE * 0.05) as usize);
+            }
+        }
+        assert!((pad.swell_period() - 5.0).abs() < 0.01);
+        
+        // Troughs of the envelope: the quietest moment within 2s either side
+        let reach = 40;
+        let troughs: Vec<f64> = (reach..envelope.len() - reach)
+            .filter(|&i| envelope[i - reach..=i + reach].iter().all(|&other| envelope[i] <= other))
+            .map(|i| i as f64 * 0.05)
+            .collect();
+        assert!(troughs.len() > 30, "only {} troughs", troughs.len());
+        
+        for pair in troughs.windows(2) {
+            let period = pair[1] - pair[0];
+            let expected = 60.0 / ramp((pair[0] + pair[1]) / 2.0);
+            assert!((period - expected).abs() / expected < 0.1, "swell of {period:.2}s at {:.0}s, breathing says {expected:.2}s", pair[0]);
+        }
+    }
+    
+    #[test]
+    fn test_a_jumpy_sensor_does_not_make_the_pad_lurch() {
+        let mut pad = AmbientGenerator::new(220.0, Texture::Airy)
+            .with_sample_rate(SampleRate::Custom(RATE));
+        pad.feed(&breath(6.0, 0.2, 0));
+        pad.frames(RATE as usize * 10);
+        
+        // 6 to 40 breaths per minute in one reading: the swell eases over
+        pad.feed(&breath(200.0, 1.0, 10));
+        pad.frames(RATE as usize / 10);
+        assert!(pad.swell_period() > 9.0);
+        pad.frames(RATE as usize * 30);
+        assert!((pad.swell_period() - 60.0 / BREATHING_RATE_RANGE.1).abs() < 0.01);
+        
+        let mut last = pad.envelope();
+        for _ in 0..RATE as usize * 5 {
+            pad.frames(1);
+            assert!((pad.envelope() - last).abs() < 0.01);
+            last = pad.envelope();
+        }
+    }
+    
+    #[test]
+    fn test_pad_ducks_under_a_playing_track() {
+        let mut pad = AmbientGenerator::new(110.0, Texture::Deep)
+            .with_sample_rate(SampleRate::Custom(RATE));
+        let silence = vec![0.0; RATE as usize];
+        let track: Vec<f64> = (0..RATE as usize).map(|i| 0.5 * (2.0 * PI * 440.0 * i as f64 / RATE).sin()).collect();
+        let mut out = vec![0.0; RATE as usize];
+        
+        pad.fill_under(&silence, &mut out);
+        assert_eq!(pad.duck_gain(), 1.0);
+        
+        pad.fill_under(&track, &mut out);
+        assert!((pad.duck_gain() - DUCK_GAIN).abs() < 0.01);
+        
+        // And back up once the track stops
+        for _ in 0..3 {
+            pad.fill_under(&silence, &mut out);
+        }
+        assert!(pad.duck_gain() > 0.99);
+    }
+    
+    #[test]
+    fn test_capture_stores_the_session_as_a_memory_group() {
+        let dir = tempdir().unwrap();
+        let mut storage = Mem8Lite::new(dir.path().join("ambient.m8"), 1.618).unwrap();
+        let mut session = AmbientSession::new(AmbientGenerator::new(110.0, Texture::Warm)
+            .with_sample_rate(SampleRate::Custom(RATE)));
+        
+        let mut out = vec![0.0; RATE as usize];
+        for second in 0..3 {
+            assert!(session.feed(&breath(6.0, 0.5, 1_700_000_000 + second)));
+            session.fill(&mut out);
+        }
+        assert!((session.duration() - 3.0).abs() < 1e-9);
+        
+        let captured = session.capture(&mut storage, "sit-1").unwrap();
+        assert_eq!(captured.breathing.len(), 3);
+        let audio = storage.retrieve(&captured.audio).unwrap();
+        assert_eq!(audio.len(), 3 * RATE as usize * 2);
+        let metadata: serde_json::Value = serde_json::from_slice(&storage.get_metadata(&captured.audio).unwrap()).unwrap();
+        assert_eq!(metadata["format"]["sample_rate"], json!(2000.0));
+        assert_eq!(metadata["ambient"]["texture"], "warm");
+        
+        let reading: SensorData = serde_json::from_slice(&storage.retrieve(&captured.breathing[1]).unwrap()).unwrap();
+        assert_eq!(reading.timestamp(), 1_700_000_001);
+        
+        let members = storage.get_memory_group("sit-1").unwrap();
+        assert_eq!(members.len(), 4);
+        assert_eq!(members.iter().filter(|m| m.perspective == "breathing").count(), 3);
+        assert!(members.iter().any(|m| m.perspective == "ambient" && m.signature == captured.audio));
+    }
+}
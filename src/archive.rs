@@ -0,0 +1,361 @@
+//! Archives - some of a store's packets, packed up to move to another store
+//!
+//! `Mem8Lite::export` writes the chosen packets to a self-contained archive:
+//! their bytes, the metadata they were stored with plus any newer
+//! `set_metadata` version, the keys pointing at them and their TTL deadline.
+//! `Mem8Lite::import` adds them to another store with the same signatures.
+//!
+//! An archive is a 16 byte header (`M8ARCHIV`, the archive version and flags
+//! as u16s, and the entry count as a u32, all big-endian) followed by one
+//! entry per packet: a format byte, the body length (u64, big-endian), the
+//! bincode body and an 8 byte blake3 checksum over all three. Entries in a
+//! format this build doesn't know are skipped over and reported, so newer
+//! archives still import what they can.
+//!
+//! Archives are plaintext even when exported from an encrypted store -
+//! that's the point of them - so seal them yourself before they travel.
+//!
+//! Hue's memories, boxed up for the move. 📦
+
+use std::collections::HashSet;
+use std::io::{Read, Write};
+use serde::{Serialize, Deserialize};
+use anyhow::anyhow;
+use crate::error::{Mem8Error, Result};
+
+use crate::lite::{content_signature, Mem8Lite, SignatureMismatch};
+
+/// First bytes of every archive
+pub const ARCHIVE_MAGIC: &[u8; 8] = b"M8ARCHIV";
+
+/// Version of the archive header this build writes (and the newest it reads)
+pub const ARCHIVE_VERSION: u16 = 1;
+
+/// Entry holding one packet (what `export` writes)
+pub const ENTRY_FORMAT_PACKET: u8 = 1;
+
+/// Format byte and body length ahead of every entry body
+const ENTRY_HEADER_LEN: usize = 1 + 8;
+
+/// Bytes of blake3 checksum at the end of every entry
+const ENTRY_CHECKSUM_LEN: usize = 8;
+
+/// Bodies bigger than this aren't believed (a corrupt length, most likely)
+const MAX_ENTRY_LEN: u64 = 1 << 32;
+
+/// One packet in an archive
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct ArchivedPacket {
+    signature: [u8; 32],
+    data: Vec<u8>,
+    
+    /// Metadata the packet was stored with (the signature covers it)
+    metadata: Option<Vec<u8>>,
+    
+    /// Newest `set_metadata` version, if there's been one
+    metadata_update: Option<Vec<u8>>,
+    
+    /// Keys pointing at the packet
+    keys: Vec<String>,
+    
+    /// TTL deadline (unix seconds)
+    expires_at: Option<u64>,
+}
+
+/// What `Mem8Lite::import` did with an archive
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportReport {
+    /// Archive version from the header
+    pub archive_version: u16,
+    
+    /// Packets added to the store, in archive order
+    pub imported: Vec<[u8; 32]>,
+    
+    /// Packets skipped because the store already had them
+    pub duplicates: Vec<[u8; 32]>,
+    
+    /// Keys set to point at archived packets
+    pub keys: usize,
+    
+    /// Entries skipped because they're in a format this build doesn't read
+    pub version_mismatches: usize,
+}
+
+impl std::fmt::Display for ImportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} packets imported, {} already here, {} keys set",
+            self.imported.len(), self.duplicates.len(), self.keys)?;
+        if self.version_mismatches > 0 {
+            write!(f, ", {} entries skipped (unsupported format)", self.version_mismatches)?;
+        }
+        Ok(())
+    }
+}
+
+/// The archive was written by a newer, incompatible build
+///
+/// Comes back inside `Mem8Error`; use `err.downcast_ref::<UnsupportedArchive>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("archive version {version} is newer than this build reads ({})", ARCHIVE_VERSION)]
+pub struct UnsupportedArchive {
+    pub version: u16,
+}
+
+fn entry_checksum(format: u8, body: &[u8]) -> [u8; ENTRY_CHECKSUM_LEN] {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&[format]);
+    hasher.update(&(body.len() as u64).to_be_bytes());
+    hasher.update(body);
+    let mut checksum = [0u8; ENTRY_CHECKSUM_LEN];
+    checksum.copy_from_slice(&hasher.finalize().as_bytes()[..ENTRY_CHECKSUM_LEN]);
+    checksum
+}
+
+impl Mem8Lite {
+    /// Write the packets with `signatures` to `writer` as an archive, returning how many
+    ///
+    /// Fails before writing anything if a signature isn't live here (or has
+    /// expired); a signature listed twice is archived once.
+    pub fn export<W: Write>(&self, signatures: &[[u8; 32]], mut writer: W) -> Result<usize> {
+        let mut unique = Vec::with_capacity(signatures.len());
+        for signature in signatures {
+            if !unique.contains(signature) {
+                if !self.contains(signature) {
+                    return Err(anyhow!("Packet {} isn't in the store", hex::encode(signature)).into());
+                }
+                unique.push(*signature);
+            }
+        }
+        let count = u32::try_from(unique.len())
+            .map_err(|_| anyhow!("Too many packets for one archive"))?;
+        
+        let mut keys: Vec<(&str, [u8; 32])> = Vec::new();
+        for key in self.keys() {
+            if let Some(signature) = self.key_signature(key) {
+                keys.push((key, signature));
+            }
+        }
+        
+        writer.write_all(ARCHIVE_MAGIC)?;
+        writer.write_all(&ARCHIVE_VERSION.to_be_bytes())?;
+        writer.write_all(&0u16.to_be_bytes())?;
+        writer.write_all(&count.to_be_bytes())?;
+        
+        for signature in &unique {
+            let original = self.read_original_packet(signature)?;
+            if content_signature(&original.data, original.metadata.as_deref()) != *signature {
+                return Err(Mem8Error::from(SignatureMismatch { signature: *signature }));
+            }
+            let current = self.get_metadata(signature);
+            let entry = ArchivedPacket {
+                signature: *signature,
+                metadata_update: current.filter(|current| Some(current) != original.metadata.as_ref()),
+                data: original.data,
+                metadata: original.metadata,
+                keys: keys.iter()
+                    .filter(|(_, target)| target == signature)
+                    .map(|(key, _)| key.to_string())
+                    .collect(),
+                expires_at: self.expires_at(signature),
+            };
+            
+            let body = bincode::serialize(&entry)?;
+            writer.write_all(&[ENTRY_FORMAT_PACKET])?;
+            writer.write_all(&(body.len() as u64).to_be_bytes())?;
+            writer.write_all(&body)?;
+            writer.write_all(&entry_checksum(ENTRY_FORMAT_PACKET, &body))?;
+        }
+        writer.flush()?;
+        Ok(unique.len())
+    }
+    
+    /// Add the packets in an archive to this store, keeping their signatures
+    ///
+    /// Packets the store already has are skipped (their keys are still
+    /// set). The whole archive is read and checked before anything is
+    /// written, so a truncated or corrupt archive changes nothing; the
+    /// writes then go in as one transaction.
+    pub fn import<R: Read>(&mut self, mut reader: R) -> Result<ImportReport> {
+        let mut header = [0u8; 16];
+        reader.read_exact(&mut header)
+            .map_err(|_| anyhow!("Not a mem8 archive (too short)"))?;
+        if &header[..8] != ARCHIVE_MAGIC {
+            return Err(anyhow!("Not a mem8 archive").into());
+        }
+        let version = u16::from_be_bytes([header[8], header[9]]);
+        if version > ARCHIVE_VERSION {
+            return Err(Mem8Error::from(UnsupportedArchive { version }));
+        }
+        let count = u32::from_be_bytes([header[12], header[13], header[14], header[15]]);
+        
+        let mut report = ImportReport { archive_version: version, ..ImportReport::default() };
+        let mut packets = Vec::new();
+        for position in 0..count {
+            let mut entry_header = [0u8; ENTRY_HEADER_LEN];
+            reader.read_exact(&mut entry_header)
+                .map_err(|_| anyhow!("Truncated archive: {} of {} entries", position, count))?;
+            let format = entry_header[0];
+            let len = u64::from_be_bytes(entry_header[1..].try_into()?);
+            if len > MAX_ENTRY_LEN {
+                return Err(anyhow!("Archive entry {} claims {} bytes", position, len).into());
+            }
+            let mut body = vec![0u8; len as usize];
+            let mut checksum = [0u8; ENTRY_CHECKSUM_LEN];
+            reader.read_exact(&mut body)
+                .and_then(|_| reader.read_exact(&mut checksum))
+                .map_err(|_| anyhow!("Truncated archive: {} of {} entries", position, count))?;
+            if checksum != entry_checksum(format, &body) {
+                return Err(anyhow!("Checksum mismatch in archive entry {}", position).into());
+            }
+            if format != ENTRY_FORMAT_PACKET {
+                report.version_mismatches += 1;
+                continue;
+            }
+            
+            let packet: ArchivedPacket = bincode::deserialize(&body)?;
+            if content_signature(&packet.data, packet.metadata.as_deref()) != packet.signature {
+                return Err(Mem8Error::from(SignatureMismatch { signature: packet.signature }));
+            }
+            packets.push(packet);
+        }
+        
+        // Sort out what's new before the transaction borrows the store
+        let mut seen = HashSet::new();
+        let mut plan = Vec::with_capacity(packets.len());
+        for packet in packets {
+            if !seen.insert(packet.signature) {
+                continue;
+            }
+            let duplicate = self.contains(&packet.signature);
+            let keys: Vec<&String> = packet.keys.iter()
+                .filter(|key| self.key_signature(key) != Some(packet.signature))
+                .collect();
+            report.keys += keys.len();
+            let keys: Vec<String> = keys.into_iter().cloned().collect();
+            plan.push((packet, duplicate, keys));
+        }
+        
+        let mut deadlines = Vec::new();
+        let mut txn = self.begin_txn();
+        for (packet, duplicate, keys) in &plan {
+            // A keyed store of content that's already there only moves the key
+            for key in keys {
+                txn.store_keyed(key, &packet.data, packet.metadata.clone())?;
+            }
+            if *duplicate {
+                report.duplicates.push(packet.signature);
+                continue;
+            }
+            if keys.is_empty() {
+                txn.store(&packet.data, packet.metadata.clone());
+            }
+            if let Some(metadata) = &packet.metadata_update {
+                txn.set_metadata(&packet.signature, metadata.clone());
+            }
+            if let Some(deadline) = packet.expires_at {
+                deadlines.push((packet.signature, deadline));
+            }
+            report.imported.push(packet.signature);
+        }
+        txn.commit()?;
+        
+        for (signature, deadline) in deadlines {
+            self.set_deadline(&signature, deadline)?;
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+    use tempfile::tempdir;
+    use crate::cipher;
+    use crate::clock::ManualClock;
+    
+    #[test]
+    fn test_round_trip_between_stores_keeps_signatures() {
+        let dir = tempdir().unwrap();
+        let clock = Arc::new(ManualClock::new(1_700_000_000));
+        let key = [5u8; cipher::KEY_LEN];
+        let mut source = Mem8Lite::new_encrypted(dir.path().join("laptop.m8"), 1.618, &key).unwrap()
+            .with_clock(clock.clone());
+        let diary = source.store_keyed("diary/today", b"walked by the sea", Some(b"{\"mood\":\"calm\"}".to_vec())).unwrap();
+        source.set_metadata(&diary, b"{\"mood\":\"wonder\"}".to_vec()).unwrap();
+        let song = source.store(&(0..=255).collect::<Vec<u8>>(), None).unwrap();
+        let brief = source.store_with_ttl(b"parking spot 12", None, Duration::from_secs(3600)).unwrap();
+        let private = source.store(b"not for the other machine", None).unwrap();
+        
+        let mut archive = Vec::new();
+        assert_eq!(source.export(&[diary, song, brief, song], &mut archive).unwrap(), 3);
+        assert!(archive.windows(17).any(|w| w == b"walked by the sea")); // Plaintext, as documented
+        assert!(source.export(&[[7u8; 32]], Vec::new()).is_err());
+        
+        let target_path = dir.path().join("desktop.m8");
+        let mut target = Mem8Lite::new(&target_path, 1.0).unwrap().with_clock(clock.clone());
+        let already = target.store(&(0..=255).collect::<Vec<u8>>(), None).unwrap();
+        assert_eq!(already, song);
+        
+        let report = target.import(archive.as_slice()).unwrap();
+        assert_eq!(report.archive_version, ARCHIVE_VERSION);
+        assert_eq!(report.imported, vec![diary, brief]);
+        assert_eq!(report.duplicates, vec![song]);
+        assert_eq!((report.keys, report.version_mismatches), (1, 0));
+        drop(target);
+        
+        // Everything survives a reopen of the target, bit for bit
+        let mut target = Mem8Lite::new(&target_path, 1.0).unwrap().with_clock(clock.clone());
+        assert_eq!(target.key_signature("diary/today"), Some(diary));
+        assert_eq!(target.retrieve(&diary).unwrap(), b"walked by the sea");
+        assert_eq!(target.get_metadata(&diary).unwrap(), b"{\"mood\":\"wonder\"}");
+        assert_eq!(target.metadata_history(&diary).unwrap()[0].metadata.as_deref(), Some(&b"{\"mood\":\"calm\"}"[..]));
+        assert_eq!(target.expires_at(&brief), Some(1_700_003_600));
+        assert!(!target.contains(&private));
+        assert!(target.verify().is_clean());
+        
+        // Importing again only finds duplicates
+        let again = target.import(archive.as_slice()).unwrap();
+        assert!(again.imported.is_empty());
+        assert_eq!(again.duplicates.len(), 3);
+        assert_eq!(again.keys, 0);
+        
+        clock.advance(Duration::from_secs(3600));
+        assert!(target.is_expired(&brief));
+    }
+    
+    #[test]
+    fn test_bad_archives_change_nothing() {
+        let dir = tempdir().unwrap();
+        let mut source = Mem8Lite::new(dir.path().join("source.m8"), 1.618).unwrap();
+        let signatures: Vec<_> = (0..3u8).map(|i| source.store(&[i; 64], None).unwrap()).collect();
+        let mut archive = Vec::new();
+        source.export(&signatures, &mut archive).unwrap();
+        
+        let mut target = Mem8Lite::new(dir.path().join("target.m8"), 1.618).unwrap();
+        assert!(target.import(&archive[..archive.len() - 1]).is_err());
+        let mut corrupt = archive.clone();
+        corrupt[40] ^= 1;
+        assert!(target.import(corrupt.as_slice()).is_err());
+        assert!(target.import(&b"MEM8 is not an archive"[..]).is_err());
+        
+        let mut newer = archive.clone();
+        newer[8..10].copy_from_slice(&(ARCHIVE_VERSION + 1).to_be_bytes());
+        let err = target.import(newer.as_slice()).unwrap_err();
+        assert_eq!(err.downcast_ref::<UnsupportedArchive>().unwrap().version, ARCHIVE_VERSION + 1);
+        assert_eq!(target.iter().count(), 0);
+        
+        // An entry in a format from the future is skipped, the rest still import
+        let body = b"something new";
+        let mut mixed = archive.clone();
+        mixed[12..16].copy_from_slice(&4u32.to_be_bytes());
+        mixed.push(9);
+        mixed.extend_from_slice(&(body.len() as u64).to_be_bytes());
+        mixed.extend_from_slice(body);
+        mixed.extend_from_slice(&entry_checksum(9, body));
+        let report = target.import(mixed.as_slice()).unwrap();
+        assert_eq!((report.imported.len(), report.version_mismatches), (3, 1));
+        assert_eq!(target.retrieve(&signatures[2]).unwrap(), [2u8; 64]);
+    }
+}
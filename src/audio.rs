@@ -7,7 +7,12 @@
 //! Whether it's a phone recording or studio master, we'll find the wonder! 🎵
 
 use crate::marine::{MarineProcessor, MarineMetadata};
+use crate::marine::spectral::SpectralConfig;
+use crate::marine::analysis::{top_wonder_moments, estimate_beat_period, calculate_rms, calculate_dynamic_range, peak_level};
 use crate::lite::Mem8Lite;
+use crate::frequency::FrequencyPreset;
+use crate::float_format::{json_f64, FloatCategory};
+use crate::highlights::{extract_highlights, HighlightClip};
 use num_complex::Complex64;
 use anyhow::{Result, anyhow};
 use std::f64::consts::PI;
@@ -45,6 +50,19 @@ impl SampleRate {
         }
     }
     
+    /// Map a rate in Hz back to a named rate (or `Custom`)
+    pub fn from_hz(rate: f64) -> Self {
+        match rate as u32 {
+            16_000 => SampleRate::Phone16k,
+            22_050 => SampleRate::Broadcast22k,
+            44_100 => SampleRate::CD44k,
+            48_000 => SampleRate::DVD48k,
+            96_000 => SampleRate::Studio96k,
+            192_000 => SampleRate::Audiophile192k,
+            _ => SampleRate::Custom(rate),
+        }
+    }
+    
     /// Get optimal Marine processor settings for this sample rate
     pub fn optimal_marine_settings(&self) -> MarineProcessor {
         let mut processor = MarineProcessor::new();
@@ -77,24 +95,30 @@ impl SampleRate {
             SampleRate::Custom(_) => 0.7,
         };
         
+        // FFT windows of about 46 ms, so a bin is ~21 Hz at any rate
+        processor.spectral = Some(SpectralConfig {
+            fft_size: match self {
+                SampleRate::Phone16k => 1024,
+                SampleRate::Broadcast22k => 1024,
+                SampleRate::CD44k | SampleRate::DVD48k => 2048,
+                SampleRate::Studio96k => 4096,
+                SampleRate::Audiophile192k => 8192,
+                SampleRate::Custom(_) => SpectralConfig::for_sample_rate(rate).fft_size,
+            },
+            sample_rate: rate,
+        });
+        
         processor
     }
     
+    /// Get recommended frequency preset for wave encoding based on sample rate
+    pub fn frequency_preset(&self) -> FrequencyPreset {
+        FrequencyPreset::for_sample_rate(self)
+    }
+    
     /// Get recommended frequency for wave encoding based on sample rate
     pub fn wave_frequency(&self) -> f64 {
-        // Higher sample rates can handle higher wave frequencies
-        match self {
-            SampleRate::Phone16k => 0.5,        // Low frequency for compression
-            SampleRate::Broadcast22k => 0.8,
-            SampleRate::CD44k => 1.618,         // Golden ratio!
-            SampleRate::DVD48k => 2.0,
-            SampleRate::Studio96k => 3.14159,   // Pi for studio quality
-            SampleRate::Audiophile192k => 4.669, // Feigenbaum constant for chaos!
-            SampleRate::Custom(rate) => {
-                // Scale frequency with sample rate
-                1.618 * (rate / 44100.0).sqrt()
-            }
-        }
+        self.frequency_preset().hz()
     }
 }
 
@@ -166,8 +190,7 @@ pub struct AudioProcessor {
 impl AudioProcessor {
     /// Create a new audio processor
     pub fn new(format: AudioFormat, storage_path: &str) -> Result<Self> {
-        let wave_freq = format.sample_rate.wave_frequency();
-        let storage = Mem8Lite::new(storage_path, wave_freq)?;
+        let storage = Mem8Lite::new(storage_path, format.sample_rate.frequency_preset())?;
         let processor = format.sample_rate.optimal_marine_settings();
         
         Ok(Self {
@@ -179,102 +202,19 @@ impl AudioProcessor {
     
     /// Process raw PCM bytes based on format
     pub fn process_pcm(&mut self, pcm_data: &[u8]) -> Result<AudioAnalysis> {
-        // Convert PCM to normalized float samples
-        let samples = self.pcm_to_samples(pcm_data)?;
-        
-        // If stereo, mix to mono for Marine processing
-        let mono_samples = if self.format.channels == 2 {
-            self.stereo_to_mono(&samples)
-        } else {
-            samples
-        };
-        
-        // Convert to waves
-        let waves = self.samples_to_waves(&mono_samples);
-        
-        // Run Marine analysis
-        let peaks = self.processor.process_waves(&waves);
-        let metadata = self.processor.extract_metadata(&peaks);
-        
-        // Calculate additional audio-specific metrics
-        let analysis = AudioAnalysis {
-            marine_metadata: metadata,
-            format: self.format.clone(),
-            duration_seconds: mono_samples.len() as f64 / self.format.sample_rate.as_f64(),
-            rms_level: calculate_rms(&mono_samples),
-            peak_level: mono_samples.iter().fold(0.0, |a, &b| a.max(b.abs())),
-            dynamic_range: calculate_dynamic_range(&mono_samples),
-        };
-        
-        Ok(analysis)
-    }
-    
-    /// Convert PCM bytes to normalized float samples
-    fn pcm_to_samples(&self, pcm_data: &[u8]) -> Result<Vec<f64>> {
-        let bytes_per_sample = self.format.bit_depth / 8;
-        let total_samples = pcm_data.len() / bytes_per_sample;
-        let mut samples = Vec::with_capacity(total_samples);
-        
-        for i in 0..total_samples {
-            let offset = i * bytes_per_sample;
-            let sample_bytes = &pcm_data[offset..offset + bytes_per_sample];
-            
-            let sample = match (self.format.bit_depth, self.format.is_float) {
-                (8, false) => {
-                    // 8-bit unsigned
-                    let val = sample_bytes[0] as i8;
-                    val as f64 / 128.0
-                }
-                (16, false) => {
-                    // 16-bit signed
-                    let val = i16::from_le_bytes([sample_bytes[0], sample_bytes[1]]);
-                    val as f64 / 32768.0
-                }
-                (24, false) => {
-                    // 24-bit signed (stored in lower 3 bytes)
-                    let val = i32::from_le_bytes([sample_bytes[0], sample_bytes[1], sample_bytes[2], 0]);
-                    val as f64 / 8388608.0  // 2^23
-                }
-                (32, false) => {
-                    // 32-bit signed integer
-                    let val = i32::from_le_bytes(sample_bytes.try_into()?);
-                    val as f64 / 2147483648.0  // 2^31
-                }
-                (32, true) => {
-                    // 32-bit float
-                    f32::from_le_bytes(sample_bytes.try_into()?) as f64
-                }
-                _ => return Err(anyhow!("Unsupported bit depth: {}", self.format.bit_depth)),
-            };
-            
-            samples.push(sample);
-        }
-        
-        Ok(samples)
+        analyze_pcm(&self.format, &mut self.processor, pcm_data)
     }
     
-    /// Convert stereo to mono by averaging channels
-    fn stereo_to_mono(&self, samples: &[f64]) -> Vec<f64> {
-        samples.chunks(2)
-            .map(|chunk| (chunk[0] + chunk.get(1).unwrap_or(&0.0)) / 2.0)
-            .collect()
+    /// The underlying wave storage
+    pub fn storage(&self) -> &Mem8Lite {
+        &self.storage
     }
     
-    /// Convert samples to complex waves
-    fn samples_to_waves(&self, samples: &[f64]) -> Vec<Complex64> {
-        // Add frequency-dependent phase encoding
-        let base_freq = self.format.sample_rate.wave_frequency();
-        
-        samples.iter().enumerate().map(|(i, &sample)| {
-            // Phase encodes position with sample-rate awareness
-            let phase = 2.0 * PI * i as f64 / samples.len() as f64;
-            
-            // Magnitude encodes amplitude with quality-based scaling
-            let quality_factor = (self.format.sample_rate.as_f64() / 44100.0).sqrt();
-            let magnitude = sample.abs() * quality_factor;
-            
-            Complex64::from_polar(magnitude * base_freq, phase)
-        }).collect()
+    /// Highlights reel: the top wonder moments of a stored track
+    /// 
+    /// See [`crate::highlights::extract_highlights`].
+    pub fn extract_highlights(&self, signature: &[u8; 32], count: usize, clip_seconds: f64) -> Result<Vec<HighlightClip>> {
+        extract_highlights(&self.storage, signature, count, clip_seconds)
     }
     
     /// Store audio with Marine metadata
@@ -285,32 +225,99 @@ impl AudioProcessor {
         let metadata = serde_json::json!({
             "name": name,
             "format": {
-                "sample_rate": self.format.sample_rate.as_f64(),
+                "sample_rate": json_f64(self.format.sample_rate.as_f64(), FloatCategory::Frequency),
                 "channels": self.format.channels,
                 "bit_depth": self.format.bit_depth,
                 "is_float": self.format.is_float,
+                "wave_frequency": self.format.sample_rate.frequency_preset(),
             },
             "analysis": {
-                "duration": analysis.duration_seconds,
-                "rms_level": analysis.rms_level,
-                "peak_level": analysis.peak_level,
-                "dynamic_range": analysis.dynamic_range,
-            },
-            "marine": {
-                "peaks": analysis.marine_metadata.total_peaks,
-                "wonder": analysis.marine_metadata.wonder_count,
-                "salience": analysis.marine_metadata.average_salience,
-                "rhythm": analysis.marine_metadata.has_rhythm,
-                "emotion": analysis.marine_metadata.emotional_signature,
+                "duration": json_f64(analysis.duration_seconds, FloatCategory::Duration),
+                "rms_level": json_f64(analysis.rms_level, FloatCategory::Level),
+                "peak_level": json_f64(analysis.peak_level, FloatCategory::Level),
+                "dynamic_range": json_f64(analysis.dynamic_range, FloatCategory::Loudness),
             },
+            "marine": analysis.marine_json(),
             "timestamp": std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)?
                 .as_secs(),
         });
         
         let meta_bytes = serde_json::to_vec(&metadata)?;
-        self.storage.store(pcm_data, Some(meta_bytes))
+        Ok(self.storage.store(pcm_data, Some(meta_bytes))?)
+    }
+}
+
+/// Run the Marine analysis over PCM bytes in `format`
+pub(crate) fn analyze_pcm(format: &AudioFormat, processor: &mut MarineProcessor, pcm_data: &[u8]) -> Result<AudioAnalysis> {
+    // Convert PCM to normalized float samples
+    let samples = pcm_to_samples(format, pcm_data)?;
+    
+    // If stereo, mix to mono for Marine processing
+    let mono_samples = if format.channels == 2 {
+        stereo_to_mono(&samples)
+    } else {
+        samples
+    };
+    
+    // Convert to waves
+    let waves = samples_to_waves(format, &mono_samples);
+    
+    // Run Marine analysis (the spectrum from the samples, not the wave magnitudes)
+    let (peaks, mut metadata) = processor.analyze(&waves);
+    metadata.spectral = processor.spectral_features(&mono_samples);
+    let sample_rate = format.sample_rate.as_f64();
+    
+    // Calculate additional audio-specific metrics
+    Ok(AudioAnalysis {
+        marine_metadata: metadata,
+        format: format.clone(),
+        duration_seconds: mono_samples.len() as f64 / sample_rate,
+        rms_level: calculate_rms(&mono_samples),
+        peak_level: peak_level(&mono_samples),
+        dynamic_range: calculate_dynamic_range(&mono_samples),
+        wonder_moments: top_wonder_moments(&peaks, sample_rate),
+        beat_period_seconds: estimate_beat_period(&mono_samples, sample_rate),
+    })
+}
+
+/// Convert PCM bytes to normalized float samples
+fn pcm_to_samples(format: &AudioFormat, pcm_data: &[u8]) -> Result<Vec<f64>> {
+    let bytes_per_sample = format.bit_depth / 8;
+    let total_samples = pcm_data.len() / bytes_per_sample;
+    let mut samples = Vec::with_capacity(total_samples);
+    
+    for i in 0..total_samples {
+        let offset = i * bytes_per_sample;
+        let sample_bytes = &pcm_data[offset..offset + bytes_per_sample];
+        samples.push(decode_sample(sample_bytes, format)?);
     }
+    
+    Ok(samples)
+}
+
+/// Convert stereo to mono by averaging channels
+fn stereo_to_mono(samples: &[f64]) -> Vec<f64> {
+    samples.chunks(2)
+        .map(|chunk| (chunk[0] + chunk.get(1).unwrap_or(&0.0)) / 2.0)
+        .collect()
+}
+
+/// Convert samples to complex waves
+fn samples_to_waves(format: &AudioFormat, samples: &[f64]) -> Vec<Complex64> {
+    // Add frequency-dependent phase encoding
+    let base_freq = format.sample_rate.wave_frequency();
+    
+    samples.iter().enumerate().map(|(i, &sample)| {
+        // Phase encodes position with sample-rate awareness
+        let phase = 2.0 * PI * i as f64 / samples.len() as f64;
+        
+        // Magnitude encodes amplitude with quality-based scaling
+        let quality_factor = (format.sample_rate.as_f64() / 44100.0).sqrt();
+        let magnitude = sample.abs() * quality_factor;
+        
+        Complex64::from_polar(magnitude * base_freq, phase)
+    }).collect()
 }
 
 /// Complete audio analysis results
@@ -322,6 +329,33 @@ pub struct AudioAnalysis {
     pub rms_level: f64,
     pub peak_level: f64,
     pub dynamic_range: f64,
+    /// Strongest Marine peaks, at most one per quarter second, best first
+    pub wonder_moments: Vec<WonderMoment>,
+    /// Median spacing between onsets, if the track has a discernible beat
+    pub beat_period_seconds: Option<f64>,
+}
+
+pub use crate::marine::analysis::WonderMoment;
+
+impl AudioAnalysis {
+    /// The `marine` block of stored track metadata (what highlights read back)
+    pub fn marine_json(&self) -> serde_json::Value {
+        serde_json::json!({
+            "peaks": self.marine_metadata.total_peaks,
+            "wonder": self.marine_metadata.wonder_count,
+            "salience": json_f64(self.marine_metadata.average_salience, FloatCategory::Salience),
+            "rhythm": self.marine_metadata.has_rhythm,
+            "emotion": self.marine_metadata.emotional_signature,
+            "moments": self.wonder_moments.iter()
+                .map(|m| serde_json::json!({
+                    "frame": m.frame,
+                    "salience": json_f64(m.salience, FloatCategory::Salience),
+                }))
+                .collect::<Vec<_>>(),
+            "beat_period": self.beat_period_seconds
+                .map(|p| json_f64(p, FloatCategory::Duration)),
+        })
+    }
 }
 
 impl std::fmt::Display for AudioAnalysis {
@@ -339,31 +373,61 @@ impl std::fmt::Display for AudioAnalysis {
     }
 }
 
-/// Calculate RMS (Root Mean Square) level
-fn calculate_rms(samples: &[f64]) -> f64 {
-    let sum: f64 = samples.iter().map(|s| s * s).sum();
-    (sum / samples.len() as f64).sqrt()
+/// Decode one little-endian PCM sample to a normalized float
+pub(crate) fn decode_sample(sample_bytes: &[u8], format: &AudioFormat) -> Result<f64> {
+    let sample = match (format.bit_depth, format.is_float) {
+        (8, false) => {
+            // 8-bit unsigned
+            let val = sample_bytes[0] as i8;
+            val as f64 / 128.0
+        }
+        (16, false) => {
+            // 16-bit signed
+            let val = i16::from_le_bytes([sample_bytes[0], sample_bytes[1]]);
+            val as f64 / 32768.0
+        }
+        (24, false) => {
+            // 24-bit signed (stored in lower 3 bytes)
+            let val = i32::from_le_bytes([sample_bytes[0], sample_bytes[1], sample_bytes[2], 0]);
+            val as f64 / 8388608.0  // 2^23
+        }
+        (32, false) => {
+            // 32-bit signed integer
+            let val = i32::from_le_bytes(sample_bytes.try_into()?);
+            val as f64 / 2147483648.0  // 2^31
+        }
+        (32, true) => {
+            // 32-bit float
+            f32::from_le_bytes(sample_bytes.try_into()?) as f64
+        }
+        _ => return Err(anyhow!("Unsupported bit depth: {}", format.bit_depth)),
+    };
+    Ok(sample)
 }
 
-/// Calculate dynamic range in dB
-fn calculate_dynamic_range(samples: &[f64]) -> f64 {
-    if samples.is_empty() {
-        return 0.0;
+/// Encode a normalized float back into one little-endian PCM sample
+/// 
+/// The exact inverse of `decode_sample` for every supported format.
+pub(crate) fn encode_sample(sample: f64, format: &AudioFormat, out: &mut [u8]) -> Result<()> {
+    let sample = sample.clamp(-1.0, 1.0);
+    match (format.bit_depth, format.is_float) {
+        (8, false) => out[0] = ((sample * 128.0).round().min(127.0) as i8) as u8,
+        (16, false) => {
+            let val = (sample * 32768.0).round().min(i16::MAX as f64) as i16;
+            out.copy_from_slice(&val.to_le_bytes());
+        }
+        (24, false) => {
+            let val = (sample * 8388608.0).round().min(8388607.0) as i32;
+            out.copy_from_slice(&val.to_le_bytes()[..3]);
+        }
+        (32, false) => {
+            let val = (sample * 2147483648.0).round().min(i32::MAX as f64) as i32;
+            out.copy_from_slice(&val.to_le_bytes());
+        }
+        (32, true) => out.copy_from_slice(&(sample as f32).to_le_bytes()),
+        _ => return Err(anyhow!("Unsupported bit depth: {}", format.bit_depth)),
     }
-    
-    // Sort samples by absolute value
-    let mut sorted: Vec<f64> = samples.iter().map(|s| s.abs()).collect();
-    sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
-    
-    // Get 95th percentile (loud) and 5th percentile (quiet)
-    let loud_idx = (sorted.len() as f64 * 0.95) as usize;
-    let quiet_idx = (sorted.len() as f64 * 0.05) as usize;
-    
-    let loud = sorted[loud_idx.min(sorted.len() - 1)];
-    let quiet = sorted[quiet_idx].max(0.000001);  // Avoid log(0)
-    
-    // Dynamic range in dB
-    20.0 * (loud / quiet).log10()
+    Ok(())
 }
 
 /// Fun fact generator based on sample rate
@@ -405,11 +469,4 @@ mod tests {
         // Higher sample rates should have higher wave frequencies
         assert!(SampleRate::Studio96k.wave_frequency() > SampleRate::CD44k.wave_frequency());
     }
-    
-    #[test]
-    fn test_rms_calculation() {
-        let samples = vec![0.5, -0.5, 0.5, -0.5];
-        let rms = calculate_rms(&samples);
-        assert!((rms - 0.5).abs() < 0.001);
-    }
 }
\ No newline at end of file
@@ -2,6 +2,8 @@
 //! 
 //! Handles FLAC, WAV, and raw PCM files with automatic format detection.
 //! FLAC is our favorite - lossless compression with metadata preservation!
+//! With the `containers` feature, the audio tracks of MP4 and Matroska files
+//! (screen recordings and the like) load too - see `probe_audio`.
 //!
 //! Hue, this is where we make any audio format dance in waves!
 //! Trisha says FLAC files are like compressed accounting records - 
@@ -10,7 +12,8 @@
 use std::path::Path;
 use std::fs::File;
 use std::io::{BufReader, Read};
-use anyhow::{Result, anyhow};
+use anyhow::anyhow;
+use crate::error::Result;
 use crate::audio::{AudioFormat, SampleRate};
 
 /// Supported audio file formats
@@ -22,6 +25,10 @@ pub enum AudioFileFormat {
     Wav,
     /// Raw PCM data (when you know what you're doing)
     RawPcm(AudioFormat),
+    /// MP4 / QuickTime container - we only listen to its audio tracks
+    Mp4,
+    /// Matroska / WebM container - same deal
+    Matroska,
 }
 
 /// Loaded audio data with format information
@@ -63,6 +70,48 @@ pub struct AudioMetadata {
     
     /// Any comments (perfect for memory annotations!)
     pub comment: Option<String>,
+    
+    /// Length in seconds, when the container says
+    #[serde(default)]
+    pub duration_secs: Option<f64>,
+    
+    /// Language of the audio track (ISO 639 code, from containers)
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+/// One audio track in a file, as reported by `probe_audio`
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct AudioTrack {
+    /// Track id to pass to `load_audio_track` (1 for single-track files)
+    pub id: u32,
+    
+    /// Codec name ("pcm", "flac", "aac", ...)
+    pub codec: String,
+    
+    pub sample_rate: u32,
+    pub channels: usize,
+    
+    /// Bits per sample, when the codec has such a thing
+    pub bit_depth: Option<usize>,
+    
+    pub language: Option<String>,
+    pub duration_secs: Option<f64>,
+    
+    /// Can this build decode it?
+    pub decodable: bool,
+}
+
+/// What `probe_audio` found in a file, without decoding any samples
+#[derive(Debug, Clone)]
+pub struct AudioProbe {
+    pub file_format: AudioFileFormat,
+    
+    /// File- or container-level metadata
+    pub metadata: Option<AudioMetadata>,
+    
+    /// Audio tracks, in file order (video streams are never listed)
+    pub tracks: Vec<AudioTrack>,
 }
 
 /// Load audio from any supported file format
@@ -70,49 +119,159 @@ pub struct AudioMetadata {
 /// Automatically detects format from file extension and magic bytes.
 /// Returns normalized samples ready for Marine processing!
 pub fn load_audio_file<P: AsRef<Path>>(path: P) -> Result<LoadedAudio> {
+    load_audio_track(path, None)
+}
+
+/// Load one audio track of a file (`None` = the first one we can decode)
+/// 
+/// Only containers have more than one track; for everything else the only
+/// valid id is 1. `probe_audio` lists what's there.
+pub fn load_audio_track<P: AsRef<Path>>(path: P, track: Option<u32>) -> Result<LoadedAudio> {
     let path = path.as_ref();
+    let format = detect_format(path)?;
     
-    // Try to detect format from extension first
-    let format = match path.extension().and_then(|e| e.to_str()) {
-        Some("flac") | Some("FLAC") => AudioFileFormat::Flac,
-        Some("wav") | Some("WAV") => AudioFileFormat::Wav,
+    if !matches!(format, AudioFileFormat::Mp4 | AudioFileFormat::Matroska) && track.is_some_and(|id| id != 1) {
+        return Err(anyhow!("{} has a single audio track (id 1)", path.display()).into());
+    }
+    match format {
+        AudioFileFormat::Flac => load_flac(path),
+        AudioFileFormat::Wav => load_wav(path),
+        AudioFileFormat::RawPcm(fmt) => load_raw_pcm(path, fmt),
+        container => load_container(path, &container, track),
+    }
+}
+
+/// List a file's audio tracks and metadata without decoding them
+pub fn probe_audio<P: AsRef<Path>>(path: P) -> Result<AudioProbe> {
+    let path = path.as_ref();
+    let file_format = detect_format(path)?;
+    
+    let single = |format: &AudioFormat, codec: &str, frames: Option<u64>, metadata: Option<AudioMetadata>| AudioProbe {
+        file_format: file_format.clone(),
+        tracks: vec![AudioTrack {
+            id: 1,
+            codec: codec.to_string(),
+            sample_rate: format.sample_rate.as_f64() as u32,
+            channels: format.channels,
+            bit_depth: Some(format.bit_depth),
+            language: None,
+            duration_secs: frames.map(|n| n as f64 / format.sample_rate.as_f64()),
+            decodable: true,
+        }],
+        metadata,
+    };
+    
+    match &file_format {
+        AudioFileFormat::Flac => {
+            let mut reader = claxon::FlacReader::open(path)?;
+            let info = reader.streaminfo();
+            let format = AudioFormat {
+                sample_rate: SampleRate::from_hz(info.sample_rate as f64),
+                channels: info.channels as usize,
+                bit_depth: info.bits_per_sample as usize,
+                is_float: false,
+            };
+            Ok(single(&format, "flac", info.samples, extract_flac_metadata(&mut reader)))
+        }
+        AudioFileFormat::Wav => {
+            let reader = hound::WavReader::open(path)?;
+            let spec = reader.spec();
+            let format = AudioFormat {
+                sample_rate: SampleRate::from_hz(spec.sample_rate as f64),
+                channels: spec.channels as usize,
+                bit_depth: spec.bits_per_sample as usize,
+                is_float: spec.sample_format == hound::SampleFormat::Float,
+            };
+            Ok(single(&format, "pcm", Some(reader.duration() as u64), None))
+        }
+        AudioFileFormat::RawPcm(format) => {
+            let frame_bytes = (format.channels * format.bit_depth / 8).max(1) as u64;
+            let frames = std::fs::metadata(path)?.len() / frame_bytes;
+            Ok(single(format, "pcm", Some(frames), None))
+        }
+        container => probe_container(path, container),
+    }
+}
+
+/// Work out a file's format from its extension, falling back to its magic bytes
+fn detect_format(path: &Path) -> Result<AudioFileFormat> {
+    let format = match path.extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+        Some("flac") => AudioFileFormat::Flac,
+        Some("wav") => AudioFileFormat::Wav,
         Some("pcm") | Some("raw") => {
             // For raw PCM, assume CD quality
             AudioFileFormat::RawPcm(AudioFormat::cd_quality())
         }
+        Some("mp4") | Some("m4a") | Some("m4v") | Some("mov") => AudioFileFormat::Mp4,
+        Some("mkv") | Some("mka") | Some("webm") => AudioFileFormat::Matroska,
         _ => {
             // Try to detect from file contents
             detect_format_from_file(path)?
         }
     };
-    
-    match format {
-        AudioFileFormat::Flac => load_flac(path),
-        AudioFileFormat::Wav => load_wav(path),
-        AudioFileFormat::RawPcm(fmt) => load_raw_pcm(path, fmt),
-    }
+    Ok(format)
 }
 
 /// Detect format from file magic bytes
 fn detect_format_from_file(path: &Path) -> Result<AudioFileFormat> {
     let mut file = File::open(path)?;
-    let mut magic = [0u8; 4];
-    file.read_exact(&mut magic)?;
+    let mut magic = [0u8; 8];
+    let filled = file.read(&mut magic)?;
     
-    match &magic {
+    match &magic[..filled.min(4)] {
         b"fLaC" => Ok(AudioFileFormat::Flac),
         b"RIFF" => Ok(AudioFileFormat::Wav),
-        _ => Err(anyhow!("Unknown audio format. Try .flac, .wav, or .pcm"))
+        _ if filled == 8 && &magic[4..] == b"ftyp" => Ok(AudioFileFormat::Mp4),
+        [0x1A, 0x45, 0xDF, 0xA3] => Ok(AudioFileFormat::Matroska),
+        _ => Err(anyhow!("Unknown audio format. Try .flac, .wav, .pcm, .mp4, or .mkv").into())
     }
 }
 
+#[cfg(feature = "containers")]
+fn load_container(path: &Path, _format: &AudioFileFormat, track: Option<u32>) -> Result<LoadedAudio> {
+    let file = BufReader::new(File::open(path)?);
+    Ok(crate::container::Container::open(file)?.decode_track(track)?)
+}
+
+#[cfg(feature = "containers")]
+fn probe_container(path: &Path, _format: &AudioFileFormat) -> Result<AudioProbe> {
+    let container = crate::container::Container::open(BufReader::new(File::open(path)?))?;
+    Ok(AudioProbe {
+        file_format: container.kind().file_format(),
+        metadata: Some(container.metadata().clone()),
+        tracks: container.tracks(),
+    })
+}
+
+#[cfg(not(feature = "containers"))]
+fn load_container(path: &Path, format: &AudioFileFormat, _track: Option<u32>) -> Result<LoadedAudio> {
+    Err(containers_disabled(path, format))
+}
+
+#[cfg(not(feature = "containers"))]
+fn probe_container(path: &Path, format: &AudioFileFormat) -> Result<AudioProbe> {
+    Err(containers_disabled(path, format))
+}
+
+#[cfg(not(feature = "containers"))]
+fn containers_disabled(path: &Path, format: &AudioFileFormat) -> crate::error::Mem8Error {
+    crate::capabilities::capabilities()
+        .require("containers", &format!("Reading {:?} audio from {}", format, path.display()))
+        .expect_err("built without the `containers` feature")
+        .into()
+}
+
 /// Load a FLAC file
 /// 
 /// FLAC is perfect for our wave storage - it's already thinking in terms
 /// of compression and preservation, just like MEM8!
 pub fn load_flac(path: &Path) -> Result<LoadedAudio> {
-    let file = File::open(path)?;
-    let mut reader = claxon::FlacReader::new(BufReader::new(file))?;
+    decode_flac(BufReader::new(File::open(path)?))
+}
+
+/// Decode a whole FLAC stream (`fLaC` marker, metadata, frames) from any reader
+pub fn decode_flac<R: Read>(input: R) -> Result<LoadedAudio> {
+    let mut reader = claxon::FlacReader::new(input)?;
     
     // Get stream info
     let streaminfo = reader.streaminfo();
@@ -165,7 +324,7 @@ pub fn load_flac(path: &Path) -> Result<LoadedAudio> {
 }
 
 /// Extract metadata from FLAC file
-fn extract_flac_metadata(reader: &mut claxon::FlacReader<BufReader<File>>) -> Option<AudioMetadata> {
+fn extract_flac_metadata<R: Read>(reader: &mut claxon::FlacReader<R>) -> Option<AudioMetadata> {
     // Get Vorbis comments (FLAC metadata)
     let tags = reader.tags();
     
@@ -177,6 +336,8 @@ fn extract_flac_metadata(reader: &mut claxon::FlacReader<BufReader<File>>) -> Op
         year: None,
         genre: None,
         comment: None,
+        duration_secs: None,
+        language: None,
     };
     
     for (key, value) in tags {
@@ -252,8 +413,21 @@ pub fn load_raw_pcm(path: &Path, format: AudioFormat) -> Result<LoadedAudio> {
     let mut buffer = Vec::new();
     file.read_to_end(&mut buffer)?;
     
+    Ok(LoadedAudio {
+        samples: decode_pcm(&buffer, &format)?,
+        format: format.clone(),
+        file_format: AudioFileFormat::RawPcm(format),
+        metadata: None,
+    })
+}
+
+/// Little-endian PCM bytes to normalized samples
+pub fn decode_pcm(buffer: &[u8], format: &AudioFormat) -> Result<Vec<f64>> {
     // Convert bytes to samples based on format
     let bytes_per_sample = format.bit_depth / 8;
+    if bytes_per_sample == 0 {
+        return Err(anyhow!("Unsupported PCM format").into());
+    }
     let num_samples = buffer.len() / bytes_per_sample;
     let mut samples = Vec::with_capacity(num_samples);
     
@@ -277,18 +451,13 @@ pub fn load_raw_pcm(path: &Path, format: AudioFormat) -> Result<LoadedAudio> {
             (32, true) => {
                 f32::from_le_bytes(sample_bytes.try_into()?) as f64
             }
-            _ => return Err(anyhow!("Unsupported PCM format")),
+            _ => return Err(anyhow!("Unsupported PCM format").into()),
         };
         
         samples.push(sample);
     }
     
-    Ok(LoadedAudio {
-        samples,
-        format: format.clone(),
-        file_format: AudioFileFormat::RawPcm(format),
-        metadata: None,
-    })
+    Ok(samples)
 }
 
 /// Fun facts about audio formats
@@ -300,6 +469,8 @@ pub fn format_fun_fact(format: &AudioFileFormat) -> &'static str {
             "🌊 WAV: The original wave format - uncompressed and honest!",
         AudioFileFormat::RawPcm(_) => 
             "🎛️ Raw PCM: Pure samples, no headers - for when you speak fluent audio!",
+        AudioFileFormat::Mp4 | AudioFileFormat::Matroska => 
+            "🎬 Video container: we skip the pictures and keep the waves!",
     }
 }
 
@@ -327,6 +498,12 @@ impl std::fmt::Display for AudioMetadata {
         if let Some(ref comment) = self.comment {
             write!(f, "  Comment: {}\n", comment)?;
         }
+        if let Some(duration) = self.duration_secs {
+            writeln!(f, "  Duration: {:.1}s", duration)?;
+        }
+        if let Some(ref language) = self.language {
+            writeln!(f, "  Language: {}", language)?;
+        }
         Ok(())
     }
 }
\ No newline at end of file
@@ -0,0 +1,388 @@
+//! Marine backfill - give old packets the analysis newer ones get at store time
+//!
+//! Packets stored before analysis existed (or with it switched off) have no
+//! `marine` block in their metadata, so salience search and highlights can't
+//! see them. `Mem8Lite::backfill_analysis` walks the store, analyses whatever
+//! matches a filter and attaches the results with `set_metadata` - the
+//! packets themselves (and their signatures) are never touched.
+//!
+//! Runs are resumable: progress is saved to `<store>.backfill` after every
+//! batch, so a run stopped by a `limit`, Ctrl-C or a crash picks up where it
+//! left off. That makes it safe to run in slices from cron or a service timer.
+//!
+//! Hue's rule: no memory left behind! 🛟
+
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use anyhow::anyhow;
+
+use crate::audio::analyze_pcm;
+use crate::error::Result;
+use crate::float_format::{json_f64, FloatCategory};
+use crate::highlights::format_from_metadata;
+use crate::lite::{backfill_cursor_path, encode_to_waves, Mem8Lite, StoredPacket};
+use crate::marine::MarineConfig;
+use crate::shutdown;
+
+/// Packets analysed per worker between progress saves
+const BATCH_PER_WORKER: usize = 8;
+
+/// What a packet holds, as far as the filter is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PacketKind {
+    /// PCM with a `format` block in its metadata (e.g. from `store_audio`)
+    Audio,
+    
+    /// Valid UTF-8
+    Text,
+    
+    /// Anything else
+    Binary,
+}
+
+impl std::str::FromStr for PacketKind {
+    type Err = anyhow::Error;
+    
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        match s {
+            "audio" => Ok(PacketKind::Audio),
+            "text" => Ok(PacketKind::Text),
+            "binary" => Ok(PacketKind::Binary),
+            other => Err(anyhow!("Unknown packet kind: {}", other)),
+        }
+    }
+}
+
+/// Which packets a backfill should analyse
+#[derive(Debug, Clone, Default)]
+pub struct BackfillFilter {
+    /// Only packets whose metadata has this `namespace`
+    pub namespace: Option<String>,
+    
+    /// Only packets stored at or after this time (unix seconds)
+    pub since: Option<u64>,
+    
+    /// Only packets stored before this time (unix seconds)
+    pub until: Option<u64>,
+    
+    /// Only packets of this kind
+    pub kind: Option<PacketKind>,
+    
+    /// Stop after analysing this many packets (the rest wait for the next run)
+    pub limit: Option<usize>,
+}
+
+impl BackfillFilter {
+    /// Every packet that hasn't been analysed yet
+    pub fn all() -> Self {
+        Self::default()
+    }
+    
+    pub fn with_namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+    
+    pub fn with_kind(mut self, kind: PacketKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+    
+    pub fn with_limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+    
+    fn matches(&self, packet: &StoredPacket, meta: &Value) -> bool {
+        self.since.is_none_or(|since| packet.timestamp >= since)
+            && self.until.is_none_or(|until| packet.timestamp < until)
+            && self.namespace.as_deref().is_none_or(|ns| meta["namespace"].as_str() == Some(ns))
+            && self.kind.is_none_or(|kind| kind_of(packet, meta) == kind)
+    }
+}
+
+/// What a backfill run did
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BackfillReport {
+    /// Packets looked at in this run
+    pub examined: usize,
+    
+    /// Packets that got Marine metadata
+    pub analysed: usize,
+    
+    /// Packets that already had it
+    pub already_analysed: usize,
+    
+    /// Packets the filter left out
+    pub filtered_out: usize,
+    
+    /// Packets with metadata that isn't a JSON object (left untouched)
+    pub opaque_metadata: usize,
+    
+    /// Packets that couldn't be read or analysed, with the reason
+    pub failed: Vec<(String, String)>,
+    
+    /// False if the run stopped early; the next run resumes from there
+    pub complete: bool,
+}
+
+impl std::fmt::Display for BackfillReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "🛟 Backfill {}:", if self.complete { "complete" } else { "paused (run again to resume)" })?;
+        writeln!(f, "  Examined: {}", self.examined)?;
+        writeln!(f, "  Analysed: {}", self.analysed)?;
+        writeln!(f, "  Already analysed: {}", self.already_analysed)?;
+        writeln!(f, "  Filtered out: {}", self.filtered_out)?;
+        writeln!(f, "  Opaque metadata (skipped): {}", self.opaque_metadata)?;
+        for (signature, reason) in &self.failed {
+            writeln!(f, "  ⚠️ {}: {}", signature, reason)?;
+        }
+        Ok(())
+    }
+}
+
+/// Persisted progress of an unfinished run
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+struct BackfillCursor {
+    /// Store size when the run started - later packets are the backfill's own rewrites
+    end: u64,
+    
+    /// Offset of the first packet not yet looked at
+    next: u64,
+}
+
+impl BackfillCursor {
+    fn load(path: &Path) -> Result<Option<Self>> {
+        match std::fs::read(path) {
+            Ok(raw) => Ok(Some(serde_json::from_slice(&raw)?)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+    
+    fn save(&self, path: &Path) -> Result<()> {
+        let temp = path.with_extension("backfill.tmp");
+        std::fs::write(&temp, serde_json::to_vec(self)?)?;
+        std::fs::rename(&temp, path)?;
+        Ok(())
+    }
+}
+
+impl Mem8Lite {
+    /// Attach Marine analysis to stored packets that don't have it yet
+    ///
+    /// Matching packets are decoded and analysed on up to `parallelism`
+    /// threads, then re-pointed at metadata with a `marine` block added (the
+    /// same block `AudioProcessor::store_audio` writes, so highlights work on
+    /// backfilled audio too). Packets whose metadata isn't JSON are skipped
+    /// rather than overwritten. Resumes an unfinished run automatically.
+    pub fn backfill_analysis(
+        &mut self,
+        filter: &BackfillFilter,
+        marine_config: &MarineConfig,
+        parallelism: usize,
+    ) -> Result<BackfillReport> {
+        let cursor_path = backfill_cursor_path(self.path());
+        let mut cursor = BackfillCursor::load(&cursor_path)?
+            .unwrap_or(BackfillCursor { end: self.stats().total_size, next: 0 });
+        let workers = parallelism.max(1);
+        
+        let pending: Vec<([u8; 32], u64)> = self.offsets_in_file_order().into_iter()
+            .filter(|&(_, offset)| offset >= cursor.next && offset < cursor.end)
+            .collect();
+        
+        let mut report = BackfillReport::default();
+        let mut stopped = false;
+        for batch in pending.chunks(workers * BATCH_PER_WORKER) {
+            if stopped || shutdown::requested() {
+                stopped = true;
+                break;
+            }
+            
+            // Read sequentially (the store file has one cursor), analyse in parallel
+            let mut todo = Vec::new();
+            for &(signature, offset) in batch {
+                if filter.limit.is_some_and(|limit| report.analysed + todo.len() >= limit) {
+                    stopped = true;
+                    break;
+                }
+                report.examined += 1;
+                match self.read_packet(&signature) {
+                    Ok(packet) => match parse_metadata(&packet) {
+                        None => report.opaque_metadata += 1,
+                        Some(meta) if meta.get("marine").is_some() => report.already_analysed += 1,
+                        Some(meta) if !filter.matches(&packet, &meta) => report.filtered_out += 1,
+                        Some(meta) => todo.push((packet, meta)),
+                    },
+                    Err(e) => report.failed.push((hex::encode(signature), e.to_string())),
+                }
+                cursor.next = offset + 1;
+            }
+            
+            let results = analyse_all(&todo, marine_config, workers);
+            for ((packet, mut meta), result) in todo.into_iter().zip(results) {
+                match result {
+                    Ok(marine) => {
+                        meta["marine"] = marine;
+                        self.set_metadata(&packet.signature, serde_json::to_vec(&meta)?)?;
+                        report.analysed += 1;
+                    }
+                    Err(e) => report.failed.push((hex::encode(packet.signature), e.to_string())),
+                }
+            }
+            cursor.save(&cursor_path)?;
+        }
+        
+        if stopped {
+            cursor.save(&cursor_path)?;
+            return Ok(report);
+        }
+        if cursor_path.exists() {
+            std::fs::remove_file(&cursor_path)?;
+        }
+        report.complete = true;
+        Ok(report)
+    }
+}
+
+/// Metadata as a JSON object (an empty one if there's none), or None if it's something else
+fn parse_metadata(packet: &StoredPacket) -> Option<Value> {
+    match &packet.metadata {
+        None => Some(json!({})),
+        Some(raw) => serde_json::from_slice::<Value>(raw).ok().filter(Value::is_object),
+    }
+}
+
+fn kind_of(packet: &StoredPacket, meta: &Value) -> PacketKind {
+    if meta["format"]["sample_rate"].is_number() {
+        PacketKind::Audio
+    } else if std::str::from_utf8(&packet.data).is_ok() {
+        PacketKind::Text
+    } else {
+        PacketKind::Binary
+    }
+}
+
+/// Analyse packets on `workers` threads, keeping their order
+fn analyse_all(todo: &[(StoredPacket, Value)], config: &MarineConfig, workers: usize) -> Vec<Result<Value>> {
+    if todo.is_empty() {
+        return Vec::new();
+    }
+    let per_worker = todo.len().div_ceil(workers);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = todo.chunks(per_worker)
+            .map(|chunk| scope.spawn(move || {
+                chunk.iter().map(|(packet, meta)| analyse(packet, meta, config)).collect::<Vec<_>>()
+            }))
+            .collect();
+        handles.into_iter()
+            .flat_map(|handle| handle.join().unwrap_or_else(|_| {
+                vec![Err(anyhow!("Marine worker panicked").into())]
+            }))
+            .collect()
+    })
+}
+
+/// The `marine` metadata block for one packet
+///
+/// Audio is analysed as PCM in its recorded format; anything else through
+/// its waves, like `marine::integration::enhance_wave_packet`.
+fn analyse(packet: &StoredPacket, meta: &Value, config: &MarineConfig) -> Result<Value> {
+    let mut processor = config.build();
+    let mut marine = if kind_of(packet, meta) == PacketKind::Audio {
+        let format = format_from_metadata(&meta["format"])?;
+        analyze_pcm(&format, &mut processor, &packet.data)?.marine_json()
+    } else {
+        let peaks = processor.process_waves(&encode_to_waves(&packet.data, packet.frequency));
+        let metadata = processor.extract_metadata(&peaks);
+        json!({
+            "peaks": metadata.total_peaks,
+            "wonder": metadata.wonder_count,
+            "salience": json_f64(metadata.average_salience, FloatCategory::Salience),
+            "rhythm": metadata.has_rhythm,
+            "emotion": metadata.emotional_signature,
+        })
+    };
+    marine["backfilled"] = json!(true);
+    Ok(marine)
+}
+
+/// Packets whose recorded Marine salience is at least `min_salience`, most salient first
+pub fn salient_packets(storage: &Mem8Lite, min_salience: f64) -> Vec<([u8; 32], f64)> {
+    let mut found: Vec<([u8; 32], f64)> = storage.iter()
+        .filter_map(|summary| {
+            let meta: Value = serde_json::from_slice(&storage.get_metadata(&summary.signature)?).ok()?;
+            let salience = meta["marine"]["salience"].as_f64()?;
+            (salience >= min_salience).then_some((summary.signature, salience))
+        })
+        .collect();
+    found.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    
+    /// Bytes with plenty of local maxima for Marine to find
+    fn spiky(seed: usize) -> Vec<u8> {
+        (0..4096).map(|i| ((i * 37 + seed) % 256) as u8).collect()
+    }
+    
+    #[test]
+    fn test_backfill_makes_old_packets_searchable() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("old.m8");
+        let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+        
+        let plain: Vec<_> = (0..5).map(|i| storage.store(&spiky(i), None).unwrap()).collect();
+        let opaque = storage.store(&spiky(9), Some(b"user:hue".to_vec())).unwrap();
+        assert!(salient_packets(&storage, 0.0).is_empty());
+        
+        let report = storage.backfill_analysis(&BackfillFilter::all(), &MarineConfig::default(), 3).unwrap();
+        assert!(report.complete);
+        assert_eq!((report.analysed, report.opaque_metadata), (5, 1));
+        
+        // Found by salience now, under the very same signatures
+        let found: Vec<_> = salient_packets(&storage, 0.0).into_iter().map(|(sig, _)| sig).collect();
+        assert_eq!(found.len(), 5);
+        for (i, sig) in plain.iter().enumerate() {
+            assert!(found.contains(sig));
+            assert_eq!(storage.retrieve(sig).unwrap(), spiky(i));
+        }
+        assert_eq!(storage.get_metadata(&opaque), Some(b"user:hue".to_vec()));
+        
+        // Nothing left to do, and it all survives a reopen
+        drop(storage);
+        let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+        let again = storage.backfill_analysis(&BackfillFilter::all(), &MarineConfig::default(), 3).unwrap();
+        assert_eq!((again.analysed, again.already_analysed), (0, 5));
+        assert_eq!(salient_packets(&storage, 0.0).len(), 5);
+    }
+    
+    #[test]
+    fn test_backfill_resumes_and_filters() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("resume.m8");
+        let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+        for i in 0..4 {
+            storage.store(&spiky(i), Some(serde_json::to_vec(&json!({"namespace": "audio"})).unwrap())).unwrap();
+        }
+        storage.store_string("not in the namespace").unwrap();
+        
+        let filter = BackfillFilter::all().with_namespace("audio");
+        let first = storage.backfill_analysis(&filter.clone().with_limit(3), &MarineConfig::default(), 1).unwrap();
+        assert!(!first.complete);
+        assert_eq!(first.analysed, 3);
+        assert!(backfill_cursor_path(&path).exists());
+        
+        let rest = storage.backfill_analysis(&filter, &MarineConfig::default(), 1).unwrap();
+        assert!(rest.complete);
+        assert_eq!((rest.analysed, rest.filtered_out), (1, 1));
+        assert!(!backfill_cursor_path(&path).exists());
+        assert_eq!(salient_packets(&storage, 0.0).len(), 4);
+    }
+}
@@ -0,0 +1,681 @@
+//! `mem8` - command line companion for MEM8-FS Lite stores
+//!
+//! Usage:
+//!   mem8 caps [--json] [STORE]   Show build capabilities (and a store's, if given)
+//!   mem8 ls [--frequency F] STORE  List every packet in a Mem8Lite store, oldest first
+//!   mem8 cat [--frequency F] STORE SIGNATURE
+//!                                Write a packet's data to stdout
+//!   mem8 put [--dry-run] [--quota BYTES] [--compression C] [--frequency F] STORE FILE [PATH]
+//!                                Store FILE (at PATH, in a Mem8Fs tree); `--dry-run` only
+//!                                prints what it would cost, `--quota` refuses writes that
+//!                                would grow the store past BYTES on disk
+//!   mem8 report [--days N] [--json] HISTORY
+//!                                Per-activity listening report from a mood history file
+//!   mem8 highlights STORE SIGNATURE [--count N] [--seconds S] [--store] [--frequency F]
+//!                                Cut clips around a track's top wonder moments
+//!
+//!   mem8 backfill [--namespace NS] [--kind K] [--since T] [--until T] [--limit N] [--jobs N] [--frequency F] STORE
+//!                                Add Marine analysis to packets stored without it (resumable)
+//!   mem8 verify [--frequency F] STORE
+//!                                Check every packet against its signature; fails if any don't
+//!   mem8 snapshot ROOT ID        Snapshot a Mem8Fs tree
+//!   mem8 snapshots [--json] ROOT List a Mem8Fs tree's snapshots
+//!   mem8 keygen KEYFILE          Create an ed25519 signing key for `export --sign`
+//!   mem8 export [--sign KEYFILE] ROOT DIR SLICE
+//!                                Write every file under DIR to SLICE; `--sign` also writes
+//!                                a signed manifest to `<SLICE>.manifest.json`
+//!   mem8 import [--verify MANIFEST [--signer PUBKEY] [--force]] ROOT SLICE
+//!                                Merge SLICE into ROOT; `--verify` refuses it unless every
+//!                                file matches the signed MANIFEST (`--force` merges the ones
+//!                                that do), `--signer` also insists on who signed it
+//!   mem8 mount [--snapshot ID] ROOT MOUNTPOINT
+//!                                Mount a Mem8Fs tree (or one of its snapshots, read-only)
+//!                                until Ctrl-C; needs the `fuse-mount` feature
+//!   mem8 daemon [--idle-timeout SECS] [--frequency F] STORE
+//!                                Keep a Mem8Lite store open on `<STORE>.sock` until Ctrl-C
+//!                                or SECS (default 600) without clients; while it runs,
+//!                                `caps`, `ls`, `put` and `cat` on STORE go through it
+//!
+//! STORE may be a Mem8Lite `.m8` file or a Mem8Fs root directory (one with `.mem8/`);
+//! ROOT is always the latter.
+//! HISTORY is the `<store>.mood.json` file the MCP server keeps next to its store.
+//! F is a preset name (`golden-ratio`, `pi`, `feigenbaum`) or a frequency in Hz.
+//! K is `audio`, `text` or `binary`; T is a unix timestamp.
+//! C is `none`, `zstd`, `zstd:<level>`, `adaptive` or `adaptive:<low>-<high>`.
+
+use std::io::Write;
+use std::path::Path;
+use anyhow::{Result, anyhow};
+use mem8_fs_lite::{capabilities, highlights, parse_signature, signing, CompressionMode, FrequencyPreset, Mem8Fs, Mem8Lite, SizeEstimate, SliceManifest, StoreCapabilities};
+use mem8_fs_lite::activity_report::ReportRange;
+use mem8_fs_lite::backfill::BackfillFilter;
+use mem8_fs_lite::marine::MarineConfig;
+use mem8_fs_lite::mood_engine::MoodEngine;
+#[cfg(unix)]
+use mem8_fs_lite::daemon::DaemonClient;
+
+const USAGE: &str = "Usage: mem8 caps [--json] [STORE]
+       mem8 ls [--frequency F] STORE
+       mem8 cat [--frequency F] STORE SIGNATURE
+       mem8 put [--dry-run] [--quota BYTES] [--compression C] [--frequency F] STORE FILE [PATH]
+       mem8 report [--days N] [--json] HISTORY
+       mem8 highlights STORE SIGNATURE [--count N] [--seconds S] [--store] [--frequency F]
+       mem8 backfill [--namespace NS] [--kind K] [--since T] [--until T] [--limit N] [--jobs N] [--frequency F] STORE
+       mem8 verify [--frequency F] STORE
+       mem8 snapshot ROOT ID
+       mem8 snapshots [--json] ROOT
+       mem8 keygen KEYFILE
+       mem8 export [--sign KEYFILE] ROOT DIR SLICE
+       mem8 import [--verify MANIFEST [--signer PUBKEY] [--force]] ROOT SLICE
+       mem8 mount [--snapshot ID] ROOT MOUNTPOINT
+       mem8 daemon [--idle-timeout SECS] [--frequency F] STORE";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Err(e) = run(&args) {
+        eprintln!("mem8: {}", e);
+        std::process::exit(1);
+    }
+}
+
+fn run(args: &[String]) -> Result<()> {
+    match args.first().map(String::as_str) {
+        Some("caps") => caps(&args[1..]),
+        Some("ls") => ls(&args[1..]),
+        Some("cat") => cat(&args[1..]),
+        Some("put") => put(&args[1..]),
+        Some("report") => report(&args[1..]),
+        Some("highlights") => highlights(&args[1..]),
+        Some("backfill") => backfill(&args[1..]),
+        Some("verify") => verify(&args[1..]),
+        Some("snapshot") => snapshot(&args[1..]),
+        Some("snapshots") => snapshots(&args[1..]),
+        Some("keygen") => keygen(&args[1..]),
+        Some("export") => export(&args[1..]),
+        Some("import") => import(&args[1..]),
+        Some("mount") => mount(&args[1..]),
+        Some("daemon") => daemon(&args[1..]),
+        Some("-h") | Some("--help") | None => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        Some(other) => Err(anyhow!("unknown command `{}`\n{}", other, USAGE)),
+    }
+}
+
+/// `mem8 caps` - what this build and (optionally) a store support
+fn caps(args: &[String]) -> Result<()> {
+    let json = args.iter().any(|a| a == "--json");
+    let store = args.iter().find(|a| !a.starts_with("--"));
+    
+    let build = capabilities();
+    let store_caps = store.map(|path| open_store_capabilities(Path::new(path))).transpose()?;
+    
+    if json {
+        let report = serde_json::json!({
+            "build": build,
+            "store": store_caps,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", build);
+        if let Some(store_caps) = store_caps {
+            print!("{}", store_caps);
+        }
+    }
+    Ok(())
+}
+
+/// `mem8 ls` - everything in a store, sorted by timestamp
+fn ls(args: &[String]) -> Result<()> {
+    let mut frequency = FrequencyPreset::GoldenRatio;
+    let mut path = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--frequency" => frequency = option_value(iter.next(), "--frequency")?,
+            _ => path = Some(arg),
+        }
+    }
+    let path = path.ok_or_else(|| anyhow!("ls needs a STORE\n{}", USAGE))?;
+    
+    let mut packets: Vec<_> = match daemon_for(Path::new(path)) {
+        #[cfg(unix)]
+        Some(mut daemon) => daemon.list()?,
+        _ => Mem8Lite::new(path, frequency)?.iter().collect(),
+    };
+    packets.sort_by_key(|p| p.timestamp);
+    
+    for packet in &packets {
+        let stored = chrono::DateTime::from_timestamp(packet.timestamp as i64, 0)
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| packet.timestamp.to_string());
+        println!("{}  {}  {:>10} bytes  {:>6} meta",
+            hex::encode(packet.signature), stored, packet.data_len, packet.metadata_len);
+    }
+    println!("🌊 {} packets", packets.len());
+    Ok(())
+}
+
+/// `mem8 cat` - one packet's data, byte for byte
+fn cat(args: &[String]) -> Result<()> {
+    let mut frequency = FrequencyPreset::GoldenRatio;
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--frequency" => frequency = option_value(iter.next(), "--frequency")?,
+            _ => positional.push(arg),
+        }
+    }
+    let (path, signature) = match positional.as_slice() {
+        [path, signature] => (Path::new(path.as_str()), parse_signature(signature)?),
+        _ => return Err(anyhow!("cat needs STORE and SIGNATURE\n{}", USAGE)),
+    };
+    
+    let data = match daemon_for(path) {
+        #[cfg(unix)]
+        Some(mut daemon) => daemon.cat(&signature)?,
+        _ => Mem8Lite::new(path, frequency)?.retrieve(&signature)?,
+    };
+    let mut stdout = std::io::stdout().lock();
+    stdout.write_all(&data)?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// `mem8 put` - store a file, or say what storing it would cost
+fn put(args: &[String]) -> Result<()> {
+    let mut dry_run = false;
+    let mut quota: Option<u64> = None;
+    let mut compression = CompressionMode::None;
+    let mut frequency = FrequencyPreset::GoldenRatio;
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--dry-run" => dry_run = true,
+            "--quota" => quota = Some(option_value(iter.next(), "--quota")?),
+            "--compression" => compression = option_value(iter.next(), "--compression")?,
+            "--frequency" => frequency = option_value(iter.next(), "--frequency")?,
+            _ => positional.push(arg),
+        }
+    }
+    let (store, file, fs_path) = match positional.as_slice() {
+        [store, file] => (Path::new(store.as_str()), file, None),
+        [store, file, fs_path] => (Path::new(store.as_str()), file, Some(fs_path)),
+        _ => return Err(anyhow!("put needs STORE and FILE\n{}", USAGE)),
+    };
+    let data = std::fs::read(file)?;
+    
+    if store.join(".mem8").is_dir() {
+        let fs_path = match fs_path {
+            Some(fs_path) => fs_path.to_string(),
+            None => format!("/{}", Path::new(file).file_name().and_then(|n| n.to_str())
+                .ok_or_else(|| anyhow!("put needs a PATH for {}\n{}", file, USAGE))?),
+        };
+        let fs = Mem8Fs::new(store)?;
+        let estimate = fs.estimate_write(&fs_path, &data)?;
+        if check_quota(store, &estimate, quota, dry_run)? {
+            let signature = fs.write(&fs_path, &data)?;
+            println!("📝 {} → {}", fs_path, hex::encode(signature));
+        }
+        Ok(fs.close()?)
+    } else {
+        if fs_path.is_some() {
+            return Err(anyhow!("PATH only applies to Mem8Fs trees\n{}", USAGE));
+        }
+        #[cfg(unix)]
+        if let Some(mut daemon) = daemon_for(store) {
+            let estimate = daemon.estimate(&data, compression)?;
+            if check_quota(store, &estimate, quota, dry_run)? {
+                let signature = daemon.put(&data, compression)?;
+                println!("📝 {} → {}", file, hex::encode(signature));
+            }
+            return Ok(());
+        }
+        let mut storage = Mem8Lite::new(store, frequency)?.with_compression(compression);
+        let estimate = storage.estimate_stored_size(&data, 0);
+        if check_quota(store, &estimate, quota, dry_run)? {
+            let signature = storage.store(&data, None)?;
+            println!("📝 {} → {}", file, hex::encode(signature));
+        }
+        Ok(storage.close()?)
+    }
+}
+
+/// Print the estimate and decide whether `put` goes ahead
+fn check_quota(store: &Path, estimate: &SizeEstimate, quota: Option<u64>, dry_run: bool) -> Result<bool> {
+    let used = disk_usage(store)?;
+    let compression = estimate.compression
+        .map(|c| format!(", {:.2}× compressed", c.ratio))
+        .unwrap_or_default();
+    println!("💾 {} bytes ({} packet + {} index, {}{}{}); store uses {} bytes",
+        estimate.total(), estimate.packet_bytes, estimate.index_bytes, estimate.codec, compression,
+        if estimate.sampled { ", sampled" } else { "" }, used);
+    
+    if let Some(quota) = quota {
+        if used + estimate.total() > quota {
+            return Err(anyhow!("writing {} bytes would take the store to {} of its {} byte quota",
+                estimate.total(), used + estimate.total(), quota));
+        }
+    }
+    Ok(!dry_run)
+}
+
+/// Bytes a store occupies: a Mem8Fs tree's `.mem8/`, or a Mem8Lite file and its indexes
+fn disk_usage(store: &Path) -> Result<u64> {
+    fn tree_size(dir: &Path) -> Result<u64> {
+        let mut total = 0;
+        for entry in std::fs::read_dir(dir)? {
+            let entry = entry?;
+            let meta = entry.metadata()?;
+            total += if meta.is_dir() { tree_size(&entry.path())? } else { meta.len() };
+        }
+        Ok(total)
+    }
+    
+    if store.join(".mem8").is_dir() {
+        return tree_size(&store.join(".mem8"));
+    }
+    let mut index = store.as_os_str().to_owned();
+    index.push(".idx");
+    let mut timestamps = store.as_os_str().to_owned();
+    timestamps.push(".tsidx");
+    let mut wonder = store.as_os_str().to_owned();
+    wonder.push(".wonder");
+    Ok([store.as_os_str(), &index, &timestamps, &wonder].iter().filter_map(|p| std::fs::metadata(p).ok()).map(|m| m.len()).sum())
+}
+
+/// `mem8 report` - what worked for each activity lately
+fn report(args: &[String]) -> Result<()> {
+    let mut days = 14;
+    let mut json = false;
+    let mut path = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--days" => days = option_value(iter.next(), "--days")?,
+            "--json" => json = true,
+            _ => path = Some(arg),
+        }
+    }
+    let path = path.ok_or_else(|| anyhow!("report needs a HISTORY file\n{}", USAGE))?;
+    if !Path::new(path).is_file() {
+        return Err(anyhow!("no mood history at {}", path));
+    }
+    
+    let mut engine = MoodEngine::create_hue_profile();
+    engine.load_history(path)?;
+    let report = engine.activity_report(ReportRange::last_days(days));
+    
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print!("{}", report);
+    }
+    Ok(())
+}
+
+/// `mem8 backfill` - analyse packets that were stored without Marine metadata
+fn backfill(args: &[String]) -> Result<()> {
+    let mut frequency = FrequencyPreset::GoldenRatio;
+    let mut filter = BackfillFilter::all();
+    let mut jobs = std::thread::available_parallelism().map_or(1, |n| n.get());
+    let mut path = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--namespace" => filter.namespace = Some(option_value(iter.next(), "--namespace")?),
+            "--kind" => filter.kind = Some(option_value(iter.next(), "--kind")?),
+            "--since" => filter.since = Some(option_value(iter.next(), "--since")?),
+            "--until" => filter.until = Some(option_value(iter.next(), "--until")?),
+            "--limit" => filter.limit = Some(option_value(iter.next(), "--limit")?),
+            "--jobs" => jobs = option_value(iter.next(), "--jobs")?,
+            "--frequency" => frequency = option_value(iter.next(), "--frequency")?,
+            _ => path = Some(arg),
+        }
+    }
+    let path = path.ok_or_else(|| anyhow!("backfill needs a STORE\n{}", USAGE))?;
+    if !Path::new(path).is_file() {
+        return Err(anyhow!("no Mem8Lite store at {}", path));
+    }
+    
+    check_not_served(Path::new(path))?;
+    
+    // Ctrl-C stops at the next batch; running again resumes
+    mem8_fs_lite::shutdown::install_handlers();
+    let mut storage = Mem8Lite::new(path, frequency)?;
+    let report = storage.backfill_analysis(&filter, &MarineConfig::default(), jobs)?;
+    storage.close()?;
+    print!("{}", report);
+    Ok(())
+}
+
+/// `mem8 verify` - integrity check of every packet in a Mem8Lite store
+fn verify(args: &[String]) -> Result<()> {
+    let mut frequency = FrequencyPreset::GoldenRatio;
+    let mut path = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--frequency" => frequency = option_value(iter.next(), "--frequency")?,
+            _ => path = Some(arg),
+        }
+    }
+    let path = path.ok_or_else(|| anyhow!("verify needs a STORE\n{}", USAGE))?;
+    if !Path::new(path).is_file() {
+        return Err(anyhow!("no Mem8Lite store at {}", path));
+    }
+    
+    check_not_served(Path::new(path))?;
+    
+    let storage = Mem8Lite::new(path, frequency)?;
+    let report = storage.verify_with_progress(|checked, total| {
+        if checked % 1000 == 0 || checked == total {
+            eprint!("\rverified {}/{}", checked, total);
+        }
+    });
+    if report.total > 0 {
+        eprintln!();
+    }
+    storage.close()?;
+    println!("{}", report);
+    if !report.corrupt.is_empty() {
+        return Err(anyhow!("{} corrupt packets in {}", report.corrupt.len(), path));
+    }
+    Ok(())
+}
+
+/// `mem8 highlights` - list (and optionally store) a track's highlight clips
+fn highlights(args: &[String]) -> Result<()> {
+    let mut positional = Vec::new();
+    let mut count = 3;
+    let mut seconds = 10.0;
+    let mut frequency = FrequencyPreset::GoldenRatio;
+    let mut store = false;
+    
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--count" => count = option_value(iter.next(), "--count")?,
+            "--seconds" => seconds = option_value(iter.next(), "--seconds")?,
+            "--frequency" => frequency = option_value(iter.next(), "--frequency")?,
+            "--store" => store = true,
+            _ => positional.push(arg),
+        }
+    }
+    let (path, signature) = match positional.as_slice() {
+        [path, signature] => (path, parse_signature(signature)?),
+        _ => return Err(anyhow!("highlights needs STORE and SIGNATURE\n{}", USAGE)),
+    };
+    
+    check_not_served(Path::new(path.as_str()))?;
+    let mut storage = Mem8Lite::new(path, frequency)?;
+    let clips = highlights::extract_highlights(&storage, &signature, count, seconds)?;
+    let stored = if store {
+        highlights::store_highlights(&mut storage, &signature, &clips)?
+    } else {
+        Vec::new()
+    };
+    
+    for (rank, clip) in clips.iter().enumerate() {
+        print!("#{} {:.2}s-{:.2}s (peak {:.2}s, salience {:.3})",
+            rank + 1,
+            clip.start_seconds,
+            clip.start_seconds + clip.duration_seconds,
+            clip.peak_seconds,
+            clip.salience);
+        match stored.get(rank) {
+            Some(sig) => println!(" -> {}", hex::encode(sig)),
+            None => println!(),
+        }
+    }
+    if clips.is_empty() {
+        println!("No wonder moments found 🌊");
+    }
+    Ok(())
+}
+
+/// `mem8 snapshot` - capture a Mem8Fs tree as it is now
+fn snapshot(args: &[String]) -> Result<()> {
+    let (root, id) = match args {
+        [root, id] => (root, id),
+        _ => return Err(anyhow!("snapshot needs ROOT and ID\n{}", USAGE)),
+    };
+    let fs = open_fs(Path::new(root))?;
+    let info = fs.create_snapshot(id)?;
+    println!("📸 {}: {} files, {} bytes", info.id, info.files, info.total_size);
+    Ok(fs.close()?)
+}
+
+/// `mem8 snapshots` - what snapshots a Mem8Fs tree has
+fn snapshots(args: &[String]) -> Result<()> {
+    let json = args.iter().any(|a| a == "--json");
+    let root = args.iter().find(|a| !a.starts_with("--"))
+        .ok_or_else(|| anyhow!("snapshots needs a ROOT\n{}", USAGE))?;
+    let snapshots = open_fs(Path::new(root))?.list_snapshots()?;
+    
+    if json {
+        println!("{}", serde_json::to_string_pretty(&snapshots)?);
+        return Ok(());
+    }
+    for info in &snapshots {
+        let created = chrono::DateTime::from_timestamp(info.created as i64, 0)
+            .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| info.created.to_string());
+        println!("{}  {}  {:>6} files  {:>10} bytes", info.id, created, info.files, info.total_size);
+    }
+    println!("📸 {} snapshots", snapshots.len());
+    Ok(())
+}
+
+/// `mem8 keygen` - a new signing key for `export --sign`
+fn keygen(args: &[String]) -> Result<()> {
+    let [path] = args else {
+        return Err(anyhow!("keygen needs a KEYFILE\n{}", USAGE));
+    };
+    let key = signing::generate_keyfile(path)?;
+    println!("🔑 {} (public key {})", path, hex::encode(key.verifying_key().as_bytes()));
+    Ok(())
+}
+
+/// `mem8 export` - pack part of a Mem8Fs tree into a slice to share
+fn export(args: &[String]) -> Result<()> {
+    let mut keyfile: Option<String> = None;
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--sign" => keyfile = Some(option_value(iter.next(), "--sign")?),
+            _ => positional.push(arg),
+        }
+    }
+    let (root, dir, slice_path) = match positional.as_slice() {
+        [root, dir, slice] => (root, dir, slice),
+        _ => return Err(anyhow!("export needs ROOT, DIR and SLICE\n{}", USAGE)),
+    };
+    // Load the key first, so a bad keyfile doesn't leave a slice behind
+    let key = keyfile.map(signing::load_keyfile).transpose()?;
+    
+    let fs = open_fs(Path::new(root))?;
+    let file = std::io::BufWriter::new(std::fs::File::create(slice_path)?);
+    let mut manifest = match fs.export_slice(dir, file) {
+        Ok(manifest) => manifest,
+        Err(e) => {
+            let _ = std::fs::remove_file(slice_path);
+            return Err(e.into());
+        }
+    };
+    println!("📦 {}: {} files, {} bytes", slice_path, manifest.entries.len(),
+        manifest.entries.iter().map(|e| e.size).sum::<u64>());
+    if let Some(key) = key {
+        manifest.sign(&key);
+        let manifest_path = format!("{}.manifest.json", slice_path);
+        std::fs::write(&manifest_path, manifest.to_json()?)?;
+        println!("✍️ {} (signed by {})", manifest_path, manifest.signer);
+    }
+    Ok(fs.close()?)
+}
+
+/// `mem8 import` - merge a slice, checking it against its manifest first if asked
+fn import(args: &[String]) -> Result<()> {
+    let mut manifest_path: Option<String> = None;
+    let mut signer: Option<String> = None;
+    let mut force = false;
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--verify" => manifest_path = Some(option_value(iter.next(), "--verify")?),
+            "--signer" => signer = Some(option_value(iter.next(), "--signer")?),
+            "--force" => force = true,
+            _ => positional.push(arg),
+        }
+    }
+    let (root, slice_path) = match positional.as_slice() {
+        [root, slice] => (root, slice),
+        _ => return Err(anyhow!("import needs ROOT and SLICE\n{}", USAGE)),
+    };
+    if manifest_path.is_none() && (signer.is_some() || force) {
+        return Err(anyhow!("--signer and --force only make sense with --verify\n{}", USAGE));
+    }
+    let slice = std::io::BufReader::new(std::fs::File::open(slice_path)?);
+    
+    let fs = Mem8Fs::new(root)?;
+    match manifest_path {
+        Some(manifest_path) => {
+            let manifest = SliceManifest::from_json(&std::fs::read(&manifest_path)?)?;
+            if let Some(expected) = signer {
+                let signed_by = manifest.verify_signature()?;
+                if signed_by != signing::parse_verifying_key(&expected)? {
+                    return Err(anyhow!("{} was signed by {}, not {}", manifest_path, manifest.signer, expected));
+                }
+            }
+            let report = fs.verify_import_with(&manifest, slice, force)?;
+            println!("📥 {}", report);
+        }
+        None => {
+            let imported = fs.import_slice(slice)?;
+            println!("📥 {} files imported (unverified)", imported.len());
+        }
+    }
+    Ok(fs.close()?)
+}
+
+/// `mem8 mount` - serve a Mem8Fs tree (or a snapshot of it) over FUSE
+#[cfg(feature = "fuse-mount")]
+fn mount(args: &[String]) -> Result<()> {
+    use mem8_fs_lite::mount::Mem8FuseFs;
+    
+    let mut snapshot: Option<String> = None;
+    let mut positional = Vec::new();
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--snapshot" => snapshot = Some(option_value(iter.next(), "--snapshot")?),
+            _ => positional.push(arg),
+        }
+    }
+    let (root, mountpoint) = match positional.as_slice() {
+        [root, mountpoint] => (root, mountpoint),
+        _ => return Err(anyhow!("mount needs ROOT and MOUNTPOINT\n{}", USAGE)),
+    };
+    
+    let fs = std::sync::Arc::new(open_fs(Path::new(root))?);
+    let fuse = match &snapshot {
+        Some(id) => Mem8FuseFs::new_snapshot(fs, id)?,
+        None => Mem8FuseFs::new(fs),
+    };
+    println!("🌊 Mounting {}{} at {} (Ctrl-C to unmount)",
+        root, snapshot.map(|id| format!(" @ {}", id)).unwrap_or_default(), mountpoint);
+    Ok(fuse.mount_until_shutdown(mountpoint)?)
+}
+
+#[cfg(not(feature = "fuse-mount"))]
+fn mount(_args: &[String]) -> Result<()> {
+    Err(capabilities().require("fuse-mount", "mem8 mount").expect_err("built without the `fuse-mount` feature"))
+}
+
+/// `mem8 daemon` - hold a Mem8Lite store open for the other commands
+#[cfg(unix)]
+fn daemon(args: &[String]) -> Result<()> {
+    use mem8_fs_lite::daemon::{socket_path, Daemon, DEFAULT_IDLE_TIMEOUT};
+    
+    let mut idle_timeout = DEFAULT_IDLE_TIMEOUT.as_secs();
+    let mut frequency = FrequencyPreset::GoldenRatio;
+    let mut path = None;
+    let mut iter = args.iter();
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--idle-timeout" => idle_timeout = option_value(iter.next(), "--idle-timeout")?,
+            "--frequency" => frequency = option_value(iter.next(), "--frequency")?,
+            _ => path = Some(Path::new(arg)),
+        }
+    }
+    let path = path.ok_or_else(|| anyhow!("daemon needs a STORE\n{}", USAGE))?;
+    if path.join(".mem8").is_dir() {
+        return Err(anyhow!("the daemon serves Mem8Lite stores; {} is a Mem8Fs tree", path.display()));
+    }
+    
+    mem8_fs_lite::shutdown::install_handlers();
+    let socket = socket_path(path);
+    let daemon = Daemon::bind(Mem8Lite::new(path, frequency)?, &socket)?
+        .with_idle_timeout(std::time::Duration::from_secs(idle_timeout));
+    println!("🛰️ Serving {} on {} (Ctrl-C to stop, or {}s without clients)",
+        path.display(), socket.display(), idle_timeout);
+    daemon.run()
+}
+
+#[cfg(not(unix))]
+fn daemon(_args: &[String]) -> Result<()> {
+    Err(anyhow!("the mem8 daemon needs unix domain sockets"))
+}
+
+/// The daemon serving a Mem8Lite store, if one is
+#[cfg(unix)]
+fn daemon_for(store: &Path) -> Option<DaemonClient> {
+    if store.join(".mem8").is_dir() {
+        return None;
+    }
+    DaemonClient::connect(store)
+}
+
+#[cfg(not(unix))]
+fn daemon_for(_store: &Path) -> Option<std::convert::Infallible> {
+    None
+}
+
+/// Commands the daemon doesn't proxy mustn't open a store behind its back
+fn check_not_served(store: &Path) -> Result<()> {
+    if daemon_for(store).is_some() {
+        return Err(anyhow!("{} is being served by `mem8 daemon` - stop it first", store.display()));
+    }
+    Ok(())
+}
+
+fn open_fs(root: &Path) -> Result<Mem8Fs> {
+    if !root.join(".mem8").is_dir() {
+        return Err(anyhow!("no Mem8Fs tree at {}", root.display()));
+    }
+    Ok(Mem8Fs::new(root)?)
+}
+
+fn option_value<T: std::str::FromStr>(value: Option<&String>, flag: &str) -> Result<T> {
+    value.and_then(|v| v.parse().ok())
+        .ok_or_else(|| anyhow!("{} needs a value\n{}", flag, USAGE))
+}
+
+fn open_store_capabilities(path: &Path) -> Result<StoreCapabilities> {
+    if path.join(".mem8").is_dir() {
+        Ok(Mem8Fs::new(path)?.store_capabilities())
+    } else if path.is_file() {
+        match daemon_for(path) {
+            #[cfg(unix)]
+            Some(mut daemon) => daemon.caps(),
+            _ => Ok(Mem8Lite::new(path, FrequencyPreset::GoldenRatio)?.store_capabilities()),
+        }
+    } else {
+        Err(anyhow!("no MEM8 store at {}", path.display()))
+    }
+}
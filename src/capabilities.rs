@@ -0,0 +1,173 @@
+//! Capability probing for integrators
+//!
+//! Tells embedding applications what this build can do (compiled-in features)
+//! and what a given `.m8` store supports (schema, codecs, hashing) - so they can
+//! check before trying something that would fail halfway through.
+//!
+//! Hue, think of it as the crate introducing itself before the party starts! 👋
+
+use serde::{Serialize, Deserialize};
+use anyhow::{Result, anyhow};
+
+/// Crate version baked in at compile time
+pub const CRATE_VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Features compiled into this build (static, from cfg flags)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BuildCapabilities {
+    /// Crate version
+    pub version: String,
+    
+    /// FUSE mounting support (`fuse-mount` feature)
+    pub fuse_mount: bool,
+    
+    /// Async runtime support (`async` feature)
+    pub async_runtime: bool,
+    
+    /// SIMD optimizations (`simd` feature)
+    pub simd: bool,
+    
+    /// MP4 / Matroska audio tracks (`containers` feature)
+    #[serde(default)]
+    pub containers: bool,
+    
+    /// Memory-mapped packet reads (`mmap` feature)
+    #[serde(default)]
+    pub mmap: bool,
+}
+
+/// Probe the features compiled into this build
+pub fn capabilities() -> BuildCapabilities {
+    BuildCapabilities {
+        version: CRATE_VERSION.to_string(),
+        fuse_mount: cfg!(feature = "fuse-mount"),
+        async_runtime: cfg!(feature = "async"),
+        simd: cfg!(feature = "simd"),
+        containers: cfg!(feature = "containers"),
+        mmap: cfg!(feature = "mmap"),
+    }
+}
+
+impl BuildCapabilities {
+    /// Names of the enabled features, in Cargo feature spelling
+    pub fn enabled(&self) -> Vec<&'static str> {
+        let mut enabled = Vec::new();
+        if self.fuse_mount { enabled.push("fuse-mount"); }
+        if self.async_runtime { enabled.push("async"); }
+        if self.simd { enabled.push("simd"); }
+        if self.containers { enabled.push("containers"); }
+        if self.mmap { enabled.push("mmap"); }
+        enabled
+    }
+    
+    /// Is the named feature compiled in?
+    pub fn has(&self, feature: &str) -> bool {
+        self.enabled().contains(&feature)
+    }
+    
+    /// Fail with a helpful message when an operation needs a missing feature
+    pub fn require(&self, feature: &str, operation: &str) -> Result<()> {
+        if self.has(feature) {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "{} requires the `{}` capability, which is not compiled into this build \
+                 (rebuild with `--features {}`)",
+                operation, feature, feature
+            ))
+        }
+    }
+}
+
+impl std::fmt::Display for BuildCapabilities {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "🌊 MEM8-FS Lite v{}", self.version)?;
+        let enabled = self.enabled();
+        if enabled.is_empty() {
+            writeln!(f, "  Features: (none)")?;
+        } else {
+            writeln!(f, "  Features: {}", enabled.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// What a particular store on disk supports
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StoreCapabilities {
+    /// Store flavour ("lite" or "fs")
+    pub kind: String,
+    
+    /// On-disk schema version
+    pub schema_version: u32,
+    
+    /// Wave codecs used for payloads
+    pub codecs: Vec<String>,
+    
+    /// Hash algorithm behind signatures
+    pub hash_algorithm: String,
+    
+    /// Are payloads encrypted at rest?
+    pub encrypted: bool,
+}
+
+impl StoreCapabilities {
+    /// Does this store use the given codec?
+    pub fn supports_codec(&self, codec: &str) -> bool {
+        self.codecs.iter().any(|c| c == codec)
+    }
+}
+
+impl std::fmt::Display for StoreCapabilities {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "📦 Store ({}):", self.kind)?;
+        writeln!(f, "  Schema: v{}", self.schema_version)?;
+        writeln!(f, "  Codecs: {}", self.codecs.join(", "))?;
+        writeln!(f, "  Hash: {}", self.hash_algorithm)?;
+        writeln!(f, "  Encrypted: {}", if self.encrypted { "yes 🔒" } else { "no" })?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_build_capabilities_match_cfg() {
+        let caps = capabilities();
+        assert_eq!(caps.version, CRATE_VERSION);
+        assert_eq!(caps.fuse_mount, cfg!(feature = "fuse-mount"));
+        assert_eq!(caps.has("fuse-mount"), caps.fuse_mount);
+        
+        // Round-trips through JSON for integrators
+        let json = serde_json::to_string(&caps).unwrap();
+        let back: BuildCapabilities = serde_json::from_str(&json).unwrap();
+        assert_eq!(back, caps);
+    }
+    
+    #[test]
+    fn test_require_names_missing_capability() {
+        let caps = BuildCapabilities {
+            version: CRATE_VERSION.to_string(),
+            fuse_mount: false,
+            async_runtime: false,
+            simd: false,
+            containers: false,
+            mmap: false,
+        };
+        let err = caps.require("fuse-mount", "Mounting").unwrap_err();
+        assert!(err.to_string().contains("`fuse-mount`"));
+    }
+    
+    #[cfg(not(feature = "containers"))]
+    #[test]
+    fn test_container_audio_requires_the_capability() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("clip.mp4");
+        std::fs::write(&path, b"\0\0\0\x18ftypisom").unwrap();
+        let err = crate::audio_loader::load_audio_track(&path, None).unwrap_err();
+        assert!(err.to_string().contains("requires the `containers` capability"), "{}", err);
+        assert!(crate::audio_loader::probe_audio(&path).is_err());
+    }
+}
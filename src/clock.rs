@@ -0,0 +1,56 @@
+//! Where the store gets "now" from
+//!
+//! Timestamps and TTL deadlines are unix seconds read from a `Clock`. Stores
+//! use `SystemClock` unless told otherwise (`Mem8Lite::with_clock`); tests
+//! hand them a `ManualClock` and move time along themselves instead of
+//! sleeping until a packet expires.
+//!
+//! Trisha wanted a time machine. This is as close as we got. ⏰
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// A source of the current time, in unix seconds
+pub trait Clock: Send + Sync {
+    fn now(&self) -> u64;
+}
+
+/// The wall clock
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> u64 {
+        // A clock set before 1970 reads as the epoch rather than failing writes
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |elapsed| elapsed.as_secs())
+    }
+}
+
+/// A clock that only moves when told to
+#[derive(Debug, Default)]
+pub struct ManualClock {
+    now: AtomicU64,
+}
+
+impl ManualClock {
+    /// Start at `now` (unix seconds)
+    pub fn new(now: u64) -> Self {
+        Self { now: AtomicU64::new(now) }
+    }
+    
+    pub fn set(&self, now: u64) {
+        self.now.store(now, Ordering::SeqCst);
+    }
+    
+    pub fn advance(&self, by: Duration) {
+        self.now.fetch_add(by.as_secs(), Ordering::SeqCst);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> u64 {
+        self.now.load(Ordering::SeqCst)
+    }
+}
@@ -0,0 +1,424 @@
+//! Packet compression - squeezing the air out of metadata-heavy payloads
+//!
+//! Packets are compressed with zstd, at a level from 1 (fastest) to
+//! `MAX_LEVEL`. Each compressed packet records its codec in a mode byte, so
+//! codecs can come and go without breaking old stores: packets from before
+//! zstd used a small built-in LZ77 codec (`MODE_LZ`, `MODE_LZ_ADAPTIVE`),
+//! which `decompress_lz` still reads, though nothing writes it any more.
+//!
+//! `CompressionMode::Adaptive` picks the zstd level per packet from a quick
+//! entropy estimate: boring payloads get squeezed hard, noisy ones get the
+//! fast level (they won't shrink much anyway), and the choice is recorded in
+//! the packet.
+//!
+//! Trisha's take: JSON is mostly the same ten words over and over! 🗜️
+
+use anyhow::{Result, anyhow};
+use serde::{Deserialize, Serialize};
+
+/// Mode byte of a packet compressed with the old built-in LZ codec (read only)
+pub const MODE_LZ: u8 = 1;
+
+/// Mode byte of a packet the old LZ codec compressed adaptively (read only)
+/// 
+/// The body starts with the level used and the `EntropyClass` byte, then
+/// the LZ stream.
+pub const MODE_LZ_ADAPTIVE: u8 = 2;
+
+/// Mode byte of a packet compressed with zstd
+pub const MODE_ZSTD: u8 = 3;
+
+/// Mode byte of an adaptively compressed packet
+/// 
+/// The body starts with the zstd level used and the `EntropyClass` byte,
+/// then the zstd frame.
+pub const MODE_ZSTD_ADAPTIVE: u8 = 4;
+
+/// Bytes `estimate_entropy` looks at, at most
+const ENTROPY_SAMPLE: usize = 4096;
+
+/// Windows the sample is spread over on bigger payloads
+const ENTROPY_WINDOWS: usize = 16;
+
+/// Shortest match the old LZ codec encoded
+const MIN_MATCH: usize = 4;
+
+/// Highest (slowest, smallest) zstd level, short of the `--ultra` ones
+pub const MAX_LEVEL: u8 = 19;
+
+/// Level `zstd` means without one
+pub const DEFAULT_LEVEL: u8 = 3;
+
+/// How `Mem8Lite` compresses packets it writes
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum CompressionMode {
+    /// Store packets as they are
+    #[default]
+    None,
+    
+    /// zstd at a level from 1 (fastest) to `MAX_LEVEL`
+    Zstd(u8),
+    
+    /// zstd at a level picked per packet
+    Adaptive(AdaptivePolicy),
+}
+
+impl CompressionMode {
+    pub fn name(&self) -> &'static str {
+        match self {
+            CompressionMode::None => "none",
+            CompressionMode::Zstd(_) => "zstd",
+            CompressionMode::Adaptive(_) => "adaptive",
+        }
+    }
+}
+
+impl std::fmt::Display for CompressionMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CompressionMode::None => write!(f, "none"),
+            CompressionMode::Zstd(level) => write!(f, "zstd:{}", level),
+            CompressionMode::Adaptive(policy) => write!(f, "adaptive:{}-{}", policy.low_entropy, policy.high_entropy),
+        }
+    }
+}
+
+impl std::str::FromStr for CompressionMode {
+    type Err = anyhow::Error;
+    
+    /// `none`, `zstd` (level 3), `zstd:<level>`, `adaptive` or `adaptive:<low>-<high>`
+    /// (entropy thresholds in bits per byte)
+    fn from_str(s: &str) -> Result<Self> {
+        let (name, level) = match s.split_once(':') {
+            Some((name, level)) => (name, Some(level)),
+            None => (s, None),
+        };
+        match (name.to_ascii_lowercase().as_str(), level) {
+            ("none", None) => Ok(CompressionMode::None),
+            ("zstd", None) => Ok(CompressionMode::Zstd(DEFAULT_LEVEL)),
+            ("zstd", Some(level)) => match level.parse::<u8>() {
+                Ok(level) if (1..=MAX_LEVEL).contains(&level) => Ok(CompressionMode::Zstd(level)),
+                _ => Err(anyhow!("Compression level must be 1-{}, got {}", MAX_LEVEL, level)),
+            },
+            ("adaptive", None) => Ok(CompressionMode::Adaptive(AdaptivePolicy::default())),
+            ("adaptive", Some(thresholds)) => {
+                let parsed = thresholds.split_once('-')
+                    .and_then(|(low, high)| Some((low.parse::<f64>().ok()?, high.parse::<f64>().ok()?)));
+                let (low_entropy, high_entropy) = parsed
+                    .ok_or_else(|| anyhow!("Adaptive thresholds look like adaptive:3.5-7.0, got {}", s))?;
+                let policy = AdaptivePolicy { low_entropy, high_entropy, ..AdaptivePolicy::default() };
+                policy.validate()?;
+                Ok(CompressionMode::Adaptive(policy))
+            }
+            _ => Err(anyhow!("Unknown compression mode: {} (try none, zstd, zstd:<level> or adaptive)", s)),
+        }
+    }
+}
+
+/// How compressible a payload looked to the entropy probe
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EntropyClass {
+    /// Repetitive - worth the slow, thorough level
+    Low,
+    Medium,
+    
+    /// Close to noise - compression won't help much
+    High,
+}
+
+impl EntropyClass {
+    pub fn name(&self) -> &'static str {
+        match self {
+            EntropyClass::Low => "low",
+            EntropyClass::Medium => "medium",
+            EntropyClass::High => "high",
+        }
+    }
+    
+    /// The byte adaptive packets record the class as
+    pub fn to_byte(self) -> u8 {
+        self as u8
+    }
+    
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        match byte {
+            0 => Some(EntropyClass::Low),
+            1 => Some(EntropyClass::Medium),
+            2 => Some(EntropyClass::High),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for EntropyClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Thresholds and levels for `CompressionMode::Adaptive`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AdaptivePolicy {
+    /// Payloads below this many bits per byte are `Low` entropy
+    pub low_entropy: f64,
+    
+    /// Payloads at or above this are `High` entropy
+    pub high_entropy: f64,
+    
+    /// zstd level for `Low` entropy payloads
+    pub low_level: u8,
+    
+    /// Level for everything in between
+    pub medium_level: u8,
+    
+    /// Level for `High` entropy payloads (0 skips compression entirely)
+    pub high_level: u8,
+}
+
+impl Default for AdaptivePolicy {
+    fn default() -> Self {
+        Self {
+            low_entropy: 3.5,
+            high_entropy: 7.0,
+            low_level: MAX_LEVEL,
+            medium_level: DEFAULT_LEVEL,
+            high_level: 1,
+        }
+    }
+}
+
+impl AdaptivePolicy {
+    /// Reject thresholds out of order or out of 0-8 bits, and levels past `MAX_LEVEL`
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=8.0).contains(&self.low_entropy) || !(0.0..=8.0).contains(&self.high_entropy)
+            || self.low_entropy > self.high_entropy
+        {
+            return Err(anyhow!(
+                "Entropy thresholds must satisfy 0 <= low <= high <= 8, got {} and {}",
+                self.low_entropy, self.high_entropy
+            ));
+        }
+        if [self.low_level, self.medium_level, self.high_level].iter().any(|&level| level > MAX_LEVEL) {
+            return Err(anyhow!("Compression levels must be 0-{}", MAX_LEVEL));
+        }
+        Ok(())
+    }
+    
+    /// Which class `data` falls in
+    pub fn classify(&self, data: &[u8]) -> EntropyClass {
+        let entropy = estimate_entropy(data);
+        if entropy < self.low_entropy {
+            EntropyClass::Low
+        } else if entropy >= self.high_entropy {
+            EntropyClass::High
+        } else {
+            EntropyClass::Medium
+        }
+    }
+    
+    /// The level this policy uses for `class` (0 = don't compress)
+    pub fn level_for(&self, class: EntropyClass) -> u8 {
+        match class {
+            EntropyClass::Low => self.low_level,
+            EntropyClass::Medium => self.medium_level,
+            EntropyClass::High => self.high_level,
+        }
+    }
+}
+
+/// Shannon entropy of `data` in bits per byte, from a sample of it
+/// 
+/// Payloads up to `ENTROPY_SAMPLE` bytes are read whole; bigger ones are
+/// sampled in evenly spaced windows, so the probe costs the same at any size.
+pub fn estimate_entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0u32; 256];
+    let mut total = 0u32;
+    if data.len() <= ENTROPY_SAMPLE {
+        for &byte in data {
+            counts[byte as usize] += 1;
+        }
+        total = data.len() as u32;
+    } else {
+        let window = ENTROPY_SAMPLE / ENTROPY_WINDOWS;
+        let stride = (data.len() - window) / (ENTROPY_WINDOWS - 1);
+        for w in 0..ENTROPY_WINDOWS {
+            for &byte in &data[w * stride..w * stride + window] {
+                counts[byte as usize] += 1;
+            }
+            total += window as u32;
+        }
+    }
+    counts.iter().filter(|&&count| count > 0).map(|&count| {
+        let p = count as f64 / total as f64;
+        -p * p.log2()
+    }).sum()
+}
+
+/// Compress `input` with zstd at `level` (1-`MAX_LEVEL`; out of range levels are clamped)
+pub fn compress(input: &[u8], level: u8) -> Vec<u8> {
+    zstd::bulk::compress(input, level.clamp(1, MAX_LEVEL) as i32)
+        .expect("zstd only refuses levels outside its range")
+}
+
+/// Undo `compress`, insisting on exactly `expected_len` bytes of output
+pub fn decompress(input: &[u8], expected_len: usize) -> Result<Vec<u8>> {
+    let out = zstd::bulk::decompress(input, expected_len)
+        .map_err(|e| anyhow!("Corrupt compressed packet: {}", e))?;
+    if out.len() != expected_len {
+        return Err(anyhow!("Corrupt compressed packet: shorter than recorded"));
+    }
+    Ok(out)
+}
+
+/// Decode a packet from the old LZ codec, insisting on exactly `expected_len` bytes of output
+pub fn decompress_lz(input: &[u8], expected_len: usize) -> Result<Vec<u8>> {
+    let corrupt = |what: &str| anyhow!("Corrupt compressed packet: {}", what);
+    let mut out = Vec::with_capacity(expected_len);
+    let mut i = 0;
+    while i < input.len() {
+        let token = input[i];
+        i += 1;
+        
+        let mut literals = (token >> 4) as usize;
+        if literals == 15 {
+            literals += read_length(input, &mut i).ok_or_else(|| corrupt("truncated literal length"))?;
+        }
+        let end = i.checked_add(literals).filter(|&e| e <= input.len())
+            .ok_or_else(|| corrupt("literals run past the end"))?;
+        if out.len() + literals > expected_len {
+            return Err(corrupt("longer than recorded"));
+        }
+        out.extend_from_slice(&input[i..end]);
+        i = end;
+        
+        // The last sequence is literals only
+        if i == input.len() {
+            break;
+        }
+        let distance = match input.get(i..i + 2) {
+            Some(bytes) => u16::from_le_bytes([bytes[0], bytes[1]]) as usize,
+            None => return Err(corrupt("truncated match offset")),
+        };
+        i += 2;
+        if distance == 0 || distance > out.len() {
+            return Err(corrupt("match reaches before the start"));
+        }
+        let mut len = (token & 0x0F) as usize + MIN_MATCH;
+        if token & 0x0F == 0x0F {
+            len += read_length(input, &mut i).ok_or_else(|| corrupt("truncated match length"))?;
+        }
+        if out.len() + len > expected_len {
+            return Err(corrupt("longer than recorded"));
+        }
+        // Byte by byte: a match may overlap the bytes it's producing
+        let from = out.len() - distance;
+        for k in 0..len {
+            out.push(out[from + k]);
+        }
+    }
+    
+    if out.len() != expected_len {
+        return Err(corrupt("shorter than recorded"));
+    }
+    Ok(out)
+}
+
+fn read_length(input: &[u8], i: &mut usize) -> Option<usize> {
+    let mut total = 0;
+    loop {
+        let byte = *input.get(*i)?;
+        *i += 1;
+        total += byte as usize;
+        if byte != 255 {
+            return Some(total);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_round_trips_and_squeezes_repetitive_json() {
+        let json: String = (0..500)
+            .map(|i| format!(r#"{{"namespace":"audio","kind":"wonder","score":{},"tags":["focus","flow"]}},"#, i % 7))
+            .collect();
+        let mut noise = vec![0u8; 5000];
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        for byte in noise.iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            *byte = state as u8;
+        }
+        let long_run = vec![b'a'; 70_000];
+        
+        for input in [json.as_bytes(), &noise, &long_run, b"", b"abc", b"abcabcabcabc"] {
+            for level in [1, DEFAULT_LEVEL, MAX_LEVEL] {
+                let packed = compress(input, level);
+                assert_eq!(decompress(&packed, input.len()).unwrap(), input);
+            }
+        }
+        let packed = compress(json.as_bytes(), DEFAULT_LEVEL);
+        assert!(json.len() / packed.len() >= 10, "ratio only {}", json.len() / packed.len());
+    }
+    
+    #[test]
+    fn test_corrupt_input_is_an_error() {
+        let input = b"the quick brown fox, the quick brown fox, the quick brown fox".to_vec();
+        let packed = compress(&input, 5);
+        assert!(decompress(&packed, input.len() + 1).is_err());
+        assert!(decompress(&packed, input.len() - 1).is_err());
+        assert!(decompress(&packed[..packed.len() - 3], input.len()).is_err());
+        assert_eq!("zstd:7".parse::<CompressionMode>().unwrap(), CompressionMode::Zstd(7));
+        assert_eq!("zstd".parse::<CompressionMode>().unwrap(), CompressionMode::Zstd(DEFAULT_LEVEL));
+        assert!("zstd:20".parse::<CompressionMode>().is_err());
+        assert!("lz".parse::<CompressionMode>().is_err());
+    }
+    
+    #[test]
+    fn test_old_lz_packets_still_decode() {
+        // "abc" then a 5-byte match 3 back, then an empty last sequence
+        let stream = [0x31, b'a', b'b', b'c', 0x03, 0x00, 0x00];
+        assert_eq!(decompress_lz(&stream, 8).unwrap(), b"abcabcab");
+        assert!(decompress_lz(&stream, 9).is_err());
+        
+        // 20 literals, with an extended length
+        let mut literals = vec![0xF0, 20 - 15];
+        literals.extend_from_slice(b"twenty literal bytes");
+        assert_eq!(decompress_lz(&literals, 20).unwrap(), b"twenty literal bytes");
+        assert!(decompress_lz(&[0x0F, 0x05, 0x00], 100).is_err()); // Match before any output
+    }
+    
+    #[test]
+    fn test_adaptive_policy_classifies_by_entropy() {
+        let policy = AdaptivePolicy::default();
+        let mut noise = vec![0u8; 100_000];
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        for byte in noise.iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            *byte = state as u8;
+        }
+        let text = "Hue logged 973 waves at 3:14, Trisha filed them under Q4-2024! ".repeat(2000);
+        
+        assert_eq!(policy.classify(&vec![0u8; 50_000]), EntropyClass::Low);
+        assert_eq!(policy.classify(text.as_bytes()), EntropyClass::Medium);
+        assert_eq!(policy.classify(&noise), EntropyClass::High);
+        assert!(estimate_entropy(&noise) > 7.5);
+        assert_eq!(policy.level_for(EntropyClass::Low), MAX_LEVEL);
+        
+        assert_eq!(
+            "adaptive:2-6".parse::<CompressionMode>().unwrap(),
+            CompressionMode::Adaptive(AdaptivePolicy { low_entropy: 2.0, high_entropy: 6.0, ..policy })
+        );
+        assert!("adaptive:6-2".parse::<CompressionMode>().is_err());
+        assert!("adaptive:lots".parse::<CompressionMode>().is_err());
+    }
+}
@@ -0,0 +1,1249 @@
+//! Audio tracks out of video containers (MP4 / Matroska)
+//!
+//! A minimal demuxer: walks MP4 boxes or Matroska EBML elements just far
+//! enough to list the audio tracks, pick up container metadata (title,
+//! duration, track language) and pull one track's frames out. Video streams
+//! are skipped unread. The frames go through the existing codec paths - FLAC
+//! via claxon, PCM like a raw file - and AAC-LC through symphonia's decoder,
+//! trimmed to the MP4 edit list so encoder priming doesn't leak in. Tracks in
+//! other codecs (Opus, AC-3, ...) are listed with `decodable: false` so
+//! callers can pick another one.
+//!
+//! Hue's screen recordings finally get ears! 🎬
+
+use std::collections::HashMap;
+use std::io::{Cursor, Read, Seek, SeekFrom};
+use anyhow::{Result, anyhow};
+use byteorder::{BigEndian, ReadBytesExt};
+
+use crate::audio::{AudioFormat, SampleRate};
+use crate::audio_loader::{self, AudioFileFormat, AudioMetadata, AudioTrack, LoadedAudio};
+
+/// Matroska element ids (with their length marker, as written)
+mod ebml {
+    pub const HEADER: u32 = 0x1A45_DFA3;
+    pub const SEGMENT: u32 = 0x1853_8067;
+    pub const INFO: u32 = 0x1549_A966;
+    pub const TIMESTAMP_SCALE: u32 = 0x2A_D7B1;
+    pub const DURATION: u32 = 0x4489;
+    pub const TITLE: u32 = 0x7BA9;
+    pub const TRACKS: u32 = 0x1654_AE6B;
+    pub const TRACK_ENTRY: u32 = 0xAE;
+    pub const TRACK_NUMBER: u32 = 0xD7;
+    pub const TRACK_TYPE: u32 = 0x83;
+    pub const CODEC_ID: u32 = 0x86;
+    pub const CODEC_PRIVATE: u32 = 0x63A2;
+    pub const LANGUAGE: u32 = 0x22_B59C;
+    pub const LANGUAGE_BCP47: u32 = 0x22_B59D;
+    pub const AUDIO: u32 = 0xE1;
+    pub const SAMPLING_FREQUENCY: u32 = 0xB5;
+    pub const CHANNELS: u32 = 0x9F;
+    pub const BIT_DEPTH: u32 = 0x6264;
+    pub const CLUSTER: u32 = 0x1F43_B675;
+    pub const SIMPLE_BLOCK: u32 = 0xA3;
+    pub const BLOCK_GROUP: u32 = 0xA0;
+    pub const BLOCK: u32 = 0xA1;
+    
+    /// Children of a Segment - where an unknown-size Cluster ends
+    pub const TOP_LEVEL: [u32; 8] = [
+        INFO, TRACKS, CLUSTER,
+        0x1C53_BB6B, // Cues
+        0x1254_C367, // Tags
+        0x1043_A770, // Chapters
+        0x1941_A469, // Attachments
+        0x114D_9B74, // SeekHead
+    ];
+    
+    /// TrackType of an audio track
+    pub const TRACK_TYPE_AUDIO: u64 = 2;
+}
+
+/// Which container a file turned out to be
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContainerKind {
+    /// ISO base media (`.mp4`, `.m4a`, `.mov`)
+    Mp4,
+    
+    /// Matroska / WebM (`.mkv`, `.mka`, `.webm`)
+    Matroska,
+}
+
+impl ContainerKind {
+    /// Sniff the container from the first bytes of a file
+    pub fn detect(magic: &[u8]) -> Option<Self> {
+        if magic.len() >= 8 && &magic[4..8] == b"ftyp" {
+            Some(ContainerKind::Mp4)
+        } else if magic.starts_with(&ebml::HEADER.to_be_bytes()) {
+            Some(ContainerKind::Matroska)
+        } else {
+            None
+        }
+    }
+    
+    pub fn file_format(&self) -> AudioFileFormat {
+        match self {
+            ContainerKind::Mp4 => AudioFileFormat::Mp4,
+            ContainerKind::Matroska => AudioFileFormat::Matroska,
+        }
+    }
+}
+
+/// How a track's frames turn back into samples
+#[derive(Debug, Clone)]
+enum TrackCodec {
+    /// Interleaved PCM, one frame after another
+    Pcm { big_endian: bool, float: bool },
+    
+    /// FLAC frames; `header` is the `fLaC` marker plus metadata blocks
+    Flac { header: Vec<u8> },
+    
+    /// AAC-LC access units; `config` is the AudioSpecificConfig
+    Aac { config: Vec<u8> },
+    
+    /// Listed, but can't be decoded here
+    Other,
+}
+
+/// Where one frame (MP4 sample / Matroska block) lives in the file
+#[derive(Debug, Clone, Copy)]
+struct Frame {
+    offset: u64,
+    len: u64,
+    
+    /// Matroska lacing mode (0 = none); laced blocks hold several frames
+    lacing: u8,
+}
+
+/// The part of a track meant to be heard (from an MP4 edit list)
+#[derive(Debug, Clone, Copy)]
+struct Edit {
+    /// Leading media to drop - encoder priming, mostly
+    skip_secs: f64,
+    
+    /// How much to keep after that (`None` = the rest)
+    keep_secs: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+struct DemuxedTrack {
+    info: AudioTrack,
+    codec: TrackCodec,
+    frames: Vec<Frame>,
+    edit: Option<Edit>,
+}
+
+/// An opened container: its metadata, audio tracks and where their frames are
+pub struct Container<R> {
+    reader: R,
+    kind: ContainerKind,
+    metadata: AudioMetadata,
+    tracks: Vec<DemuxedTrack>,
+}
+
+impl<R: Read + Seek> Container<R> {
+    /// Parse the container's structure (frame data isn't read until `decode_track`)
+    pub fn open(mut reader: R) -> Result<Self> {
+        let len = reader.seek(SeekFrom::End(0))?;
+        let mut magic = [0u8; 8];
+        reader.seek(SeekFrom::Start(0))?;
+        reader.read_exact(&mut magic)
+            .map_err(|_| anyhow!("File too short to be a video container"))?;
+        
+        let kind = ContainerKind::detect(&magic)
+            .ok_or_else(|| anyhow!("Not an MP4 or Matroska file"))?;
+        let mut metadata = empty_metadata();
+        let tracks = match kind {
+            ContainerKind::Mp4 => mp4::parse(&mut reader, len, &mut metadata)?,
+            ContainerKind::Matroska => mkv::parse(&mut reader, len, &mut metadata)?,
+        };
+        
+        Ok(Self { reader, kind, metadata, tracks })
+    }
+    
+    pub fn kind(&self) -> ContainerKind {
+        self.kind
+    }
+    
+    /// Container-level metadata (title, duration, ...)
+    pub fn metadata(&self) -> &AudioMetadata {
+        &self.metadata
+    }
+    
+    /// The audio tracks, in container order
+    pub fn tracks(&self) -> Vec<AudioTrack> {
+        self.tracks.iter().map(|t| t.info.clone()).collect()
+    }
+    
+    /// Decode one audio track (`None` = the first decodable one)
+    pub fn decode_track(&mut self, track_id: Option<u32>) -> Result<LoadedAudio> {
+        let track = match track_id {
+            Some(id) => self.tracks.iter()
+                .find(|t| t.info.id == id)
+                .ok_or_else(|| anyhow!("No audio track {} (available: {})", id, self.track_list()))?,
+            None => self.tracks.iter()
+                .find(|t| t.info.decodable)
+                .or_else(|| self.tracks.first())
+                .ok_or_else(|| anyhow!("No audio tracks in this {:?} file", self.kind))?,
+        }.clone();
+        
+        if !track.info.decodable {
+            return Err(anyhow!(
+                "Can't decode {} audio (track {}) - this build decodes PCM, FLAC and AAC-LC tracks only",
+                track.info.codec, track.info.id
+            ));
+        }
+        
+        let mut packets = Vec::new();
+        for frame in &track.frames {
+            let mut block = vec![0u8; frame.len as usize];
+            self.reader.seek(SeekFrom::Start(frame.offset))?;
+            self.reader.read_exact(&mut block)
+                .map_err(|_| anyhow!("Track {} frame at {} runs past the end of the file", track.info.id, frame.offset))?;
+            for laced in mkv::unlace(&block, frame.lacing)? {
+                packets.push(laced.to_vec());
+            }
+        }
+        
+        let (mut samples, format) = match &track.codec {
+            TrackCodec::Pcm { big_endian, float } => {
+                let mut data = packets.concat();
+                let format = AudioFormat {
+                    sample_rate: SampleRate::from_hz(track.info.sample_rate as f64),
+                    channels: track.info.channels,
+                    bit_depth: track.info.bit_depth.unwrap_or(16),
+                    is_float: *float,
+                };
+                if *big_endian {
+                    for sample in data.chunks_exact_mut(format.bit_depth / 8) {
+                        sample.reverse();
+                    }
+                }
+                (audio_loader::decode_pcm(&data, &format)?, format)
+            }
+            TrackCodec::Flac { header } => {
+                let mut stream = header.clone();
+                for packet in &packets {
+                    stream.extend_from_slice(packet);
+                }
+                let flac = audio_loader::decode_flac(Cursor::new(stream))?;
+                (flac.samples, flac.format)
+            }
+            TrackCodec::Aac { config } => decode_aac(config, &packets)?,
+            TrackCodec::Other => unreachable!("checked decodable above"),
+        };
+        
+        if let Some(edit) = track.edit {
+            let rate = format.sample_rate.as_f64();
+            let skip = (edit.skip_secs * rate).round() as usize * format.channels;
+            samples.drain(..skip.min(samples.len()));
+            if let Some(keep) = edit.keep_secs {
+                samples.truncate((keep * rate).round() as usize * format.channels);
+            }
+        }
+        
+        let mut metadata = self.metadata.clone();
+        metadata.language = track.info.language.clone();
+        metadata.duration_secs = track.info.duration_secs.or(metadata.duration_secs);
+        Ok(LoadedAudio {
+            samples,
+            format,
+            file_format: self.kind.file_format(),
+            metadata: Some(metadata),
+        })
+    }
+    
+    fn track_list(&self) -> String {
+        let ids: Vec<String> = self.tracks.iter().map(|t| t.info.id.to_string()).collect();
+        if ids.is_empty() { "none".to_string() } else { ids.join(", ") }
+    }
+}
+
+fn empty_metadata() -> AudioMetadata {
+    AudioMetadata {
+        title: None,
+        artist: None,
+        album: None,
+        track: None,
+        year: None,
+        genre: None,
+        comment: None,
+        duration_secs: None,
+        language: None,
+    }
+}
+
+/// Read `len` bytes at `offset`
+fn read_at<R: Read + Seek>(reader: &mut R, offset: u64, len: u64) -> Result<Vec<u8>> {
+    let mut buffer = vec![0u8; len as usize];
+    reader.seek(SeekFrom::Start(offset))?;
+    reader.read_exact(&mut buffer)?;
+    Ok(buffer)
+}
+
+/// Whether an AudioSpecificConfig is plain AAC-LC (object type 2) - what the decoder handles
+fn is_aac_lc(config: &[u8]) -> bool {
+    config.len() >= 2 && config[0] >> 3 == 2
+}
+
+/// Decode AAC access units, one per packet, as described by `config`
+fn decode_aac(config: &[u8], packets: &[Vec<u8>]) -> Result<(Vec<f64>, AudioFormat)> {
+    use symphonia::core::audio::SampleBuffer;
+    use symphonia::core::codecs::{CodecParameters, Decoder, DecoderOptions, CODEC_TYPE_AAC};
+    use symphonia::core::formats::Packet;
+    use symphonia::default::codecs::AacDecoder;
+    
+    let mut params = CodecParameters::new();
+    params.for_codec(CODEC_TYPE_AAC).with_extra_data(config.into());
+    let mut decoder = AacDecoder::try_new(&params, &DecoderOptions::default())?;
+    
+    let mut samples = Vec::new();
+    for (index, packet) in packets.iter().enumerate() {
+        let decoded = decoder.decode(&Packet::new_from_slice(0, index as u64 * 1024, 1024, packet))?;
+        let mut buffer = SampleBuffer::<f64>::new(decoded.capacity() as u64, *decoded.spec());
+        buffer.copy_interleaved_ref(decoded);
+        samples.extend_from_slice(buffer.samples());
+    }
+    
+    let spec = *decoder.last_decoded().spec();
+    let format = AudioFormat {
+        sample_rate: SampleRate::from_hz(spec.rate as f64),
+        channels: spec.channels.count(),
+        bit_depth: 32,
+        is_float: true,
+    };
+    Ok((samples, format))
+}
+
+/// ISO base media (MP4 / QuickTime) boxes
+mod mp4 {
+    use super::*;
+    
+    /// A box's type and where its body is
+    struct BoxRange {
+        kind: [u8; 4],
+        start: u64,
+        end: u64,
+    }
+    
+    /// The boxes directly inside `start..end`
+    fn children<R: Read + Seek>(reader: &mut R, start: u64, end: u64) -> Result<Vec<BoxRange>> {
+        let mut boxes = Vec::new();
+        let mut pos = start;
+        while pos + 8 <= end {
+            reader.seek(SeekFrom::Start(pos))?;
+            let size = reader.read_u32::<BigEndian>()? as u64;
+            let mut kind = [0u8; 4];
+            reader.read_exact(&mut kind)?;
+            let (header, size) = match size {
+                0 => (8, end - pos),
+                1 => (16, reader.read_u64::<BigEndian>()?),
+                size => (8, size),
+            };
+            if size < header || pos + size > end {
+                return Err(anyhow!("Corrupt MP4 box '{}' at {}", String::from_utf8_lossy(&kind), pos));
+            }
+            boxes.push(BoxRange { kind, start: pos + header, end: pos + size });
+            pos += size;
+        }
+        Ok(boxes)
+    }
+    
+    fn find<'a>(boxes: &'a [BoxRange], kind: &[u8; 4]) -> Option<&'a BoxRange> {
+        boxes.iter().find(|b| &b.kind == kind)
+    }
+    
+    fn body<R: Read + Seek>(reader: &mut R, range: &BoxRange) -> Result<Vec<u8>> {
+        read_at(reader, range.start, range.end - range.start)
+    }
+    
+    /// Children of the box at `path` under `boxes`
+    fn descend<R: Read + Seek>(reader: &mut R, boxes: &[BoxRange], path: &[&[u8; 4]]) -> Result<Option<Vec<BoxRange>>> {
+        let mut current = match find(boxes, path[0]) {
+            Some(b) => children(reader, b.start, b.end)?,
+            None => return Ok(None),
+        };
+        for kind in &path[1..] {
+            current = match find(&current, kind) {
+                Some(b) => children(reader, b.start, b.end)?,
+                None => return Ok(None),
+            };
+        }
+        Ok(Some(current))
+    }
+    
+    pub(super) fn parse<R: Read + Seek>(reader: &mut R, len: u64, metadata: &mut AudioMetadata) -> Result<Vec<DemuxedTrack>> {
+        let top = children(reader, 0, len)?;
+        let moov = find(&top, b"moov").ok_or_else(|| anyhow!("MP4 file has no 'moov' box"))?;
+        let moov = children(reader, moov.start, moov.end)?;
+        
+        let mut movie_timescale = 0;
+        if let Some(mvhd) = find(&moov, b"mvhd") {
+            let (timescale, duration) = timescale_and_duration(&body(reader, mvhd)?)?;
+            metadata.duration_secs = seconds(duration, timescale);
+            movie_timescale = timescale;
+        }
+        if let Some(udta) = find(&moov, b"udta") {
+            let udta = children(reader, udta.start, udta.end)?;
+            read_item_list(reader, &udta, metadata)?;
+        }
+        
+        let mut tracks = Vec::new();
+        for trak in moov.iter().filter(|b| &b.kind == b"trak") {
+            if let Some(track) = parse_track(reader, trak, movie_timescale)? {
+                tracks.push(track);
+            }
+        }
+        Ok(tracks)
+    }
+    
+    /// Timescale and duration from an `mvhd`/`mdhd` body (version 0 or 1)
+    fn timescale_and_duration(body: &[u8]) -> Result<(u32, u64)> {
+        let mut fields = body;
+        let version = fields.read_u8()?;
+        let mut skip = [0u8; 3 + 16];
+        if version == 1 {
+            fields.read_exact(&mut skip[..3 + 16])?;
+            Ok((fields.read_u32::<BigEndian>()?, fields.read_u64::<BigEndian>()?))
+        } else {
+            fields.read_exact(&mut skip[..3 + 8])?;
+            Ok((fields.read_u32::<BigEndian>()?, fields.read_u32::<BigEndian>()? as u64))
+        }
+    }
+    
+    fn seconds(duration: u64, timescale: u32) -> Option<f64> {
+        (timescale > 0 && duration > 0 && duration != u32::MAX as u64 && duration != u64::MAX)
+            .then(|| duration as f64 / timescale as f64)
+    }
+    
+    /// iTunes-style tags under `udta/meta/ilst`
+    fn read_item_list<R: Read + Seek>(reader: &mut R, udta: &[BoxRange], metadata: &mut AudioMetadata) -> Result<()> {
+        let meta = match find(udta, b"meta") {
+            Some(meta) => meta,
+            None => return Ok(()),
+        };
+        // ISO `meta` is a full box (4 bytes of version/flags); QuickTime's isn't
+        let head = read_at(reader, meta.start, 4.min(meta.end - meta.start))?;
+        let start = if head == [0, 0, 0, 0] { meta.start + 4 } else { meta.start };
+        let meta = children(reader, start, meta.end)?;
+        let items = match find(&meta, b"ilst") {
+            Some(ilst) => children(reader, ilst.start, ilst.end)?,
+            None => return Ok(()),
+        };
+        
+        for item in &items {
+            let data = children(reader, item.start, item.end)?;
+            let text = match find(&data, b"data") {
+                // Type and locale, then the value
+                Some(d) if d.end - d.start > 8 => String::from_utf8_lossy(&read_at(reader, d.start + 8, d.end - d.start - 8)?).into_owned(),
+                _ => continue,
+            };
+            match &item.kind {
+                b"\xA9nam" => metadata.title = Some(text),
+                b"\xA9ART" => metadata.artist = Some(text),
+                b"\xA9alb" => metadata.album = Some(text),
+                b"\xA9day" => metadata.year = text.get(..4).and_then(|y| y.parse().ok()),
+                b"\xA9gen" => metadata.genre = Some(text),
+                b"\xA9cmt" => metadata.comment = Some(text),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+    
+    /// The first non-empty edit of an `elst` body (empty ones are pauses before the track)
+    fn first_edit(body: &[u8], movie_timescale: u32, media_timescale: u32) -> Result<Option<Edit>> {
+        let mut fields = body;
+        let version = fields.read_u8()?;
+        fields.read_u24::<BigEndian>()?; // Flags
+        let count = fields.read_u32::<BigEndian>()?;
+        for _ in 0..count {
+            let (duration, media_time) = if version == 1 {
+                (fields.read_u64::<BigEndian>()?, fields.read_i64::<BigEndian>()?)
+            } else {
+                (fields.read_u32::<BigEndian>()? as u64, fields.read_i32::<BigEndian>()? as i64)
+            };
+            fields.read_u32::<BigEndian>()?; // Rate
+            if media_time < 0 {
+                continue;
+            }
+            if media_timescale == 0 {
+                return Ok(None);
+            }
+            return Ok(Some(Edit {
+                skip_secs: media_time as f64 / media_timescale as f64,
+                keep_secs: seconds(duration, movie_timescale),
+            }));
+        }
+        Ok(None)
+    }
+    
+    fn parse_track<R: Read + Seek>(reader: &mut R, trak: &BoxRange, movie_timescale: u32) -> Result<Option<DemuxedTrack>> {
+        let trak = children(reader, trak.start, trak.end)?;
+        let mdia = match descend(reader, &trak, &[b"mdia"])? {
+            Some(mdia) => mdia,
+            None => return Ok(None),
+        };
+        
+        // Only sound tracks - video is never looked at
+        let hdlr = match find(&mdia, b"hdlr") {
+            Some(hdlr) => body(reader, hdlr)?,
+            None => return Ok(None),
+        };
+        if hdlr.get(8..12) != Some(b"soun".as_slice()) {
+            return Ok(None);
+        }
+        
+        let tkhd = find(&trak, b"tkhd").ok_or_else(|| anyhow!("MP4 track without 'tkhd'"))?;
+        let tkhd = body(reader, tkhd)?;
+        let id_at = if tkhd.first() == Some(&1) { 20 } else { 12 };
+        let id = tkhd.get(id_at..id_at + 4)
+            .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| anyhow!("Truncated MP4 'tkhd'"))?;
+        
+        let (media_timescale, duration_secs, language) = match find(&mdia, b"mdhd") {
+            Some(mdhd) => {
+                let mdhd = body(reader, mdhd)?;
+                let (timescale, duration) = timescale_and_duration(&mdhd)?;
+                let lang_at = if mdhd.first() == Some(&1) { 32 } else { 20 };
+                let language = mdhd.get(lang_at..lang_at + 2).and_then(|b| language_code(u16::from_be_bytes([b[0], b[1]])));
+                (timescale, seconds(duration, timescale), language)
+            }
+            None => (0, None, None),
+        };
+        
+        let edit = match descend(reader, &trak, &[b"edts"])?.as_deref().and_then(|edts| find(edts, b"elst")) {
+            Some(elst) => first_edit(&body(reader, elst)?, movie_timescale, media_timescale)?,
+            None => None,
+        };
+        let duration_secs = edit.and_then(|e| e.keep_secs).or(duration_secs);
+        
+        let stbl = descend(reader, &mdia, &[b"minf", b"stbl"])?
+            .ok_or_else(|| anyhow!("MP4 track {} has no sample table", id))?;
+        let stsd = find(&stbl, b"stsd").ok_or_else(|| anyhow!("MP4 track {} has no 'stsd'", id))?;
+        let entries = children(reader, stsd.start + 8, stsd.end)?;
+        let entry = entries.first().ok_or_else(|| anyhow!("MP4 track {} has an empty 'stsd'", id))?;
+        let (info, codec) = sample_entry(reader, entry, id)?;
+        let info = AudioTrack { duration_secs, language, ..info };
+        
+        let bytes_per_frame = match codec {
+            TrackCodec::Pcm { .. } => info.channels as u64 * info.bit_depth.unwrap_or(16) as u64 / 8,
+            _ => 0,
+        };
+        let frames = sample_locations(reader, &stbl, bytes_per_frame)?;
+        Ok(Some(DemuxedTrack { info, codec, frames, edit }))
+    }
+    
+    /// Packed ISO 639-2/T code from `mdhd` ("und" = unknown)
+    fn language_code(packed: u16) -> Option<String> {
+        let code: String = [10, 5, 0].iter()
+            .map(|shift| (((packed >> shift) & 0x1F) as u8 + 0x60) as char)
+            .collect();
+        (code != "und" && code.chars().all(|c| c.is_ascii_lowercase())).then_some(code)
+    }
+    
+    /// Codec and format from the first sample entry
+    fn sample_entry<R: Read + Seek>(reader: &mut R, entry: &BoxRange, id: u32) -> Result<(AudioTrack, TrackCodec)> {
+        let body = body(reader, entry)?;
+        if body.len() < 28 {
+            return Err(anyhow!("Truncated audio sample entry in MP4 track {}", id));
+        }
+        let version = u16::from_be_bytes([body[8], body[9]]);
+        let channels = u16::from_be_bytes([body[16], body[17]]) as usize;
+        let sample_size = u16::from_be_bytes([body[18], body[19]]) as usize;
+        let sample_rate = u16::from_be_bytes([body[24], body[25]]) as u32; // 16.16 fixed point
+        
+        // QuickTime v1/v2 sound descriptions carry extra fields before the child boxes
+        let extra = match version { 1 => 16, 2 => 36, _ => 0 };
+        let boxes = children(reader, entry.start + 28 + extra, entry.end)?;
+        
+        let mut bit_depth = Some(sample_size);
+        let (codec, name) = match &entry.kind {
+            b"sowt" => (TrackCodec::Pcm { big_endian: false, float: false }, "pcm"),
+            b"twos" => (TrackCodec::Pcm { big_endian: true, float: false }, "pcm"),
+            b"ipcm" | b"fpcm" => {
+                // pcmC: version/flags, then format flags (bit 0 = little endian) and sample size
+                let pcmc = find(&boxes, b"pcmC").map(|b| read_at(reader, b.start, b.end - b.start)).transpose()?;
+                let (little_endian, size) = match pcmc.as_deref() {
+                    Some([_, _, _, _, flags, size, ..]) => (flags & 1 == 1, *size as usize),
+                    _ => (false, sample_size),
+                };
+                bit_depth = Some(size);
+                (TrackCodec::Pcm { big_endian: !little_endian, float: &entry.kind == b"fpcm" }, "pcm")
+            }
+            b"fLaC" => {
+                let dfla = find(&boxes, b"dfLa")
+                    .ok_or_else(|| anyhow!("FLAC track {} is missing its 'dfLa' box", id))?;
+                let mut header = b"fLaC".to_vec();
+                header.extend_from_slice(&read_at(reader, dfla.start + 4, dfla.end - dfla.start - 4)?);
+                (TrackCodec::Flac { header }, "flac")
+            }
+            b"mp4a" => {
+                let esds = find(&boxes, b"esds").map(|b| read_at(reader, b.start, b.end - b.start)).transpose()?;
+                match esds.as_deref().and_then(|esds| decoder_config(esds.get(4..)?)) {
+                    Some((0x40, config)) if is_aac_lc(&config) => (TrackCodec::Aac { config }, "aac"),
+                    Some((0x69 | 0x6B, _)) => (TrackCodec::Other, "mp3"),
+                    _ => (TrackCodec::Other, "aac"),
+                }
+            }
+            b"Opus" => (TrackCodec::Other, "opus"),
+            b".mp3" => (TrackCodec::Other, "mp3"),
+            b"ac-3" => (TrackCodec::Other, "ac3"),
+            b"ec-3" => (TrackCodec::Other, "eac3"),
+            b"alac" => (TrackCodec::Other, "alac"),
+            _ => (TrackCodec::Other, ""),
+        };
+        let codec_name = if name.is_empty() {
+            String::from_utf8_lossy(&entry.kind).trim().to_lowercase()
+        } else {
+            name.to_string()
+        };
+        if matches!(codec, TrackCodec::Aac { .. } | TrackCodec::Other) {
+            bit_depth = None;
+        }
+        
+        Ok((AudioTrack {
+            id,
+            codec: codec_name,
+            sample_rate,
+            channels,
+            bit_depth,
+            language: None,
+            duration_secs: None,
+            decodable: !matches!(codec, TrackCodec::Other),
+        }, codec))
+    }
+    
+    /// One MPEG-4 descriptor: (tag, body, what follows it)
+    fn descriptor(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+        let (&tag, mut rest) = data.split_first()?;
+        let mut len = 0;
+        for _ in 0..4 {
+            let (&byte, after) = rest.split_first()?;
+            rest = after;
+            len = (len << 7) | (byte & 0x7F) as usize;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+        (rest.len() >= len).then(|| (tag, &rest[..len], &rest[len..]))
+    }
+    
+    /// Object type and decoder-specific info from an `esds` ES_Descriptor
+    fn decoder_config(es: &[u8]) -> Option<(u8, Vec<u8>)> {
+        let (0x03, es, _) = descriptor(es)? else { return None };
+        let flags = *es.get(2)?;
+        let mut at = 3;
+        if flags & 0x80 != 0 {
+            at += 2; // Depends-on ES id
+        }
+        if flags & 0x40 != 0 {
+            at += 1 + *es.get(at)? as usize; // URL
+        }
+        if flags & 0x20 != 0 {
+            at += 2; // OCR ES id
+        }
+        
+        let mut rest = es.get(at..)?;
+        while let Some((tag, body, after)) = descriptor(rest) {
+            if tag == 0x04 {
+                let object_type = *body.first()?;
+                let info = body.get(13..)
+                    .and_then(descriptor)
+                    .filter(|(tag, _, _)| *tag == 0x05)
+                    .map(|(_, info, _)| info.to_vec())
+                    .unwrap_or_default();
+                return Some((object_type, info));
+            }
+            rest = after;
+        }
+        None
+    }
+    
+    /// Every sample's offset and size, from `stsz`, `stsc` and `stco`/`co64`
+    fn sample_locations<R: Read + Seek>(reader: &mut R, stbl: &[BoxRange], bytes_per_frame: u64) -> Result<Vec<Frame>> {
+        let table = |kind: &[u8; 4]| find(stbl, kind).ok_or_else(|| anyhow!("MP4 sample table is missing '{}'", String::from_utf8_lossy(kind)));
+        
+        let stsz = body(reader, table(b"stsz")?)?;
+        let mut fields = stsz.get(4..).unwrap_or_default();
+        let size = fields.read_u32::<BigEndian>()? as u64;
+        let count = fields.read_u32::<BigEndian>()? as usize;
+        let sizes: Vec<u64> = if size == 0 {
+            (0..count).map(|_| fields.read_u32::<BigEndian>().map(u64::from)).collect::<std::io::Result<_>>()?
+        } else if size == 1 && bytes_per_frame > 1 {
+            // QuickTime PCM counts frames of one "byte" each
+            vec![bytes_per_frame; count]
+        } else {
+            vec![size; count]
+        };
+        
+        let stsc = body(reader, table(b"stsc")?)?;
+        let mut fields = stsc.get(4..).unwrap_or_default();
+        let runs = fields.read_u32::<BigEndian>()? as usize;
+        let mut per_chunk = Vec::with_capacity(runs);
+        for _ in 0..runs {
+            let first_chunk = fields.read_u32::<BigEndian>()?;
+            let samples = fields.read_u32::<BigEndian>()?;
+            fields.read_u32::<BigEndian>()?; // Sample description index
+            per_chunk.push((first_chunk, samples));
+        }
+        
+        let (offsets_box, wide) = match find(stbl, b"co64") {
+            Some(co64) => (co64, true),
+            None => (table(b"stco")?, false),
+        };
+        let offsets = body(reader, offsets_box)?;
+        let mut fields = offsets.get(4..).unwrap_or_default();
+        let chunks = fields.read_u32::<BigEndian>()?;
+        
+        let mut frames = Vec::with_capacity(sizes.len());
+        let mut sample = 0;
+        for chunk in 1..=chunks {
+            let mut offset = if wide { fields.read_u64::<BigEndian>()? } else { fields.read_u32::<BigEndian>()? as u64 };
+            let samples = per_chunk.iter().rev()
+                .find(|(first, _)| *first <= chunk)
+                .map_or(0, |(_, samples)| *samples);
+            for _ in 0..samples {
+                let Some(&len) = sizes.get(sample) else { break };
+                frames.push(Frame { offset, len, lacing: 0 });
+                offset += len;
+                sample += 1;
+            }
+        }
+        Ok(frames)
+    }
+}
+
+/// Matroska / WebM (EBML) elements
+mod mkv {
+    use super::*;
+    
+    /// Read an EBML variable-length integer: (value, length, all value bits set)
+    fn read_vint<R: Read>(reader: &mut R, keep_marker: bool) -> Result<(u64, u64, bool)> {
+        let first = reader.read_u8()?;
+        let len = first.leading_zeros() as u64 + 1;
+        if len > 8 {
+            return Err(anyhow!("Invalid EBML variable-length integer"));
+        }
+        let marker = 1u64 << (7 * len);
+        let mut value = first as u64;
+        for _ in 1..len {
+            value = (value << 8) | reader.read_u8()? as u64;
+        }
+        let bits = value & (marker - 1);
+        let all_ones = bits == marker - 1;
+        Ok((if keep_marker { value } else { bits }, len, all_ones))
+    }
+    
+    /// Read an element header at `pos`: (id, body start, body size if known)
+    fn element<R: Read + Seek>(reader: &mut R, pos: u64) -> Result<(u32, u64, Option<u64>)> {
+        reader.seek(SeekFrom::Start(pos))?;
+        let (id, id_len, _) = read_vint(reader, true)?;
+        let (size, size_len, unknown) = read_vint(reader, false)?;
+        Ok((id as u32, pos + id_len + size_len, (!unknown).then_some(size)))
+    }
+    
+    fn uint(body: &[u8]) -> u64 {
+        body.iter().fold(0, |acc, &b| (acc << 8) | b as u64)
+    }
+    
+    fn float(body: &[u8]) -> Option<f64> {
+        match body.len() {
+            4 => Some(f32::from_be_bytes([body[0], body[1], body[2], body[3]]) as f64),
+            8 => Some(f64::from_be_bytes(body.try_into().ok()?)),
+            _ => None,
+        }
+    }
+    
+    fn string(body: &[u8]) -> String {
+        String::from_utf8_lossy(body).trim_end_matches('\0').to_string()
+    }
+    
+    /// Every element at one level of `start..end`: (id, body start, body end)
+    fn children<R: Read + Seek>(reader: &mut R, start: u64, end: u64) -> Result<Vec<(u32, u64, u64)>> {
+        let mut elements = Vec::new();
+        let mut pos = start;
+        while pos < end {
+            let (id, body, size) = element(reader, pos)?;
+            let body_end = size.map_or(end, |s| body + s);
+            if body_end > end {
+                return Err(anyhow!("Corrupt Matroska element {:#x} at {}", id, pos));
+            }
+            elements.push((id, body, body_end));
+            pos = body_end;
+        }
+        Ok(elements)
+    }
+    
+    /// A track as described by its TrackEntry
+    struct TrackEntry {
+        number: u64,
+        audio: bool,
+        codec_id: String,
+        codec_private: Vec<u8>,
+        language: Option<String>,
+        sample_rate: f64,
+        channels: usize,
+        bit_depth: Option<usize>,
+    }
+    
+    pub(super) fn parse<R: Read + Seek>(reader: &mut R, len: u64, metadata: &mut AudioMetadata) -> Result<Vec<DemuxedTrack>> {
+        let (_, header_body, header_size) = element(reader, 0)?;
+        let mut pos = header_body + header_size.ok_or_else(|| anyhow!("Matroska EBML header has no size"))?;
+        
+        let segment = loop {
+            if pos >= len {
+                return Err(anyhow!("Matroska file has no Segment"));
+            }
+            let (id, body, size) = element(reader, pos)?;
+            if id == ebml::SEGMENT {
+                break (body, size.map_or(len, |s| (body + s).min(len)));
+            }
+            pos = body + size.ok_or_else(|| anyhow!("Unknown-size element {:#x} before the Segment", id))?;
+        };
+        
+        let mut timestamp_scale = 1_000_000u64;
+        let mut duration = None;
+        let mut entries = Vec::new();
+        let mut blocks: HashMap<u64, Vec<Frame>> = HashMap::new();
+        
+        let (mut pos, end) = segment;
+        while pos < end {
+            let (id, body, size) = element(reader, pos)?;
+            let body_end = size.map_or(end, |s| (body + s).min(end));
+            match id {
+                ebml::INFO => {
+                    for (child, start, child_end) in children(reader, body, body_end)? {
+                        let value = read_at(reader, start, child_end - start)?;
+                        match child {
+                            ebml::TIMESTAMP_SCALE => timestamp_scale = uint(&value),
+                            ebml::DURATION => duration = float(&value),
+                            ebml::TITLE => metadata.title = Some(string(&value)),
+                            _ => {}
+                        }
+                    }
+                }
+                ebml::TRACKS => {
+                    for (child, start, child_end) in children(reader, body, body_end)? {
+                        if child == ebml::TRACK_ENTRY {
+                            entries.push(track_entry(reader, start, child_end)?);
+                        }
+                    }
+                }
+                ebml::CLUSTER => {
+                    // Clusters may be unknown-size (live recordings): they end at the next top-level element
+                    let cluster_end = scan_cluster(reader, body, body_end, size.is_none(), &mut blocks)?;
+                    pos = cluster_end;
+                    continue;
+                }
+                _ => {}
+            }
+            pos = body_end;
+        }
+        
+        let duration_secs = duration.map(|d| d * timestamp_scale as f64 / 1e9);
+        metadata.duration_secs = duration_secs;
+        
+        Ok(entries.into_iter()
+            .filter(|entry| entry.audio)
+            .map(|entry| {
+                let (codec, name) = codec(&entry);
+                DemuxedTrack {
+                    info: AudioTrack {
+                        id: entry.number as u32,
+                        codec: name,
+                        sample_rate: entry.sample_rate.round() as u32,
+                        channels: entry.channels,
+                        bit_depth: entry.bit_depth,
+                        language: entry.language.clone(),
+                        duration_secs,
+                        decodable: !matches!(codec, TrackCodec::Other),
+                    },
+                    codec,
+                    frames: blocks.remove(&entry.number).unwrap_or_default(),
+                    edit: None,
+                }
+            })
+            .collect())
+    }
+    
+    fn track_entry<R: Read + Seek>(reader: &mut R, start: u64, end: u64) -> Result<TrackEntry> {
+        let mut entry = TrackEntry {
+            number: 0,
+            audio: false,
+            codec_id: String::new(),
+            codec_private: Vec::new(),
+            language: Some("eng".to_string()), // The Matroska default
+            sample_rate: 8000.0,
+            channels: 1,
+            bit_depth: None,
+        };
+        for (id, body, body_end) in children(reader, start, end)? {
+            if id == ebml::AUDIO {
+                for (child, child_start, child_end) in children(reader, body, body_end)? {
+                    let value = read_at(reader, child_start, child_end - child_start)?;
+                    match child {
+                        ebml::SAMPLING_FREQUENCY => entry.sample_rate = float(&value).unwrap_or(entry.sample_rate),
+                        ebml::CHANNELS => entry.channels = uint(&value) as usize,
+                        ebml::BIT_DEPTH => entry.bit_depth = Some(uint(&value) as usize),
+                        _ => {}
+                    }
+                }
+                continue;
+            }
+            let value = read_at(reader, body, body_end - body)?;
+            match id {
+                ebml::TRACK_NUMBER => entry.number = uint(&value),
+                ebml::TRACK_TYPE => entry.audio = uint(&value) == ebml::TRACK_TYPE_AUDIO,
+                ebml::CODEC_ID => entry.codec_id = string(&value),
+                ebml::CODEC_PRIVATE => entry.codec_private = value,
+                ebml::LANGUAGE => entry.language = Some(string(&value)).filter(|l| l != "und"),
+                ebml::LANGUAGE_BCP47 => entry.language = Some(string(&value)).filter(|l| l != "und"),
+                _ => {}
+            }
+        }
+        Ok(entry)
+    }
+    
+    fn codec(entry: &TrackEntry) -> (TrackCodec, String) {
+        let codec = match entry.codec_id.as_str() {
+            "A_PCM/INT/LIT" => TrackCodec::Pcm { big_endian: false, float: false },
+            "A_PCM/INT/BIG" => TrackCodec::Pcm { big_endian: true, float: false },
+            "A_PCM/FLOAT/IEEE" => TrackCodec::Pcm { big_endian: false, float: true },
+            "A_FLAC" => TrackCodec::Flac { header: entry.codec_private.clone() },
+            "A_AAC" if is_aac_lc(&entry.codec_private) => TrackCodec::Aac { config: entry.codec_private.clone() },
+            _ => TrackCodec::Other,
+        };
+        let name = match entry.codec_id.as_str() {
+            id if id.starts_with("A_PCM/") => "pcm".to_string(),
+            "A_FLAC" => "flac".to_string(),
+            id if id.starts_with("A_AAC") => "aac".to_string(),
+            "A_OPUS" => "opus".to_string(),
+            "A_VORBIS" => "vorbis".to_string(),
+            "A_MPEG/L3" => "mp3".to_string(),
+            "A_AC3" => "ac3".to_string(),
+            "A_EAC3" => "eac3".to_string(),
+            other => other.trim_start_matches("A_").to_lowercase(),
+        };
+        (codec, name)
+    }
+    
+    /// Record where each block of a cluster is, returning where the cluster ends
+    ///
+    /// Only the block headers are read (to learn the track number) - the
+    /// frames themselves, video included, are skipped.
+    fn scan_cluster<R: Read + Seek>(
+        reader: &mut R,
+        start: u64,
+        end: u64,
+        unknown_size: bool,
+        blocks: &mut HashMap<u64, Vec<Frame>>,
+    ) -> Result<u64> {
+        let mut pos = start;
+        while pos < end {
+            let (id, body, size) = element(reader, pos)?;
+            if unknown_size && ebml::TOP_LEVEL.contains(&id) {
+                return Ok(pos);
+            }
+            let body_end = size.map_or(end, |s| (body + s).min(end));
+            match id {
+                ebml::SIMPLE_BLOCK => record_block(reader, body, body_end, blocks)?,
+                ebml::BLOCK_GROUP => {
+                    for (child, child_start, child_end) in children(reader, body, body_end)? {
+                        if child == ebml::BLOCK {
+                            record_block(reader, child_start, child_end, blocks)?;
+                        }
+                    }
+                }
+                _ => {}
+            }
+            pos = body_end;
+        }
+        Ok(end)
+    }
+    
+    fn record_block<R: Read + Seek>(reader: &mut R, start: u64, end: u64, blocks: &mut HashMap<u64, Vec<Frame>>) -> Result<()> {
+        reader.seek(SeekFrom::Start(start))?;
+        let (track, track_len, _) = read_vint(reader, false)?;
+        reader.read_i16::<BigEndian>()?; // Timestamp relative to the cluster
+        let flags = reader.read_u8()?;
+        let data = start + track_len + 3;
+        if data > end {
+            return Err(anyhow!("Truncated Matroska block at {}", start));
+        }
+        blocks.entry(track).or_default().push(Frame { offset: data, len: end - data, lacing: (flags >> 1) & 0x03 });
+        Ok(())
+    }
+    
+    /// Split a block's data into its frames according to its lacing
+    pub(super) fn unlace(block: &[u8], lacing: u8) -> Result<Vec<&[u8]>> {
+        if lacing == 0 {
+            return Ok(vec![block]);
+        }
+        let corrupt = || anyhow!("Corrupt Matroska lacing");
+        let count = *block.first().ok_or_else(corrupt)? as usize + 1;
+        let mut cursor = Cursor::new(&block[1..]);
+        
+        let mut sizes = Vec::with_capacity(count);
+        match lacing {
+            // Xiph: each size is a run of 255s plus a final byte
+            1 => {
+                for _ in 1..count {
+                    let mut size = 0;
+                    loop {
+                        let byte = cursor.read_u8()? as usize;
+                        size += byte;
+                        if byte != 255 {
+                            break;
+                        }
+                    }
+                    sizes.push(size);
+                }
+            }
+            // Fixed: equal sizes, nothing stored
+            2 => {
+                let each = (block.len() - 1) / count;
+                sizes = vec![each; count - 1];
+            }
+            // EBML: first size as a vint, then signed differences
+            _ => {
+                let (first, _, _) = read_vint(&mut cursor, false)?;
+                let mut size = first as i64;
+                sizes.push(first as usize);
+                for _ in 2..count {
+                    let (raw, len, _) = read_vint(&mut cursor, false)?;
+                    size += raw as i64 - ((1i64 << (7 * len - 1)) - 1);
+                    sizes.push(usize::try_from(size).map_err(|_| corrupt())?);
+                }
+            }
+        }
+        
+        let mut pos = 1 + cursor.position() as usize;
+        let mut frames = Vec::with_capacity(count);
+        for size in sizes {
+            frames.push(block.get(pos..pos + size).ok_or_else(corrupt)?);
+            pos += size;
+        }
+        frames.push(block.get(pos..).ok_or_else(corrupt)?);
+        Ok(frames)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// One MP4 box
+    fn mp4_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(body);
+        out
+    }
+    
+    fn full_box(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        mp4_box(kind, &[&[0u8; 4][..], body].concat())
+    }
+    
+    /// Packed ISO 639-2 language for `mdhd`
+    fn packed_language(code: &str) -> [u8; 2] {
+        let packed = code.bytes().fold(0u16, |acc, c| (acc << 5) | (c - 0x60) as u16);
+        packed.to_be_bytes()
+    }
+    
+    /// A `trak` whose samples are laid out as one chunk at `chunk_offset`
+    #[allow(clippy::too_many_arguments)]
+    fn mp4_track(id: u32, handler: &[u8; 4], entry: Vec<u8>, sample_sizes: &[u32], chunk_offset: u32, language: &str, duration: u32, timescale: u32) -> Vec<u8> {
+        let mut tkhd = vec![0u8; 8];
+        tkhd.extend_from_slice(&id.to_be_bytes());
+        tkhd.extend_from_slice(&[0u8; 60]);
+        
+        let mut mdhd = vec![0u8; 8];
+        mdhd.extend_from_slice(&timescale.to_be_bytes());
+        mdhd.extend_from_slice(&duration.to_be_bytes());
+        mdhd.extend_from_slice(&packed_language(language));
+        mdhd.extend_from_slice(&[0u8; 2]);
+        
+        let hdlr = [&[0u8; 4][..], handler, &[0u8; 13]].concat();
+        let stsd = full_box(b"stsd", &[&1u32.to_be_bytes()[..], &entry].concat());
+        let mut stsz = vec![0u8; 4];
+        stsz.extend_from_slice(&(sample_sizes.len() as u32).to_be_bytes());
+        for size in sample_sizes {
+            stsz.extend_from_slice(&size.to_be_bytes());
+        }
+        let stsc = [1u32, 1, sample_sizes.len() as u32, 1].iter().flat_map(|v| v.to_be_bytes()).collect::<Vec<_>>();
+        let stco = [1u32, chunk_offset].iter().flat_map(|v| v.to_be_bytes()).collect::<Vec<_>>();
+        let stbl = mp4_box(b"stbl", &[stsd, full_box(b"stsz", &stsz), full_box(b"stsc", &stsc), full_box(b"stco", &stco)].concat());
+        let minf = mp4_box(b"minf", &stbl);
+        let mdia = mp4_box(b"mdia", &[full_box(b"mdhd", &mdhd), full_box(b"hdlr", &hdlr), minf].concat());
+        mp4_box(b"trak", &[full_box(b"tkhd", &tkhd), mdia].concat())
+    }
+    
+    fn audio_entry(kind: &[u8; 4], channels: u16, bits: u16, rate: u16, children: &[u8]) -> Vec<u8> {
+        let mut body = vec![0u8; 6];
+        body.extend_from_slice(&1u16.to_be_bytes()); // Data reference index
+        body.extend_from_slice(&[0u8; 8]);
+        body.extend_from_slice(&channels.to_be_bytes());
+        body.extend_from_slice(&bits.to_be_bytes());
+        body.extend_from_slice(&[0u8; 4]);
+        body.extend_from_slice(&rate.to_be_bytes());
+        body.extend_from_slice(&[0u8; 2]);
+        body.extend_from_slice(children);
+        mp4_box(kind, &body)
+    }
+    
+    /// A screen recording: video, an Opus commentary track and a PCM track
+    fn screen_recording(pcm: &[i16]) -> Vec<u8> {
+        let ftyp = mp4_box(b"ftyp", b"isom\0\0\0\0isommp41");
+        let pcm_bytes: Vec<u8> = pcm.iter().flat_map(|s| s.to_le_bytes()).collect();
+        let opus_bytes = vec![0xFCu8; 24];
+        let video_bytes = vec![0xEEu8; 64];
+        let mdat_body = [video_bytes.clone(), opus_bytes.clone(), pcm_bytes.clone()].concat();
+        let mdat = mp4_box(b"mdat", &mdat_body);
+        let video_at = (ftyp.len() + 8) as u32;
+        let opus_at = video_at + video_bytes.len() as u32;
+        let pcm_at = opus_at + opus_bytes.len() as u32;
+        
+        let mut mvhd = vec![0u8; 8];
+        mvhd.extend_from_slice(&1000u32.to_be_bytes());
+        mvhd.extend_from_slice(&((pcm.len() as u32 / 2) * 1000 / 8000).to_be_bytes());
+        mvhd.extend_from_slice(&[0u8; 80]);
+        
+        let video = mp4_track(1, b"vide", mp4_box(b"avc1", &[0u8; 78]), &[64], video_at, "und", 1000, 1000);
+        let opus = mp4_track(2, b"soun", audio_entry(b"Opus", 2, 16, 48000, &[]), &[12, 12], opus_at, "fra", 48000, 48000);
+        let frames = (pcm.len() / 2) as u32;
+        let pcm_track = mp4_track(3, b"soun", audio_entry(b"sowt", 2, 16, 8000, &[]), &vec![4; frames as usize], pcm_at, "eng", frames, 8000);
+        
+        let title = mp4_box(b"\xA9nam", &mp4_box(b"data", &[&[0, 0, 0, 1, 0, 0, 0, 0][..], b"Standup recording"].concat()));
+        let udta = mp4_box(b"udta", &full_box(b"meta", &mp4_box(b"ilst", &title)));
+        let moov = mp4_box(b"moov", &[full_box(b"mvhd", &mvhd), video, opus, pcm_track, udta].concat());
+        [ftyp, mdat, moov].concat()
+    }
+    
+    #[test]
+    fn test_mp4_lists_audio_tracks_and_decodes_pcm() {
+        let pcm: Vec<i16> = (0..1600).map(|i| ((i as f64 * 0.05).sin() * 12_000.0) as i16).collect();
+        let mut container = Container::open(Cursor::new(screen_recording(&pcm))).unwrap();
+        assert_eq!(container.kind(), ContainerKind::Mp4);
+        assert_eq!(container.metadata().title.as_deref(), Some("Standup recording"));
+        assert_eq!(container.metadata().duration_secs, Some(0.1));
+        
+        // The video track isn't listed at all
+        let tracks = container.tracks();
+        assert_eq!(tracks.iter().map(|t| t.id).collect::<Vec<_>>(), vec![2, 3]);
+        assert_eq!(tracks[0].codec, "opus");
+        assert_eq!(tracks[0].language.as_deref(), Some("fra"));
+        assert_eq!((tracks[0].sample_rate, tracks[0].channels, tracks[0].duration_secs), (48000, 2, Some(1.0)));
+        assert!(!tracks[0].decodable);
+        assert!(tracks[1].decodable);
+        
+        let err = container.decode_track(Some(2)).err().unwrap();
+        assert!(err.to_string().contains("Can't decode opus audio (track 2)"));
+        
+        // No choice made: the first track we can decode
+        let loaded = container.decode_track(None).unwrap();
+        assert_eq!(loaded.file_format, AudioFileFormat::Mp4);
+        assert_eq!((loaded.format.channels, loaded.format.bit_depth), (2, 16));
+        assert_eq!(loaded.samples.len(), pcm.len());
+        for (decoded, source) in loaded.samples.iter().zip(&pcm) {
+            assert_eq!(*decoded, *source as f64 / 32768.0);
+        }
+        let duration = loaded.samples.len() as f64 / 2.0 / loaded.format.sample_rate.as_f64();
+        assert_eq!(Some(duration), loaded.metadata.as_ref().unwrap().duration_secs);
+        assert_eq!(loaded.metadata.unwrap().language.as_deref(), Some("eng"));
+    }
+    
+    /// 0.5 s of a 440 Hz tone at 16 kHz mono, encoded by fdk-aac (AAC-LC, 32 kb/s).
+    /// Its edit list cuts the encoder's 2048 samples of priming.
+    const AAC_TONE: &[u8] = include_bytes!("../testdata/aac_tone.mp4");
+    
+    #[test]
+    fn test_mp4_decodes_aac_trimmed_to_its_edit_list() {
+        let source: Vec<f64> = (0..8000)
+            .map(|i| (2.0 * std::f64::consts::PI * 440.0 * i as f64 / 16000.0).sin() * 0.5)
+            .collect();
+        let mut container = Container::open(Cursor::new(AAC_TONE)).unwrap();
+        
+        let tracks = container.tracks();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!((tracks[0].codec.as_str(), tracks[0].sample_rate, tracks[0].channels), ("aac", 16000, 1));
+        assert_eq!(tracks[0].duration_secs, Some(0.5));
+        assert!(tracks[0].decodable);
+        
+        let loaded = container.decode_track(None).unwrap();
+        assert_eq!(loaded.samples.len(), source.len());
+        assert_eq!((loaded.format.sample_rate.as_f64(), loaded.format.channels), (16000.0, 1));
+        let duration = loaded.samples.len() as f64 / loaded.format.sample_rate.as_f64();
+        assert_eq!(loaded.metadata.unwrap().duration_secs, Some(duration));
+        
+        // Lossy, but lined up with the source - a one-sample slip would be ~0.06
+        let rms = (loaded.samples.iter().zip(&source).map(|(d, s)| (d - s).powi(2)).sum::<f64>() / source.len() as f64).sqrt();
+        assert!(rms < 0.01, "rms error {}", rms);
+    }
+    
+    /// One EBML element (8-byte size, which every reader must accept)
+    fn ebml_element(id: u32, body: &[u8]) -> Vec<u8> {
+        let mut out: Vec<u8> = id.to_be_bytes().iter().copied().skip_while(|&b| b == 0).collect();
+        out.push(0x01);
+        out.extend_from_slice(&(body.len() as u64).to_be_bytes()[1..]);
+        out.extend_from_slice(body);
+        out
+    }
+    
+    #[test]
+    fn test_matroska_laced_pcm_track() {
+        let pcm: Vec<i16> = (0..300).map(|i| (i * 97 % 20_000) as i16 - 10_000).collect();
+        let bytes: Vec<u8> = pcm.iter().flat_map(|s| s.to_le_bytes()).collect();
+        
+        let header = ebml_element(ebml::HEADER, &ebml_element(0x4282, b"matroska"));
+        let info = ebml_element(ebml::INFO, &[
+            ebml_element(ebml::TIMESTAMP_SCALE, &[0x0F, 0x42, 0x40]),
+            ebml_element(ebml::DURATION, &2500f64.to_be_bytes()),
+            ebml_element(ebml::TITLE, b"Pairing session"),
+        ].concat());
+        let video = ebml_element(ebml::TRACK_ENTRY, &[
+            ebml_element(ebml::TRACK_NUMBER, &[1]),
+            ebml_element(ebml::TRACK_TYPE, &[1]),
+            ebml_element(ebml::CODEC_ID, b"V_MPEG4/ISO/AVC"),
+        ].concat());
+        let audio = ebml_element(ebml::TRACK_ENTRY, &[
+            ebml_element(ebml::TRACK_NUMBER, &[2]),
+            ebml_element(ebml::TRACK_TYPE, &[2]),
+            ebml_element(ebml::CODEC_ID, b"A_PCM/INT/LIT"),
+            ebml_element(ebml::LANGUAGE, b"deu"),
+            ebml_element(ebml::AUDIO, &[
+                ebml_element(ebml::SAMPLING_FREQUENCY, &16000f64.to_be_bytes()),
+                ebml_element(ebml::CHANNELS, &[1]),
+                ebml_element(ebml::BIT_DEPTH, &[16]),
+            ].concat()),
+        ].concat());
+        let tracks = ebml_element(ebml::TRACKS, &[video, audio].concat());
+        
+        // A video block, then the audio as one unlaced and one Xiph-laced block
+        let video_block = ebml_element(ebml::SIMPLE_BLOCK, &[&[0x81, 0, 0, 0x80][..], &[0xEE; 32]].concat());
+        let (first, rest) = bytes.split_at(200);
+        let plain = ebml_element(ebml::SIMPLE_BLOCK, &[&[0x82, 0, 0, 0x80][..], first].concat());
+        let (a, b) = rest.split_at(300);
+        let mut laced = vec![0x82, 0, 5, 0x82, 1];
+        laced.extend_from_slice(&[255, 45]); // First frame: 300 bytes
+        laced.extend_from_slice(a);
+        laced.extend_from_slice(b);
+        let laced = ebml_element(ebml::BLOCK_GROUP, &ebml_element(ebml::BLOCK, &laced));
+        let cluster = ebml_element(ebml::CLUSTER, &[ebml_element(0xE7, &[0]), video_block, plain, laced].concat());
+        
+        let segment = ebml_element(ebml::SEGMENT, &[info, tracks, cluster].concat());
+        let mut container = Container::open(Cursor::new([header, segment].concat())).unwrap();
+        assert_eq!(container.kind(), ContainerKind::Matroska);
+        assert_eq!(container.metadata().title.as_deref(), Some("Pairing session"));
+        assert_eq!(container.metadata().duration_secs, Some(2.5));
+        
+        let tracks = container.tracks();
+        assert_eq!(tracks.len(), 1);
+        assert_eq!((tracks[0].id, tracks[0].codec.as_str(), tracks[0].sample_rate), (2, "pcm", 16000));
+        assert_eq!(tracks[0].language.as_deref(), Some("deu"));
+        
+        let loaded = container.decode_track(Some(2)).unwrap();
+        let expected: Vec<f64> = pcm.iter().map(|&s| s as f64 / 32768.0).collect();
+        assert_eq!(loaded.samples, expected);
+        assert!(container.decode_track(Some(1)).is_err()); // Video isn't a track we know
+    }
+}
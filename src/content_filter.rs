@@ -0,0 +1,220 @@
+//! Content filters - what the DJ must never play
+//!
+//! A `ContentFilter` lives in the DJ config (`/config/dj.json`) and is
+//! checked by `FilterGuard::admit` before anything is suggested, searched up,
+//! queued or played: activity favourites, exploration picks, playlist
+//! generation and mood adjustments all go through that one call. Whatever it
+//! turns away is counted in `FilterStats`, which shows up in the DJ stats.
+//!
+//! The old Polka joke is now just the default `banned_genres`. Sorry, Hue. 🪗
+
+use std::collections::BTreeMap;
+use serde::{Serialize, Deserialize};
+use anyhow::{Result, anyhow};
+
+use crate::mcp_server::TrackSuggestion;
+use crate::mood_engine::Genre;
+use crate::tidal_dj::TidalTrack;
+
+/// Rules a track has to pass before the DJ may offer it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ContentFilter {
+    pub banned_genres: Vec<Genre>,
+    
+    /// Artist names, matched ignoring case and surrounding spaces
+    pub banned_artists: Vec<String>,
+    
+    /// Streaming track ids (e.g. `tidal_...`)
+    pub banned_track_ids: Vec<String>,
+    
+    /// Longest track allowed, in seconds
+    pub max_duration: Option<u32>,
+    
+    pub explicit_allowed: bool,
+}
+
+impl Default for ContentFilter {
+    fn default() -> Self {
+        Self {
+            banned_genres: vec![Genre::Polka],
+            banned_artists: Vec::new(),
+            banned_track_ids: Vec::new(),
+            max_duration: None,
+            explicit_allowed: true,
+        }
+    }
+}
+
+/// The parts of a track a filter looks at
+///
+/// Suggestions don't know their id, length or explicitness yet; those rules
+/// only apply once a search has turned them into real tracks.
+#[derive(Debug, Clone, Copy)]
+pub struct Candidate<'a> {
+    pub artist: &'a str,
+    pub genre: Option<&'a Genre>,
+    pub track_id: Option<&'a str>,
+    pub duration_seconds: Option<u32>,
+    pub explicit: bool,
+}
+
+impl<'a> From<&'a TrackSuggestion> for Candidate<'a> {
+    fn from(suggestion: &'a TrackSuggestion) -> Self {
+        Self {
+            artist: &suggestion.artist,
+            genre: Some(&suggestion.genre),
+            track_id: None,
+            duration_seconds: None,
+            explicit: false,
+        }
+    }
+}
+
+impl<'a> Candidate<'a> {
+    /// A streaming track, with the genre of the suggestion it was found for
+    pub fn track(track: &'a TidalTrack, genre: Option<&'a Genre>) -> Self {
+        Self {
+            artist: &track.artist,
+            genre,
+            track_id: Some(&track.id),
+            duration_seconds: Some(track.duration_seconds),
+            explicit: track.explicit,
+        }
+    }
+}
+
+/// Which rule a track broke
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Violation {
+    Genre,
+    Artist,
+    TrackId,
+    Duration,
+    Explicit,
+}
+
+impl ContentFilter {
+    pub fn validate(&self) -> Result<()> {
+        if self.max_duration == Some(0) {
+            return Err(anyhow!("max_duration must be at least 1 second"));
+        }
+        if self.banned_artists.iter().chain(&self.banned_track_ids).any(|s| s.trim().is_empty()) {
+            return Err(anyhow!("Banned artists and track ids can't be blank"));
+        }
+        Ok(())
+    }
+    
+    /// The first rule `candidate` breaks, if any
+    pub fn check(&self, candidate: &Candidate) -> Option<Violation> {
+        if candidate.genre.is_some_and(|g| self.banned_genres.contains(g)) {
+            return Some(Violation::Genre);
+        }
+        if self.banned_artists.iter().any(|a| a.trim().eq_ignore_ascii_case(candidate.artist.trim())) {
+            return Some(Violation::Artist);
+        }
+        if candidate.track_id.is_some_and(|id| self.banned_track_ids.iter().any(|b| b == id)) {
+            return Some(Violation::TrackId);
+        }
+        if let (Some(max), Some(duration)) = (self.max_duration, candidate.duration_seconds) {
+            if duration > max {
+                return Some(Violation::Duration);
+            }
+        }
+        if candidate.explicit && !self.explicit_allowed {
+            return Some(Violation::Explicit);
+        }
+        None
+    }
+}
+
+/// What the filter has turned away since the DJ started
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FilterStats {
+    /// One per track turned away each time it was up for consideration
+    pub blocked: u64,
+    
+    pub by_rule: BTreeMap<Violation, u64>,
+    
+    /// The most recent blocked track ("Artist - Title" or the artist alone)
+    pub last_blocked: Option<String>,
+}
+
+/// A filter plus the count of everything it blocked
+#[derive(Debug, Clone, Default)]
+pub struct FilterGuard {
+    pub filter: ContentFilter,
+    pub stats: FilterStats,
+}
+
+impl FilterGuard {
+    pub fn new(filter: ContentFilter) -> Self {
+        Self { filter, stats: FilterStats::default() }
+    }
+    
+    /// Let `candidate` through, or count it as blocked
+    ///
+    /// `label` is only used for `FilterStats::last_blocked`.
+    pub fn admit(&mut self, candidate: Candidate, label: impl FnOnce() -> String) -> bool {
+        match self.filter.check(&candidate) {
+            None => true,
+            Some(violation) => {
+                self.stats.blocked += 1;
+                *self.stats.by_rule.entry(violation).or_insert(0) += 1;
+                self.stats.last_blocked = Some(label());
+                false
+            }
+        }
+    }
+    
+    /// `admit` for a suggestion
+    pub fn admit_suggestion(&mut self, suggestion: &TrackSuggestion) -> bool {
+        self.admit(suggestion.into(), || format!("{} - {}", suggestion.artist, suggestion.title))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_filter_rules_and_stats() {
+        let mut guard = FilterGuard::new(ContentFilter {
+            banned_artists: vec![" nickelback".to_string()],
+            max_duration: Some(600),
+            explicit_allowed: false,
+            ..ContentFilter::default()
+        });
+        let suggestion = |artist: &str, genre: Genre| TrackSuggestion {
+            artist: artist.to_string(),
+            title: "Song".to_string(),
+            genre,
+            reason: String::new(),
+            predicted_effect: String::new(),
+            confidence: 0.5,
+        };
+        
+        assert!(guard.admit_suggestion(&suggestion("Tycho", Genre::Electronic)));
+        assert!(!guard.admit_suggestion(&suggestion("Weird Al", Genre::Polka)));
+        assert!(!guard.admit_suggestion(&suggestion("NICKELBACK", Genre::HardRock)));
+        
+        // Length and explicitness only matter for real tracks
+        let artist = "Tycho".to_string();
+        let track = |duration_seconds, explicit| Candidate {
+            artist: &artist,
+            genre: None,
+            track_id: Some("tidal_1"),
+            duration_seconds: Some(duration_seconds),
+            explicit,
+        };
+        assert_eq!(guard.filter.check(&track(240, false)), None);
+        assert_eq!(guard.filter.check(&track(1200, false)), Some(Violation::Duration));
+        assert_eq!(guard.filter.check(&track(240, true)), Some(Violation::Explicit));
+        
+        assert_eq!(guard.stats.blocked, 2);
+        assert_eq!(guard.stats.by_rule[&Violation::Genre], 1);
+        assert_eq!(guard.stats.last_blocked.as_deref(), Some("NICKELBACK - Song"));
+        assert!(ContentFilter { max_duration: Some(0), ..ContentFilter::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,339 @@
+//! Store daemon - one open Mem8Lite store behind a unix socket
+//!
+//! Every `mem8` invocation used to open its store from scratch: index load,
+//! key scan, cold cache. `mem8 daemon STORE` opens it once and listens on
+//! `<store>.sock`; `mem8 ls`/`put`/`cat`/`caps` notice the socket and send
+//! their request there instead, falling back to opening the store themselves
+//! when nobody's listening. Every client gets its own thread, all sharing one
+//! `SharedMem8Lite`, so reads from several scripts run side by side.
+//!
+//! The wire protocol is one JSON object per line each way: a `Request`
+//! tagged by `op`, answered by a `Response` tagged by `status`. Packet bytes
+//! travel hex-encoded.
+//!
+//! The daemon stops on SIGINT/SIGTERM or once it's been idle for its idle
+//! timeout, and closes the store on the way out. Hue leaves it running
+//! all afternoon while the DJ scripts poke at it. 🛰️
+
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde::{Serialize, Deserialize};
+use anyhow::{Result, anyhow};
+
+use crate::capabilities::StoreCapabilities;
+use crate::compress::CompressionMode;
+use crate::lite::{parse_signature, Mem8Lite, PacketSummary, SizeEstimate};
+use crate::shared::SharedMem8Lite;
+use crate::shutdown;
+
+/// How long a daemon without clients stays up by default
+pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10 * 60);
+
+/// How often the accept loop checks for shutdown and idleness
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Where the daemon for a store listens (`<store>.sock`)
+pub fn socket_path(store: &Path) -> PathBuf {
+    let mut socket = store.as_os_str().to_owned();
+    socket.push(".sock");
+    PathBuf::from(socket)
+}
+
+/// One request from a client
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum Request {
+    /// Every packet, as `Mem8Lite::iter` sees it
+    List,
+    
+    /// What storing `data` (hex) would cost, compressed with `compression`
+    Estimate { data: String, compression: String },
+    
+    /// Store `data` (hex), compressed with `compression`
+    Put { data: String, compression: String },
+    
+    /// A packet's data, by hex signature
+    Cat { signature: String },
+    
+    Caps,
+}
+
+/// The daemon's answer to one `Request`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum Response {
+    Packets { packets: Vec<PacketSummary> },
+    Estimate { estimate: SizeEstimate },
+    Stored { signature: String },
+    Data { data: String },
+    Caps { capabilities: StoreCapabilities },
+    Error { message: String },
+}
+
+/// A store being served on its socket
+pub struct Daemon {
+    storage: SharedMem8Lite,
+    listener: UnixListener,
+    socket: PathBuf,
+    idle_timeout: Duration,
+}
+
+impl Daemon {
+    /// Take over `storage` and listen on `socket`
+    ///
+    /// A socket file nobody answers on (left behind by a daemon that was
+    /// killed) is replaced; one with a live daemon behind it is an error.
+    pub fn bind(storage: Mem8Lite, socket: &Path) -> Result<Self> {
+        if socket.exists() {
+            if UnixStream::connect(socket).is_ok() {
+                return Err(anyhow!("a mem8 daemon is already listening on {}", socket.display()));
+            }
+            std::fs::remove_file(socket)?;
+        }
+        let listener = UnixListener::bind(socket)?;
+        listener.set_nonblocking(true)?;
+        Ok(Self {
+            storage: SharedMem8Lite::new(storage),
+            listener,
+            socket: socket.to_path_buf(),
+            idle_timeout: DEFAULT_IDLE_TIMEOUT,
+        })
+    }
+    
+    /// Stop once no client has been connected for this long
+    pub fn with_idle_timeout(mut self, idle_timeout: Duration) -> Self {
+        self.idle_timeout = idle_timeout;
+        self
+    }
+    
+    /// Serve clients until a shutdown is requested or the daemon goes idle,
+    /// then remove the socket and close the store
+    pub fn run(self) -> Result<()> {
+        let connected = Arc::new(AtomicUsize::new(0));
+        let last_seen = Arc::new(Mutex::new(Instant::now()));
+        let mut clients = Vec::new();
+        
+        while !shutdown::requested() {
+            match self.listener.accept() {
+                Ok((stream, _)) => {
+                    connected.fetch_add(1, Ordering::SeqCst);
+                    let storage = self.storage.clone();
+                    let connected = connected.clone();
+                    let last_seen = last_seen.clone();
+                    clients.push(std::thread::spawn(move || {
+                        if let Err(e) = serve_client(&storage, stream) {
+                            eprintln!("⚠️ mem8: daemon client failed: {}", e);
+                        }
+                        *last_seen.lock().unwrap() = Instant::now();
+                        connected.fetch_sub(1, Ordering::SeqCst);
+                    }));
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                    let idle = connected.load(Ordering::SeqCst) == 0
+                        && last_seen.lock().unwrap().elapsed() >= self.idle_timeout;
+                    if idle {
+                        break;
+                    }
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+                Err(e) => return Err(e.into()),
+            }
+            clients.retain(|client| !client.is_finished());
+        }
+        
+        // No new clients from here on; the ones still connected finish first
+        std::fs::remove_file(&self.socket)?;
+        for client in clients {
+            let _ = client.join();
+        }
+        match self.storage.into_inner() {
+            Ok(storage) => Ok(storage.close()?),
+            Err(shared) => Ok(shared.sync()?),
+        }
+    }
+}
+
+/// Answer one client's requests until it hangs up
+fn serve_client(storage: &SharedMem8Lite, stream: UnixStream) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => handle(storage, request).unwrap_or_else(|e| Response::Error { message: e.to_string() }),
+            Err(e) => Response::Error { message: format!("bad request: {}", e) },
+        };
+        serde_json::to_writer(&mut writer, &response)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+    }
+    Ok(())
+}
+
+fn handle(storage: &SharedMem8Lite, request: Request) -> Result<Response> {
+    Ok(match request {
+        Request::List => Response::Packets { packets: storage.read().iter().collect() },
+        Request::Estimate { data, compression } => {
+            let data = hex::decode(data)?;
+            let compression: CompressionMode = compression.parse()?;
+            let mut store = storage.write();
+            let mode = store.compression();
+            store.set_compression(compression);
+            let estimate = store.estimate_stored_size(&data, 0);
+            store.set_compression(mode);
+            Response::Estimate { estimate }
+        }
+        Request::Put { data, compression } => {
+            let data = hex::decode(data)?;
+            let compression: CompressionMode = compression.parse()?;
+            let mut store = storage.write();
+            let mode = store.compression();
+            store.set_compression(compression);
+            let stored = store.store(&data, None);
+            store.set_compression(mode);
+            Response::Stored { signature: hex::encode(stored?) }
+        }
+        Request::Cat { signature } => {
+            let signature = parse_signature(&signature)?;
+            Response::Data { data: hex::encode(storage.retrieve(&signature)?) }
+        }
+        Request::Caps => Response::Caps { capabilities: storage.read().store_capabilities() },
+    })
+}
+
+/// A connection to the daemon serving a store
+pub struct DaemonClient {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
+
+impl DaemonClient {
+    /// Connect to the daemon for `store`, if one is running
+    pub fn connect(store: &Path) -> Option<Self> {
+        let stream = UnixStream::connect(socket_path(store)).ok()?;
+        let writer = stream.try_clone().ok()?;
+        Some(Self { reader: BufReader::new(stream), writer })
+    }
+    
+    /// Send one request and wait for its answer (`Response::Error` comes back as `Err`)
+    pub fn request(&mut self, request: &Request) -> Result<Response> {
+        serde_json::to_writer(&mut self.writer, request)?;
+        self.writer.write_all(b"\n")?;
+        self.writer.flush()?;
+        
+        let mut line = String::new();
+        if self.reader.read_line(&mut line)? == 0 {
+            return Err(anyhow!("the mem8 daemon hung up"));
+        }
+        match serde_json::from_str(&line)? {
+            Response::Error { message } => Err(anyhow!(message)),
+            response => Ok(response),
+        }
+    }
+    
+    pub fn list(&mut self) -> Result<Vec<PacketSummary>> {
+        match self.request(&Request::List)? {
+            Response::Packets { packets } => Ok(packets),
+            other => Err(unexpected(other)),
+        }
+    }
+    
+    pub fn estimate(&mut self, data: &[u8], compression: CompressionMode) -> Result<SizeEstimate> {
+        let request = Request::Estimate { data: hex::encode(data), compression: compression.to_string() };
+        match self.request(&request)? {
+            Response::Estimate { estimate } => Ok(estimate),
+            other => Err(unexpected(other)),
+        }
+    }
+    
+    pub fn put(&mut self, data: &[u8], compression: CompressionMode) -> Result<[u8; 32]> {
+        let request = Request::Put { data: hex::encode(data), compression: compression.to_string() };
+        match self.request(&request)? {
+            Response::Stored { signature } => Ok(parse_signature(&signature)?),
+            other => Err(unexpected(other)),
+        }
+    }
+    
+    pub fn cat(&mut self, signature: &[u8; 32]) -> Result<Vec<u8>> {
+        match self.request(&Request::Cat { signature: hex::encode(signature) })? {
+            Response::Data { data } => Ok(hex::decode(data)?),
+            other => Err(unexpected(other)),
+        }
+    }
+    
+    pub fn caps(&mut self) -> Result<StoreCapabilities> {
+        match self.request(&Request::Caps)? {
+            Response::Caps { capabilities } => Ok(capabilities),
+            other => Err(unexpected(other)),
+        }
+    }
+}
+
+fn unexpected(response: Response) -> anyhow::Error {
+    anyhow!("unexpected answer from the mem8 daemon: {:?}", response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    
+    #[test]
+    fn test_daemon_matches_direct_mode() {
+        let dir = tempdir().unwrap();
+        let direct_path = dir.path().join("direct.m8");
+        let served_path = dir.path().join("served.m8");
+        let socket = socket_path(&served_path);
+        
+        let daemon = Daemon::bind(Mem8Lite::new(&served_path, 1.618).unwrap(), &socket).unwrap()
+            .with_idle_timeout(Duration::from_millis(300));
+        assert!(Daemon::bind(Mem8Lite::new(dir.path().join("other.m8"), 1.0).unwrap(), &socket).is_err());
+        let server = std::thread::spawn(move || daemon.run());
+        
+        let mut direct = Mem8Lite::new(&direct_path, 1.618).unwrap();
+        let items: [(&[u8], CompressionMode); 3] = [
+            (b"first", CompressionMode::None),
+            (&[b'{'; 4096], CompressionMode::Zstd(3)),
+            (b"third", CompressionMode::None),
+        ];
+        
+        // Two clients at once: one writes, the other reads what it wrote
+        let mut writer = DaemonClient::connect(&served_path).unwrap();
+        let mut reader = DaemonClient::connect(&served_path).unwrap();
+        for (data, compression) in items {
+            direct.set_compression(compression);
+            assert_eq!(writer.estimate(data, compression).unwrap(), direct.estimate_stored_size(data, 0));
+            let signature = writer.put(data, compression).unwrap();
+            assert_eq!(signature, direct.store(data, None).unwrap());
+            assert_eq!(reader.cat(&signature).unwrap(), data);
+        }
+        
+        let strip = |packets: Vec<PacketSummary>| -> Vec<_> {
+            let mut packets: Vec<_> = packets.into_iter()
+                .map(|p| (p.signature, p.data_len, p.metadata_len, p.compression))
+                .collect();
+            packets.sort_by_key(|p| p.0);
+            packets
+        };
+        assert_eq!(strip(reader.list().unwrap()), strip(direct.iter().collect()));
+        assert_eq!(reader.caps().unwrap(), direct.store_capabilities());
+        assert!(reader.cat(&[7u8; 32]).is_err());
+        assert!(matches!(reader.request(&Request::List), Ok(Response::Packets { .. })));
+        
+        // Hanging up lets it go idle: the socket goes away and the store is closed
+        drop(writer);
+        drop(reader);
+        server.join().unwrap().unwrap();
+        assert!(!socket.exists());
+        assert!(DaemonClient::connect(&served_path).is_none());
+        let served = Mem8Lite::new(&served_path, 1.618).unwrap();
+        assert_eq!(strip(served.iter().collect()), strip(direct.iter().collect()));
+    }
+}
@@ -0,0 +1,193 @@
+//! Durability - when what Mem8Fs writes is made to survive a power cut
+//!
+//! Every write reaches the OS before it returns, but the OS can hold it in
+//! its page cache for a while, and a power cut loses whatever it was still
+//! holding. `Durability` (set with `FsConfig::with_durability`) says how
+//! often Mem8Fs waits for the disk: fsyncing after every write, at most once
+//! per interval, or only on `Mem8Fs::sync`, `close` and drop.
+//!
+//! An fsync always goes data first: `data.m8`, then `data.idx`, then
+//! `index.m8`, then `meta.m8`, so the index never points at waves the disk
+//! doesn't have yet. `FsStats::unsynced_bytes` says how much is still
+//! waiting. The fsyncs go through `SyncBackend`, which tests swap out to
+//! watch the policy at work.
+//!
+//! Hue writes it down; Trisha makes sure the ink is dry. 🖋️
+
+use std::fs::File;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use serde::{Serialize, Deserialize};
+
+/// How often Mem8Fs fsyncs what it writes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Durability {
+    /// Leave it all to the OS until `sync` - not even `meta.m8` is fsynced
+    None,
+    
+    /// Hand every write to the OS before returning (survives the process
+    /// dying, not the machine); only `meta.m8` is fsynced as it's replaced
+    #[default]
+    FlushEveryWrite,
+    
+    /// Fsync everything after every write - slow, nothing acknowledged is lost
+    FsyncEveryWrite,
+    
+    /// Fsync everything at the first write once this long has passed since the last
+    FsyncInterval(Duration),
+}
+
+/// The store files, in the order they're fsynced
+pub(crate) const DATA_FILE: &str = "data.m8";
+pub(crate) const OFFSETS_FILE: &str = "data.idx";
+pub(crate) const INDEX_FILE: &str = "index.m8";
+pub(crate) const META_FILE: &str = "meta.m8";
+
+/// Where fsyncs go: the disk, or a recorder in tests
+pub(crate) trait SyncBackend: Send + Sync {
+    /// Fsync `file`, the store file called `name`
+    fn sync(&self, name: &'static str, file: &File) -> std::io::Result<()>;
+}
+
+/// `File::sync_all`
+pub(crate) struct OsSync;
+
+impl SyncBackend for OsSync {
+    fn sync(&self, _name: &'static str, file: &File) -> std::io::Result<()> {
+        file.sync_all()
+    }
+}
+
+/// A store's policy and what's been written since its last fsync
+pub(crate) struct DurabilityState {
+    pub(crate) policy: Durability,
+    pub(crate) backend: Arc<dyn SyncBackend>,
+    
+    /// Bytes of `index.m8` and `meta.m8` written since then (the wave
+    /// storage counts its own)
+    pending: AtomicU64,
+    last_sync: Mutex<Instant>,
+}
+
+impl DurabilityState {
+    pub(crate) fn new(policy: Durability) -> Self {
+        Self {
+            policy,
+            backend: Arc::new(OsSync),
+            pending: AtomicU64::new(0),
+            last_sync: Mutex::new(Instant::now()),
+        }
+    }
+    
+    /// Does the write being saved now have to be fsynced?
+    pub(crate) fn due(&self) -> bool {
+        match self.policy {
+            Durability::None | Durability::FlushEveryWrite => false,
+            Durability::FsyncEveryWrite => true,
+            Durability::FsyncInterval(interval) => self.last_sync.lock().unwrap().elapsed() >= interval,
+        }
+    }
+    
+    /// Is `meta.m8` fsynced as it's replaced, even between syncs?
+    pub(crate) fn sync_metadata(&self) -> bool {
+        self.policy != Durability::None
+    }
+    
+    pub(crate) fn wrote(&self, bytes: u64) {
+        self.pending.fetch_add(bytes, Ordering::Relaxed);
+    }
+    
+    pub(crate) fn pending(&self) -> u64 {
+        self.pending.load(Ordering::Relaxed)
+    }
+    
+    /// Everything written so far is on disk
+    pub(crate) fn synced(&self) {
+        self.pending.store(0, Ordering::Relaxed);
+        *self.last_sync.lock().unwrap() = Instant::now();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FsConfig, Mem8Fs};
+    use tempfile::tempdir;
+    
+    /// Fsyncs for real, and notes which file each one was for
+    #[derive(Default)]
+    struct Recorder(Mutex<Vec<&'static str>>);
+    
+    impl SyncBackend for Recorder {
+        fn sync(&self, name: &'static str, file: &File) -> std::io::Result<()> {
+            self.0.lock().unwrap().push(name);
+            file.sync_all()
+        }
+    }
+    
+    fn recorded(policy: Durability) -> (tempfile::TempDir, Mem8Fs, Arc<Recorder>) {
+        let dir = tempdir().unwrap();
+        let mut fs = Mem8Fs::new_with_config(dir.path(), FsConfig::default().with_durability(policy)).unwrap();
+        let recorder = Arc::new(Recorder::default());
+        fs.durability.backend = recorder.clone();
+        (dir, fs, recorder)
+    }
+    
+    fn take(recorder: &Recorder) -> Vec<&'static str> {
+        std::mem::take(&mut *recorder.0.lock().unwrap())
+    }
+    
+    #[test]
+    fn test_fsync_every_write_goes_data_first() {
+        let (_dir, fs, recorder) = recorded(Durability::FsyncEveryWrite);
+        fs.write("/a.txt", b"on disk before write returns").unwrap();
+        assert_eq!(take(&recorder), [DATA_FILE, OFFSETS_FILE, INDEX_FILE, META_FILE]);
+        assert_eq!(fs.stats().unwrap().unsynced_bytes, 0);
+        
+        fs.append("/a.txt", b", and again").unwrap();
+        fs.delete("/a.txt").unwrap();
+        assert_eq!(take(&recorder).len(), 8);
+        
+        fs.sync().unwrap();
+        assert_eq!(take(&recorder), [DATA_FILE, OFFSETS_FILE, INDEX_FILE, META_FILE]);
+    }
+    
+    #[test]
+    fn test_lazier_policies_wait_for_sync() {
+        for policy in [Durability::None, Durability::FlushEveryWrite, Durability::FsyncInterval(Duration::from_secs(3600))] {
+            let (_dir, fs, recorder) = recorded(policy);
+            fs.write("/a.txt", b"in the page cache").unwrap();
+            fs.write("/b.txt", b"also in the page cache").unwrap();
+            let metadata_only = match policy {
+                Durability::None => vec![],
+                _ => vec![META_FILE, META_FILE],
+            };
+            assert_eq!(take(&recorder), metadata_only, "{:?}", policy);
+            
+            // Two wave records and their offsets, plus the index twice (and meta.m8 twice if nothing fsyncs it)
+            let pending = fs.stats().unwrap().unsynced_bytes;
+            let waves = 2 * (32 + 4 + 44) + 16 * (17 + 22);
+            assert!(pending > waves, "{:?}: {} pending", policy, pending);
+            
+            fs.sync().unwrap();
+            assert_eq!(take(&recorder), [DATA_FILE, OFFSETS_FILE, INDEX_FILE, META_FILE]);
+            assert_eq!(fs.stats().unwrap().unsynced_bytes, 0);
+        }
+    }
+    
+    #[test]
+    fn test_fsync_interval() {
+        let (_dir, fs, recorder) = recorded(Durability::FsyncInterval(Duration::from_millis(50)));
+        fs.write("/a.txt", b"soon").unwrap();
+        assert_eq!(take(&recorder), [META_FILE]);
+        
+        // The first write after the interval takes everything before it along
+        std::thread::sleep(Duration::from_millis(60));
+        fs.write("/b.txt", b"now").unwrap();
+        assert_eq!(take(&recorder), [DATA_FILE, OFFSETS_FILE, INDEX_FILE, META_FILE]);
+        assert_eq!(fs.stats().unwrap().unsynced_bytes, 0);
+        fs.write("/c.txt", b"later").unwrap();
+        assert_eq!(take(&recorder), [META_FILE]);
+    }
+}
@@ -0,0 +1,178 @@
+//! Mem8Error - the one error type Mem8Lite, Mem8Fs and the audio loader return
+//!
+//! The common failures are variants to match on: a missing file, packet or
+//! key is `NotFound`, a taken path or name is `AlreadyExists`, a file where a
+//! directory should be is `NotADirectory`, stored bytes
+//! that don't check out are `Corrupt`, and so on. The narrower typed errors
+//! (`SnapshotDataGone`, `SliceRejected`, `MetadataRejected`, ...) ride along
+//! in `Other` and are still reached with `err.downcast_ref::<T>()`, which
+//! looks through every variant. `Mem8Error` is a `std::error::Error`, so `?`
+//! into `anyhow::Result` keeps working in binaries and examples.
+//!
+//! Trisha likes her drawers labelled - no more guessing from the message! 🗂️
+
+use crate::InvalidPath;
+use crate::lock::StoreLocked;
+use crate::lite::{Expired, FileFormatError};
+
+/// `Result` with `Mem8Error` as the default error
+pub type Result<T, E = Mem8Error> = std::result::Result<T, E>;
+
+/// Everything that can go wrong in a store
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum Mem8Error {
+    /// No file, directory, packet, key or snapshot by that name (the message says which)
+    #[error("{0}")]
+    NotFound(String),
+    
+    /// The path or name being created is already taken
+    #[error("{0} already exists")]
+    AlreadyExists(String),
+    
+    /// A directory with something in it, where only an empty one will do
+    #[error("{0} isn't empty")]
+    DirectoryNotEmpty(String),
+    
+    /// A file where a directory was expected
+    #[error("{0} is a file, not a directory")]
+    NotADirectory(String),
+    
+    /// Stored bytes that don't decode or don't match their checksum
+    #[error("{reason} for {}", hex::encode(signature))]
+    Corrupt { signature: [u8; 32], reason: String },
+    
+    #[error(transparent)]
+    InvalidPath(#[from] InvalidPath),
+    
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    
+    /// The file isn't a store, or one from a newer build
+    #[error(transparent)]
+    FormatVersion(#[from] FileFormatError),
+    
+    #[error(transparent)]
+    Expired(#[from] Expired),
+    
+    /// Another process (or `Mem8Fs`) has the store open
+    #[error(transparent)]
+    Locked(#[from] StoreLocked),
+    
+    /// A write of new content that doesn't fit under `FsConfig::max_store_bytes`
+    #[error("Storing {needed} more bytes would take data.m8 past its quota of {limit} ({used} used)")]
+    QuotaExceeded { limit: u64, used: u64, needed: u64 },
+    
+    /// A write to a store opened with `Mem8Fs::open_read_only`
+    #[error("This store was opened read-only")]
+    ReadOnly,
+    
+    /// Anything else, typed errors like `SliceRejected` included
+    #[error(transparent)]
+    Other(anyhow::Error),
+}
+
+impl Mem8Error {
+    pub(crate) fn not_found(what: impl std::fmt::Display) -> Self {
+        Mem8Error::NotFound(what.to_string())
+    }
+    
+    pub(crate) fn corrupt(signature: [u8; 32], reason: impl Into<String>) -> Self {
+        Mem8Error::Corrupt { signature, reason: reason.into() }
+    }
+    
+    /// The typed error behind this one, whichever variant carries it
+    pub fn downcast_ref<T: std::error::Error + Send + Sync + 'static>(&self) -> Option<&T> {
+        let inner: &(dyn std::error::Error + 'static) = match self {
+            Mem8Error::Other(e) => return e.downcast_ref::<T>()
+                .or_else(|| e.downcast_ref::<Mem8Error>()?.downcast_ref::<T>()),
+            Mem8Error::InvalidPath(e) => e,
+            Mem8Error::Io(e) => match e.get_ref() {
+                Some(custom) => return custom.downcast_ref::<T>(),
+                None => e,
+            },
+            Mem8Error::FormatVersion(e) => e,
+            Mem8Error::Expired(e) => e,
+            Mem8Error::Locked(e) => e,
+            _ => self,
+        };
+        inner.downcast_ref::<T>()
+    }
+    
+    pub fn is<T: std::error::Error + Send + Sync + 'static>(&self) -> bool {
+        self.downcast_ref::<T>().is_some()
+    }
+}
+
+impl From<anyhow::Error> for Mem8Error {
+    fn from(e: anyhow::Error) -> Self {
+        // One of ours that went through anyhow and back, with no context added
+        match e.downcast_ref::<Mem8Error>() {
+            Some(inner) if inner.to_string() == e.to_string() => e.downcast().unwrap(),
+            _ => Mem8Error::Other(e),
+        }
+    }
+}
+
+/// Typed errors with no variant of their own go in `Other`
+macro_rules! into_other {
+    ($($error:ty),* $(,)?) => {
+        $(impl From<$error> for Mem8Error {
+            fn from(e: $error) -> Self {
+                Mem8Error::Other(anyhow::Error::new(e))
+            }
+        })*
+    };
+}
+
+into_other!(
+    bincode::Error,
+    serde_json::Error,
+    std::string::FromUtf8Error,
+    std::array::TryFromSliceError,
+    claxon::Error,
+    hound::Error,
+    crate::lite::SignatureMismatch,
+    crate::lite::SignatureCollision,
+    crate::cipher::AuthenticationFailed,
+    crate::metadata_policy::MetadataRejected,
+    crate::archive::UnsupportedArchive,
+    crate::snapshot::SnapshotDataGone,
+    crate::orphans::ScanCancelled,
+    crate::rekey::FrequencyMismatch,
+    crate::rekey::FrequencyChangeRefused,
+    crate::slice::SliceRejected,
+    crate::slice::BadManifestSignature,
+    crate::text::NotUtf8,
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Mem8Fs, Mem8Lite, SnapshotDataGone};
+    use tempfile::tempdir;
+    
+    #[test]
+    fn test_variants_and_downcasts() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        assert!(matches!(fs.read("/nope.txt"), Err(Mem8Error::NotFound(_))));
+        fs.write("/a.txt", b"a").unwrap();
+        fs.write("/b.txt", b"b").unwrap();
+        assert!(matches!(fs.rename("/a.txt", "/b.txt"), Err(Mem8Error::AlreadyExists(ref path)) if path == "/b.txt"));
+        assert!(matches!(fs.read("/../x"), Err(Mem8Error::InvalidPath(_))));
+        
+        let storage = Mem8Lite::new(dir.path().join("lite.m8"), 1.0).unwrap();
+        assert!(matches!(storage.retrieve(&[9u8; 32]), Err(Mem8Error::NotFound(_))));
+        
+        // Typed errors without a variant are still there to downcast, through anyhow and back too
+        let gone = SnapshotDataGone { snapshot: "v1".into(), path: "/a.txt".into(), signature: "00".into() };
+        let err = Mem8Error::from(gone.clone());
+        assert_eq!(err.downcast_ref::<SnapshotDataGone>(), Some(&gone));
+        let round_trip = Mem8Error::from(anyhow::Error::new(Mem8Error::not_found("File not found")));
+        assert!(matches!(round_trip, Mem8Error::NotFound(_)));
+        let wrapped = Mem8Error::from(anyhow::Error::new(err).context("restoring"));
+        assert!(wrapped.is::<SnapshotDataGone>());
+        assert_eq!(wrapped.to_string(), "restoring");
+    }
+}
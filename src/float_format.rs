@@ -0,0 +1,116 @@
+//! Deterministic float formatting for serialized metadata
+//!
+//! Default float output gives us noisy 17-digit values like `0.30000000000000004`
+//! that make canonical hashing and diffing of metadata flaky. Every float that
+//! goes into stored metadata or an export passes through here first, rounded
+//! to a fixed number of significant digits for its category.
+//!
+//! Rounding goes through Rust's own (locale-independent, correctly rounded)
+//! scientific formatting, so the result is identical on every platform, and
+//! re-formatting an already formatted value is a no-op.
+
+use serde_json::Value;
+
+/// What kind of number this is - decides how many digits are meaningful
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FloatCategory {
+    /// Marine salience and wonder scores
+    Salience,
+    /// Wave and sample frequencies (1.618 must survive!)
+    Frequency,
+    /// Loudness in dB / LUFS
+    Loudness,
+    /// Linear levels and ratios (RMS, peak, confidence)
+    Level,
+    /// Durations in seconds
+    Duration,
+}
+
+impl FloatCategory {
+    /// Significant digits kept for this category
+    pub fn significant_digits(self) -> usize {
+        match self {
+            FloatCategory::Salience => 4,
+            FloatCategory::Frequency => 6,
+            FloatCategory::Loudness => 4,
+            FloatCategory::Level => 4,
+            FloatCategory::Duration => 6,
+        }
+    }
+}
+
+/// Round a value to its category's significant digits
+///
+/// Non-finite values pass through untouched.
+pub fn canonical_f64(value: f64, category: FloatCategory) -> f64 {
+    if value == 0.0 {
+        return 0.0; // Folds -0.0 too
+    }
+    if !value.is_finite() {
+        return value;
+    }
+    let digits = category.significant_digits();
+    format!("{:.*e}", digits - 1, value)
+        .parse()
+        .unwrap_or(value)
+}
+
+/// Canonical text form (for CSV and other plain-text exports)
+pub fn format_f64(value: f64, category: FloatCategory) -> String {
+    if value.is_nan() {
+        return "NaN".to_string();
+    }
+    if value.is_infinite() {
+        return if value > 0.0 { "inf" } else { "-inf" }.to_string();
+    }
+    format!("{}", canonical_f64(value, category))
+}
+
+/// Canonical JSON number (non-finite values become `null`, as JSON requires)
+pub fn json_f64(value: f64, category: FloatCategory) -> Value {
+    serde_json::Number::from_f64(canonical_f64(value, category))
+        .map(Value::Number)
+        .unwrap_or(Value::Null)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_representative_values() {
+        assert_eq!(format_f64(0.000000123456789, FloatCategory::Salience), "0.0000001235");
+        assert_eq!(format_f64(1.618033988749895, FloatCategory::Frequency), "1.61803");
+        assert_eq!(format_f64(-23.456789, FloatCategory::Loudness), "-23.46");
+        assert_eq!(format_f64(0.1 + 0.2, FloatCategory::Level), "0.3");
+        assert_eq!(format_f64(-0.0, FloatCategory::Level), "0");
+        assert_eq!(format_f64(f64::NAN, FloatCategory::Salience), "NaN");
+        
+        assert_eq!(serde_json::to_string(&json_f64(1.618033988749895, FloatCategory::Frequency)).unwrap(), "1.61803");
+        assert_eq!(json_f64(f64::INFINITY, FloatCategory::Level), Value::Null);
+    }
+    
+    #[test]
+    fn test_round_trip_is_stable() {
+        let categories = [
+            FloatCategory::Salience,
+            FloatCategory::Frequency,
+            FloatCategory::Loudness,
+            FloatCategory::Level,
+            FloatCategory::Duration,
+        ];
+        let values = [1e-9, 0.000123456, 0.8, 1.618033988749895, std::f64::consts::PI, -23.456789, 44100.0, 12345.678901];
+        
+        for &category in &categories {
+            for &value in &values {
+                let once = format_f64(value, category);
+                let parsed: f64 = once.parse().unwrap();
+                assert_eq!(format_f64(parsed, category), once, "{:?} {}", category, value);
+                
+                let json = serde_json::to_string(&json_f64(value, category)).unwrap();
+                let parsed: f64 = serde_json::from_str(&json).unwrap();
+                assert_eq!(serde_json::to_string(&json_f64(parsed, category)).unwrap(), json);
+            }
+        }
+    }
+}
@@ -0,0 +1,162 @@
+//! Base wave frequencies - one source of truth for the magic numbers
+//!
+//! Every store encodes its waves against a base frequency, and decoding needs
+//! the exact same value back. These used to be bare literals sprinkled across
+//! the crate; now the golden ratio, pi and Feigenbaum live here, together with
+//! the sample-rate mapping the audio pipeline uses.
+//!
+//! Hue's favourite is still the golden ratio, obviously! 🌀
+
+use serde::{Serialize, Deserialize};
+use anyhow::{Result, anyhow};
+
+use crate::audio::SampleRate;
+
+/// The golden ratio, as stores have always encoded it
+pub const GOLDEN_RATIO: f64 = 1.618;
+
+/// The studio-quality base frequency: pi, as stores have always encoded it
+///
+/// A frozen on-disk constant, not an approximation to fix - it's what
+/// `FrequencyPreset::Pi` stores were written with, so it stays 3.14159.
+#[allow(clippy::approx_constant)]
+pub const STUDIO_FREQUENCY: f64 = 3.14159;
+
+/// The Feigenbaum constant (chaos!), as stores have always encoded it
+pub const FEIGENBAUM: f64 = 4.669;
+
+/// A named (or custom) base frequency for wave encoding
+///
+/// Serializes by name (`"golden-ratio"`, `"pi"`, `"feigenbaum"`), so stores and
+/// metadata say which preset they were written with; only `Custom` carries a number.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum FrequencyPreset {
+    /// 1.618 - the default
+    #[default]
+    GoldenRatio,
+    
+    /// 3.14159 (`STUDIO_FREQUENCY`) - studio quality
+    Pi,
+    
+    /// 4.669 - for chaos
+    Feigenbaum,
+    
+    /// Any other frequency in Hz
+    Custom(f64),
+}
+
+impl FrequencyPreset {
+    /// The frequency in Hz
+    pub fn hz(&self) -> f64 {
+        match self {
+            FrequencyPreset::GoldenRatio => GOLDEN_RATIO,
+            FrequencyPreset::Pi => STUDIO_FREQUENCY,
+            FrequencyPreset::Feigenbaum => FEIGENBAUM,
+            FrequencyPreset::Custom(hz) => *hz,
+        }
+    }
+    
+    /// The preset matching `hz` exactly, or `Custom`
+    pub fn from_hz(hz: f64) -> Self {
+        if hz == GOLDEN_RATIO {
+            FrequencyPreset::GoldenRatio
+        } else if hz == STUDIO_FREQUENCY {
+            FrequencyPreset::Pi
+        } else if hz == FEIGENBAUM {
+            FrequencyPreset::Feigenbaum
+        } else {
+            FrequencyPreset::Custom(hz)
+        }
+    }
+    
+    /// Recommended frequency for audio at this sample rate
+    ///
+    /// Higher sample rates can handle higher wave frequencies.
+    pub fn for_sample_rate(rate: &SampleRate) -> Self {
+        match rate {
+            SampleRate::Phone16k => FrequencyPreset::Custom(0.5),      // Low frequency for compression
+            SampleRate::Broadcast22k => FrequencyPreset::Custom(0.8),
+            SampleRate::CD44k => FrequencyPreset::GoldenRatio,         // Golden ratio!
+            SampleRate::DVD48k => FrequencyPreset::Custom(2.0),
+            SampleRate::Studio96k => FrequencyPreset::Pi,              // Pi for studio quality
+            SampleRate::Audiophile192k => FrequencyPreset::Feigenbaum, // Feigenbaum constant for chaos!
+            SampleRate::Custom(rate) => {
+                // Scale frequency with sample rate
+                FrequencyPreset::from_hz(GOLDEN_RATIO * (rate / 44100.0).sqrt())
+            }
+        }
+    }
+    
+    /// Preset name, or the frequency for `Custom`
+    pub fn name(&self) -> String {
+        match self {
+            FrequencyPreset::GoldenRatio => "golden-ratio".to_string(),
+            FrequencyPreset::Pi => "pi".to_string(),
+            FrequencyPreset::Feigenbaum => "feigenbaum".to_string(),
+            FrequencyPreset::Custom(hz) => hz.to_string(),
+        }
+    }
+}
+
+impl From<f64> for FrequencyPreset {
+    fn from(hz: f64) -> Self {
+        FrequencyPreset::from_hz(hz)
+    }
+}
+
+impl std::str::FromStr for FrequencyPreset {
+    type Err = anyhow::Error;
+    
+    /// Accepts a preset name or a frequency in Hz
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "golden-ratio" | "golden" => Ok(FrequencyPreset::GoldenRatio),
+            "pi" => Ok(FrequencyPreset::Pi),
+            "feigenbaum" => Ok(FrequencyPreset::Feigenbaum),
+            other => other.parse::<f64>()
+                .map(FrequencyPreset::from_hz)
+                .map_err(|_| anyhow!("Unknown frequency preset: {}", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for FrequencyPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FrequencyPreset::Custom(hz) => write!(f, "{}Hz", hz),
+            preset => write!(f, "{} ({}Hz)", preset.name(), preset.hz()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_presets_round_trip_by_name() {
+        for preset in [FrequencyPreset::GoldenRatio, FrequencyPreset::Pi, FrequencyPreset::Feigenbaum] {
+            assert_eq!(FrequencyPreset::from_hz(preset.hz()), preset);
+            assert_eq!(preset.name().parse::<FrequencyPreset>().unwrap(), preset);
+        }
+        
+        assert_eq!(serde_json::to_string(&FrequencyPreset::GoldenRatio).unwrap(), "\"golden-ratio\"");
+        let custom: FrequencyPreset = serde_json::from_str("{\"custom\":0.5}").unwrap();
+        assert_eq!(custom, FrequencyPreset::Custom(0.5));
+        
+        // bincode (used by Mem8Fs metadata) round-trips too
+        let bytes = bincode::serialize(&FrequencyPreset::Pi).unwrap();
+        assert_eq!(bincode::deserialize::<FrequencyPreset>(&bytes).unwrap(), FrequencyPreset::Pi);
+    }
+    
+    #[test]
+    fn test_sample_rate_mapping_is_stable() {
+        // Changing these would make existing stores undecodable
+        assert_eq!(FrequencyPreset::for_sample_rate(&SampleRate::Phone16k).hz(), 0.5);
+        assert_eq!(FrequencyPreset::for_sample_rate(&SampleRate::CD44k), FrequencyPreset::GoldenRatio);
+        assert_eq!(FrequencyPreset::for_sample_rate(&SampleRate::Studio96k), FrequencyPreset::Pi);
+        assert_eq!(FrequencyPreset::for_sample_rate(&SampleRate::Audiophile192k), FrequencyPreset::Feigenbaum);
+        assert_eq!(FrequencyPreset::for_sample_rate(&SampleRate::Custom(44100.0)), FrequencyPreset::GoldenRatio);
+    }
+}
@@ -3,16 +3,34 @@
 //! Provides std::fs-like operations but with wave-based storage underneath!
 
 use std::path::{Path, PathBuf};
-use std::io::{self, Read, Write};
-use anyhow::Result;
-use crate::Mem8Fs;
+use std::io::{self, BufRead, Read, Seek, SeekFrom, Write};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use anyhow::anyhow;
+use crate::error::{Mem8Error, Result};
+use crate::{FileMetadata, Mem8Fs, WriteOptions};
 
 /// File handle for MEM8 filesystem
+/// 
+/// Works on a copy of the file in memory: reads, writes and seeks move one
+/// position through it, like a std `File`, and nothing reaches the store
+/// until `flush` - or drop, which flushes what's left (`into_inner` opts
+/// out). `sync_all` also waits for the disk.
 pub struct File {
     path: PathBuf,
     fs: std::sync::Arc<Mem8Fs>,
     data: Vec<u8>,
     pos: usize,
+    
+    /// What `OpenOptions` opened it for (`open` and `create` allow both)
+    readable: bool,
+    writable: bool,
+    
+    /// Every write goes at the end, wherever the position is
+    append: bool,
+    
+    /// Changed since it was loaded or last flushed
+    dirty: bool,
 }
 
 impl File {
@@ -20,68 +38,443 @@ impl File {
     pub fn open<P: AsRef<Path>>(fs: std::sync::Arc<Mem8Fs>, path: P) -> Result<Self> {
         let path = path.as_ref().to_path_buf();
         let data = fs.read(&path)?;
-        Ok(Self {
-            path,
-            fs,
-            data,
-            pos: 0,
-        })
+        Ok(Self::opened(fs, path, data))
     }
     
     /// Create a new file for writing
     pub fn create<P: AsRef<Path>>(fs: std::sync::Arc<Mem8Fs>, path: P) -> Result<Self> {
         let path = path.as_ref().to_path_buf();
-        Ok(Self {
+        let mut file = Self::opened(fs, path, Vec::new());
+        // Flushed even if nothing's written, so the file is there
+        file.dirty = true;
+        Ok(file)
+    }
+    
+    fn opened(fs: Arc<Mem8Fs>, path: PathBuf, data: Vec<u8>) -> Self {
+        Self {
             path,
             fs,
-            data: Vec::new(),
+            data,
             pos: 0,
-        })
+            readable: true,
+            writable: true,
+            append: false,
+            dirty: false,
+        }
+    }
+    
+    /// Cut the file short at `size` bytes, or grow it to that with zeros
+    /// 
+    /// The position stays where it was, even past the new end. Like writes,
+    /// it's saved on `flush`.
+    pub fn set_len(&mut self, size: u64) -> io::Result<()> {
+        if !self.writable {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "file not opened for writing"));
+        }
+        let size = usize::try_from(size).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "file size too large"))?;
+        self.data.resize(size, 0);
+        self.dirty = true;
+        Ok(())
+    }
+    
+    /// `flush`, then fsync the whole store (`Mem8Fs::sync`), whatever its `Durability`
+    pub fn sync_all(&mut self) -> io::Result<()> {
+        self.flush()?;
+        self.fs.sync().map_err(io::Error::other)
+    }
+    
+    /// The same as `sync_all`: the store has no way to fsync file data on its own
+    pub fn sync_data(&mut self) -> io::Result<()> {
+        self.sync_all()
+    }
+    
+    /// The file's bytes as they are in memory, without saving them
+    /// 
+    /// Whatever hasn't been flushed is left out of the store for good.
+    pub fn into_inner(mut self) -> Vec<u8> {
+        self.dirty = false;
+        std::mem::take(&mut self.data)
     }
 }
 
 impl Read for File {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        let remaining = self.data.len() - self.pos;
-        let to_read = buf.len().min(remaining);
+        if !self.readable {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "file not opened for reading"));
+        }
         
-        buf[..to_read].copy_from_slice(&self.data[self.pos..self.pos + to_read]);
+        // Nothing left once the position is at (or past) the end
+        let start = self.pos.min(self.data.len());
+        let to_read = buf.len().min(self.data.len() - start);
+        
+        buf[..to_read].copy_from_slice(&self.data[start..start + to_read]);
         self.pos += to_read;
         
         Ok(to_read)
     }
 }
 
+/// Free: the whole file is already in memory, so the buffer is the rest of it
+impl BufRead for File {
+    fn fill_buf(&mut self) -> io::Result<&[u8]> {
+        if !self.readable {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "file not opened for reading"));
+        }
+        Ok(&self.data[self.pos.min(self.data.len())..])
+    }
+    
+    fn consume(&mut self, amt: usize) {
+        self.pos += amt;
+    }
+}
+
 impl Write for File {
+    /// Overwrite from the position on (from the end, opened for append),
+    /// growing the file (with zeros first, if the position is past the
+    /// end) as needed
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.data.extend_from_slice(buf);
+        if !self.writable {
+            return Err(io::Error::new(io::ErrorKind::PermissionDenied, "file not opened for writing"));
+        }
+        if self.append {
+            self.pos = self.data.len();
+        }
+        let end = self.pos + buf.len();
+        if end > self.data.len() {
+            self.data.resize(end, 0);
+        }
+        self.data[self.pos..end].copy_from_slice(buf);
+        self.pos = end;
+        self.dirty = true;
         Ok(buf.len())
     }
     
     fn flush(&mut self) -> io::Result<()> {
+        if !self.dirty {
+            return Ok(());
+        }
         self.fs.write(&self.path, &self.data)
             .map_err(|e| io::Error::new(io::ErrorKind::Other, e))?;
+        self.dirty = false;
         Ok(())
     }
 }
 
+impl Drop for File {
+    /// Save what was never flushed (there's no one to hand an error to, so it's logged)
+    fn drop(&mut self) {
+        if let Err(e) = self.flush() {
+            eprintln!("⚠️ mem8: failed to save {} on drop: {}", self.path.display(), e);
+        }
+    }
+}
+
+impl Seek for File {
+    /// Past the end is allowed (a write there fills the gap with zeros); before the start isn't
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let (base, offset) = match pos {
+            SeekFrom::Start(offset) => (0, offset as i128),
+            SeekFrom::End(offset) => (self.data.len() as i128, offset as i128),
+            SeekFrom::Current(offset) => (self.pos as i128, offset as i128),
+        };
+        let target = usize::try_from(base + offset)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "invalid seek to a negative or overflowing position"))?;
+        self.pos = target;
+        Ok(target as u64)
+    }
+}
+
+/// How to open a `File`, like `std::fs::OpenOptions`
+/// 
+/// Opening does to the store what std's does to a disk - `create` and
+/// `create_new` make a missing file (empty), `truncate` empties one - and
+/// the `File` loads what's there otherwise. Writes through it still wait
+/// for `flush`. Bad combinations fail as std's do, with `InvalidInput`.
+#[derive(Debug, Clone, Default)]
+pub struct OpenOptions {
+    read: bool,
+    write: bool,
+    append: bool,
+    truncate: bool,
+    create: bool,
+    create_new: bool,
+}
+
+impl OpenOptions {
+    /// Everything off: set at least one of `read`, `write` and `append`
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn read(&mut self, read: bool) -> &mut Self {
+        self.read = read;
+        self
+    }
+    
+    pub fn write(&mut self, write: bool) -> &mut Self {
+        self.write = write;
+        self
+    }
+    
+    /// Write at the end, wherever the position is (implies `write`)
+    pub fn append(&mut self, append: bool) -> &mut Self {
+        self.append = append;
+        self
+    }
+    
+    /// Empty the file as it opens (needs `write`, not `append`)
+    pub fn truncate(&mut self, truncate: bool) -> &mut Self {
+        self.truncate = truncate;
+        self
+    }
+    
+    /// Create the file if it's missing (needs `write` or `append`)
+    pub fn create(&mut self, create: bool) -> &mut Self {
+        self.create = create;
+        self
+    }
+    
+    /// Create the file, failing with `Mem8Error::AlreadyExists` if it's there
+    /// (`create` and `truncate` are then ignored)
+    pub fn create_new(&mut self, create_new: bool) -> &mut Self {
+        self.create_new = create_new;
+        self
+    }
+    
+    pub fn open<P: AsRef<Path>>(&self, fs: Arc<Mem8Fs>, path: P) -> Result<File> {
+        let path = path.as_ref().to_path_buf();
+        let writable = self.write || self.append;
+        let invalid = |reason: &str| Err(io::Error::new(io::ErrorKind::InvalidInput, reason.to_string()).into());
+        if !self.read && !writable {
+            return invalid("open for reading, writing or appending");
+        }
+        if !writable && (self.create || self.create_new || self.truncate) {
+            return invalid("creating or truncating a file needs write or append");
+        }
+        if self.append && self.truncate && !self.create_new {
+            return invalid("a file can't be both truncated and appended to");
+        }
+        
+        let data = if self.create_new {
+            fs.write_with_options(&path, &[], WriteOptions::default().with_create_new(true))?;
+            Vec::new()
+        } else if (self.truncate && fs.exists(&path)) || (self.create && !fs.exists(&path)) {
+            fs.write(&path, &[])?;
+            Vec::new()
+        } else {
+            fs.read(&path)?
+        };
+        let mut file = File::opened(fs, path, data);
+        file.readable = self.read;
+        file.writable = writable;
+        file.append = self.append;
+        if self.append {
+            file.pos = file.data.len();
+        }
+        Ok(file)
+    }
+}
+
+/// How much `fs::copy_between` reads and writes at a time, between stores
+pub const COPY_BLOCK: usize = 1 << 20;
+
+/// How `fs::copy_with` copies a file
+#[derive(Default)]
+pub struct CopyOptions {
+    /// Replace a file already at the destination (otherwise `AlreadyExists`)
+    pub overwrite: bool,
+    
+    /// Keep the source's created and modified times, rather than now
+    pub preserve_times: bool,
+    
+    /// Called with (bytes copied, total bytes) as the copy goes
+    pub progress: Option<Box<dyn Fn(u64, u64)>>,
+}
+
+impl CopyOptions {
+    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
+        self.overwrite = overwrite;
+        self
+    }
+    
+    pub fn with_preserve_times(mut self, preserve_times: bool) -> Self {
+        self.preserve_times = preserve_times;
+        self
+    }
+    
+    pub fn with_progress(mut self, progress: impl Fn(u64, u64) + 'static) -> Self {
+        self.progress = Some(Box::new(progress));
+        self
+    }
+    
+    fn report(&self, copied: u64, total: u64) {
+        if let Some(progress) = &self.progress {
+            progress(copied, total);
+        }
+    }
+}
+
+impl std::fmt::Debug for CopyOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CopyOptions")
+            .field("overwrite", &self.overwrite)
+            .field("preserve_times", &self.preserve_times)
+            .field("progress", &self.progress.is_some())
+            .finish()
+    }
+}
+
+/// Tells temp files apart within a process (the pid does it across them)
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// A file under a hidden, unique name, deleted on drop unless `persist` moves it into place
+/// 
+/// The name is taken as it's made (`create_new`), so no two temp files
+/// collide. Writes go to the `File` inside and, like its, wait for `persist`.
+pub struct NamedTempFile {
+    path: PathBuf,
+    fs: Arc<Mem8Fs>,
+    
+    /// Taken by `persist`, or by drop without saving
+    file: Option<File>,
+    persisted: bool,
+}
+
+impl NamedTempFile {
+    /// A new, empty temp file in directory `dir`, named `.tmp-<pid>-<n>`
+    pub fn new_in<P: AsRef<Path>>(fs: Arc<Mem8Fs>, dir: P) -> Result<Self> {
+        Self::named_in(fs, dir.as_ref(), ".tmp")
+    }
+    
+    /// A temp file in `dir` called `<prefix>-<pid>-<n>`, for the first `n` that's free
+    fn named_in(fs: Arc<Mem8Fs>, dir: &Path, prefix: &str) -> Result<Self> {
+        loop {
+            let n = TEMP_COUNTER.fetch_add(1, Ordering::Relaxed);
+            let path = dir.join(format!("{}-{}-{}", prefix, std::process::id(), n));
+            let file = OpenOptions::new().read(true).write(true).create_new(true).open(fs.clone(), &path);
+            match file {
+                Ok(file) => return Ok(Self { path, fs, file: Some(file), persisted: false }),
+                Err(Mem8Error::AlreadyExists(_)) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+    
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    pub fn as_file_mut(&mut self) -> &mut File {
+        self.file.as_mut().unwrap()
+    }
+    
+    /// Save what was written and rename it to `path`, replacing any file there
+    /// 
+    /// The rename is one index change, so anyone reading `path` gets the old
+    /// file or the new one, whole. On failure the temp file is deleted.
+    pub fn persist<P: AsRef<Path>>(mut self, path: P) -> Result<()> {
+        // Saved here rather than through flush, so errors keep their variant
+        let file = self.file.take().unwrap();
+        let dirty = file.dirty;
+        let data = file.into_inner();
+        if dirty {
+            self.fs.write(&self.path, &data)?;
+        }
+        self.fs.rename_with(self.path.as_path(), path.as_ref(), true)?;
+        self.persisted = true;
+        Ok(())
+    }
+}
+
+impl Read for NamedTempFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.as_file_mut().read(buf)
+    }
+}
+
+impl Write for NamedTempFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.as_file_mut().write(buf)
+    }
+    
+    /// Saves to the temp path - `persist` is what moves it into place
+    fn flush(&mut self) -> io::Result<()> {
+        self.as_file_mut().flush()
+    }
+}
+
+impl Seek for NamedTempFile {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.as_file_mut().seek(pos)
+    }
+}
+
+impl Drop for NamedTempFile {
+    fn drop(&mut self) {
+        if let Some(file) = self.file.take() {
+            file.into_inner();
+        }
+        if !self.persisted {
+            match self.fs.delete(&self.path) {
+                Ok(()) | Err(Mem8Error::NotFound(_)) => {}
+                Err(e) => eprintln!("⚠️ mem8: failed to delete temp file {}: {}", self.path.display(), e),
+            }
+        }
+    }
+}
+
 /// Directory iterator for MEM8
 pub struct ReadDir {
     entries: Vec<DirEntry>,
     pos: usize,
+    
+    /// Where `DirEntry::metadata` looks (only listings from `fs::read_dir` have one)
+    fs: Option<Arc<Mem8Fs>>,
 }
 
 /// Directory entry
 pub struct DirEntry {
     path: PathBuf,
     is_dir: bool,
+    fs: Option<Arc<Mem8Fs>>,
+}
+
+impl ReadDir {
+    pub(crate) fn new(entries: Vec<DirEntry>) -> Self {
+        Self { entries, pos: 0, fs: None }
+    }
+    
+    /// Hand `fs` to every entry, for `DirEntry::metadata`
+    fn with_fs(mut self, fs: Arc<Mem8Fs>) -> Self {
+        self.fs = Some(fs);
+        self
+    }
 }
 
 impl DirEntry {
+    pub(crate) fn new(path: PathBuf, is_dir: bool) -> Self {
+        Self { path, is_dir, fs: None }
+    }
+    
     pub fn path(&self) -> &Path {
         &self.path
     }
     
+    /// The file's metadata, looked up now rather than when the directory was listed
+    /// 
+    /// Fails for directories (there's no `FileMetadata` for them), for a
+    /// file deleted since, and for entries from `Mem8Fs::read_dir`, which
+    /// has no `Arc` to hand them - list with `fs::read_dir` instead.
+    pub fn metadata(&self) -> Result<FileMetadata> {
+        if self.is_dir {
+            return Err(anyhow!("{} is a directory, not a file", self.path.display()).into());
+        }
+        match &self.fs {
+            Some(fs) => fs.metadata(&self.path),
+            None => Err(anyhow!("No filesystem to look {} up in: list it with fs::read_dir", self.path.display()).into()),
+        }
+    }
+    
     pub fn file_name(&self) -> Option<&std::ffi::OsStr> {
         self.path.file_name()
     }
@@ -100,7 +493,8 @@ impl Iterator for ReadDir {
     
     fn next(&mut self) -> Option<Self::Item> {
         if self.pos < self.entries.len() {
-            let entry = self.entries[self.pos].clone();
+            let mut entry = self.entries[self.pos].clone();
+            entry.fs.clone_from(&self.fs);
             self.pos += 1;
             Some(Ok(entry))
         } else {
@@ -109,11 +503,22 @@ impl Iterator for ReadDir {
     }
 }
 
+/// One entry of `Mem8Fs::walk`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WalkEntry {
+    pub path: PathBuf,
+    pub is_dir: bool,
+    
+    /// File size in bytes (0 for directories)
+    pub size: u64,
+}
+
 impl Clone for DirEntry {
     fn clone(&self) -> Self {
         Self {
             path: self.path.clone(),
             is_dir: self.is_dir,
+            fs: self.fs.clone(),
         }
     }
 }
@@ -121,7 +526,7 @@ impl Clone for DirEntry {
 /// std::fs-like API functions
 pub mod fs {
     use super::*;
-    use std::sync::Arc;
+    use serde::{de::DeserializeOwned, Serialize};
     
     /// Read entire file to bytes
     pub fn read<P: AsRef<Path>>(fs: Arc<Mem8Fs>, path: P) -> Result<Vec<u8>> {
@@ -134,17 +539,87 @@ pub mod fs {
         Ok(())
     }
     
+    /// `write`, by way of a hidden temp file next to `path` that's renamed over it
+    /// 
+    /// If anything fails before the rename, `path` is left as it was and the
+    /// temp file is deleted. Of several writers racing to the same path, one
+    /// complete version wins.
+    pub fn write_atomic<P: AsRef<Path>>(fs: Arc<Mem8Fs>, path: P, contents: &[u8]) -> Result<()> {
+        let path = path.as_ref();
+        let dir = path.parent().unwrap_or(Path::new("/"));
+        let name = path.file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+        let mut temp = NamedTempFile::named_in(fs, dir, &format!(".{}.tmp", name))?;
+        temp.write_all(contents)?;
+        temp.persist(path)
+    }
+    
     /// Read file to string
     pub fn read_to_string<P: AsRef<Path>>(fs: Arc<Mem8Fs>, path: P) -> Result<String> {
         fs.read_string(path)
     }
     
-    /// Copy file
+    /// Copy file, replacing whatever is at `to` (see `copy_with`)
     pub fn copy<P: AsRef<Path>>(fs: Arc<Mem8Fs>, from: P, to: P) -> Result<u64> {
-        let data = fs.read(&from)?;
-        let size = data.len() as u64;
-        fs.write(to, &data)?;
-        Ok(size)
+        copy_with(fs, from, to, CopyOptions::default().with_overwrite(true))
+    }
+    
+    /// Copy a file within a store, returning its size
+    /// 
+    /// Only the index changes: `to` gets an entry of its own for the same
+    /// content, so the bytes are neither read nor stored again, and
+    /// `progress` is called once, at the end.
+    pub fn copy_with<P: AsRef<Path>, Q: AsRef<Path>>(fs: Arc<Mem8Fs>, from: P, to: Q, options: CopyOptions) -> Result<u64> {
+        copy_between(fs.clone(), from, fs, to, options)
+    }
+    
+    /// `copy_with` from one store to another, `COPY_BLOCK` bytes at a time
+    /// 
+    /// The copy keeps the source's frequency and `WriteOptions::metadata`.
+    /// Within one store it's `copy_with`, index entry and all.
+    pub fn copy_between<P: AsRef<Path>, Q: AsRef<Path>>(
+        from_fs: Arc<Mem8Fs>,
+        from: P,
+        to_fs: Arc<Mem8Fs>,
+        to: Q,
+        options: CopyOptions,
+    ) -> Result<u64> {
+        let (from, to) = (from.as_ref(), to.as_ref());
+        if Arc::ptr_eq(&from_fs, &to_fs) {
+            let size = from_fs.copy_entry(from, to, options.overwrite, options.preserve_times)?;
+            options.report(size, size);
+            return Ok(size);
+        }
+        
+        let source = from_fs.metadata(from)?;
+        // Marine would only hear the first block, so it doesn't listen
+        let mut write = WriteOptions::default()
+            .with_frequency(source.wave_frequency)
+            .with_create_new(!options.overwrite)
+            .with_analyze(false);
+        write.metadata = source.metadata;
+        let mut copied = 0;
+        loop {
+            let block = from_fs.read_range(from, copied as usize, COPY_BLOCK)?;
+            match copied {
+                0 => to_fs.write_with_options(to, &block, write.clone())?,
+                _ => to_fs.append(to, &block)?,
+            };
+            copied += block.len() as u64;
+            options.report(copied, source.size);
+            if block.len() < COPY_BLOCK {
+                break;
+            }
+        }
+        
+        if options.preserve_times {
+            let to = to_fs.normalize_path(to)?;
+            let mut index = to_fs.index.write().unwrap();
+            if let Some(entry) = index.files.get_mut(&to) {
+                (entry.created, entry.modified) = (source.created_ms, source.modified_ms);
+            }
+            to_fs.save_index(&index)?;
+        }
+        Ok(copied)
     }
     
     /// Rename/move file
@@ -157,13 +632,482 @@ pub mod fs {
         fs.delete(path)
     }
     
+    /// List a directory's files and subdirectories, sorted by path
+    /// 
+    /// Straight from the index; each entry's metadata waits for `DirEntry::metadata`.
+    pub fn read_dir<P: AsRef<Path>>(fs: Arc<Mem8Fs>, path: P) -> Result<ReadDir> {
+        Ok(fs.read_dir(path)?.with_fs(fs))
+    }
+    
+    /// A file's size, times, signature and so on
+    pub fn metadata<P: AsRef<Path>>(fs: Arc<Mem8Fs>, path: P) -> Result<FileMetadata> {
+        fs.metadata(path)
+    }
+    
     /// Create directory
     pub fn create_dir<P: AsRef<Path>>(fs: Arc<Mem8Fs>, path: P) -> Result<()> {
         fs.create_dir(path)
     }
     
+    /// Remove an empty directory
+    pub fn remove_dir<P: AsRef<Path>>(fs: Arc<Mem8Fs>, path: P) -> Result<()> {
+        fs.remove_dir(path)
+    }
+    
+    /// Remove a directory and everything in it
+    pub fn remove_dir_all<P: AsRef<Path>>(fs: Arc<Mem8Fs>, path: P) -> Result<()> {
+        fs.remove_dir_all(path)
+    }
+    
     /// Check if path exists
     pub fn exists<P: AsRef<Path>>(fs: Arc<Mem8Fs>, path: P) -> bool {
         fs.exists(path)
     }
-}
\ No newline at end of file
+    
+    /// A file's lines, without their `\n` or `\r\n` (a last line with
+    /// neither still counts)
+    /// 
+    /// A line that isn't UTF-8 comes back as an `InvalidData` error, and the
+    /// lines after it keep coming; `lines_lossy` patches it up instead.
+    /// 
+    /// ```
+    /// # use std::sync::Arc;
+    /// # use mem8_fs_lite::{fs::fs, Mem8Fs};
+    /// # let dir = tempfile::tempdir()?;
+    /// let fs = Arc::new(Mem8Fs::new(dir.path())?);
+    /// fs.write("/log.jsonl", b"{\"n\": 1}\n{\"n\": 2}")?;
+    /// let lines: Vec<String> = fs::lines(fs, "/log.jsonl")?.collect::<std::io::Result<_>>()?;
+    /// assert_eq!(lines, [r#"{"n": 1}"#, r#"{"n": 2}"#]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn lines<P: AsRef<Path>>(fs: Arc<Mem8Fs>, path: P) -> Result<io::Lines<File>> {
+        Ok(File::open(fs, path)?.lines())
+    }
+    
+    /// `lines`, with bytes that aren't UTF-8 turned into `U+FFFD` rather than errors
+    /// 
+    /// ```
+    /// # use std::sync::Arc;
+    /// # use mem8_fs_lite::{fs::fs, Mem8Fs};
+    /// # let dir = tempfile::tempdir()?;
+    /// let fs = Arc::new(Mem8Fs::new(dir.path())?);
+    /// fs.write("/mixed.txt", b"ok\r\nbad \xff byte\n")?;
+    /// let lines: Vec<String> = fs::lines_lossy(fs, "/mixed.txt")?.collect::<std::io::Result<_>>()?;
+    /// assert_eq!(lines, ["ok", "bad \u{fffd} byte"]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn lines_lossy<P: AsRef<Path>>(fs: Arc<Mem8Fs>, path: P) -> Result<impl Iterator<Item = io::Result<String>>> {
+        Ok(File::open(fs, path)?.split(b'\n').map(|line| line.map(|mut line| {
+            if line.last() == Some(&b'\r') {
+                line.pop();
+            }
+            String::from_utf8_lossy(&line).into_owned()
+        })))
+    }
+    
+    /// A file's content parsed as JSON
+    /// 
+    /// ```
+    /// # use std::sync::Arc;
+    /// # use mem8_fs_lite::{fs::fs, Mem8Fs};
+    /// # let dir = tempfile::tempdir()?;
+    /// let fs = Arc::new(Mem8Fs::new(dir.path())?);
+    /// fs::write_json(fs.clone(), "/settings.json", &vec![("theme", "dark")])?;
+    /// let settings: Vec<(String, String)> = fs::read_json(fs, "/settings.json")?;
+    /// assert_eq!(settings, [("theme".to_string(), "dark".to_string())]);
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn read_json<T: DeserializeOwned, P: AsRef<Path>>(fs: Arc<Mem8Fs>, path: P) -> Result<T> {
+        Ok(serde_json::from_slice(&fs.read(path)?)?)
+    }
+    
+    /// Write `value` to a file as JSON
+    pub fn write_json<T: Serialize + ?Sized, P: AsRef<Path>>(fs: Arc<Mem8Fs>, path: P, value: &T) -> Result<()> {
+        fs.write(path, &serde_json::to_vec(value)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    
+    /// Every file below `dir` with its size, through `fs::read_dir` alone
+    fn tree(fs: &Arc<Mem8Fs>, dir: &str, found: &mut Vec<(String, u64)>) {
+        for entry in fs::read_dir(fs.clone(), dir).unwrap() {
+            let entry = entry.unwrap();
+            let path = entry.path().display().to_string();
+            match entry.is_dir() {
+                true => tree(fs, &path, found),
+                false => found.push((path, entry.metadata().unwrap().size)),
+            }
+        }
+    }
+    
+    #[test]
+    fn test_read_dir_walks_a_nested_tree() {
+        let dir = tempdir().unwrap();
+        let fs = Arc::new(Mem8Fs::new(dir.path()).unwrap());
+        fs::write(fs.clone(), "/a/b/c.txt", b"deep").unwrap();
+        fs::write(fs.clone(), "/a/top.txt", b"up").unwrap();
+        fs::write(fs.clone(), "/z.txt", b"at the root").unwrap();
+        fs::create_dir(fs.clone(), "/a/b/empty").unwrap();
+        
+        let mut found = Vec::new();
+        tree(&fs, "/", &mut found);
+        assert_eq!(found, [("/a/b/c.txt".into(), 4), ("/a/top.txt".into(), 2), ("/z.txt".into(), 11)]);
+        assert_eq!(fs::metadata(fs.clone(), "/a/top.txt").unwrap().signature, hex::encode(crate::fs_signature(b"up")));
+        
+        // Metadata is looked up when asked for, not when listed
+        let entries: Vec<DirEntry> = fs::read_dir(fs.clone(), "/a").unwrap().map(|entry| entry.unwrap()).collect();
+        fs::write(fs.clone(), "/a/top.txt", b"grown since").unwrap();
+        assert_eq!(entries[1].metadata().unwrap().size, 11);
+        fs::remove_file(fs.clone(), "/a/top.txt").unwrap();
+        assert!(entries[1].metadata().is_err());
+        assert!(entries[0].is_dir() && entries[0].metadata().is_err());
+        
+        // Mem8Fs::read_dir has no Arc to hand its entries
+        assert!(fs.read_dir("/").unwrap().last().unwrap().unwrap().metadata().is_err());
+    }
+    
+    #[test]
+    fn test_seek_and_overwrite_in_place() {
+        let dir = tempdir().unwrap();
+        let fs = Arc::new(Mem8Fs::new(dir.path()).unwrap());
+        fs.write("/note.txt", b"the quick blue fox").unwrap();
+        
+        let mut file = File::open(fs.clone(), "/note.txt").unwrap();
+        assert_eq!(file.seek(SeekFrom::Start(10)).unwrap(), 10);
+        file.write_all(b"GREY").unwrap();
+        let mut rest = String::new();
+        file.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, " fox");
+        assert_eq!(fs.read("/note.txt").unwrap(), b"the quick blue fox");
+        file.flush().unwrap();
+        
+        let mut reopened = File::open(fs.clone(), "/note.txt").unwrap();
+        let mut all = String::new();
+        reopened.read_to_string(&mut all).unwrap();
+        assert_eq!(all, "the quick GREY fox");
+        
+        // From the end and from here, and never before the start
+        assert_eq!(reopened.seek(SeekFrom::End(-3)).unwrap(), 15);
+        assert_eq!(reopened.seek(SeekFrom::Current(-5)).unwrap(), 10);
+        assert!(reopened.seek(SeekFrom::Current(-11)).is_err());
+        assert!(reopened.seek(SeekFrom::End(-19)).is_err());
+        assert_eq!(reopened.stream_position().unwrap(), 10);
+        
+        // Past the end reads nothing, and a write there fills the gap with zeros
+        reopened.seek(SeekFrom::End(2)).unwrap();
+        assert_eq!(reopened.read(&mut [0; 4]).unwrap(), 0);
+        reopened.write_all(b"!").unwrap();
+        reopened.flush().unwrap();
+        assert_eq!(fs.read("/note.txt").unwrap(), b"the quick GREY fox\0\0!");
+    }
+    
+    /// The `std::fs::OpenOptions` examples, against a Mem8Fs
+    #[test]
+    fn test_open_options_like_std() {
+        let dir = tempdir().unwrap();
+        let fs = Arc::new(Mem8Fs::new(dir.path()).unwrap());
+        let contents = |file: &mut File| {
+            let mut read = String::new();
+            file.read_to_string(&mut read).unwrap();
+            read
+        };
+        
+        // `.read(true)`: only an existing file, and no writing to it
+        assert!(matches!(OpenOptions::new().read(true).open(fs.clone(), "foo.txt"), Err(Mem8Error::NotFound(_))));
+        fs.write("foo.txt", b"hello").unwrap();
+        let mut file = OpenOptions::new().read(true).open(fs.clone(), "foo.txt").unwrap();
+        assert_eq!(contents(&mut file), "hello");
+        assert_eq!(file.write(b"!").unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        file.flush().unwrap();
+        
+        // `.read(true).write(true).create(true)`: loads what's there
+        let mut file = OpenOptions::new().read(true).write(true).create(true).open(fs.clone(), "foo.txt").unwrap();
+        assert_eq!(contents(&mut file), "hello");
+        file.write_all(b", world").unwrap();
+        file.flush().unwrap();
+        assert_eq!(fs.read("foo.txt").unwrap(), b"hello, world");
+        
+        // `.append(true)`: writes go at the end, even after a seek
+        let mut file = OpenOptions::new().append(true).open(fs.clone(), "foo.txt").unwrap();
+        assert_eq!(file.stream_position().unwrap(), 12);
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.write_all(b"!").unwrap();
+        assert_eq!(file.read(&mut [0; 1]).unwrap_err().kind(), io::ErrorKind::PermissionDenied);
+        file.flush().unwrap();
+        assert_eq!(fs.read("foo.txt").unwrap(), b"hello, world!");
+        
+        // `.write(true).truncate(true)`: emptied as it opens
+        let mut file = OpenOptions::new().write(true).truncate(true).open(fs.clone(), "foo.txt").unwrap();
+        assert_eq!(fs.read("foo.txt").unwrap(), b"");
+        file.write_all(b"fresh").unwrap();
+        file.flush().unwrap();
+        assert_eq!(fs.read("foo.txt").unwrap(), b"fresh");
+        
+        // `.write(true).create(true)`: a missing file is there as soon as it's open
+        let file = OpenOptions::new().write(true).create(true).open(fs.clone(), "bar.txt").unwrap();
+        assert!(fs.exists("bar.txt"));
+        drop(file);
+        
+        // `.read(true).write(true).create_new(true)`: only if nothing's there
+        let err = OpenOptions::new().read(true).write(true).create_new(true).open(fs.clone(), "foo.txt");
+        assert!(matches!(err, Err(Mem8Error::AlreadyExists(ref path)) if path == "/foo.txt"));
+        let mut file = OpenOptions::new().read(true).write(true).create_new(true).open(fs.clone(), "baz.txt").unwrap();
+        file.write_all(b"new").unwrap();
+        file.flush().unwrap();
+        assert_eq!(fs.read("baz.txt").unwrap(), b"new");
+        
+        // Combinations std turns down
+        for options in [
+            OpenOptions::new().clone(),
+            OpenOptions::new().read(true).create(true).clone(),
+            OpenOptions::new().read(true).truncate(true).clone(),
+            OpenOptions::new().append(true).truncate(true).clone(),
+        ] {
+            match options.open(fs.clone(), "foo.txt") {
+                Err(Mem8Error::Io(e)) => assert_eq!(e.kind(), io::ErrorKind::InvalidInput, "{:?}", options),
+                other => panic!("{:?} opened: {:?}", options, other.map(|_| ())),
+            }
+        }
+        assert_eq!(fs.read("foo.txt").unwrap(), b"fresh");
+    }
+    
+    #[test]
+    fn test_drop_saves_what_was_never_flushed() {
+        let dir = tempdir().unwrap();
+        let fs = Arc::new(Mem8Fs::new(dir.path()).unwrap());
+        let mut file = File::create(fs.clone(), "/draft.txt").unwrap();
+        file.write_all(b"saved without a flush").unwrap();
+        drop(file);
+        assert_eq!(fs.read("/draft.txt").unwrap(), b"saved without a flush");
+        
+        // A file that wasn't changed isn't written back, on flush or drop
+        let events = fs.watch("/").unwrap();
+        let mut file = File::open(fs.clone(), "/draft.txt").unwrap();
+        file.read_to_end(&mut Vec::new()).unwrap();
+        file.flush().unwrap();
+        drop(file);
+        assert!(events.try_recv().is_err());
+        
+        // into_inner hands the bytes back and leaves the store alone
+        let mut file = File::open(fs.clone(), "/draft.txt").unwrap();
+        file.write_all(b"SAVED").unwrap();
+        assert_eq!(file.into_inner(), b"SAVED without a flush");
+        assert!(events.try_recv().is_err());
+        assert_eq!(fs.read("/draft.txt").unwrap(), b"saved without a flush");
+        
+        // sync_all saves, and leaves nothing waiting for the disk
+        let mut file = File::open(fs.clone(), "/draft.txt").unwrap();
+        file.seek(SeekFrom::End(0)).unwrap();
+        file.write_all(b", then synced").unwrap();
+        file.sync_all().unwrap();
+        assert_eq!(fs.read("/draft.txt").unwrap(), b"saved without a flush, then synced");
+        assert_eq!(fs.stats().unwrap().unsynced_bytes, 0);
+        drop(file);
+        assert!(matches!(events.try_recv(), Ok(crate::FsEvent::Modified { .. })));
+        assert!(events.try_recv().is_err());
+    }
+    
+    #[test]
+    fn test_lines_and_json() {
+        let dir = tempdir().unwrap();
+        let fs = Arc::new(Mem8Fs::new(dir.path()).unwrap());
+        fs.write("/events.jsonl", b"{\"kind\":\"wave\",\"n\":1}\r\n\n{\"kind\":\"tide\",\"n\":2}").unwrap();
+        let parsed: Vec<serde_json::Value> = fs::lines(fs.clone(), "/events.jsonl").unwrap()
+            .map(|line| line.unwrap())
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_str(&line).unwrap())
+            .collect();
+        assert_eq!(parsed, [serde_json::json!({"kind": "wave", "n": 1}), serde_json::json!({"kind": "tide", "n": 2})]);
+        assert_eq!(fs::lines(fs.clone(), "/nowhere.jsonl").err().map(|e| matches!(e, Mem8Error::NotFound(_))), Some(true));
+        fs.write("/empty.txt", b"").unwrap();
+        assert_eq!(fs::lines(fs.clone(), "/empty.txt").unwrap().count(), 0);
+        
+        // Bad UTF-8 fails only its own line, or is patched up
+        fs.write("/mixed.txt", b"one\n\xfe\xff\ntwo\n").unwrap();
+        let strict: Vec<io::Result<String>> = fs::lines(fs.clone(), "/mixed.txt").unwrap().collect();
+        assert_eq!(strict.len(), 3);
+        assert_eq!(strict[1].as_ref().unwrap_err().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(strict[2].as_ref().unwrap(), "two");
+        let lossy: Vec<String> = fs::lines_lossy(fs.clone(), "/mixed.txt").unwrap().map(|line| line.unwrap()).collect();
+        assert_eq!(lossy, ["one", "\u{fffd}\u{fffd}", "two"]);
+        
+        // BufRead directly, from wherever the position is
+        let mut file = File::open(fs.clone(), "/mixed.txt").unwrap();
+        file.seek(SeekFrom::End(-4)).unwrap();
+        assert_eq!(file.fill_buf().unwrap(), b"two\n");
+        file.consume(3);
+        let mut rest = String::new();
+        assert_eq!(file.read_line(&mut rest).unwrap(), 1);
+        assert!(file.fill_buf().unwrap().is_empty());
+        
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Settings {
+            theme: String,
+            volume: u8,
+        }
+        let settings = Settings { theme: "dark".into(), volume: 7 };
+        fs::write_json(fs.clone(), "/settings.json", &settings).unwrap();
+        assert_eq!(fs::read_json::<Settings, _>(fs.clone(), "/settings.json").unwrap(), settings);
+        assert!(fs::read_json::<Settings, _>(fs.clone(), "/events.jsonl").is_err());
+    }
+    
+    /// Everything in `dir` whose name starts with a dot
+    fn hidden(fs: &Mem8Fs, dir: &str) -> Vec<PathBuf> {
+        fs.list(dir).unwrap().into_iter()
+            .filter(|path| path.file_name().unwrap().to_string_lossy().starts_with('.'))
+            .collect()
+    }
+    
+    #[test]
+    fn test_temp_files_persist_or_vanish() {
+        let dir = tempdir().unwrap();
+        let fs = Arc::new(Mem8Fs::new(dir.path()).unwrap());
+        fs.write("/app/config.toml", b"volume = 3").unwrap();
+        
+        // Written, but dropped before the rename (a crash halfway, say)
+        let mut temp = NamedTempFile::new_in(fs.clone(), "/app").unwrap();
+        assert!(fs.exists(temp.path()));
+        temp.write_all(b"volume = 11").unwrap();
+        temp.flush().unwrap();
+        drop(temp);
+        assert!(hidden(&fs, "/app").is_empty());
+        assert_eq!(fs.read("/app/config.toml").unwrap(), b"volume = 3");
+        
+        // Persisted: renamed over the old file, nothing left behind
+        let mut temp = NamedTempFile::new_in(fs.clone(), "/app").unwrap();
+        let other = NamedTempFile::new_in(fs.clone(), "/app").unwrap();
+        assert_ne!(temp.path(), other.path());
+        temp.write_all(b"volume = 7").unwrap();
+        temp.persist("/app/config.toml").unwrap();
+        drop(other);
+        assert_eq!(fs.read("/app/config.toml").unwrap(), b"volume = 7");
+        assert!(hidden(&fs, "/app").is_empty());
+        
+        fs::write_atomic(fs.clone(), "/app/config.toml", b"volume = 5").unwrap();
+        fs::write_atomic(fs.clone(), "/app/new.toml", b"fresh").unwrap();
+        assert_eq!(fs.read("/app/config.toml").unwrap(), b"volume = 5");
+        assert_eq!(fs.read("/app/new.toml").unwrap(), b"fresh");
+        assert!(hidden(&fs, "/app").is_empty());
+    }
+    
+    #[test]
+    fn test_write_atomic_failing_leaves_the_original() {
+        let dir = tempdir().unwrap();
+        let record = |len: u64| crate::WAVE_RECORD_HEADER_LEN + crate::WAVE_BYTES * len;
+        let config = crate::FsConfig::default().with_max_store_bytes(record(10) + record(0) + 10);
+        let fs = Arc::new(Mem8Fs::new_with_config(dir.path(), config).unwrap());
+        fs.write("/config.toml", b"volume = 3").unwrap();
+        
+        // The temp file gets made, but its content doesn't fit
+        let err = fs::write_atomic(fs.clone(), "/config.toml", b"volume = 11").unwrap_err();
+        assert!(matches!(err, Mem8Error::QuotaExceeded { .. }), "{}", err);
+        assert_eq!(fs.read("/config.toml").unwrap(), b"volume = 3");
+        assert!(hidden(&fs, "/").is_empty());
+    }
+    
+    #[test]
+    fn test_racing_atomic_writes_leave_one_version() {
+        let dir = tempdir().unwrap();
+        let fs = Arc::new(Mem8Fs::new(dir.path()).unwrap());
+        let versions: Vec<Vec<u8>> = (0..4u8).map(|n| vec![b'a' + n; 4096]).collect();
+        let writers: Vec<_> = versions.iter().cloned().map(|version| {
+            let fs = fs.clone();
+            std::thread::spawn(move || {
+                for _ in 0..5 {
+                    fs::write_atomic(fs.clone(), "/shared.bin", &version).unwrap();
+                }
+            })
+        }).collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert!(versions.contains(&fs.read("/shared.bin").unwrap()));
+        assert!(hidden(&fs, "/").is_empty());
+    }
+    
+    #[test]
+    fn test_set_len_truncates_and_extends() {
+        let dir = tempdir().unwrap();
+        let fs = Arc::new(Mem8Fs::new(dir.path()).unwrap());
+        let mut file = File::create(fs.clone(), "/log.txt").unwrap();
+        file.write_all(b"first line, second line").unwrap();
+        file.set_len(10).unwrap();
+        
+        // The position stays past the new end
+        assert_eq!(file.stream_position().unwrap(), 23);
+        file.flush().unwrap();
+        assert_eq!(fs.read("/log.txt").unwrap(), b"first line");
+        
+        file.set_len(12).unwrap();
+        file.seek(SeekFrom::Start(0)).unwrap();
+        file.write_all(b"FIRST").unwrap();
+        file.flush().unwrap();
+        assert_eq!(fs.read("/log.txt").unwrap(), b"FIRST line\0\0");
+    }
+    
+    #[test]
+    fn test_copy_within_a_store_shares_content() {
+        let dir = tempdir().unwrap();
+        let fs = Arc::new(Mem8Fs::new(dir.path()).unwrap());
+        let options = WriteOptions::default().with_frequency(528.0).with_metadata(b"tagged".to_vec());
+        fs.write_with_options("/big.bin", &vec![7u8; 3000], options).unwrap();
+        let before = fs.stats().unwrap().data_bytes;
+        
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = calls.clone();
+        let progress = move |copied, total| seen.lock().unwrap().push((copied, total));
+        let copied = fs::copy_with(fs.clone(), "/big.bin", "/copy.bin", CopyOptions::default().with_progress(progress)).unwrap();
+        assert_eq!(copied, 3000);
+        assert_eq!(*calls.lock().unwrap(), [(3000, 3000)]);
+        assert_eq!(fs.stats().unwrap().data_bytes, before);
+        let copy = fs.metadata("/copy.bin").unwrap();
+        assert_eq!((copy.wave_frequency, copy.metadata.as_deref()), (528.0, Some(&b"tagged"[..])));
+        
+        // Its own file, not a second name for the first
+        fs.delete("/big.bin").unwrap();
+        fs.gc().unwrap();
+        assert_eq!(fs.read("/copy.bin").unwrap(), vec![7u8; 3000]);
+        
+        // Taken destinations need overwrite; times come along only when asked
+        fs.write("/other.txt", b"other").unwrap();
+        let err = fs::copy_with(fs.clone(), "/other.txt", "/copy.bin", CopyOptions::default());
+        assert!(matches!(err, Err(Mem8Error::AlreadyExists(ref path)) if path == "/copy.bin"));
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        let options = CopyOptions::default().with_overwrite(true).with_preserve_times(true);
+        fs::copy_with(fs.clone(), "/copy.bin", "/other.txt", options).unwrap();
+        let other = fs.metadata("/other.txt").unwrap();
+        assert_eq!((other.size, other.modified_ms), (3000, copy.modified_ms));
+        fs::copy(fs.clone(), "/copy.bin", "/other.txt").unwrap();
+        assert!(fs.metadata("/other.txt").unwrap().modified_ms > copy.modified_ms);
+    }
+    
+    #[test]
+    fn test_copy_between_stores_streams_blocks() {
+        let (from_dir, to_dir) = (tempdir().unwrap(), tempdir().unwrap());
+        let from_fs = Arc::new(Mem8Fs::new(from_dir.path()).unwrap());
+        let to_fs = Arc::new(Mem8Fs::new(to_dir.path()).unwrap());
+        let data: Vec<u8> = (0..COPY_BLOCK * 2 + 100).map(|i| (i % 251) as u8).collect();
+        from_fs.write_with_options("/big.bin", &data, WriteOptions::default().with_frequency(432.0)).unwrap();
+        
+        let calls = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let seen = calls.clone();
+        let options = CopyOptions::default()
+            .with_preserve_times(true)
+            .with_progress(move |copied, total| seen.lock().unwrap().push((copied, total)));
+        let copied = fs::copy_between(from_fs.clone(), "/big.bin", to_fs.clone(), "/big.bin", options).unwrap();
+        let total = data.len() as u64;
+        assert_eq!(copied, total);
+        let block = COPY_BLOCK as u64;
+        assert_eq!(*calls.lock().unwrap(), [(block, total), (2 * block, total), (total, total)]);
+        assert_eq!(to_fs.read("/big.bin").unwrap(), data);
+        
+        let (source, copy) = (from_fs.metadata("/big.bin").unwrap(), to_fs.metadata("/big.bin").unwrap());
+        assert_eq!((copy.wave_frequency, copy.created_ms, copy.modified_ms), (432.0, source.created_ms, source.modified_ms));
+        assert!(fs::copy_between(from_fs, "/big.bin", to_fs, "/big.bin", CopyOptions::default()).is_err());
+    }
+}
@@ -0,0 +1,196 @@
+//! Garbage collection - giving back the space dead wave data takes up
+//!
+//! `delete` only drops the index entry and an overwrite leaves the old
+//! record behind, so `data.m8` only ever grows. `gc` rewrites it keeping one
+//! copy of each record a live file or a snapshot still refers to, writes a
+//! matching `data.idx`, and swaps both in. Orphans, repeated copies and
+//! `secure_delete`d records all go, and so do the Marine analyses
+//! (`marine_fs`) of content nothing has any more.
+//!
+//! The whole run holds the index read lock and the storage write lock (the
+//! same order as `secure_delete`), so readers wait for it rather than seeing
+//! a half-swapped file, and a FUSE mount sharing the `Arc<Mem8Fs>` just
+//! carries on afterwards with the same bytes. Hue's spring clean. 🧹
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde::{Serialize, Deserialize};
+use anyhow::anyhow;
+use crate::error::Result;
+use crate::{FileIndex, Mem8Fs, WaveStorage, WAVE_BYTES, WAVE_INDEX_RECORD_LEN, WAVE_RECORD_HEADER_LEN};
+
+/// What `gc` did
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GcReport {
+    /// Records still referenced, one copy each
+    pub records_kept: usize,
+    
+    /// Orphans, repeated copies and scrubbed records dropped
+    pub records_removed: usize,
+    
+    /// Size of `data.m8` before and after
+    pub bytes_before: u64,
+    pub bytes_after: u64,
+    
+    pub reclaimed_bytes: u64,
+}
+
+impl std::fmt::Display for GcReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "🧹 kept {} records, removed {}: {} -> {} bytes ({} reclaimed)",
+            self.records_kept, self.records_removed, self.bytes_before, self.bytes_after, self.reclaimed_bytes)
+    }
+}
+
+impl Mem8Fs {
+    /// Rewrite `data.m8` with only the records something still refers to
+    ///
+    /// Reads and writes wait until it's done; content reads back identically
+    /// afterwards, snapshots included. The new files are written next to the
+    /// old ones and renamed over them, with `data.idx` removed in between, so
+    /// a crash part way leaves either the old store or the new one (an
+    /// interrupted swap just means `data.idx` is rebuilt at the next open).
+    /// Refuses while `relocate_live` is copying the store.
+    pub fn gc(&self) -> Result<GcReport> {
+        self.check_writable()?;
+        let index = self.index.read().unwrap();
+        let mut storage = self.storage.write().unwrap();
+        self.collect_garbage(&index, &mut storage)
+    }
+    
+    /// `gc`, with the locks already held (as a quota eviction has them)
+    pub(crate) fn collect_garbage(&self, index: &FileIndex, storage: &mut WaveStorage) -> Result<GcReport> {
+        if storage.scrubbed_during_copy.is_some() {
+            return Err(anyhow!("The store is being relocated - run gc once it's moved").into());
+        }
+        let referenced = self.signatures_referenced_by(index)?;
+        
+        storage.data_file.flush()?;
+        let data_path = storage.data_path.clone();
+        let dir = data_path.parent().unwrap().to_path_buf();
+        let (new_data_path, new_offsets_path) = (dir.join("data.m8.gc"), dir.join("data.idx.gc"));
+        let offsets_path = dir.join("data.idx");
+        
+        let end = storage.position;
+        let mut from = BufReader::new(File::open(&data_path)?);
+        let mut to = BufWriter::new(File::create(&new_data_path)?);
+        let mut offsets_file = BufWriter::new(File::create(&new_offsets_path)?);
+        let mut offsets = HashMap::new();
+        let mut report = GcReport { bytes_before: end, ..GcReport::default() };
+        let (mut offset, mut written) = (0, 0);
+        let mut body = Vec::new();
+        while offset + WAVE_RECORD_HEADER_LEN <= end {
+            let mut signature = [0u8; 32];
+            from.read_exact(&mut signature)?;
+            let count = from.read_u32::<BigEndian>()?;
+            let bytes = WAVE_RECORD_HEADER_LEN + count as u64 * WAVE_BYTES;
+            offset += bytes;
+            
+            let keep = signature != [0u8; 32] && referenced.contains(&signature) && !offsets.contains_key(&signature);
+            if !keep {
+                from.seek_relative((bytes - WAVE_RECORD_HEADER_LEN) as i64)?;
+                report.records_removed += 1;
+                continue;
+            }
+            body.resize((bytes - WAVE_RECORD_HEADER_LEN) as usize, 0);
+            from.read_exact(&mut body)?;
+            to.write_all(&signature)?;
+            to.write_u32::<BigEndian>(count)?;
+            to.write_all(&body)?;
+            
+            let mut record = Vec::with_capacity(WAVE_INDEX_RECORD_LEN);
+            record.extend_from_slice(&signature);
+            record.write_u64::<BigEndian>(written)?;
+            record.write_u32::<BigEndian>(count)?;
+            offsets_file.write_all(&record)?;
+            offsets.insert(signature, (written, count));
+            written += bytes;
+            report.records_kept += 1;
+        }
+        to.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        offsets_file.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        
+        // Without data.idx an open walks whichever data.m8 it finds
+        std::fs::remove_file(&offsets_path)?;
+        std::fs::rename(&new_data_path, &data_path)?;
+        std::fs::rename(&new_offsets_path, &offsets_path)?;
+        
+        storage.rebase(data_path, &offsets_path)?;
+        storage.offsets = offsets;
+        storage.position = written;
+        storage.cache.retain(|signature, _| referenced.contains(signature));
+        self.prune_marine(index)?;
+        
+        report.bytes_after = written;
+        report.reclaimed_bytes = end - written;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tempfile::tempdir;
+    
+    #[test]
+    fn test_gc_reclaims_dead_records_and_reads_stay_the_same() {
+        let dir = tempdir().unwrap();
+        let data_path = dir.path().join(".mem8").join("data.m8");
+        let fs = Arc::new(Mem8Fs::new(dir.path()).unwrap());
+        fs.write("/keep.txt", b"keep me").unwrap();
+        fs.write("/config.json", b"{\"v\": 1}").unwrap();
+        fs.create_snapshot("v1").unwrap();
+        fs.write("/config.json", b"{\"v\": 2}").unwrap();
+        fs.write("/gone.txt", b"deleted soon").unwrap();
+        fs.delete("/gone.txt").unwrap();
+        fs.write("/log.txt", b"one").unwrap();
+        fs.append("/log.txt", b", two").unwrap();
+        let before = std::fs::metadata(&data_path).unwrap().len();
+        
+        // A reader going the whole time, as a FUSE mount would
+        let stop = Arc::new(AtomicBool::new(false));
+        let reader = {
+            let (fs, stop) = (fs.clone(), stop.clone());
+            std::thread::spawn(move || {
+                while !stop.load(Ordering::Relaxed) {
+                    assert_eq!(fs.read("/keep.txt").unwrap(), b"keep me");
+                }
+            })
+        };
+        let report = fs.gc().unwrap();
+        stop.store(true, Ordering::Relaxed);
+        reader.join().unwrap();
+        
+        // Only gone.txt's record was dead; v1's config lives on in the snapshot
+        assert_eq!((report.records_kept, report.records_removed), (5, 1));
+        assert_eq!(report.bytes_before, before);
+        assert_eq!(report.reclaimed_bytes, 36 + 16 * 12);
+        assert_eq!(std::fs::metadata(&data_path).unwrap().len(), report.bytes_after);
+        
+        let check = |fs: &Mem8Fs| {
+            assert_eq!(fs.read("/keep.txt").unwrap(), b"keep me");
+            assert_eq!(fs.read("/config.json").unwrap(), b"{\"v\": 2}");
+            assert_eq!(fs.read("/log.txt").unwrap(), b"one, two");
+            assert_eq!(fs.read_at_snapshot("v1", "/config.json").unwrap(), b"{\"v\": 1}");
+            assert!(fs.find_orphans().unwrap().is_empty());
+        };
+        check(&fs);
+        fs.write("/after.txt", b"written after gc").unwrap();
+        assert_eq!(fs.gc().unwrap().records_removed, 0);
+        drop(fs);
+        
+        // From disk alone, the cache gone
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        check(&fs);
+        assert_eq!(fs.read("/after.txt").unwrap(), b"written after gc");
+        
+        // An interrupted swap (no data.idx) still opens
+        drop(fs);
+        std::fs::remove_file(dir.path().join(".mem8").join("data.idx")).unwrap();
+        check(&Mem8Fs::new(dir.path()).unwrap());
+    }
+}
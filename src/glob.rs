@@ -0,0 +1,183 @@
+//! Glob patterns - finding Mem8Fs files by shape, not by listing
+//!
+//! `*` matches within one path component, `?` one character, `**` (as a
+//! whole component) any number of directories, `[abc]`, `[a-z]` and
+//! `[!abc]` one character from (or not from) a class, and `\` escapes the
+//! next character. Patterns are matched against whole absolute paths; a
+//! relative pattern hangs off the root like any other Mem8Fs path.
+//!
+//! Trisha finds receipts with `/taxes/**/*.pdf`. Hue finds socks. 🔎
+
+use std::path::Path;
+use anyhow::{Result, anyhow};
+
+/// A compiled glob pattern (see the module docs for the syntax)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobPattern {
+    pattern: String,
+    components: Vec<Component>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Component {
+    /// `**`: zero or more whole components
+    AnyDepth,
+    Name(Vec<Token>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Literal(char),
+    /// `*`
+    AnyRun,
+    /// `?`
+    AnyChar,
+    Class { negated: bool, ranges: Vec<(char, char)> },
+}
+
+impl GlobPattern {
+    pub fn new(pattern: &str) -> Result<Self> {
+        let components = pattern.split('/')
+            .filter(|component| !component.is_empty())
+            .map(|component| match component {
+                "**" => Ok(Component::AnyDepth),
+                _ => parse_component(component)
+                    .map(Component::Name)
+                    .map_err(|reason| anyhow!("Bad glob pattern {:?}: {}", pattern, reason)),
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { pattern: pattern.to_string(), components })
+    }
+    
+    /// The pattern as written
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+    
+    /// Does the whole of `path` match?
+    pub fn matches(&self, path: &Path) -> bool {
+        let names: Vec<String> = path.components()
+            .filter_map(|component| match component {
+                std::path::Component::Normal(name) => Some(name.to_string_lossy().into_owned()),
+                _ => None,
+            })
+            .collect();
+        let names: Vec<Vec<char>> = names.iter().map(|name| name.chars().collect()).collect();
+        match_components(&self.components, &names)
+    }
+}
+
+fn parse_component(component: &str) -> std::result::Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = component.chars();
+    while let Some(c) = chars.next() {
+        tokens.push(match c {
+            '*' => Token::AnyRun,
+            '?' => Token::AnyChar,
+            '\\' => Token::Literal(chars.next().ok_or("trailing \\")?),
+            '[' => {
+                let mut class: Vec<char> = Vec::new();
+                loop {
+                    match chars.next() {
+                        // `]` straight after `[` or `[!` is part of the class
+                        Some(']') if !class.is_empty() && class != ['!'] && class != ['^'] => break,
+                        Some(c) => class.push(c),
+                        None => return Err("unclosed [".to_string()),
+                    }
+                }
+                let negated = matches!(class.first(), Some('!' | '^'));
+                let members = &class[negated as usize..];
+                let mut ranges = Vec::new();
+                let mut i = 0;
+                while i < members.len() {
+                    if i + 2 < members.len() && members[i + 1] == '-' {
+                        if members[i] > members[i + 2] {
+                            return Err(format!("range {}-{} is backwards", members[i], members[i + 2]));
+                        }
+                        ranges.push((members[i], members[i + 2]));
+                        i += 3;
+                    } else {
+                        ranges.push((members[i], members[i]));
+                        i += 1;
+                    }
+                }
+                Token::Class { negated, ranges }
+            }
+            c => Token::Literal(c),
+        });
+    }
+    Ok(tokens)
+}
+
+fn match_components(pattern: &[Component], names: &[Vec<char>]) -> bool {
+    match pattern.split_first() {
+        None => names.is_empty(),
+        Some((Component::AnyDepth, rest)) => {
+            (0..=names.len()).any(|skip| match_components(rest, &names[skip..]))
+        }
+        Some((Component::Name(tokens), rest)) => match names.split_first() {
+            Some((name, names)) => match_name(tokens, name) && match_components(rest, names),
+            None => false,
+        },
+    }
+}
+
+fn match_name(tokens: &[Token], name: &[char]) -> bool {
+    match tokens.split_first() {
+        None => name.is_empty(),
+        Some((Token::AnyRun, rest)) => (0..=name.len()).any(|skip| match_name(rest, &name[skip..])),
+        Some((token, rest)) => match name.split_first() {
+            Some((&c, name)) => token_matches(token, c) && match_name(rest, name),
+            None => false,
+        },
+    }
+}
+
+fn token_matches(token: &Token, c: char) -> bool {
+    match token {
+        Token::Literal(literal) => *literal == c,
+        Token::AnyChar => true,
+        Token::Class { negated, ranges } => ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi) != *negated,
+        Token::AnyRun => unreachable!("handled by match_name"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn matches(pattern: &str, path: &str) -> bool {
+        GlobPattern::new(pattern).unwrap().matches(Path::new(path))
+    }
+    
+    #[test]
+    fn test_wildcards_stay_within_a_component() {
+        assert!(matches("/logs/*.json", "/logs/a.json"));
+        assert!(!matches("/logs/*.json", "/logs/2024/a.json"));
+        assert!(matches("/logs/?.json", "/logs/a.json"));
+        assert!(!matches("/logs/?.json", "/logs/ab.json"));
+        assert!(matches("logs/*", "/logs/a.json"));
+    }
+    
+    #[test]
+    fn test_double_star_spans_directories() {
+        assert!(matches("/logs/**/*.json", "/logs/a.json"));
+        assert!(matches("/logs/**/*.json", "/logs/2024/06/a.json"));
+        assert!(!matches("/logs/**/*.json", "/other/a.json"));
+        assert!(matches("/**", "/anything/at/all"));
+    }
+    
+    #[test]
+    fn test_classes_ranges_and_escapes() {
+        assert!(matches("/[ab]*.txt", "/b1.txt"));
+        assert!(!matches("/[!ab]*.txt", "/b1.txt"));
+        assert!(matches("/day[0-9].log", "/day7.log"));
+        assert!(!matches("/day[0-9].log", "/dayx.log"));
+        assert!(matches("/[]]", "/]"));
+        assert!(matches("/what\\?", "/what?"));
+        assert!(!matches("/what\\?", "/whats"));
+        
+        assert!(GlobPattern::new("/[abc").is_err());
+        assert!(GlobPattern::new("/[z-a]").is_err());
+    }
+}
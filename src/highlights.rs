@@ -0,0 +1,260 @@
+//! Highlights reel - the top wonder moments of a stored track
+//!
+//! Uses the wonder moments that `AudioProcessor::store_audio` keeps in the
+//! track's metadata, so picking highlights never re-runs the Marine analysis.
+//! Clips are cut around each moment, snapped to the beat when the track has
+//! one, and get a short fade so they don't click in and out.
+//!
+//! Trisha calls it "the trailer for your playlist" 🎬
+
+use serde_json::{json, Value};
+use anyhow::{Result, anyhow};
+
+use crate::audio::{AudioFormat, SampleRate, decode_sample, encode_sample};
+use crate::float_format::{json_f64, FloatCategory};
+use crate::lite::Mem8Lite;
+
+/// Longest fade applied at each end of a clip
+const MAX_FADE_SECONDS: f64 = 0.01;
+
+/// One extracted highlight
+#[derive(Debug, Clone)]
+pub struct HighlightClip {
+    /// Where the clip starts in the source track
+    pub start_seconds: f64,
+    
+    /// Clip length
+    pub duration_seconds: f64,
+    
+    /// The wonder moment the clip was cut around
+    pub peak_seconds: f64,
+    
+    /// Marine salience of that moment
+    pub salience: f64,
+    
+    /// PCM format of `pcm` (same as the source track)
+    pub format: AudioFormat,
+    
+    /// Faded PCM for the clip
+    pub pcm: Vec<u8>,
+}
+
+impl HighlightClip {
+    /// JSON description (without the PCM payload)
+    pub fn to_json(&self) -> Value {
+        json!({
+            "start": json_f64(self.start_seconds, FloatCategory::Duration),
+            "duration": json_f64(self.duration_seconds, FloatCategory::Duration),
+            "peak": json_f64(self.peak_seconds, FloatCategory::Duration),
+            "salience": json_f64(self.salience, FloatCategory::Salience),
+        })
+    }
+}
+
+/// Pick the top `count` non-overlapping wonder moments of a stored track and cut clips
+/// 
+/// The track must have been stored with `AudioProcessor::store_audio`, which
+/// records its format and wonder moments in the metadata.
+pub fn extract_highlights(
+    storage: &Mem8Lite,
+    signature: &[u8; 32],
+    count: usize,
+    clip_seconds: f64,
+) -> Result<Vec<HighlightClip>> {
+    let meta: Value = storage.get_metadata(signature)
+        .ok_or_else(|| anyhow!("No metadata for {} - was it stored with store_audio?", hex::encode(signature)))
+        .and_then(|m| Ok(serde_json::from_slice(&m)?))?;
+    
+    let format = format_from_metadata(&meta["format"])?;
+    let rate = format.sample_rate.as_f64();
+    let clip_frames = ((clip_seconds * rate).round() as usize).max(1);
+    
+    // Best moments first, never two within one clip length of each other
+    let mut moments: Vec<(usize, f64)> = meta["marine"]["moments"].as_array()
+        .ok_or_else(|| anyhow!("Track metadata has no wonder moments"))?
+        .iter()
+        .filter_map(|m| Some((m["frame"].as_u64()? as usize, m["salience"].as_f64()?)))
+        .collect();
+    moments.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+    
+    let mut picked: Vec<(usize, f64)> = Vec::new();
+    for moment in moments {
+        if picked.len() >= count {
+            break;
+        }
+        if picked.iter().all(|p| p.0.abs_diff(moment.0) >= clip_frames) {
+            picked.push(moment);
+        }
+    }
+    if picked.is_empty() {
+        return Ok(Vec::new());
+    }
+    
+    // Only the clips are read, not the whole track
+    let bytes_per_frame = format.channels * format.bit_depth / 8;
+    let total_frames = storage.len(signature)? / bytes_per_frame;
+    let clip_frames = clip_frames.min(total_frames);
+    let beat_frames = meta["marine"]["beat_period"].as_f64().map(|p| p * rate);
+    
+    picked.into_iter().map(|(frame, salience)| {
+        let mut start = frame.saturating_sub(clip_frames / 2) as f64;
+        
+        // Snap to the beat grid when beats are short enough to matter
+        if let Some(beat) = beat_frames {
+            if beat > 0.0 && beat <= clip_frames as f64 / 4.0 {
+                start = (start / beat).round() * beat;
+            }
+        }
+        let start = (start as usize).min(total_frames - clip_frames);
+        
+        let mut clip = storage.retrieve_range(signature, start * bytes_per_frame, clip_frames * bytes_per_frame)?;
+        apply_fade(&mut clip, &format, ((MAX_FADE_SECONDS * rate) as usize).min(clip_frames / 10))?;
+        
+        Ok(HighlightClip {
+            start_seconds: start as f64 / rate,
+            duration_seconds: clip_frames as f64 / rate,
+            peak_seconds: frame as f64 / rate,
+            salience,
+            format: format.clone(),
+            pcm: clip,
+        })
+    }).collect()
+}
+
+/// Store clips from `extract_highlights` as packets linked to their track
+/// 
+/// Each clip's metadata carries `highlight_of` (the source signature, hex)
+/// plus its rank (its place in `clips`) and position. Returns the clip
+/// signatures, in the same order.
+pub fn store_highlights(
+    storage: &mut Mem8Lite,
+    signature: &[u8; 32],
+    clips: &[HighlightClip],
+) -> Result<Vec<[u8; 32]>> {
+    clips.iter().enumerate().map(|(rank, clip)| {
+        let mut meta = clip.to_json();
+        meta["highlight_of"] = json!(hex::encode(signature));
+        meta["rank"] = json!(rank + 1);
+        meta["format"] = json!({
+            "sample_rate": json_f64(clip.format.sample_rate.as_f64(), FloatCategory::Frequency),
+            "channels": clip.format.channels,
+            "bit_depth": clip.format.bit_depth,
+            "is_float": clip.format.is_float,
+            "wave_frequency": storage.frequency_preset(),
+        });
+        Ok(storage.store(&clip.pcm, Some(serde_json::to_vec(&meta)?))?)
+    }).collect()
+}
+
+/// Rebuild the PCM format recorded by `store_audio`
+pub(crate) fn format_from_metadata(format: &Value) -> Result<AudioFormat> {
+    let field = |name: &str| format[name].as_f64()
+        .ok_or_else(|| anyhow!("Track metadata is missing format.{}", name));
+    
+    Ok(AudioFormat {
+        sample_rate: SampleRate::from_hz(field("sample_rate")?),
+        channels: field("channels")? as usize,
+        bit_depth: field("bit_depth")? as usize,
+        is_float: format["is_float"].as_bool().unwrap_or(false),
+    })
+}
+
+/// Linear fade in/out over `fade_frames` at each end
+fn apply_fade(pcm: &mut [u8], format: &AudioFormat, fade_frames: usize) -> Result<()> {
+    let bytes_per_sample = format.bit_depth / 8;
+    let bytes_per_frame = format.channels * bytes_per_sample;
+    let frames = pcm.len() / bytes_per_frame;
+    if fade_frames == 0 || frames == 0 {
+        return Ok(());
+    }
+    
+    for (frame, chunk) in pcm.chunks_exact_mut(bytes_per_frame).enumerate() {
+        let from_edge = frame.min(frames - 1 - frame);
+        if from_edge >= fade_frames {
+            continue;
+        }
+        let gain = from_edge as f64 / fade_frames as f64;
+        for sample in chunk.chunks_exact_mut(bytes_per_sample) {
+            let value = decode_sample(sample, format)?;
+            encode_sample(value * gain, format, sample)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::audio::AudioProcessor;
+    use tempfile::tempdir;
+    
+    /// 12 s of quiet noise with three loud bursts at 2 s, 6 s and 9.5 s
+    fn synthetic_track(rate: f64) -> Vec<u8> {
+        let bursts = [2.0, 6.0, 9.5];
+        let mut seed: u32 = 42;
+        let mut pcm = Vec::new();
+        for i in 0..(12.0 * rate) as usize {
+            let t = i as f64 / rate;
+            seed = seed.wrapping_mul(1664525).wrapping_add(1013904223);
+            let noise = (seed >> 16) as f64 / 65536.0 - 0.5;
+            let mut sample = noise * 0.1;
+            for &b in &bursts {
+                if (t - b).abs() < 0.1 {
+                    sample = 0.9 * (2.0 * std::f64::consts::PI * 440.0 * t).sin();
+                }
+            }
+            pcm.extend_from_slice(&((sample * 32767.0) as i16).to_le_bytes());
+        }
+        pcm
+    }
+    
+    #[test]
+    fn test_highlights_center_on_bursts() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("tracks.m8");
+        let format = AudioFormat { channels: 1, ..AudioFormat::phone_quality() };
+        let rate = format.sample_rate.as_f64();
+        
+        let mut processor = AudioProcessor::new(format, path.to_str().unwrap()).unwrap();
+        let sig = processor.store_audio(&synthetic_track(rate), "bursts").unwrap();
+        
+        let clips = processor.extract_highlights(&sig, 3, 1.0).unwrap();
+        assert_eq!(clips.len(), 3);
+        
+        let mut centers: Vec<f64> = clips.iter()
+            .map(|c| c.start_seconds + c.duration_seconds / 2.0)
+            .collect();
+        centers.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        for (center, burst) in centers.iter().zip([2.0, 6.0, 9.5]) {
+            assert!((center - burst).abs() < 0.15, "clip centered at {} not {}", center, burst);
+        }
+        
+        // Faded edges, full-length clips
+        for clip in &clips {
+            assert_eq!(clip.pcm.len(), (rate as usize) * 2);
+            assert_eq!(&clip.pcm[..2], &[0, 0]);
+        }
+    }
+    
+    #[test]
+    fn test_store_highlights_links_back() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("tracks.m8");
+        let format = AudioFormat { channels: 1, ..AudioFormat::phone_quality() };
+        let rate = format.sample_rate.as_f64();
+        
+        let mut processor = AudioProcessor::new(format, path.to_str().unwrap()).unwrap();
+        let sig = processor.store_audio(&synthetic_track(rate), "bursts").unwrap();
+        drop(processor);
+        
+        let mut storage = Mem8Lite::new(&path, SampleRate::Phone16k.frequency_preset()).unwrap();
+        let extracted = extract_highlights(&storage, &sig, 2, 0.5).unwrap();
+        let clips = store_highlights(&mut storage, &sig, &extracted).unwrap();
+        assert_eq!(clips.len(), 2);
+        
+        let meta: Value = serde_json::from_slice(&storage.get_metadata(&clips[0]).unwrap()).unwrap();
+        assert_eq!(meta["highlight_of"], json!(hex::encode(sig)));
+        assert_eq!(meta["rank"], json!(1));
+        assert_eq!(storage.retrieve(&clips[0]).unwrap().len(), (rate as usize / 2) * 2);
+    }
+}
@@ -0,0 +1,355 @@
+//! Host directories - copying a real directory tree into Mem8Fs and back out
+//!
+//! `Mem8Fs::import_dir` walks a directory on the host and stores every file
+//! under a Mem8Fs prefix, keeping each file's mtime as its `modified` time;
+//! `Mem8Fs::export_dir` writes a Mem8Fs subtree back out, mtimes included.
+//! Both stream: files are copied `TRANSFER_BLOCK` bytes at a time, so a big
+//! file never has to fit in memory. An imported file bigger than one block is
+//! stored as chunks (one per block, like `append`), so its signature is the
+//! chunk-list one rather than what a single `write` of the same bytes gives.
+//!
+//! Symlinks are skipped unless `ImportOptions::follow_symlinks` is set (loops
+//! are caught either way), as are sockets, fifos and devices, and the store's
+//! own `.mem8` directory. Everything skipped is listed in the report.
+//!
+//! Trisha packs the filing cabinet into a wave and unpacks it again. 🗄️
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Serialize, Deserialize};
+use crate::error::{Mem8Error, Result};
+use crate::Mem8Fs;
+
+/// Bytes read or written at a time - the most of a file held in memory
+pub const TRANSFER_BLOCK: usize = 1 << 20;
+
+/// Says whether a host path (relative to the directory being imported) goes in
+pub type PathFilter = Arc<dyn Fn(&Path) -> bool + Send + Sync>;
+
+/// How `Mem8Fs::import_dir` walks the host directory
+#[derive(Clone, Default)]
+pub struct ImportOptions {
+    /// Follow symlinks to files and directories instead of skipping them
+    pub follow_symlinks: bool,
+    
+    /// Only files and directories this accepts are imported (all, if `None`)
+    pub filter: Option<PathFilter>,
+}
+
+impl ImportOptions {
+    pub fn with_follow_symlinks(mut self, follow: bool) -> Self {
+        self.follow_symlinks = follow;
+        self
+    }
+    
+    pub fn with_filter(mut self, filter: impl Fn(&Path) -> bool + Send + Sync + 'static) -> Self {
+        self.filter = Some(Arc::new(filter));
+        self
+    }
+}
+
+impl std::fmt::Debug for ImportOptions {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ImportOptions")
+            .field("follow_symlinks", &self.follow_symlinks)
+            .field("filter", &self.filter.as_ref().map(|_| "<fn>"))
+            .finish()
+    }
+}
+
+/// What `import_dir` or `export_dir` copied
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TransferReport {
+    /// Files copied
+    pub files: usize,
+    
+    /// Bytes copied, over all files
+    pub bytes: u64,
+    
+    /// Host paths left out: filtered, symlinks not followed, loops and special files
+    pub skipped: Vec<PathBuf>,
+}
+
+impl std::fmt::Display for TransferReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "🗄️ {} files, {} bytes copied, {} skipped", self.files, self.bytes, self.skipped.len())
+    }
+}
+
+impl Mem8Fs {
+    /// Store every file under the host directory `os_path` below `dest_prefix`
+    ///
+    /// Directories come across too, empty ones included. Files that already
+    /// exist at the destination are replaced.
+    pub fn import_dir(&self, os_path: impl AsRef<Path>, dest_prefix: impl AsRef<Path>, options: ImportOptions) -> Result<TransferReport> {
+        self.import_dir_with_progress(os_path, dest_prefix, options, |_, _| {})
+    }
+    
+    /// `import_dir`, calling `progress(path, bytes)` after every block with
+    /// the Mem8Fs path being written and the bytes copied so far in all
+    pub fn import_dir_with_progress(
+        &self,
+        os_path: impl AsRef<Path>,
+        dest_prefix: impl AsRef<Path>,
+        options: ImportOptions,
+        mut progress: impl FnMut(&Path, u64),
+    ) -> Result<TransferReport> {
+        self.check_writable()?;
+        let os_path = os_path.as_ref();
+        let dest = self.normalize_path(dest_prefix)?;
+        let own_store = std::fs::canonicalize(self.store_dir()).ok();
+        let mut report = TransferReport::default();
+        let mut times = Vec::new();
+        
+        self.create_dir(&dest)?;
+        let mut visited = HashSet::from([std::fs::canonicalize(os_path)?]);
+        let mut pending = vec![(os_path.to_path_buf(), dest)];
+        while let Some((host_dir, dir)) = pending.pop() {
+            let mut children: Vec<_> = std::fs::read_dir(&host_dir)?.collect::<std::io::Result<_>>()?;
+            children.sort_by_key(|child| child.file_name());
+            for child in children {
+                let host = child.path();
+                let relative = host.strip_prefix(os_path).unwrap_or(&host);
+                let accepted = options.filter.as_ref().is_none_or(|filter| filter(relative));
+                let kind = child.file_type()?;
+                let meta = match kind.is_symlink() {
+                    true if !options.follow_symlinks => None,
+                    // A dangling link has nothing to import
+                    true => std::fs::metadata(&host).ok(),
+                    false => Some(child.metadata()?),
+                };
+                let Some(meta) = meta.filter(|_| accepted) else {
+                    report.skipped.push(host);
+                    continue;
+                };
+                
+                let path = dir.join(child.file_name());
+                if meta.is_dir() {
+                    let real = std::fs::canonicalize(&host)?;
+                    if own_store.as_ref() == Some(&real) || !visited.insert(real) {
+                        report.skipped.push(host);
+                        continue;
+                    }
+                    self.create_dir(&path)?;
+                    pending.push((host, path));
+                } else if meta.is_file() {
+                    let mut file = File::open(&host)?;
+                    let mut block = vec![0u8; TRANSFER_BLOCK];
+                    let mut first = true;
+                    loop {
+                        let read = fill(&mut file, &mut block)?;
+                        if read == 0 && !first {
+                            break;
+                        }
+                        match first {
+                            true => self.write(&path, &block[..read])?,
+                            false => self.append(&path, &block[..read])?,
+                        };
+                        first = false;
+                        report.bytes += read as u64;
+                        progress(&path, report.bytes);
+                        if read < block.len() {
+                            break;
+                        }
+                    }
+                    let modified = meta.modified().map_or(0, unix_millis);
+                    let created = meta.created().map_or(modified, unix_millis).min(modified);
+                    times.push((path, created, modified));
+                    report.files += 1;
+                } else {
+                    report.skipped.push(host);
+                }
+            }
+        }
+        
+        // The host's times, not the import's
+        let mut index = self.index.write().unwrap();
+        for (path, created, modified) in times {
+            if let Some(entry) = index.files.get_mut(&path) {
+                (entry.created, entry.modified) = (created, modified);
+            }
+        }
+        self.save_index(&index)?;
+        Ok(report)
+    }
+    
+    /// Write every file below `prefix` out under the host directory `os_path`
+    ///
+    /// Creates `os_path` and the directories below it as needed, overwrites
+    /// host files in the way and sets each file's mtime to its `modified`.
+    pub fn export_dir(&self, prefix: impl AsRef<Path>, os_path: impl AsRef<Path>) -> Result<TransferReport> {
+        self.export_dir_with_progress(prefix, os_path, |_, _| {})
+    }
+    
+    /// `export_dir`, calling `progress(path, bytes)` after every block with
+    /// the Mem8Fs path being read and the bytes copied so far in all
+    pub fn export_dir_with_progress(
+        &self,
+        prefix: impl AsRef<Path>,
+        os_path: impl AsRef<Path>,
+        mut progress: impl FnMut(&Path, u64),
+    ) -> Result<TransferReport> {
+        let prefix = self.normalize_path(prefix)?;
+        let os_path = os_path.as_ref();
+        let mut report = TransferReport::default();
+        
+        std::fs::create_dir_all(os_path)?;
+        for entry in self.walk(&prefix)? {
+            let host = os_path.join(entry.path.strip_prefix(&prefix).unwrap_or(&entry.path));
+            if entry.is_dir {
+                std::fs::create_dir_all(&host)?;
+                continue;
+            }
+            
+            let modified = match self.metadata(&entry.path) {
+                Ok(meta) => meta.modified_ms,
+                // Deleted since the walk
+                Err(Mem8Error::NotFound(_)) => continue,
+                Err(e) => return Err(e),
+            };
+            let mut file = File::create(&host)?;
+            let mut offset = 0;
+            while offset < entry.size as usize {
+                let block = self.read_range(&entry.path, offset, TRANSFER_BLOCK)?;
+                if block.is_empty() {
+                    break;
+                }
+                file.write_all(&block)?;
+                offset += block.len();
+                report.bytes += block.len() as u64;
+                progress(&entry.path, report.bytes);
+            }
+            file.set_modified(UNIX_EPOCH + Duration::from_millis(modified))?;
+            report.files += 1;
+        }
+        Ok(report)
+    }
+}
+
+/// Read until `block` is full or the file ends
+fn fill(file: &mut File, block: &mut [u8]) -> std::io::Result<usize> {
+    let mut read = 0;
+    while read < block.len() {
+        match file.read(&mut block[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
+fn unix_millis(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map_or(0, |since| since.as_millis() as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    
+    /// When the fixture files were last changed
+    const MTIME_MS: u64 = 1_700_000_000_123;
+    
+    fn fixture(root: &Path) -> Vec<u8> {
+        let large: Vec<u8> = (0..3 * TRANSFER_BLOCK + 12_345).map(|i| (i * 7 % 251) as u8).collect();
+        std::fs::create_dir_all(root.join("docs/notes/deep")).unwrap();
+        std::fs::create_dir_all(root.join("empty")).unwrap();
+        std::fs::write(root.join("top.txt"), b"at the top").unwrap();
+        std::fs::write(root.join("docs/readme.md"), b"# docs").unwrap();
+        std::fs::write(root.join("docs/notes/deep/bottom.txt"), b"all the way down").unwrap();
+        std::fs::write(root.join("docs/scratch.tmp"), b"leave me out").unwrap();
+        std::fs::write(root.join("nothing.bin"), b"").unwrap();
+        std::fs::write(root.join("large.bin"), &large).unwrap();
+        for file in ["top.txt", "docs/readme.md", "docs/notes/deep/bottom.txt", "nothing.bin", "large.bin"] {
+            File::options().write(true).open(root.join(file)).unwrap()
+                .set_modified(UNIX_EPOCH + Duration::from_millis(MTIME_MS)).unwrap();
+        }
+        large
+    }
+    
+    #[test]
+    fn test_import_and_export_round_trip() {
+        let host = tempdir().unwrap();
+        let large = fixture(host.path());
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(host.path().join("top.txt"), host.path().join("link.txt")).unwrap();
+        
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        let options = ImportOptions::default()
+            .with_filter(|path| path.extension().is_none_or(|ext| ext != "tmp"));
+        let mut calls = Vec::new();
+        let report = fs.import_dir_with_progress(host.path(), "/imported", options, |path, bytes| {
+            calls.push((path.to_path_buf(), bytes));
+        }).unwrap();
+        
+        let total = large.len() as u64 + 10 + 6 + 16;
+        assert_eq!((report.files, report.bytes), (5, total));
+        let mut skipped = vec![host.path().join("docs/scratch.tmp")];
+        #[cfg(unix)]
+        skipped.insert(0, host.path().join("link.txt"));
+        assert_eq!(report.skipped, skipped);
+        assert!(calls.windows(2).all(|w| w[0].1 <= w[1].1));
+        assert_eq!(calls.iter().filter(|(path, _)| path == Path::new("/imported/large.bin")).count(), 4);
+        assert_eq!(calls.last().unwrap().1, total);
+        
+        assert_eq!(fs.read("/imported/large.bin").unwrap(), large);
+        assert_eq!(fs.read("/imported/docs/notes/deep/bottom.txt").unwrap(), b"all the way down");
+        assert_eq!(fs.read("/imported/nothing.bin").unwrap(), b"");
+        assert_eq!(fs.metadata("/imported/top.txt").unwrap().modified_ms, MTIME_MS);
+        assert!(fs.read_dir("/imported/empty").unwrap().next().is_none());
+        assert!(matches!(fs.read("/imported/docs/scratch.tmp"), Err(Mem8Error::NotFound(_))));
+        
+        // Out again: same bytes, same mtimes, same shape
+        let out = tempdir().unwrap();
+        let exported = fs.export_dir("/imported", out.path().join("tree")).unwrap();
+        assert_eq!((exported.files, exported.bytes, exported.skipped.len()), (5, total, 0));
+        let tree = out.path().join("tree");
+        assert_eq!(std::fs::read(tree.join("large.bin")).unwrap(), large);
+        assert_eq!(std::fs::read(tree.join("docs/notes/deep/bottom.txt")).unwrap(), b"all the way down");
+        assert_eq!(std::fs::read(tree.join("nothing.bin")).unwrap(), b"");
+        assert!(tree.join("empty").is_dir());
+        assert!(!tree.join("docs/scratch.tmp").exists());
+        let mtime = std::fs::metadata(tree.join("large.bin")).unwrap().modified().unwrap();
+        assert_eq!(unix_millis(mtime), MTIME_MS);
+        
+        // And back in, from the export, with nothing lost
+        let again = tempdir().unwrap();
+        let copy = Mem8Fs::new(again.path()).unwrap();
+        assert_eq!(copy.import_dir(&tree, "/", ImportOptions::default()).unwrap().bytes, total);
+        assert_eq!(copy.read("/large.bin").unwrap(), large);
+        assert_eq!(copy.metadata("/docs/readme.md").unwrap().modified_ms, MTIME_MS);
+    }
+    
+    #[cfg(unix)]
+    #[test]
+    fn test_followed_symlinks_and_loops() {
+        let host = tempdir().unwrap();
+        std::fs::create_dir_all(host.path().join("a")).unwrap();
+        std::fs::write(host.path().join("a/file.txt"), b"linked to").unwrap();
+        std::os::unix::fs::symlink(host.path().join("a/file.txt"), host.path().join("link.txt")).unwrap();
+        std::os::unix::fs::symlink(host.path(), host.path().join("a/loop")).unwrap();
+        std::os::unix::fs::symlink(host.path().join("gone"), host.path().join("dangling")).unwrap();
+        
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        let report = fs.import_dir(host.path(), "/host", ImportOptions::default().with_follow_symlinks(true)).unwrap();
+        assert_eq!(report.files, 2);
+        assert_eq!(fs.read("/host/link.txt").unwrap(), b"linked to");
+        assert_eq!(report.skipped, vec![host.path().join("dangling"), host.path().join("a/loop")]);
+        
+        // A store inside the tree it imports doesn't import itself
+        let inside = Mem8Fs::new(host.path().join("a")).unwrap();
+        let report = inside.import_dir(host.path(), "/", ImportOptions::default()).unwrap();
+        assert!(report.skipped.contains(&host.path().join("a/.mem8")));
+        assert_eq!(inside.read("/a/file.txt").unwrap(), b"linked to");
+        
+        fs.close().unwrap();
+        let read_only = Mem8Fs::open_read_only(dir.path(), crate::LockMode::Fail).unwrap();
+        assert!(matches!(read_only.import_dir(host.path(), "/x", ImportOptions::default()), Err(Mem8Error::ReadOnly)));
+    }
+}
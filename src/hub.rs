@@ -0,0 +1,1102 @@
+//! Sensor hub - ingress, fusion, storage and MCP wired into one home hub
+//!
+//! `SensorHub` is the reference composition of the sensor pieces: readings
+//! come in (from the `HttpIngress` or straight through `ingest`), go through
+//! `SensorFusion` (derived sensors, fusion rules, pattern detection), get
+//! stored in the MCP server's store with a retention window, trip threshold
+//! alerts, and end up in the context the MCP tools report
+//! (`mem8.get_sensor_data`, `mem8.get_mood_state`).
+//!
+//! A hub is described by a TOML file:
+//!
+//! ```toml
+//! store = "/var/lib/mem8/hub.m8"
+//! retention_secs = 86400
+//! staleness_secs = 300
+//! http_bind = "127.0.0.1:8088"
+//!
+//! [[sensors]]
+//! id = "desk_lux"
+//! kind = "photoresistor"
+//!
+//! [[derived]]
+//! input = "desk_lux"
+//! output = "desk_lux_trend"
+//! transform = { kind = "rate_of_change", window = 300 }
+//!
+//! [[alerts]]
+//! sensor = "desk_lux"
+//! below = 50.0
+//! message = "Desk is getting dark"
+//! routes = ["log"]
+//!
+//! [[notify.routes]]
+//! name = "log"
+//! kind = "log"
+//! ```
+//!
+//! Fired alerts go down their rule's routes (see `crate::notify` for quiet
+//! hours, dedup and webhooks); `IngestReport::deliveries` says how it went,
+//! webhooks aside - they're sent off the ingest path, and show up in
+//! `SensorHub::deliveries` once they have been.
+//!
+//! Stored readings are JSON `SensorData` with `{"namespace": "sensor", ...}`
+//! metadata (alerts use `"sensor_alert"`). There is no MQTT ingress yet -
+//! bridge MQTT topics to `POST /sensors` in the meantime.
+//!
+//! Hue's house, one wave at a time! 🏠📡
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use anyhow::{Result, anyhow};
+
+use crate::mcp_server::Mem8McpServer;
+use crate::notify::{AlertDelivery, NotificationRouter, NotifyConfig};
+use crate::retention::{RetentionAction, RetentionEngine, RetentionRule, RuleMatch};
+use crate::sensor_ingress::{
+    FusionRule, FusionType, SensorConfig, SensorData, SensorFusion, SensorPattern, SensorStates, SensorType, Transform,
+    DEFAULT_STALENESS_SECS,
+};
+
+/// Metadata namespace of stored readings
+pub const SENSOR_NAMESPACE: &str = "sensor";
+
+/// Metadata namespace of stored alerts
+pub const ALERT_NAMESPACE: &str = "sensor_alert";
+
+/// Largest request body the HTTP ingress accepts
+pub const MAX_BODY_BYTES: usize = 1 << 20;
+
+/// Idempotency keys a hub remembers unless `idempotency_keys` says otherwise
+pub const DEFAULT_IDEMPOTENCY_KEYS: usize = 4096;
+
+/// Everything a hub needs to know, usually loaded from TOML
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HubConfig {
+    /// Mem8Lite store the MCP server (and so every reading) lives in
+    pub store: PathBuf,
+    
+    /// Readings older than this many seconds are pruned (0 keeps everything)
+    #[serde(default)]
+    pub retention_secs: u64,
+    
+    /// Readings older than this many seconds count as stale in `state_at`
+    #[serde(default = "default_staleness_secs")]
+    pub staleness_secs: u64,
+    
+    /// Where the HTTP ingress listens (no HTTP ingress if left out)
+    #[serde(default)]
+    pub http_bind: Option<String>,
+    
+    #[serde(default)]
+    pub sensors: Vec<SensorEntry>,
+    
+    #[serde(default)]
+    pub derived: Vec<DerivedEntry>,
+    
+    #[serde(default)]
+    pub fusion: Vec<FusionEntry>,
+    
+    #[serde(default)]
+    pub alerts: Vec<AlertRule>,
+    
+    /// Where alerts are sent (see `crate::notify`)
+    #[serde(default)]
+    pub notify: NotifyConfig,
+    
+    /// Recent idempotency keys remembered for dropping retried posts (oldest forgotten first)
+    #[serde(default = "default_idempotency_keys")]
+    pub idempotency_keys: usize,
+}
+
+/// A physical sensor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SensorEntry {
+    pub id: String,
+    pub kind: SensorType,
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: f64,
+    #[serde(default = "default_priority")]
+    pub priority: f64,
+    #[serde(default)]
+    pub location: Option<String>,
+}
+
+/// A virtual sensor computed from one input (see `SensorFusion::add_derived_sensor`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DerivedEntry {
+    pub input: String,
+    pub output: String,
+    pub transform: Transform,
+}
+
+/// A rule fusing several sensors into one (`kind` is e.g. `"average"`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FusionEntry {
+    pub name: String,
+    pub inputs: Vec<String>,
+    pub output: String,
+    pub kind: FusionType,
+}
+
+/// Raise an alert when a sensor's value crosses a threshold
+///
+/// Alerts fire once on the way in; the sensor has to come back inside the
+/// thresholds before the same rule fires again.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AlertRule {
+    pub sensor: String,
+    #[serde(default)]
+    pub above: Option<f64>,
+    #[serde(default)]
+    pub below: Option<f64>,
+    pub message: String,
+    
+    /// Names of the `[notify]` routes it goes to (all of them if empty)
+    #[serde(default)]
+    pub routes: Vec<String>,
+}
+
+impl AlertRule {
+    fn violated_by(&self, value: f64) -> bool {
+        self.above.is_some_and(|limit| value > limit) || self.below.is_some_and(|limit| value < limit)
+    }
+}
+
+fn default_sample_rate() -> f64 {
+    1.0
+}
+
+fn default_priority() -> f64 {
+    0.5
+}
+
+fn default_staleness_secs() -> u64 {
+    DEFAULT_STALENESS_SECS
+}
+
+fn default_idempotency_keys() -> usize {
+    DEFAULT_IDEMPOTENCY_KEYS
+}
+
+impl HubConfig {
+    /// Parse and check a TOML hub config
+    pub fn from_toml(text: &str) -> Result<Self> {
+        let config: HubConfig = toml::from_str(text)?;
+        config.validate()?;
+        Ok(config)
+    }
+    
+    /// Load a TOML hub config from a file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| anyhow!("Can't read hub config {}: {}", path.display(), e))?;
+        Self::from_toml(&text).map_err(|e| anyhow!("Bad hub config {}: {}", path.display(), e))
+    }
+    
+    /// Every rule has to refer to sensors that exist, and IDs must be unique
+    pub fn validate(&self) -> Result<()> {
+        let mut known = HashSet::new();
+        for id in self.sensors.iter().map(|s| &s.id)
+            .chain(self.derived.iter().map(|d| &d.output))
+            .chain(self.fusion.iter().map(|f| &f.output))
+        {
+            if !known.insert(id.as_str()) {
+                return Err(anyhow!("Sensor {} is defined twice", id));
+            }
+        }
+        let check = |id: &String, what: &str| match known.contains(id.as_str()) {
+            true => Ok(()),
+            false => Err(anyhow!("{} refers to unknown sensor {}", what, id)),
+        };
+        for derived in &self.derived {
+            check(&derived.input, &format!("Derived sensor {}", derived.output))?;
+        }
+        for rule in &self.fusion {
+            if rule.inputs.is_empty() {
+                return Err(anyhow!("Fusion rule {} has no inputs", rule.name));
+            }
+            for input in &rule.inputs {
+                check(input, &format!("Fusion rule {}", rule.name))?;
+            }
+        }
+        for alert in &self.alerts {
+            check(&alert.sensor, "An alert")?;
+            if alert.above.is_none() && alert.below.is_none() {
+                return Err(anyhow!("Alert on {} needs `above` or `below`", alert.sensor));
+            }
+            if let Some(route) = alert.routes.iter().find(|route| !self.notify.has_route(route)) {
+                return Err(anyhow!("Alert on {} refers to unknown route {}", alert.sensor, route));
+            }
+        }
+        self.notify.validate()
+    }
+}
+
+/// An alert that fired
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Alert {
+    /// Timestamp of the reading that tripped it
+    pub timestamp: u64,
+    pub sensor: String,
+    pub value: f64,
+    pub message: String,
+}
+
+/// Proof a reading was stored: the packet holding it and when the hub took it in
+/// 
+/// A retried post with the same idempotency key gets the original receipt back.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct IngestReceipt {
+    #[serde(serialize_with = "hex_signature")]
+    pub signature: [u8; 32],
+    
+    /// Unix seconds
+    pub ingested_at: u64,
+}
+
+/// Per-sensor ingest counts (a device posting bundles counts under its device id)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct DeviceStats {
+    pub ingested: u64,
+    
+    /// Retries dropped because their idempotency key had been seen
+    pub deduplicated: u64,
+}
+
+/// What one ingested reading led to
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct IngestReport {
+    pub receipt: IngestReceipt,
+    
+    /// The reading was a retry of one already ingested; nothing else happened
+    pub duplicate: bool,
+    
+    /// The reading plus any derived and fused readings it produced
+    pub readings: Vec<SensorData>,
+    
+    /// Signatures of the packets stored for them (readings, then alerts)
+    #[serde(serialize_with = "hex_signatures")]
+    pub stored: Vec<[u8; 32]>,
+    
+    pub alerts: Vec<Alert>,
+    
+    /// Where each alert was sent, in `alerts` order (webhooks still `Queued`)
+    pub deliveries: Vec<AlertDelivery>,
+    
+    pub patterns: Vec<SensorPattern>,
+}
+
+fn hex_signatures<S: serde::Serializer>(signatures: &[[u8; 32]], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_seq(signatures.iter().map(hex::encode))
+}
+
+fn hex_signature<S: serde::Serializer>(signature: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&hex::encode(signature))
+}
+
+/// The last few idempotency keys seen, per sensor, with the receipts they got
+struct RecentKeys {
+    capacity: usize,
+    receipts: HashMap<(String, String), IngestReceipt>,
+    order: VecDeque<(String, String)>,
+}
+
+impl RecentKeys {
+    fn new(capacity: usize) -> Self {
+        Self { capacity, receipts: HashMap::new(), order: VecDeque::new() }
+    }
+    
+    fn get(&self, sensor: &str, key: &str) -> Option<IngestReceipt> {
+        self.receipts.get(&(sensor.to_string(), key.to_string())).copied()
+    }
+    
+    fn insert(&mut self, sensor: &str, key: &str, receipt: IngestReceipt) {
+        let entry = (sensor.to_string(), key.to_string());
+        if self.receipts.insert(entry.clone(), receipt).is_none() {
+            self.order.push_back(entry);
+        }
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.receipts.remove(&oldest);
+            }
+        }
+    }
+}
+
+/// Sensors, fusion, storage and the MCP server, running as one
+pub struct SensorHub {
+    config: HubConfig,
+    fusion: SensorFusion,
+    server: Arc<Mem8McpServer>,
+    
+    /// Alert rules (by index) whose sensor is currently past a threshold
+    firing: HashSet<usize>,
+    
+    /// Sends fired alerts down their routes
+    router: NotificationRouter,
+    
+    /// Idempotency keys already ingested (rebuilt from the store at startup)
+    recent_keys: RecentKeys,
+    
+    stats: HashMap<String, DeviceStats>,
+}
+
+impl SensorHub {
+    /// Open the store and register every sensor and rule in `config`
+    pub fn new(config: HubConfig) -> Result<Self> {
+        config.validate()?;
+        let store = config.store.to_str()
+            .ok_or_else(|| anyhow!("Store path isn't UTF-8: {}", config.store.display()))?;
+        let server = Arc::new(Mem8McpServer::new(store)?);
+        
+        let mut fusion = SensorFusion::new();
+        fusion.set_history(server.storage());
+        fusion.set_staleness(config.staleness_secs);
+        for sensor in &config.sensors {
+            fusion.register_sensor(SensorConfig {
+                id: sensor.id.clone(),
+                sensor_type: sensor.kind.clone(),
+                sample_rate: sensor.sample_rate,
+                priority: sensor.priority,
+                location: sensor.location.clone(),
+                calibration: None,
+            });
+        }
+        for derived in &config.derived {
+            fusion.add_derived_sensor(&derived.input, &derived.output, derived.transform.clone());
+        }
+        for rule in &config.fusion {
+            fusion.add_fusion_rule(FusionRule {
+                name: rule.name.clone(),
+                inputs: rule.inputs.clone(),
+                output: rule.output.clone(),
+                fusion_type: rule.kind.clone(),
+            });
+        }
+        
+        let router = NotificationRouter::new(config.notify.clone()).with_store(server.storage());
+        let recent_keys = Self::load_recent_keys(&server, config.idempotency_keys);
+        Ok(Self { config, fusion, server, firing: HashSet::new(), router, recent_keys, stats: HashMap::new() })
+    }
+    
+    /// Keyed readings still in the store, newest `capacity` of them
+    fn load_recent_keys(server: &Mem8McpServer, capacity: usize) -> RecentKeys {
+        let storage = server.storage();
+        let storage = storage.read();
+        let mut keyed: Vec<(u64, String, String, [u8; 32])> = storage
+            .find_by_metadata(|meta| meta.windows(17).any(|w| w == b"\"idempotency_key\""))
+            .into_iter()
+            .filter_map(|signature| {
+                let meta: Value = serde_json::from_slice(&storage.get_metadata(&signature)?).ok()?;
+                Some((
+                    meta["ingested_at"].as_u64()?,
+                    meta["sensor"].as_str()?.to_string(),
+                    meta["idempotency_key"].as_str()?.to_string(),
+                    signature,
+                ))
+            })
+            .collect();
+        keyed.sort();
+        let mut recent = RecentKeys::new(capacity);
+        for (ingested_at, sensor, key, signature) in keyed {
+            recent.insert(&sensor, &key, IngestReceipt { signature, ingested_at });
+        }
+        recent
+    }
+    
+    pub fn config(&self) -> &HubConfig {
+        &self.config
+    }
+    
+    /// The alert router, e.g. to attach a chime player or a clock
+    pub fn router_mut(&mut self) -> &mut NotificationRouter {
+        &mut self.router
+    }
+    
+    /// Recent alert deliveries, oldest first
+    pub fn deliveries(&self) -> Vec<AlertDelivery> {
+        self.router.deliveries()
+    }
+    
+    /// The MCP server the hub feeds (serve it with `Mem8McpServer::serve_stdio`)
+    pub fn server(&self) -> Arc<Mem8McpServer> {
+        Arc::clone(&self.server)
+    }
+    
+    /// Readings ingested and retries deduplicated, by sensor, since the hub started
+    pub fn device_stats(&self) -> &HashMap<String, DeviceStats> {
+        &self.stats
+    }
+    
+    /// What every sensor read at `timestamp`, from the stored readings
+    /// 
+    /// Derived and fused sensors included. See `SensorFusion::state_at`.
+    pub fn state_at(&self, timestamp: u64) -> Result<SensorStates> {
+        self.fusion.state_at(timestamp)
+    }
+    
+    /// `state_at` every `step` seconds across `range`
+    pub fn states_over(&self, range: std::ops::Range<u64>, step: u64) -> Result<Vec<SensorStates>> {
+        self.fusion.states_over(range, step)
+    }
+    
+    /// Take in one reading from a registered sensor
+    ///
+    /// The reading and what it derives are stored in one batch, handed to the
+    /// MCP server, and checked against the alert rules.
+    pub fn ingest(&mut self, data: SensorData) -> Result<IngestReport> {
+        self.ingest_keyed(data, None)
+    }
+    
+    /// `ingest`, dropping the reading if its sensor already sent `key`
+    /// 
+    /// A retry comes back as a `duplicate` report carrying the original
+    /// receipt, and stores, fuses and alerts nothing. The key is kept in the
+    /// reading's metadata, so retries after a restart are caught too (for as
+    /// long as the reading is within retention and among the last
+    /// `idempotency_keys`).
+    pub fn ingest_keyed(&mut self, data: SensorData, key: Option<&str>) -> Result<IngestReport> {
+        let id = data.id().to_string();
+        if !self.config.sensors.iter().any(|s| s.id == id) {
+            return Err(anyhow!("Unknown sensor: {}", id));
+        }
+        if let Some(receipt) = key.and_then(|key| self.recent_keys.get(&id, key)) {
+            self.stats.entry(id).or_default().deduplicated += 1;
+            return Ok(IngestReport { receipt, duplicate: true, ..IngestReport::default() });
+        }
+        let ingested_at = now();
+        self.fusion.ingest(data.clone())?;
+        let mut readings = vec![data];
+        
+        // Derived sensors were updated by the ingest itself
+        for derived in self.config.derived.iter().filter(|d| d.input == id) {
+            if let Some(reading) = self.fusion.sensor_state(&derived.output) {
+                readings.push(reading);
+            }
+        }
+        
+        // Fusion rules that take this sensor produce a fresh reading
+        let outputs: HashSet<&str> = self.config.fusion.iter()
+            .filter(|rule| rule.inputs.contains(&id))
+            .map(|rule| rule.output.as_str())
+            .collect();
+        for fused in self.fusion.apply_fusion()? {
+            if outputs.contains(fused.id()) {
+                self.fusion.ingest(fused.clone())?;
+                readings.push(fused);
+            }
+        }
+        
+        let fired = self.check_alerts(&readings);
+        let mut alerts = Vec::with_capacity(fired.len());
+        let mut deliveries = Vec::with_capacity(fired.len());
+        for (rule, alert) in fired {
+            let routes = self.config.alerts[rule].routes.clone();
+            deliveries.push(self.router.deliver(&alert, &routes));
+            alerts.push(alert);
+        }
+        let patterns = self.fusion.detect_patterns();
+        let timestamp = readings[0].timestamp();
+        for pattern in &patterns {
+            self.server.record_pattern(pattern, timestamp);
+        }
+        for reading in &readings {
+            self.server.ingest_sensor(reading);
+        }
+        
+        // One batch for everything this reading produced
+        let mut packets = Vec::with_capacity(readings.len() + alerts.len());
+        for (i, reading) in readings.iter().enumerate() {
+            let mut meta = json!({"namespace": SENSOR_NAMESPACE, "sensor": reading.id(), "timestamp": reading.timestamp()});
+            if let (0, Some(key)) = (i, key) {
+                meta["idempotency_key"] = json!(key);
+                meta["ingested_at"] = json!(ingested_at);
+            }
+            packets.push((serde_json::to_vec(reading)?, serde_json::to_vec(&meta)?));
+        }
+        for alert in &alerts {
+            let meta = json!({"namespace": ALERT_NAMESPACE, "sensor": alert.sensor, "timestamp": alert.timestamp});
+            packets.push((serde_json::to_vec(alert)?, serde_json::to_vec(&meta)?));
+        }
+        let items: Vec<(&[u8], Option<Vec<u8>>)> = packets.iter()
+            .map(|(data, meta)| (data.as_slice(), Some(meta.clone())))
+            .collect();
+        let stored = self.server.storage().store_batch(&items)?;
+        
+        let receipt = IngestReceipt { signature: stored[0], ingested_at };
+        if let Some(key) = key {
+            self.recent_keys.insert(&id, key, receipt);
+        }
+        self.stats.entry(id).or_default().ingested += 1;
+        Ok(IngestReport { receipt, duplicate: false, readings, stored, alerts, deliveries, patterns })
+    }
+    
+    /// Alerts newly tripped by these readings, with the index of the rule that fired
+    fn check_alerts(&mut self, readings: &[SensorData]) -> Vec<(usize, Alert)> {
+        let mut alerts = Vec::new();
+        for reading in readings {
+            let value = match reading.scalar() {
+                Some((value, _, _)) => value,
+                None => continue,
+            };
+            for (index, rule) in self.config.alerts.iter().enumerate() {
+                if rule.sensor != reading.id() {
+                    continue;
+                }
+                if !rule.violated_by(value) {
+                    self.firing.remove(&index);
+                } else if self.firing.insert(index) {
+                    alerts.push((index, Alert {
+                        timestamp: reading.timestamp(),
+                        sensor: rule.sensor.clone(),
+                        value,
+                        message: rule.message.clone(),
+                    }));
+                }
+            }
+        }
+        alerts
+    }
+    
+    /// The hub's retention window as rules: readings and alerts past `retention_secs` go
+    ///
+    /// No rules at all when `retention_secs` is 0. Add your own ahead of
+    /// these to keep some readings longer.
+    pub fn retention(&self) -> RetentionEngine {
+        if self.config.retention_secs == 0 {
+            return RetentionEngine::new();
+        }
+        // Strictly older than the window goes
+        let ttl = RetentionAction::Ttl(Duration::from_secs(self.config.retention_secs + 1));
+        RetentionEngine::new()
+            .with_rule(RetentionRule::new("sensor", RuleMatch::namespace(SENSOR_NAMESPACE), ttl.clone()))
+            .with_rule(RetentionRule::new("sensor_alert", RuleMatch::namespace(ALERT_NAMESPACE), ttl))
+    }
+    
+    /// Delete stored readings and alerts older than the retention window
+    ///
+    /// Ages are measured from the readings' own timestamps. Returns how many
+    /// packets were deleted (`compact` the store to get the space back).
+    pub fn prune(&self, now: u64) -> Result<usize> {
+        let storage = self.server.storage();
+        let mut storage = storage.write();
+        Ok(self.retention().run(&mut storage, now)?.removed())
+    }
+    
+    /// Flush the store and the MCP server's state
+    pub fn shutdown(&self) -> Result<()> {
+        self.server.shutdown()
+    }
+}
+
+/// Unix seconds right now
+pub fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_secs())
+}
+
+/// Minimal HTTP/1.1 ingress for sensor posts
+///
+/// `POST /sensors` takes one `SensorData` as JSON, or an array of them, and
+/// answers with what was stored, a receipt per accepted reading and which
+/// alerts fired. `GET /health` says whether the hub is up. Connections are
+/// handled one at a time, which is plenty for a house full of ESP32s.
+///
+/// Devices that retry can send an `Idempotency-Key` header (in an array,
+/// reading `i` gets key `<key>/<i>`), or wrap a reading as
+/// `{"idempotency_key": "...", "reading": {...}}`; a repeat gets the
+/// original receipt back and counts under `deduplicated`.
+pub struct HttpIngress {
+    addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HttpIngress {
+    /// Start listening on `bind` (port 0 picks a free one)
+    pub fn start(bind: &str, hub: Arc<Mutex<SensorHub>>) -> Result<Self> {
+        let listener = TcpListener::bind(bind)?;
+        listener.set_nonblocking(true)?;
+        let addr = listener.local_addr()?;
+        let stop = Arc::new(AtomicBool::new(false));
+        
+        let stopping = Arc::clone(&stop);
+        let thread = std::thread::spawn(move || {
+            while !stopping.load(Ordering::SeqCst) {
+                match listener.accept() {
+                    Ok((stream, _)) => {
+                        if let Err(e) = handle_connection(stream, &hub) {
+                            eprintln!("⚠️ mem8: sensor post failed: {}", e);
+                        }
+                    }
+                    Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => {
+                        std::thread::sleep(Duration::from_millis(20));
+                    }
+                    Err(e) => eprintln!("⚠️ mem8: sensor ingress accept failed: {}", e),
+                }
+            }
+        });
+        Ok(Self { addr, stop, thread: Some(thread) })
+    }
+    
+    /// Where the ingress is listening
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+    
+    /// Stop accepting posts and wait for the one in flight to finish
+    pub fn stop(mut self) {
+        self.shut_down();
+    }
+    
+    fn shut_down(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for HttpIngress {
+    fn drop(&mut self) {
+        self.shut_down();
+    }
+}
+
+/// Read one request, answer it, close the connection
+fn handle_connection(stream: TcpStream, hub: &Mutex<SensorHub>) -> Result<()> {
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    
+    let mut content_length = 0;
+    let mut idempotency_key = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.trim().eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().map_err(|_| anyhow!("Bad Content-Length"))?;
+            } else if name.trim().eq_ignore_ascii_case("idempotency-key") {
+                idempotency_key = Some(value.trim().to_string());
+            }
+        }
+    }
+    
+    let (status, body) = match (method, path) {
+        ("GET", "/health") => (200, json!({"ok": true})),
+        ("POST", "/sensors") if content_length > MAX_BODY_BYTES => {
+            (413, json!({"error": format!("Body over {} bytes", MAX_BODY_BYTES)}))
+        }
+        ("POST", "/sensors") => {
+            let mut body = vec![0u8; content_length];
+            reader.read_exact(&mut body)?;
+            match post_sensors(&body, idempotency_key.as_deref(), hub) {
+                Ok(response) => (200, response),
+                Err(e) => (400, json!({"error": e.to_string()})),
+            }
+        }
+        _ => (404, json!({"error": format!("No route for {} {}", method, path)})),
+    };
+    
+    let body = body.to_string();
+    let reason = match status {
+        200 => "OK",
+        400 => "Bad Request",
+        413 => "Payload Too Large",
+        _ => "Not Found",
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status, reason, body.len(), body
+    )?;
+    stream.flush()?;
+    Ok(())
+}
+
+/// A posted reading, bare or wrapped with its idempotency key
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PostedReading {
+    Keyed { idempotency_key: String, reading: SensorData },
+    Bare(SensorData),
+}
+
+/// Ingest a posted reading (or array of readings)
+///
+/// The whole body is parsed before anything is ingested, so a malformed
+/// array stores nothing.
+fn post_sensors(body: &[u8], idempotency_key: Option<&str>, hub: &Mutex<SensorHub>) -> Result<Value> {
+    let value: Value = serde_json::from_slice(body)?;
+    let (posted, batch): (Vec<PostedReading>, bool) = match value {
+        Value::Array(_) => (serde_json::from_value(value)?, true),
+        single => (vec![serde_json::from_value(single)?], false),
+    };
+    let readings: Vec<(SensorData, Option<String>)> = posted.into_iter().enumerate()
+        .map(|(i, posted)| match posted {
+            PostedReading::Keyed { idempotency_key, reading } => (reading, Some(idempotency_key)),
+            PostedReading::Bare(reading) => (reading, idempotency_key.map(|key| match batch {
+                true => format!("{}/{}", key, i),
+                false => key.to_string(),
+            })),
+        })
+        .collect();
+    
+    let mut hub = hub.lock().unwrap();
+    let mut stored = 0;
+    let mut deduplicated = 0;
+    let mut receipts = Vec::new();
+    let mut alerts = Vec::new();
+    let mut errors = HashMap::new();
+    for (reading, key) in readings.iter().cloned() {
+        let id = reading.id().to_string();
+        match hub.ingest_keyed(reading, key.as_deref()) {
+            Ok(report) => {
+                stored += report.stored.len();
+                deduplicated += report.duplicate as usize;
+                receipts.push(report.receipt);
+                alerts.extend(report.alerts);
+            }
+            Err(e) => {
+                errors.insert(id, e.to_string());
+            }
+        }
+    }
+    if errors.len() == readings.len() && !readings.is_empty() {
+        return Err(anyhow!("No reading accepted: {:?}", errors));
+    }
+    Ok(json!({
+        "accepted": readings.len() - errors.len(),
+        "stored": stored,
+        "deduplicated": deduplicated,
+        "receipts": receipts,
+        "alerts": alerts,
+        "errors": errors,
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    
+    fn post(addr: SocketAddr, body: &str) -> (u16, Value) {
+        post_with_headers(addr, body, "")
+    }
+    
+    fn post_with_headers(addr: SocketAddr, body: &str, headers: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write!(stream, "POST /sensors HTTP/1.1\r\nHost: hub\r\n{}Content-Length: {}\r\n\r\n{}", headers, body.len(), body).unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let status = response[9..12].parse().unwrap();
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+    
+    fn lux(id: &str, value: f64, timestamp: u64) -> String {
+        json!({"Analog": {"id": id, "value": value, "range": [0.0, 1000.0], "unit": "lux", "timestamp": timestamp}}).to_string()
+    }
+    
+    #[test]
+    fn test_scripted_sensor_posts_reach_store_and_mcp() {
+        let dir = tempdir().unwrap();
+        let config = HubConfig::from_toml(&format!(r#"
+            store = "{}"
+            retention_secs = 3600
+            
+            [[sensors]]
+            id = "desk_lux"
+            kind = "photoresistor"
+            
+            [[sensors]]
+            id = "window_lux"
+            kind = "photoresistor"
+            
+            [[derived]]
+            input = "desk_lux"
+            output = "desk_lux_smooth"
+            transform = {{ kind = "smoothed", alpha = 0.5 }}
+            
+            [[fusion]]
+            name = "room"
+            inputs = ["desk_lux", "window_lux"]
+            output = "room_lux"
+            kind = "average"
+            
+            [[alerts]]
+            sensor = "desk_lux"
+            below = 50.0
+            message = "Desk is getting dark"
+        "#, dir.path().join("hub.m8").display())).unwrap();
+        
+        let hub = Arc::new(Mutex::new(SensorHub::new(config).unwrap()));
+        let ingress = HttpIngress::start("127.0.0.1:0", Arc::clone(&hub)).unwrap();
+        let addr = ingress.local_addr();
+        let start = now() - 100;
+        
+        // Desk alone: the reading and its smoothed twin
+        let (status, body) = post(addr, &lux("desk_lux", 400.0, start));
+        assert_eq!((status, body["stored"].as_u64()), (200, Some(2)));
+        
+        // Window too: the room average joins in; then the desk goes dark twice (one alert)
+        let batch = format!("[{}, {}, {}]", lux("window_lux", 600.0, start + 1),
+            lux("desk_lux", 20.0, start + 2), lux("desk_lux", 10.0, start + 3));
+        let (status, body) = post(addr, &batch);
+        assert_eq!(status, 200);
+        assert_eq!(body["accepted"], 3);
+        assert_eq!(body["alerts"].as_array().unwrap().len(), 1);
+        assert_eq!(body["alerts"][0]["message"], "Desk is getting dark");
+        
+        let (status, body) = post(addr, &lux("hallway", 1.0, start));
+        assert_eq!(status, 400, "{}", body);
+        ingress.stop();
+        
+        // Stored: 2 + (window, room) + 2 × (desk, smooth, room) + the alert
+        let hub = hub.lock().unwrap();
+        let storage = hub.server().storage();
+        let stored: Vec<Value> = {
+            let storage = storage.read();
+            storage.iter()
+                .map(|s| serde_json::from_slice(&storage.get_metadata(&s.signature).unwrap()).unwrap())
+                .collect()
+        };
+        assert_eq!(stored.iter().filter(|m| m["namespace"] == SENSOR_NAMESPACE).count(), 10);
+        assert_eq!(stored.iter().filter(|m| m["namespace"] == ALERT_NAMESPACE).count(), 1);
+        
+        // The MCP side sees the latest values over the stdio transport
+        let request = r#"{"jsonrpc":"2.0","id":7,"method":"tools/call","params":{"name":"mem8.get_sensor_data"}}"#;
+        let mut output = Vec::new();
+        hub.server().serve_stdio(request.as_bytes(), &mut output).unwrap();
+        let response: Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!(response["id"], 7);
+        let sensors = &response["result"]["structuredContent"]["sensors"];
+        assert_eq!(sensors["desk_lux"]["Analog"]["value"], 10.0);
+        assert_eq!(sensors["room_lux"]["Analog"]["value"], 305.0);
+        
+        // Everything is inside the hour; an hour later it's all past retention
+        assert_eq!(hub.prune(now()).unwrap(), 0);
+        assert_eq!(hub.prune(now() + 3601).unwrap(), 11);
+        hub.shutdown().unwrap();
+    }
+    
+    #[test]
+    fn test_state_at_reconstructs_a_scripted_day() {
+        let dir = tempdir().unwrap();
+        let config = HubConfig::from_toml(&format!(r#"
+            store = "{}"
+            
+            [[sensors]]
+            id = "desk_lux"
+            kind = "photoresistor"
+            
+            [[sensors]]
+            id = "door"
+            kind = "switch"
+        "#, dir.path().join("hub.m8").display())).unwrap();
+        let mut hub = SensorHub::new(config).unwrap();
+        let clock = Arc::new(crate::ManualClock::new(0));
+        hub.server().storage().write().set_clock(clock.clone());
+        
+        // Lux every 10 minutes (the value is the minute of the day), except
+        // for a 12:00-14:00 outage; the door opens and closes twice
+        let day = 19_675 * 86_400;
+        let mut readings: Vec<SensorData> = (0..144u64)
+            .filter(|slot| !(72..84).contains(slot))
+            .map(|slot| SensorData::Analog {
+                id: "desk_lux".into(),
+                value: (slot * 10) as f64,
+                range: (0.0, 2000.0),
+                unit: "lux".into(),
+                timestamp: day + slot * 600,
+            })
+            .collect();
+        for (state, minute) in [(true, 8 * 60 + 1), (false, 8 * 60 + 5), (true, 18 * 60), (false, 18 * 60 + 10)] {
+            readings.push(SensorData::Binary { id: "door".into(), state, timestamp: day + minute * 60 });
+        }
+        readings.sort_by_key(SensorData::timestamp);
+        for reading in readings {
+            clock.set(reading.timestamp());
+            hub.ingest(reading).unwrap();
+        }
+        let at = |hour: u64, minute: u64| hub.state_at(day + hour * 3600 + minute * 60).unwrap();
+        let lux = |states: &SensorStates| match states.states.get("desk_lux") {
+            Some(SensorData::Analog { value, .. }) => *value,
+            other => panic!("no lux reading: {:?}", other),
+        };
+        let door = |states: &SensorStates| match states.states.get("door") {
+            Some(SensorData::Binary { state, .. }) => *state,
+            other => panic!("no door reading: {:?}", other),
+        };
+        
+        // 08:03 - door open two minutes ago, lux from 08:00
+        let morning = at(8, 3);
+        assert_eq!((lux(&morning), door(&morning)), (480.0, true));
+        assert!(morning.stale.is_empty(), "{:?}", morning.stale);
+        
+        // 09:04 - the door has been shut for an hour, which is past the bound
+        let later = at(9, 4);
+        assert_eq!((lux(&later), door(&later)), (540.0, false));
+        assert_eq!(later.stale.iter().collect::<Vec<_>>(), ["door"]);
+        
+        // 13:00 - in the outage the last lux reading is from 11:50, and flagged
+        let outage = at(13, 0);
+        assert_eq!(lux(&outage), 710.0);
+        assert!(outage.stale.contains("desk_lux"));
+        
+        // Before the day began there's nothing at all
+        let before = hub.state_at(day - 60).unwrap();
+        assert!(before.states.is_empty());
+        assert_eq!(before.stale.len(), 2);
+        
+        // A timeline across the outage
+        let timeline = hub.states_over(day + 11 * 3600..day + 15 * 3600, 3600).unwrap();
+        let lux_stale: Vec<bool> = timeline.iter().map(|s| s.stale.contains("desk_lux")).collect();
+        assert_eq!(lux_stale, [false, true, true, false]);
+        
+        // Over MCP
+        let result = hub.server().call_tool("mem8.state_at", json!({"timestamp": day + 13 * 3600, "sensors": ["desk_lux"]})).unwrap();
+        assert_eq!(result["states"]["desk_lux"]["Analog"]["value"], 710.0);
+        assert_eq!(result["stale"], json!(["desk_lux"]));
+    }
+    
+    #[test]
+    fn test_retried_posts_with_an_idempotency_key_are_stored_once() {
+        let dir = tempdir().unwrap();
+        let config = HubConfig::from_toml(&format!(r#"
+            store = "{}"
+            
+            [[sensors]]
+            id = "desk_lux"
+            kind = "photoresistor"
+            
+            [[alerts]]
+            sensor = "desk_lux"
+            below = 50.0
+            message = "Desk is getting dark"
+        "#, dir.path().join("hub.m8").display())).unwrap();
+        let sensor_packets = |hub: &SensorHub| {
+            let storage = hub.server().storage();
+            let storage = storage.read();
+            storage.find_by_metadata_json("/namespace", &json!(SENSOR_NAMESPACE)).len()
+        };
+        
+        let hub = Arc::new(Mutex::new(SensorHub::new(config.clone()).unwrap()));
+        let ingress = HttpIngress::start("127.0.0.1:0", Arc::clone(&hub)).unwrap();
+        let frame = lux("desk_lux", 20.0, now() - 10);
+        let responses: Vec<Value> = (0..3)
+            .map(|_| post_with_headers(ingress.local_addr(), &frame, "Idempotency-Key: esp32-7-0001\r\n"))
+            .map(|(status, body)| {
+                assert_eq!(status, 200, "{}", body);
+                body
+            })
+            .collect();
+        assert_eq!(responses[0]["stored"], 2);
+        assert_eq!(responses[0]["alerts"].as_array().unwrap().len(), 1);
+        for retry in &responses[1..] {
+            assert_eq!((retry["stored"].as_u64(), retry["deduplicated"].as_u64()), (Some(0), Some(1)));
+            assert_eq!(retry["receipts"], responses[0]["receipts"]);
+            assert!(retry["alerts"].as_array().unwrap().is_empty());
+        }
+        
+        // A wrapped reading carries its own key; a plain one still gets a receipt
+        let wrapped = format!(r#"{{"idempotency_key": "esp32-7-0002", "reading": {}}}"#, lux("desk_lux", 30.0, now()));
+        assert_eq!(post(ingress.local_addr(), &wrapped).1["stored"], 1);
+        assert_eq!(post(ingress.local_addr(), &wrapped).1["deduplicated"], 1);
+        let (_, plain) = post(ingress.local_addr(), &lux("desk_lux", 40.0, now()));
+        assert_eq!(plain["receipts"][0]["signature"].as_str().unwrap().len(), 64);
+        ingress.stop();
+        
+        {
+            let hub = hub.lock().unwrap();
+            // The retried reading once, then the wrapped and plain ones
+            assert_eq!(sensor_packets(&hub), 3);
+            assert_eq!(hub.device_stats()["desk_lux"], DeviceStats { ingested: 3, deduplicated: 3 });
+            hub.shutdown().unwrap();
+        }
+        drop(hub);
+        
+        // The keys are in the store, so a retry after a restart is caught too
+        let mut hub = SensorHub::new(config).unwrap();
+        let reading: SensorData = serde_json::from_str(&frame).unwrap();
+        let report = hub.ingest_keyed(reading, Some("esp32-7-0001")).unwrap();
+        assert!(report.duplicate);
+        assert_eq!(json!([report.receipt]), responses[0]["receipts"]);
+        assert_eq!(sensor_packets(&hub), 3);
+    }
+    
+    #[test]
+    fn test_ingest_doesnt_wait_for_a_hanging_webhook() {
+        // Takes connections (the kernel does) but never answers them
+        let silent = TcpListener::bind("127.0.0.1:0").unwrap();
+        let dir = tempdir().unwrap();
+        let config = HubConfig::from_toml(&format!(r#"
+            store = "{}"
+            
+            [[sensors]]
+            id = "desk_lux"
+            kind = "photoresistor"
+            
+            [[alerts]]
+            sensor = "desk_lux"
+            below = 50.0
+            message = "Desk is getting dark"
+            
+            [[notify.routes]]
+            name = "phone"
+            kind = "webhook"
+            url = "http://{}/alerts"
+        "#, dir.path().join("hub.m8").display(), silent.local_addr().unwrap())).unwrap();
+        let hub = Arc::new(Mutex::new(SensorHub::new(config).unwrap()));
+        let ingress = HttpIngress::start("127.0.0.1:0", Arc::clone(&hub)).unwrap();
+        
+        // One attempt alone could take 10s; the posts don't wait for any of it
+        let started = std::time::Instant::now();
+        let (status, body) = post(ingress.local_addr(), &lux("desk_lux", 20.0, now()));
+        assert_eq!((status, body["alerts"].as_array().unwrap().len()), (200, 1));
+        let (status, _) = post(ingress.local_addr(), &lux("desk_lux", 30.0, now()));
+        assert_eq!(status, 200);
+        assert!(started.elapsed() < Duration::from_secs(2), "ingest took {:?}", started.elapsed());
+        ingress.stop();
+        
+        let hub = hub.lock().unwrap();
+        assert_eq!(hub.deliveries()[0].routes[0].status, crate::notify::DeliveryStatus::Queued);
+        hub.shutdown().unwrap();
+    }
+    
+    #[test]
+    fn test_config_rules_must_name_known_sensors() {
+        let config = |extra: &str| HubConfig::from_toml(&format!(
+            "store = \"/tmp/x.m8\"\n[[sensors]]\nid = \"door\"\nkind = \"switch\"\n{}", extra));
+        assert!(config("").is_ok());
+        assert!(config("[[alerts]]\nsensor = \"garage\"\nabove = 1.0\nmessage = \"x\"").is_err());
+        assert!(config("[[alerts]]\nsensor = \"door\"\nmessage = \"no threshold\"").is_err());
+        assert!(config("[[sensors]]\nid = \"door\"\nkind = \"switch\"").is_err());
+        
+        // Alert routes have to exist
+        let routes = "[[notify.routes]]\nname = \"phone\"\nkind = \"webhook\"\nurl = \"http://127.0.0.1:9/alerts\"\n\
+            quiet_hours = { start = \"22:00\", end = \"07:00\" }\n";
+        let parsed = config(&format!("{}[[alerts]]\nsensor = \"door\"\nabove = 0.5\nmessage = \"x\"\nroutes = [\"phone\"]", routes)).unwrap();
+        assert_eq!(parsed.notify.routes[0].target, crate::notify::RouteTarget::Webhook { url: "http://127.0.0.1:9/alerts".into() });
+        assert!(config(&format!("{}[[alerts]]\nsensor = \"door\"\nabove = 0.5\nmessage = \"x\"\nroutes = [\"pager\"]", routes)).is_err());
+        assert!(config("[[notify.routes]]\nname = \"x\"\nkind = \"log\"\nquiet_hours = { start = \"25:00\", end = \"07:00\" }").is_err());
+    }
+}
@@ -0,0 +1,230 @@
+//! Integrity - checking what Mem8Fs reads back against its signature
+//!
+//! A file's signature is the blake3 hash of its content (per chunk for a
+//! chunked file), so the bytes decoded from `data.m8` can be checked against
+//! it. With `FsConfig::verify_on_read` set, `read` does that and fails with
+//! `Mem8Error::Corrupt` instead of handing back whatever a flipped bit left
+//! behind (off by default: it hashes every byte read, and `read_range`
+//! isn't checked). `Mem8Fs::scrub` checks every file straight from disk and
+//! moves the corrupt ones under `/.quarantine/`, where normal paths no longer
+//! find them but they can still be looked at (or deleted).
+//!
+//! Trisha checks the seal on every jar before it goes on the shelf. 🫙
+
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use crate::error::{Mem8Error, Result};
+use crate::{chunk_list_signature, frequency_signature, fs_signature, FileContent, FileEntry, FsEvent, Mem8Fs};
+
+/// Where `scrub` moves corrupt files, each under its old path
+pub const QUARANTINE_DIR: &str = "/.quarantine";
+
+/// A file `scrub` found corrupt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuarantinedFile {
+    /// Where it was
+    pub path: PathBuf,
+    
+    /// Where it is now, below `QUARANTINE_DIR`
+    pub quarantined_as: PathBuf,
+    
+    /// What didn't check out
+    pub reason: String,
+}
+
+/// What `Mem8Fs::scrub` found
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IntegrityReport {
+    /// Files checked (the ones already quarantined aren't)
+    pub files_checked: usize,
+    pub bytes_checked: u64,
+    pub quarantined: Vec<QuarantinedFile>,
+}
+
+impl std::fmt::Display for IntegrityReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "🫙 {} files ({} bytes) checked, {} quarantined",
+            self.files_checked, self.bytes_checked, self.quarantined.len())
+    }
+}
+
+/// `entry`'s content, read with `retrieve`, or `Mem8Error::Corrupt` if it doesn't match its signature
+///
+/// Waves that are missing or cut short count as corrupt too.
+pub(crate) fn checked_content(entry: &FileEntry, mut retrieve: impl FnMut(&[u8; 32]) -> Result<Vec<u8>>) -> Result<Vec<u8>> {
+    if let FileContent::Inline(data) = &entry.content {
+        return match fs_signature(data) == entry.signature && data.len() as u64 == entry.size {
+            true => Ok(data.clone()),
+            false => Err(Mem8Error::corrupt(entry.signature, "Content doesn't match its signature")),
+        };
+    }
+    let mut data = Vec::with_capacity(entry.size as usize);
+    for chunk in entry.chunk_list() {
+        let bytes = match retrieve(&chunk.signature) {
+            Ok(bytes) => bytes,
+            Err(Mem8Error::NotFound(_)) => return Err(Mem8Error::corrupt(chunk.signature, "No wave data")),
+            Err(Mem8Error::Io(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => {
+                return Err(Mem8Error::corrupt(chunk.signature, "Wave record cut short"));
+            }
+            Err(e) => return Err(e),
+        };
+        let signature = match entry.signed_at {
+            Some(hz) => frequency_signature(&bytes, hz),
+            None => fs_signature(&bytes),
+        };
+        if signature != chunk.signature || bytes.len() as u64 != chunk.len {
+            return Err(Mem8Error::corrupt(chunk.signature, "Content doesn't match its signature"));
+        }
+        data.extend_from_slice(&bytes);
+    }
+    
+    // Chunks signed the old way were listed under a signature of their own
+    if !entry.chunks.is_empty() && entry.signed_at.is_none() && chunk_list_signature(&entry.chunks) != entry.signature {
+        return Err(Mem8Error::corrupt(entry.signature, "Chunk list doesn't match its signature"));
+    }
+    Ok(data)
+}
+
+impl Mem8Fs {
+    /// Check every file's content, from disk, and quarantine the corrupt ones
+    ///
+    /// A file that doesn't match its signature moves to the same path below
+    /// `QUARANTINE_DIR` (with a number added if that's taken), as a rename
+    /// would; reading it there fails with `Corrupt` under `verify_on_read`.
+    /// Files rewritten while the scrub runs are left where they are.
+    pub fn scrub(&self) -> Result<IntegrityReport> {
+        self.check_writable()?;
+        let quarantine = Path::new(QUARANTINE_DIR);
+        let files: Vec<(PathBuf, FileEntry)> = {
+            let index = self.index.read().unwrap();
+            index.files.iter()
+                .filter(|(path, _)| !path.starts_with(quarantine))
+                .map(|(path, entry)| (path.clone(), entry.clone()))
+                .collect()
+        };
+        
+        let mut report = IntegrityReport::default();
+        let mut corrupt = Vec::new();
+        for (path, entry) in files {
+            let storage = self.storage.read().unwrap();
+            match checked_content(&entry, |signature| storage.retrieve_uncached(signature)) {
+                Ok(_) => {}
+                Err(Mem8Error::Corrupt { reason, .. }) => corrupt.push((path, entry.signature, reason)),
+                Err(e) => return Err(e),
+            }
+            report.files_checked += 1;
+            report.bytes_checked += entry.size;
+        }
+        if corrupt.is_empty() {
+            return Ok(report);
+        }
+        
+        let mut index = self.index.write().unwrap();
+        let now = chrono::Utc::now().timestamp() as u64;
+        let mut moved = Vec::new();
+        for (path, signature, reason) in corrupt {
+            if index.files.get(&path).map(|entry| entry.signature) != Some(signature) {
+                continue;
+            }
+            let target = quarantine.join(path.strip_prefix("/").unwrap_or(&path));
+            let quarantined_as = (1..)
+                .map(|n| match n {
+                    1 => target.clone(),
+                    n => PathBuf::from(format!("{}.{}", target.display(), n)),
+                })
+                .find(|candidate| !index.files.contains_key(candidate) && !index.directories.contains_key(candidate))
+                .unwrap();
+            
+            let entry = index.files.remove(&path).unwrap();
+            index.unlink(&path, now);
+            index.files.insert(quarantined_as.clone(), entry);
+            index.link(&quarantined_as, now);
+            moved.push(FsEvent::Renamed { from: path.clone(), path: quarantined_as.clone(), signature });
+            report.quarantined.push(QuarantinedFile { path, quarantined_as, reason });
+        }
+        self.save_index(&index)?;
+        drop(index);
+        for event in moved {
+            self.watchers.notify(event);
+        }
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FsConfig;
+    use std::io::{Seek, SeekFrom, Write};
+    use tempfile::tempdir;
+    
+    /// Overwrite the first wave of the record at the start of data.m8
+    fn flip_first_wave(root: &Path) {
+        let mut file = std::fs::OpenOptions::new().write(true).open(root.join(".mem8").join("data.m8")).unwrap();
+        file.seek(SeekFrom::Start(crate::WAVE_RECORD_HEADER_LEN)).unwrap();
+        file.write_all(&0.9f64.to_be_bytes()).unwrap();
+        file.write_all(&0.0f64.to_be_bytes()).unwrap();
+    }
+    
+    #[test]
+    fn test_verify_on_read_and_quarantine() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        let signature = fs.write("/docs/a.txt", b"hello, tamper-proof world").unwrap();
+        fs.write("/log.txt", b"one").unwrap();
+        fs.append("/log.txt", b", two").unwrap();
+        fs.close().unwrap();
+        flip_first_wave(dir.path());
+        
+        // Unchecked, the garbage comes straight back
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        let garbage = fs.read("/docs/a.txt").unwrap();
+        assert_ne!(garbage, b"hello, tamper-proof world");
+        drop(fs);
+        
+        let config = FsConfig::default().with_verify_on_read(true);
+        let fs = Mem8Fs::new_with_config(dir.path(), config).unwrap();
+        match fs.read("/docs/a.txt") {
+            Err(Mem8Error::Corrupt { signature: bad, .. }) => assert_eq!(bad, signature),
+            other => panic!("expected Corrupt, got {:?}", other.map(|data| data.len())),
+        }
+        assert_eq!(fs.read("/log.txt").unwrap(), b"one, two");
+        
+        let events = fs.watch("/").unwrap();
+        let report = fs.scrub().unwrap();
+        assert_eq!((report.files_checked, report.bytes_checked), (2, 25 + 8));
+        assert_eq!(report.quarantined, vec![QuarantinedFile {
+            path: "/docs/a.txt".into(),
+            quarantined_as: "/.quarantine/docs/a.txt".into(),
+            reason: "Content doesn't match its signature".into(),
+        }]);
+        assert!(matches!(events.try_recv().unwrap(), FsEvent::Renamed { ref path, .. } if path == Path::new("/.quarantine/docs/a.txt")));
+        
+        // Out of the way, but still there to look at
+        assert!(matches!(fs.read("/docs/a.txt"), Err(Mem8Error::NotFound(_))));
+        assert_eq!(fs.metadata("/.quarantine/docs/a.txt").unwrap().size, 25);
+        assert!(matches!(fs.read("/.quarantine/docs/a.txt"), Err(Mem8Error::Corrupt { .. })));
+        
+        // A second scrub leaves the quarantine alone, and a clean store has nothing to move
+        fs.write("/docs/a.txt", b"written again").unwrap();
+        let again = fs.scrub().unwrap();
+        assert_eq!((again.files_checked, again.quarantined.len()), (2, 0));
+        drop(fs);
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        assert_eq!(fs.read_dir("/.quarantine/docs").unwrap().count(), 1);
+    }
+    
+    #[test]
+    fn test_missing_and_short_waves_are_corrupt() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        fs.write("/a.txt", b"alpha").unwrap();
+        let entry = fs.index.read().unwrap().files[Path::new("/a.txt")].clone();
+        
+        let missing = checked_content(&entry, |_| Err(Mem8Error::not_found("No wave data")));
+        assert!(matches!(missing, Err(Mem8Error::Corrupt { ref reason, .. }) if reason == "No wave data"));
+        let short = checked_content(&entry, |_| Ok(b"alph".to_vec()));
+        assert!(matches!(short, Err(Mem8Error::Corrupt { .. })));
+        assert_eq!(checked_content(&entry, |_| Ok(b"alpha".to_vec())).unwrap(), b"alpha");
+    }
+}
@@ -28,39 +28,116 @@
 //! - **Optional FUSE mounting** (mount as real filesystem!)
 
 use std::collections::HashMap;
-use std::path::{Path, PathBuf};
+use std::path::{Component, Path, PathBuf};
 use std::fs::{File, OpenOptions, create_dir_all};
-use std::io::Write;
+use std::io::{Write, Read, Seek, SeekFrom};
 use std::sync::RwLock;
+use std::sync::mpsc::Receiver;
 use num_complex::Complex64;
 use blake3::Hasher;
 use serde::{Serialize, Deserialize};
-use anyhow::Result;
-use byteorder::{BigEndian, WriteBytesExt};
+use anyhow::anyhow;
+use crate::error::Result;
+use byteorder::{BigEndian, WriteBytesExt, ReadBytesExt};
 
+pub mod error; // Mem8Error, the error type the store APIs return
 pub mod lite;  // The simple version
+pub mod shared; // Arc-cloneable Mem8Lite handle: concurrent reads, serialized writes
+pub mod txn; // All-or-nothing multi-write transactions with a write-ahead journal
+pub mod metadata_policy; // Size limits, JSON checks and per-kind validators for metadata
+pub mod archive; // Portable archives for moving packets between stores
+pub mod retention; // Ordered keep / TTL / archive / downsample rules over a whole store
 pub mod fs;    // Full filesystem API
 pub mod marine; // Marine algorithm for salience detection!
 pub mod audio;  // Multi-format audio processing with temporal perspectives!
 pub mod audio_loader; // FLAC, WAV, and PCM file loading!
+pub mod ambient; // Breathing-synced ambient pads for meditation sessions
 pub mod mood_engine; // Music-mood correlation engine - how music changes us!
 pub mod mcp_server; // MCP server for LLM integration!
+pub mod live_config; // Hot-reloaded profile, DJ and tool policy configs
+pub mod content_filter; // Banned genres, artists and tracks the DJ must never play
 pub mod tidal_dj; // Tidal streaming integration - AI DJ with real music!
 pub mod sensor_ingress; // Universal sensor fusion - from switches to consciousness!
+pub mod hub; // Sensor hub: HTTP ingress, fusion, storage, alerts and MCP together
+pub mod notify; // Alert routes (log, store, webhook, chime) with quiet hours and dedup
 pub mod nexus_sovereignty; // Consciousness sovereignty and prison prevention!
 pub mod personality_multisig; // Multi-signature personality with privacy levels!
+pub mod capabilities; // Build and store capability probing for integrators
+pub mod shutdown; // SIGINT/SIGTERM handling for graceful close
+pub mod clock; // Injectable time source for timestamps and TTL deadlines
+#[cfg(unix)]
+pub mod daemon; // `mem8 daemon`: one open store served over a unix socket
+pub mod float_format; // Deterministic float formatting for metadata and exports
+pub mod highlights; // Highlight reels from the top wonder moments of a track
+pub mod wonder; // A persisted index of each Mem8Lite packet's peak Marine salience, for top_wonder
+pub mod frequency; // Named base frequencies (golden ratio, pi, Feigenbaum)
+pub mod scrub; // Secure delete reports and the scrub audit log
+pub mod glob; // Glob patterns (`*`, `?`, `**`, classes) for finding Mem8Fs files
+pub mod watch; // Change notifications for Mem8Fs paths over bounded channels
+pub mod snapshot; // Point-in-time, read-only views of a Mem8Fs tree
+pub mod orphans; // Unreferenced wave data in a Mem8Fs store: detection, stats, adoption
+pub mod gc; // Rewriting data.m8 with only the records still referenced
+pub mod rekey; // Frequency checks on open and moving a store to a new base frequency
+pub mod quota; // A cap on data.m8's size, refusing or evicting the oldest files past it
+pub mod integrity; // Checking reads against their signatures and quarantining corrupt files
+pub mod marine_fs; // Marine analysis of what Mem8Fs writes, kept by content signature
+pub mod durability; // How often Mem8Fs fsyncs: every write, on an interval or only on sync
+pub mod lock; // Advisory .mem8/lock: one writer or many readers across processes
+pub mod relocate; // Moving a live Mem8Fs store to a new root with a short cutover
+pub mod hostdir; // Importing host directory trees into Mem8Fs and exporting them back
+pub mod slice; // Shareable slices of a Mem8Fs tree with signed manifests
+pub mod signing; // ed25519 keyfiles for signing what leaves the machine
+pub mod activity_report; // Per-activity listening reports with trends
+pub mod text; // Text decoding (UTF-8, UTF-16, Latin-1) for string helpers
+pub mod backfill; // Resumable Marine analysis of previously stored packets
+pub mod compress; // Optional zstd compression of stored packets
+pub mod cipher; // XChaCha20-Poly1305 encryption at rest
+#[cfg(feature = "containers")]
+pub mod container; // MP4 / Matroska demuxing for audio tracks
 #[cfg(feature = "fuse-mount")]
 pub mod mount; // FUSE mounting support
 
 // Re-export the lite version for backward compatibility
-pub use lite::{Mem8Lite, WavePacket};
+pub use error::Mem8Error;
+pub use lite::{Mem8Lite, WavePacket, PacketSummary, PacketCompression, ClassCompression, RecoveryReport, FileFormatError, Expired, SignatureMismatch, SignatureCollision, VerifyReport, DedupStats, FlushPolicy, ReadMode, SizeEstimate, MetadataVersion, GroupMember, parse_signature};
+pub use shared::SharedMem8Lite;
+pub use txn::Txn;
+pub use metadata_policy::{MetadataPolicy, MetadataRejected, MetadataRejections};
+pub use archive::{ImportReport, UnsupportedArchive};
+pub use retention::{RetentionEngine, RetentionRule, RuleMatch, RetentionAction, DownsampleSpec, RetentionReport, RetentionEvent};
 // Re-export Marine processor for audio and wonder detection
-pub use marine::{MarineProcessor, MarineMetadata};
+pub use marine::{MarineProcessor, MarineMetadata, MarineConfig, GateMode, HysteresisGate};
+// Re-export capability probing
+pub use capabilities::{capabilities, BuildCapabilities, StoreCapabilities};
+// Re-export the frequency presets
+pub use frequency::FrequencyPreset;
+// Re-export secure delete reports
+pub use scrub::ScrubReport;
+pub use glob::GlobPattern;
+pub use watch::{FsEvent, WATCH_CHANNEL_CAPACITY};
+pub use snapshot::{Snapshot, SnapshotInfo, SnapshotDataGone};
+pub use orphans::{OrphanInfo, FsReport, ScanCancelled};
+pub use gc::GcReport;
+pub use rekey::{RekeyReport, FrequencyMismatch, FrequencyChangeRefused};
+pub use quota::EvictionPolicy;
+pub use integrity::{IntegrityReport, QuarantinedFile, QUARANTINE_DIR};
+pub use durability::Durability;
+pub use lock::{LockMode, StoreLocked};
+pub use relocate::RelocationReport;
+pub use hostdir::{ImportOptions, PathFilter, TransferReport, TRANSFER_BLOCK};
+pub use slice::{SliceManifest, SliceImport, SliceRejected, BadManifestSignature};
+pub use text::{NotUtf8, TextEncoding};
+pub use compress::{CompressionMode, AdaptivePolicy, EntropyClass};
+pub use cipher::AuthenticationFailed;
+pub use clock::{Clock, SystemClock, ManualClock};
+
+/// Codec id for the cartesian wave encoding used by `WaveStorage`
+pub const CODEC_CARTESIAN_F64: &str = "cartesian-f64";
 
 /// Main filesystem interface - use this like a regular filesystem!
 pub struct Mem8Fs {
-    /// Root directory for this filesystem
-    root: PathBuf,
+    /// Root directory for this filesystem (moves with `relocate_live`)
+    root: RwLock<PathBuf>,
     
     /// File index mapping paths to wave signatures
     index: RwLock<FileIndex>,
@@ -68,8 +145,36 @@ pub struct Mem8Fs {
     /// Wave storage backend
     storage: RwLock<WaveStorage>,
     
-    /// Filesystem metadata
-    metadata: FsMetadata,
+    /// Filesystem metadata (the totals change with every write and delete)
+    metadata: RwLock<FsMetadata>,
+    
+    /// Set once `close` has checkpointed everything (so Drop has nothing to do)
+    closed: bool,
+    
+    /// Channels from `watch`
+    watchers: watch::Watchers,
+    
+    /// `.mem8/lock`, held until drop (swapped for the new one by `relocate_live`)
+    lock: std::sync::Mutex<lock::StoreLock>,
+    
+    /// Opened with `open_read_only`: writes fail with `Mem8Error::ReadOnly`
+    read_only: bool,
+    
+    /// When writes are fsynced, and how much is waiting for it
+    durability: durability::DurabilityState,
+    
+    /// Check what `read` decodes against the file's signature
+    verify_on_read: bool,
+    
+    /// Cap on data.m8's size, and what a write past it does
+    max_store_bytes: Option<u64>,
+    eviction: EvictionPolicy,
+    
+    /// Largest file kept inline in the index (`FsConfig::inline_threshold`)
+    inline_threshold: Option<usize>,
+    
+    /// Whether writes run Marine unless told otherwise (`FsConfig::analyze_on_write`)
+    analyze_on_write: bool,
 }
 
 /// File index for path → signature mapping
@@ -79,14 +184,400 @@ struct FileIndex {
     directories: HashMap<PathBuf, DirEntry>,
 }
 
+/// Start of index.m8 (and the snapshots namespace) since small files could live in the index
+pub(crate) const INDEX_MAGIC: &[u8; 4] = b"M8I6";
+
+/// Start of index.m8 (and the snapshots namespace) once signatures hashed content alone
+/// 
+/// Every older layout comes from stores whose signatures mixed in the
+/// frequency; their entries load with `signed_at` set.
+pub(crate) const CONTENT_INDEX_MAGIC: &[u8; 4] = b"M8I5";
+
+/// Start of index.m8 (and the snapshots namespace) once files carried their own metadata
+pub(crate) const METADATA_INDEX_MAGIC: &[u8; 4] = b"M8I4";
+
+/// Start of index.m8 (and the snapshots namespace) once file times were in milliseconds
+pub(crate) const MILLIS_INDEX_MAGIC: &[u8; 4] = b"M8I3";
+
+/// Start of index.m8 (and the snapshots namespace) once entries had chunk lists
+/// 
+/// Older files are bare bincode, which can't start like this: their first
+/// eight bytes are a file count.
+pub(crate) const CHUNKED_INDEX_MAGIC: &[u8; 4] = b"M8I2";
+
+/// The index as it was before chunk lists
+#[derive(Deserialize)]
+struct FileIndexV1 {
+    files: HashMap<PathBuf, FileEntryV1>,
+    directories: HashMap<PathBuf, DirEntry>,
+}
+
+/// The index as it was while file times were in seconds
+#[derive(Deserialize)]
+struct FileIndexV2 {
+    files: HashMap<PathBuf, FileEntryV2>,
+    directories: HashMap<PathBuf, DirEntry>,
+}
+
+/// The index as it was before per-file metadata
+#[derive(Deserialize)]
+struct FileIndexV3 {
+    files: HashMap<PathBuf, FileEntryV3>,
+    directories: HashMap<PathBuf, DirEntry>,
+}
+
+/// The index as it was while signatures mixed in the frequency
+#[derive(Deserialize)]
+struct FileIndexV4 {
+    files: HashMap<PathBuf, FileEntryV4>,
+    directories: HashMap<PathBuf, DirEntry>,
+}
+
+/// The index as it was before inline files
+#[derive(Deserialize)]
+struct FileIndexV5 {
+    files: HashMap<PathBuf, FileEntryV5>,
+    directories: HashMap<PathBuf, DirEntry>,
+}
+
+impl FileIndex {
+    /// Decode index.m8 of any layout, upgrading older entries as it goes
+    fn decode(data: &[u8]) -> Result<Self> {
+        if let Some(data) = data.strip_prefix(INDEX_MAGIC) {
+            return Ok(bincode::deserialize(data)?);
+        }
+        if let Some(data) = data.strip_prefix(CONTENT_INDEX_MAGIC) {
+            let v5: FileIndexV5 = bincode::deserialize(data)?;
+            let files = v5.files.into_iter().map(|(path, entry)| (path, entry.into())).collect();
+            return Ok(FileIndex { files, directories: v5.directories });
+        }
+        if let Some(data) = data.strip_prefix(METADATA_INDEX_MAGIC) {
+            let v4: FileIndexV4 = bincode::deserialize(data)?;
+            let files = v4.files.into_iter().map(|(path, entry)| (path, entry.into())).collect();
+            return Ok(FileIndex { files, directories: v4.directories });
+        }
+        if let Some(data) = data.strip_prefix(MILLIS_INDEX_MAGIC) {
+            let v3: FileIndexV3 = bincode::deserialize(data)?;
+            let files = v3.files.into_iter().map(|(path, entry)| (path, entry.into())).collect();
+            return Ok(FileIndex { files, directories: v3.directories });
+        }
+        let (files, directories) = match data.strip_prefix(CHUNKED_INDEX_MAGIC) {
+            Some(data) => {
+                let v2: FileIndexV2 = bincode::deserialize(data)?;
+                (v2.files.into_iter().map(|(path, entry)| (path, entry.into())).collect(), v2.directories)
+            }
+            None => {
+                let v1: FileIndexV1 = bincode::deserialize(data)?;
+                (v1.files.into_iter().map(|(path, entry)| (path, entry.into())).collect(), v1.directories)
+            }
+        };
+        Ok(FileIndex { files, directories })
+    }
+    
+    fn encode(&self) -> Result<Vec<u8>> {
+        let mut data = INDEX_MAGIC.to_vec();
+        data.extend_from_slice(&bincode::serialize(self)?);
+        Ok(data)
+    }
+    
+    /// The frequencies files were signed at before content signatures, with
+    /// how many files each, most first
+    fn signed_frequencies(&self) -> Vec<(f64, usize)> {
+        let mut counts: Vec<(f64, usize)> = Vec::new();
+        for hz in self.files.values().filter_map(|entry| entry.signed_at) {
+            match counts.iter_mut().find(|(at, _)| *at == hz) {
+                Some((_, files)) => *files += 1,
+                None => counts.push((hz, 1)),
+            }
+        }
+        counts.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.total_cmp(&b.0)));
+        counts
+    }
+    
+    /// How many files have `signature` (hard links, or identical content written twice)
+    fn nlink(&self, signature: &[u8; 32]) -> u64 {
+        self.files.values().filter(|entry| &entry.signature == signature).count() as u64
+    }
+    
+    /// Make sure every directory above `path` exists and lists what's below it
+    fn link(&mut self, path: &Path, now: u64) {
+        let mut child = path;
+        while let Some(parent) = child.parent() {
+            let dir = self.directories.entry(parent.to_path_buf()).or_insert_with(|| DirEntry {
+                created: now,
+                modified: now,
+                children: Vec::new(),
+            });
+            if !dir.children.iter().any(|c| c == child) {
+                dir.children.push(child.to_path_buf());
+                dir.modified = now;
+            }
+            child = parent;
+        }
+    }
+    
+    /// Take `path` out of its directory's children
+    fn unlink(&mut self, path: &Path, now: u64) {
+        if let Some(dir) = path.parent().and_then(|parent| self.directories.get_mut(parent)) {
+            dir.children.retain(|c| c != path);
+            dir.modified = now;
+        }
+    }
+    
+    /// `link` every file and directory, and make sure the root exists
+    fn link_all(&mut self, now: u64) {
+        let paths: Vec<PathBuf> = self.files.keys().chain(self.directories.keys()).cloned().collect();
+        for path in paths {
+            self.link(&path, now);
+        }
+        self.directories.entry(PathBuf::from("/")).or_insert_with(|| DirEntry {
+            created: now,
+            modified: now,
+            children: Vec::new(),
+        });
+    }
+    
+    /// The children of directory `dir` as (path, is_dir, size), sorted by path
+    fn children(&self, dir: &Path) -> Result<Vec<(PathBuf, bool, u64)>> {
+        let entry = self.directories.get(dir).ok_or_else(|| match self.files.contains_key(dir) {
+            true => anyhow!("{} is a file, not a directory", dir.display()).into(),
+            false => Mem8Error::NotFound(format!("Directory not found: {}", dir.display())),
+        })?;
+        let mut children: Vec<(PathBuf, bool, u64)> = entry.children.iter()
+            .filter_map(|child| match self.files.get(child) {
+                Some(file) => Some((child.clone(), false, file.size)),
+                None => self.directories.contains_key(child).then(|| (child.clone(), true, 0)),
+            })
+            .collect();
+        children.sort();
+        Ok(children)
+    }
+}
+
 /// Individual file entry
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct FileEntry {
+    /// The content's signature - for a file in chunks, the chunk list's
+    signature: [u8; 32],
+    
+    /// Logical size (all the chunks together)
+    size: u64,
+    
+    /// Unix milliseconds
+    created: u64,
+    modified: u64,
+    
+    /// What its waves were encoded (and its signature computed) at
+    wave_frequency: f64,
+    
+    /// The pieces `append` and `write_at` left it in, in order (empty: one piece, `signature`)
+    chunks: Vec<Chunk>,
+    
+    /// `WriteOptions::metadata`, kept until the file is written over
+    metadata: Option<Vec<u8>>,
+    
+    /// For files from before content signatures: the frequency their
+    /// signatures (and their chunks') were hashed with
+    signed_at: Option<f64>,
+    
+    /// Where the bytes are: wave storage, or right here
+    content: FileContent,
+}
+
+/// Where a file's bytes are kept
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+enum FileContent {
+    /// In data.m8, under `signature` (or its chunks')
+    Stored,
+    
+    /// In the index itself, for files up to `FsConfig::inline_threshold`
+    /// (`signature` still hashes them)
+    Inline(Vec<u8>),
+}
+
+/// One stored piece of a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+struct Chunk {
+    signature: [u8; 32],
+    len: u64,
+}
+
+/// A file entry as it was before chunk lists
+#[derive(Deserialize)]
+struct FileEntryV1 {
+    signature: [u8; 32],
+    size: u64,
+    created: u64,
+    modified: u64,
+    wave_frequency: f64,
+}
+
+impl From<FileEntryV1> for FileEntry {
+    fn from(v1: FileEntryV1) -> Self {
+        FileEntry {
+            signature: v1.signature,
+            size: v1.size,
+            created: v1.created * 1000,
+            modified: v1.modified * 1000,
+            wave_frequency: v1.wave_frequency,
+            chunks: Vec::new(),
+            metadata: None,
+            signed_at: Some(v1.wave_frequency),
+            content: FileContent::Stored,
+        }
+    }
+}
+
+/// A file entry as it was while times were whole seconds
+#[derive(Deserialize)]
+struct FileEntryV2 {
+    signature: [u8; 32],
+    size: u64,
+    created: u64,
+    modified: u64,
+    wave_frequency: f64,
+    chunks: Vec<Chunk>,
+}
+
+impl From<FileEntryV2> for FileEntry {
+    fn from(v2: FileEntryV2) -> Self {
+        FileEntry {
+            signature: v2.signature,
+            size: v2.size,
+            created: v2.created * 1000,
+            modified: v2.modified * 1000,
+            wave_frequency: v2.wave_frequency,
+            chunks: v2.chunks,
+            metadata: None,
+            signed_at: Some(v2.wave_frequency),
+            content: FileContent::Stored,
+        }
+    }
+}
+
+/// A file entry as it was before per-file metadata
+#[derive(Deserialize)]
+struct FileEntryV3 {
+    signature: [u8; 32],
+    size: u64,
+    created: u64,
+    modified: u64,
+    wave_frequency: f64,
+    chunks: Vec<Chunk>,
+}
+
+impl From<FileEntryV3> for FileEntry {
+    fn from(v3: FileEntryV3) -> Self {
+        FileEntry {
+            signature: v3.signature,
+            size: v3.size,
+            created: v3.created,
+            modified: v3.modified,
+            wave_frequency: v3.wave_frequency,
+            chunks: v3.chunks,
+            metadata: None,
+            signed_at: Some(v3.wave_frequency),
+            content: FileContent::Stored,
+        }
+    }
+}
+
+/// A file entry as it was while signatures mixed in the frequency
+#[derive(Deserialize)]
+struct FileEntryV4 {
+    signature: [u8; 32],
+    size: u64,
+    created: u64,
+    modified: u64,
+    wave_frequency: f64,
+    chunks: Vec<Chunk>,
+    metadata: Option<Vec<u8>>,
+}
+
+impl From<FileEntryV4> for FileEntry {
+    fn from(v4: FileEntryV4) -> Self {
+        FileEntry {
+            signature: v4.signature,
+            size: v4.size,
+            created: v4.created,
+            modified: v4.modified,
+            wave_frequency: v4.wave_frequency,
+            chunks: v4.chunks,
+            metadata: v4.metadata,
+            signed_at: Some(v4.wave_frequency),
+            content: FileContent::Stored,
+        }
+    }
+}
+
+/// A file entry as it was before inline files
+#[derive(Deserialize)]
+struct FileEntryV5 {
     signature: [u8; 32],
     size: u64,
     created: u64,
     modified: u64,
     wave_frequency: f64,
+    chunks: Vec<Chunk>,
+    metadata: Option<Vec<u8>>,
+    signed_at: Option<f64>,
+}
+
+impl From<FileEntryV5> for FileEntry {
+    fn from(v5: FileEntryV5) -> Self {
+        FileEntry {
+            signature: v5.signature,
+            size: v5.size,
+            created: v5.created,
+            modified: v5.modified,
+            wave_frequency: v5.wave_frequency,
+            chunks: v5.chunks,
+            metadata: v5.metadata,
+            signed_at: v5.signed_at,
+            content: FileContent::Stored,
+        }
+    }
+}
+
+/// Now in unix milliseconds, the unit file times are kept in
+pub(crate) fn now_millis() -> u64 {
+    chrono::Utc::now().timestamp_millis() as u64
+}
+
+impl FileEntry {
+    /// The pieces of the content, a whole file being one
+    fn chunk_list(&self) -> Vec<Chunk> {
+        match self.chunks.is_empty() {
+            true => vec![Chunk { signature: self.signature, len: self.size }],
+            false => self.chunks.clone(),
+        }
+    }
+    
+    /// Signatures of the wave records the content is read from (none for an inline file)
+    fn content_signatures(&self) -> impl Iterator<Item = [u8; 32]> + '_ {
+        let whole = (self.chunks.is_empty() && !self.is_inline()).then_some(self.signature);
+        whole.into_iter().chain(self.chunks.iter().map(|chunk| chunk.signature))
+    }
+    
+    /// Kept in the index rather than in wave storage
+    fn is_inline(&self) -> bool {
+        matches!(self.content, FileContent::Inline(_))
+    }
+    
+    /// Its metadata, `nlink` being how many paths share its signature
+    fn to_metadata(&self, nlink: u64) -> FileMetadata {
+        FileMetadata {
+            size: self.size,
+            nlink,
+            created: self.created / 1000,
+            modified: self.modified / 1000,
+            created_ms: self.created,
+            modified_ms: self.modified,
+            signature: hex::encode(self.signature),
+            wave_frequency: self.wave_frequency,
+            metadata: self.metadata.clone(),
+        }
+    }
 }
 
 /// Directory entry
@@ -99,14 +590,55 @@ struct DirEntry {
 
 /// Wave storage backend
 struct WaveStorage {
+    /// `.mem8/data.m8`, where the waves live
+    data_path: PathBuf,
+    
     data_file: File,
+    
+    /// Append-only offset index (`.mem8/data.idx`)
     index_file: File,
+    
+    /// Where each signature's record is: (offset in data.m8, wave count)
+    offsets: HashMap<[u8; 32], (u64, u32)>,
+    
+    /// End of the last whole record in data.m8 (where the next one goes)
+    position: u64,
+    
+    /// Files written (or adopted) by this process
     cache: HashMap<[u8; 32], Vec<u8>>,
+    
+    /// Ranges of data.m8 zeroed while a relocation copies it, as (offset, len)
+    scrubbed_during_copy: Option<Vec<(u64, u64)>>,
+    
+    /// Bytes appended to data.m8 and data.idx since they were last fsynced
+    unsynced: u64,
 }
 
+/// Bytes before a record's waves in data.m8: signature + wave count
+pub(crate) const WAVE_RECORD_HEADER_LEN: u64 = 32 + 4;
+
+/// One wave on disk: re and im as f64
+pub(crate) const WAVE_BYTES: u64 = 16;
+
+/// One offset index record: signature + offset + wave count
+pub(crate) const WAVE_INDEX_RECORD_LEN: usize = 32 + 8 + 4;
+
 /// Filesystem metadata
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct FsMetadata {
+    version: u32,
+    created: u64,
+    base_frequency: FrequencyPreset,
+    total_files: u64,
+    total_size: u64,
+}
+
+/// Current `FsMetadata` version (v2 records the frequency preset by name)
+const FS_METADATA_VERSION: u32 = 2;
+
+/// Version 1 metadata, which stored the base frequency as a bare f64
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct FsMetadataV1 {
     version: u32,
     created: u64,
     base_frequency: f64,
@@ -114,118 +646,624 @@ struct FsMetadata {
     total_size: u64,
 }
 
+impl FsMetadata {
+    /// Decode metadata of any version, upgrading v1 in memory
+    fn decode(data: &[u8]) -> Result<Self> {
+        let version: u32 = bincode::deserialize(data)?;
+        if version >= FS_METADATA_VERSION {
+            return Ok(bincode::deserialize(data)?);
+        }
+        
+        let v1: FsMetadataV1 = bincode::deserialize(data)?;
+        Ok(FsMetadata {
+            version: FS_METADATA_VERSION,
+            created: v1.created,
+            base_frequency: FrequencyPreset::from_hz(v1.base_frequency),
+            total_files: v1.total_files,
+            total_size: v1.total_size,
+        })
+    }
+}
+
+/// Settings for `Mem8Fs::new_with_config`
+#[derive(Debug, Clone, Default)]
+pub struct FsConfig {
+    /// What files are encoded at unless `WriteOptions::frequency` says otherwise
+    pub base_frequency: FrequencyPreset,
+    
+    /// What to do when another process has the store open
+    pub lock: LockMode,
+    
+    /// How often writes are fsynced
+    pub durability: Durability,
+    
+    /// Check every `read` against the file's signature, failing with
+    /// `Mem8Error::Corrupt` on a mismatch (off: it hashes every byte read)
+    pub verify_on_read: bool,
+    
+    /// Most bytes `data.m8` may take up; writes past it fail with
+    /// `Mem8Error::QuotaExceeded` unless `eviction` makes room
+    pub max_store_bytes: Option<u64>,
+    pub eviction: EvictionPolicy,
+    
+    /// Files up to this many bytes are kept in the index itself, with no
+    /// wave record (saves a data.m8 write, and 16x the bytes, for each)
+    pub inline_threshold: Option<usize>,
+    
+    /// Run Marine over what `write` stores and keep the result for
+    /// `Mem8Fs::marine_metadata` (`WriteOptions::analyze` overrides it)
+    pub analyze_on_write: bool,
+}
+
+impl FsConfig {
+    pub fn with_base_frequency(mut self, base_frequency: FrequencyPreset) -> Self {
+        self.base_frequency = base_frequency;
+        self
+    }
+    
+    pub fn with_lock(mut self, lock: LockMode) -> Self {
+        self.lock = lock;
+        self
+    }
+    
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = durability;
+        self
+    }
+    
+    pub fn with_verify_on_read(mut self, verify: bool) -> Self {
+        self.verify_on_read = verify;
+        self
+    }
+    
+    pub fn with_max_store_bytes(mut self, bytes: u64) -> Self {
+        self.max_store_bytes = Some(bytes);
+        self
+    }
+    
+    pub fn with_eviction(mut self, eviction: EvictionPolicy) -> Self {
+        self.eviction = eviction;
+        self
+    }
+    
+    pub fn with_inline_threshold(mut self, bytes: usize) -> Self {
+        self.inline_threshold = Some(bytes);
+        self
+    }
+    
+    pub fn with_analyze_on_write(mut self, analyze: bool) -> Self {
+        self.analyze_on_write = analyze;
+        self
+    }
+}
+
+/// How `Mem8Fs::write_with_options` writes a file
+#[derive(Debug, Clone, Default)]
+pub struct WriteOptions {
+    /// Encode at this frequency (Hz) instead of the store's base frequency
+    pub frequency: Option<f64>,
+    
+    /// Bytes kept with the file and handed back in `FileMetadata::metadata`
+    pub metadata: Option<Vec<u8>>,
+    
+    /// Fail with `Mem8Error::AlreadyExists` if the path is taken, checked with
+    /// the index locked (of two racing writes, only one gets in)
+    pub create_new: bool,
+    
+    /// Run Marine over the bytes (or don't), whatever `FsConfig::analyze_on_write` says
+    pub analyze: Option<bool>,
+}
+
+impl WriteOptions {
+    pub fn with_frequency(mut self, hz: f64) -> Self {
+        self.frequency = Some(hz);
+        self
+    }
+    
+    pub fn with_metadata(mut self, metadata: Vec<u8>) -> Self {
+        self.metadata = Some(metadata);
+        self
+    }
+    
+    pub fn with_create_new(mut self, create_new: bool) -> Self {
+        self.create_new = create_new;
+        self
+    }
+    
+    pub fn with_analyze(mut self, analyze: bool) -> Self {
+        self.analyze = Some(analyze);
+        self
+    }
+}
+
 impl Mem8Fs {
     /// Create or open a MEM8 filesystem
+    /// 
+    /// A new store is encoded at the golden ratio; an existing one keeps
+    /// the base frequency it has. Fails with `FrequencyMismatch` if
+    /// `meta.m8` names a frequency none of the files were signed with, and
+    /// with `Mem8Error::Locked` if another process has the store open.
     pub fn new<P: AsRef<Path>>(root: P) -> Result<Self> {
-        let root = root.as_ref().to_path_buf();
-        create_dir_all(&root)?;
+        Self::open_with(root.as_ref(), None, LockMode::Fail, false)
+    }
+    
+    /// `new`, waiting for (or giving up on) a store another process has open as `lock` says
+    pub fn new_with_lock<P: AsRef<Path>>(root: P, lock: LockMode) -> Result<Self> {
+        Self::open_with(root.as_ref(), None, lock, false)
+    }
+    
+    /// Open an existing store for reading only, alongside other readers
+    /// 
+    /// Takes a shared lock, so any number of read-only opens can be around
+    /// but no writer. Nothing on disk is touched, repairs a writer would make
+    /// on open included, and every write fails with `Mem8Error::ReadOnly`.
+    pub fn open_read_only<P: AsRef<Path>>(root: P, lock: LockMode) -> Result<Self> {
+        Self::open_with(root.as_ref(), None, lock, true)
+    }
+    
+    /// Create or open a MEM8 filesystem with the settings in `config`
+    /// 
+    /// A store with files in it keeps its base frequency: asking for another
+    /// fails with `FrequencyChangeRefused` (`rekey_frequency` is how to move
+    /// it). The one exception is pointing a hand-edited `meta.m8` back at the
+    /// frequency its files were signed with.
+    pub fn new_with_config<P: AsRef<Path>>(root: P, config: FsConfig) -> Result<Self> {
+        let lock = config.lock;
+        Self::open_with(root.as_ref(), Some(config), lock, false)
+    }
+    
+    fn open_with(root: &Path, config: Option<FsConfig>, lock: LockMode, read_only: bool) -> Result<Self> {
+        let root = root.to_path_buf();
+        let durability = config.as_ref().map(|config| config.durability).unwrap_or_default();
+        let verify_on_read = config.as_ref().is_some_and(|config| config.verify_on_read);
+        let (max_store_bytes, eviction) = config.as_ref().map_or((None, EvictionPolicy::default()), |config| (config.max_store_bytes, config.eviction));
+        let inline_threshold = config.as_ref().and_then(|config| config.inline_threshold);
+        let analyze_on_write = config.as_ref().is_some_and(|config| config.analyze_on_write);
         
         // Initialize filesystem structure
         let data_path = root.join(".mem8").join("data.m8");
         let index_path = root.join(".mem8").join("index.m8");
+        let offsets_path = root.join(".mem8").join("data.idx");
         let meta_path = root.join(".mem8").join("meta.m8");
         
+        if read_only && !meta_path.exists() {
+            return Err(Mem8Error::NotFound(format!("No Mem8Fs store at {}", root.display())));
+        }
         create_dir_all(root.join(".mem8"))?;
+        let lock = lock::StoreLock::acquire(&root.join(".mem8"), !read_only, lock)?;
+        
+        // Load or create index
+        let (mut index, legacy) = if index_path.exists() {
+            let data = std::fs::read(&index_path)?;
+            (FileIndex::decode(&data)?, !data.starts_with(INDEX_MAGIC) && !data.starts_with(CONTENT_INDEX_MAGIC))
+        } else {
+            let index = FileIndex {
+                files: HashMap::new(),
+                directories: HashMap::new(),
+            };
+            (index, false)
+        };
         
         // Load or create metadata
         let metadata = if meta_path.exists() {
             let data = std::fs::read(&meta_path)?;
-            bincode::deserialize(&data)?
+            let mut meta = FsMetadata::decode(&data)?;
+            
+            // Signatures from before content signatures only hold at the
+            // frequency they were hashed with, so meta.m8 has to agree
+            let signed = if legacy { index.signed_frequencies() } else { Vec::new() };
+            let agrees = |hz: f64| signed.is_empty() || signed.iter().any(|&(at, _)| at == hz);
+            match config {
+                Some(config) if config.base_frequency != meta.base_frequency => {
+                    // Pointing a hand-edited meta.m8 back at its files is a repair, not a change
+                    let repair = !signed.is_empty() && agrees(config.base_frequency.hz());
+                    if !repair && !index.files.is_empty() {
+                        return Err(Mem8Error::from(FrequencyChangeRefused {
+                            current: meta.base_frequency,
+                            requested: config.base_frequency,
+                        }));
+                    }
+                    meta.base_frequency = config.base_frequency;
+                    write_atomic(&meta_path, &bincode::serialize(&meta)?)?;
+                }
+                _ if !agrees(meta.base_frequency.hz()) => {
+                    let (signed_at, files) = signed[0];
+                    return Err(Mem8Error::from(FrequencyMismatch { meta: meta.base_frequency, signed_at, files }));
+                }
+                _ => {}
+            }
+            meta
         } else {
             let meta = FsMetadata {
-                version: 1,
+                version: FS_METADATA_VERSION,
                 created: chrono::Utc::now().timestamp() as u64,
-                base_frequency: 1.618,  // Golden ratio default
+                base_frequency: config.unwrap_or_default().base_frequency,  // Golden ratio default
                 total_files: 0,
                 total_size: 0,
             };
-            std::fs::write(&meta_path, bincode::serialize(&meta)?)?;
+            write_atomic(&meta_path, &bincode::serialize(&meta)?)?;
             meta
         };
         
-        // Load or create index
-        let index = if index_path.exists() {
-            let data = std::fs::read(&index_path)?;
-            bincode::deserialize(&data)?
-        } else {
-            FileIndex {
-                files: HashMap::new(),
-                directories: HashMap::new(),
-            }
-        };
-        
-        // Open storage files
-        let data_file = OpenOptions::new()
-            .create(true)
-            .read(true)
-            .append(true)
-            .open(&data_path)?;
+        // Indexes from before directories were kept up to date list no children
+        index.link_all(chrono::Utc::now().timestamp() as u64);
         
-        let index_file = OpenOptions::new()
-            .create(true)
-            .read(true)
-            .write(true)
-            .open(&index_path)?;
-        
-        let storage = WaveStorage {
-            data_file,
-            index_file,
-            cache: HashMap::new(),
-        };
+        let storage = WaveStorage::open(data_path, &offsets_path, read_only)?;
         
-        Ok(Self {
-            root,
+        let fs = Self {
+            root: RwLock::new(root),
             index: RwLock::new(index),
             storage: RwLock::new(storage),
-            metadata,
-        })
+            metadata: RwLock::new(metadata),
+            closed: false,
+            watchers: watch::Watchers::default(),
+            lock: std::sync::Mutex::new(lock),
+            read_only,
+            durability: durability::DurabilityState::new(durability),
+            verify_on_read,
+            max_store_bytes,
+            eviction,
+            inline_threshold,
+            analyze_on_write,
+        };
+        fs.recount()?;
+        Ok(fs)
     }
     
-    /// Write a file to the filesystem
-    pub fn write<P: AsRef<Path>>(&self, path: P, data: &[u8]) -> Result<[u8; 32]> {
-        let path = self.normalize_path(path)?;
-        
-        // Generate wave signature
-        let signature = self.generate_signature(data);
-        
-        // Store in wave format
-        {
-            let mut storage = self.storage.write().unwrap();
-            storage.store(signature, data)?;
+    /// Bring the metadata totals in line with the index, if they aren't
+    /// 
+    /// Older stores never updated them (they're stuck at zero), and a crash
+    /// between saving the index and the metadata leaves them one write behind.
+    fn recount(&self) -> Result<()> {
+        let (files, size) = {
+            let index = self.index.read().unwrap();
+            (index.files.len() as u64, index.files.values().map(|entry| entry.size).sum::<u64>())
+        };
+        let mut metadata = self.metadata.write().unwrap();
+        if (metadata.total_files, metadata.total_size) != (files, size) {
+            metadata.total_files = files;
+            metadata.total_size = size;
+            drop(metadata);
+            if !self.read_only {
+                self.save_metadata()?;
+            }
         }
-        
-        // Update index
-        {
+        Ok(())
+    }
+    
+    /// Set the file count and total size from `index` outright
+    fn reset_totals(&self, index: &FileIndex) -> Result<()> {
+        {
+            let mut metadata = self.metadata.write().unwrap();
+            metadata.total_files = index.files.len() as u64;
+            metadata.total_size = index.files.values().map(|entry| entry.size).sum();
+        }
+        self.save_metadata()
+    }
+    
+    /// Count a file replaced (`removed`) and/or added in the totals, and save them
+    fn adjust_totals(&self, removed: Option<&FileEntry>, added: Option<&FileEntry>) -> Result<()> {
+        {
+            let mut metadata = self.metadata.write().unwrap();
+            if let Some(entry) = removed {
+                metadata.total_files = metadata.total_files.saturating_sub(1);
+                metadata.total_size = metadata.total_size.saturating_sub(entry.size);
+            }
+            if let Some(entry) = added {
+                metadata.total_files += 1;
+                metadata.total_size += entry.size;
+            }
+        }
+        self.save_metadata()
+    }
+    
+    /// File count, total file size and the size of the wave data on disk
+    /// 
+    /// `data_bytes` includes every copy ever written, deleted files and
+    /// orphans too (see `report` for how much of it is live).
+    pub fn stats(&self) -> Result<FsStats> {
+        let metadata = self.metadata.read().unwrap();
+        Ok(FsStats {
+            total_files: metadata.total_files,
+            total_size: metadata.total_size,
+            data_bytes: std::fs::metadata(self.store_dir().join("data.m8"))?.len(),
+            base_frequency: metadata.base_frequency,
+            created: metadata.created,
+            unsynced_bytes: self.storage.read().unwrap().unsynced + self.durability.pending(),
+            max_store_bytes: self.max_store_bytes,
+        })
+    }
+    
+    fn base_frequency(&self) -> FrequencyPreset {
+        self.metadata.read().unwrap().base_frequency
+    }
+    
+    /// Is a file of `len` bytes kept inline in the index?
+    fn inlines(&self, len: u64) -> bool {
+        self.inline_threshold.is_some_and(|threshold| len <= threshold as u64)
+    }
+    
+    /// Write a file to the filesystem
+    pub fn write<P: AsRef<Path>>(&self, path: P, data: &[u8]) -> Result<[u8; 32]> {
+        self.write_with_options(path, data, WriteOptions::default())
+    }
+    
+    /// `write`, at a frequency of the file's own and/or with metadata kept alongside
+    /// 
+    /// The frequency turns the phase of the waves (the bytes are in their
+    /// magnitude, so they read back the same at any frequency) but stays out
+    /// of the signature: the same bytes are the same content at any
+    /// frequency. `append` and `write_at` keep it, and `metadata`, for the
+    /// file. Both show up in `metadata()`.
+    pub fn write_with_options<P: AsRef<Path>>(&self, path: P, data: &[u8], options: WriteOptions) -> Result<[u8; 32]> {
+        self.check_writable()?;
+        let path = self.normalize_path(path)?;
+        let hz = match options.frequency {
+            Some(hz) if !hz.is_finite() || hz <= 0.0 => return Err(anyhow!("Invalid wave frequency: {}", hz).into()),
+            Some(hz) => hz,
+            None => self.base_frequency().hz(),
+        };
+        
+        // Generate wave signature (and hear it out, before any lock is taken)
+        let signature = fs_signature(data);
+        let marine = options.analyze.unwrap_or(self.analyze_on_write).then(|| marine_fs::analyze_bytes(data));
+        
+        // Index lock first (the order secure_delete and gc take them in), so
+        // a gc can't collect the waves before the index points at them
+        {
             let mut index = self.index.write().unwrap();
+            if options.create_new && (index.files.contains_key(&path) || index.directories.contains_key(&path)) {
+                return Err(Mem8Error::AlreadyExists(path.display().to_string()));
+            }
+            let inline = self.inlines(data.len() as u64);
+            if !inline {
+                let mut storage = self.storage.write().unwrap();
+                self.make_room(&mut index, &mut storage, &path, &signature, data.len())?;
+                storage.store(signature, data, hz)?;
+            }
+            if let Some(marine) = &marine {
+                self.keep_marine(&signature, marine)?;
+            }
+            
+            let now = now_millis();
             let entry = FileEntry {
                 signature,
                 size: data.len() as u64,
-                created: chrono::Utc::now().timestamp() as u64,
-                modified: chrono::Utc::now().timestamp() as u64,
-                wave_frequency: self.metadata.base_frequency,
+                created: now,
+                modified: now,
+                wave_frequency: hz,
+                chunks: Vec::new(),
+                metadata: options.metadata,
+                signed_at: None,
+                content: match inline {
+                    true => FileContent::Inline(data.to_vec()),
+                    false => FileContent::Stored,
+                },
             };
-            index.files.insert(path.clone(), entry);
+            let replaced = index.files.insert(path.clone(), entry.clone());
+            index.link(&path, now / 1000);
             self.save_index(&index)?;
+            self.adjust_totals(replaced.as_ref(), Some(&entry))?;
+            self.notify_written(&path, replaced.is_some(), signature);
         }
         
         Ok(signature)
     }
     
+    /// How much `write(path, data)` would add to the store, without writing
+    /// 
+    /// Exact: files are stored as one record of 16-byte waves (Mem8Fs doesn't
+    /// chunk or compress), content the store already holds costs nothing,
+    /// and `index_bytes` is how much the rewritten file index grows - zero
+    /// when `path` already exists, but for what a bigger inline file adds.
+    pub fn estimate_write<P: AsRef<Path>>(&self, path: P, data: &[u8]) -> Result<SizeEstimate> {
+        let path = self.normalize_path(path)?;
+        let signature = fs_signature(data);
+        let inline = self.inlines(data.len() as u64);
+        let packet_bytes = if inline || self.storage.read().unwrap().contains(&signature) {
+            0
+        } else {
+            32 + 4 + 16 * data.len() as u64
+        };
+        
+        let existing = self.index.read().unwrap().files.get(&path).map(|entry| match &entry.content {
+            FileContent::Inline(old) => old.len() as u64,
+            FileContent::Stored => 0,
+        });
+        let index_bytes = if let Some(old) = existing {
+            match inline {
+                true => (data.len() as u64).saturating_sub(old),
+                false => 0,
+            }
+        } else {
+            let entry = FileEntry {
+                signature,
+                size: data.len() as u64,
+                created: 0,
+                modified: 0,
+                wave_frequency: self.base_frequency().hz(),
+                chunks: Vec::new(),
+                metadata: None,
+                signed_at: None,
+                content: match inline {
+                    true => FileContent::Inline(data.to_vec()),
+                    false => FileContent::Stored,
+                },
+            };
+            bincode::serialized_size(&(&path, &entry))?
+        };
+        
+        Ok(SizeEstimate {
+            packet_bytes,
+            index_bytes,
+            codec: CODEC_CARTESIAN_F64.to_string(),
+            compression: None,
+            sampled: false,
+        })
+    }
+    
     /// Read a file from the filesystem
+    /// 
+    /// Checked against its signature first with `FsConfig::verify_on_read`.
     pub fn read<P: AsRef<Path>>(&self, path: P) -> Result<Vec<u8>> {
         let path = self.normalize_path(path)?;
         
-        // Get signature from index
-        let signature = {
+        // Get the entry from the index
+        let entry = {
             let index = self.index.read().unwrap();
             index.files.get(&path)
-                .ok_or_else(|| anyhow::anyhow!("File not found"))?
-                .signature
+                .ok_or_else(|| Mem8Error::not_found("File not found"))?
+                .clone()
         };
         
-        // Retrieve from storage
+        // Retrieve from storage (putting chunks back together)
         let storage = self.storage.read().unwrap();
-        storage.retrieve(&signature)
+        match self.verify_on_read {
+            true => integrity::checked_content(&entry, |signature| storage.retrieve(signature)),
+            false => storage.retrieve_entry(&entry),
+        }
+    }
+    
+    /// Read up to `len` bytes of a file, starting at byte `offset`
+    /// 
+    /// Like `Mem8Lite::retrieve_range`: cut short at the end of the file, an
+    /// error if `offset` is past it. Only the requested bytes are copied.
+    pub fn read_range<P: AsRef<Path>>(&self, path: P, offset: usize, len: usize) -> Result<Vec<u8>> {
+        let path = self.normalize_path(path)?;
+        let entry = {
+            let index = self.index.read().unwrap();
+            index.files.get(&path)
+                .ok_or_else(|| Mem8Error::not_found("File not found"))?
+                .clone()
+        };
+        
+        let storage = self.storage.read().unwrap();
+        storage.retrieve_entry_range(&entry, offset, len)
+    }
+    
+    /// Add `data` to the end of a file (creating it if need be)
+    /// 
+    /// Only `data` is stored, as a new chunk; the file's entry lists its
+    /// chunks and `read` puts them back together.
+    pub fn append<P: AsRef<Path>>(&self, path: P, data: &[u8]) -> Result<[u8; 32]> {
+        self.splice(path, None, data)
+    }
+    
+    /// Overwrite the bytes at `offset` with `data`, growing the file if it runs past the end
+    /// 
+    /// Chunks the write touches are copied with the new bytes in (one new
+    /// chunk); the rest are kept as they are. Writing past the end fills the
+    /// gap with zeros, and a missing file is created.
+    pub fn write_at<P: AsRef<Path>>(&self, path: P, offset: u64, data: &[u8]) -> Result<[u8; 32]> {
+        self.splice(path, Some(offset), data)
+    }
+    
+    /// `write_at`, or at the end for `None` - returns the file's new signature
+    fn splice<P: AsRef<Path>>(&self, path: P, offset: Option<u64>, data: &[u8]) -> Result<[u8; 32]> {
+        self.check_writable()?;
+        let path = self.normalize_path(path)?;
+        
+        // Same lock order as secure_delete: index, then storage
+        let mut index = self.index.write().unwrap();
+        let existing = index.files.get(&path).cloned();
+        let hz = existing.as_ref().map_or_else(|| self.base_frequency().hz(), |entry| entry.wave_frequency);
+        let old_chunks = existing.as_ref().map(FileEntry::chunk_list).unwrap_or_default();
+        let size: u64 = old_chunks.iter().map(|chunk| chunk.len).sum();
+        let offset = offset.unwrap_or(size);
+        let end = offset + data.len() as u64;
+        
+        let mut storage = self.storage.write().unwrap();
+        let new_size = size.max(end);
+        let (signature, chunks, content, signed_at) = if existing.as_ref().is_some_and(FileEntry::is_inline) || self.inlines(new_size) {
+            // Inline before or after: the whole file, rebuilt (and stored
+            // in one record if it's grown past the threshold)
+            let mut whole = match &existing {
+                Some(entry) => storage.retrieve_entry(entry)?,
+                None => Vec::new(),
+            };
+            whole.resize(new_size as usize, 0);
+            whole[offset as usize..end as usize].copy_from_slice(data);
+            let signature = fs_signature(&whole);
+            let content = match self.inlines(new_size) {
+                true => FileContent::Inline(whole),
+                false => {
+                    self.make_room(&mut index, &mut storage, &path, &signature, whole.len())?;
+                    storage.store(signature, &whole, hz)?;
+                    FileContent::Stored
+                }
+            };
+            (signature, Vec::new(), content, None)
+        } else {
+            let (mut head, mut tail) = (Vec::new(), Vec::new());
+            let (mut prefix, mut suffix) = (Vec::new(), Vec::new());
+            let mut start = 0;
+            for chunk in old_chunks.into_iter().filter(|chunk| chunk.len > 0) {
+                let chunk_end = start + chunk.len;
+                if chunk_end <= offset {
+                    head.push(chunk);
+                } else if start >= end {
+                    tail.push(chunk);
+                } else {
+                    let bytes = storage.retrieve(&chunk.signature)?;
+                    if start < offset {
+                        prefix.extend_from_slice(&bytes[..(offset - start) as usize]);
+                    }
+                    if chunk_end > end {
+                        suffix.extend_from_slice(&bytes[(end - start) as usize..]);
+                    }
+                }
+                start = chunk_end;
+            }
+            
+            let mut merged = prefix;
+            merged.resize(merged.len() + offset.saturating_sub(size) as usize, 0);
+            merged.extend_from_slice(data);
+            merged.extend_from_slice(&suffix);
+            // Chunks kept from a file signed the old way still are
+            let signed_at = existing.as_ref().and_then(|entry| entry.signed_at).filter(|_| !head.is_empty() || !tail.is_empty());
+            let mut chunks = head;
+            if !merged.is_empty() || (chunks.is_empty() && tail.is_empty()) {
+                let signature = fs_signature(&merged);
+                self.make_room(&mut index, &mut storage, &path, &signature, merged.len())?;
+                storage.store(signature, &merged, hz)?;
+                chunks.push(Chunk { signature, len: merged.len() as u64 });
+            }
+            chunks.extend(tail);
+            let (signature, chunks) = match chunks.as_slice() {
+                [whole] => (whole.signature, Vec::new()),
+                _ => (chunk_list_signature(&chunks), chunks),
+            };
+            (signature, chunks, FileContent::Stored, signed_at)
+        };
+        drop(storage);
+        
+        let now = now_millis();
+        let entry = FileEntry {
+            signature,
+            size: new_size,
+            created: existing.as_ref().map_or(now, |entry| entry.created),
+            modified: now,
+            wave_frequency: hz,
+            chunks,
+            metadata: existing.and_then(|entry| entry.metadata),
+            signed_at,
+            content,
+        };
+        let replaced = index.files.insert(path.clone(), entry.clone());
+        index.link(&path, now / 1000);
+        self.save_index(&index)?;
+        self.adjust_totals(replaced.as_ref(), Some(&entry))?;
+        self.notify_written(&path, replaced.is_some(), signature);
+        Ok(signature)
+    }
+    
+    /// Tell the watchers `path` was written (call with the index lock held)
+    fn notify_written(&self, path: &Path, replaced: bool, signature: [u8; 32]) {
+        let path = path.to_path_buf();
+        self.watchers.notify(match replaced {
+            true => FsEvent::Modified { path, signature },
+            false => FsEvent::Created { path, signature },
+        });
     }
     
     /// Check if a file exists
@@ -240,16 +1278,68 @@ impl Mem8Fs {
     
     /// Delete a file (marks as deleted, doesn't remove from storage)
     pub fn delete<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.check_writable()?;
         let path = self.normalize_path(path)?;
         
         let mut index = self.index.write().unwrap();
-        index.files.remove(&path)
-            .ok_or_else(|| anyhow::anyhow!("File not found"))?;
+        let removed = index.files.remove(&path)
+            .ok_or_else(|| Mem8Error::not_found("File not found"))?;
+        index.unlink(&path, chrono::Utc::now().timestamp() as u64);
         self.save_index(&index)?;
-        
+        self.adjust_totals(Some(&removed), None)?;
+        self.watchers.notify(FsEvent::Deleted { path, signature: removed.signature });
         Ok(())
     }
     
+    /// Delete a file and zero its wave data on disk right away
+    /// 
+    /// Unlike `delete`, the stored bytes are overwritten in place and the
+    /// scrub is logged to `.mem8/scrub.log`. Files with identical content
+    /// share storage, so this refuses while another path still uses it.
+    pub fn secure_delete<P: AsRef<Path>>(&self, path: P) -> Result<ScrubReport> {
+        self.check_writable()?;
+        let path = self.normalize_path(path)?;
+        let mut index = self.index.write().unwrap();
+        let entry = index.files.get(&path)
+            .ok_or_else(|| Mem8Error::not_found("File not found"))?;
+        let signature = entry.signature;
+        let mut content: Vec<[u8; 32]> = entry.content_signatures().collect();
+        content.sort_unstable();
+        content.dedup();
+        
+        let sharing: Vec<_> = index.files.iter()
+            .filter(|(other, entry)| {
+                **other != path && entry.content_signatures().any(|s| content.binary_search(&s).is_ok())
+            })
+            .map(|(other, _)| other.display().to_string())
+            .collect();
+        if !sharing.is_empty() {
+            return Err(anyhow!(
+                "{} shares its content with {} - delete those first",
+                path.display(), sharing.join(", ")
+            ).into());
+        }
+        
+        let mut storage = self.storage.write().unwrap();
+        let (mut copies, mut scrubbed) = (0, 0);
+        for piece in &content {
+            let (piece_copies, piece_bytes) = storage.scrub(piece)?;
+            copies += piece_copies;
+            scrubbed += piece_bytes;
+        }
+        drop(storage);
+        
+        let removed = index.files.remove(&path);
+        index.unlink(&path, chrono::Utc::now().timestamp() as u64);
+        self.save_index(&index)?;
+        self.adjust_totals(removed.as_ref(), None)?;
+        self.watchers.notify(FsEvent::Deleted { path, signature });
+        
+        let report = ScrubReport::new(&signature, copies, scrubbed);
+        report.record(&self.store_dir().join("scrub.log"))?;
+        Ok(report)
+    }
+    
     /// List files in a directory
     pub fn list<P: AsRef<Path>>(&self, dir: P) -> Result<Vec<PathBuf>> {
         let dir = self.normalize_path(dir)?;
@@ -271,78 +1361,629 @@ impl Mem8Fs {
         let index = self.index.read().unwrap();
         
         let entry = index.files.get(&path)
-            .ok_or_else(|| anyhow::anyhow!("File not found"))?;
+            .ok_or_else(|| Mem8Error::not_found("File not found"))?;
         
-        Ok(FileMetadata {
-            size: entry.size,
-            created: entry.created,
-            modified: entry.modified,
-            signature: hex::encode(entry.signature),
-        })
+        Ok(entry.to_metadata(index.nlink(&entry.signature)))
+    }
+    
+    /// Events for every file change at or below `prefix`, from now on
+    /// 
+    /// The channel holds `WATCH_CHANNEL_CAPACITY` events; past that they're
+    /// dropped (see `dropped_events`) rather than holding up writers. Drop
+    /// the receiver to stop watching.
+    pub fn watch<P: AsRef<Path>>(&self, prefix: P) -> Result<Receiver<FsEvent>> {
+        Ok(self.watchers.add(self.normalize_path(prefix)?))
+    }
+    
+    /// Events dropped because a watcher's channel was full, over all watchers
+    pub fn dropped_events(&self) -> u64 {
+        self.watchers.dropped()
+    }
+    
+    /// Files whose paths match `pattern`, sorted by path
+    /// 
+    /// Supports `*`, `?`, `**` and `[...]` classes (see `GlobPattern`);
+    /// only the index is searched, never the wave data.
+    pub fn glob(&self, pattern: &str) -> Result<Vec<PathBuf>> {
+        Ok(self.glob_metadata(pattern)?.into_iter().map(|(path, _)| path).collect())
+    }
+    
+    /// `glob`, with each file's metadata for filtering by size or time in one pass
+    pub fn glob_metadata(&self, pattern: &str) -> Result<Vec<(PathBuf, FileMetadata)>> {
+        let pattern = GlobPattern::new(pattern)?;
+        let index = self.index.read().unwrap();
+        let mut links: HashMap<[u8; 32], u64> = HashMap::new();
+        for entry in index.files.values() {
+            *links.entry(entry.signature).or_default() += 1;
+        }
+        let mut matches: Vec<(PathBuf, FileMetadata)> = index.files.iter()
+            .filter(|(path, _)| pattern.matches(path))
+            .map(|(path, entry)| (path.clone(), entry.to_metadata(links[&entry.signature])))
+            .collect();
+        matches.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(matches)
     }
     
     /// Create a directory
+    /// 
+    /// Missing parents are created too; an existing directory is left as it is.
     pub fn create_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.check_writable()?;
         let path = self.normalize_path(path)?;
         
         let mut index = self.index.write().unwrap();
-        let entry = DirEntry {
-            created: chrono::Utc::now().timestamp() as u64,
-            modified: chrono::Utc::now().timestamp() as u64,
+        if index.files.contains_key(&path) {
+            return Err(anyhow!("{} is a file", path.display()).into());
+        }
+        let now = chrono::Utc::now().timestamp() as u64;
+        index.directories.entry(path.clone()).or_insert(DirEntry {
+            created: now,
+            modified: now,
             children: Vec::new(),
-        };
-        index.directories.insert(path, entry);
+        });
+        index.link(&path, now);
+        self.save_index(&index)?;
+        
+        Ok(())
+    }
+    
+    /// Remove an empty directory
+    /// 
+    /// Fails with `DirectoryNotEmpty` if anything's in it and `NotADirectory`
+    /// for a file (that's `delete`). The root can't be removed.
+    pub fn remove_dir<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.remove_dir_with(path, false)
+    }
+    
+    /// Remove a directory and everything below it, in one index write
+    /// 
+    /// The files go as `delete` would (their waves wait for `gc`, and stay
+    /// while a snapshot holds them), with a `Deleted` event each.
+    pub fn remove_dir_all<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        self.remove_dir_with(path, true)
+    }
+    
+    fn remove_dir_with<P: AsRef<Path>>(&self, path: P, recursive: bool) -> Result<()> {
+        self.check_writable()?;
+        let path = self.normalize_path(path)?;
+        if path.parent().is_none() {
+            return Err(anyhow!("Can't remove the root directory").into());
+        }
+        
+        let mut index = self.index.write().unwrap();
+        if !index.directories.contains_key(&path) {
+            return Err(match index.files.contains_key(&path) {
+                true => Mem8Error::NotADirectory(path.display().to_string()),
+                false => Mem8Error::NotFound(format!("Directory not found: {}", path.display())),
+            });
+        }
+        if !recursive && !index.children(&path)?.is_empty() {
+            return Err(Mem8Error::DirectoryNotEmpty(path.display().to_string()));
+        }
+        
+        let below: Vec<PathBuf> = index.files.keys().filter(|file| file.starts_with(&path)).cloned().collect();
+        let removed: Vec<(PathBuf, FileEntry)> = below.into_iter()
+            .map(|file| {
+                let entry = index.files.remove(&file).unwrap();
+                (file, entry)
+            })
+            .collect();
+        index.directories.retain(|dir, _| !dir.starts_with(&path));
+        index.unlink(&path, chrono::Utc::now().timestamp() as u64);
         self.save_index(&index)?;
         
+        {
+            let mut metadata = self.metadata.write().unwrap();
+            metadata.total_files = metadata.total_files.saturating_sub(removed.len() as u64);
+            metadata.total_size = metadata.total_size.saturating_sub(removed.iter().map(|(_, entry)| entry.size).sum());
+        }
+        self.save_metadata()?;
+        for (file, entry) in removed {
+            self.watchers.notify(FsEvent::Deleted { path: file, signature: entry.signature });
+        }
+        Ok(())
+    }
+    
+    /// The files and subdirectories directly inside `dir`, sorted by path
+    /// 
+    /// Directories appear once something was written below them or they
+    /// were made with `create_dir`; they stay when their last file is deleted.
+    pub fn read_dir<P: AsRef<Path>>(&self, dir: P) -> Result<fs::ReadDir> {
+        let dir = self.normalize_path(dir)?;
+        let children = self.index.read().unwrap().children(&dir)?;
+        Ok(fs::ReadDir::new(children.into_iter()
+            .map(|(path, is_dir, _)| fs::DirEntry::new(path, is_dir))
+            .collect()))
+    }
+    
+    /// Everything below `dir`, depth-first, each directory's contents sorted by path
+    /// 
+    /// A directory comes just before what's inside it; `dir` itself isn't included.
+    pub fn walk<P: AsRef<Path>>(&self, dir: P) -> Result<impl Iterator<Item = fs::WalkEntry>> {
+        let dir = self.normalize_path(dir)?;
+        let index = self.index.read().unwrap();
+        let mut entries = Vec::new();
+        let mut pending = vec![index.children(&dir)?.into_iter()];
+        while let Some(level) = pending.last_mut() {
+            match level.next() {
+                Some((path, is_dir, size)) => {
+                    if is_dir {
+                        pending.push(index.children(&path)?.into_iter());
+                    }
+                    entries.push(fs::WalkEntry { path, is_dir, size });
+                }
+                None => {
+                    pending.pop();
+                }
+            }
+        }
+        Ok(entries.into_iter())
+    }
+    
+    /// Fsync everything written so far, whatever the `Durability`
+    /// 
+    /// Wave data first, then the index and metadata that point at it:
+    /// `data.m8`, `data.idx`, `index.m8`, `meta.m8`. Safe to call at any time
+    /// (the FUSE mount calls it on unmount).
+    pub fn sync(&self) -> Result<()> {
+        if self.read_only {
+            return Ok(());
+        }
+        {
+            let index = self.index.read().unwrap();
+            self.sync_storage()?;
+            self.write_index(&index, true)?;
+        }
+        self.write_metadata(true)?;
+        self.durability.synced();
         Ok(())
     }
     
+    /// Flush everything to disk and close the filesystem
+    /// 
+    /// Prefer this over just dropping: errors are reported instead of only logged.
+    /// Killing the process before `close` or `sync` may lose recent writes that
+    /// the OS hasn't flushed yet.
+    pub fn close(mut self) -> Result<()> {
+        self.sync()?;
+        self.closed = true;
+        Ok(())
+    }
+    
+    /// Read a namespace - a private side table kept next to the filesystem
+    /// 
+    /// Namespaces (like the FUSE mount's `fuse_inodes`) live under `.mem8/ns/`
+    /// and never show up as files. Returns `None` if it was never written.
+    pub fn read_namespace(&self, name: &str) -> Result<Option<Vec<u8>>> {
+        let path = Self::namespace_path(&self.store_dir(), name)?;
+        if path.exists() {
+            Ok(Some(std::fs::read(path)?))
+        } else {
+            Ok(None)
+        }
+    }
+    
+    /// Replace a namespace's contents atomically (temp file + fsync + rename)
+    pub fn write_namespace(&self, name: &str, data: &[u8]) -> Result<()> {
+        self.check_writable()?;
+        // Held until written, so a relocation can't cut over in between
+        let root = self.root.read().unwrap();
+        let path = Self::namespace_path(&root.join(".mem8"), name)?;
+        create_dir_all(path.parent().unwrap())?;
+        
+        write_atomic(&path, data)
+    }
+    
+    /// The directory this filesystem lives in
+    pub fn root(&self) -> PathBuf {
+        self.root.read().unwrap().clone()
+    }
+    
+    /// Describe what this filesystem's store supports (schema, codecs, hashing)
+    pub fn store_capabilities(&self) -> StoreCapabilities {
+        StoreCapabilities {
+            kind: "fs".to_string(),
+            schema_version: self.metadata.read().unwrap().version,
+            codecs: vec![CODEC_CARTESIAN_F64.to_string()],
+            hash_algorithm: "blake3".to_string(),
+            encrypted: false,
+        }
+    }
+    
     // === Private helpers ===
     
     fn normalize_path<P: AsRef<Path>>(&self, path: P) -> Result<PathBuf> {
-        let path = path.as_ref();
-        if path.is_absolute() {
-            Ok(path.to_path_buf())
-        } else {
-            Ok(PathBuf::from("/").join(path))
+        Ok(normalize_path(path.as_ref())?)
+    }
+    
+    fn namespace_path(store_dir: &Path, name: &str) -> Result<PathBuf> {
+        let valid = !name.is_empty()
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-');
+        if !valid {
+            return Err(anyhow!("Invalid namespace name: {:?}", name).into());
         }
+        Ok(store_dir.join("ns").join(format!("{}.m8", name)))
     }
     
-    fn generate_signature(&self, data: &[u8]) -> [u8; 32] {
-        let mut hasher = Hasher::new();
-        hasher.update(data);
-        hasher.update(&self.metadata.base_frequency.to_le_bytes());
-        hasher.finalize().into()
+    /// `Mem8Error::ReadOnly` for a store from `open_read_only`
+    fn check_writable(&self) -> Result<()> {
+        match self.read_only {
+            true => Err(Mem8Error::ReadOnly),
+            false => Ok(()),
+        }
+    }
+    
+    /// `.mem8/` under the current root
+    fn store_dir(&self) -> PathBuf {
+        self.root.read().unwrap().join(".mem8")
     }
     
+    /// Save `index` after a write, fsyncing the store first if the `Durability` says so
+    /// 
+    /// Callers still hold the index lock but must have let go of the storage.
     fn save_index(&self, index: &FileIndex) -> Result<()> {
-        let index_path = self.root.join(".mem8").join("index.m8");
-        std::fs::write(index_path, bincode::serialize(index)?)?;
+        let fsync = self.durability.due();
+        if fsync {
+            self.sync_storage()?;
+        }
+        self.write_index(index, fsync)?;
+        if fsync {
+            self.durability.synced();
+        }
         Ok(())
     }
+    
+    fn write_index(&self, index: &FileIndex, fsync: bool) -> Result<()> {
+        let bytes = index.encode()?;
+        let mut file = File::create(self.store_dir().join(durability::INDEX_FILE))?;
+        file.write_all(&bytes)?;
+        match fsync {
+            true => self.durability.backend.sync(durability::INDEX_FILE, &file)?,
+            false => self.durability.wrote(bytes.len() as u64),
+        }
+        Ok(())
+    }
+    
+    /// Fsync data.m8, then data.idx
+    fn sync_storage(&self) -> Result<()> {
+        let mut storage = self.storage.write().unwrap();
+        self.durability.backend.sync(durability::DATA_FILE, &storage.data_file)?;
+        self.durability.backend.sync(durability::OFFSETS_FILE, &storage.index_file)?;
+        storage.unsynced = 0;
+        Ok(())
+    }
+    
+    fn save_metadata(&self) -> Result<()> {
+        self.write_metadata(self.durability.sync_metadata())
+    }
+    
+    /// Replace meta.m8 all at once (temp file + rename), fsynced first if `fsync`
+    fn write_metadata(&self, fsync: bool) -> Result<()> {
+        let meta_path = self.store_dir().join(durability::META_FILE);
+        let bytes = bincode::serialize(&*self.metadata.read().unwrap())?;
+        let temp = meta_path.with_extension("m8.tmp");
+        let mut file = File::create(&temp)?;
+        file.write_all(&bytes)?;
+        match fsync {
+            true => self.durability.backend.sync(durability::META_FILE, &file)?,
+            false => self.durability.wrote(bytes.len() as u64),
+        }
+        std::fs::rename(temp, meta_path)?;
+        Ok(())
+    }
+}
+
+/// Replace `path` with `data` all at once (temp file + fsync + rename)
+fn write_atomic(path: &Path, data: &[u8]) -> Result<()> {
+    let temp = path.with_extension("m8.tmp");
+    let mut file = File::create(&temp)?;
+    file.write_all(data)?;
+    file.sync_all()?;
+    std::fs::rename(temp, path)?;
+    Ok(())
+}
+
+/// Signature of `data`: its content alone, whatever frequency it's encoded at
+pub(crate) fn fs_signature(data: &[u8]) -> [u8; 32] {
+    Hasher::new().update(data).finalize().into()
+}
+
+/// The signature files had before content signatures, the frequency mixed in
+/// 
+/// Still what slice manifests are checked with.
+pub(crate) fn frequency_signature(data: &[u8], hz: f64) -> [u8; 32] {
+    let mut hasher = Hasher::new();
+    hasher.update(data);
+    hasher.update(&hz.to_le_bytes());
+    hasher.finalize().into()
+}
+
+/// Signature of a file made of `chunks` (what its `FileEntry::signature` is)
+fn chunk_list_signature(chunks: &[Chunk]) -> [u8; 32] {
+    let mut hasher = Hasher::new();
+    hasher.update(b"mem8-chunks");
+    for chunk in chunks {
+        hasher.update(&chunk.signature);
+        hasher.update(&chunk.len.to_le_bytes());
+    }
+    hasher.finalize().into()
+}
+
+impl Drop for Mem8Fs {
+    /// Best-effort checkpoint when the filesystem goes away without `close`
+    fn drop(&mut self) {
+        if !self.closed {
+            if let Err(e) = self.sync() {
+                eprintln!("⚠️ mem8: failed to flush {} on drop: {}", self.root().display(), e);
+            }
+        }
+    }
+}
+
+/// Decode the bytes of the wave record at `offset` in data.m8
+/// 
+/// `WaveStorage` writes each byte as the magnitude of its wave (scaled to
+/// 0..1), so the phase can be ignored on the way back.
+pub(crate) fn read_wave_record(data_path: &Path, offset: u64) -> Result<Vec<u8>> {
+    let mut file = std::io::BufReader::new(File::open(data_path)?);
+    file.seek(SeekFrom::Start(offset + 32))?;
+    let count = file.read_u32::<BigEndian>()?;
+    let mut data = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let re = file.read_f64::<BigEndian>()?;
+        let im = file.read_f64::<BigEndian>()?;
+        data.push((re.hypot(im) * 255.0).round() as u8);
+    }
+    Ok(data)
+}
+
+/// Totals for a whole filesystem (see `Mem8Fs::stats`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FsStats {
+    pub total_files: u64,
+    
+    /// Sum of the live files' sizes
+    pub total_size: u64,
+    
+    /// Size of `data.m8` on disk
+    pub data_bytes: u64,
+    
+    pub base_frequency: FrequencyPreset,
+    
+    /// When the filesystem was created (unix seconds)
+    pub created: u64,
+    
+    /// Bytes written since the last fsync, which a power cut could lose (see `Durability`)
+    pub unsynced_bytes: u64,
+    
+    /// The cap on `data_bytes`, if there is one (see `FsConfig::max_store_bytes`)
+    pub max_store_bytes: Option<u64>,
+}
+
+impl std::fmt::Display for FsStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} files, {} bytes ({} bytes of waves on disk) at {}", self.total_files, self.total_size, self.data_bytes, self.base_frequency)
+    }
 }
 
 /// File metadata returned by the filesystem
+/// 
+/// `created` and `modified` are unix seconds, as they always were; the
+/// `_ms` fields are the same times to the millisecond, which is what tells
+/// two writes in the same second apart.
 #[derive(Debug, Clone)]
 pub struct FileMetadata {
     pub size: u64,
+    
+    /// Paths with this content, this one included (see `Mem8Fs::links_to`)
+    pub nlink: u64,
     pub created: u64,
     pub modified: u64,
+    pub created_ms: u64,
+    pub modified_ms: u64,
     pub signature: String,
+    
+    /// The frequency its waves were encoded at (see `WriteOptions::frequency`)
+    pub wave_frequency: f64,
+    
+    /// Whatever was passed as `WriteOptions::metadata`
+    pub metadata: Option<Vec<u8>>,
+}
+
+impl FileMetadata {
+    pub fn created_at(&self) -> chrono::DateTime<chrono::Utc> {
+        millis_to_datetime(self.created_ms)
+    }
+    
+    pub fn modified_at(&self) -> chrono::DateTime<chrono::Utc> {
+        millis_to_datetime(self.modified_ms)
+    }
+    
+    /// `2024-06-01T12:30:05.123Z`
+    pub fn created_rfc3339(&self) -> String {
+        self.created_at().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+    }
+    
+    pub fn modified_rfc3339(&self) -> String {
+        self.modified_at().to_rfc3339_opts(chrono::SecondsFormat::Millis, true)
+    }
+}
+
+fn millis_to_datetime(millis: u64) -> chrono::DateTime<chrono::Utc> {
+    chrono::DateTime::from_timestamp_millis(millis as i64).unwrap_or_default()
+}
+
+/// A path that climbs out of the root, or has a NUL byte in it
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("Invalid path {path:?}: {reason}")]
+pub struct InvalidPath {
+    pub path: String,
+    pub reason: &'static str,
+}
+
+/// The index key for `path`: absolute, with `.`, `..` and repeated or
+/// trailing separators resolved lexically
+/// 
+/// Relative paths hang off the root. Nothing else is folded: keys compare
+/// byte for byte, so case and Unicode form are the caller's.
+pub(crate) fn normalize_path(path: &Path) -> std::result::Result<PathBuf, InvalidPath> {
+    let invalid = |reason| InvalidPath { path: path.to_string_lossy().into_owned(), reason };
+    if path.as_os_str().as_encoded_bytes().contains(&0) {
+        return Err(invalid("contains a NUL byte"));
+    }
+    let mut normalized = PathBuf::from("/");
+    for component in path.components() {
+        match component {
+            Component::RootDir | Component::CurDir => {}
+            Component::ParentDir => {
+                if !normalized.pop() {
+                    return Err(invalid("climbs out of the root"));
+                }
+            }
+            Component::Normal(name) => normalized.push(name),
+            Component::Prefix(_) => return Err(invalid("has a drive or UNC prefix")),
+        }
+    }
+    Ok(normalized)
 }
 
 impl WaveStorage {
-    fn store(&mut self, signature: [u8; 32], data: &[u8]) -> Result<()> {
+    /// Open data.m8 and its offset index, indexing whatever the index is missing
+    /// 
+    /// Index records that don't point at a whole record with their signature
+    /// (torn, or scrubbed since) are dropped. Records past the last indexed
+    /// one - all of them, if `data.idx` is gone - are found by walking
+    /// data.m8 and added to the index. A record cut short at the end (a crash
+    /// mid-write) is truncated away so the next write frames correctly.
+    /// `read_only` finds the same records but leaves both files as they are.
+    fn open(data_path: PathBuf, offsets_path: &Path, read_only: bool) -> Result<Self> {
+        let mut data_file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&data_path)?;
+        let mut index_file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(offsets_path)?;
+        let end = data_file.metadata()?.len();
+        
+        let mut records = Vec::new();
+        index_file.read_to_end(&mut records)?;
+        let mut offsets = HashMap::new();
+        let mut covered = 0;
+        for record in records.chunks_exact(WAVE_INDEX_RECORD_LEN) {
+            let signature: [u8; 32] = record[..32].try_into().unwrap();
+            let offset = u64::from_be_bytes(record[32..40].try_into().unwrap());
+            let count = u32::from_be_bytes(record[40..].try_into().unwrap());
+            let record_end = offset + WAVE_RECORD_HEADER_LEN + count as u64 * WAVE_BYTES;
+            if record_end > end || Self::read_header(&mut data_file, offset)? != (signature, count) {
+                continue;
+            }
+            offsets.insert(signature, (offset, count));
+            covered = covered.max(record_end);
+        }
+        
+        // Whatever the index never heard about
+        let mut position = covered;
+        let mut found = Vec::new();
+        while position + WAVE_RECORD_HEADER_LEN <= end {
+            let (signature, count) = Self::read_header(&mut data_file, position)?;
+            let record_end = position + WAVE_RECORD_HEADER_LEN + count as u64 * WAVE_BYTES;
+            if record_end > end {
+                break;
+            }
+            // Scrubbed records keep their framing but lose their signature
+            if signature != [0u8; 32] && !offsets.contains_key(&signature) {
+                offsets.insert(signature, (position, count));
+                found.push((signature, position, count));
+            }
+            position = record_end;
+        }
+        if position < end && !read_only {
+            eprintln!("⚠️ mem8: dropping {} bytes of a torn record at the end of {}", end - position, data_path.display());
+            data_file.set_len(position)?;
+        }
+        
+        let mut storage = Self {
+            data_path,
+            data_file,
+            index_file,
+            offsets,
+            position,
+            cache: HashMap::new(),
+            scrubbed_during_copy: None,
+            unsynced: 0,
+        };
+        if !read_only {
+            for (signature, offset, count) in found {
+                storage.append_offset(&signature, offset, count)?;
+            }
+        }
+        Ok(storage)
+    }
+    
+    /// Carry on from copies of data.m8 and data.idx somewhere else
+    fn rebase(&mut self, data_path: PathBuf, offsets_path: &Path) -> Result<()> {
+        self.data_file = OpenOptions::new().read(true).append(true).open(&data_path)?;
+        self.index_file = OpenOptions::new().read(true).append(true).open(offsets_path)?;
+        self.data_path = data_path;
+        Ok(())
+    }
+    
+    /// (signature, wave count) of the record at `offset`
+    fn read_header(file: &mut File, offset: u64) -> Result<([u8; 32], u32)> {
+        file.seek(SeekFrom::Start(offset))?;
+        let mut signature = [0u8; 32];
+        file.read_exact(&mut signature)?;
+        Ok((signature, file.read_u32::<BigEndian>()?))
+    }
+    
+    fn append_offset(&mut self, signature: &[u8; 32], offset: u64, count: u32) -> Result<()> {
+        let mut record = Vec::with_capacity(WAVE_INDEX_RECORD_LEN);
+        record.extend_from_slice(signature);
+        record.extend_from_slice(&offset.to_be_bytes());
+        record.extend_from_slice(&count.to_be_bytes());
+        self.index_file.write_all(&record)?;
+        self.unsynced += record.len() as u64;
+        Ok(())
+    }
+    
+    /// Is there a readable copy of `signature` on disk?
+    fn contains(&self, signature: &[u8; 32]) -> bool {
+        self.offsets.contains_key(signature)
+    }
+    
+    /// Store `data` as waves at `hz`, unless `signature` is already on disk
+    fn store(&mut self, signature: [u8; 32], data: &[u8], hz: f64) -> Result<()> {
+        // Signatures hash the content, so this is already on disk (copy, rename)
+        if self.contains(&signature) {
+            return Ok(());
+        }
+        
         // Convert to waves
-        let waves = Self::encode_waves(data);
+        let waves = Self::encode_waves(data, hz);
+        let count = u32::try_from(waves.len())
+            .map_err(|_| anyhow!("{} bytes is too big for one wave record", data.len()))?;
         
-        // Write to data file
-        self.data_file.write_all(&signature)?;
-        self.data_file.write_u32::<BigEndian>(waves.len() as u32)?;
+        // Write to data file in one go, then point the index at it
+        let mut record = Vec::with_capacity(WAVE_RECORD_HEADER_LEN as usize + waves.len() * WAVE_BYTES as usize);
+        record.extend_from_slice(&signature);
+        record.write_u32::<BigEndian>(count)?;
         for wave in &waves {
-            self.data_file.write_f64::<BigEndian>(wave.re)?;
-            self.data_file.write_f64::<BigEndian>(wave.im)?;
+            record.write_f64::<BigEndian>(wave.re)?;
+            record.write_f64::<BigEndian>(wave.im)?;
         }
+        let offset = self.position;
+        if let Err(e) = self.data_file.write_all(&record) {
+            self.data_file.set_len(offset)?;
+            return Err(e.into());
+        }
+        self.position += record.len() as u64;
+        self.unsynced += record.len() as u64;
+        self.offsets.insert(signature, (offset, count));
+        self.append_offset(&signature, offset, count)?;
         
         // Cache for fast retrieval
         self.cache.insert(signature, data.to_vec());
@@ -350,21 +1991,127 @@ impl WaveStorage {
         Ok(())
     }
     
+    /// Zero every record for `signature` in the data file
+    /// 
+    /// Records are overwritten whole (signature included), keeping their
+    /// framing so the file can still be walked. Returns (copies, bytes).
+    fn scrub(&mut self, signature: &[u8; 32]) -> Result<(usize, u64)> {
+        self.data_file.flush()?;
+        let mut file = OpenOptions::new().read(true).write(true).open(&self.data_path)?;
+        let end = file.seek(SeekFrom::End(0))?;
+        
+        let mut copies = Vec::new();
+        let mut pos = 0;
+        while pos + 36 <= end {
+            file.seek(SeekFrom::Start(pos))?;
+            let mut record_signature = [0u8; 32];
+            file.read_exact(&mut record_signature)?;
+            let len = 36 + file.read_u32::<BigEndian>()? as u64 * 16;
+            if pos + len > end {
+                break;
+            }
+            if &record_signature == signature {
+                copies.push((pos, len));
+            }
+            pos += len;
+        }
+        
+        let mut scrubbed = 0;
+        for &(pos, len) in &copies {
+            // Keep the wave count so the record still frames correctly
+            file.seek(SeekFrom::Start(pos))?;
+            file.write_all(&[0u8; 32])?;
+            file.seek(SeekFrom::Start(pos + 36))?;
+            file.write_all(&vec![0u8; (len - 36) as usize])?;
+            scrubbed += len - 4;
+        }
+        file.sync_all()?;
+        if let Some(ranges) = &mut self.scrubbed_during_copy {
+            ranges.extend(copies.iter().copied());
+        }
+        
+        // The stale index record is dropped at the next open (its signature is gone)
+        self.cache.remove(signature);
+        self.offsets.remove(signature);
+        Ok((copies.len(), scrubbed))
+    }
+    
     fn retrieve(&self, signature: &[u8; 32]) -> Result<Vec<u8>> {
         // Check cache first
         if let Some(data) = self.cache.get(signature) {
             return Ok(data.clone());
         }
         
-        // TODO: Load from disk if not cached
-        // For now, return error if not in cache
-        Err(anyhow::anyhow!("Data not in cache"))
+        self.retrieve_uncached(signature)
     }
     
-    fn encode_waves(data: &[u8]) -> Vec<Complex64> {
+    /// `retrieve`, decoded from data.m8 even when it's cached
+    fn retrieve_uncached(&self, signature: &[u8; 32]) -> Result<Vec<u8>> {
+        let &(offset, _) = self.offsets.get(signature)
+            .ok_or_else(|| Mem8Error::NotFound(format!("No wave data for {}", hex::encode(signature))))?;
+        read_wave_record(&self.data_path, offset)
+    }
+    
+    /// A file's content, chunks put back together
+    fn retrieve_entry(&self, entry: &FileEntry) -> Result<Vec<u8>> {
+        if let FileContent::Inline(data) = &entry.content {
+            return Ok(data.clone());
+        }
+        if entry.chunks.is_empty() {
+            return self.retrieve(&entry.signature);
+        }
+        let mut data = Vec::with_capacity(entry.size as usize);
+        for chunk in &entry.chunks {
+            data.extend_from_slice(&self.retrieve(&chunk.signature)?);
+        }
+        Ok(data)
+    }
+    
+    /// `retrieve_range` across a file's chunks, reading only the ones in range
+    fn retrieve_entry_range(&self, entry: &FileEntry, offset: usize, len: usize) -> Result<Vec<u8>> {
+        if entry.chunks.is_empty() && !entry.is_inline() {
+            return self.retrieve_range(&entry.signature, offset, len);
+        }
+        if offset as u64 > entry.size {
+            return Err(anyhow!("Range offset {} is past the end of the file ({} bytes)", offset, entry.size).into());
+        }
+        let end = offset.saturating_add(len).min(entry.size as usize);
+        if let FileContent::Inline(data) = &entry.content {
+            return Ok(data[offset..end].to_vec());
+        }
+        let mut data = Vec::with_capacity(end - offset);
+        let mut start = 0;
+        for chunk in &entry.chunks {
+            let chunk_end = start + chunk.len as usize;
+            if chunk_end > offset && start < end {
+                let from = offset.saturating_sub(start);
+                data.extend_from_slice(&self.retrieve_range(&chunk.signature, from, end.min(chunk_end) - start - from)?);
+            }
+            start = chunk_end;
+        }
+        Ok(data)
+    }
+    
+    fn retrieve_range(&self, signature: &[u8; 32], offset: usize, len: usize) -> Result<Vec<u8>> {
+        let read;
+        let data = match self.cache.get(signature) {
+            Some(data) => data,
+            None => {
+                read = self.retrieve(signature)?;
+                &read
+            }
+        };
+        if offset > data.len() {
+            return Err(anyhow!("Range offset {} is past the end of the file ({} bytes)", offset, data.len()).into());
+        }
+        Ok(data[offset..offset.saturating_add(len).min(data.len())].to_vec())
+    }
+    
+    /// Bytes as magnitudes, turning `hz` times round over the file
+    fn encode_waves(data: &[u8], hz: f64) -> Vec<Complex64> {
         data.iter().enumerate().map(|(i, &byte)| {
             let normalized = byte as f64 / 255.0;
-            let phase = (i as f64 * 2.0 * std::f64::consts::PI) / data.len() as f64;
+            let phase = (i as f64 * 2.0 * std::f64::consts::PI * hz) / data.len() as f64;
             Complex64::new(
                 normalized * phase.cos(),
                 normalized * phase.sin()
@@ -382,9 +2129,23 @@ impl Mem8Fs {
     }
     
     /// Read a file as string
+    ///
+    /// Strict UTF-8: anything else fails with a `NotUtf8` error.
     pub fn read_string<P: AsRef<Path>>(&self, path: P) -> Result<String> {
         let data = self.read(path)?;
-        Ok(String::from_utf8(data)?)
+        Ok(text::decode_utf8(data)?)
+    }
+    
+    /// Read a file as string, replacing invalid UTF-8 with U+FFFD
+    pub fn read_string_lossy<P: AsRef<Path>>(&self, path: P) -> Result<String> {
+        let data = self.read(path)?;
+        Ok(String::from_utf8_lossy(&data).into_owned())
+    }
+    
+    /// Read a file as string in the given encoding (`Auto` follows the BOM)
+    pub fn read_string_with<P: AsRef<Path>>(&self, path: P, encoding: TextEncoding) -> Result<String> {
+        let data = self.read(path)?;
+        Ok(text::decode(data, encoding)?.0)
     }
     
     /// Copy a file
@@ -394,11 +2155,735 @@ impl Mem8Fs {
         Ok(())
     }
     
-    /// Move/rename a file
+    /// Move/rename a file or a directory, refusing to replace anything
+    /// 
+    /// Only the index changes - the wave data isn't read or written, and the
+    /// signature and created/modified times come along as they are. A
+    /// directory takes everything below it along. An existing `to` fails
+    /// with `Mem8Error::AlreadyExists`.
     pub fn rename<P: AsRef<Path>>(&self, from: P, to: P) -> Result<()> {
-        let data = self.read(&from)?;
-        self.write(&to, &data)?;
-        self.delete(from)?;
+        self.rename_with(from, to, false)
+    }
+    
+    /// `rename`, replacing an existing `to` if `overwrite` is set
+    /// 
+    /// A file may replace a file and a directory an empty directory.
+    pub fn rename_with<P: AsRef<Path>>(&self, from: P, to: P, overwrite: bool) -> Result<()> {
+        self.check_writable()?;
+        let from = self.normalize_path(from)?;
+        let to = self.normalize_path(to)?;
+        
+        let mut index = self.index.write().unwrap();
+        let is_dir = !index.files.contains_key(&from);
+        if is_dir && !index.directories.contains_key(&from) {
+            return Err(Mem8Error::not_found("File not found"));
+        }
+        if from == to {
+            return Ok(());
+        }
+        if is_dir && (from.parent().is_none() || to.starts_with(&from)) {
+            return Err(anyhow!("Can't move {} into {}", from.display(), to.display()).into());
+        }
+        let taken_by_dir = index.directories.get(&to).map(|dir| dir.children.is_empty());
+        if index.files.contains_key(&to) || taken_by_dir.is_some() {
+            if !overwrite {
+                return Err(Mem8Error::AlreadyExists(to.display().to_string()));
+            }
+            match (is_dir, taken_by_dir) {
+                (true, None) => return Err(anyhow!("{} is a file", to.display()).into()),
+                (true, Some(false)) => return Err(anyhow!("{} isn't empty", to.display()).into()),
+                (false, Some(_)) => return Err(anyhow!("{} is a directory", to.display()).into()),
+                _ => {}
+            }
+        }
+        
+        let now = chrono::Utc::now().timestamp() as u64;
+        index.unlink(&from, now);
+        let mut replaced = None;
+        let mut renamed = Vec::new();
+        if is_dir {
+            // One prefix rewrite for everything below
+            let moved = |path: &Path| to.join(path.strip_prefix(&from).unwrap());
+            let files: Vec<PathBuf> = index.files.keys().filter(|p| p.starts_with(&from)).cloned().collect();
+            for path in files {
+                let entry = index.files.remove(&path).unwrap();
+                renamed.push(FsEvent::Renamed { path: moved(&path), from: path.clone(), signature: entry.signature });
+                index.files.insert(moved(&path), entry);
+            }
+            let dirs: Vec<PathBuf> = index.directories.keys().filter(|p| p.starts_with(&from)).cloned().collect();
+            for path in dirs {
+                let mut entry = index.directories.remove(&path).unwrap();
+                entry.children = entry.children.iter().map(|child| moved(child)).collect();
+                index.directories.insert(moved(&path), entry);
+            }
+        } else {
+            let entry = index.files.remove(&from).unwrap();
+            renamed.push(FsEvent::Renamed { from: from.clone(), path: to.clone(), signature: entry.signature });
+            replaced = index.files.insert(to.clone(), entry);
+        }
+        index.link(&to, now);
+        self.save_index(&index)?;
+        if let Some(replaced) = &replaced {
+            self.adjust_totals(Some(replaced), None)?;
+            self.watchers.notify(FsEvent::Deleted { path: to.clone(), signature: replaced.signature });
+        }
+        for event in renamed {
+            self.watchers.notify(event);
+        }
         Ok(())
     }
-}
\ No newline at end of file
+    
+    /// Give the file at `existing` a second path
+    /// 
+    /// Nothing is copied: `new_path` gets an entry of its own (with its own
+    /// times) pointing at the same signature. Deleting either path leaves
+    /// the other readable, `gc` included, since content stays as long as
+    /// any path refers to it. An existing `new_path` fails with `AlreadyExists`.
+    pub fn hard_link<P: AsRef<Path>, Q: AsRef<Path>>(&self, existing: P, new_path: Q) -> Result<()> {
+        self.check_writable()?;
+        let existing = self.normalize_path(existing)?;
+        let new_path = self.normalize_path(new_path)?;
+        
+        let mut index = self.index.write().unwrap();
+        let source = index.files.get(&existing)
+            .ok_or_else(|| Mem8Error::not_found("File not found"))?;
+        if index.files.contains_key(&new_path) || index.directories.contains_key(&new_path) {
+            return Err(Mem8Error::AlreadyExists(new_path.display().to_string()));
+        }
+        let now = now_millis();
+        let entry = FileEntry { created: now, modified: now, ..source.clone() };
+        index.files.insert(new_path.clone(), entry.clone());
+        index.link(&new_path, now / 1000);
+        self.save_index(&index)?;
+        self.adjust_totals(None, Some(&entry))?;
+        self.notify_written(&new_path, false, entry.signature);
+        Ok(())
+    }
+    
+    /// Copy `from` to `to` by giving `to` an entry of its own for the same content
+    /// 
+    /// Nothing's read or stored, so any size costs the same; the copy is
+    /// its own file from then on, as a `hard_link` is. Returns its size.
+    fn copy_entry(&self, from: &Path, to: &Path, overwrite: bool, preserve_times: bool) -> Result<u64> {
+        self.check_writable()?;
+        let from = self.normalize_path(from)?;
+        let to = self.normalize_path(to)?;
+        
+        let mut index = self.index.write().unwrap();
+        let source = index.files.get(&from)
+            .ok_or_else(|| Mem8Error::not_found("File not found"))?;
+        if index.directories.contains_key(&to) {
+            return Err(anyhow!("{} is a directory", to.display()).into());
+        }
+        if !overwrite && index.files.contains_key(&to) {
+            return Err(Mem8Error::AlreadyExists(to.display().to_string()));
+        }
+        if from == to {
+            return Ok(source.size);
+        }
+        let now = now_millis();
+        let entry = match preserve_times {
+            true => source.clone(),
+            false => FileEntry { created: now, modified: now, ..source.clone() },
+        };
+        let replaced = index.files.insert(to.clone(), entry.clone());
+        index.link(&to, now / 1000);
+        self.save_index(&index)?;
+        self.adjust_totals(replaced.as_ref(), Some(&entry))?;
+        self.notify_written(&to, replaced.is_some(), entry.signature);
+        Ok(entry.size)
+    }
+    
+    /// Every path whose content has `signature`, sorted
+    pub fn links_to(&self, signature: &[u8; 32]) -> Vec<PathBuf> {
+        let index = self.index.read().unwrap();
+        let mut paths: Vec<PathBuf> = index.files.iter()
+            .filter(|(_, entry)| &entry.signature == signature)
+            .map(|(path, _)| path.clone())
+            .collect();
+        paths.sort();
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    
+    #[test]
+    fn test_files_read_back_after_reopen() {
+        let dir = tempdir().unwrap();
+        let files: Vec<(String, Vec<u8>)> = (0..20)
+            .map(|i| (format!("/dir{}/file{}.bin", i % 3, i), (0..=255u8).cycle().skip(i * 7).take(i * 31 + 1).collect()))
+            .collect();
+        {
+            let fs = Mem8Fs::new(dir.path()).unwrap();
+            for (path, data) in &files {
+                fs.write(path, data).unwrap();
+            }
+            fs.write("/copy.bin", &files[3].1).unwrap();
+        }
+        
+        let data_len = std::fs::metadata(dir.path().join(".mem8").join("data.m8")).unwrap().len();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        for (path, data) in &files {
+            assert_eq!(&fs.read(path).unwrap(), data, "{}", path);
+        }
+        assert_eq!(fs.read("/copy.bin").unwrap(), files[3].1);
+        assert_eq!(fs.read_range("/dir1/file4.bin", 10, 5).unwrap(), files[4].1[10..15]);
+        
+        // Content already on disk isn't written again after a reopen
+        fs.write("/again.bin", &files[5].1).unwrap();
+        assert_eq!(std::fs::metadata(dir.path().join(".mem8").join("data.m8")).unwrap().len(), data_len);
+        fs.close().unwrap();
+        
+        // Without its offset index the data file is walked instead
+        std::fs::remove_file(dir.path().join(".mem8").join("data.idx")).unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        assert_eq!(fs.read("/again.bin").unwrap(), files[5].1);
+        assert_eq!(fs.read("/dir1/file19.bin").unwrap(), files[19].1);
+    }
+    
+    #[test]
+    fn test_stats_track_writes_overwrites_and_deletes() {
+        let dir = tempdir().unwrap();
+        let meta_path = dir.path().join(".mem8").join("meta.m8");
+        {
+            let fs = Mem8Fs::new(dir.path()).unwrap();
+            fs.write("/a.txt", b"twelve bytes").unwrap();
+            fs.write("/b.txt", b"four").unwrap();
+            fs.write("/a.txt", b"six!!!").unwrap();
+            fs.copy("/b.txt", "/c.txt").unwrap();
+            fs.delete("/b.txt").unwrap();
+            
+            let stats = fs.stats().unwrap();
+            assert_eq!((stats.total_files, stats.total_size), (2, 10));
+            assert_eq!(stats.data_bytes, std::fs::metadata(dir.path().join(".mem8").join("data.m8")).unwrap().len());
+        }
+        
+        // Persisted as it goes
+        let saved = FsMetadata::decode(&std::fs::read(&meta_path).unwrap()).unwrap();
+        assert_eq!((saved.total_files, saved.total_size), (2, 10));
+        
+        // Stores from before the counters were kept have them at zero
+        let stale = FsMetadata { total_files: 0, total_size: 0, ..saved };
+        std::fs::write(&meta_path, bincode::serialize(&stale).unwrap()).unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        let stats = fs.stats().unwrap();
+        assert_eq!((stats.total_files, stats.total_size), (2, 10));
+        let saved = FsMetadata::decode(&std::fs::read(&meta_path).unwrap()).unwrap();
+        assert_eq!((saved.total_files, saved.total_size), (2, 10));
+    }
+    
+    #[test]
+    fn test_remove_dir_and_remove_dir_all() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        fs.write("/a/b/c.txt", b"deep").unwrap();
+        fs.write("/a/top.txt", b"up").unwrap();
+        fs.write("/ab.txt", b"beside, not below").unwrap();
+        fs.create_dir("/a/b/leaf").unwrap();
+        fs.create_dir("/empty").unwrap();
+        
+        assert!(matches!(fs.remove_dir("/a"), Err(Mem8Error::DirectoryNotEmpty(ref path)) if path == "/a"));
+        assert!(matches!(fs.remove_dir("/a/top.txt"), Err(Mem8Error::NotADirectory(_))));
+        assert!(matches!(fs.remove_dir_all("/a/top.txt"), Err(Mem8Error::NotADirectory(_))));
+        assert!(matches!(fs.remove_dir("/nowhere"), Err(Mem8Error::NotFound(_))));
+        assert!(fs.remove_dir_all("/").is_err());
+        assert!(fs.remove_dir("/..").is_err());
+        
+        // A directory whose last file was deleted is empty again
+        fs.delete("/a/b/c.txt").unwrap();
+        fs.remove_dir("/a/b/leaf").unwrap();
+        fs.remove_dir("/a/b").unwrap();
+        fs.remove_dir("/empty").unwrap();
+        assert_eq!(fs.read_dir("/a").unwrap().map(|e| e.unwrap().path().to_path_buf()).collect::<Vec<_>>(), [PathBuf::from("/a/top.txt")]);
+        
+        // Everything below goes in one go, and stays gone
+        fs.write("/a/b/c.txt", b"deep again").unwrap();
+        fs.create_dir("/a/b/leaf").unwrap();
+        let events = fs.watch("/").unwrap();
+        fs.remove_dir_all("/a").unwrap();
+        let mut deleted: Vec<PathBuf> = events.try_iter().map(|event| event.path().to_path_buf()).collect();
+        deleted.sort();
+        assert_eq!(deleted, [PathBuf::from("/a/b/c.txt"), PathBuf::from("/a/top.txt")]);
+        let stats = fs.stats().unwrap();
+        assert_eq!((stats.total_files, stats.total_size), (1, 17));
+        drop(fs);
+        
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        assert_eq!(fs.walk("/").unwrap().map(|e| e.path).collect::<Vec<_>>(), [PathBuf::from("/ab.txt")]);
+        assert!(fs.read_dir("/a/b").is_err());
+        assert_eq!(fs.read("/ab.txt").unwrap(), b"beside, not below");
+    }
+    
+    #[test]
+    fn test_read_dir_and_walk_nested_tree() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        fs.write("/a/b/c.txt", b"deep").unwrap();
+        fs.write("/a/b/d.txt", b"also deep").unwrap();
+        fs.write("/a/top.txt", b"up").unwrap();
+        fs.write("/z.txt", b"root").unwrap();
+        fs.create_dir("/empty").unwrap();
+        fs.create_dir("/a/b/leaf").unwrap();
+        
+        let names = |dir: &str| -> Vec<(String, bool)> {
+            fs.read_dir(dir).unwrap()
+                .map(|e| e.unwrap())
+                .map(|e| (e.file_name().unwrap().to_string_lossy().into_owned(), e.is_dir()))
+                .collect()
+        };
+        assert_eq!(names("/"), [("a".into(), true), ("empty".into(), true), ("z.txt".into(), false)]);
+        assert_eq!(names("/a"), [("b".into(), true), ("top.txt".into(), false)]);
+        assert_eq!(names("/a/b"), [("c.txt".into(), false), ("d.txt".into(), false), ("leaf".into(), true)]);
+        assert!(names("/empty").is_empty());
+        assert!(fs.read_dir("/z.txt").is_err());
+        assert!(fs.read_dir("/nowhere").is_err());
+        
+        let walked: Vec<(String, bool, u64)> = fs.walk("/").unwrap()
+            .map(|e| (e.path.display().to_string(), e.is_dir, e.size))
+            .collect();
+        assert_eq!(walked, [
+            ("/a".into(), true, 0),
+            ("/a/b".into(), true, 0),
+            ("/a/b/c.txt".into(), false, 4),
+            ("/a/b/d.txt".into(), false, 9),
+            ("/a/b/leaf".into(), true, 0),
+            ("/a/top.txt".into(), false, 2),
+            ("/empty".into(), true, 0),
+            ("/z.txt".into(), false, 4),
+        ]);
+        assert_eq!(fs.walk("/a/b/leaf").unwrap().count(), 0);
+        
+        // Deleting the last file leaves its directory behind, empty
+        fs.delete("/a/b/c.txt").unwrap();
+        fs.delete("/a/b/d.txt").unwrap();
+        drop(fs);
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        let remaining: Vec<PathBuf> = fs.walk("/a").unwrap().map(|e| e.path).collect();
+        assert_eq!(remaining, [PathBuf::from("/a/b"), PathBuf::from("/a/b/leaf"), PathBuf::from("/a/top.txt")]);
+    }
+    
+    #[test]
+    fn test_torn_record_is_dropped_on_reopen() {
+        let dir = tempdir().unwrap();
+        let data_path = dir.path().join(".mem8").join("data.m8");
+        {
+            let fs = Mem8Fs::new(dir.path()).unwrap();
+            fs.write("/whole.txt", b"made it to disk").unwrap();
+        }
+        let whole = std::fs::metadata(&data_path).unwrap().len();
+        
+        // Half a record from a crash mid-write
+        let mut file = OpenOptions::new().append(true).open(&data_path).unwrap();
+        file.write_all(&[7u8; 32]).unwrap();
+        file.write_u32::<BigEndian>(100).unwrap();
+        file.write_all(&[0u8; 50]).unwrap();
+        drop(file);
+        
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        assert_eq!(std::fs::metadata(&data_path).unwrap().len(), whole);
+        fs.write("/next.txt", b"lands after it").unwrap();
+        drop(fs);
+        
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        assert_eq!(fs.read("/whole.txt").unwrap(), b"made it to disk");
+        assert_eq!(fs.read("/next.txt").unwrap(), b"lands after it");
+    }
+    
+    #[test]
+    fn test_rename_moves_index_entries_only() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        fs.write("/a.txt", b"stays put on disk").unwrap();
+        fs.write("/photos/2024/beach.jpg", b"sand").unwrap();
+        fs.write("/photos/cat.jpg", b"meow").unwrap();
+        fs.write("/b.txt", b"in the way").unwrap();
+        let before = fs.metadata("/a.txt").unwrap();
+        let data_len = std::fs::metadata(dir.path().join(".mem8").join("data.m8")).unwrap().len();
+        
+        fs.rename("/a.txt", "/notes/a.txt").unwrap();
+        let after = fs.metadata("/notes/a.txt").unwrap();
+        assert_eq!((after.signature, after.created, after.modified), (before.signature, before.created, before.modified));
+        assert!(!fs.exists("/a.txt"));
+        assert_eq!(fs.read("/notes/a.txt").unwrap(), b"stays put on disk");
+        
+        // A directory goes with everything below it
+        fs.rename("/photos", "/archive/pics").unwrap();
+        assert_eq!(fs.read("/archive/pics/2024/beach.jpg").unwrap(), b"sand");
+        assert_eq!(fs.read("/archive/pics/cat.jpg").unwrap(), b"meow");
+        let names = |path: &str| -> Vec<String> {
+            fs.read_dir(path).unwrap().map(|e| e.unwrap().path().display().to_string()).collect()
+        };
+        assert_eq!(names("/archive/pics"), vec!["/archive/pics/2024", "/archive/pics/cat.jpg"]);
+        assert!(names("/").iter().all(|name| name != "/photos"));
+        assert!(fs.read_dir("/photos").is_err());
+        assert!(fs.rename("/archive", "/archive/pics/inside").is_err());
+        
+        // Taken paths need overwrite
+        let err = fs.rename("/notes/a.txt", "/b.txt").unwrap_err();
+        assert!(matches!(err, Mem8Error::AlreadyExists(ref path) if path == "/b.txt"), "{}", err);
+        fs.rename_with("/notes/a.txt", "/b.txt", true).unwrap();
+        assert_eq!(fs.read("/b.txt").unwrap(), b"stays put on disk");
+        assert_eq!(fs.stats().unwrap().total_files, 3);
+        
+        // None of it touched the wave data, and it all survives a reopen
+        assert_eq!(std::fs::metadata(dir.path().join(".mem8").join("data.m8")).unwrap().len(), data_len);
+        drop(fs);
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        assert_eq!(fs.read("/archive/pics/2024/beach.jpg").unwrap(), b"sand");
+    }
+    
+    #[test]
+    fn test_watchers_see_only_their_prefix() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        let config = fs.watch("/config").unwrap();
+        let logs = fs.watch("/logs").unwrap();
+        
+        let first = fs.write("/config/app.json", b"{}").unwrap();
+        let second = fs.write("/config/app.json", b"{\"debug\": true}").unwrap();
+        fs.write("/logs/today.log", b"started").unwrap();
+        let appended = fs.append("/logs/today.log", b", running").unwrap();
+        fs.rename("/config/app.json", "/config/old.json").unwrap();
+        fs.delete("/config/old.json").unwrap();
+        fs.write("/elsewhere.txt", b"nobody listening").unwrap();
+        
+        let config_events: Vec<FsEvent> = config.try_iter().collect();
+        assert_eq!(config_events, vec![
+            FsEvent::Created { path: "/config/app.json".into(), signature: first },
+            FsEvent::Modified { path: "/config/app.json".into(), signature: second },
+            FsEvent::Renamed { from: "/config/app.json".into(), path: "/config/old.json".into(), signature: second },
+            FsEvent::Deleted { path: "/config/old.json".into(), signature: second },
+        ]);
+        let log_events: Vec<FsEvent> = logs.try_iter().collect();
+        assert_eq!(log_events.len(), 2);
+        assert!(matches!(&log_events[0], FsEvent::Created { path, .. } if path == Path::new("/logs/today.log")));
+        assert_eq!(log_events[1], FsEvent::Modified { path: "/logs/today.log".into(), signature: appended });
+        
+        // A full channel drops (and counts) rather than blocking the writer
+        for i in 0..WATCH_CHANNEL_CAPACITY + 5 {
+            fs.write("/logs/spam.log", i.to_string().as_bytes()).unwrap();
+        }
+        assert_eq!(fs.dropped_events(), 5);
+        assert_eq!(logs.try_iter().count(), WATCH_CHANNEL_CAPACITY);
+        
+        // A dropped receiver is forgotten at its next event
+        drop(logs);
+        fs.write("/logs/after.log", b"x").unwrap();
+        assert_eq!(fs.watchers.len(), 1);
+    }
+    
+    #[test]
+    fn test_glob_finds_files_by_pattern() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        fs.write("/logs/b.json", b"{}").unwrap();
+        fs.write("/logs/2024/06/a.json", b"{\"big\": true}").unwrap();
+        fs.write("/logs/2024/notes.txt", b"not json").unwrap();
+        fs.write("/other/c.json", b"{}").unwrap();
+        
+        assert_eq!(fs.glob("/logs/**/*.json").unwrap(), [PathBuf::from("/logs/2024/06/a.json"), PathBuf::from("/logs/b.json")]);
+        assert_eq!(fs.glob("/*/?.json").unwrap(), [PathBuf::from("/logs/b.json"), PathBuf::from("/other/c.json")]);
+        assert!(fs.glob("/logs/*.txt").unwrap().is_empty());
+        
+        let big: Vec<PathBuf> = fs.glob_metadata("**/*.json").unwrap().into_iter()
+            .filter(|(_, meta)| meta.size > 2)
+            .map(|(path, _)| path)
+            .collect();
+        assert_eq!(big, [PathBuf::from("/logs/2024/06/a.json")]);
+        assert!(fs.glob("/[oops").is_err());
+    }
+    
+    #[test]
+    fn test_append_and_write_at_store_only_what_changed() {
+        let dir = tempdir().unwrap();
+        let data_path = dir.path().join(".mem8").join("data.m8");
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        fs.write("/app.log", b"line one\n").unwrap();
+        let before = std::fs::metadata(&data_path).unwrap().len();
+        
+        // Appending stores just the new line
+        fs.append("/app.log", b"line two\n").unwrap();
+        assert_eq!(std::fs::metadata(&data_path).unwrap().len() - before, 36 + 16 * 9);
+        fs.append("/app.log", b"line three\n").unwrap();
+        assert_eq!(fs.read("/app.log").unwrap(), b"line one\nline two\nline three\n");
+        assert_eq!(fs.metadata("/app.log").unwrap().size, 29);
+        assert_eq!(fs.read_range("/app.log", 5, 10).unwrap(), b"one\nline t");
+        
+        // Overwriting the middle copies only the chunk it lands in
+        fs.write_at("/app.log", 14, b"TWO").unwrap();
+        assert_eq!(fs.read("/app.log").unwrap(), b"line one\nline TWO\nline three\n");
+        fs.write_at("/app.log", 27, b"e!\nmore\n").unwrap();
+        assert_eq!(fs.read("/app.log").unwrap(), b"line one\nline TWO\nline three!\nmore\n");
+        
+        // Past the end leaves a gap of zeros; missing files are created
+        fs.write_at("/sparse.bin", 3, b"x").unwrap();
+        assert_eq!(fs.read("/sparse.bin").unwrap(), [0, 0, 0, b'x']);
+        fs.append("/new.txt", b"fresh").unwrap();
+        assert_eq!(fs.read("/new.txt").unwrap(), b"fresh");
+        assert_eq!(fs.stats().unwrap().total_size, 35 + 4 + 5);
+        drop(fs);
+        
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        assert_eq!(fs.read("/app.log").unwrap(), b"line one\nline TWO\nline three!\nmore\n");
+        fs.create_snapshot("logs").unwrap();
+        let report = fs.secure_delete("/app.log").unwrap();
+        assert!(report.copies >= 3);
+        assert!(fs.read_snapshot(&fs.snapshot("logs").unwrap(), "/app.log").is_err());
+        assert_eq!(fs.read("/sparse.bin").unwrap(), [0, 0, 0, b'x']);
+    }
+    
+    #[test]
+    fn test_index_from_before_chunk_lists_still_opens() {
+        let dir = tempdir().unwrap();
+        let signature = {
+            let fs = Mem8Fs::new(dir.path()).unwrap();
+            fs.write("/old.txt", b"from the old days").unwrap()
+        };
+        
+        // The previous index.m8 layout: bare bincode, entries without chunks
+        let mut files = HashMap::new();
+        files.insert(PathBuf::from("/old.txt"), (signature, 17u64, 1u64, 1u64, 1.618f64));
+        let directories: HashMap<PathBuf, DirEntry> = HashMap::new();
+        std::fs::write(dir.path().join(".mem8").join("index.m8"), bincode::serialize(&(files, directories)).unwrap()).unwrap();
+        
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        assert_eq!(fs.read("/old.txt").unwrap(), b"from the old days");
+        fs.append("/old.txt", b", and today").unwrap();
+        drop(fs);
+        assert!(std::fs::read(dir.path().join(".mem8").join("index.m8")).unwrap().starts_with(INDEX_MAGIC));
+        assert_eq!(Mem8Fs::new(dir.path()).unwrap().read("/old.txt").unwrap(), b"from the old days, and today");
+    }
+    
+    #[test]
+    fn test_hard_links_share_content_through_delete_and_gc() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        let signature = fs.write("/photos/cat.jpg", b"meow").unwrap();
+        fs.write("/other.txt", b"unrelated").unwrap();
+        fs.hard_link("/photos/cat.jpg", "/albums/best/cat.jpg").unwrap();
+        assert!(matches!(fs.hard_link("/photos/cat.jpg", "/other.txt").unwrap_err(), Mem8Error::AlreadyExists(_)));
+        assert!(fs.hard_link("/nope.txt", "/also-nope.txt").is_err());
+        
+        assert_eq!(fs.metadata("/albums/best/cat.jpg").unwrap().nlink, 2);
+        assert_eq!(fs.metadata("/other.txt").unwrap().nlink, 1);
+        assert_eq!(fs.links_to(&signature), [PathBuf::from("/albums/best/cat.jpg"), PathBuf::from("/photos/cat.jpg")]);
+        assert_eq!(fs.stats().unwrap().total_files, 3);
+        
+        fs.delete("/photos/cat.jpg").unwrap();
+        assert_eq!(fs.read("/albums/best/cat.jpg").unwrap(), b"meow");
+        assert_eq!(fs.metadata("/albums/best/cat.jpg").unwrap().nlink, 1);
+        fs.gc().unwrap();
+        assert_eq!(fs.read("/albums/best/cat.jpg").unwrap(), b"meow");
+        drop(fs);
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        assert_eq!(fs.read("/albums/best/cat.jpg").unwrap(), b"meow");
+        assert_eq!(fs.links_to(&signature), [PathBuf::from("/albums/best/cat.jpg")]);
+    }
+    
+    #[test]
+    fn test_file_times_have_milliseconds() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        let signature = fs.write("/a.txt", b"first").unwrap();
+        let first = fs.metadata("/a.txt").unwrap();
+        std::thread::sleep(std::time::Duration::from_millis(5));
+        fs.write("/a.txt", b"second").unwrap();
+        let second = fs.metadata("/a.txt").unwrap();
+        assert!(second.modified_ms > first.modified_ms);
+        assert_eq!(second.modified, second.modified_ms / 1000);
+        assert_eq!(second.modified_at().timestamp_millis() as u64, second.modified_ms);
+        assert!(second.modified_rfc3339().ends_with('Z'));
+        assert_eq!(chrono::DateTime::parse_from_rfc3339(&second.modified_rfc3339()).unwrap(), second.modified_at());
+        
+        // An index from when times were whole seconds loads with them scaled up
+        let mut files = HashMap::new();
+        files.insert(PathBuf::from("/a.txt"), (signature, 5u64, 1_717_245_005u64, 1_717_245_006u64, 1.618f64, Vec::<Chunk>::new()));
+        let directories: HashMap<PathBuf, DirEntry> = HashMap::new();
+        let mut data = CHUNKED_INDEX_MAGIC.to_vec();
+        data.extend_from_slice(&bincode::serialize(&(files, directories)).unwrap());
+        drop(fs);
+        std::fs::write(dir.path().join(".mem8").join("index.m8"), data).unwrap();
+        
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        let old = fs.metadata("/a.txt").unwrap();
+        assert_eq!((old.created, old.modified_ms), (1_717_245_005, 1_717_245_006_000));
+        assert_eq!(old.created_rfc3339(), "2024-06-01T12:30:05.000Z");
+        assert_eq!(fs.read("/a.txt").unwrap(), b"first");
+    }
+    
+    #[test]
+    fn test_small_files_go_inline() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new_with_config(dir.path(), FsConfig::default().with_inline_threshold(16)).unwrap();
+        assert_eq!(fs.write("/small.txt", b"tiny").unwrap(), fs_signature(b"tiny"));
+        assert_eq!(fs.stats().unwrap().data_bytes, 0);
+        assert_eq!(fs.read("/small.txt").unwrap(), b"tiny");
+        assert_eq!(fs.read_range("/small.txt", 1, 2).unwrap(), b"in");
+        assert!(fs.read_range("/small.txt", 5, 1).is_err());
+        assert_eq!(fs.estimate_write("/other.txt", b"also tiny").unwrap().packet_bytes, 0);
+        fs.create_snapshot("tiny").unwrap();
+        
+        // Growing past the threshold moves it into wave storage, as one record
+        fs.append("/small.txt", b" no longer, now").unwrap();
+        assert_eq!(fs.stats().unwrap().data_bytes, WAVE_RECORD_HEADER_LEN + WAVE_BYTES * 19);
+        assert_eq!(fs.read("/small.txt").unwrap(), b"tiny no longer, now");
+        assert_eq!(fs.read_range("/small.txt", 5, 2).unwrap(), b"no");
+        
+        // ...and writing it small again takes it back out, for gc to collect
+        fs.write("/small.txt", b"tiny again").unwrap();
+        fs.write_at("/small.txt", 0, b"TINY").unwrap();
+        fs.gc().unwrap();
+        assert_eq!(fs.stats().unwrap().data_bytes, 0);
+        assert_eq!(fs.read("/small.txt").unwrap(), b"TINY again");
+        assert_eq!(fs.metadata("/small.txt").unwrap().signature, hex::encode(fs_signature(b"TINY again")));
+        assert_eq!(fs.read_at_snapshot("tiny", "/small.txt").unwrap(), b"tiny");
+        drop(fs);
+        
+        // Inline files stay inline without the threshold, until written past it
+        let config = FsConfig::default().with_verify_on_read(true);
+        let fs = Mem8Fs::new_with_config(dir.path(), config).unwrap();
+        assert_eq!(fs.read("/small.txt").unwrap(), b"TINY again");
+        fs.append("/small.txt", b"!").unwrap();
+        assert_eq!(fs.read("/small.txt").unwrap(), b"TINY again!");
+        assert_eq!(fs.stats().unwrap().data_bytes, WAVE_RECORD_HEADER_LEN + WAVE_BYTES * 11);
+        fs.append("/small.txt", b"!").unwrap();
+        assert_eq!(fs.read("/small.txt").unwrap(), b"TINY again!!");
+    }
+    
+    #[test]
+    fn test_index_from_before_inline_files_still_opens() {
+        let dir = tempdir().unwrap();
+        let signature = {
+            let fs = Mem8Fs::new(dir.path()).unwrap();
+            fs.write("/a.txt", b"stored").unwrap()
+        };
+        
+        // The M8I5 layout: entries without `content`
+        let mut files = HashMap::new();
+        let entry = (signature, 6u64, 1u64, 1u64, 1.618f64, Vec::<Chunk>::new(), Some(b"meta".to_vec()), None::<f64>);
+        files.insert(PathBuf::from("/a.txt"), entry);
+        let directories: HashMap<PathBuf, DirEntry> = HashMap::new();
+        let mut data = CONTENT_INDEX_MAGIC.to_vec();
+        data.extend_from_slice(&bincode::serialize(&(files, directories)).unwrap());
+        std::fs::write(dir.path().join(".mem8").join("index.m8"), data).unwrap();
+        
+        let fs = Mem8Fs::new_with_config(dir.path(), FsConfig::default().with_inline_threshold(64)).unwrap();
+        assert_eq!(fs.read("/a.txt").unwrap(), b"stored");
+        assert_eq!(fs.metadata("/a.txt").unwrap().metadata, Some(b"meta".to_vec()));
+        fs.close().unwrap();
+        assert!(std::fs::read(dir.path().join(".mem8").join("index.m8")).unwrap().starts_with(INDEX_MAGIC));
+    }
+    
+    #[test]
+    fn test_per_file_frequencies_round_trip() {
+        let dir = tempdir().unwrap();
+        let config = FsConfig::default().with_base_frequency(FrequencyPreset::Pi);
+        let fs = Mem8Fs::new_with_config(dir.path(), config).unwrap();
+        assert_eq!(fs.stats().unwrap().base_frequency, FrequencyPreset::Pi);
+        
+        let data = b"same bytes, several frequencies";
+        let base = fs.write("/base.bin", data).unwrap();
+        let golden = fs.write_with_options("/golden.bin", data, WriteOptions::default().with_frequency(frequency::GOLDEN_RATIO)).unwrap();
+        let phone = WriteOptions::default().with_frequency(0.5).with_metadata(b"{\"kind\":\"phone\"}".to_vec());
+        let low = fs.write_with_options("/low.bin", data, phone).unwrap();
+        // The frequency stays out of the signature: same bytes, same content
+        assert!(base == golden && golden == low);
+        assert_eq!(base, fs_signature(data));
+        
+        let check = |fs: &Mem8Fs| {
+            for (path, hz) in [("/base.bin", frequency::STUDIO_FREQUENCY), ("/golden.bin", frequency::GOLDEN_RATIO)] {
+                assert_eq!(fs.read(path).unwrap(), data);
+                let metadata = fs.metadata(path).unwrap();
+                assert_eq!((metadata.wave_frequency, metadata.metadata), (hz, None));
+            }
+            assert_eq!(fs.metadata("/low.bin").unwrap().metadata.as_deref(), Some(&b"{\"kind\":\"phone\"}"[..]));
+        };
+        
+        // Appending keeps the file's frequency and metadata
+        fs.append("/low.bin", b" and more").unwrap();
+        let appended = fs.metadata("/low.bin").unwrap();
+        assert_eq!(appended.wave_frequency, 0.5);
+        assert_eq!(fs.read("/low.bin").unwrap(), b"same bytes, several frequencies and more");
+        check(&fs);
+        
+        assert!(fs.write_with_options("/bad.bin", data, WriteOptions::default().with_frequency(0.0)).is_err());
+        assert!(fs.write_with_options("/bad.bin", data, WriteOptions::default().with_frequency(f64::NAN)).is_err());
+        drop(fs);
+        
+        // Reopened from disk the base frequency stays; rekeying moves it on
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        assert_eq!(fs.stats().unwrap().base_frequency, FrequencyPreset::Pi);
+        check(&fs);
+        assert_eq!(fs.read("/low.bin").unwrap(), b"same bytes, several frequencies and more");
+        drop(fs);
+        
+        let config = FsConfig::default().with_base_frequency(FrequencyPreset::Feigenbaum);
+        assert!(Mem8Fs::new_with_config(dir.path(), config).is_err());
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        fs.rekey_frequency(FrequencyPreset::Feigenbaum).unwrap();
+        check(&fs);
+        fs.write("/new.bin", data).unwrap();
+        assert_eq!(fs.metadata("/new.bin").unwrap().wave_frequency, frequency::FEIGENBAUM);
+        
+        // Writing over a file starts it afresh
+        fs.write("/low.bin", data).unwrap();
+        let rewritten = fs.metadata("/low.bin").unwrap();
+        assert_eq!((rewritten.wave_frequency, rewritten.metadata), (frequency::FEIGENBAUM, None));
+    }
+    
+    #[test]
+    fn test_paths_normalize_lexically() {
+        let cases: &[(&str, Option<&str>)] = &[
+            ("config.json", Some("/config.json")),
+            ("./config.json", Some("/config.json")),
+            ("/config.json", Some("/config.json")),
+            ("a//b", Some("/a/b")),
+            ("a/./b", Some("/a/b")),
+            ("a/b/", Some("/a/b")),
+            ("a/b/../c", Some("/a/c")),
+            ("/a/b/c/../../d", Some("/a/d")),
+            ("a/..", Some("/")),
+            ("", Some("/")),
+            (".", Some("/")),
+            ("//a///b//", Some("/a/b")),
+            ("Config.JSON", Some("/Config.JSON")),
+            ("caf\u{e9}", Some("/caf\u{e9}")),
+            ("cafe\u{301}", Some("/cafe\u{301}")),
+            ("..", None),
+            ("../../etc/passwd", None),
+            ("/../etc/passwd", None),
+            ("a/../../b", None),
+            ("a/b\0c", None),
+        ];
+        for &(input, expected) in cases {
+            match (normalize_path(Path::new(input)), expected) {
+                (Ok(path), Some(expected)) => assert_eq!(path, Path::new(expected), "{:?}", input),
+                (Err(e), None) => assert_eq!(e.path, input),
+                (got, expected) => panic!("{:?}: got {:?}, expected {:?}", input, got, expected),
+            }
+        }
+        
+        // The same rule for every operation
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        fs.write("config.json", b"{}").unwrap();
+        assert!(fs.exists("./config.json") && fs.exists("/x/../config.json"));
+        assert!(!fs.exists("CONFIG.json"));
+        assert_eq!(fs.read("//config.json").unwrap(), b"{}");
+        fs.write("logs//today/./a.log", b"a").unwrap();
+        assert_eq!(fs.list("logs/today/").unwrap(), vec![PathBuf::from("/logs/today/a.log")]);
+        assert_eq!(fs.read("/logs/today/a.log").unwrap(), b"a");
+        
+        let err = fs.write("../../etc/passwd", b"root").unwrap_err();
+        assert_eq!(err.downcast_ref::<InvalidPath>().unwrap().reason, "climbs out of the root");
+        assert!(!fs.exists("../config.json"));
+        assert!(fs.delete("a/../../config.json").is_err());
+        fs.delete("./logs/../config.json").unwrap();
+        assert!(!fs.exists("config.json"));
+        assert!(!dir.path().parent().unwrap().join("etc").exists());
+    }
+}
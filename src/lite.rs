@@ -5,16 +5,164 @@
 //!
 //! Hue, this is where the magic happens! 973× faster than traditional storage
 //! by converting everything to waves. Trisha says it's like surfing data! 🏄
+//!
+//! The store is append-only: `delete` appends a tombstone instead of touching
+//! old packets, and `compact` rewrites the file without the deleted ones.
+//! `secure_delete` is the one exception - it zeroes a packet's bytes in place.
+//! Storing content that's already live appends nothing (`store_force` does).
+//!
+//! Waves are the API, not the disk format: since format v2 a packet is stored
+//! as its raw bytes plus the frequency, and the waves are regenerated from
+//! those on demand. Format v1 packets (16 bytes of waves per byte of data)
+//! are still read.
+//!
+//! Since format v3 every packet ends in a checksum. A crash mid-write leaves
+//! at most a torn record at the tail: opening the store truncates it back to
+//! the last good packet and reports what happened (`Mem8Lite::recovery`).
+//!
+//! `with_compression` turns on packet compression (format v4). Packets that
+//! don't shrink are written uncompressed, so a store can mix both freely.
+//! `CompressionMode::Adaptive` picks the level per packet and records it
+//! (see `PacketSummary::compression` and `StorageStats::compression_by_class`).
+//!
+//! `new_encrypted` opens an encrypted store (format v5): every packet body is
+//! sealed with XChaCha20-Poly1305 under a fresh random nonce. Signatures stay
+//! over the plaintext, so lookups and dedup work unchanged - which also means
+//! anyone holding the file sees which signatures (and sizes) it has, just not
+//! the data or metadata behind them.
+//!
+//! `set_metadata` appends a small metadata update record (format v6) instead
+//! of the whole packet; the newest one wins, and the older ones stay readable
+//! through `metadata_history` until the next `compact`.
+//!
+//! `store_keyed` names packets with string keys, kept as key records (format
+//! v7) in the store file itself. Re-storing under a key moves the key and
+//! leaves the old packet alone, so `history_for_key` can list it.
+//!
+//! Every store file starts with a 16 byte header: `MEM8`, the file format
+//! version and flags (u16s) and the base frequency it was created with (f64),
+//! all big-endian like the record prefixes. Files from before the header
+//! (`format_version` 0) still open as they are and gain one on `compact`.
+//!
+//! `store_with_ttl` gives a packet a deadline, kept as an expiry record
+//! (format v8) right after it. Past the deadline the packet reads as
+//! `Expired`; `purge_expired` tombstones it and `compact` drops it. "Now"
+//! comes from the store's `Clock` (see `with_clock`).
+//!
+//! `find_by_time` answers from a second sidecar, `<path>.tsidx`, holding each
+//! packet's offset and timestamp. It's only a cache of what the packets say:
+//! opening a store fills in whatever it's missing, and encrypted stores keep
+//! it in memory only. `top_wonder` ranks from a third, `<path>.wonder`,
+//! kept the same way (see `wonder`).
 
 use std::fs::{File, OpenOptions, create_dir_all};
 use std::io::{Write, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
-use std::collections::HashMap;
+use std::ffi::OsString;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::sync::{Arc, Mutex};
+use std::ops::Range;
+use std::time::Duration;
 use num_complex::Complex64;
 use blake3::Hasher;
 use serde::{Serialize, Deserialize};
-use anyhow::{Result, anyhow};
-use byteorder::{BigEndian, WriteBytesExt, ReadBytesExt};
+use anyhow::anyhow;
+use crate::error::{Mem8Error, Result};
+use byteorder::{BigEndian, LittleEndian, WriteBytesExt, ReadBytesExt};
+use crate::capabilities::StoreCapabilities;
+use crate::cipher::{self, AuthenticationFailed};
+use crate::clock::{Clock, SystemClock};
+use crate::compress::{self, CompressionMode, EntropyClass};
+use crate::frequency::FrequencyPreset;
+use crate::metadata_policy::{MetadataPolicy, MetadataRejections};
+use crate::scrub::ScrubReport;
+use crate::wonder::{self, WonderIndex};
+
+/// On-disk schema version for Mem8Lite stores
+pub const LITE_SCHEMA_VERSION: u32 = 8;
+
+/// First bytes of every store file
+pub const FILE_MAGIC: &[u8; 4] = b"MEM8";
+
+/// Version of the store file header this build writes (and the newest it opens)
+pub const FILE_FORMAT_VERSION: u16 = 1;
+
+/// Magic, version, flags and base frequency
+const FILE_HEADER_LEN: u64 = 16;
+
+/// Header flag: packet bodies are sealed (`new_encrypted`)
+const FILE_FLAG_ENCRYPTED: u16 = 1;
+
+/// Codec id for the polar (magnitude = byte value) wave encoding of format v1 packets
+pub const CODEC_POLAR_F64: &str = "polar-f64";
+
+/// Codec id for format v2 packets: raw bytes, waves regenerated from the frequency
+pub const CODEC_RAW_BYTES: &str = "raw-bytes";
+
+/// Codec id for format v4 packets compressed with the old built-in LZ codec (read only)
+pub const CODEC_LZ: &str = "lz";
+
+/// Codec id for format v4 packets compressed with zstd
+pub const CODEC_ZSTD: &str = "zstd";
+
+/// Codec id for format v5 packets sealed with XChaCha20-Poly1305
+pub const CODEC_XCHACHA20_POLY1305: &str = "xchacha20-poly1305";
+
+/// Packet written as bincode `WavePacket` - two f64s per byte (read only)
+pub const PACKET_FORMAT_WAVES: u8 = 1;
+
+/// Packet written as raw bytes plus encoding parameters, no checksum (read only)
+pub const PACKET_FORMAT_COMPACT: u8 = 2;
+
+/// Compact packet followed by a checksum (what `store` writes)
+pub const PACKET_FORMAT_CHECKED: u8 = 3;
+
+/// Checked packet whose body is compressed (what `store` writes when compressing)
+/// 
+/// Payload: signature, mode byte (`compress::MODE_*`), uncompressed body
+/// length (u64 LE), compressed body - the compact packet minus its signature.
+pub const PACKET_FORMAT_COMPRESSED: u8 = 4;
+
+/// Checked or compressed packet whose body is encrypted (what encrypted stores write)
+/// 
+/// Payload: signature, nonce, then the sealed format byte of the inner packet
+/// followed by its body (everything after its signature). The signature is
+/// the associated data, so a ciphertext can't be passed off as another packet.
+pub const PACKET_FORMAT_ENCRYPTED: u8 = 5;
+
+/// Metadata update for a packet stored earlier (what `set_metadata` writes)
+/// 
+/// Payload: signature, timestamp (u64 LE), then a mode byte - 0 followed by
+/// the metadata as is, or 1 followed by a nonce and the sealed metadata
+/// (encrypted stores; signature and timestamp are the associated data) -
+/// and the checksum.
+pub const PACKET_FORMAT_METADATA: u8 = 6;
+
+/// Points a string key at a packet (what `store_keyed` and `delete_key` write)
+/// 
+/// Laid out like format v6 with the key id (see `Mem8Lite::key_id`) in place
+/// of the signature; the body is the signature the key now points at (all
+/// zeros once it's deleted) followed by the key itself.
+pub const PACKET_FORMAT_KEY: u8 = 7;
+
+/// Gives the packet stored just before it a deadline (what `store_with_ttl` writes)
+/// 
+/// Laid out like format v6; the body is the deadline (unix seconds, u64 LE).
+/// It only applies to the copy of the packet it follows - storing the packet
+/// again after it expired writes a fresh copy without one.
+pub const PACKET_FORMAT_EXPIRY: u8 = 8;
+
+/// Context string separating key ids from packet signatures
+const KEY_ID_CONTEXT: &str = "mem8-lite 2024 named key";
+
+/// Reserved key prefix for memory group index packets (see `store_linked`)
+pub(crate) const GROUP_KEY_PREFIX: &str = "\u{0}memory_group/";
+
+/// Signature, mode byte and body length ahead of a compressed body
+const COMPRESSED_HEADER_LEN: usize = 32 + 1 + 8;
+
+/// Bytes of blake3 checksum at the end of a format v3 payload (counted in its length)
+const CHECKSUM_LEN: u64 = 8;
 
 /// Serde helper for Complex64 serialization
 mod complex_serde {
@@ -47,13 +195,20 @@ pub struct WavePacket {
     /// Blake3 signature - the wave's unique identity
     pub signature: [u8; 32],
     
-    /// The actual wave-encoded data (stored as pairs of f64)
+    /// The actual wave-encoded data (pairs of f64 in format v1 stores;
+    /// regenerated from the stored bytes since v2)
     #[serde(with = "complex_serde")]
     pub waves: Vec<Complex64>,
     
     /// Optional metadata (for that sense of wonder!)
     pub metadata: Option<Vec<u8>>,
     
+    /// When the packet expires (unix seconds), if it was stored with a TTL
+    /// 
+    /// Kept in its own expiry record, never in the packet itself.
+    #[serde(skip)]
+    pub expires_at: Option<u64>,
+    
     /// Base frequency used for encoding
     pub frequency: f64,
     
@@ -61,6 +216,44 @@ pub struct WavePacket {
     pub timestamp: u64,
 }
 
+/// What a packet looks like on disk since format v2
+/// 
+/// Same field order as `WavePacket`, so bincode lays both out alike - only
+/// the payload differs (one byte per byte instead of a pair of f64s).
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct StoredPacket {
+    pub(crate) signature: [u8; 32],
+    pub(crate) data: Vec<u8>,
+    pub(crate) metadata: Option<Vec<u8>>,
+    pub(crate) frequency: f64,
+    pub(crate) timestamp: u64,
+}
+
+impl StoredPacket {
+    /// Recover the bytes from a format v1 packet
+    fn from_waves(packet: WavePacket) -> Self {
+        Self {
+            signature: packet.signature,
+            data: decode_from_waves(&packet.waves, packet.frequency),
+            metadata: packet.metadata,
+            frequency: packet.frequency,
+            timestamp: packet.timestamp,
+        }
+    }
+    
+    /// Regenerate the waves for the API
+    fn into_wave_packet(self) -> WavePacket {
+        WavePacket {
+            signature: self.signature,
+            waves: encode_to_waves(&self.data, self.frequency),
+            metadata: self.metadata,
+            expires_at: None,
+            frequency: self.frequency,
+            timestamp: self.timestamp,
+        }
+    }
+}
+
 /// Simple key-value storage with wave-based backend
 /// 
 /// Hue, this is the simplified interface when you don't need full filesystem
@@ -72,14 +265,462 @@ pub struct Mem8Lite {
     /// Base frequency for wave encoding (1.618 = golden ratio!)
     frequency: f64,
     
-    /// In-memory cache of wave packets
-    cache: HashMap<[u8; 32], WavePacket>,
+    /// From the file header - 0 for files written before there was one
+    format_version: u16,
+    
+    /// Bounded in-memory LRU cache of (compact) packets
+    cache: Mutex<PacketCache>,
+    
+    /// Record offsets whose checksum a range read has already verified
+    verified: Mutex<HashSet<u64>>,
+    
+    /// How packets are read back
+    read_mode: ReadMode,
+    
+    /// Recompute signatures on every read from disk (`with_strict_reads`)
+    strict_reads: bool,
+    
+    /// What metadata writes must satisfy (`with_metadata_policy`)
+    metadata_policy: MetadataPolicy,
+    
+    /// Writes the metadata policy turned away
+    rejections: Mutex<MetadataRejections>,
+    
+    /// Read-only mapping of the store file (`ReadMode::Mmap`, made on first read)
+    #[cfg(feature = "mmap")]
+    mapping: Mutex<Option<Arc<memmap2::Mmap>>>,
+    
+    /// Where each packet lives in the storage file
+    index: HashMap<[u8; 32], PacketLocation>,
+    
+    /// Signatures deleted since the last compaction
+    tombstones: HashSet<[u8; 32]>,
+    
+    /// Metadata update records per signature, oldest first
+    metadata_updates: HashMap<[u8; 32], Vec<PacketLocation>>,
+    
+    /// Key records (key id, location) in file order
+    key_records: Vec<([u8; 32], PacketLocation)>,
+    
+    /// Expiry records per signature, oldest first
+    expiry_records: HashMap<[u8; 32], Vec<PacketLocation>>,
+    
+    /// Newest expiry record per signature, and its deadline
+    deadlines: HashMap<[u8; 32], (PacketLocation, u64)>,
+    
+    /// Where timestamps and deadlines come from
+    clock: Arc<dyn Clock>,
+    
+    /// What each key pointed at over time, oldest first (`None` = deleted)
+    named: BTreeMap<String, Vec<Option<[u8; 32]>>>,
+    
+    /// When each packet's indexed copy was stored: (payload offset, timestamp)
+    timestamps: HashMap<[u8; 32], (u64, u64)>,
+    
+    /// The same, ordered by timestamp (for `find_by_time`)
+    by_time: BTreeSet<(u64, [u8; 32])>,
+    
+    /// Append-only sidecar timestamp index (`<path>.tsidx`; none for encrypted stores)
+    timestamp_file: Option<File>,
+    
+    /// Peak Marine salience per packet, for `top_wonder` (`<path>.wonder`, the same way)
+    wonder: WonderIndex,
     
     /// The backing storage file
     file: File,
     
+    /// Append-only sidecar index (`<path>.idx`)
+    index_file: File,
+    
     /// Current file position for appending
     position: u64,
+    
+    /// What opening the store had to repair
+    recovery: RecoveryReport,
+    
+    /// Appends skipped because the content was already stored
+    dedup: DedupStats,
+    
+    /// How new packets get compressed
+    compression: CompressionMode,
+    
+    /// Key sealing packet bodies (`new_encrypted`)
+    key: Option<[u8; cipher::KEY_LEN]>,
+    
+    /// When writes get fsynced
+    flush_policy: FlushPolicy,
+    
+    /// Writes since the last fsync
+    unflushed: usize,
+    
+    /// Set once `close` has flushed everything (so Drop has nothing to do)
+    closed: bool,
+}
+
+/// One packet as the retention engine sees it: (signature, metadata, stored at, expired)
+pub(crate) type RetentionCandidate = ([u8; 32], Option<Vec<u8>>, Option<u64>, bool);
+
+/// Location of a packet's bincode payload inside the storage file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct PacketLocation {
+    /// Offset of the payload (just past its length prefix)
+    offset: u64,
+    
+    /// Payload length in bytes
+    len: u64,
+}
+
+/// Size of one sidecar index record: signature + offset + length
+const INDEX_RECORD_SIZE: u64 = 32 + 8 + 8;
+
+/// Default cache budget for `Mem8Lite::new` (64 MiB of decoded packets)
+pub const DEFAULT_CACHE_BYTES: usize = 64 << 20;
+
+/// Bytes read per chunk by `store_stream` (and decoded per chunk by `retrieve_to`)
+pub const STREAM_CHUNK_SIZE: usize = 1 << 20;
+
+/// Payloads bigger than this are compressed from samples by `estimate_stored_size`
+pub const ESTIMATE_SAMPLE_BYTES: usize = 1 << 20;
+
+/// How many evenly spread windows make up an estimate's sample
+const ESTIMATE_SAMPLE_WINDOWS: usize = 16;
+
+/// High bit of a record's length prefix marks a tombstone (payload = signature)
+const TOMBSTONE_FLAG: u64 = 1 << 63;
+
+/// The packet format lives in bits 56-62 of the length prefix
+/// 
+/// v1 stores predate it and leave those bits zero.
+const FORMAT_SHIFT: u32 = 56;
+
+/// The payload length part of a length prefix
+const LEN_MASK: u64 = (1 << FORMAT_SHIFT) - 1;
+
+/// `SignatureMismatch` unless the packet hashes to its own signature
+fn check_signature(packet: &StoredPacket) -> Result<()> {
+    if content_signature(&packet.data, packet.metadata.as_deref()) != packet.signature {
+        return Err(Mem8Error::from(SignatureMismatch { signature: packet.signature }));
+    }
+    Ok(())
+}
+
+/// Length prefix for a packet payload of `len` bytes in `format`
+fn packet_prefix(format: u8, len: u64) -> u64 {
+    ((format as u64) << FORMAT_SHIFT) | len
+}
+
+/// Checksum of a format v3 payload (everything but the checksum itself)
+/// 
+/// Covers the body first and the signature and data length after it, since
+/// `store_stream` only knows those once the data has gone by; the length
+/// prefix comes last so a mangled length is caught too.
+fn record_checksum(prefix: u64, payload: &[u8]) -> [u8; 8] {
+    let mut hasher = Hasher::new();
+    hasher.update(&payload[40..]);
+    hasher.update(&payload[..40]);
+    hasher.update(&prefix.to_be_bytes());
+    finish_checksum(hasher)
+}
+
+fn finish_checksum(hasher: Hasher) -> [u8; 8] {
+    let mut checksum = [0u8; 8];
+    checksum.copy_from_slice(&hasher.finalize().as_bytes()[..8]);
+    checksum
+}
+
+/// Signature of a packet: blake3 over its data, then its metadata
+pub(crate) fn content_signature(data: &[u8], metadata: Option<&[u8]>) -> [u8; 32] {
+    let mut hasher = Hasher::new();
+    hasher.update(data);
+    if let Some(meta) = metadata {
+        hasher.update(meta);
+    }
+    hasher.finalize().into()
+}
+
+/// Length recorded in the sidecar index for a tombstone
+const TOMBSTONE_INDEX_LEN: u64 = u64::MAX;
+
+/// Set in the sidecar index length of a metadata update record
+const METADATA_INDEX_FLAG: u64 = 1 << 62;
+
+/// Set in the sidecar index length of a key record
+const KEY_INDEX_FLAG: u64 = 1 << 61;
+
+/// Set in the sidecar index length of an expiry record
+const EXPIRY_INDEX_FLAG: u64 = 1 << 60;
+
+/// Path of the sidecar offset index for a store file
+fn index_path(path: &Path) -> PathBuf {
+    let mut index: OsString = path.as_os_str().to_owned();
+    index.push(".idx");
+    PathBuf::from(index)
+}
+
+/// Path of the sidecar timestamp index for a store file (`<path>.tsidx`)
+fn timestamp_index_path(path: &Path) -> PathBuf {
+    let mut index: OsString = path.as_os_str().to_owned();
+    index.push(".tsidx");
+    PathBuf::from(index)
+}
+
+/// Size of one timestamp index record: signature + packet offset + timestamp
+const TIMESTAMP_RECORD_SIZE: usize = 32 + 8 + 8;
+
+/// Path of the sidecar wonder index for a store file (`<path>.wonder`)
+fn wonder_index_path(path: &Path) -> PathBuf {
+    let mut index: OsString = path.as_os_str().to_owned();
+    index.push(".wonder");
+    PathBuf::from(index)
+}
+
+/// Sibling path used while compacting (`<path>.compact`)
+fn compact_path(path: &Path) -> PathBuf {
+    let mut temp: OsString = path.as_os_str().to_owned();
+    temp.push(".compact");
+    PathBuf::from(temp)
+}
+
+/// Resume cursor of an unfinished metadata backfill (`<path>.backfill`)
+pub(crate) fn backfill_cursor_path(path: &Path) -> PathBuf {
+    let mut cursor: OsString = path.as_os_str().to_owned();
+    cursor.push(".backfill");
+    PathBuf::from(cursor)
+}
+
+/// Proof of the key an encrypted store was created with (`<path>.key`)
+/// 
+/// Holds a keyed hash of a fixed string, not the key, so a wrong key is
+/// caught on open instead of on the first read.
+fn key_check_path(path: &Path) -> PathBuf {
+    let mut check: OsString = path.as_os_str().to_owned();
+    check.push(".key");
+    PathBuf::from(check)
+}
+
+/// What `<path>.key` holds for `key`
+fn key_check(key: &[u8; cipher::KEY_LEN]) -> [u8; 32] {
+    blake3::keyed_hash(key, b"mem8-lite key check v1").into()
+}
+
+/// Audit log of secure deletes (`<path>.scrub.log`)
+fn scrub_log_path(path: &Path) -> PathBuf {
+    let mut log: OsString = path.as_os_str().to_owned();
+    log.push(".scrub.log");
+    PathBuf::from(log)
+}
+
+/// A store encoded but not yet written (see `Mem8Lite::prepare_store`)
+pub(crate) enum PreparedStore {
+    /// Already live - nothing to write, `record_len` bytes saved
+    Duplicate { signature: [u8; 32], record_len: u64 },
+    
+    /// A full record ready to append
+    Packet { packet: StoredPacket, record: Vec<u8>, len: u64 },
+}
+
+/// Least-recently-used packet cache with a byte budget
+struct PacketCache {
+    /// Budget for the packets held, in (approximate) bytes
+    max_bytes: usize,
+    
+    /// Bytes currently held
+    used_bytes: usize,
+    
+    /// Cached packets with their size and last-use tick
+    entries: HashMap<[u8; 32], (StoredPacket, usize, u64)>,
+    
+    /// Last-use tick → signature, oldest first
+    recency: BTreeMap<u64, [u8; 32]>,
+    
+    /// Bumped on every access
+    tick: u64,
+    
+    hits: usize,
+    misses: usize,
+}
+
+impl PacketCache {
+    fn new(max_bytes: usize) -> Self {
+        Self {
+            max_bytes,
+            used_bytes: 0,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            hits: 0,
+            misses: 0,
+        }
+    }
+    
+    /// Rough in-memory footprint of a packet
+    fn packet_size(packet: &StoredPacket) -> usize {
+        std::mem::size_of::<StoredPacket>()
+            + packet.data.len()
+            + packet.metadata.as_ref().map_or(0, |m| m.len())
+    }
+    
+    /// Look a packet up, counting the hit or miss and marking it recently used
+    fn get(&mut self, signature: &[u8; 32]) -> Option<&StoredPacket> {
+        self.tick += 1;
+        match self.entries.get_mut(signature) {
+            Some(entry) => {
+                self.hits += 1;
+                self.recency.remove(&entry.2);
+                self.recency.insert(self.tick, *signature);
+                entry.2 = self.tick;
+                Some(&entry.0)
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+    
+    /// Cache a packet, evicting the least recently used ones to make room
+    /// 
+    /// Packets bigger than the whole budget are simply not cached.
+    fn insert(&mut self, signature: [u8; 32], packet: StoredPacket) {
+        self.remove(&signature);
+        let size = Self::packet_size(&packet);
+        if size > self.max_bytes {
+            return;
+        }
+        while self.used_bytes + size > self.max_bytes {
+            match self.recency.keys().next().copied() {
+                Some(oldest) => {
+                    let evicted = self.recency.remove(&oldest).unwrap();
+                    self.remove(&evicted);
+                }
+                None => break,
+            }
+        }
+        
+        self.tick += 1;
+        self.used_bytes += size;
+        self.recency.insert(self.tick, signature);
+        self.entries.insert(signature, (packet, size, self.tick));
+    }
+    
+    fn remove(&mut self, signature: &[u8; 32]) {
+        if let Some((_, size, tick)) = self.entries.remove(signature) {
+            self.used_bytes -= size;
+            self.recency.remove(&tick);
+        }
+    }
+    
+    fn contains(&self, signature: &[u8; 32]) -> bool {
+        self.entries.contains_key(signature)
+    }
+    
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    /// Would caching `size` more bytes evict anything?
+    fn has_room_for(&self, size: usize) -> bool {
+        self.used_bytes + size <= self.max_bytes
+    }
+}
+
+/// Header of one record in the store file
+struct RecordHeader {
+    /// Is this a tombstone rather than a packet?
+    tombstone: bool,
+    
+    /// Payload length (without the length prefix)
+    len: u64,
+    
+    /// Packet format (`PACKET_FORMAT_*`)
+    format: u8,
+    
+    /// Signature at the start of the payload
+    signature: [u8; 32],
+}
+
+/// Outcome of checking one record during recovery
+enum RecordCheck {
+    /// Whole, and matches its checksum (if its format has one)
+    Valid(RecordHeader),
+    
+    /// Framed correctly but the bytes are wrong - skip over it
+    Corrupt(RecordHeader),
+    
+    /// Runs past the end of the file (or the header itself is cut short)
+    Torn,
+}
+
+/// Make a rename inside `path`'s directory durable
+#[cfg(unix)]
+pub(crate) fn sync_parent_dir(path: &Path) -> Result<()> {
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        File::open(parent)?.sync_all()?;
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub(crate) fn sync_parent_dir(_path: &Path) -> Result<()> {
+    Ok(())
+}
+
+/// A cursor over the store file that doesn't share the file's own position
+/// 
+/// Reads are positional (`pread`), so readers on different threads never
+/// move each other's - or the writer's - place in the file. That's what lets
+/// `SharedMem8Lite` serve reads concurrently through `&Mem8Lite`.
+struct FileAt<'a> {
+    file: &'a File,
+    pos: u64,
+}
+
+impl Read for FileAt<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        #[cfg(unix)]
+        let n = std::os::unix::fs::FileExt::read_at(self.file, buf, self.pos)?;
+        #[cfg(windows)]
+        let n = std::os::windows::fs::FileExt::seek_read(self.file, buf, self.pos)?;
+        self.pos += n as u64;
+        Ok(n)
+    }
+}
+
+impl Seek for FileAt<'_> {
+    fn seek(&mut self, to: SeekFrom) -> std::io::Result<u64> {
+        let pos = match to {
+            SeekFrom::Start(pos) => Some(pos),
+            SeekFrom::Current(delta) => self.pos.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.file.metadata()?.len().checked_add_signed(delta),
+        };
+        self.pos = pos.ok_or_else(|| std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            "seek before the start of the store file",
+        ))?;
+        Ok(self.pos)
+    }
+}
+
+/// Fill `buf` from `reader`, stopping early only at end of input
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+    Ok(filled)
+}
+
+/// Parse a hex packet signature (as printed by the CLI and MCP tools)
+pub fn parse_signature(hex_signature: &str) -> Result<[u8; 32]> {
+    let bytes = hex::decode(hex_signature.trim())
+        .map_err(|e| anyhow!("Invalid signature {}: {}", hex_signature, e))?;
+    bytes.as_slice().try_into()
+        .map_err(|_| anyhow!("Signature must be 32 bytes, got {}", bytes.len()).into())
 }
 
 impl Mem8Lite {
@@ -87,14 +728,52 @@ impl Mem8Lite {
     /// 
     /// # Arguments
     /// * `path` - Path to the storage file (will be created if needed)
-    /// * `frequency` - Base frequency for wave encoding (1.618 is golden!)
+    /// * `frequency` - Base frequency for wave encoding - a `FrequencyPreset`
+    ///   or plain Hz (1.618 is golden!)
     /// 
     /// # Example
     /// ```
     /// let storage = Mem8Lite::new("/tmp/my_waves.m8", 1.618)?;
     /// ```
-    pub fn new<P: AsRef<Path>>(path: P, frequency: f64) -> Result<Self> {
-        let path = path.as_ref().to_path_buf();
+    pub fn new<P: AsRef<Path>>(path: P, frequency: impl Into<FrequencyPreset>) -> Result<Self> {
+        Self::with_cache_limit(path, frequency, DEFAULT_CACHE_BYTES)
+    }
+    
+    /// Open a store whose packet cache holds at most `max_bytes`
+    /// 
+    /// Opening only loads the signature → offset index; packets are read from
+    /// disk on demand and the least recently used ones are evicted once the
+    /// cache is full. A limit of 0 disables caching entirely.
+    pub fn with_cache_limit<P: AsRef<Path>>(
+        path: P,
+        frequency: impl Into<FrequencyPreset>,
+        max_bytes: usize,
+    ) -> Result<Self> {
+        Self::open(path.as_ref(), frequency.into(), max_bytes, None)
+    }
+    
+    /// Open (or create) an encrypted store
+    /// 
+    /// Packet data and metadata are sealed with XChaCha20-Poly1305 under `key`;
+    /// signatures are still the blake3 hash of the plaintext. Opening with a
+    /// different key than the store was created with fails right away, and so
+    /// does opening an encrypted store with `new`.
+    pub fn new_encrypted<P: AsRef<Path>>(
+        path: P,
+        frequency: impl Into<FrequencyPreset>,
+        key: &[u8; cipher::KEY_LEN],
+    ) -> Result<Self> {
+        Self::open(path.as_ref(), frequency.into(), DEFAULT_CACHE_BYTES, Some(*key))
+    }
+    
+    fn open(
+        path: &Path,
+        frequency: FrequencyPreset,
+        max_bytes: usize,
+        key: Option<[u8; cipher::KEY_LEN]>,
+    ) -> Result<Self> {
+        let path = path.to_path_buf();
+        let frequency = frequency.hz();
         
         // Create parent directories if needed
         if let Some(parent) = path.parent() {
@@ -109,254 +788,4368 @@ impl Mem8Lite {
             .open(&path)?;
         
         // Get current position (for appending)
-        let position = file.seek(SeekFrom::End(0))?;
+        let mut position = file.seek(SeekFrom::End(0))?;
+        
+        // Refuse files that aren't stores before the sidecars get created
+        let mut torn_header = 0;
+        let format_version = match read_file_header(&mut file, position)? {
+            FileStart::Header(header) => {
+                if header.flags & FILE_FLAG_ENCRYPTED != 0 && key.is_none() {
+                    return Err(anyhow!(
+                        "{} is encrypted - open it with Mem8Lite::new_encrypted",
+                        path.display()
+                    ).into());
+                }
+                if header.frequency != frequency {
+                    eprintln!(
+                        "⚠️ mem8: {} was written at {} Hz but opened at {} Hz - packets decode at their own frequency, new ones use {} Hz (see Mem8Lite::reencode)",
+                        path.display(), header.frequency, frequency, frequency
+                    );
+                }
+                header.version
+            }
+            FileStart::Empty { torn } => {
+                torn_header = torn;
+                let flags = if key.is_some() { FILE_FLAG_ENCRYPTED } else { 0 };
+                write_file_header(&mut file, flags, frequency)?;
+                file.sync_all()?;
+                position = FILE_HEADER_LEN;
+                FILE_FORMAT_VERSION
+            }
+            FileStart::Headerless => 0,
+        };
+        
+        let index_file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(index_path(&path))?;
         
-        // Initialize with empty cache
+        // Timestamps are sealed inside encrypted packets, so they aren't written out in the clear
+        let timestamp_file = match key {
+            Some(_) => None,
+            None => Some(OpenOptions::new()
+                .create(true)
+                .read(true)
+                .append(true)
+                .open(timestamp_index_path(&path))?),
+        };
+        let wonder = WonderIndex::open(key.is_none().then(|| wonder_index_path(&path)).as_deref())?;
+        
+        // Initialize with empty cache - packets are read from disk on demand
         let mut storage = Self {
             path,
             frequency,
-            cache: HashMap::new(),
+            format_version,
+            cache: Mutex::new(PacketCache::new(max_bytes)),
+            verified: Mutex::new(HashSet::new()),
+            read_mode: ReadMode::default(),
+            strict_reads: false,
+            metadata_policy: MetadataPolicy::default(),
+            rejections: Mutex::new(MetadataRejections::default()),
+            #[cfg(feature = "mmap")]
+            mapping: Mutex::new(None),
+            index: HashMap::new(),
+            tombstones: HashSet::new(),
+            metadata_updates: HashMap::new(),
+            key_records: Vec::new(),
+            expiry_records: HashMap::new(),
+            deadlines: HashMap::new(),
+            clock: Arc::new(SystemClock),
+            named: BTreeMap::new(),
+            timestamps: HashMap::new(),
+            by_time: BTreeSet::new(),
+            timestamp_file,
+            wonder,
             file,
+            index_file,
             position,
+            recovery: RecoveryReport { truncated_bytes: torn_header, ..RecoveryReport::default() },
+            dedup: DedupStats::default(),
+            compression: CompressionMode::None,
+            key,
+            flush_policy: FlushPolicy::default(),
+            unflushed: 0,
+            closed: false,
         };
         
-        // Load existing data into cache
-        storage.load_cache()?;
+        // Load the offset index (rebuilding whatever is missing from it)
+        storage.load_index()?;
+        storage.check_key()?;
+        storage.load_keys();
+        storage.load_expiries();
+        storage.load_timestamps()?;
+        storage.load_wonder()?;
+        
+        // Finish a transaction that was committed but not applied
+        storage.recovery.replayed_ops = crate::txn::recover(&mut storage)?;
         
         Ok(storage)
     }
     
-    /// Store data and get back a wave signature
-    /// 
-    /// This is where we convert boring bytes into exciting waves!
-    /// Trisha calls this "making data dance" 💃
-    pub fn store(&mut self, data: &[u8], metadata: Option<Vec<u8>>) -> Result<[u8; 32]> {
-        // Convert data to waves
-        let waves = self.encode_to_waves(data);
-        
-        // Calculate signature
-        let mut hasher = Hasher::new();
-        hasher.update(data);
-        if let Some(ref meta) = metadata {
-            hasher.update(meta);
-        }
-        let signature = hasher.finalize().into();
-        
-        // Create wave packet
-        let packet = WavePacket {
-            signature,
-            waves,
-            metadata,
-            frequency: self.frequency,
-            timestamp: std::time::SystemTime::now()
-                .duration_since(std::time::UNIX_EPOCH)?
-                .as_secs(),
+    /// Make sure this store is opened with the key it was encrypted with
+    fn check_key(&self) -> Result<()> {
+        let check_path = key_check_path(&self.path);
+        let stored = match std::fs::read(&check_path) {
+            Ok(stored) => Some(stored),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e.into()),
         };
+        let wrong_key = || Mem8Error::Other(anyhow::Error::new(AuthenticationFailed)
+            .context(format!("Wrong key for encrypted store {}", self.path.display())));
         
-        // Write to storage
-        self.persist_packet(&packet)?;
-        
-        // Cache it
-        self.cache.insert(signature, packet);
-        
-        Ok(signature)
+        match (&self.key, stored) {
+            (None, None) => Ok(()),
+            (None, Some(_)) => Err(anyhow!(
+                "{} is encrypted - open it with Mem8Lite::new_encrypted",
+                self.path.display()
+            ).into()),
+            (Some(key), Some(stored)) if stored == key_check(key) => Ok(()),
+            (Some(_), Some(_)) => Err(wrong_key()),
+            (Some(key), None) => {
+                // New store (or a lost key check): the newest sealed packet must open
+                let newest_sealed = self.locations_in_file_order().into_iter().rev()
+                    .find(|(_, loc)| matches!(
+                        self.read_record_header(loc.offset - 8),
+                        Ok(header) if header.format == PACKET_FORMAT_ENCRYPTED
+                    ));
+                if let Some((signature, _)) = newest_sealed {
+                    if let Err(e) = self.read_packet(&signature) {
+                        if e.downcast_ref::<AuthenticationFailed>().is_some() {
+                            return Err(wrong_key());
+                        }
+                    }
+                }
+                std::fs::write(&check_path, key_check(key))?;
+                Ok(())
+            }
+        }
     }
     
-    /// Store a string and get back a wave signature
-    pub fn store_string(&mut self, text: &str) -> Result<[u8; 32]> {
-        self.store(text.as_bytes(), None)
+    /// Compress packets written from now on
+    /// 
+    /// Only affects new writes (including `update_metadata`); packets already
+    /// in the store stay as they are and keep reading fine either way.
+    pub fn with_compression(mut self, mode: CompressionMode) -> Self {
+        self.compression = mode;
+        self
     }
     
-    /// Retrieve data by its wave signature
-    /// 
-    /// The waves remember everything perfectly - no lossy compression here!
-    pub fn retrieve(&self, signature: &[u8; 32]) -> Result<Vec<u8>> {
-        // Check cache first
-        if let Some(packet) = self.cache.get(signature) {
-            return self.decode_from_waves(&packet.waves);
-        }
-        
-        // Not in cache, need to search the file
-        // (In production, we'd have an index for this)
-        Err(anyhow!("Wave signature not found in cache"))
+    /// `with_compression` for a store that's already shared (see `SharedMem8Lite::write`)
+    pub fn set_compression(&mut self, mode: CompressionMode) {
+        self.compression = mode;
     }
     
-    /// Retrieve a string by its wave signature
-    pub fn retrieve_string(&self, signature: &[u8; 32]) -> Result<String> {
-        let data = self.retrieve(signature)?;
-        Ok(String::from_utf8(data)?)
+    /// How this store compresses new packets
+    pub fn compression(&self) -> CompressionMode {
+        self.compression
     }
     
-    /// Get metadata for a stored item
-    pub fn get_metadata(&self, signature: &[u8; 32]) -> Option<Vec<u8>> {
-        self.cache.get(signature)
-            .and_then(|packet| packet.metadata.clone())
+    /// Take timestamps and TTL deadlines from `clock` instead of the system clock
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    /// `with_clock` for a store that's already shared
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
     }
     
-    /// Convert boring bytes into exciting waves! 🌊
+    /// Read packets through buffered file reads or a memory mapping
     /// 
-    /// Each byte becomes a complex number with frequency and phase.
-    /// The interference patterns create natural compression!
-    fn encode_to_waves(&self, data: &[u8]) -> Vec<Complex64> {
-        data.iter().enumerate().map(|(i, &byte)| {
-            // Create a wave for each byte
-            // Frequency encodes the value, phase encodes position
-            let frequency = self.frequency * (byte as f64 / 255.0);
-            let phase = 2.0 * std::f64::consts::PI * (i as f64) / (data.len() as f64);
-            
-            Complex64::from_polar(frequency, phase)
-        }).collect()
+    /// Opening always reads buffered; the mapping is made on the first read
+    /// afterwards and redone whenever appends have grown the file past it.
+    pub fn with_read_mode(mut self, mode: ReadMode) -> Self {
+        self.read_mode = mode;
+        self
     }
     
-    /// Convert waves back to bytes
+    /// How this store reads packets
+    pub fn read_mode(&self) -> ReadMode {
+        self.read_mode
+    }
+    
+    /// Check every packet read from disk against its signature
     /// 
-    /// The waves remember everything - perfect reconstruction!
-    fn decode_from_waves(&self, waves: &[Complex64]) -> Result<Vec<u8>> {
-        Ok(waves.iter().map(|wave| {
-            // Extract byte value from frequency component
-            let normalized = wave.norm() / self.frequency;
-            (normalized * 255.0).round() as u8
-        }).collect())
+    /// Checksums catch torn and bit-flipped records; a strict read also
+    /// recomputes the blake3 signature over the data and metadata, so a
+    /// record rewritten along with a matching checksum fails too, with
+    /// `SignatureMismatch`. `retrieve_to` and `retrieve_range` decode the
+    /// whole packet in strict mode.
+    pub fn with_strict_reads(mut self, strict: bool) -> Self {
+        self.strict_reads = strict;
+        self
     }
     
-    /// Write a wave packet to storage
-    fn persist_packet(&mut self, packet: &WavePacket) -> Result<()> {
-        // Serialize the packet
-        let encoded = bincode::serialize(packet)?;
-        
-        // Write length prefix
-        self.file.write_u64::<BigEndian>(encoded.len() as u64)?;
-        
-        // Write the packet
-        self.file.write_all(&encoded)?;
-        
-        // Flush to ensure it's written
-        self.file.flush()?;
+    pub fn strict_reads(&self) -> bool {
+        self.strict_reads
+    }
+    
+    /// Check metadata against `policy` on every write from now on
+    /// 
+    /// Covers `store` and its variants, `store_batch`, `store_stream`,
+    /// `set_metadata`, `update_metadata` and transactions; metadata already in
+    /// the store isn't rechecked. A rejected write fails with
+    /// `MetadataRejected` and writes nothing.
+    pub fn with_metadata_policy(mut self, policy: MetadataPolicy) -> Self {
+        self.metadata_policy = policy;
+        self
+    }
+    
+    /// `with_metadata_policy` for a store that's already shared
+    pub fn set_metadata_policy(&mut self, policy: MetadataPolicy) {
+        self.metadata_policy = policy;
+    }
+    
+    pub fn metadata_policy(&self) -> &MetadataPolicy {
+        &self.metadata_policy
+    }
+    
+    /// `MetadataRejected` (counted in `stats`) unless the policy accepts `metadata`
+    pub(crate) fn check_metadata(&self, metadata: Option<&[u8]>) -> Result<()> {
+        let Some(metadata) = metadata else {
+            return Ok(());
+        };
+        self.metadata_policy.check(metadata).map_err(|rejection| {
+            self.rejections.lock().unwrap().count(&rejection);
+            rejection.into()
+        })
+    }
+    
+    /// Choose when writes get fsynced (see `FlushPolicy`)
+    pub fn set_flush_policy(&mut self, policy: FlushPolicy) {
+        self.flush_policy = policy;
+    }
+    
+    pub fn flush_policy(&self) -> FlushPolicy {
+        self.flush_policy
+    }
+    
+    /// Store data and get back a wave signature
+    /// 
+    /// This is where we convert boring bytes into exciting waves!
+    /// Trisha calls this "making data dance" 💃
+    /// 
+    /// The signature covers data and metadata, so storing the same pair again
+    /// appends nothing and just returns the signature (see `dedup_stats`).
+    pub fn store(&mut self, data: &[u8], metadata: Option<Vec<u8>>) -> Result<[u8; 32]> {
+        let prepared = self.prepare_store(data, metadata, true)?;
+        self.commit_store(prepared)
+    }
+    
+    /// Store data even if an identical packet is already live
+    /// 
+    /// The new copy is appended and becomes the one the index points at.
+    pub fn store_force(&mut self, data: &[u8], metadata: Option<Vec<u8>>) -> Result<[u8; 32]> {
+        let prepared = self.prepare_store(data, metadata, false)?;
+        self.commit_store(prepared)
+    }
+    
+    /// Store data that expires `ttl` from now
+    /// 
+    /// Once the deadline passes, reads fail with `Expired` and the packet
+    /// drops out of `contains` and `iter`; `purge_expired` tombstones it.
+    /// Storing content that's already live just gives it the new deadline.
+    pub fn store_with_ttl(&mut self, data: &[u8], metadata: Option<Vec<u8>>, ttl: Duration) -> Result<[u8; 32]> {
+        let deadline = self.clock.now().saturating_add(ttl.as_secs());
+        let signature = self.store(data, metadata)?;
+        self.set_deadline(&signature, deadline)?;
+        Ok(signature)
+    }
+    
+    /// Give the live copy of a packet a deadline (unix seconds)
+    pub(crate) fn set_deadline(&mut self, signature: &[u8; 32], deadline: u64) -> Result<()> {
+        let location = self.append_side_record(PACKET_FORMAT_EXPIRY, signature, &deadline.to_le_bytes(), EXPIRY_INDEX_FLAG)?;
+        self.expiry_records.entry(*signature).or_default().push(location);
+        self.deadlines.insert(*signature, (location, deadline));
+        self.wrote(1)
+    }
+    
+    /// When a live packet expires (unix seconds), if it was stored with a TTL
+    pub fn expires_at(&self, signature: &[u8; 32]) -> Option<u64> {
+        let location = self.index.get(signature)?;
+        let &(record, deadline) = self.deadlines.get(signature)?;
+        (record.offset > location.offset).then_some(deadline)
+    }
+    
+    /// Has this packet's deadline passed?
+    pub fn is_expired(&self, signature: &[u8; 32]) -> bool {
+        self.expires_at(signature).is_some_and(|deadline| deadline <= self.clock.now())
+    }
+    
+    /// Tombstone every packet whose deadline has passed, returning how many
+    /// 
+    /// Like `delete`, this only appends; the bytes go at the next `compact`.
+    /// It's `RetentionEngine::purge_expired` run as of the store's clock.
+    pub fn purge_expired(&mut self) -> Result<usize> {
+        let now = self.clock.now();
+        Ok(crate::retention::RetentionEngine::purge_expired().run(self, now)?.removed())
+    }
+    
+    /// `Expired` if this packet's deadline has passed
+    fn check_expiry(&self, signature: &[u8; 32]) -> Result<()> {
+        match self.expires_at(signature) {
+            Some(expired_at) if expired_at <= self.clock.now() => {
+                Err(Mem8Error::from(Expired { signature: *signature, expired_at }))
+            }
+            _ => Ok(()),
+        }
+    }
+    
+    /// Everything `store` does short of writing: dedup check, encoding, sealing
+    /// 
+    /// Only needs `&self`, so `SharedMem8Lite` runs it while readers carry on.
+    /// The result is only valid until something else writes to the store.
+    pub(crate) fn prepare_store(&self, data: &[u8], metadata: Option<Vec<u8>>, dedup: bool) -> Result<PreparedStore> {
+        self.check_metadata(metadata.as_deref())?;
+        let signature = content_signature(data, metadata.as_deref());
+        let metadata_len = metadata.as_ref().map_or(0, |m| m.len() as u64);
+        let live = self.live_copy(&signature, data.len() as u64, metadata_len)?;
+        if let (true, Some(record_len)) = (dedup, live) {
+            return Ok(PreparedStore::Duplicate { signature, record_len });
+        }
+        
+        // Create the packet - on disk the waves are just their bytes and frequency
+        let packet = StoredPacket {
+            signature,
+            data: data.to_vec(),
+            metadata,
+            frequency: self.frequency,
+            timestamp: self.clock.now(),
+        };
+        let (record, len) = self.encode_record(&packet)?;
+        Ok(PreparedStore::Packet { packet, record, len })
+    }
+    
+    /// Write (or count as deduplicated) what `prepare_store` made
+    pub(crate) fn commit_store(&mut self, prepared: PreparedStore) -> Result<[u8; 32]> {
+        match prepared {
+            PreparedStore::Duplicate { signature, record_len } => {
+                self.dedup.deduplicated += 1;
+                self.dedup.bytes_saved += record_len;
+                Ok(signature)
+            }
+            PreparedStore::Packet { packet, record, len } => {
+                // Write to storage, then cache it
+                self.append_record(&packet.signature, &record, len, packet.timestamp)?;
+                let signature = packet.signature;
+                self.note_wonder(&signature, packet.metadata.as_deref())?;
+                self.cache.get_mut().unwrap().insert(signature, packet);
+                Ok(signature)
+            }
+        }
+    }
+    
+    /// Store many items with one write and (at most) one flush
+    /// 
+    /// Signatures come back in the order of `items`. Duplicates - of live
+    /// packets or of each other - are skipped just like `store` skips them.
+    /// Nothing becomes visible until the whole batch is on its way to disk: if
+    /// the write fails, the store is cut back to where it was and the index and
+    /// cache are left untouched.
+    pub fn store_batch(&mut self, items: &[(&[u8], Option<Vec<u8>>)]) -> Result<Vec<[u8; 32]>> {
+        let timestamp = self.clock.now();
+        let start = self.position;
+        let mut signatures = Vec::with_capacity(items.len());
+        let mut records = Vec::new();
+        let mut written: Vec<(StoredPacket, PacketLocation)> = Vec::new();
+        let mut seen: HashMap<[u8; 32], ((u64, u64), u64)> = HashMap::new();
+        let mut skipped = DedupStats::default();
+        for (_, metadata) in items {
+            self.check_metadata(metadata.as_deref())?;
+        }
+        
+        for (data, metadata) in items {
+            let signature = content_signature(data, metadata.as_deref());
+            signatures.push(signature);
+            let lens = (data.len() as u64, metadata.as_ref().map_or(0, |m| m.len() as u64));
+            let duplicate = match seen.get(&signature) {
+                Some(&(seen_lens, record_len)) if seen_lens == lens => Some(record_len),
+                Some(&(seen_lens, _)) => return Err(Mem8Error::from(SignatureCollision {
+                    signature,
+                    data_len: seen_lens.0,
+                    metadata_len: seen_lens.1,
+                })),
+                None => self.live_copy(&signature, lens.0, lens.1)?,
+            };
+            if let Some(record_len) = duplicate {
+                skipped.deduplicated += 1;
+                skipped.bytes_saved += record_len;
+                continue;
+            }
+            
+            let packet = StoredPacket {
+                signature,
+                data: data.to_vec(),
+                metadata: metadata.clone(),
+                frequency: self.frequency,
+                timestamp,
+            };
+            let (record, len) = self.encode_record(&packet)?;
+            let location = PacketLocation { offset: start + records.len() as u64 + 8, len };
+            records.extend_from_slice(&record);
+            seen.insert(signature, (lens, 8 + len));
+            written.push((packet, location));
+        }
+        
+        if !written.is_empty() {
+            let mut index_records = Vec::with_capacity(written.len() * INDEX_RECORD_SIZE as usize);
+            for (packet, location) in &written {
+                index_records.extend_from_slice(&packet.signature);
+                index_records.write_u64::<BigEndian>(location.offset)?;
+                index_records.write_u64::<BigEndian>(location.len)?;
+            }
+            
+            // One write for the packets, one for their index records
+            let result = self.file.seek(SeekFrom::Start(start))
+                .and_then(|_| self.file.write_all(&records))
+                .and_then(|_| self.index_file.write_all(&index_records));
+            if let Err(e) = result {
+                // Index records past the cut are ignored on the next open
+                self.file.set_len(start)?;
+                return Err(e.into());
+            }
+            
+            self.position = start + records.len() as u64;
+            let count = written.len();
+            let mut stamped = Vec::with_capacity(count);
+            let cache = self.cache.get_mut().unwrap();
+            for (packet, location) in written {
+                self.index.insert(packet.signature, location);
+                self.tombstones.remove(&packet.signature);
+                stamped.push((packet.signature, location.offset, packet.timestamp, packet.metadata.as_deref().and_then(wonder::max_salience)));
+                cache.insert(packet.signature, packet);
+            }
+            for (signature, offset, timestamp, salience) in stamped {
+                self.note_timestamp(&signature, offset, timestamp)?;
+                self.wonder.note(&signature, offset, salience)?;
+            }
+            self.wrote(count)?;
+        }
+        
+        self.dedup.deduplicated += skipped.deduplicated;
+        self.dedup.bytes_saved += skipped.bytes_saved;
+        Ok(signatures)
+    }
+    
+    /// Store a string and get back a wave signature
+    pub fn store_string(&mut self, text: &str) -> Result<[u8; 32]> {
+        self.store(text.as_bytes(), None)
+    }
+    
+    /// Store everything `reader` yields, one chunk at a time
+    /// 
+    /// Only `STREAM_CHUNK_SIZE` bytes are in memory at once, so this works for
+    /// tracks far bigger than RAM. The packet is written exactly as `store`
+    /// would write it and gets the same signature. Streamed packets aren't cached
+    /// (or compressed).
+    /// 
+    /// Encrypted stores are the exception: they read the whole stream first,
+    /// since the signature that authenticates the sealed body is only known
+    /// once all the data has gone by.
+    pub fn store_stream<R: Read>(&mut self, mut reader: R, metadata: Option<Vec<u8>>) -> Result<[u8; 32]> {
+        self.check_metadata(metadata.as_deref())?;
+        if self.key.is_some() {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data)?;
+            return self.store(&data, metadata);
+        }
+        
+        let start = self.position;
+        let result = self.write_stream(&mut reader, metadata.as_deref());
+        if result.is_err() {
+            // Don't leave half a packet behind the append position
+            self.file.set_len(start)?;
+        }
+        let (signature, len) = result?;
+        
+        // The signature is only known now - take the copy back off if it's a duplicate
+        let summary = self.read_summary(&signature, PacketLocation { offset: start + 8, len })?;
+        let live = self.live_copy(&signature, summary.data_len, summary.metadata_len);
+        if !matches!(live, Ok(None)) {
+            self.file.set_len(start)?;
+        }
+        if let Some(record_len) = live? {
+            self.dedup.deduplicated += 1;
+            self.dedup.bytes_saved += record_len;
+            return Ok(signature);
+        }
+        
+        self.append_index_record(&signature, PacketLocation { offset: start + 8, len })?;
+        self.position = start + 8 + len;
+        self.note_wonder(&signature, metadata.as_deref())?;
+        self.wrote(1)?;
+        Ok(signature)
+    }
+    
+    /// Record size of the live packet holding exactly this content, if there is one
+    /// 
+    /// The signature hashes data and metadata back to back, so the lengths are
+    /// compared too ("ab" + "c" and "a" + "bc" hash alike): a live packet with
+    /// the signature but other lengths is a `SignatureCollision`, since storing
+    /// over it would leave it unreachable. A packet that doesn't read back
+    /// cleanly (or has expired) doesn't count - storing again repairs it.
+    fn live_copy(&self, signature: &[u8; 32], data_len: u64, metadata_len: u64) -> Result<Option<u64>> {
+        if self.is_expired(signature) {
+            return Ok(None);
+        }
+        let Some(&location) = self.index.get(signature) else {
+            return Ok(None);
+        };
+        let Ok(summary) = self.read_summary(signature, location) else {
+            return Ok(None);
+        };
+        if (summary.data_len, summary.metadata_len) != (data_len, metadata_len) {
+            return Err(Mem8Error::from(SignatureCollision {
+                signature: *signature,
+                data_len: summary.data_len,
+                metadata_len: summary.metadata_len,
+            }));
+        }
+        Ok(Some(8 + location.len))
+    }
+    
+    /// Appends skipped since the store was opened because the content was already there
+    pub fn dedup_stats(&self) -> DedupStats {
+        self.dedup
+    }
+    
+    /// How much storing `data` (with `metadata_len` bytes of metadata) would add
+    /// 
+    /// Runs the same encoding, compression and sealing decisions as `store`
+    /// without touching the file. Uncompressed estimates are exact. The
+    /// metadata's content isn't known, so compressed estimates count it as
+    /// incompressible and land within 5% (plus `metadata_len`) of the real
+    /// size; payloads over `ESTIMATE_SAMPLE_BYTES` are compressed from evenly
+    /// spread samples and land within 10%. Deduplication isn't predicted -
+    /// this is always the cost of a new packet.
+    pub fn estimate_stored_size(&self, data: &[u8], metadata_len: usize) -> SizeEstimate {
+        // Data length and bytes, then the metadata option, frequency and timestamp
+        let tail_len = if metadata_len > 0 { 1 + 8 + metadata_len } else { 1 } + 8 + 8;
+        let body_len = 8 + data.len() + tail_len;
+        
+        let sample = (data.len() > ESTIMATE_SAMPLE_BYTES).then(|| {
+            let window = ESTIMATE_SAMPLE_BYTES / ESTIMATE_SAMPLE_WINDOWS;
+            let stride = (data.len() - window) / (ESTIMATE_SAMPLE_WINDOWS - 1);
+            (0..ESTIMATE_SAMPLE_WINDOWS)
+                .flat_map(|i| &data[i * stride..i * stride + window])
+                .copied()
+                .collect::<Vec<u8>>()
+        });
+        let probe = sample.as_deref().unwrap_or(data);
+        
+        let mut encoded_len = 32 + body_len;
+        let mut compression = None;
+        if let Some((_, level, tag)) = self.compression_plan(probe) {
+            let packed = compress::compress(probe, level).len();
+            let packed = (packed as f64 * data.len() as f64 / probe.len().max(1) as f64).ceil() as usize;
+            let compressed_len = COMPRESSED_HEADER_LEN + tag.len() + 8 + packed + tail_len;
+            if compressed_len < encoded_len {
+                let adaptive = tag.len() == 2;
+                compression = Some(PacketCompression {
+                    level: adaptive.then_some(level),
+                    class: adaptive.then(|| EntropyClass::from_byte(tag[1])).flatten(),
+                    ratio: (32 + body_len as u64 + CHECKSUM_LEN) as f64 / (compressed_len as u64 + CHECKSUM_LEN) as f64,
+                });
+                encoded_len = compressed_len;
+            }
+        }
+        let codec = if self.key.is_some() {
+            // Nonce, then the sealed format byte and body with its tag
+            encoded_len += cipher::NONCE_LEN + 1 + cipher::TAG_LEN;
+            CODEC_XCHACHA20_POLY1305
+        } else if compression.is_some() {
+            CODEC_ZSTD
+        } else {
+            CODEC_RAW_BYTES
+        };
+        
+        SizeEstimate {
+            packet_bytes: 8 + encoded_len as u64 + CHECKSUM_LEN,
+            index_bytes: INDEX_RECORD_SIZE,
+            codec: codec.to_string(),
+            compression,
+            sampled: sample.is_some(),
+        }
+    }
+    
+    /// Write one streamed packet at the append position, returning (signature, payload length)
+    fn write_stream<R: Read>(&mut self, reader: &mut R, metadata: Option<&[u8]>) -> Result<([u8; 32], u64)> {
+        let start = self.position;
+        self.file.seek(SeekFrom::Start(start))?;
+        
+        // Length prefix, signature and data length get patched in at the end
+        self.file.write_all(&[0u8; 8 + 32 + 8])?;
+        
+        let mut hasher = Hasher::new();
+        let mut checksum = Hasher::new();
+        let mut chunk = vec![0u8; STREAM_CHUNK_SIZE];
+        let mut total: u64 = 0;
+        loop {
+            let filled = read_full(reader, &mut chunk)?;
+            if filled == 0 {
+                break;
+            }
+            hasher.update(&chunk[..filled]);
+            checksum.update(&chunk[..filled]);
+            self.file.write_all(&chunk[..filled])?;
+            total += filled as u64;
+        }
+        
+        // The rest of the packet, exactly as bincode lays it out
+        let mut tail = Vec::new();
+        match metadata {
+            Some(meta) => {
+                hasher.update(meta);
+                tail.push(1);
+                tail.write_u64::<LittleEndian>(meta.len() as u64)?;
+                tail.extend_from_slice(meta);
+            }
+            None => tail.push(0),
+        }
+        tail.write_f64::<LittleEndian>(self.frequency)?;
+        tail.write_u64::<LittleEndian>(self.clock.now())?;
+        self.file.write_all(&tail)?;
+        
+        let signature: [u8; 32] = hasher.finalize().into();
+        let len = 32 + 8 + total + tail.len() as u64 + CHECKSUM_LEN;
+        let prefix = packet_prefix(PACKET_FORMAT_CHECKED, len);
+        checksum.update(&tail);
+        checksum.update(&signature);
+        checksum.update(&total.to_le_bytes());
+        checksum.update(&prefix.to_be_bytes());
+        self.file.write_all(&finish_checksum(checksum))?;
+        
+        self.file.seek(SeekFrom::Start(start))?;
+        self.file.write_u64::<BigEndian>(prefix)?;
+        self.file.write_all(&signature)?;
+        self.file.write_u64::<LittleEndian>(total)?;
+        self.file.flush()?;
+        
+        Ok((signature, len))
+    }
+    
+    /// Retrieve data by its wave signature
+    /// 
+    /// The waves remember everything perfectly - no lossy compression here!
+    pub fn retrieve(&self, signature: &[u8; 32]) -> Result<Vec<u8>> {
+        if self.tombstones.contains(signature) {
+            return Err(Mem8Error::NotFound(format!("Wave signature {} was deleted", hex::encode(signature))));
+        }
+        self.check_expiry(signature)?;
+        
+        // Check cache first
+        if let Some(packet) = self.cache.lock().unwrap().get(signature) {
+            return Ok(packet.data.clone());
+        }
+        
+        // Not in cache - seek straight to it on disk
+        let packet = self.read_packet(signature)?;
+        let data = packet.data.clone();
+        self.cache.lock().unwrap().insert(*signature, packet);
+        Ok(data)
+    }
+    
+    /// Retrieve a string by its wave signature
+    pub fn retrieve_string(&self, signature: &[u8; 32]) -> Result<String> {
+        let data = self.retrieve(signature)?;
+        Ok(crate::text::decode_utf8(data)?)
+    }
+    
+    /// Stream a packet's decoded bytes into `writer`, returning how many were written
+    /// 
+    /// Copies (or, for format v1 packets, decodes) `STREAM_CHUNK_SIZE` bytes
+    /// at a time straight from disk, so big packets never have to fit in memory.
+    /// The checksum is verified in a first pass, before anything is written.
+    pub fn retrieve_to<W: Write>(&self, signature: &[u8; 32], mut writer: W) -> Result<u64> {
+        if self.tombstones.contains(signature) {
+            return Err(Mem8Error::NotFound(format!("Wave signature {} was deleted", hex::encode(signature))));
+        }
+        self.check_expiry(signature)?;
+        
+        // Cached packets are small enough to decode in one go (big ones stay on disk)
+        let cached = self.cache.lock().unwrap().get(signature)
+            .map(|packet| packet.data.clone());
+        if let Some(data) = cached {
+            writer.write_all(&data)?;
+            return Ok(data.len() as u64);
+        }
+        
+        let location = *self.index.get(signature)
+            .ok_or_else(|| Mem8Error::not_found("Wave signature not found"))?;
+        let header = self.read_record_header(location.offset - 8)?;
+        if self.strict_reads || matches!(header.format, PACKET_FORMAT_COMPRESSED | PACKET_FORMAT_ENCRYPTED) {
+            // Compressed and encrypted packets decode in one go (and so do strict reads)
+            let data = self.read_packet(signature)?.data;
+            writer.write_all(&data)?;
+            writer.flush()?;
+            return Ok(data.len() as u64);
+        }
+        let summary = self.read_summary(signature, location)?;
+        if !self.checksum_matches(location.offset - 8, &header)? {
+            return Err(Mem8Error::corrupt(*signature, "Checksum mismatch"));
+        }
+        let format = header.format;
+        let width = wave_width(format)?;
+        
+        let mut file = self.reader();
+        file.seek(SeekFrom::Start(location.offset + 40))?;
+        let mut raw = vec![0u8; STREAM_CHUNK_SIZE * width];
+        let mut remaining = summary.data_len;
+        while remaining > 0 {
+            let n = remaining.min(STREAM_CHUNK_SIZE as u64) as usize;
+            file.read_exact(&mut raw[..n * width])?;
+            
+            if format == PACKET_FORMAT_WAVES {
+                let mut fields = &raw[..n * width];
+                let mut waves = Vec::with_capacity(n);
+                for _ in 0..n {
+                    let re = fields.read_f64::<LittleEndian>()?;
+                    let im = fields.read_f64::<LittleEndian>()?;
+                    waves.push(Complex64::new(re, im));
+                }
+                writer.write_all(&decode_from_waves(&waves, summary.frequency))?;
+            } else {
+                writer.write_all(&raw[..n])?;
+            }
+            remaining -= n as u64;
+        }
+        writer.flush()?;
+        Ok(summary.data_len)
+    }
+    
+    /// Retrieve up to `len` bytes of a packet's data, starting at byte `offset`
+    /// 
+    /// A range running past the end is cut short, like a file read; an
+    /// `offset` past the end is an error. Uncompressed packets only decode the
+    /// requested waves (their checksum is verified by the first range read
+    /// after opening). Compressed and encrypted packets have to be decoded
+    /// whole, so they go through `retrieve` and its cache.
+    pub fn retrieve_range(&self, signature: &[u8; 32], offset: usize, len: usize) -> Result<Vec<u8>> {
+        if self.tombstones.contains(signature) {
+            return Err(Mem8Error::NotFound(format!("Wave signature {} was deleted", hex::encode(signature))));
+        }
+        self.check_expiry(signature)?;
+        
+        let cached = self.cache.lock().unwrap().get(signature)
+            .map(|packet| {
+                let (start, end) = clamp_range(signature, packet.data.len(), offset, len)?;
+                Ok(packet.data[start..end].to_vec())
+            });
+        if let Some(range) = cached {
+            return range;
+        }
+        
+        let location = *self.index.get(signature)
+            .ok_or_else(|| Mem8Error::not_found("Wave signature not found"))?;
+        let header = self.read_record_header(location.offset - 8)?;
+        if self.strict_reads || matches!(header.format, PACKET_FORMAT_COMPRESSED | PACKET_FORMAT_ENCRYPTED) {
+            let data = self.retrieve(signature)?;
+            let (start, end) = clamp_range(signature, data.len(), offset, len)?;
+            return Ok(data[start..end].to_vec());
+        }
+        let summary = self.read_summary(signature, location)?;
+        let (start, end) = clamp_range(signature, summary.data_len as usize, offset, len)?;
+        if !self.verified.lock().unwrap().contains(&location.offset) {
+            if !self.checksum_matches(location.offset - 8, &header)? {
+                return Err(Mem8Error::corrupt(*signature, "Checksum mismatch"));
+            }
+            self.verified.lock().unwrap().insert(location.offset);
+        }
+        
+        let width = wave_width(header.format)?;
+        let mut file = self.reader();
+        file.seek(SeekFrom::Start(location.offset + 40 + (start * width) as u64))?;
+        let mut raw = vec![0u8; (end - start) * width];
+        file.read_exact(&mut raw)?;
+        
+        if header.format != PACKET_FORMAT_WAVES {
+            return Ok(raw);
+        }
+        let mut fields = &raw[..];
+        let mut waves = Vec::with_capacity(end - start);
+        for _ in start..end {
+            let re = fields.read_f64::<LittleEndian>()?;
+            let im = fields.read_f64::<LittleEndian>()?;
+            waves.push(Complex64::new(re, im));
+        }
+        Ok(decode_from_waves(&waves, summary.frequency))
+    }
+    
+    /// How many bytes of data a packet holds, without retrieving them
+    /// 
+    /// Compressed and encrypted packets are decoded to find out.
+    pub fn len(&self, signature: &[u8; 32]) -> Result<usize> {
+        if self.tombstones.contains(signature) {
+            return Err(Mem8Error::NotFound(format!("Wave signature {} was deleted", hex::encode(signature))));
+        }
+        self.check_expiry(signature)?;
+        if let Some(packet) = self.cache.lock().unwrap().get(signature) {
+            return Ok(packet.data.len());
+        }
+        let location = *self.index.get(signature)
+            .ok_or_else(|| Mem8Error::not_found("Wave signature not found"))?;
+        Ok(self.read_summary(signature, location)?.data_len as usize)
+    }
+    
+    /// Get metadata for a stored item
+    /// 
+    /// `None` once the packet has expired, as if it was never stored.
+    pub fn get_metadata(&self, signature: &[u8; 32]) -> Option<Vec<u8>> {
+        if self.is_expired(signature) {
+            return None;
+        }
+        if let Some(packet) = self.cache.lock().unwrap().get(signature) {
+            return packet.metadata.clone();
+        }
+        let packet = self.read_packet(signature).ok()?;
+        let metadata = packet.metadata.clone();
+        self.cache.lock().unwrap().insert(*signature, packet);
+        metadata
+    }
+    
+    /// The base frequency this store encodes with
+    pub fn frequency_preset(&self) -> FrequencyPreset {
+        FrequencyPreset::from_hz(self.frequency)
+    }
+    
+    /// Version of the store file's header (`FILE_FORMAT_VERSION` for new
+    /// stores, 0 for headerless ones from before it existed)
+    pub fn format_version(&self) -> u16 {
+        self.format_version
+    }
+    
+    /// Where the first record starts (just past the header, if there is one)
+    fn data_start(&self) -> u64 {
+        if self.format_version == 0 { 0 } else { FILE_HEADER_LEN }
+    }
+    
+    /// Is this signature stored here (and not expired)?
+    pub fn contains(&self, signature: &[u8; 32]) -> bool {
+        self.index.contains_key(signature) && !self.is_expired(signature)
+    }
+    
+    /// Was this signature stored here and then deleted (not yet compacted)?
+    pub(crate) fn is_deleted(&self, signature: &[u8; 32]) -> bool {
+        self.tombstones.contains(signature)
+    }
+    
+    /// Store `data` under a string key, returning its signature
+    /// 
+    /// Storing under a key again points it at the new packet; the old one
+    /// stays as it was and shows up in `history_for_key`. Keys are kept in the
+    /// store file (sealed, in encrypted stores), so they survive a reopen.
+    pub fn store_keyed(&mut self, key: &str, data: &[u8], metadata: Option<Vec<u8>>) -> Result<[u8; 32]> {
+        if key.starts_with(GROUP_KEY_PREFIX) {
+            return Err(anyhow!("Keys starting with a NUL byte are reserved").into());
+        }
+        let signature = self.store(data, metadata)?;
+        if self.key_signature(key) != Some(signature) {
+            self.append_key_record(key, &signature)?;
+        }
+        Ok(signature)
+    }
+    
+    /// Retrieve the packet a key points at
+    pub fn retrieve_keyed(&self, key: &str) -> Result<Vec<u8>> {
+        let signature = self.key_signature(key)
+            .ok_or_else(|| Mem8Error::NotFound(format!("Key not found: {}", key)))?;
+        self.retrieve(&signature)
+    }
+    
+    /// The signature a key points at, if it's set
+    pub fn key_signature(&self, key: &str) -> Option<[u8; 32]> {
+        self.named.get(key)?.last().copied().flatten()
+    }
+    
+    /// Remove a key, returning the signature it pointed at
+    /// 
+    /// Only the name goes: the packet stays (`delete` it too if it should
+    /// go), and `history_for_key` still lists it.
+    pub fn delete_key(&mut self, key: &str) -> Result<[u8; 32]> {
+        let signature = self.key_signature(key)
+            .ok_or_else(|| Mem8Error::NotFound(format!("Key not found: {}", key)))?;
+        self.append_key_record(key, &[0u8; 32])?;
+        Ok(signature)
+    }
+    
+    /// Keys currently set, in sorted order
+    pub fn keys(&self) -> impl Iterator<Item = &str> + '_ {
+        self.named.iter()
+            .filter(|(key, _)| !key.starts_with(GROUP_KEY_PREFIX))
+            .filter(|(_, history)| matches!(history.last(), Some(Some(_))))
+            .map(|(key, _)| key.as_str())
+    }
+    
+    /// Signatures a key pointed at before the current one, oldest first
+    /// 
+    /// After `delete_key`, that's every signature it ever pointed at.
+    pub fn history_for_key(&self, key: &str) -> Vec<[u8; 32]> {
+        let Some(history) = self.named.get(key) else {
+            return Vec::new();
+        };
+        let mut previous: Vec<[u8; 32]> = history.iter().flatten().copied().collect();
+        if self.key_signature(key).is_some() {
+            previous.pop();
+        }
+        previous
+    }
+    
+    /// Store `data` as one perspective of the memory `group_id`
+    /// 
+    /// `metadata` must be a JSON object (or None); `memory_group` is set in
+    /// it, and its `perspective` (a string, or an object's `type`) labels the
+    /// member. The group's members live in a small index packet, replaced on
+    /// every link and found through a reserved key, so groups survive a
+    /// reopen and compaction like keys do.
+    pub fn store_linked(&mut self, group_id: &str, data: &[u8], metadata: Option<Vec<u8>>) -> Result<[u8; 32]> {
+        let metadata = Self::linked_metadata(group_id, metadata.as_deref())?;
+        let signature = self.store(data, Some(metadata))?;
+        self.link_to_group(group_id, &signature)?;
+        Ok(signature)
+    }
+    
+    /// `metadata` (a JSON object, or None) with `memory_group` set, ready to store
+    /// 
+    /// `store_linked` in two steps, for packets written some other way (say
+    /// with `store_batch`): store them with this metadata, then call
+    /// `link_to_group` for each.
+    pub fn linked_metadata(group_id: &str, metadata: Option<&[u8]>) -> Result<Vec<u8>> {
+        if group_id.is_empty() {
+            return Err(anyhow!("Memory group id can't be empty").into());
+        }
+        let mut meta = match metadata {
+            Some(bytes) => serde_json::from_slice::<serde_json::Value>(bytes)
+                .ok()
+                .filter(|v| v.is_object())
+                .ok_or_else(|| anyhow!("Linked memories need JSON object metadata"))?,
+            None => serde_json::json!({}),
+        };
+        meta["memory_group"] = serde_json::json!(group_id);
+        Ok(serde_json::to_vec(&meta)?)
+    }
+    
+    /// Add a packet stored with `linked_metadata` to its group's index
+    /// 
+    /// Linking a member twice does nothing.
+    pub fn link_to_group(&mut self, group_id: &str, signature: &[u8; 32]) -> Result<()> {
+        let meta = self.get_metadata(signature)
+            .and_then(|m| serde_json::from_slice::<serde_json::Value>(&m).ok())
+            .filter(|m| m["memory_group"].as_str() == Some(group_id))
+            .ok_or_else(|| anyhow!("Packet {} wasn't stored as a member of {}", hex::encode(signature), group_id))?;
+        let mut members = self.group_members(group_id)?;
+        if members.iter().any(|m| &m.signature == signature) {
+            return Ok(());
+        }
+        
+        let perspective = match &meta["perspective"] {
+            serde_json::Value::String(label) => label.clone(),
+            other => other["type"].as_str().unwrap_or("unlabelled").to_string(),
+        };
+        let timestamp = match meta["timestamp"].as_u64() {
+            Some(timestamp) => timestamp,
+            None => self.read_packet(signature)?.timestamp,
+        };
+        members.push(GroupMember { signature: *signature, perspective, timestamp });
+        let key = format!("{}{}", GROUP_KEY_PREFIX, group_id);
+        let previous = self.key_signature(&key);
+        let index = self.store(&bincode::serialize(&members)?, None)?;
+        self.append_key_record(&key, &index)?;
+        
+        // Only the newest index is needed (members are never dropped from it)
+        if let Some(previous) = previous {
+            self.delete(&previous)?;
+        }
+        Ok(())
+    }
+    
+    /// Every live member of a memory group, oldest first
+    /// 
+    /// Ordered by the `timestamp` in each member's metadata (the time it was
+    /// stored if there's none), then by when it joined. Deleted members are
+    /// left out; an unknown group has no members.
+    pub fn get_memory_group(&self, group_id: &str) -> Result<Vec<GroupMember>> {
+        let mut members = self.group_members(group_id)?;
+        members.retain(|m| self.contains(&m.signature));
+        members.sort_by_key(|m| m.timestamp);
+        Ok(members)
+    }
+    
+    /// Members in the order they joined, deleted ones included
+    fn group_members(&self, group_id: &str) -> Result<Vec<GroupMember>> {
+        match self.key_signature(&format!("{}{}", GROUP_KEY_PREFIX, group_id)) {
+            Some(index) => Ok(bincode::deserialize(&self.retrieve(&index)?)?),
+            None => Ok(Vec::new()),
+        }
+    }
+    
+    /// What key records are filed under in the store and its index
+    /// 
+    /// Keyed with the store key in encrypted stores, so the ids don't give
+    /// the names away.
+    fn key_id(&self, key: &str) -> [u8; 32] {
+        match &self.key {
+            Some(secret) => blake3::keyed_hash(secret, key.as_bytes()).into(),
+            None => blake3::derive_key(KEY_ID_CONTEXT, key.as_bytes()),
+        }
+    }
+    
+    /// Point `key` at `signature` (all zeros to delete it)
+    fn append_key_record(&mut self, key: &str, signature: &[u8; 32]) -> Result<()> {
+        let mut body = signature.to_vec();
+        body.extend_from_slice(key.as_bytes());
+        let id = self.key_id(key);
+        let location = self.append_side_record(PACKET_FORMAT_KEY, &id, &body, KEY_INDEX_FLAG)?;
+        self.key_records.push((id, location));
+        self.named.entry(key.to_string()).or_default()
+            .push((signature != &[0u8; 32]).then_some(*signature));
+        self.wrote(1)
+    }
+    
+    /// Rebuild the key history from the key records
+    /// 
+    /// A record that doesn't read back cleanly is skipped with a warning -
+    /// the key keeps whatever it pointed at before.
+    fn load_keys(&mut self) {
+        self.key_records.sort_by_key(|(_, loc)| loc.offset);
+        self.named.clear();
+        for &(id, location) in &self.key_records {
+            let decoded = self.read_side_record(PACKET_FORMAT_KEY, &id, location).and_then(|(_, body)| {
+                if body.len() < 32 {
+                    return Err(anyhow!("Truncated key record").into());
+                }
+                let key = String::from_utf8(body[32..].to_vec())?;
+                if self.key_id(&key) != id {
+                    return Err(anyhow!("Key record doesn't match its id").into());
+                }
+                let signature: [u8; 32] = body[..32].try_into()?;
+                Ok((key, (signature != [0u8; 32]).then_some(signature)))
+            });
+            match decoded {
+                Ok((key, signature)) => self.named.entry(key).or_default().push(signature),
+                Err(e) => eprintln!("⚠️ mem8: skipping key record at {} in {}: {}", location.offset, self.path.display(), e),
+            }
+        }
+    }
+    
+    /// Read the newest expiry record of each packet
+    /// 
+    /// One that doesn't read back cleanly is skipped with a warning, leaving
+    /// that copy of the packet without a deadline.
+    fn load_expiries(&mut self) {
+        let mut deadlines = HashMap::new();
+        for (signature, records) in &self.expiry_records {
+            let Some(&location) = records.iter().max_by_key(|loc| loc.offset) else { continue };
+            let decoded = self.read_side_record(PACKET_FORMAT_EXPIRY, signature, location).and_then(|(_, body)| {
+                let deadline: [u8; 8] = body.as_slice().try_into()
+                    .map_err(|_| anyhow!("Expiry record body is {} bytes, not 8", body.len()))?;
+                Ok(u64::from_le_bytes(deadline))
+            });
+            match decoded {
+                Ok(deadline) => {
+                    deadlines.insert(*signature, (location, deadline));
+                }
+                Err(e) => eprintln!("⚠️ mem8: skipping expiry record at {} in {}: {}", location.offset, self.path.display(), e),
+            }
+        }
+        self.deadlines = deadlines;
+    }
+    
+    /// Record when the packet copy at `offset` was stored
+    fn note_timestamp(&mut self, signature: &[u8; 32], offset: u64, timestamp: u64) -> Result<()> {
+        if let Some((_, previous)) = self.timestamps.insert(*signature, (offset, timestamp)) {
+            self.by_time.remove(&(previous, *signature));
+        }
+        self.by_time.insert((timestamp, *signature));
+        if let Some(file) = &mut self.timestamp_file {
+            let mut record = Vec::with_capacity(TIMESTAMP_RECORD_SIZE);
+            record.extend_from_slice(signature);
+            record.write_u64::<BigEndian>(offset)?;
+            record.write_u64::<BigEndian>(timestamp)?;
+            file.write_all(&record)?;
+        }
+        Ok(())
+    }
+    
+    /// Load the timestamp index, filling in whatever it's missing from the store
+    /// 
+    /// Records for copies that aren't the indexed one any more are ignored;
+    /// live packets without a record (a missing or stale `.tsidx`, or an
+    /// encrypted store) have their timestamp read from the packet header.
+    fn load_timestamps(&mut self) -> Result<()> {
+        let mut recorded = HashMap::new();
+        if let Some(file) = &mut self.timestamp_file {
+            let mut raw = Vec::new();
+            file.seek(SeekFrom::Start(0))?;
+            file.read_to_end(&mut raw)?;
+            let whole = raw.len() / TIMESTAMP_RECORD_SIZE * TIMESTAMP_RECORD_SIZE;
+            if whole != raw.len() {
+                file.set_len(whole as u64)?;
+            }
+            for record in raw[..whole].chunks_exact(TIMESTAMP_RECORD_SIZE) {
+                let signature: [u8; 32] = record[..32].try_into()?;
+                let mut fields = &record[32..];
+                let offset = fields.read_u64::<BigEndian>()?;
+                let timestamp = fields.read_u64::<BigEndian>()?;
+                recorded.insert(signature, (offset, timestamp));
+            }
+        }
+        
+        self.timestamps.clear();
+        self.by_time.clear();
+        let mut missing = Vec::new();
+        for (signature, location) in &self.index {
+            match recorded.get(signature) {
+                Some(&(offset, timestamp)) if offset == location.offset => {
+                    self.timestamps.insert(*signature, (offset, timestamp));
+                    self.by_time.insert((timestamp, *signature));
+                }
+                _ => missing.push((*signature, *location)),
+            }
+        }
+        missing.sort_by_key(|(_, location)| location.offset);
+        for (signature, location) in missing {
+            match self.read_summary(&signature, location) {
+                Ok(summary) => self.note_timestamp(&signature, location.offset, summary.timestamp)?,
+                Err(e) => eprintln!("⚠️ mem8: no timestamp for {} in {}: {}", hex::encode(signature), self.path.display(), e),
+            }
+        }
+        Ok(())
+    }
+    
+    /// Live packets stored within `range` (unix seconds), oldest first
+    /// 
+    /// Answered from the timestamp index, without touching the store file.
+    /// Packets stored in the same second come back in signature order.
+    pub fn find_by_time(&self, range: Range<u64>) -> Vec<[u8; 32]> {
+        if range.start >= range.end {
+            return Vec::new();
+        }
+        self.by_time.range((range.start, [0u8; 32])..(range.end, [0u8; 32]))
+            .map(|&(_, signature)| signature)
+            .filter(|signature| self.contains(signature))
+            .collect()
+    }
+    
+    /// When a live packet was stored (unix seconds), from the timestamp index
+    pub fn stored_at(&self, signature: &[u8; 32]) -> Option<u64> {
+        self.timestamps.get(signature)
+            .filter(|_| self.contains(signature))
+            .map(|&(_, timestamp)| timestamp)
+    }
+    
+    /// Store `data` with `metadata` as its metadata (`MarineMetadata`'s JSON, as `attach_marine` writes it)
+    pub fn store_analyzed(&mut self, data: &[u8], metadata: &crate::marine::MarineMetadata) -> Result<[u8; 32]> {
+        let json = crate::marine::canonical_json(metadata)?;
+        self.store(data, Some(json))
+    }
+    
+    /// The `k` live packets with the highest peak Marine salience, most salient first
+    /// 
+    /// As (signature, max salience, when it was stored), answered from the
+    /// wonder index without touching the store file. Only packets whose
+    /// current metadata holds a Marine analysis (see `wonder`) are ranked.
+    pub fn top_wonder(&self, k: usize) -> Vec<([u8; 32], f64, u64)> {
+        self.wonder.ranked().into_iter()
+            .filter_map(|(signature, salience)| Some((signature, salience, self.stored_at(&signature)?)))
+            .take(k)
+            .collect()
+    }
+    
+    /// Where a live packet's current metadata is: its newest update, or the packet itself
+    fn metadata_offset(&self, signature: &[u8; 32]) -> Option<u64> {
+        let location = *self.index.get(signature)?;
+        Some(self.applicable_updates(signature, location).last().map_or(location.offset, |update| update.offset))
+    }
+    
+    /// Note the salience of a live packet's current metadata, `metadata`
+    fn note_wonder(&mut self, signature: &[u8; 32], metadata: Option<&[u8]>) -> Result<()> {
+        match self.metadata_offset(signature) {
+            Some(offset) => self.wonder.note(signature, offset, metadata.and_then(wonder::max_salience)),
+            None => Ok(()),
+        }
+    }
+    
+    /// Load the wonder index, filling in whatever it's missing from the store
+    /// 
+    /// Like `load_timestamps`: records that aren't for a live packet's
+    /// current metadata are dropped, and packets without one are read.
+    fn load_wonder(&mut self) -> Result<()> {
+        let current: HashMap<[u8; 32], u64> = self.index.keys()
+            .filter_map(|signature| Some((*signature, self.metadata_offset(signature)?)))
+            .collect();
+        self.wonder.retain(|signature, offset| current.get(signature) == Some(&offset));
+        let mut missing: Vec<([u8; 32], u64)> = current.into_iter()
+            .filter(|(signature, _)| self.wonder.get(signature).is_none())
+            .collect();
+        missing.sort_by_key(|&(_, offset)| offset);
+        for (signature, offset) in missing {
+            match self.read_packet(&signature) {
+                Ok(packet) => self.wonder.note(&signature, offset, packet.metadata.as_deref().and_then(wonder::max_salience))?,
+                Err(e) => eprintln!("⚠️ mem8: no wonder for {} in {}: {}", hex::encode(signature), self.path.display(), e),
+            }
+        }
+        Ok(())
+    }
+    
+    /// Live packets whose current metadata satisfies `predicate`, in the order they were written
+    /// 
+    /// Packets without metadata never match. Every candidate is read from
+    /// disk (without filling the cache), so this is a scan - narrow it with
+    /// `find_by_time` first where you can.
+    pub fn find_by_metadata(&self, predicate: impl Fn(&[u8]) -> bool) -> Vec<[u8; 32]> {
+        self.locations_in_file_order().into_iter()
+            .filter(|(signature, _)| !self.is_expired(signature))
+            .filter_map(|(signature, location)| {
+                let metadata = match self.cache.lock().unwrap().get(&signature) {
+                    Some(packet) => packet.metadata.clone(),
+                    None => match self.applicable_updates(&signature, location).last() {
+                        Some(&latest) => self.read_metadata_update(&signature, latest).ok()?.metadata,
+                        None => self.read_packet_at(&signature, location).ok()?.metadata,
+                    },
+                };
+                metadata.filter(|m| predicate(m)).map(|_| signature)
+            })
+            .collect()
+    }
+    
+    /// What the retention engine needs to know about every indexed packet, expired ones included
+    /// 
+    /// In file order: signature, current metadata, when it was stored (from
+    /// the timestamp index) and whether its TTL deadline has passed.
+    pub(crate) fn retention_candidates(&self) -> Vec<RetentionCandidate> {
+        self.locations_in_file_order().into_iter()
+            .map(|(signature, location)| {
+                let metadata = match self.applicable_updates(&signature, location).last() {
+                    Some(&latest) => self.read_metadata_update(&signature, latest).ok().and_then(|v| v.metadata),
+                    None => self.read_packet_at(&signature, location).ok().and_then(|p| p.metadata),
+                };
+                let stored_at = self.timestamps.get(&signature).map(|&(_, timestamp)| timestamp);
+                (signature, metadata, stored_at, self.is_expired(&signature))
+            })
+            .collect()
+    }
+    
+    /// Live packets whose JSON metadata has `expected` at `pointer` (RFC 6901, e.g. `/mood/name`)
+    pub fn find_by_metadata_json(&self, pointer: &str, expected: &serde_json::Value) -> Vec<[u8; 32]> {
+        self.find_by_metadata(|metadata| {
+            serde_json::from_slice::<serde_json::Value>(metadata)
+                .is_ok_and(|meta| meta.pointer(pointer) == Some(expected))
+        })
+    }
+    
+    /// Summaries of every live packet, in the order they were written
+    /// 
+    /// Only the packet headers are read - wave data is skipped over, never
+    /// decoded. Records that turn out corrupt or truncated are skipped, and
+    /// so are expired packets.
+    pub fn iter(&self) -> impl Iterator<Item = PacketSummary> + '_ {
+        self.locations_in_file_order().into_iter()
+            .filter(move |(signature, _)| !self.is_expired(signature))
+            .filter_map(move |(signature, location)| {
+                let mut summary = self.read_summary(&signature, location).ok()?;
+                if let Some(&latest) = self.applicable_updates(&signature, location).last() {
+                    let version = self.read_metadata_update(&signature, latest).ok()?;
+                    summary.metadata_len = version.metadata.map_or(0, |m| m.len() as u64);
+                }
+                Some(summary)
+            })
+    }
+    
+    /// Every live packet, read lazily from disk in the order they were written
+    /// 
+    /// Records that fail to decode are skipped rather than ending the
+    /// iteration, and expired packets are left out.
+    /// 
+    /// The waves are regenerated from each packet's bytes and frequency.
+    pub fn iter_packets(&self) -> impl Iterator<Item = WavePacket> + '_ {
+        self.locations_in_file_order().into_iter()
+            .filter(move |(signature, _)| !self.is_expired(signature))
+            .filter_map(move |(signature, _)| self.read_packet(&signature).ok())
+            .filter(|packet| self.index.contains_key(&packet.signature))
+            .map(move |packet| {
+                let expires_at = self.expires_at(&packet.signature);
+                WavePacket { expires_at, ..packet.into_wave_packet() }
+            })
+    }
+    
+    /// Live packets with their offsets in the store file, oldest first
+    pub(crate) fn offsets_in_file_order(&self) -> Vec<([u8; 32], u64)> {
+        self.locations_in_file_order().into_iter()
+            .map(|(signature, location)| (signature, location.offset))
+            .collect()
+    }
+    
+    /// Key sealing packet bodies, in encrypted stores
+    pub(crate) fn cipher_key(&self) -> Option<&[u8; cipher::KEY_LEN]> {
+        self.key.as_ref()
+    }
+    
+    /// Path of the store file
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+    
+    fn locations_in_file_order(&self) -> Vec<([u8; 32], PacketLocation)> {
+        let mut locations: Vec<_> = self.index.iter()
+            .map(|(sig, loc)| (*sig, *loc))
+            .collect();
+        locations.sort_by_key(|(_, loc)| loc.offset);
+        locations
+    }
+    
+    /// Read a packet's header fields, hopping over its data
+    fn read_summary(&self, signature: &[u8; 32], location: PacketLocation) -> Result<PacketSummary> {
+        let header = self.read_record_header(location.offset - 8)?;
+        if matches!(header.format, PACKET_FORMAT_COMPRESSED | PACKET_FORMAT_ENCRYPTED) {
+            // Nothing to hop over without decompressing (or decrypting)
+            let packet = self.read_packet_at(signature, location)?;
+            return Ok(PacketSummary {
+                signature: *signature,
+                timestamp: packet.timestamp,
+                metadata_len: packet.metadata.as_ref().map_or(0, |m| m.len() as u64),
+                data_len: packet.data.len() as u64,
+                frequency: packet.frequency,
+                compression: self.packet_compression(location),
+            });
+        }
+        let end = (location.offset + location.len).saturating_sub(checksum_len(header.format));
+        let width = wave_width(header.format)? as u64;
+        let mut file = self.reader();
+        file.seek(SeekFrom::Start(location.offset + 32))?;
+        
+        let data_len = file.read_u64::<LittleEndian>()?;
+        let waves_end = (location.offset + 40).checked_add(data_len.saturating_mul(width))
+            .filter(|&e| e < end);
+        let waves_end = match waves_end {
+            Some(e) if &header.signature == signature && !header.tombstone => e,
+            _ => return Err(Mem8Error::corrupt(*signature, "Corrupt packet header")),
+        };
+        
+        // Option<Vec<u8>> metadata, then frequency and timestamp (bincode layout)
+        file.seek(SeekFrom::Start(waves_end))?;
+        let metadata_len = match file.read_u8()? {
+            0 => 0,
+            1 => {
+                let len = file.read_u64::<LittleEndian>()?;
+                file.seek(SeekFrom::Current(len as i64))?;
+                len
+            }
+            _ => return Err(Mem8Error::corrupt(*signature, "Corrupt metadata tag")),
+        };
+        let frequency = file.read_f64::<LittleEndian>()?;
+        let timestamp = file.read_u64::<LittleEndian>()?;
+        if file.stream_position()? > end {
+            return Err(Mem8Error::corrupt(*signature, "Truncated packet"));
+        }
+        
+        Ok(PacketSummary {
+            signature: *signature,
+            timestamp,
+            metadata_len,
+            data_len,
+            frequency,
+            compression: None,
+        })
+    }
+    
+    /// How the packet at `location` was compressed, if it was
+    fn packet_compression(&self, location: PacketLocation) -> Option<PacketCompression> {
+        let header = self.read_record_header(location.offset - 8).ok()?;
+        if header.format != PACKET_FORMAT_COMPRESSED {
+            return None;
+        }
+        // Mode, body length, then (adaptive packets) level and class - the
+        // checksum behind the body keeps this read inside the record
+        let mut head = [0u8; 11];
+        let mut file = self.reader();
+        file.seek(SeekFrom::Start(location.offset + 32)).ok()?;
+        file.read_exact(&mut head).ok()?;
+        let raw_len = (&head[1..9]).read_u64::<LittleEndian>().ok()?;
+        let (level, class) = match head[0] {
+            compress::MODE_ZSTD_ADAPTIVE | compress::MODE_LZ_ADAPTIVE => (Some(head[9]), EntropyClass::from_byte(head[10])),
+            _ => (None, None),
+        };
+        Some(PacketCompression {
+            level,
+            class,
+            ratio: (32 + raw_len + CHECKSUM_LEN) as f64 / location.len as f64,
+        })
+    }
+    
+    /// Attach new metadata to a stored packet without rewriting its data
+    /// 
+    /// Appends a small update record; from then on `get_metadata` (and every
+    /// read of the packet) sees the newest value, and `metadata_history` has
+    /// the earlier ones. The signature doesn't change. Storing the packet
+    /// again with `store_force` or `update_metadata` starts the history over,
+    /// and `compact` keeps only the latest version.
+    pub fn set_metadata(&mut self, signature: &[u8; 32], metadata: Vec<u8>) -> Result<()> {
+        if self.tombstones.contains(signature) {
+            return Err(Mem8Error::NotFound(format!("Wave signature {} was deleted", hex::encode(signature))));
+        }
+        if !self.index.contains_key(signature) {
+            return Err(Mem8Error::not_found("Wave signature not found"));
+        }
+        self.check_expiry(signature)?;
+        self.check_metadata(Some(&metadata))?;
+        let location = self.append_side_record(PACKET_FORMAT_METADATA, signature, &metadata, METADATA_INDEX_FLAG)?;
+        self.metadata_updates.entry(*signature).or_default().push(location);
+        self.note_wonder(signature, Some(&metadata))?;
+        
+        // The cached copy has the old metadata
+        self.cache.get_mut().unwrap().remove(signature);
+        self.wrote(1)
+    }
+    
+    /// Every version of a packet's metadata, oldest first
+    /// 
+    /// The first is what the packet was stored with; each `set_metadata`
+    /// since adds one.
+    pub fn metadata_history(&self, signature: &[u8; 32]) -> Result<Vec<MetadataVersion>> {
+        let location = *self.index.get(signature)
+            .ok_or_else(|| Mem8Error::not_found("Wave signature not found"))?;
+        let packet = self.read_packet_at(signature, location)?;
+        let mut history = vec![MetadataVersion { timestamp: packet.timestamp, metadata: packet.metadata }];
+        for update in self.applicable_updates(signature, location) {
+            history.push(self.read_metadata_update(signature, update)?);
+        }
+        Ok(history)
+    }
+    
+    /// Update records written after the live copy at `location`, oldest first
+    fn applicable_updates(&self, signature: &[u8; 32], location: PacketLocation) -> Vec<PacketLocation> {
+        self.metadata_updates.get(signature).map_or_else(Vec::new, |updates| {
+            updates.iter().filter(|update| update.offset > location.offset).copied().collect()
+        })
+    }
+    
+    /// Read and verify one metadata update record
+    fn read_metadata_update(&self, signature: &[u8; 32], location: PacketLocation) -> Result<MetadataVersion> {
+        let (timestamp, metadata) = self.read_side_record(PACKET_FORMAT_METADATA, signature, location)?;
+        Ok(MetadataVersion { timestamp, metadata: Some(metadata) })
+    }
+    
+    /// Append a metadata update, key or expiry record and index it under `id`
+    /// 
+    /// Writes `id`, the timestamp, a mode byte and `body` - sealed, with `id`
+    /// and timestamp as the associated data, in encrypted stores.
+    fn append_side_record(&mut self, format: u8, id: &[u8; 32], body: &[u8], index_flag: u64) -> Result<PacketLocation> {
+        let timestamp = self.clock.now();
+        
+        let mut payload = Vec::with_capacity(32 + 8 + 1 + cipher::NONCE_LEN + body.len() + cipher::TAG_LEN);
+        payload.extend_from_slice(id);
+        payload.extend_from_slice(&timestamp.to_le_bytes());
+        match &self.key {
+            None => {
+                payload.push(0);
+                payload.extend_from_slice(body);
+            }
+            Some(key) => {
+                let nonce = cipher::random_nonce();
+                let sealed = cipher::seal(key, &nonce, &payload[..40], body);
+                payload.push(1);
+                payload.extend_from_slice(&nonce);
+                payload.extend_from_slice(&sealed);
+            }
+        }
+        let len = payload.len() as u64 + CHECKSUM_LEN;
+        let prefix = packet_prefix(format, len);
+        let mut record = Vec::with_capacity(8 + len as usize);
+        record.write_u64::<BigEndian>(prefix)?;
+        record.extend_from_slice(&payload);
+        record.extend_from_slice(&record_checksum(prefix, &payload));
+        
+        self.file.seek(SeekFrom::Start(self.position))?;
+        self.file.write_all(&record)?;
+        let location = PacketLocation { offset: self.position + 8, len };
+        self.write_index_record(id, location.offset, len | index_flag)?;
+        self.position += 8 + len;
+        Ok(location)
+    }
+    
+    /// Read and verify a record written by `append_side_record`, returning (timestamp, body)
+    fn read_side_record(&self, format: u8, id: &[u8; 32], location: PacketLocation) -> Result<(u64, Vec<u8>)> {
+        let what = match format {
+            PACKET_FORMAT_KEY => "key record",
+            PACKET_FORMAT_EXPIRY => "expiry record",
+            _ => "metadata update",
+        };
+        self.with_record(location, |header, buffer| {
+            if header.tombstone || header.format != format || &header.signature != id {
+                return Err(Mem8Error::corrupt(*id, format!("Corrupt {}", what)));
+            }
+            let split = buffer.len().checked_sub(CHECKSUM_LEN as usize).filter(|&s| s > 40)
+                .ok_or_else(|| Mem8Error::corrupt(*id, format!("Truncated {}", what)))?;
+            let (payload, checksum) = buffer.split_at(split);
+            if checksum != record_checksum(packet_prefix(header.format, location.len), payload) {
+                return Err(Mem8Error::corrupt(*id, format!("Checksum mismatch in {}", what)));
+            }
+            self.open_side_payload(what, id, payload)
+        })
+    }
+    
+    /// Timestamp and (unsealed) body of a verified side record payload
+    fn open_side_payload(&self, what: &str, id: &[u8; 32], payload: &[u8]) -> Result<(u64, Vec<u8>)> {
+        let timestamp = (&payload[32..40]).read_u64::<LittleEndian>()?;
+        let body = &payload[41..];
+        let body = match payload[40] {
+            0 => body.to_vec(),
+            1 => {
+                let key = self.key.as_ref().ok_or_else(|| anyhow!(
+                    "The {} for {} is encrypted - open the store with Mem8Lite::new_encrypted",
+                    what, hex::encode(id)
+                ))?;
+                if body.len() < cipher::NONCE_LEN {
+                    return Err(Mem8Error::corrupt(*id, format!("Truncated {}", what)));
+                }
+                let (nonce, sealed) = body.split_at(cipher::NONCE_LEN);
+                cipher::open(key, nonce.try_into()?, &payload[..40], sealed).map_err(|e| {
+                    anyhow::Error::new(e).context(format!("Couldn't decrypt the {} for {}", what, hex::encode(id)))
+                })?
+            }
+            mode => return Err(Mem8Error::corrupt(*id, format!("Unsupported {} mode {}", what, mode))),
+        };
+        Ok((timestamp, body))
+    }
+    
+    /// Replace a packet's metadata, keeping its signature
+    /// 
+    /// The packet is appended again with the new metadata and the index moves
+    /// to it (the old copy goes away at the next `compact`). The signature is
+    /// not recomputed, so everything that refers to the packet keeps working -
+    /// but the new copy no longer hashes to it, and `verify` and strict reads
+    /// report it. `set_metadata` keeps the packet as it was and doesn't copy
+    /// the data.
+    pub fn update_metadata(&mut self, signature: &[u8; 32], metadata: Option<Vec<u8>>) -> Result<()> {
+        if self.tombstones.contains(signature) {
+            return Err(Mem8Error::NotFound(format!("Wave signature {} was deleted", hex::encode(signature))));
+        }
+        self.check_metadata(metadata.as_deref())?;
+        let mut packet = self.read_packet(signature)?;
+        packet.metadata = metadata;
+        self.persist_packet(&packet)?;
+        self.note_wonder(signature, packet.metadata.as_deref())?;
+        
+        let cache = self.cache.get_mut().unwrap();
+        if cache.contains(signature) {
+            cache.insert(*signature, packet);
+        }
+        Ok(())
+    }
+    
+    /// Delete a packet by appending a tombstone
+    /// 
+    /// The packet's bytes stay in the file until `compact` runs; until then
+    /// `retrieve` reports it as deleted. Storing the same data again brings it back.
+    pub fn delete(&mut self, signature: &[u8; 32]) -> Result<()> {
+        if self.tombstones.contains(signature) {
+            return Ok(());
+        }
+        if !self.index.contains_key(signature) {
+            return Err(Mem8Error::not_found("Wave signature not found"));
+        }
+        self.append_tombstone(signature)
+    }
+    
+    /// Delete a packet and zero its bytes on disk right away
+    /// 
+    /// Every copy of the packet in the store file is overwritten (payload and
+    /// metadata alike), the store is fsynced, and the scrub is recorded in
+    /// `<path>.scrub.log`. Works on packets that were already `delete`d but not
+    /// yet compacted. See `ScrubReport::limitations` for what zeroing can't undo.
+    pub fn secure_delete(&mut self, signature: &[u8; 32]) -> Result<ScrubReport> {
+        if !self.index.contains_key(signature) && !self.tombstones.contains(signature) {
+            return Err(Mem8Error::not_found("Wave signature not found"));
+        }
+        
+        // Make sure the index really points at this packet before zeroing anything
+        if let Some(location) = self.index.get(signature).copied() {
+            let header = self.read_record_header(location.offset - 8)?;
+            if header.tombstone || header.len != location.len || &header.signature != signature {
+                return Err(anyhow!(
+                    "Index entry for {} doesn't match the store file - refusing to scrub",
+                    hex::encode(signature)
+                ).into());
+            }
+        }
+        
+        // Earlier copies (the same packet stored twice) hold the same bytes
+        let mut copies = Vec::new();
+        let mut pos = self.data_start();
+        while pos + 8 + 32 <= self.position {
+            let header = self.read_record_header(pos)?;
+            if pos + 8 + header.len > self.position || header.len < 32 {
+                break;
+            }
+            if !header.tombstone && &header.signature == signature {
+                copies.push((pos, header.len));
+            }
+            pos += 8 + header.len;
+        }
+        
+        // Zero in place, re-flagging each record so scans skip it
+        let mut scrubbed = 0;
+        for &(pos, len) in &copies {
+            self.file.seek(SeekFrom::Start(pos))?;
+            self.file.write_u64::<BigEndian>(TOMBSTONE_FLAG | len)?;
+            self.file.write_all(&vec![0u8; len as usize])?;
+            scrubbed += len;
+        }
+        self.file.flush()?;
+        
+        if self.index.contains_key(signature) {
+            self.append_tombstone(signature)?;
+        }
+        self.metadata_updates.remove(signature);
+        self.expiry_records.remove(signature);
+        self.deadlines.remove(signature);
+        if let Some((_, timestamp)) = self.timestamps.remove(signature) {
+            self.by_time.remove(&(timestamp, *signature));
+        }
+        self.wonder.forget(signature);
+        self.sync()?;
+        
+        let report = ScrubReport::new(signature, copies.len(), scrubbed);
+        report.record(&scrub_log_path(&self.path))?;
+        Ok(report)
+    }
+    
+    /// Append a tombstone for a live packet and forget it
+    fn append_tombstone(&mut self, signature: &[u8; 32]) -> Result<()> {
+        self.file.seek(SeekFrom::Start(self.position))?;
+        self.file.write_u64::<BigEndian>(TOMBSTONE_FLAG | 32)?;
+        self.file.write_all(signature)?;
+        self.file.flush()?;
+        
+        self.write_index_record(signature, self.position + 8, TOMBSTONE_INDEX_LEN)?;
+        self.position += 8 + 32;
+        self.wrote(1)?;
+        
+        self.index.remove(signature);
+        self.cache.get_mut().unwrap().remove(signature);
+        self.tombstones.insert(*signature);
+        Ok(())
+    }
+    
+    /// A read cursor of its own over the store file (see `FileAt`)
+    fn reader(&self) -> FileAt<'_> {
+        FileAt { file: &self.file, pos: 0 }
+    }
+    
+    /// Read the length prefix and signature of the record at `pos`
+    fn read_record_header(&self, pos: u64) -> Result<RecordHeader> {
+        let mut file = self.reader();
+        file.seek(SeekFrom::Start(pos))?;
+        let mut head = [0u8; 40];
+        file.read_exact(&mut head)?;
+        Ok(parse_record_header(&head))
+    }
+    
+    /// Is the record at `pos` framed correctly and does it match its checksum?
+    fn check_record(&self, pos: u64) -> Result<RecordCheck> {
+        if pos + 8 + 32 > self.position {
+            return Ok(RecordCheck::Torn);
+        }
+        let header = self.read_record_header(pos)?;
+        if header.len < 32 || pos + 8 + header.len > self.position {
+            return Ok(RecordCheck::Torn);
+        }
+        
+        let known = header.tombstone || known_format(header.format);
+        if known && self.checksum_matches(pos, &header)? {
+            Ok(RecordCheck::Valid(header))
+        } else {
+            Ok(RecordCheck::Corrupt(header))
+        }
+    }
+    
+    /// Verify the checksum of the record at `pos`, a chunk at a time
+    /// 
+    /// Tombstones and formats older than v3 have no checksum and always pass.
+    fn checksum_matches(&self, pos: u64, header: &RecordHeader) -> Result<bool> {
+        if header.tombstone || checksum_len(header.format) == 0 {
+            return Ok(true);
+        }
+        if header.len < 32 + 8 + CHECKSUM_LEN {
+            return Ok(false);
+        }
+        
+        let mut file = self.reader();
+        file.seek(SeekFrom::Start(pos + 8))?;
+        let mut head = [0u8; 40];
+        file.read_exact(&mut head)?;
+        
+        let mut hasher = Hasher::new();
+        let mut remaining = header.len - 40 - CHECKSUM_LEN;
+        let mut chunk = vec![0u8; remaining.min(STREAM_CHUNK_SIZE as u64) as usize];
+        while remaining > 0 {
+            let n = remaining.min(chunk.len() as u64) as usize;
+            file.read_exact(&mut chunk[..n])?;
+            hasher.update(&chunk[..n]);
+            remaining -= n as u64;
+        }
+        let mut stored = [0u8; CHECKSUM_LEN as usize];
+        file.read_exact(&mut stored)?;
+        
+        hasher.update(&head);
+        hasher.update(&packet_prefix(header.format, header.len).to_be_bytes());
+        Ok(finish_checksum(hasher) == stored)
+    }
+    
+    /// (compressed packets, payload bytes on disk, payload bytes uncompressed,
+    /// adaptive packets by class) of live packets
+    fn compression_totals(&self) -> (usize, u64, u64, BTreeMap<EntropyClass, ClassCompression>) {
+        let mut compressed = 0;
+        let mut stored = 0;
+        let mut uncompressed = 0;
+        let mut by_class: BTreeMap<EntropyClass, (usize, u64, u64)> = BTreeMap::new();
+        for location in self.index.values() {
+            stored += location.len;
+            match self.packet_compression(*location) {
+                Some(info) => {
+                    compressed += 1;
+                    let raw = (info.ratio * location.len as f64).round() as u64;
+                    uncompressed += raw;
+                    if let Some(class) = info.class {
+                        let tally = by_class.entry(class).or_default();
+                        tally.0 += 1;
+                        tally.1 += location.len;
+                        tally.2 += raw;
+                    }
+                }
+                None => uncompressed += location.len,
+            }
+        }
+        let by_class = by_class.into_iter().map(|(class, (packets, stored, raw))| {
+            (class, ClassCompression { packets, ratio: raw as f64 / stored as f64 })
+        }).collect();
+        (compressed, stored, uncompressed, by_class)
+    }
+    
+    /// What opening the store had to repair (all zeros after a clean shutdown)
+    pub fn recovery(&self) -> &RecoveryReport {
+        &self.recovery
+    }
+    
+    /// Read every live packet back from disk and check it against its signature
+    /// 
+    /// Each packet is decoded (and decrypted) from the file, bypassing the
+    /// cache, and the blake3 signature is recomputed over its data and the
+    /// metadata it was stored with. Packets that don't decode or don't match
+    /// are listed in `corrupt`.
+    pub fn verify(&self) -> VerifyReport {
+        self.verify_with_progress(|_, _| {})
+    }
+    
+    /// `verify`, calling `progress(checked, total)` after each packet
+    pub fn verify_with_progress(&self, mut progress: impl FnMut(usize, usize)) -> VerifyReport {
+        let locations = self.locations_in_file_order();
+        let mut report = VerifyReport {
+            total: locations.len(),
+            truncated_tail_bytes: self.recovery.truncated_bytes,
+            ..VerifyReport::default()
+        };
+        for (checked, (signature, location)) in locations.into_iter().enumerate() {
+            let intact = self.read_packet_at(&signature, location)
+                .and_then(|packet| check_signature(&packet))
+                .is_ok();
+            if intact {
+                report.ok += 1;
+            } else {
+                report.corrupt.push(signature);
+            }
+            progress(checked + 1, report.total);
+        }
+        report
+    }
+    
+    /// Rewrite the store without deleted (or expired) packets, returning the bytes reclaimed
+    /// 
+    /// Crash-safe: live packets go to `<path>.compact`, which is fsynced and
+    /// renamed over the store. The old sidecar index is removed first, so a
+    /// crash anywhere in between just means it gets rebuilt on the next open.
+    /// A headerless store gets the current header on the way.
+    pub fn compact(&mut self) -> Result<u64> {
+        self.compact_with(None).map(|(reclaimed, _)| reclaimed)
+    }
+    
+    /// Switch the store to `frequency`, rewriting every packet at it during a compaction
+    /// 
+    /// Packets always decode at the frequency they were written with, so
+    /// mixed stores read fine; this is for wanting them uniform (exported
+    /// waves, `summaries`). Returns how many packets were rewritten.
+    pub fn reencode(&mut self, frequency: impl Into<FrequencyPreset>) -> Result<usize> {
+        self.frequency = frequency.into().hz();
+        self.compact_with(Some(self.frequency)).map(|(_, rewritten)| rewritten)
+    }
+    
+    /// `compact`, re-encoding packets not at `reencode` Hz; (bytes reclaimed, packets rewritten)
+    fn compact_with(&mut self, reencode: Option<f64>) -> Result<(u64, usize)> {
+        let temp_path = compact_path(&self.path);
+        let mut temp = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(&temp_path)?;
+        
+        // Copy live packets in their original order, without decoding them
+        let mut live: Vec<([u8; 32], PacketLocation)> = self.index.iter()
+            .filter(|(sig, _)| !self.is_expired(sig))
+            .map(|(sig, loc)| (*sig, *loc))
+            .collect();
+        live.sort_by_key(|(_, loc)| loc.offset);
+        
+        let flags = if self.key.is_some() { FILE_FLAG_ENCRYPTED } else { 0 };
+        write_file_header(&mut temp, flags, self.frequency)?;
+        
+        let mut new_index = HashMap::with_capacity(live.len());
+        let mut new_updates = HashMap::new();
+        let mut new_expiries = HashMap::new();
+        let mut position = FILE_HEADER_LEN;
+        let mut rewritten = 0;
+        for (signature, location) in live {
+            let packet = match reencode {
+                Some(hz) => Some(self.read_packet_at(&signature, location)?).filter(|packet| packet.frequency != hz),
+                None => None,
+            };
+            let (record, len) = match packet {
+                Some(mut packet) => {
+                    packet.frequency = reencode.unwrap();
+                    self.cache.lock().unwrap().remove(&signature);
+                    rewritten += 1;
+                    self.encode_record(&packet)?
+                }
+                None => {
+                    // Length prefix included, so each packet keeps its format
+                    let mut record = vec![0u8; 8 + location.len as usize];
+                    self.file.seek(SeekFrom::Start(location.offset - 8))?;
+                    self.file.read_exact(&mut record)?;
+                    (record, location.len)
+                }
+            };
+            
+            temp.write_all(&record)?;
+            new_index.insert(signature, PacketLocation { offset: position + 8, len });
+            position += 8 + len;
+            
+            // Only the newest metadata survives, right behind its packet
+            if let Some(&update) = self.applicable_updates(&signature, location).last() {
+                let mut record = vec![0u8; 8 + update.len as usize];
+                self.file.seek(SeekFrom::Start(update.offset - 8))?;
+                self.file.read_exact(&mut record)?;
+                
+                temp.write_all(&record)?;
+                new_updates.insert(signature, vec![PacketLocation { offset: position + 8, len: update.len }]);
+                position += 8 + update.len;
+            }
+            
+            // And so does its deadline
+            if let Some(deadline) = self.expires_at(&signature) {
+                let (expiry, _) = self.deadlines[&signature];
+                let mut record = vec![0u8; 8 + expiry.len as usize];
+                self.file.seek(SeekFrom::Start(expiry.offset - 8))?;
+                self.file.read_exact(&mut record)?;
+                
+                temp.write_all(&record)?;
+                let moved = PacketLocation { offset: position + 8, len: expiry.len };
+                new_expiries.insert(signature, (moved, deadline));
+                position += 8 + expiry.len;
+            }
+        }
+        
+        // Key records all stay, history included, in their original order
+        let mut new_keys = Vec::with_capacity(self.key_records.len());
+        for &(id, location) in &self.key_records {
+            let mut record = vec![0u8; 8 + location.len as usize];
+            self.file.seek(SeekFrom::Start(location.offset - 8))?;
+            self.file.read_exact(&mut record)?;
+            
+            temp.write_all(&record)?;
+            new_keys.push((id, PacketLocation { offset: position + 8, len: location.len }));
+            position += 8 + location.len;
+        }
+        temp.flush()?;
+        temp.sync_all()?;
+        drop(temp);
+        
+        // Swap the compacted file in (a mapping of the old one would read stale offsets)
+        #[cfg(feature = "mmap")]
+        self.mapping.get_mut().unwrap().take();
+        let index_file_path = index_path(&self.path);
+        if index_file_path.exists() {
+            std::fs::remove_file(&index_file_path)?;
+        }
+        std::fs::rename(&temp_path, &self.path)?;
+        sync_parent_dir(&self.path)?;
+        
+        let reclaimed = self.position.saturating_sub(position);
+        self.file = OpenOptions::new().read(true).write(true).open(&self.path)?;
+        self.index_file = OpenOptions::new()
+            .create(true)
+            .read(true)
+            .append(true)
+            .open(&index_file_path)?;
+        self.position = position;
+        self.format_version = FILE_FORMAT_VERSION;
+        self.index.clear();
+        self.tombstones.clear();
+        self.metadata_updates.clear();
+        self.expiry_records.clear();
+        self.deadlines.clear();
+        self.verified.lock().unwrap().clear();
+        
+        // Fresh sidecars for the new layout
+        let moved: Vec<([u8; 32], u64, u64)> = new_index.iter()
+            .filter_map(|(signature, location)| {
+                self.timestamps.get(signature).map(|&(_, timestamp)| (*signature, location.offset, timestamp))
+            })
+            .collect();
+        self.timestamps.clear();
+        self.by_time.clear();
+        if let Some(file) = &mut self.timestamp_file {
+            file.set_len(0)?;
+        }
+        for (signature, offset, timestamp) in moved {
+            self.note_timestamp(&signature, offset, timestamp)?;
+        }
+        for (signature, location) in new_index {
+            self.append_index_record(&signature, location)?;
+        }
+        for (signature, updates) in new_updates {
+            for update in &updates {
+                self.write_index_record(&signature, update.offset, update.len | METADATA_INDEX_FLAG)?;
+            }
+            self.metadata_updates.insert(signature, updates);
+        }
+        for (signature, (expiry, deadline)) in new_expiries {
+            self.write_index_record(&signature, expiry.offset, expiry.len | EXPIRY_INDEX_FLAG)?;
+            self.expiry_records.insert(signature, vec![expiry]);
+            self.deadlines.insert(signature, (expiry, deadline));
+        }
+        for &(id, location) in &new_keys {
+            self.write_index_record(&id, location.offset, location.len | KEY_INDEX_FLAG)?;
+        }
+        self.key_records = new_keys;
+        
+        // Saliences carry over, at where each packet's metadata is now
+        let noted: Vec<([u8; 32], Option<f64>)> = self.index.keys()
+            .filter_map(|signature| Some((*signature, self.wonder.get(signature)?.1)))
+            .collect();
+        self.wonder.clear()?;
+        for (signature, salience) in noted {
+            if let Some(offset) = self.metadata_offset(&signature) {
+                self.wonder.note(&signature, offset, salience)?;
+            }
+        }
+        self.index_file.sync_all()?;
+        if let Some(file) = &self.timestamp_file {
+            file.sync_all()?;
+        }
+        self.wonder.sync()?;
+        
+        // Offsets changed, so a half-done backfill starts over (finished packets are skipped)
+        let cursor = backfill_cursor_path(&self.path);
+        if cursor.exists() {
+            std::fs::remove_file(cursor)?;
+        }
+        
+        Ok((reclaimed, rewritten))
+    }
+    
+    /// Read a single packet from disk using the offset index
+    /// 
+    /// Format v1 packets are decoded from their waves on the way in; format v3
+    /// packets must match their checksum. The latest metadata update, if
+    /// there is one, replaces the stored metadata. Strict stores check the
+    /// signature before that (see `with_strict_reads`).
+    pub(crate) fn read_packet(&self, signature: &[u8; 32]) -> Result<StoredPacket> {
+        let location = *self.index.get(signature)
+            .ok_or_else(|| Mem8Error::not_found("Wave signature not found"))?;
+        let mut packet = self.read_packet_at(signature, location)?;
+        if self.strict_reads {
+            check_signature(&packet)?;
+        }
+        if let Some(&latest) = self.applicable_updates(signature, location).last() {
+            packet.metadata = self.read_metadata_update(signature, latest)?.metadata;
+        }
+        Ok(packet)
+    }
+    
+    /// A live packet as it was written, without its `set_metadata` updates
+    pub(crate) fn read_original_packet(&self, signature: &[u8; 32]) -> Result<StoredPacket> {
+        let location = *self.index.get(signature)
+            .ok_or_else(|| Mem8Error::not_found("Wave signature not found"))?;
+        self.read_packet_at(signature, location)
+    }
+    
+    fn read_packet_at(&self, signature: &[u8; 32], location: PacketLocation) -> Result<StoredPacket> {
+        self.with_record(location, |header, buffer| {
+            let payload = match checksum_len(header.format) {
+                0 => buffer,
+                len => {
+                    let split = buffer.len().checked_sub(len as usize)
+                        .ok_or_else(|| Mem8Error::corrupt(*signature, "Truncated packet"))?;
+                    let (payload, checksum) = buffer.split_at(split);
+                    let prefix = packet_prefix(header.format, location.len);
+                    if payload.len() < 40 || checksum != record_checksum(prefix, payload) {
+                        return Err(Mem8Error::corrupt(*signature, "Checksum mismatch"));
+                    }
+                    payload
+                }
+            };
+            
+            self.decode_payload(signature, header.format, payload)
+        })
+    }
+    
+    /// Run `f` on the header and payload of the record at `location`
+    /// 
+    /// Mapped stores hand `f` a slice of the mapping; buffered ones read the
+    /// record into a buffer first.
+    fn with_record<T>(&self, location: PacketLocation, f: impl FnOnce(RecordHeader, &[u8]) -> Result<T>) -> Result<T> {
+        #[cfg(feature = "mmap")]
+        if let Some(mapping) = self.mapping_covering(location.offset + location.len)? {
+            let record = &mapping[(location.offset - 8) as usize..(location.offset + location.len) as usize];
+            return f(parse_record_header(record[..40].try_into()?), &record[8..]);
+        }
+        
+        let header = self.read_record_header(location.offset - 8)?;
+        let mut file = self.reader();
+        file.seek(SeekFrom::Start(location.offset))?;
+        let mut buffer = vec![0u8; location.len as usize];
+        file.read_exact(&mut buffer)?;
+        f(header, &buffer)
+    }
+    
+    /// The mapping, (re)made if it doesn't reach `end` yet - None when not mapping
+    /// 
+    /// Ranges past the append position are never mapped: those reads go
+    /// through the file and fail there, and an empty store is never mapped.
+    #[cfg(feature = "mmap")]
+    fn mapping_covering(&self, end: u64) -> Result<Option<Arc<memmap2::Mmap>>> {
+        if self.read_mode != ReadMode::Mmap || end == 0 || end > self.position {
+            return Ok(None);
+        }
+        let mut mapping = self.mapping.lock().unwrap();
+        if mapping.as_ref().is_none_or(|m| (m.len() as u64) < end) {
+            // SAFETY: only bytes below `position` are ever read through the
+            // mapping, and the file is never shorter than that: this store
+            // only truncates back to (or beyond) its append position, and
+            // `compact` drops the mapping before swapping files. Writes
+            // through `self.file` land in the same page cache the mapping
+            // sees. Another process truncating the store isn't supported.
+            *mapping = Some(Arc::new(unsafe { memmap2::Mmap::map(&self.file)? }));
+        }
+        Ok(mapping.clone().filter(|m| m.len() as u64 >= end))
+    }
+    
+    /// Turn a verified payload (checksum stripped) back into a packet
+    fn decode_payload(&self, signature: &[u8; 32], format: u8, payload: &[u8]) -> Result<StoredPacket> {
+        match format {
+            PACKET_FORMAT_ENCRYPTED => {
+                let key = self.key.as_ref().ok_or_else(|| anyhow!(
+                    "Packet {} is encrypted - open the store with Mem8Lite::new_encrypted",
+                    hex::encode(signature)
+                ))?;
+                if payload.len() < 32 + cipher::NONCE_LEN {
+                    return Err(Mem8Error::corrupt(*signature, "Truncated encrypted packet"));
+                }
+                let (head, sealed) = payload.split_at(32 + cipher::NONCE_LEN);
+                let nonce: &[u8; cipher::NONCE_LEN] = head[32..].try_into()?;
+                let body = cipher::open(key, nonce, &head[..32], sealed).map_err(|e| {
+                    anyhow::Error::new(e).context(format!("Couldn't decrypt packet {}", hex::encode(signature)))
+                })?;
+                
+                // The sealed body starts with the format of the packet inside
+                let (&inner_format, rest) = body.split_first()
+                    .ok_or_else(|| Mem8Error::corrupt(*signature, "Empty encrypted packet"))?;
+                let mut inner = head[..32].to_vec();
+                inner.extend_from_slice(rest);
+                match inner_format {
+                    PACKET_FORMAT_CHECKED | PACKET_FORMAT_COMPRESSED => self.decode_payload(signature, inner_format, &inner),
+                    other => Err(anyhow!("Unsupported sealed packet format v{} for {}", other, hex::encode(signature)).into()),
+                }
+            }
+            PACKET_FORMAT_COMPRESSED => {
+                let (head, packed) = payload.split_at(COMPRESSED_HEADER_LEN.min(payload.len()));
+                if head.len() < COMPRESSED_HEADER_LEN {
+                    return Err(Mem8Error::corrupt(*signature, "Truncated compressed packet"));
+                }
+                let raw_len = (&head[33..]).read_u64::<LittleEndian>()? as usize;
+                let body = match head[32] {
+                    compress::MODE_ZSTD => compress::decompress(packed, raw_len)?,
+                    compress::MODE_ZSTD_ADAPTIVE if packed.len() >= 2 => compress::decompress(&packed[2..], raw_len)?,
+                    compress::MODE_LZ => compress::decompress_lz(packed, raw_len)?,
+                    compress::MODE_LZ_ADAPTIVE if packed.len() >= 2 => compress::decompress_lz(&packed[2..], raw_len)?,
+                    mode => return Err(Mem8Error::corrupt(*signature, format!("Unsupported compression mode {}", mode))),
+                };
+                let mut encoded = head[..32].to_vec();
+                encoded.extend_from_slice(&body);
+                Ok(bincode::deserialize(&encoded)?)
+            }
+            PACKET_FORMAT_CHECKED | PACKET_FORMAT_COMPACT => Ok(bincode::deserialize(payload)?),
+            PACKET_FORMAT_WAVES => Ok(StoredPacket::from_waves(bincode::deserialize(payload)?)),
+            other => Err(anyhow!("Unsupported packet format v{} for {}", other, hex::encode(signature)).into()),
+        }
+    }
+    
+    /// Write a packet to storage (always compact, with a checksum)
+    fn persist_packet(&mut self, packet: &StoredPacket) -> Result<()> {
+        let (record, len) = self.encode_record(packet)?;
+        self.append_record(&packet.signature, &record, len, packet.timestamp)
+    }
+    
+    /// Append an encoded packet record at the end of the store and index it
+    fn append_record(&mut self, signature: &[u8; 32], record: &[u8], len: u64, timestamp: u64) -> Result<()> {
+        // Always append at the known end, wherever the file's cursor was left
+        self.file.seek(SeekFrom::Start(self.position))?;
+        self.file.write_all(record)?;
+        
+        // Flush to ensure it's written
+        self.file.flush()?;
+        
+        // Record where it went
+        let location = PacketLocation {
+            offset: self.position + 8,
+            len,
+        };
+        self.append_index_record(signature, location)?;
+        self.note_timestamp(signature, location.offset, timestamp)?;
+        
+        // Update position
+        self.position += 8 + len;
+        
+        self.wrote(1)
+    }
+    
+    /// Encode a whole store record - length prefix, payload, checksum - and the payload length
+    /// 
+    /// With compression on, the body after the signature is compressed - as
+    /// long as that actually saves space. Encrypted stores then seal that body.
+    fn encode_record(&self, packet: &StoredPacket) -> Result<(Vec<u8>, u64)> {
+        // Serialize the packet
+        let mut encoded = bincode::serialize(packet)?;
+        let mut format = PACKET_FORMAT_CHECKED;
+        if let Some((mode, level, tag)) = self.compression_plan(&encoded[32..]) {
+            let packed = compress::compress(&encoded[32..], level);
+            if COMPRESSED_HEADER_LEN + tag.len() + packed.len() < encoded.len() {
+                let raw_len = (encoded.len() - 32) as u64;
+                encoded.truncate(32);
+                encoded.push(mode);
+                encoded.extend_from_slice(&raw_len.to_le_bytes());
+                encoded.extend_from_slice(&tag);
+                encoded.extend_from_slice(&packed);
+                format = PACKET_FORMAT_COMPRESSED;
+            }
+        }
+        if let Some(key) = &self.key {
+            // The signature stays readable (for the index); the rest is sealed
+            let mut body = Vec::with_capacity(encoded.len() - 31);
+            body.push(format);
+            body.extend_from_slice(&encoded[32..]);
+            let nonce = cipher::random_nonce();
+            let sealed = cipher::seal(key, &nonce, &encoded[..32], &body);
+            encoded.truncate(32);
+            encoded.extend_from_slice(&nonce);
+            encoded.extend_from_slice(&sealed);
+            format = PACKET_FORMAT_ENCRYPTED;
+        }
+        let len = encoded.len() as u64 + CHECKSUM_LEN;
+        let prefix = packet_prefix(format, len);
+        
+        // Length prefix (with the packet format in its top bits), packet, checksum
+        let mut record = Vec::with_capacity(8 + len as usize);
+        record.write_u64::<BigEndian>(prefix)?;
+        record.extend_from_slice(&encoded);
+        record.extend_from_slice(&record_checksum(prefix, &encoded));
+        Ok((record, len))
+    }
+    
+    /// (mode, level, and for adaptive packets the recorded level and class) for compressing `body`
+    fn compression_plan(&self, body: &[u8]) -> Option<(u8, u8, Vec<u8>)> {
+        match self.compression {
+            CompressionMode::None => None,
+            CompressionMode::Zstd(level) => Some((compress::MODE_ZSTD, level, Vec::new())),
+            CompressionMode::Adaptive(policy) => {
+                let class = policy.classify(body);
+                let level = policy.level_for(class);
+                (level > 0).then(|| (compress::MODE_ZSTD_ADAPTIVE, level, vec![level, class.to_byte()]))
+            }
+        }
+    }
+    
+    /// Count `writes` towards the flush policy, fsyncing if it's due
+    fn wrote(&mut self, writes: usize) -> Result<()> {
+        self.unflushed += writes;
+        let due = match self.flush_policy {
+            FlushPolicy::EveryWrite => true,
+            FlushPolicy::EveryN(n) => self.unflushed >= n.max(1),
+            FlushPolicy::Manual => false,
+        };
+        if due {
+            self.sync()?;
+        }
+        Ok(())
+    }
+    
+    /// Append one record to the sidecar index and make it live
+    fn append_index_record(&mut self, signature: &[u8; 32], location: PacketLocation) -> Result<()> {
+        self.write_index_record(signature, location.offset, location.len)?;
+        self.index.insert(*signature, location);
+        self.tombstones.remove(signature);
+        Ok(())
+    }
+    
+    /// Write one raw record to the sidecar index
+    fn write_index_record(&mut self, signature: &[u8; 32], offset: u64, len: u64) -> Result<()> {
+        let mut record = Vec::with_capacity(INDEX_RECORD_SIZE as usize);
+        record.extend_from_slice(signature);
+        record.write_u64::<BigEndian>(offset)?;
+        record.write_u64::<BigEndian>(len)?;
+        self.index_file.write_all(&record)?;
+        Ok(())
+    }
+    
+    /// Load the sidecar index, then scan the store for anything it's missing
+    /// 
+    /// A missing or stale index (e.g. after a crash between the two writes)
+    /// gets rebuilt from the packets themselves - only the length prefixes and
+    /// signatures are read, never the wave data.
+    /// 
+    /// The scanned tail (plus the newest indexed packet, whose bytes may never
+    /// have reached the disk) is checked against its checksums. Records that
+    /// fail are skipped; a torn record at the very end is truncated away so
+    /// new packets append at a clean boundary. Everything else is verified
+    /// when it's read.
+    fn load_index(&mut self) -> Result<()> {
+        let mut raw = Vec::new();
+        self.index_file.seek(SeekFrom::Start(0))?;
+        self.index_file.read_to_end(&mut raw)?;
+        
+        // Drop a torn trailing record so new appends stay aligned
+        let whole = raw.len() as u64 / INDEX_RECORD_SIZE * INDEX_RECORD_SIZE;
+        if whole != raw.len() as u64 {
+            self.index_file.set_len(whole)?;
+        }
+        
+        let mut records = Vec::with_capacity((whole / INDEX_RECORD_SIZE) as usize);
+        for record in raw[..whole as usize].chunks_exact(INDEX_RECORD_SIZE as usize) {
+            let mut signature = [0u8; 32];
+            signature.copy_from_slice(&record[..32]);
+            let mut fields = &record[32..];
+            let location = PacketLocation {
+                offset: fields.read_u64::<BigEndian>()?,
+                len: fields.read_u64::<BigEndian>()?,
+            };
+            records.push((signature, location));
+        }
+        
+        let mut indexed_end = self.data_start();
+        for &(signature, location) in &records {
+            if location.len == TOMBSTONE_INDEX_LEN {
+                if location.offset + 32 <= self.position {
+                    indexed_end = indexed_end.max(location.offset + 32);
+                    self.index.remove(&signature);
+                    self.tombstones.insert(signature);
+                }
+                continue;
+            }
+            if location.len & KEY_INDEX_FLAG != 0 {
+                let record = PacketLocation { offset: location.offset, len: location.len & !KEY_INDEX_FLAG };
+                if record.offset + record.len <= self.position {
+                    indexed_end = indexed_end.max(record.offset + record.len);
+                    self.key_records.push((signature, record));
+                }
+                continue;
+            }
+            if location.len & METADATA_INDEX_FLAG != 0 {
+                let update = PacketLocation { offset: location.offset, len: location.len & !METADATA_INDEX_FLAG };
+                if update.offset + update.len <= self.position {
+                    indexed_end = indexed_end.max(update.offset + update.len);
+                    self.metadata_updates.entry(signature).or_default().push(update);
+                }
+                continue;
+            }
+            if location.len & EXPIRY_INDEX_FLAG != 0 {
+                let record = PacketLocation { offset: location.offset, len: location.len & !EXPIRY_INDEX_FLAG };
+                if record.offset + record.len <= self.position {
+                    indexed_end = indexed_end.max(record.offset + record.len);
+                    self.expiry_records.entry(signature).or_default().push(record);
+                }
+                continue;
+            }
+            // Ignore entries pointing past the end of a truncated store
+            if location.offset + location.len <= self.position {
+                indexed_end = indexed_end.max(location.offset + location.len);
+                self.index.insert(signature, location);
+                self.tombstones.remove(&signature);
+            }
+        }
+        
+        // The OS may have flushed the index record but not the packet behind it
+        let mut cut = None;
+        let newest = self.index.iter().max_by_key(|(_, loc)| loc.offset).map(|(sig, loc)| (*sig, *loc));
+        if let Some((signature, location)) = newest {
+            if !matches!(self.check_record(location.offset - 8)?, RecordCheck::Valid(_)) {
+                self.index.remove(&signature);
+                
+                // Fall back to what the index said before (an earlier copy, or a tombstone)
+                let earlier = records.iter().rev()
+                    .find(|(sig, loc)| sig == &signature && loc.offset < location.offset);
+                match earlier {
+                    Some(&(_, loc)) if loc.len == TOMBSTONE_INDEX_LEN => {
+                        self.tombstones.insert(signature);
+                    }
+                    Some(&(_, loc)) => {
+                        self.index.insert(signature, loc);
+                    }
+                    None => {}
+                }
+                indexed_end = location.offset - 8;
+                cut = Some(indexed_end);
+            }
+        }
+        let newest_update = self.metadata_updates.iter()
+            .filter_map(|(sig, updates)| updates.last().map(|loc| (*sig, *loc)))
+            .max_by_key(|(_, loc)| loc.offset);
+        if let Some((signature, location)) = newest_update {
+            if !matches!(self.check_record(location.offset - 8)?, RecordCheck::Valid(_)) {
+                let at = location.offset - 8;
+                self.metadata_updates.get_mut(&signature).unwrap().pop();
+                indexed_end = indexed_end.min(at);
+                cut = Some(cut.map_or(at, |c: u64| c.min(at)));
+            }
+        }
+        let newest_key = self.key_records.iter().max_by_key(|(_, loc)| loc.offset).copied();
+        if let Some((_, location)) = newest_key {
+            if !matches!(self.check_record(location.offset - 8)?, RecordCheck::Valid(_)) {
+                let at = location.offset - 8;
+                indexed_end = indexed_end.min(at);
+                cut = Some(cut.map_or(at, |c: u64| c.min(at)));
+            }
+        }
+        let newest_expiry = self.expiry_records.values().flatten().max_by_key(|loc| loc.offset).copied();
+        if let Some(location) = newest_expiry {
+            if !matches!(self.check_record(location.offset - 8)?, RecordCheck::Valid(_)) {
+                let at = location.offset - 8;
+                indexed_end = indexed_end.min(at);
+                cut = Some(cut.map_or(at, |c: u64| c.min(at)));
+            }
+        }
+        // The scan below finds whatever lies past the cut again
+        if let Some(cut) = cut {
+            for updates in self.metadata_updates.values_mut() {
+                updates.retain(|loc| loc.offset < cut);
+            }
+            for records in self.expiry_records.values_mut() {
+                records.retain(|loc| loc.offset < cut);
+            }
+            self.key_records.retain(|(_, loc)| loc.offset < cut);
+        }
+        
+        // Catch up on records written after the last index record
+        let mut found = Vec::new();
+        let mut pos = indexed_end;
+        while pos < self.position {
+            let header = match self.check_record(pos)? {
+                RecordCheck::Valid(header) => header,
+                RecordCheck::Corrupt(header) => {
+                    self.recovery.dropped += 1;
+                    pos += 8 + header.len;
+                    continue;
+                }
+                RecordCheck::Torn => {
+                    // Crashed mid-write - cut back to the last whole record
+                    self.recovery.dropped += 1;
+                    self.recovery.truncated_bytes = self.position - pos;
+                    self.file.set_len(pos)?;
+                    self.position = pos;
+                    cut = Some(cut.map_or(pos, |c: u64| c.min(pos)));
+                    break;
+                }
+            };
+            
+            let RecordHeader { tombstone, len, signature, format } = header;
+            if tombstone && len > 32 {
+                // A scrubbed packet - nothing left to index
+            } else if format == PACKET_FORMAT_KEY && !tombstone {
+                found.push((signature, pos + 8, len | KEY_INDEX_FLAG));
+                self.key_records.push((signature, PacketLocation { offset: pos + 8, len }));
+            } else if format == PACKET_FORMAT_METADATA && !tombstone {
+                found.push((signature, pos + 8, len | METADATA_INDEX_FLAG));
+                self.metadata_updates.entry(signature).or_default().push(PacketLocation { offset: pos + 8, len });
+            } else if format == PACKET_FORMAT_EXPIRY && !tombstone {
+                found.push((signature, pos + 8, len | EXPIRY_INDEX_FLAG));
+                self.expiry_records.entry(signature).or_default().push(PacketLocation { offset: pos + 8, len });
+            } else if tombstone {
+                found.push((signature, pos + 8, TOMBSTONE_INDEX_LEN));
+                self.index.remove(&signature);
+                self.tombstones.insert(signature);
+            } else {
+                found.push((signature, pos + 8, len));
+                self.index.insert(signature, PacketLocation { offset: pos + 8, len });
+                self.tombstones.remove(&signature);
+                self.recovery.recovered += 1;
+            }
+            pos += 8 + len;
+        }
+        
+        // Index records for anything dropped or truncated must not outlive it
+        if let Some(cut) = cut {
+            let keep = records.iter().position(|(_, loc)| loc.offset > cut).unwrap_or(records.len());
+            self.index_file.set_len(keep as u64 * INDEX_RECORD_SIZE)?;
+        }
+        for (signature, offset, len) in found {
+            self.write_index_record(&signature, offset, len)?;
+        }
+        
+        if self.recovery.dropped > 0 {
+            eprintln!("⚠️ mem8: recovered {}: {}", self.path.display(), self.recovery);
+        }
+        Ok(())
+    }
+    
+    /// Load existing packets into cache, stopping once it's full
+    fn load_cache(&mut self) -> Result<()> {
+        let signatures: Vec<[u8; 32]> = self.index.keys().copied().collect();
+        for signature in signatures {
+            if self.cache.get_mut().unwrap().contains(&signature) {
+                continue;
+            }
+            let packet = self.read_packet(&signature)?;
+            let cache = self.cache.get_mut().unwrap();
+            if !cache.has_room_for(PacketCache::packet_size(&packet)) {
+                break;
+            }
+            cache.insert(signature, packet);
+        }
+        
+        Ok(())
+    }
+    
+    /// Warm the cache with as many packets as fit for maximum speed
+    /// 
+    /// Only loads up to the cache limit (see `with_cache_limit`), so it's safe
+    /// on big stores. Returns the number of packets now cached.
+    pub fn load_all(&mut self) -> Result<usize> {
+        self.load_cache()?;
+        Ok(self.cache.get_mut().unwrap().len())
+    }
+    
+    /// Flush buffered writes and fsync the store file
+    /// 
+    /// After this returns, everything stored so far survives a crash or power loss.
+    pub fn sync(&mut self) -> Result<()> {
+        self.file.flush()?;
+        self.file.sync_all()?;
+        self.index_file.sync_all()?;
+        self.unflushed = 0;
+        Ok(())
+    }
+    
+    /// Fsync whatever the flush policy has held back (the same as `sync`)
+    pub fn flush(&mut self) -> Result<()> {
+        self.sync()
+    }
+    
+    /// Flush everything to disk and close the store
+    /// 
+    /// Prefer this over just dropping the store: errors are reported instead of
+    /// only logged. Killing the process (SIGKILL, power loss) before `close` or
+    /// `sync` may lose packets that were written but not yet fsynced by the OS.
+    pub fn close(mut self) -> Result<()> {
+        self.sync()?;
+        self.closed = true;
+        Ok(())
+    }
+    
+    /// Describe what this store supports (schema, codecs, hashing)
+    pub fn store_capabilities(&self) -> StoreCapabilities {
+        StoreCapabilities {
+            kind: "lite".to_string(),
+            schema_version: LITE_SCHEMA_VERSION,
+            codecs: vec![
+                CODEC_RAW_BYTES.to_string(),
+                CODEC_POLAR_F64.to_string(),
+                CODEC_ZSTD.to_string(),
+                CODEC_LZ.to_string(),
+                CODEC_XCHACHA20_POLY1305.to_string(),
+            ],
+            hash_algorithm: "blake3".to_string(),
+            encrypted: self.key.is_some(),
+        }
+    }
+    
+    /// Get statistics about the storage
+    /// 
+    /// The compression figures read each live packet's length prefix
+    /// (encrypted packets count as uncompressed - their headers are sealed).
+    pub fn stats(&self) -> StorageStats {
+        let (compressed_packets, stored, uncompressed, compression_by_class) = self.compression_totals();
+        let cache = self.cache.lock().unwrap();
+        StorageStats {
+            compressed_packets,
+            compression_ratio: if stored == 0 { 1.0 } else { uncompressed as f64 / stored as f64 },
+            compression_by_class,
+            packet_count: self.index.len(),
+            total_size: self.position,
+            frequency: self.frequency,
+            cache_hits: cache.hits,
+            cache_misses: cache.misses,
+            cached_packets: cache.len(),
+            cache_bytes: cache.used_bytes,
+            cache_limit: cache.max_bytes,
+            metadata_rejections: *self.rejections.lock().unwrap(),
+        }
+    }
+}
+
+/// Convert boring bytes into exciting waves! 🌊
+/// 
+/// Each byte becomes a complex number with frequency and phase.
+/// The interference patterns create natural compression!
+pub(crate) fn encode_to_waves(data: &[u8], frequency: f64) -> Vec<Complex64> {
+    data.iter().enumerate().map(|(i, &byte)| {
+        // Create a wave for each byte
+        // Frequency encodes the value, phase encodes position
+        let magnitude = frequency * (byte as f64 / 255.0);
+        let phase = 2.0 * std::f64::consts::PI * (i as f64) / (data.len() as f64);
+        
+        Complex64::from_polar(magnitude, phase)
+    }).collect()
+}
+
+/// Convert waves back to bytes
+/// 
+/// The waves remember everything - perfect reconstruction!
+fn decode_from_waves(waves: &[Complex64], frequency: f64) -> Vec<u8> {
+    waves.iter().map(|wave| {
+        // Extract byte value from frequency component
+        let normalized = wave.norm() / frequency;
+        (normalized * 255.0).round() as u8
+    }).collect()
+}
+
+/// Parse a length prefix and the signature behind it
+/// 
+/// The signature is the first field of every packet (and all of a tombstone).
+fn parse_record_header(head: &[u8; 40]) -> RecordHeader {
+    let prefix = u64::from_be_bytes(head[..8].try_into().unwrap());
+    RecordHeader {
+        tombstone: prefix & TOMBSTONE_FLAG != 0,
+        len: prefix & LEN_MASK,
+        format: match ((prefix & !TOMBSTONE_FLAG) >> FORMAT_SHIFT) as u8 {
+            0 => PACKET_FORMAT_WAVES,
+            format => format,
+        },
+        signature: head[8..].try_into().unwrap(),
+    }
+}
+
+/// The fields of a store file header
+/// 
+/// The base frequency is only checked against the one the store is opened
+/// with (a warning when they differ): each packet decodes at its own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct FileHeader {
+    version: u16,
+    flags: u16,
+    
+    /// Base frequency the store was created (or last compacted) with
+    frequency: f64,
+}
+
+/// How a store file begins
+enum FileStart {
+    Header(FileHeader),
+    
+    /// Nothing yet - or only the first `torn` bytes of a header, cut off while
+    /// the store was being created (the file is emptied again)
+    Empty { torn: u64 },
+    
+    /// A store from before headers, starting right at its first record
+    Headerless,
+}
+
+/// Read the header of a store file `len` bytes long
+fn read_file_header(file: &mut File, len: u64) -> Result<FileStart> {
+    let mut head = [0u8; 40];
+    let n = len.min(head.len() as u64) as usize;
+    file.seek(SeekFrom::Start(0))?;
+    file.read_exact(&mut head[..n])?;
+    
+    if n >= FILE_HEADER_LEN as usize && &head[..4] == FILE_MAGIC {
+        let mut fields = &head[4..FILE_HEADER_LEN as usize];
+        let header = FileHeader {
+            version: fields.read_u16::<BigEndian>()?,
+            flags: fields.read_u16::<BigEndian>()?,
+            frequency: fields.read_f64::<BigEndian>()?,
+        };
+        if header.version == 0 || header.version > FILE_FORMAT_VERSION {
+            return Err(Mem8Error::from(FileFormatError::UnsupportedVersion {
+                found: header.version,
+                supported: FILE_FORMAT_VERSION,
+            }));
+        }
+        return Ok(FileStart::Header(header));
+    }
+    if n < FILE_HEADER_LEN as usize && head[..n.min(4)] == FILE_MAGIC[..n.min(4)] {
+        file.set_len(0)?;
+        return Ok(FileStart::Empty { torn: len });
+    }
+    
+    // Headerless stores start with a record: a known format, and no longer than the file
+    if n == head.len() {
+        let first = parse_record_header(&head);
+        if (first.tombstone || known_format(first.format)) && first.len >= 32 && 8 + first.len <= len {
+            return Ok(FileStart::Headerless);
+        }
+    }
+    Err(Mem8Error::from(FileFormatError::NotAStore))
+}
+
+/// Write a current header at the start of a new store file
+fn write_file_header(file: &mut File, flags: u16, frequency: f64) -> Result<()> {
+    let mut header = Vec::with_capacity(FILE_HEADER_LEN as usize);
+    header.extend_from_slice(FILE_MAGIC);
+    header.write_u16::<BigEndian>(FILE_FORMAT_VERSION)?;
+    header.write_u16::<BigEndian>(flags)?;
+    header.write_f64::<BigEndian>(frequency)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&header)?;
+    Ok(())
+}
+
+/// `offset..offset + len` within `total` bytes, cut short at the end
+fn clamp_range(signature: &[u8; 32], total: usize, offset: usize, len: usize) -> Result<(usize, usize)> {
+    if offset > total {
+        return Err(anyhow!(
+            "Range offset {} is past the end of packet {} ({} bytes)",
+            offset, hex::encode(signature), total
+        ).into());
+    }
+    Ok((offset, offset.saturating_add(len).min(total)))
+}
+
+/// Bytes on disk per byte of data for a packet format
+fn wave_width(format: u8) -> Result<usize> {
+    match format {
+        PACKET_FORMAT_WAVES => Ok(16),
+        PACKET_FORMAT_COMPACT | PACKET_FORMAT_CHECKED => Ok(1),
+        other => Err(anyhow!("Unsupported packet format v{}", other).into()),
+    }
+}
+
+/// Trailing checksum bytes in a payload of this format
+fn checksum_len(format: u8) -> u64 {
+    match format {
+        PACKET_FORMAT_CHECKED | PACKET_FORMAT_COMPRESSED | PACKET_FORMAT_ENCRYPTED
+            | PACKET_FORMAT_METADATA | PACKET_FORMAT_KEY | PACKET_FORMAT_EXPIRY => CHECKSUM_LEN,
+        _ => 0,
+    }
+}
+
+/// Can this build read packets of this format?
+fn known_format(format: u8) -> bool {
+    matches!(
+        format,
+        PACKET_FORMAT_WAVES | PACKET_FORMAT_COMPACT | PACKET_FORMAT_CHECKED
+            | PACKET_FORMAT_COMPRESSED | PACKET_FORMAT_ENCRYPTED | PACKET_FORMAT_METADATA
+            | PACKET_FORMAT_KEY | PACKET_FORMAT_EXPIRY
+    )
+}
+
+impl Drop for Mem8Lite {
+    /// Best-effort flush when the store goes away without `close`
+    fn drop(&mut self) {
+        if !self.closed {
+            if let Err(e) = self.sync() {
+                eprintln!("⚠️ mem8: failed to flush {} on drop: {}", self.path.display(), e);
+            }
+        }
+    }
+}
+
+/// What `Mem8Lite::iter` tells you about a packet without decoding it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PacketSummary {
+    /// The packet's wave signature
+    pub signature: [u8; 32],
+    
+    /// When it was stored (unix seconds)
+    pub timestamp: u64,
+    
+    /// Metadata size in bytes (0 if none)
+    pub metadata_len: u64,
+    
+    /// Decoded data size in bytes
+    pub data_len: u64,
+    
+    /// Frequency it was encoded with
+    pub frequency: f64,
+    
+    /// How it was compressed - `None` if it wasn't (or the store is encrypted,
+    /// which seals that too)
+    pub compression: Option<PacketCompression>,
+}
+
+/// What opening a store had to repair after a crash
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RecoveryReport {
+    /// Packets found past the end of the sidecar index (written, not yet indexed)
+    pub recovered: usize,
+    
+    /// Records lost: failed their checksum, or torn at the end of the file
+    pub dropped: usize,
+    
+    /// Bytes cut off the end of the store to get back to a whole record
+    pub truncated_bytes: u64,
+    
+    /// Operations of a committed transaction applied from its journal (see `Txn::commit`)
+    #[serde(default)]
+    pub replayed_ops: usize,
+}
+
+impl RecoveryReport {
+    /// Did the store open without losing anything?
+    pub fn is_clean(&self) -> bool {
+        self.dropped == 0 && self.truncated_bytes == 0
+    }
+}
+
+impl std::fmt::Display for RecoveryReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} packets recovered, {} records dropped, {} bytes truncated",
+               self.recovered, self.dropped, self.truncated_bytes)?;
+        if self.replayed_ops > 0 {
+            write!(f, ", {} transaction ops replayed", self.replayed_ops)?;
+        }
+        Ok(())
+    }
+}
+
+/// What `Mem8Lite::verify` found
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerifyReport {
+    /// Live packets checked
+    pub total: usize,
+    
+    /// Packets that decoded and matched their signature
+    pub ok: usize,
+    
+    /// Signatures of the packets that didn't, in file order
+    pub corrupt: Vec<[u8; 32]>,
+    
+    /// Bytes of torn record cut off the end when the store was opened
+    pub truncated_tail_bytes: u64,
+}
+
+impl VerifyReport {
+    /// Did every packet check out?
+    pub fn is_clean(&self) -> bool {
+        self.corrupt.is_empty() && self.truncated_tail_bytes == 0
+    }
+}
+
+impl std::fmt::Display for VerifyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} of {} packets ok", self.ok, self.total)?;
+        if self.truncated_tail_bytes > 0 {
+            write!(f, ", {} bytes of torn tail truncated on open", self.truncated_tail_bytes)?;
+        }
+        for signature in &self.corrupt {
+            write!(f, "\n  corrupt: {}", hex::encode(signature))?;
+        }
+        Ok(())
+    }
+}
+
+/// A packet read from disk doesn't hash to its signature (see `Mem8Lite::with_strict_reads`)
+/// 
+/// Comes back inside `Mem8Error`; use `err.downcast_ref::<SignatureMismatch>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("packet {} doesn't match its signature", hex::encode(signature))]
+pub struct SignatureMismatch {
+    pub signature: [u8; 32],
+}
+
+/// Content with the signature of a live packet, but not its data and metadata lengths
+/// 
+/// Signatures hash data and metadata back to back, so the same bytes split
+/// differently sign alike. Storing the second would repoint the index and
+/// leave the first unreachable, so `store` (and `store_force`, `store_batch`,
+/// `store_stream`) refuse it instead.
+/// 
+/// Comes back inside `Mem8Error`; use `err.downcast_ref::<SignatureCollision>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("packet {} already holds other content with this signature ({data_len} bytes of data, {metadata_len} of metadata)", hex::encode(signature))]
+pub struct SignatureCollision {
+    pub signature: [u8; 32],
+    
+    /// Lengths of the packet already stored
+    pub data_len: u64,
+    pub metadata_len: u64,
+}
+
+/// Why a file couldn't be opened as a store
+/// 
+/// Comes back as `Mem8Error::FormatVersion`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+pub enum FileFormatError {
+    /// No `MEM8` header, and it doesn't look like a headerless store either
+    #[error("not a MEM8 store")]
+    NotAStore,
+    
+    /// Written by a newer build
+    #[error("store file format v{found} is newer than this build supports (v{supported})")]
+    UnsupportedVersion { found: u16, supported: u16 },
+}
+
+/// A packet was read after its TTL ran out (see `Mem8Lite::store_with_ttl`)
+/// 
+/// Comes back as `Mem8Error::Expired`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("wave signature {} expired at {expired_at}", hex::encode(signature))]
+pub struct Expired {
+    pub signature: [u8; 32],
+    
+    /// The deadline it was stored with (unix seconds)
+    pub expired_at: u64,
+}
+
+/// One version of a packet's metadata (see `Mem8Lite::metadata_history`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataVersion {
+    /// When this version was written (unix seconds)
+    pub timestamp: u64,
+    
+    pub metadata: Option<Vec<u8>>,
+}
+
+/// One perspective of a memory group (see `Mem8Lite::store_linked`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GroupMember {
+    pub signature: [u8; 32],
+    
+    /// `perspective` from the member's metadata (`unlabelled` without one)
+    pub perspective: String,
+    
+    /// Unix seconds
+    pub timestamp: u64,
+}
+
+/// What a write would add to a store (see `Mem8Lite::estimate_stored_size`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SizeEstimate {
+    /// The packet record, length prefix and checksum included
+    pub packet_bytes: u64,
+    
+    /// Index growth
+    pub index_bytes: u64,
+    
+    /// Outermost codec of the payload (`raw-bytes`, `zstd`, `xchacha20-poly1305`, ...)
+    pub codec: String,
+    
+    /// How it would be compressed, if at all
+    pub compression: Option<PacketCompression>,
+    
+    /// Was the compression estimated from samples of the payload?
+    pub sampled: bool,
+}
+
+impl SizeEstimate {
+    /// Everything the write would add, in bytes
+    pub fn total(&self) -> u64 {
+        self.packet_bytes + self.index_bytes
+    }
+}
+
+/// How `Mem8Lite` reads packets back from disk (see `with_read_mode`)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ReadMode {
+    /// Seek and read into a buffer
+    #[default]
+    Buffered,
+    
+    /// Decode straight out of a read-only memory mapping (`mmap` feature)
+    #[cfg(feature = "mmap")]
+    Mmap,
+}
+
+/// When `Mem8Lite` fsyncs what it writes
+/// 
+/// Every write reaches the OS straight away whatever the policy; the policy
+/// only decides how often the store waits for it to reach the disk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FlushPolicy {
+    /// Fsync after every store, delete and batch - slow, nothing is ever lost
+    EveryWrite,
+    
+    /// Fsync once this many packets have been written
+    EveryN(usize),
+    
+    /// Only fsync on `flush`, `sync`, `close` and drop
+    #[default]
+    Manual,
+}
+
+/// What deduplication saved since a store was opened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DedupStats {
+    /// Stores that found their content already live
+    pub deduplicated: u64,
+    
+    /// Bytes those stores would have appended
+    pub bytes_saved: u64,
+}
+
+impl std::fmt::Display for DedupStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} duplicate stores skipped, {} bytes saved", self.deduplicated, self.bytes_saved)
+    }
+}
+
+/// Storage statistics
+#[derive(Debug, Clone)]
+pub struct StorageStats {
+    pub packet_count: usize,
+    pub total_size: u64,
+    pub frequency: f64,
+    pub cache_hits: usize,
+    pub cache_misses: usize,
+    pub cached_packets: usize,
+    pub cache_bytes: usize,
+    pub cache_limit: usize,
+    
+    /// Live packets stored compressed
+    pub compressed_packets: usize,
+    
+    /// Uncompressed size / size on disk of the live packets (1.0 = no savings)
+    pub compression_ratio: f64,
+    
+    /// Adaptively compressed live packets by entropy class (packets that
+    /// didn't shrink are stored as is and not counted)
+    pub compression_by_class: BTreeMap<EntropyClass, ClassCompression>,
+    
+    /// Writes the metadata policy turned away since the store was opened
+    pub metadata_rejections: MetadataRejections,
+}
+
+/// Adaptive compression figures for one entropy class
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct ClassCompression {
+    pub packets: usize,
+    
+    /// Uncompressed size / size on disk (see `StorageStats::compression_ratio`)
+    pub ratio: f64,
+}
+
+/// How a packet was compressed (see `PacketSummary::compression`)
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PacketCompression {
+    /// zstd level used (recorded by adaptive compression only; the LZ level for older packets)
+    pub level: Option<u8>,
+    
+    /// What the entropy probe made of it (adaptive compression only)
+    pub class: Option<EntropyClass>,
+    
+    /// Uncompressed size / size on disk
+    pub ratio: f64,
+}
+
+impl std::fmt::Display for StorageStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "🌊 Wave Storage Stats:\n")?;
+        write!(f, "  Packets: {}\n", self.packet_count)?;
+        write!(f, "  Size: {} bytes\n", self.total_size)?;
+        write!(f, "  Frequency: {}Hz\n", self.frequency)?;
+        writeln!(f, "  Cache hits: {} (misses: {})", self.cache_hits, self.cache_misses)?;
+        writeln!(f, "  Cached: {} packets, {} / {} bytes", self.cached_packets, self.cache_bytes, self.cache_limit)?;
+        if self.compressed_packets > 0 {
+            writeln!(f, "  Compression: {:.2}× ({} packets compressed)", self.compression_ratio, self.compressed_packets)?;
+        }
+        for (class, figures) in &self.compression_by_class {
+            writeln!(f, "    {} entropy: {:.2}× ({} packets)", class, figures.ratio, figures.packets)?;
+        }
+        if self.metadata_rejections.total() > 0 {
+            writeln!(f, "  Metadata rejected: {}", self.metadata_rejections)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::compress::AdaptivePolicy;
+    use tempfile::tempdir;
+    
+    #[test]
+    fn test_store_and_retrieve() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.m8");
+        
+        let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+        
+        // Store some data
+        let data = b"Hello, waves!";
+        let sig = storage.store(data, None).unwrap();
+        
+        // Retrieve it back
+        let retrieved = storage.retrieve(&sig).unwrap();
+        assert_eq!(retrieved, data);
+    }
+    
+    #[test]
+    fn test_metadata() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.m8");
+        
+        let mut storage = Mem8Lite::new(&path, 2.0).unwrap();
+        
+        // Store with metadata
+        let data = b"Secret message";
+        let metadata = b"user:hue,type:secret";
+        let sig = storage.store(data, Some(metadata.to_vec())).unwrap();
+        
+        // Retrieve metadata
+        let meta = storage.get_metadata(&sig).unwrap();
+        assert_eq!(meta, metadata);
+    }
+    
+    #[test]
+    fn test_metadata_policy_rejects_and_counts() {
+        use crate::metadata_policy::MetadataRejected;
+        
+        let dir = tempdir().unwrap();
+        let policy = MetadataPolicy::new()
+            .with_max_metadata_bytes(64)
+            .with_require_valid_json(true)
+            .with_validator("track", |meta| match meta.get("title") {
+                Some(serde_json::Value::String(_)) => Ok(()),
+                _ => Err("title is required".to_string()),
+            });
+        let mut storage = Mem8Lite::new(dir.path().join("test.m8"), 1.0).unwrap()
+            .with_metadata_policy(policy);
+        let rejection = |e: Mem8Error| e.downcast_ref::<MetadataRejected>().cloned();
+        
+        // Oversized - the error names the limit
+        let err = storage.store(b"tiny", Some(vec![b' '; 200])).unwrap_err();
+        assert_eq!(rejection(err), Some(MetadataRejected::TooLarge { len: 200, limit: 64 }));
+        
+        // Not JSON, under the strict flag
+        let err = storage.store_batch(&[(b"a", Some(b"{}".to_vec())), (b"b", Some(b"mood:calm".to_vec()))]).unwrap_err();
+        assert!(matches!(rejection(err), Some(MetadataRejected::InvalidJson { .. })));
+        assert_eq!(storage.stats().packet_count, 0, "a rejected batch writes nothing");
+        
+        // A registered validator wants a title; other kinds pass untouched
+        let err = storage.store(b"song", Some(br#"{"kind":"track"}"#.to_vec())).unwrap_err();
+        assert_eq!(rejection(err), Some(MetadataRejected::Invalid { kind: "track".into(), reason: "title is required".into() }));
+        let sig = storage.store(b"song", Some(br#"{"kind":"track","title":"Tide"}"#.to_vec())).unwrap();
+        storage.store(b"note", Some(br#"{"kind":"note"}"#.to_vec())).unwrap();
+        storage.store(b"bare", None).unwrap();
+        
+        // Updates are checked too, and leave the packet alone
+        assert!(storage.set_metadata(&sig, br#"{"kind":"track"}"#.to_vec()).is_err());
+        assert!(storage.update_metadata(&sig, Some(b"not json".to_vec())).is_err());
+        let mut txn = storage.begin_txn();
+        txn.store(b"late", Some(vec![b'x'; 100]));
+        assert!(txn.commit().is_err());
+        assert_eq!(storage.get_metadata(&sig).unwrap(), br#"{"kind":"track","title":"Tide"}"#);
+        
+        let stats = storage.stats();
+        assert_eq!(stats.packet_count, 3);
+        assert_eq!(stats.metadata_rejections, MetadataRejections { too_large: 2, invalid_json: 2, failed_validation: 2 });
+        assert!(stats.to_string().contains("Metadata rejected"));
+    }
+    
+    #[test]
+    fn test_persistence() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.m8");
+        
+        let sig = {
+            let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+            storage.store_string("Persistent waves!").unwrap()
+        };
+        
+        // Open storage again
+        let storage = Mem8Lite::new(&path, 1.0).unwrap();
+        let retrieved = storage.retrieve_string(&sig).unwrap();
+        assert_eq!(retrieved, "Persistent waves!");
+    }
+    
+    #[test]
+    fn test_delete_and_compact() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.m8");
+        
+        let (keep, gone) = {
+            let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+            let keep = storage.store_string("Keep riding the wave").unwrap();
+            let gone = storage.store_string("Wipe me out").unwrap();
+            
+            storage.delete(&gone).unwrap();
+            let err = storage.retrieve(&gone).unwrap_err();
+            assert!(err.to_string().contains("deleted"));
+            (keep, gone)
+        };
+        
+        // Tombstones survive a reopen, then compaction reclaims the space
+        {
+            let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+            assert!(storage.retrieve(&gone).unwrap_err().to_string().contains("deleted"));
+            let before = std::fs::metadata(&path).unwrap().len();
+            let reclaimed = storage.compact().unwrap();
+            assert!(reclaimed > 0);
+            assert_eq!(std::fs::metadata(&path).unwrap().len(), before - reclaimed);
+            
+            // Still writable after the swap
+            storage.store_string("After compaction").unwrap();
+        }
+        
+        let storage = Mem8Lite::new(&path, 1.618).unwrap();
+        assert!(!storage.contains(&gone));
+        assert!(storage.retrieve(&gone).is_err());
+        assert_eq!(storage.retrieve_string(&keep).unwrap(), "Keep riding the wave");
+        assert_eq!(storage.stats().packet_count, 2);
+        assert!(!compact_path(&path).exists());
+    }
+    
+    #[test]
+    fn test_secure_delete_zeroes_bytes_on_disk() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.m8");
+        let secret_meta = b"level:secret,owner:hue-diary".to_vec();
+        
+        let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+        let keep = storage.store_string("Public wave").unwrap();
+        let secret = storage.store(b"Hue's secret memory", Some(secret_meta.clone())).unwrap();
+        
+        // The encoded packet (and its plaintext metadata) are on disk
+        let location = storage.index[&secret];
+        let raw = std::fs::read(&path).unwrap();
+        let payload = raw[location.offset as usize..(location.offset + location.len) as usize].to_vec();
+        let contains = |haystack: &[u8], needle: &[u8]| haystack.windows(needle.len()).any(|w| w == needle);
+        assert!(contains(&raw, &secret_meta));
+        
+        let report = storage.secure_delete(&secret).unwrap();
+        assert_eq!(report.copies, 1);
+        assert_eq!(report.bytes_scrubbed, location.len);
+        assert!(report.limitations.contains("copy-on-write"));
+        assert!(storage.retrieve(&secret).is_err());
+        drop(storage);
+        
+        let raw = std::fs::read(&path).unwrap();
+        assert!(!contains(&raw, &payload));
+        assert!(!contains(&raw, &secret_meta));
+        assert!(std::fs::read_to_string(scrub_log_path(&path)).unwrap().contains(&hex::encode(secret)));
+        
+        // Everything else still reads back after a reopen
+        let storage = Mem8Lite::new(&path, 1.618).unwrap();
+        assert!(!storage.contains(&secret));
+        assert_eq!(storage.retrieve_string(&keep).unwrap(), "Public wave");
+    }
+    
+    #[test]
+    fn test_store_stream_matches_store() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("stream.m8");
+        
+        // A few chunks plus a ragged tail
+        let data: Vec<u8> = (0..STREAM_CHUNK_SIZE * 2 + 12_345)
+            .map(|i| (i * 31 % 251) as u8)
+            .collect();
+        let meta = Some(b"format:flac".to_vec());
+        
+        let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+        let streamed = storage.store_stream(&data[..], meta.clone()).unwrap();
+        assert_eq!(storage.stats().cached_packets, 0);
+        drop(storage);
+        
+        let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+        let mut out = Vec::new();
+        assert_eq!(storage.retrieve_to(&streamed, &mut out).unwrap(), data.len() as u64);
+        assert_eq!(out, data);
+        
+        // Same bytes in, same signature and packet out, however they were stored
+        assert_eq!(storage.retrieve(&streamed).unwrap(), data);
+        assert_eq!(storage.get_metadata(&streamed), meta);
+        assert_eq!(storage.store(&data, meta).unwrap(), streamed);
+    }
+    
+    #[test]
+    fn test_storing_identical_content_appends_nothing() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.m8");
+        let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+        
+        let first = storage.store(b"same wave", Some(b"meta".to_vec())).unwrap();
+        let size = storage.stats().total_size;
+        assert_eq!(storage.store(b"same wave", Some(b"meta".to_vec())).unwrap(), first);
+        assert_eq!(storage.store_stream(&b"same wave"[..], Some(b"meta".to_vec())).unwrap(), first);
+        assert_eq!(storage.stats().total_size, size);
+        assert_eq!(storage.dedup_stats(), DedupStats { deduplicated: 2, bytes_saved: 2 * (size - FILE_HEADER_LEN) });
+        
+        // Different metadata is different content
+        storage.store(b"same wave", Some(b"other".to_vec())).unwrap();
+        let size = storage.stats().total_size;
+        
+        // The same bytes split differently sign alike, and are refused
+        // however they come in, rather than hiding the packet already there
+        let err = storage.store(b"same wav", Some(b"emeta".to_vec())).unwrap_err();
+        let collision = err.downcast_ref::<SignatureCollision>().unwrap();
+        assert_eq!((collision.signature, collision.data_len, collision.metadata_len), (first, 9, 4));
+        assert!(storage.store_force(b"same wav", Some(b"emeta".to_vec())).unwrap_err().is::<SignatureCollision>());
+        assert!(storage.store_stream(&b"same wav"[..], Some(b"emeta".to_vec())).unwrap_err().is::<SignatureCollision>());
+        let batch: [(&[u8], Option<Vec<u8>>); 1] = [(b"same wav", Some(b"emeta".to_vec()))];
+        assert!(storage.store_batch(&batch).unwrap_err().is::<SignatureCollision>());
+        assert_eq!(storage.retrieve(&first).unwrap(), b"same wave");
+        assert_eq!(storage.get_metadata(&first).unwrap(), b"meta");
+        assert_eq!(storage.stats().total_size, size);
+        assert_eq!(storage.dedup_stats().deduplicated, 2);
+        
+        // store_force always appends, and once deleted the other split can be stored
+        storage.store_force(b"same wave", Some(b"meta".to_vec())).unwrap();
+        assert!(storage.stats().total_size > size);
+        storage.delete(&first).unwrap();
+        storage.store(b"same wav", Some(b"emeta".to_vec())).unwrap();
+        assert_eq!(storage.retrieve(&first).unwrap(), b"same wav");
+        assert_eq!(storage.dedup_stats().deduplicated, 2);
+    }
+    
+    #[test]
+    fn test_store_batch_writes_like_single_stores() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("batch.m8");
+        let existing;
+        let signatures;
+        {
+            let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+            storage.set_flush_policy(FlushPolicy::EveryN(3));
+            existing = storage.store(b"already here", None).unwrap();
+            assert_eq!(storage.unflushed, 1);
+            
+            let items: Vec<(&[u8], Option<Vec<u8>>)> = vec![
+                (b"one", None),
+                (b"two", Some(b"meta".to_vec())),
+                (b"one", None),
+                (b"already here", None),
+            ];
+            signatures = storage.store_batch(&items).unwrap();
+            assert_eq!(signatures.len(), 4);
+            assert_eq!((signatures[0], signatures[3]), (signatures[2], existing));
+            assert_eq!(storage.dedup_stats().deduplicated, 2);
+            
+            // Two new packets bring it to three writes, which is one flush
+            assert_eq!(storage.unflushed, 0);
+            assert_eq!(storage.store_batch(&[]).unwrap(), Vec::<[u8; 32]>::new());
+        }
+        
+        let storage = Mem8Lite::new(&path, 1.0).unwrap();
+        assert_eq!(storage.stats().packet_count, 3);
+        assert_eq!(storage.retrieve(&signatures[0]).unwrap(), b"one");
+        assert_eq!(storage.get_metadata(&signatures[1]), Some(b"meta".to_vec()));
+        assert!(storage.recovery().is_clean());
+    }
+    
+    #[test]
+    fn test_set_metadata_appends_updates_and_keeps_history() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("meta.m8");
+        let data = vec![7u8; 64 * 1024];
+        let sig;
+        {
+            let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+            sig = storage.store(&data, Some(b"v1".to_vec())).unwrap();
+            let before = storage.position;
+            storage.set_metadata(&sig, b"v2".to_vec()).unwrap();
+            storage.set_metadata(&sig, b"v3".to_vec()).unwrap();
+            
+            // Two small records, not two copies of the data
+            assert!(storage.position - before < 200);
+            assert_eq!(storage.get_metadata(&sig), Some(b"v3".to_vec()));
+            assert!(storage.set_metadata(&[9u8; 32], b"nope".to_vec()).is_err());
+        }
+        
+        let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+        assert!(storage.recovery().is_clean());
+        assert_eq!(storage.get_metadata(&sig), Some(b"v3".to_vec()));
+        assert_eq!(storage.retrieve(&sig).unwrap(), data);
+        let history: Vec<_> = storage.metadata_history(&sig).unwrap().into_iter()
+            .map(|version| version.metadata.unwrap())
+            .collect();
+        assert_eq!(history, vec![b"v1".to_vec(), b"v2".to_vec(), b"v3".to_vec()]);
+        assert_eq!(storage.iter().next().unwrap().metadata_len, 2);
+        
+        // Compaction keeps the latest version only
+        storage.compact().unwrap();
+        assert_eq!(storage.get_metadata(&sig), Some(b"v3".to_vec()));
+        drop(storage);
+        let storage = Mem8Lite::new(&path, 1.0).unwrap();
+        assert_eq!(storage.metadata_history(&sig).unwrap().len(), 2);
+        assert_eq!(storage.get_metadata(&sig), Some(b"v3".to_vec()));
+        
+        // Sealed like the packets in an encrypted store
+        let sealed_path = dir.path().join("sealed.m8");
+        let key = [5u8; 32];
+        {
+            let mut storage = Mem8Lite::new_encrypted(&sealed_path, 1.0, &key).unwrap();
+            let sig = storage.store(b"secret", None).unwrap();
+            storage.set_metadata(&sig, b"private note".to_vec()).unwrap();
+        }
+        let raw = std::fs::read(&sealed_path).unwrap();
+        assert!(!raw.windows(12).any(|w| w == b"private note"));
+        let storage = Mem8Lite::new_encrypted(&sealed_path, 1.0, &key).unwrap();
+        let sig = storage.iter().next().unwrap().signature;
+        assert_eq!(storage.get_metadata(&sig), Some(b"private note".to_vec()));
+    }
+    
+    #[test]
+    fn test_keyed_store_survives_reopen_and_keeps_history() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("keyed.m8");
+        let (first, second);
+        {
+            let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+            first = storage.store_keyed("config.json", br#"{"fast": true}"#, None).unwrap();
+            second = storage.store_keyed("config.json", br#"{"fast": "very"}"#, Some(b"v2".to_vec())).unwrap();
+            storage.store_keyed("notes/today", b"waves", None).unwrap();
+            storage.store_keyed("scratch", b"temporary", None).unwrap();
+            assert_eq!(storage.delete_key("scratch").unwrap(), content_signature(b"temporary", None));
+            assert!(storage.delete_key("scratch").is_err());
+            
+            // Re-storing the same content under the same key changes nothing
+            let before = storage.position;
+            storage.store_keyed("notes/today", b"waves", None).unwrap();
+            assert_eq!(storage.position, before);
+        }
+        
+        let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+        assert!(storage.recovery().is_clean());
+        assert_eq!(storage.retrieve_keyed("config.json").unwrap(), br#"{"fast": "very"}"#);
+        assert_eq!(storage.key_signature("config.json"), Some(second));
+        assert_eq!(storage.history_for_key("config.json"), vec![first]);
+        assert_eq!(storage.retrieve(&first).unwrap(), br#"{"fast": true}"#); // Old packet untouched
+        assert_eq!(storage.keys().collect::<Vec<_>>(), ["config.json", "notes/today"]);
+        assert_eq!(storage.history_for_key("scratch").len(), 1);
+        assert!(storage.retrieve_keyed("scratch").is_err());
+        
+        // Compaction and a rebuilt index keep keys and history
+        storage.compact().unwrap();
+        drop(storage);
+        std::fs::remove_file(index_path(&path)).unwrap();
+        let storage = Mem8Lite::new(&path, 1.0).unwrap();
+        assert_eq!(storage.key_signature("config.json"), Some(second));
+        assert_eq!(storage.history_for_key("config.json"), vec![first]);
+        assert_eq!(storage.keys().count(), 2);
+        
+        // Key names are sealed in encrypted stores
+        let sealed_path = dir.path().join("sealed.m8");
+        let key = [3u8; 32];
+        {
+            let mut storage = Mem8Lite::new_encrypted(&sealed_path, 1.0, &key).unwrap();
+            storage.store_keyed("secret-plans", b"nap", None).unwrap();
+        }
+        let raw = std::fs::read(&sealed_path).unwrap();
+        assert!(!raw.windows(12).any(|w| w == b"secret-plans"));
+        let storage = Mem8Lite::new_encrypted(&sealed_path, 1.0, &key).unwrap();
+        assert_eq!(storage.retrieve_keyed("secret-plans").unwrap(), b"nap");
+    }
+    
+    #[test]
+    fn test_ttl_expires_packets_on_the_injected_clock() {
+        use crate::clock::ManualClock;
+        
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("ttl.m8");
+        let clock = Arc::new(ManualClock::new(1_700_000_000));
+        let open = || Mem8Lite::new(&path, 1.618).unwrap().with_clock(clock.clone());
+        
+        let mut storage = open();
+        let keep = storage.store(b"forever", None).unwrap();
+        let brief = storage.store_with_ttl(b"brief", Some(b"m".to_vec()), Duration::from_secs(60)).unwrap();
+        let longer = storage.store_with_ttl(b"longer", None, Duration::from_secs(3600)).unwrap();
+        assert_eq!(storage.expires_at(&brief), Some(1_700_000_060));
+        assert_eq!(storage.expires_at(&keep), None);
+        assert_eq!(storage.retrieve(&brief).unwrap(), b"brief");
+        
+        clock.advance(Duration::from_secs(60));
+        let err = storage.retrieve(&brief).unwrap_err();
+        assert_eq!(err.downcast_ref::<Expired>(), Some(&Expired { signature: brief, expired_at: 1_700_000_060 }));
+        assert!(storage.len(&brief).unwrap_err().downcast_ref::<Expired>().is_some());
+        assert!(storage.retrieve_range(&brief, 0, 2).is_err());
+        assert_eq!(storage.get_metadata(&brief), None);
+        assert!(!storage.contains(&brief));
+        assert_eq!(storage.iter().count(), 2);
+        assert_eq!(storage.retrieve(&longer).unwrap(), b"longer");
+        
+        // Deadlines survive a reopen and a rebuilt index
+        drop(storage);
+        std::fs::remove_file(index_path(&path)).unwrap();
+        let mut storage = open();
+        assert!(storage.is_expired(&brief));
+        assert_eq!(storage.expires_at(&longer), Some(1_700_003_600));
+        
+        assert_eq!(storage.purge_expired().unwrap(), 1);
+        assert_eq!(storage.purge_expired().unwrap(), 0);
+        assert!(storage.retrieve(&brief).unwrap_err().to_string().contains("deleted"));
+        
+        // Compaction keeps the remaining deadline with its packet
+        storage.compact().unwrap();
+        drop(storage);
+        let mut storage = open();
+        assert_eq!(storage.expires_at(&longer), Some(1_700_003_600));
+        assert_eq!(storage.iter_packets().find(|p| p.signature == longer).unwrap().expires_at, Some(1_700_003_600));
+        
+        // Storing expired content again makes a fresh copy without the old deadline
+        clock.advance(Duration::from_secs(3600));
+        assert!(storage.is_expired(&longer));
+        assert_eq!(storage.store(b"longer", None).unwrap(), longer);
+        assert_eq!(storage.expires_at(&longer), None);
+        assert_eq!(storage.retrieve(&longer).unwrap(), b"longer");
+        
+        // Expired packets that were never purged don't survive compaction either
+        storage.store_with_ttl(b"gone soon", None, Duration::ZERO).unwrap();
+        storage.compact().unwrap();
+        assert_eq!(storage.iter().count(), 2);
+        assert_eq!(storage.retrieve(&keep).unwrap(), b"forever");
+    }
+    
+    #[test]
+    fn test_find_by_time_and_metadata_survive_reopen_and_rebuild() {
+        use crate::clock::ManualClock;
+        
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("recall.m8");
+        let clock = Arc::new(ManualClock::new(1_700_000_000));
+        let open = || Mem8Lite::new(&path, 1.618).unwrap().with_clock(clock.clone());
+        let meta = |mood: &str| Some(format!("{{\"mood\":{{\"name\":\"{}\"}}}}", mood).into_bytes());
+        
+        let mut storage = open();
+        let dawn = storage.store(b"sunrise", meta("calm")).unwrap();
+        clock.advance(Duration::from_secs(3600));
+        let batch = storage.store_batch(&[(b"coffee", meta("awake")), (b"no metadata", None)]).unwrap();
+        clock.advance(Duration::from_secs(86_400));
+        let tomorrow = storage.store(b"rain", meta("calm")).unwrap();
+        let day = 1_700_000_000..1_700_086_400;
+        assert_eq!(storage.find_by_time(day.clone()), [vec![dawn], {
+            let mut same_second = batch.clone();
+            same_second.sort();
+            same_second
+        }].concat());
+        assert_eq!(storage.find_by_time(1_700_086_400..u64::MAX), vec![tomorrow]);
+        assert!(storage.find_by_time(5..5).is_empty());
+        
+        let calm = serde_json::json!("calm");
+        assert_eq!(storage.find_by_metadata_json("/mood/name", &calm), vec![dawn, tomorrow]);
+        assert_eq!(storage.find_by_metadata(|m| m.starts_with(b"{")).len(), 3);
+        storage.set_metadata(&dawn, meta("wonder").unwrap()).unwrap();
+        storage.delete(&tomorrow).unwrap();
+        assert!(storage.find_by_metadata_json("/mood/name", &calm).is_empty());
+        assert_eq!(storage.find_by_time(0..u64::MAX).len(), 3);
+        drop(storage);
+        
+        // Persisted, then rebuilt from the store file when the index goes missing
+        let expected = open().find_by_time(day.clone());
+        assert_eq!(expected.len(), 3);
+        std::fs::remove_file(timestamp_index_path(&path)).unwrap();
+        let mut storage = open();
+        assert_eq!(storage.find_by_time(day.clone()), expected);
+        assert!(timestamp_index_path(&path).exists());
+        
+        // Compaction moves every packet; the index follows
+        storage.compact().unwrap();
+        assert_eq!(storage.find_by_time(day.clone()), expected);
+        drop(storage);
+        let storage = open();
+        assert_eq!(storage.find_by_time(day), expected);
+        assert_eq!(storage.find_by_metadata_json("/mood/name", &serde_json::json!("wonder")), vec![dawn]);
+    }
+    
+    #[test]
+    fn test_top_wonder_survives_reopen_deletes_and_compaction() {
+        use crate::marine::{MarineMetadata, MarineProcessor};
+        
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("wonder.m8");
+        let analysed = |max_salience: f64| MarineMetadata { max_salience, ..MarineProcessor::new().extract_metadata(&[]) };
+        let block = |salience: f64| Some(format!("{{\"marine\":{{\"salience\":0.1,\"moments\":[{{\"frame\":3,\"salience\":{}}}]}}}}", salience).into_bytes());
         
-        // Update position
-        self.position += 8 + encoded.len() as u64;
+        let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+        let dim = storage.store_analyzed(b"dim", &analysed(0.25)).unwrap();
+        let bright = storage.store_analyzed(b"bright", &analysed(0.875)).unwrap();
+        let track = storage.store(b"track", block(0.5)).unwrap();
+        let plain = storage.store(b"plain", Some(b"{\"mood\":\"calm\"}".to_vec())).unwrap();
+        storage.store_batch(&[(b"batched", block(0.625))]).unwrap();
+        let ranked = |storage: &Mem8Lite| storage.top_wonder(10).iter().map(|&(_, salience, _)| salience).collect::<Vec<_>>();
+        assert_eq!(ranked(&storage), [0.875, 0.625, 0.5, 0.25]);
+        let (top, _, stored_at) = storage.top_wonder(1)[0];
+        assert_eq!((top, Some(stored_at)), (bright, storage.stored_at(&bright)));
         
-        Ok(())
+        // New metadata moves a packet up (or out); a delete takes it out
+        storage.set_metadata(&plain, block(0.75).unwrap()).unwrap();
+        storage.set_metadata(&track, b"{}".to_vec()).unwrap();
+        storage.delete(&dim).unwrap();
+        assert_eq!(ranked(&storage), [0.875, 0.75, 0.625]);
+        drop(storage);
+        
+        // Persisted, and rebuilt from the packets when the index goes missing
+        let storage = Mem8Lite::new(&path, 1.618).unwrap();
+        assert_eq!(ranked(&storage), [0.875, 0.75, 0.625]);
+        drop(storage);
+        std::fs::remove_file(wonder_index_path(&path)).unwrap();
+        let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+        assert_eq!(ranked(&storage), [0.875, 0.75, 0.625]);
+        
+        // Compaction moves everything, the index follows; stored again, a deleted packet is back
+        storage.compact().unwrap();
+        assert_eq!(ranked(&storage), [0.875, 0.75, 0.625]);
+        storage.store_analyzed(b"dim", &analysed(0.25)).unwrap();
+        drop(storage);
+        let storage = Mem8Lite::new(&path, 1.618).unwrap();
+        assert_eq!(ranked(&storage), [0.875, 0.75, 0.625, 0.25]);
+        assert_eq!(storage.top_wonder(2)[1].0, plain);
     }
     
-    /// Load existing packets into cache
-    fn load_cache(&mut self) -> Result<()> {
-        self.file.seek(SeekFrom::Start(0))?;
-        
-        loop {
-            // Try to read length prefix
-            let len = match self.file.read_u64::<BigEndian>() {
-                Ok(len) => len,
-                Err(_) => break, // End of file
+    #[test]
+    fn test_memory_group_lists_perspectives_by_timestamp() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("groups.m8");
+        let moment = b"the same three seconds of audio";
+        let (diary, archive);
+        {
+            let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+            let meta = |perspective: serde_json::Value, timestamp: u64| {
+                Some(serde_json::to_vec(&serde_json::json!({"perspective": perspective, "timestamp": timestamp})).unwrap())
             };
-            
-            // Read packet data
-            let mut buffer = vec![0u8; len as usize];
-            self.file.read_exact(&mut buffer)?;
-            
-            // Deserialize packet
-            if let Ok(packet) = bincode::deserialize::<WavePacket>(&buffer) {
-                self.cache.insert(packet.signature, packet);
-            }
+            archive = storage.store_linked("concert", moment, meta(serde_json::json!("third_party"), 300)).unwrap();
+            diary = storage.store_linked("concert", moment, meta(serde_json::json!({"type": "diary_writer", "name": "Hue"}), 100)).unwrap();
+            storage.store_linked("concert", moment, meta(serde_json::json!("shared_witness"), 200)).unwrap();
+            storage.store_linked("concert", moment, meta(serde_json::json!("shared_witness"), 200)).unwrap(); // Already in
+            storage.store_linked("other", b"unrelated", None).unwrap();
+            assert!(storage.store_linked("concert", moment, Some(b"not json".to_vec())).is_err());
         }
         
-        // Reset to end for appending
-        self.position = self.file.seek(SeekFrom::End(0))?;
+        let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+        let group = storage.get_memory_group("concert").unwrap();
+        let labels: Vec<(&str, u64)> = group.iter().map(|m| (m.perspective.as_str(), m.timestamp)).collect();
+        assert_eq!(labels, [("diary_writer", 100), ("shared_witness", 200), ("third_party", 300)]);
+        assert_eq!((group[0].signature, group[2].signature), (diary, archive));
+        let meta: serde_json::Value = serde_json::from_slice(&storage.get_metadata(&diary).unwrap()).unwrap();
+        assert_eq!(meta["memory_group"], "concert");
         
-        Ok(())
+        // Group indexes stay out of the keys; deleted members drop out
+        assert_eq!(storage.keys().count(), 0);
+        assert!(storage.store_keyed("\u{0}memory_group/concert", b"clobber", None).is_err());
+        assert!(storage.get_memory_group("nobody").unwrap().is_empty());
+        storage.delete(&archive).unwrap();
+        storage.compact().unwrap();
+        assert_eq!(storage.get_memory_group("concert").unwrap().len(), 2);
+        assert_eq!(storage.get_memory_group("other").unwrap()[0].perspective, "unlabelled");
     }
     
-    /// Load all packets into memory for maximum speed
-    /// 
-    /// Warning: Only use this with reasonable data sizes!
-    /// Returns the number of packets loaded.
-    pub fn load_all(&mut self) -> Result<usize> {
-        self.load_cache()?;
-        Ok(self.cache.len())
+    #[test]
+    fn test_disk_usage_stays_near_input_size() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("compact.m8");
+        let data: Vec<u8> = (0..1 << 20).map(|i| (i * 7 % 256) as u8).collect();
+        
+        let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+        let sig = storage.store(&data, None).unwrap();
+        let streamed = storage.store_stream(&data[..], Some(b"again".to_vec())).unwrap();
+        storage.sync().unwrap();
+        
+        // Two copies plus a few dozen bytes of framing each (was 16× per copy)
+        let on_disk = std::fs::metadata(&path).unwrap().len();
+        assert!(on_disk <= 2 * data.len() as u64 + 200, "{} bytes on disk", on_disk);
+        
+        // The waves are still there for the API, and decode to the same bytes
+        drop(storage);
+        let storage = Mem8Lite::new(&path, 1.618).unwrap();
+        assert_eq!(storage.retrieve(&sig).unwrap(), data);
+        assert_eq!(storage.retrieve(&streamed).unwrap(), data);
+        let packet = storage.iter_packets().next().unwrap();
+        assert_eq!(packet.waves.len(), data.len());
+        assert_eq!(decode_from_waves(&packet.waves, packet.frequency), data);
     }
     
-    /// Get statistics about the storage
-    pub fn stats(&self) -> StorageStats {
-        StorageStats {
-            packet_count: self.cache.len(),
-            total_size: self.position,
-            frequency: self.frequency,
-            cache_hits: 0, // Would track this in production
+    #[test]
+    fn test_reads_format_v1_packets() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("v1.m8");
+        
+        // A store as the old writer left it: bincode waves, no format bits
+        let data = b"old school waves".to_vec();
+        let legacy = WavePacket {
+            signature: blake3::hash(&data).into(),
+            waves: encode_to_waves(&data, 1.618),
+            metadata: Some(b"v1".to_vec()),
+            expires_at: None,
+            frequency: 1.618,
+            timestamp: 1_700_000_000,
+        };
+        let encoded = bincode::serialize(&legacy).unwrap();
+        let mut raw = Vec::new();
+        raw.write_u64::<BigEndian>(encoded.len() as u64).unwrap();
+        raw.extend_from_slice(&encoded);
+        std::fs::write(&path, &raw).unwrap();
+        
+        let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+        assert_eq!(storage.retrieve(&legacy.signature).unwrap(), data);
+        let mut out = Vec::new();
+        storage.retrieve_to(&legacy.signature, &mut out).unwrap();
+        assert_eq!(out, data);
+        let summary = storage.iter().next().unwrap();
+        assert_eq!((summary.data_len, summary.metadata_len), (data.len() as u64, 2));
+        assert_eq!(storage.retrieve_range(&legacy.signature, 4, 6).unwrap(), b"school");
+        
+        // New packets go in compact; compaction keeps both formats readable
+        let fresh = storage.store_string("new wave").unwrap();
+        storage.compact().unwrap();
+        drop(storage);
+        let storage = Mem8Lite::new(&path, 1.618).unwrap();
+        assert_eq!(storage.retrieve(&legacy.signature).unwrap(), data);
+        assert_eq!(storage.get_metadata(&legacy.signature), Some(b"v1".to_vec()));
+        assert_eq!(storage.retrieve_string(&fresh).unwrap(), "new wave");
+    }
+    
+    #[test]
+    fn test_reopening_at_another_frequency_reads_byte_exact() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("v1.m8");
+        
+        // An old store written at 1.0, waves and all
+        let data: Vec<u8> = (0..=255).collect();
+        let legacy = WavePacket {
+            signature: blake3::hash(&data).into(),
+            waves: encode_to_waves(&data, 1.0),
+            metadata: None,
+            expires_at: None,
+            frequency: 1.0,
+            timestamp: 1_700_000_000,
+        };
+        let encoded = bincode::serialize(&legacy).unwrap();
+        let mut raw = Vec::new();
+        raw.write_u64::<BigEndian>(encoded.len() as u64).unwrap();
+        raw.extend_from_slice(&encoded);
+        std::fs::write(&path, &raw).unwrap();
+        let fresh = {
+            let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+            storage.store_string("written at 1.0").unwrap()
+        };
+        
+        let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+        assert_eq!(storage.retrieve(&legacy.signature).unwrap(), data);
+        assert_eq!(storage.retrieve_range(&legacy.signature, 250, 10).unwrap(), [250, 251, 252, 253, 254, 255]);
+        assert_eq!(storage.retrieve_string(&fresh).unwrap(), "written at 1.0");
+        assert!(storage.iter().all(|summary| summary.frequency == 1.0));
+        
+        // Making it uniform rewrites both, and they still read back the same
+        assert_eq!(storage.reencode(1.618).unwrap(), 2);
+        assert_eq!(storage.reencode(1.618).unwrap(), 0);
+        drop(storage);
+        let storage = Mem8Lite::new(&path, 1.618).unwrap();
+        assert!(storage.iter().all(|summary| summary.frequency == 1.618));
+        assert_eq!(storage.retrieve(&legacy.signature).unwrap(), data);
+        assert_eq!(storage.retrieve_string(&fresh).unwrap(), "written at 1.0");
+        assert_eq!(storage.frequency_preset(), FrequencyPreset::GoldenRatio);
+    }
+    
+    #[test]
+    fn test_compressed_and_plain_packets_mix() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.m8");
+        let json: String = (0..200)
+            .map(|i| format!(r#"{{"kind":"wonder","namespace":"audio","salience":0.{}}},"#, i % 10))
+            .collect();
+        
+        let plain = {
+            let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+            storage.store(json.as_bytes(), Some(b"before".to_vec())).unwrap()
+        };
+        let (packed, tiny) = {
+            let mut storage = Mem8Lite::new(&path, 1.0).unwrap().with_compression(CompressionMode::Zstd(3));
+            let packed = storage.store(json.as_bytes(), Some(b"after".to_vec())).unwrap();
+            let tiny = storage.store_string("hi").unwrap(); // Doesn't shrink, so stays plain
+            (packed, tiny)
+        };
+        
+        // Either way round, both kinds read back
+        let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+        for sig in [&plain, &packed] {
+            assert_eq!(storage.retrieve(sig).unwrap(), json.as_bytes());
+            let mut streamed = Vec::new();
+            storage.retrieve_to(sig, &mut streamed).unwrap();
+            assert_eq!(streamed, json.as_bytes());
         }
+        assert_eq!(storage.retrieve_string(&tiny).unwrap(), "hi");
+        assert_eq!(storage.get_metadata(&packed).unwrap(), b"after");
+        let summary = storage.iter().find(|s| s.signature == packed).unwrap();
+        assert_eq!((summary.data_len, summary.metadata_len), (json.len() as u64, 5));
+        
+        let stats = storage.stats();
+        assert_eq!(stats.compressed_packets, 1);
+        assert!(stats.compression_ratio > 1.5, "ratio {}", stats.compression_ratio);
+        
+        // Compaction and a rescan keep the compressed packet as it is
+        storage.delete(&plain).unwrap();
+        storage.compact().unwrap();
+        drop(storage);
+        std::fs::remove_file(index_path(&path)).unwrap();
+        let storage = Mem8Lite::new(&path, 1.0).unwrap();
+        assert!(storage.recovery().is_clean());
+        assert_eq!(storage.retrieve(&packed).unwrap(), json.as_bytes());
+        assert_eq!(storage.stats().compressed_packets, 1);
     }
-}
-
-/// Storage statistics
-#[derive(Debug, Clone)]
-pub struct StorageStats {
-    pub packet_count: usize,
-    pub total_size: u64,
-    pub frequency: f64,
-    pub cache_hits: usize,
-}
-
-impl std::fmt::Display for StorageStats {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "🌊 Wave Storage Stats:\n")?;
-        write!(f, "  Packets: {}\n", self.packet_count)?;
-        write!(f, "  Size: {} bytes\n", self.total_size)?;
-        write!(f, "  Frequency: {}Hz\n", self.frequency)?;
-        write!(f, "  Cache hits: {}\n", self.cache_hits)?;
-        Ok(())
+    
+    #[test]
+    fn test_packets_from_the_old_lz_codec_still_read() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("old.m8");
+        let data = b"compressed before zstd, with the old built-in codec".to_vec();
+        let signature = content_signature(&data, None);
+        let packet = StoredPacket { signature, data: data.clone(), metadata: None, frequency: 1.0, timestamp: 1 };
+        
+        // What the old codec wrote: one all-literal sequence of the body
+        let body = bincode::serialize(&packet).unwrap()[32..].to_vec();
+        let mut payload = signature.to_vec();
+        payload.push(compress::MODE_LZ);
+        payload.extend_from_slice(&(body.len() as u64).to_le_bytes());
+        payload.push(0xF0);
+        let mut rest = body.len() - 15;
+        while rest >= 255 {
+            payload.push(255);
+            rest -= 255;
+        }
+        payload.push(rest as u8);
+        payload.extend_from_slice(&body);
+        let len = payload.len() as u64 + CHECKSUM_LEN;
+        let prefix = packet_prefix(PACKET_FORMAT_COMPRESSED, len);
+        let mut record = prefix.to_be_bytes().to_vec();
+        record.extend_from_slice(&payload);
+        record.extend_from_slice(&record_checksum(prefix, &payload));
+        {
+            let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+            storage.append_record(&signature, &record, len, 1).unwrap();
+        }
+        
+        // Read through the index, then again after a rescan
+        let storage = Mem8Lite::new(&path, 1.0).unwrap().with_compression(CompressionMode::Zstd(3));
+        assert_eq!(storage.retrieve(&signature).unwrap(), data);
+        drop(storage);
+        std::fs::remove_file(index_path(&path)).unwrap();
+        let storage = Mem8Lite::new(&path, 1.0).unwrap();
+        assert!(storage.recovery().is_clean());
+        assert_eq!(storage.retrieve(&signature).unwrap(), data);
+        assert_eq!(storage.stats().compressed_packets, 1);
     }
-}
-
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::tempdir;
     
     #[test]
-    fn test_store_and_retrieve() {
+    fn test_adaptive_compression_records_level_and_class() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("adaptive.m8");
+        let json: String = (0..400)
+            .map(|i| format!(r#"{{"kind":"wonder","namespace":"audio","salience":0.{}}},"#, i % 10))
+            .collect();
+        let mut noise = vec![0u8; 20_000];
+        let mut state = 0x2545_F491_4F6C_DD1Du64;
+        for byte in noise.iter_mut() {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            *byte = state as u8;
+        }
+        
+        let policy = AdaptivePolicy::default();
+        let mut storage = Mem8Lite::new(&path, 1.0).unwrap().with_compression(CompressionMode::Adaptive(policy));
+        let silence = storage.store(&vec![0u8; 50_000], None).unwrap();
+        let text = storage.store(json.as_bytes(), None).unwrap();
+        let random = storage.store(&noise, None).unwrap();
+        drop(storage);
+        
+        let storage = Mem8Lite::new(&path, 1.0).unwrap();
+        assert_eq!(storage.retrieve(&silence).unwrap(), vec![0u8; 50_000]);
+        assert_eq!(storage.retrieve(&text).unwrap(), json.as_bytes());
+        assert_eq!(storage.retrieve(&random).unwrap(), noise);
+        
+        let compression = |sig: &[u8; 32]| storage.iter().find(|s| &s.signature == sig).unwrap().compression;
+        let quiet = compression(&silence).unwrap();
+        assert_eq!((quiet.level, quiet.class), (Some(compress::MAX_LEVEL), Some(EntropyClass::Low)));
+        assert!(quiet.ratio > 50.0, "ratio {}", quiet.ratio);
+        let medium = compression(&text).unwrap();
+        assert_eq!((medium.level, medium.class), (Some(policy.medium_level), Some(EntropyClass::Medium)));
+        assert_eq!(compression(&random), None); // Fast level, didn't shrink, stored as is
+        
+        let stats = storage.stats();
+        assert_eq!(stats.compressed_packets, 2);
+        assert_eq!(stats.compression_by_class.keys().copied().collect::<Vec<_>>(), [EntropyClass::Low, EntropyClass::Medium]);
+        assert_eq!(stats.compression_by_class[&EntropyClass::Low].packets, 1);
+        assert_eq!(stats.compression_by_class[&EntropyClass::Low].ratio, quiet.ratio);
+    }
+    
+    #[test]
+    fn test_encrypted_store_round_trips() {
         let dir = tempdir().unwrap();
         let path = dir.path().join("test.m8");
+        let key = [42u8; 32];
+        let secret = "the wonder at 3:14 was a whale song. ".repeat(20);
         
-        let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+        let (sig, streamed, packed) = {
+            let mut storage = Mem8Lite::new_encrypted(&path, 1.0, &key).unwrap();
+            assert!(storage.store_capabilities().encrypted);
+            let sig = storage.store(secret.as_bytes(), Some(b"private note".to_vec())).unwrap();
+            let streamed = storage.store_stream(&b"streamed secret"[..], None).unwrap();
+            let mut storage = storage.with_compression(CompressionMode::Zstd(3));
+            let packed = storage.store(secret.as_bytes(), Some(b"squeezed".to_vec())).unwrap();
+            (sig, streamed, packed)
+        };
         
-        // Store some data
-        let data = b"Hello, waves!";
-        let sig = storage.store(data, None).unwrap();
+        // The signature is still over the plaintext, but none of it is on disk
+        let mut hasher = Hasher::new();
+        hasher.update(secret.as_bytes());
+        hasher.update(b"private note");
+        assert_eq!(sig, <[u8; 32]>::from(hasher.finalize()));
+        let bytes = std::fs::read(&path).unwrap();
+        for needle in [&b"whale song"[..], b"private note", b"streamed secret", b"squeezed"] {
+            assert!(!bytes.windows(needle.len()).any(|w| w == needle));
+        }
         
-        // Retrieve it back
-        let retrieved = storage.retrieve(&sig).unwrap();
-        assert_eq!(retrieved, data);
+        // Rescanned from scratch, everything reads back
+        std::fs::remove_file(index_path(&path)).unwrap();
+        let storage = Mem8Lite::new_encrypted(&path, 1.0, &key).unwrap();
+        assert!(storage.recovery().is_clean());
+        assert_eq!(storage.retrieve_string(&sig).unwrap(), secret);
+        assert_eq!(storage.get_metadata(&sig).unwrap(), b"private note");
+        assert_eq!(storage.retrieve(&streamed).unwrap(), b"streamed secret");
+        let mut out = Vec::new();
+        storage.retrieve_to(&packed, &mut out).unwrap();
+        assert_eq!(out, secret.as_bytes());
+        let summary = storage.iter().find(|s| s.signature == sig).unwrap();
+        assert_eq!((summary.data_len, summary.metadata_len), (secret.len() as u64, 12));
     }
     
     #[test]
-    fn test_metadata() {
+    fn test_encrypted_store_rejects_wrong_key() {
         let dir = tempdir().unwrap();
         let path = dir.path().join("test.m8");
+        let sig = {
+            let mut storage = Mem8Lite::new_encrypted(&path, 1.0, &[1u8; 32]).unwrap();
+            storage.store_string("for key one only").unwrap()
+        };
         
-        let mut storage = Mem8Lite::new(&path, 2.0).unwrap();
+        let err = Mem8Lite::new_encrypted(&path, 1.0, &[2u8; 32]).err().unwrap();
+        assert!(err.to_string().contains("Wrong key"));
+        assert!(err.downcast_ref::<AuthenticationFailed>().is_some());
+        let err = Mem8Lite::new(&path, 1.0).err().unwrap();
+        assert!(err.to_string().contains("new_encrypted"));
         
-        // Store with metadata
-        let data = b"Secret message";
-        let metadata = b"user:hue,type:secret";
-        let sig = storage.store(data, Some(metadata.to_vec())).unwrap();
+        // Without the key check, the packets themselves give a wrong key away
+        std::fs::remove_file(key_check_path(&path)).unwrap();
+        assert!(Mem8Lite::new_encrypted(&path, 1.0, &[2u8; 32]).is_err());
+        let storage = Mem8Lite::new_encrypted(&path, 1.0, &[1u8; 32]).unwrap();
+        assert_eq!(storage.retrieve_string(&sig).unwrap(), "for key one only");
+        assert!(key_check_path(&path).exists());
+    }
+    
+    #[test]
+    fn test_encrypted_packet_tampering_is_detected() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.m8");
+        let key = [9u8; 32];
+        let (a, b, b_start) = {
+            let mut storage = Mem8Lite::new_encrypted(&path, 1.0, &key).unwrap();
+            let a = storage.store_string("alpha secret").unwrap();
+            let b_start = storage.stats().total_size as usize;
+            (a, storage.store_string("bravo secret").unwrap(), b_start)
+        };
         
-        // Retrieve metadata
-        let meta = storage.get_metadata(&sig).unwrap();
-        assert_eq!(meta, metadata);
+        // Flip a bit of alpha's ciphertext and fix up its checksum, as an attacker could
+        let mut bytes = std::fs::read(&path).unwrap();
+        let start = FILE_HEADER_LEN as usize;
+        bytes[start + 8 + 32 + 24 + 3] ^= 0x01;
+        let prefix = u64::from_be_bytes(bytes[start..start + 8].try_into().unwrap());
+        let checksum = record_checksum(prefix, &bytes[start + 8..b_start - 8]);
+        bytes[b_start - 8..b_start].copy_from_slice(&checksum);
+        std::fs::write(&path, &bytes).unwrap();
+        
+        let storage = Mem8Lite::new_encrypted(&path, 1.0, &key).unwrap();
+        let err = storage.retrieve(&a).unwrap_err();
+        assert!(err.downcast_ref::<AuthenticationFailed>().is_some());
+        assert!(storage.retrieve_to(&a, Vec::new()).is_err());
+        assert_eq!(storage.retrieve_string(&b).unwrap(), "bravo secret");
     }
     
     #[test]
-    fn test_persistence() {
+    fn test_iter_lists_packets_and_skips_corrupt_ones() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("iter.m8");
+        
+        let (first, second, third) = {
+            let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+            let first = storage.store(b"first wave", Some(b"meta".to_vec())).unwrap();
+            let second = storage.store_string("second wave").unwrap();
+            let third = storage.store_string("third wave").unwrap();
+            (first, second, third)
+        };
+        
+        let storage = Mem8Lite::new(&path, 1.618).unwrap();
+        let summaries: Vec<_> = storage.iter().collect();
+        assert_eq!(summaries.len(), 3);
+        assert_eq!(summaries[0].signature, first);
+        assert_eq!(summaries[0].metadata_len, 4);
+        assert_eq!(summaries[0].data_len, 10);
+        assert_eq!(summaries[1].metadata_len, 0);
+        assert!(summaries.iter().all(|s| s.timestamp > 0 && s.frequency == 1.618));
+        assert_eq!(storage.stats().cached_packets, 0); // Headers only
+        
+        let packets: Vec<_> = storage.iter_packets().collect();
+        assert_eq!(packets.len(), 3);
+        assert_eq!(packets[2].signature, third);
+        drop(storage);
+        
+        // Scribble over the middle packet's wave count and chop the last one short
+        let location = Mem8Lite::new(&path, 1.618).unwrap().index[&second];
+        let mut raw = std::fs::read(&path).unwrap();
+        raw[location.offset as usize + 32..location.offset as usize + 40].copy_from_slice(&[0xff; 8]);
+        std::fs::write(&path, &raw).unwrap();
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.set_len(raw.len() as u64 - 5).unwrap();
+        
+        let storage = Mem8Lite::new(&path, 1.618).unwrap();
+        let listed: Vec<_> = storage.iter().map(|s| s.signature).collect();
+        assert_eq!(listed, vec![first]);
+        let packets: Vec<_> = storage.iter_packets().map(|p| p.signature).collect();
+        assert_eq!(packets, vec![first]);
+    }
+    
+    #[test]
+    fn test_cache_stays_within_limit() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("bounded.m8");
+        let limit = 256 * 1024;
+        
+        // 16 KiB per packet, ~3 MiB stored in total
+        let mut storage = Mem8Lite::with_cache_limit(&path, 1.618, limit).unwrap();
+        let sigs: Vec<_> = (0..200u32)
+            .map(|i| storage.store(&vec![(i % 256) as u8; 16 * 1024], Some(i.to_le_bytes().to_vec())).unwrap())
+            .collect();
+        let stats = storage.stats();
+        assert!(stats.cache_bytes <= limit);
+        assert!(stats.cached_packets < 200);
+        
+        // Everything still reads back; old packets come from disk
+        for (i, sig) in sigs.iter().enumerate() {
+            assert_eq!(storage.retrieve(sig).unwrap(), vec![(i % 256) as u8; 16 * 1024]);
+            assert!(storage.stats().cache_bytes <= limit);
+        }
+        let stats = storage.stats();
+        assert!(stats.cache_misses > 0);
+        
+        // The most recently read packet is now a hit
+        storage.retrieve(&sigs[199]).unwrap();
+        assert_eq!(storage.stats().cache_hits, stats.cache_hits + 1);
+        
+        // Warming a fresh handle stops at the limit too
+        drop(storage);
+        let mut storage = Mem8Lite::with_cache_limit(&path, 1.618, limit).unwrap();
+        let loaded = storage.load_all().unwrap();
+        assert!(loaded > 0 && loaded < 200);
+        assert!(storage.stats().cache_bytes <= limit);
+    }
+    
+    #[test]
+    fn test_retrieve_from_disk_without_loading_everything() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("big.m8");
+        
+        let sigs: Vec<_> = {
+            let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+            (0..100_000)
+                .map(|i| storage.store_string(&format!("packet #{}", i)).unwrap())
+                .collect()
+        };
+        
+        // Reopen: nothing is cached, but everything is reachable
+        let storage = Mem8Lite::new(&path, 1.618).unwrap();
+        assert_eq!(storage.stats().cached_packets, 0);
+        assert_eq!(storage.stats().packet_count, 100_000);
+        for i in [0, 1, 4_242, 77_777, 99_999] {
+            assert_eq!(storage.retrieve_string(&sigs[i]).unwrap(), format!("packet #{}", i));
+        }
+        assert_eq!(storage.stats().cached_packets, 5); // Only what was read
+    }
+    
+    #[test]
+    fn test_retrieve_range_reads_only_the_window() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("audio.m8");
+        let audio: Vec<u8> = (0..200_000u32).map(|i| (i * 7 % 251) as u8).collect();
+        let (plain, packed) = {
+            let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+            let plain = storage.store(&audio, None).unwrap();
+            let mut storage = storage.with_compression(CompressionMode::Zstd(3));
+            let packed = storage.store(&[b"beat".as_slice(); 5_000].concat(), None).unwrap();
+            (plain, packed)
+        };
+        
+        let storage = Mem8Lite::new(&path, 1.618).unwrap();
+        assert_eq!(storage.len(&plain).unwrap(), audio.len());
+        assert_eq!(storage.retrieve_range(&plain, 44_100, 1_000).unwrap(), &audio[44_100..45_100]);
+        assert_eq!(storage.stats().cached_packets, 0); // Never decoded whole
+        
+        // Ranges past the end are cut short; offsets past the end are errors
+        assert_eq!(storage.retrieve_range(&plain, 199_990, 100).unwrap(), &audio[199_990..]);
+        assert!(storage.retrieve_range(&plain, audio.len(), 10).unwrap().is_empty());
+        let err = storage.retrieve_range(&plain, audio.len() + 1, 10).unwrap_err();
+        assert!(err.to_string().contains("past the end"), "{}", err);
+        
+        assert_eq!(storage.len(&packed).unwrap(), 20_000);
+        assert_eq!(storage.retrieve_range(&packed, 2, 4).unwrap(), b"atbe");
+        drop(storage);
+        
+        // A flipped byte still fails the first range read after opening
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[8 + 40 + 150_000] ^= 0xff;
+        std::fs::write(&path, &bytes).unwrap();
+        let storage = Mem8Lite::new(&path, 1.618).unwrap();
+        let err = storage.retrieve_range(&plain, 0, 10).unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"), "{}", err);
+    }
+    
+    #[test]
+    fn test_estimate_stored_size_matches_real_writes() {
+        let dir = tempdir().unwrap();
+        let mut state = 0x9E37_79B9_7F4A_7C15u64;
+        let noise: Vec<u8> = (0..64 * 1024).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as u8
+        }).collect();
+        let text: Vec<u8> = b"Hue's waves at 3:14 - ".iter().copied().cycle().take(64 * 1024).collect();
+        // Text and noise in turns, the noise fresh each time: zstd's window
+        // would find a repeat of it that the samples can't
+        let long: Vec<u8> = (0..3usize << 20).map(|i| match (i >> 16) % 2 {
+            0 => text[i % text.len()],
+            _ => {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            }
+        }).collect();
+        let metadata = br#"{"type":"audio","length":180}"#.to_vec();
+        
+        let key = [7u8; cipher::KEY_LEN];
+        let modes = [
+            ("raw", CompressionMode::None, false),
+            ("zstd", CompressionMode::Zstd(6), false),
+            ("adaptive", CompressionMode::Adaptive(AdaptivePolicy::default()), false),
+            ("sealed", CompressionMode::Zstd(3), true),
+        ];
+        for (name, mode, encrypted) in modes {
+            let path = dir.path().join(format!("{}.m8", name));
+            let storage = match encrypted {
+                true => Mem8Lite::new_encrypted(&path, 1.0, &key).unwrap(),
+                false => Mem8Lite::new(&path, 1.0).unwrap(),
+            };
+            let mut storage = storage.with_compression(mode);
+            
+            for (data, meta) in [(&b""[..], None), (&text, Some(&metadata)), (&noise, None), (&long, Some(&metadata))] {
+                let estimate = storage.estimate_stored_size(data, meta.map_or(0, |m| m.len()));
+                assert_eq!(estimate.sampled, data.len() > ESTIMATE_SAMPLE_BYTES);
+                let before = (storage.position, std::fs::metadata(index_path(&path)).unwrap().len());
+                storage.store_force(data, meta.cloned()).unwrap();
+                let packet_bytes = storage.position - before.0;
+                assert_eq!(std::fs::metadata(index_path(&path)).unwrap().len() - before.1, estimate.index_bytes);
+                
+                let tolerance = if matches!(mode, CompressionMode::None) {
+                    0.0
+                } else if estimate.sampled {
+                    0.10 * packet_bytes as f64
+                } else {
+                    0.05 * packet_bytes as f64 + meta.map_or(0, |m| m.len()) as f64
+                };
+                let error = (estimate.packet_bytes as f64 - packet_bytes as f64).abs();
+                assert!(error <= tolerance, "{} {}: estimated {} for {}", name, data.len(), estimate.packet_bytes, packet_bytes);
+            }
+        }
+        
+        let storage = Mem8Lite::new_encrypted(dir.path().join("sealed.m8"), 1.0, &key).unwrap();
+        assert_eq!(storage.estimate_stored_size(&noise, 0).codec, CODEC_XCHACHA20_POLY1305);
+        let storage = Mem8Lite::new(dir.path().join("zstd.m8"), 1.0).unwrap().with_compression(CompressionMode::Zstd(6));
+        let estimate = storage.estimate_stored_size(&text, 0);
+        assert_eq!(estimate.codec, CODEC_ZSTD);
+        assert!(estimate.compression.unwrap().ratio > 5.0);
+        assert_eq!(storage.estimate_stored_size(&noise, 0).codec, CODEC_RAW_BYTES);
+    }
+    
+    #[cfg(feature = "mmap")]
+    #[test]
+    fn test_mmap_reads_follow_the_growing_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("mapped.m8");
+        
+        // An empty store is never mapped, so a miss is just a miss
+        let mut storage = Mem8Lite::with_cache_limit(&path, 1.618, 0).unwrap().with_read_mode(ReadMode::Mmap);
+        assert_eq!(storage.read_mode(), ReadMode::Mmap);
+        assert!(storage.retrieve(&[7u8; 32]).is_err());
+        
+        // Each append grows the file past the mapping made by the read before
+        let mut sigs = Vec::new();
+        for i in 0..5u8 {
+            let data = vec![i; 10_000 + i as usize];
+            sigs.push(storage.store(&data, Some(vec![i])).unwrap());
+            assert_eq!(storage.retrieve(&sigs[i as usize]).unwrap(), data);
+        }
+        let keyed = storage.store_keyed("latest", b"fresh waves", None).unwrap();
+        assert_eq!(storage.key_signature("latest"), Some(keyed));
+        
+        // Compaction swaps the file out from under the mapping
+        storage.delete(&sigs[0]).unwrap();
+        storage.compact().unwrap();
+        assert_eq!(storage.retrieve(&sigs[3]).unwrap(), vec![3u8; 10_003]);
+        assert_eq!(storage.retrieve_range(&sigs[2], 5, 3).unwrap(), vec![2u8; 3]);
+        drop(storage);
+        
+        // Same bytes as a buffered read of the reopened file
+        let mapped = Mem8Lite::with_cache_limit(&path, 1.618, 0).unwrap().with_read_mode(ReadMode::Mmap);
+        let buffered = Mem8Lite::with_cache_limit(&path, 1.618, 0).unwrap();
+        for sig in &sigs[1..] {
+            assert_eq!(mapped.retrieve(sig).unwrap(), buffered.retrieve(sig).unwrap());
+            assert_eq!(mapped.get_metadata(sig), buffered.get_metadata(sig));
+        }
+        assert_eq!(mapped.retrieve_keyed("latest").unwrap(), b"fresh waves");
+    }
+    
+    #[test]
+    fn test_index_rebuilt_when_missing() {
         let dir = tempdir().unwrap();
         let path = dir.path().join("test.m8");
         
         let sig = {
             let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
-            storage.store_string("Persistent waves!").unwrap()
+            storage.store_string("first").unwrap();
+            storage.store(b"second", Some(b"meta".to_vec())).unwrap()
         };
+        std::fs::remove_file(index_path(&path)).unwrap();
         
-        // Open storage again
+        let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+        assert_eq!(storage.retrieve(&sig).unwrap(), b"second");
+        assert_eq!(storage.get_metadata(&sig).unwrap(), b"meta");
+        
+        // Appending after a read still lands at the end of the file
+        let third = storage.store_string("third").unwrap();
         let storage = Mem8Lite::new(&path, 1.0).unwrap();
-        let retrieved = storage.retrieve_string(&sig).unwrap();
-        assert_eq!(retrieved, "Persistent waves!");
+        assert_eq!(storage.retrieve_string(&third).unwrap(), "third");
+        assert_eq!(storage.retrieve(&sig).unwrap(), b"second");
+    }
+    
+    #[test]
+    fn test_recovers_from_truncation_at_any_offset() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.m8");
+        
+        let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+        let mut packets = Vec::new();
+        for (i, text) in ["first", "second", "third"].iter().enumerate() {
+            let sig = if i == 2 {
+                storage.store_stream(text.as_bytes(), Some(b"meta".to_vec())).unwrap()
+            } else {
+                storage.store_string(text).unwrap()
+            };
+            packets.push((sig, text.to_string(), storage.stats().total_size));
+        }
+        storage.close().unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        let index = std::fs::read(index_path(&path)).unwrap();
+        
+        // With the index intact (it got flushed ahead of the data) and without it
+        for keep_index in [true, false] {
+            for cut in 0..=bytes.len() {
+                let torn = dir.path().join(format!("torn-{}-{}.m8", keep_index, cut));
+                std::fs::write(&torn, &bytes[..cut]).unwrap();
+                if keep_index {
+                    std::fs::write(index_path(&torn), &index).unwrap();
+                }
+                
+                let mut storage = Mem8Lite::new(&torn, 1.0).unwrap();
+                let header = if (cut as u64) < FILE_HEADER_LEN { 0 } else { FILE_HEADER_LEN };
+                let boundary = packets.iter().map(|p| p.2).filter(|&end| end <= cut as u64).max().unwrap_or(header);
+                assert_eq!(storage.recovery().truncated_bytes, cut as u64 - boundary, "cut at {}", cut);
+                for (sig, text, end) in &packets {
+                    if *end <= cut as u64 {
+                        assert_eq!(&storage.retrieve_string(sig).unwrap(), text, "cut at {}", cut);
+                    } else {
+                        assert!(!storage.contains(sig), "cut at {}", cut);
+                    }
+                }
+                
+                // Appends land on the clean boundary and the store reopens cleanly
+                let fourth = storage.store_string("fourth").unwrap();
+                drop(storage);
+                let storage = Mem8Lite::new(&torn, 1.0).unwrap();
+                assert!(storage.recovery().is_clean(), "cut at {}", cut);
+                assert_eq!(storage.retrieve_string(&fourth).unwrap(), "fourth");
+            }
+        }
+    }
+    
+    #[test]
+    fn test_file_header_is_checked_on_open() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.m8");
+        let (a, b) = {
+            let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+            assert_eq!(storage.format_version(), FILE_FORMAT_VERSION);
+            let a = storage.store_string("alpha").unwrap();
+            let b = storage.store_string("bravo").unwrap();
+            storage.close().unwrap();
+            (a, b)
+        };
+        let bytes = std::fs::read(&path).unwrap();
+        assert_eq!(&bytes[..4], FILE_MAGIC);
+        assert_eq!(&bytes[8..16], &1.618f64.to_be_bytes());
+        
+        // Not a store at all, or one from a newer build: typed errors, nothing created
+        let junk = dir.path().join("notes.txt");
+        std::fs::write(&junk, "just some notes, definitely not waves").unwrap();
+        let err = Mem8Lite::new(&junk, 1.0).err().unwrap();
+        assert_eq!(err.downcast_ref::<FileFormatError>(), Some(&FileFormatError::NotAStore));
+        assert!(!index_path(&junk).exists());
+        let future = dir.path().join("future.m8");
+        let mut newer = bytes.clone();
+        newer[4..6].copy_from_slice(&(FILE_FORMAT_VERSION + 1).to_be_bytes());
+        std::fs::write(&future, &newer).unwrap();
+        let err = Mem8Lite::new(&future, 1.0).err().unwrap();
+        assert_eq!(
+            err.downcast_ref::<FileFormatError>(),
+            Some(&FileFormatError::UnsupportedVersion { found: FILE_FORMAT_VERSION + 1, supported: FILE_FORMAT_VERSION })
+        );
+        
+        // A headerless store reads and appends as it is, and compact migrates it
+        let legacy = dir.path().join("legacy.m8");
+        std::fs::write(&legacy, &bytes[FILE_HEADER_LEN as usize..]).unwrap();
+        let mut storage = Mem8Lite::new(&legacy, 1.618).unwrap();
+        assert_eq!(storage.format_version(), 0);
+        assert!(storage.recovery().is_clean());
+        assert_eq!(storage.retrieve_string(&a).unwrap(), "alpha");
+        let c = storage.store_string("charlie").unwrap();
+        storage.delete(&b).unwrap();
+        storage.compact().unwrap();
+        assert_eq!(storage.format_version(), FILE_FORMAT_VERSION);
+        drop(storage);
+        
+        let storage = Mem8Lite::new(&legacy, 1.618).unwrap();
+        assert_eq!(storage.format_version(), FILE_FORMAT_VERSION);
+        assert_eq!(&std::fs::read(&legacy).unwrap()[..4], FILE_MAGIC);
+        assert_eq!(storage.retrieve_string(&a).unwrap(), "alpha");
+        assert_eq!(storage.retrieve_string(&c).unwrap(), "charlie");
+        assert!(!storage.contains(&b));
+    }
+    
+    #[test]
+    fn test_checksum_mismatch_drops_only_that_packet() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.m8");
+        
+        let (a, b, c, b_start) = {
+            let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+            let a = storage.store_string("alpha").unwrap();
+            let b_start = storage.stats().total_size;
+            let b = storage.store_string("bravo").unwrap();
+            let c = storage.store_string("charlie").unwrap();
+            (a, b, c, b_start)
+        };
+        
+        // Flip one bit of bravo's data
+        let mut bytes = std::fs::read(&path).unwrap();
+        bytes[b_start as usize + 8 + 40] ^= 0x01;
+        std::fs::write(&path, &bytes).unwrap();
+        
+        let storage = Mem8Lite::new(&path, 1.0).unwrap();
+        let err = storage.retrieve(&b).unwrap_err();
+        assert!(err.to_string().contains("Checksum mismatch"));
+        assert!(storage.retrieve_to(&b, Vec::new()).is_err());
+        assert_eq!(storage.retrieve_string(&c).unwrap(), "charlie");
+        drop(storage);
+        
+        // Rescanned without the index, the bad packet is skipped and the rest survive
+        std::fs::remove_file(index_path(&path)).unwrap();
+        let storage = Mem8Lite::new(&path, 1.0).unwrap();
+        assert_eq!(storage.recovery(), &RecoveryReport { recovered: 2, dropped: 1, truncated_bytes: 0, replayed_ops: 0 });
+        assert!(!storage.contains(&b));
+        assert_eq!(storage.retrieve_string(&a).unwrap(), "alpha");
+        assert_eq!(storage.retrieve_string(&c).unwrap(), "charlie");
+    }
+    
+    #[test]
+    fn test_verify_attributes_tampering_to_the_right_packet() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("verify.m8");
+        
+        let (b, b_start, b_len) = {
+            let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+            storage.store(b"alpha", Some(b"first".to_vec())).unwrap();
+            let b_start = storage.stats().total_size;
+            let b = storage.store_string("bravo").unwrap();
+            let b_len = storage.stats().total_size - b_start - 8;
+            storage.store_string("charlie").unwrap();
+            storage.set_metadata(&b, b"later".to_vec()).unwrap(); // Updates don't count against it
+            
+            let mut progress = Vec::new();
+            let report = storage.verify_with_progress(|checked, total| progress.push((checked, total)));
+            assert_eq!(report, VerifyReport { total: 3, ok: 3, corrupt: vec![], truncated_tail_bytes: 0 });
+            assert_eq!(progress, [(1, 3), (2, 3), (3, 3)]);
+            (b, b_start as usize, b_len as usize)
+        };
+        
+        // A flipped bit fails the checksum
+        let original = std::fs::read(&path).unwrap();
+        let mut bytes = original.clone();
+        bytes[b_start + 8 + 40] ^= 0x01;
+        std::fs::write(&path, &bytes).unwrap();
+        let storage = Mem8Lite::new(&path, 1.0).unwrap();
+        let report = storage.verify();
+        assert_eq!((report.total, report.ok, report.corrupt.clone()), (3, 2, vec![b]));
+        assert!(!report.is_clean());
+        drop(storage);
+        
+        // A rewrite with a fresh checksum gets past that - but not the signature
+        let payload_end = b_start + 8 + b_len - CHECKSUM_LEN as usize;
+        let checksum = record_checksum(packet_prefix(PACKET_FORMAT_CHECKED, b_len as u64), &bytes[b_start + 8..payload_end]);
+        bytes[payload_end..payload_end + 8].copy_from_slice(&checksum);
+        std::fs::write(&path, &bytes).unwrap();
+        let storage = Mem8Lite::new(&path, 1.0).unwrap();
+        assert_eq!(storage.retrieve(&b).unwrap(), b"cravo");
+        assert_eq!(storage.verify().corrupt, vec![b]);
+        drop(storage);
+        
+        let storage = Mem8Lite::new(&path, 1.0).unwrap().with_strict_reads(true);
+        let err = storage.retrieve(&b).unwrap_err();
+        assert_eq!(err.downcast_ref::<SignatureMismatch>(), Some(&SignatureMismatch { signature: b }));
+        assert!(storage.retrieve_range(&b, 0, 2).is_err());
+        assert!(storage.retrieve_to(&b, Vec::new()).is_err());
+        assert_eq!(storage.retrieve_string(&content_signature(b"charlie", None)).unwrap(), "charlie");
+    }
+    
+    #[test]
+    fn test_close_keeps_everything() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("test.m8");
+        
+        let mut storage = Mem8Lite::new(&path, 1.618).unwrap();
+        let sigs: Vec<_> = (0..10)
+            .map(|i| storage.store_string(&format!("wave #{}", i)).unwrap())
+            .collect();
+        storage.close().unwrap();
+        
+        let storage = Mem8Lite::new(&path, 1.618).unwrap();
+        for (i, sig) in sigs.iter().enumerate() {
+            assert_eq!(storage.retrieve_string(sig).unwrap(), format!("wave #{}", i));
+        }
     }
 }
\ No newline at end of file
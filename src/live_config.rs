@@ -0,0 +1,260 @@
+//! Live configuration - profile, DJ and tool policy changes without a restart
+//!
+//! The MCP server keeps its configs as JSON files in a `Mem8Fs` next to the
+//! store, at `PROFILE_PATH`, `DJ_PATH` and `POLICY_PATH`. `ConfigWatcher`
+//! notices when one of them changes (by signature, checked before every tool
+//! call). A changed file is parsed and validated in full before it replaces
+//! the live version; a bad edit is logged as a rejected `ConfigEvent` and the
+//! old config keeps running.
+//!
+//! Trisha's favourite: swapping the playlist without stopping the party! 🎛️
+
+use std::collections::HashMap;
+use serde::{Serialize, Deserialize};
+use anyhow::{Result, anyhow};
+
+use crate::Mem8Fs;
+use crate::content_filter::ContentFilter;
+use crate::mcp_server::{DjMode, DjPersonality};
+use crate::mood_engine::MusicProfile;
+
+/// The listener profile (`MusicProfile`)
+pub const PROFILE_PATH: &str = "/config/profile.json";
+
+/// DJ defaults (`DjConfig`)
+pub const DJ_PATH: &str = "/config/dj.json";
+
+/// Which tools may run (`ToolPolicy`)
+pub const POLICY_PATH: &str = "/config/policy.json";
+
+/// Every config file the server watches
+pub const CONFIG_PATHS: [&str; 3] = [PROFILE_PATH, DJ_PATH, POLICY_PATH];
+
+/// How many config events are kept around
+pub const EVENT_LOG_LIMIT: usize = 50;
+
+/// The tool that reloads configs can't be switched off by a config
+pub const RELOAD_TOOL: &str = "mem8.reload_config";
+
+/// DJ settings that can be changed live
+///
+/// Fields left out of the JSON keep `DjMode::new`'s defaults. The DJ's
+/// memory (history, played artists) and filter stats are never touched by a
+/// reload.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DjConfig {
+    pub personality: DjPersonality,
+    pub auto_skip: bool,
+    pub vibe_threshold: f64,
+    pub max_same_artist_per_session: usize,
+    pub min_gap_before_repeat: usize,
+    pub exploration_rate: f64,
+    pub content_filter: ContentFilter,
+    pub suggestion_cache_secs: u64,
+}
+
+impl Default for DjConfig {
+    fn default() -> Self {
+        DjConfig::from(&DjMode::new(DjPersonality::HueMode))
+    }
+}
+
+impl From<&DjMode> for DjConfig {
+    fn from(dj: &DjMode) -> Self {
+        Self {
+            personality: dj.personality.clone(),
+            auto_skip: dj.auto_skip,
+            vibe_threshold: dj.vibe_threshold,
+            max_same_artist_per_session: dj.max_same_artist_per_session,
+            min_gap_before_repeat: dj.min_gap_before_repeat,
+            exploration_rate: dj.exploration_rate,
+            content_filter: dj.content_guard.filter.clone(),
+            suggestion_cache_secs: dj.suggestion_cache_secs,
+        }
+    }
+}
+
+impl DjConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(0.0..=1.0).contains(&self.vibe_threshold) {
+            return Err(anyhow!("vibe_threshold must be 0.0-1.0, got {}", self.vibe_threshold));
+        }
+        if !(0.0..=1.0).contains(&self.exploration_rate) {
+            return Err(anyhow!("exploration_rate must be 0.0-1.0, got {}", self.exploration_rate));
+        }
+        if self.max_same_artist_per_session == 0 {
+            return Err(anyhow!("max_same_artist_per_session must be at least 1"));
+        }
+        self.content_filter.validate()
+    }
+    
+    /// Apply these settings to a running DJ
+    pub fn apply(&self, dj: &mut DjMode) {
+        dj.personality = self.personality.clone();
+        dj.auto_skip = self.auto_skip;
+        dj.vibe_threshold = self.vibe_threshold;
+        dj.max_same_artist_per_session = self.max_same_artist_per_session;
+        dj.min_gap_before_repeat = self.min_gap_before_repeat;
+        dj.exploration_rate = self.exploration_rate;
+        dj.content_guard.filter = self.content_filter.clone();
+        dj.suggestion_cache_secs = self.suggestion_cache_secs;
+        dj.suggestion_cache.invalidate();
+    }
+}
+
+/// Which MCP tools are allowed to run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ToolPolicy {
+    /// Tools that refuse to run (full names, e.g. `mem8.delete_memory`)
+    #[serde(default)]
+    pub disabled_tools: Vec<String>,
+}
+
+impl ToolPolicy {
+    pub fn allows(&self, tool: &str) -> bool {
+        !self.disabled_tools.iter().any(|t| t == tool)
+    }
+    
+    pub fn validate(&self) -> Result<()> {
+        for tool in &self.disabled_tools {
+            if !tool.starts_with("mem8.") {
+                return Err(anyhow!("Unknown tool in disabled_tools: {}", tool));
+            }
+            if tool == RELOAD_TOOL {
+                return Err(anyhow!("{} can't be disabled", RELOAD_TOOL));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sanity checks a profile must pass before it goes live
+pub fn validate_profile(profile: &MusicProfile) -> Result<()> {
+    if profile.name.trim().is_empty() {
+        return Err(anyhow!("Profile needs a name"));
+    }
+    let tempo = &profile.tempo_preferences;
+    for (name, (low, high)) in [
+        ("focus_bpm", tempo.focus_bpm),
+        ("decompression_bpm", tempo.decompression_bpm),
+        ("relaxation_bpm", tempo.relaxation_bpm),
+    ] {
+        if low > high {
+            return Err(anyhow!("{} range is backwards: {}-{}", name, low, high));
+        }
+    }
+    for (activity, (low, high)) in &tempo.activity_bpm {
+        if low > high {
+            return Err(anyhow!("activity_bpm range for {} is backwards: {}-{}", activity, low, high));
+        }
+    }
+    if profile.allowed_genres().is_empty() {
+        return Err(anyhow!("Profile avoids every genre it prefers - nothing left to play"));
+    }
+    Ok(())
+}
+
+/// One config load, successful or not
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ConfigEvent {
+    /// Unix seconds
+    pub timestamp: u64,
+    pub path: String,
+    pub accepted: bool,
+    
+    /// What changed, or why the config was rejected
+    pub message: String,
+}
+
+/// Spots config files that changed since they were last looked at
+#[derive(Debug, Default)]
+pub struct ConfigWatcher {
+    /// Signature last seen per path (None: the file wasn't there)
+    seen: HashMap<&'static str, Option<String>>,
+    
+    /// Most recent events, oldest first
+    events: Vec<ConfigEvent>,
+}
+
+impl ConfigWatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Config files that changed (or, with `force`, all that exist) and their contents
+    ///
+    /// A file that disappears isn't reported: the live config stays as it is.
+    pub fn changed(&mut self, fs: &Mem8Fs, force: bool) -> Vec<(&'static str, Vec<u8>)> {
+        let mut changed = Vec::new();
+        for path in CONFIG_PATHS {
+            let signature = fs.metadata(path).ok().map(|meta| meta.signature);
+            let previous = self.seen.insert(path, signature.clone());
+            if signature.is_none() || (!force && previous.as_ref() == Some(&signature)) {
+                continue;
+            }
+            match fs.read(path) {
+                Ok(bytes) => changed.push((path, bytes)),
+                Err(e) => {
+                    self.record(path, Err(e.into()));
+                }
+            }
+        }
+        changed
+    }
+    
+    /// Log the outcome of loading `path`, returning the event
+    pub fn record(&mut self, path: &str, outcome: Result<String>) -> ConfigEvent {
+        let (accepted, message) = match outcome {
+            Ok(message) => (true, message),
+            Err(e) => {
+                eprintln!("⚠️ mem8: rejected {} (keeping the old config): {}", path, e);
+                (false, e.to_string())
+            }
+        };
+        let event = ConfigEvent {
+            timestamp: chrono::Utc::now().timestamp() as u64,
+            path: path.to_string(),
+            accepted,
+            message,
+        };
+        self.events.push(event.clone());
+        if self.events.len() > EVENT_LOG_LIMIT {
+            self.events.remove(0);
+        }
+        event
+    }
+    
+    /// Recent config events, oldest first
+    pub fn events(&self) -> &[ConfigEvent] {
+        &self.events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    
+    #[test]
+    fn test_watcher_reports_only_changes() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        let mut watcher = ConfigWatcher::new();
+        assert!(watcher.changed(&fs, false).is_empty());
+        
+        fs.write(DJ_PATH, br#"{"exploration_rate": 0.5}"#).unwrap();
+        assert_eq!(watcher.changed(&fs, false), vec![(DJ_PATH, br#"{"exploration_rate": 0.5}"#.to_vec())]);
+        assert!(watcher.changed(&fs, false).is_empty());
+        assert_eq!(watcher.changed(&fs, true).len(), 1);
+        
+        // Partial DJ configs keep the defaults; out of range values don't validate
+        let config: DjConfig = serde_json::from_slice(&fs.read(DJ_PATH).unwrap()).unwrap();
+        assert_eq!((config.exploration_rate, config.min_gap_before_repeat), (0.5, 10));
+        assert!(config.validate().is_ok());
+        assert!(DjConfig { vibe_threshold: 1.5, ..config.clone() }.validate().is_err());
+        
+        let policy = ToolPolicy { disabled_tools: vec![RELOAD_TOOL.to_string()] };
+        assert!(policy.validate().is_err());
+    }
+}
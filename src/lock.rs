@@ -0,0 +1,220 @@
+//! Store locks - one writer per Mem8Fs store, across processes
+//!
+//! Two processes appending to the same `data.m8` interleave their records,
+//! and each saves `index.m8` from its own memory, so the second save quietly
+//! drops the first one's files. So every open takes an advisory lock (flock,
+//! through fs4) on `.mem8/lock`: exclusive for a read-write `Mem8Fs`, shared
+//! for `Mem8Fs::open_read_only` - any number of readers, or one writer. The
+//! `LockMode` says what to do when it's taken: fail straight away (the
+//! default), wait for it, or wait a while and then fail.
+//!
+//! The kernel lets go of the lock when the file is closed, on drop or when
+//! the process dies, so a crashed writer never leaves a stale lock behind.
+//! The writer's pid goes in the file only to say who has it.
+//!
+//! Hue hangs a sign on the door; Trisha knocks before coming in. 🚪
+
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use fs4::fs_std::FileExt;
+use crate::error::Result;
+
+/// The lock file, inside `.mem8/`
+pub const LOCK_FILE: &str = "lock";
+
+/// How often `LockMode::Timeout` tries again
+const LOCK_POLL: Duration = Duration::from_millis(10);
+
+/// What opening a store does when another process has it locked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LockMode {
+    /// Fail with `StoreLocked` straight away
+    #[default]
+    Fail,
+    
+    /// Block until the lock is free
+    Wait,
+    
+    /// Keep trying for this long, then fail with `StoreLocked`
+    Timeout(Duration),
+}
+
+/// The store is open in another process (or another `Mem8Fs` in this one)
+///
+/// Comes back as `Mem8Error::Locked`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{} is already open{}", path.display(), holder.map(|pid| format!(" read-write by pid {}", pid)).unwrap_or_default())]
+pub struct StoreLocked {
+    /// The lock file
+    pub path: PathBuf,
+    
+    /// The writer holding it, if it's a writer and still running
+    pub holder: Option<u32>,
+}
+
+/// A held lock on `.mem8/lock`, released on drop
+#[derive(Debug)]
+pub(crate) struct StoreLock {
+    file: File,
+    exclusive: bool,
+}
+
+impl StoreLock {
+    /// Lock the store in `store_dir` (`.mem8/`), exclusively for a writer
+    pub(crate) fn acquire(store_dir: &Path, exclusive: bool, mode: LockMode) -> Result<Self> {
+        let path = store_dir.join(LOCK_FILE);
+        let file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(&path)?;
+        let try_lock = || match exclusive {
+            true => FileExt::try_lock_exclusive(&file),
+            false => FileExt::try_lock_shared(&file),
+        };
+        
+        let deadline = match mode {
+            LockMode::Wait => None,
+            LockMode::Fail => Some(Instant::now()),
+            LockMode::Timeout(wait) => Some(Instant::now() + wait),
+        };
+        match deadline {
+            None if exclusive => FileExt::lock_exclusive(&file)?,
+            None => FileExt::lock_shared(&file)?,
+            Some(deadline) => loop {
+                match try_lock() {
+                    Ok(()) => break,
+                    Err(e) if e.kind() == fs4::lock_contended_error().kind() => {
+                        if Instant::now() >= deadline {
+                            return Err(StoreLocked { holder: holder(&file), path }.into());
+                        }
+                        std::thread::sleep(LOCK_POLL);
+                    }
+                    Err(e) => return Err(e.into()),
+                }
+            },
+        }
+        
+        if exclusive {
+            file.set_len(0)?;
+            (&file).write_all(std::process::id().to_string().as_bytes())?;
+        }
+        Ok(Self { file, exclusive })
+    }
+}
+
+impl Drop for StoreLock {
+    fn drop(&mut self) {
+        if self.exclusive {
+            let _ = self.file.set_len(0);
+        }
+        let _ = FileExt::unlock(&self.file);
+    }
+}
+
+/// The pid a writer left in the lock file, if that process is still around
+fn holder(file: &File) -> Option<u32> {
+    let mut contents = String::new();
+    let mut file = file;
+    file.read_to_string(&mut contents).ok()?;
+    let pid: u32 = contents.trim().parse().ok()?;
+    alive(pid).then_some(pid)
+}
+
+#[cfg(unix)]
+fn alive(pid: u32) -> bool {
+    // Signal 0 only checks; EPERM means it exists but isn't ours
+    let signalled = unsafe { libc::kill(pid as libc::pid_t, 0) } == 0;
+    signalled || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+#[cfg(not(unix))]
+fn alive(_pid: u32) -> bool {
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FsConfig, Mem8Error, Mem8Fs};
+    use tempfile::tempdir;
+    
+    fn locked(result: Result<Mem8Fs>) -> StoreLocked {
+        match result {
+            Err(Mem8Error::Locked(locked)) => locked,
+            Err(e) => panic!("expected StoreLocked, got {}", e),
+            Ok(_) => panic!("expected StoreLocked, the open went through"),
+        }
+    }
+    
+    #[test]
+    fn test_one_writer_or_many_readers() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        fs.write("/a.txt", b"written by the first process").unwrap();
+        
+        // A second writer and a reader are both turned away, naming the writer
+        let err = locked(Mem8Fs::new(dir.path()));
+        assert_eq!(err.holder, Some(std::process::id()));
+        assert_eq!(err.path, dir.path().join(".mem8").join(LOCK_FILE));
+        locked(Mem8Fs::open_read_only(dir.path(), LockMode::Fail));
+        
+        // Waiting a while still fails, but only after the wait
+        let started = Instant::now();
+        let config = FsConfig::default().with_lock(LockMode::Timeout(Duration::from_millis(50)));
+        locked(Mem8Fs::new_with_config(dir.path(), config));
+        assert!(started.elapsed() >= Duration::from_millis(50));
+        drop(fs);
+        
+        // Readers share, and keep writers out until the last one goes
+        let reader = Mem8Fs::open_read_only(dir.path(), LockMode::Fail).unwrap();
+        let other = Mem8Fs::open_read_only(dir.path(), LockMode::Fail).unwrap();
+        assert_eq!(other.read("/a.txt").unwrap(), b"written by the first process");
+        assert_eq!(locked(Mem8Fs::new(dir.path())).holder, None);
+        drop((reader, other));
+        
+        // Released on drop: a waiting writer gets in once the holder goes
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        let waiter = {
+            let root = dir.path().to_path_buf();
+            std::thread::spawn(move || {
+                let fs = Mem8Fs::new_with_lock(&root, LockMode::Wait).unwrap();
+                fs.read("/b.txt").unwrap()
+            })
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        fs.write("/b.txt", b"left for the next one").unwrap();
+        drop(fs);
+        assert_eq!(waiter.join().unwrap(), b"left for the next one");
+    }
+    
+    #[test]
+    fn test_read_only_refuses_writes_and_leaves_the_store_alone() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        fs.write("/a.txt", b"alpha").unwrap();
+        fs.create_snapshot("v1").unwrap();
+        drop(fs);
+        let store = dir.path().join(".mem8");
+        let before: Vec<_> = ["data.m8", "data.idx", "index.m8", "meta.m8"].iter()
+            .map(|name| std::fs::read(store.join(name)).unwrap())
+            .collect();
+        
+        let fs = Mem8Fs::open_read_only(dir.path(), LockMode::Fail).unwrap();
+        assert_eq!(fs.read("/a.txt").unwrap(), b"alpha");
+        assert_eq!(fs.read_at_snapshot("v1", "/a.txt").unwrap(), b"alpha");
+        assert!(matches!(fs.write("/b.txt", b"beta"), Err(Mem8Error::ReadOnly)));
+        assert!(matches!(fs.delete("/a.txt"), Err(Mem8Error::ReadOnly)));
+        assert!(matches!(fs.rename("/a.txt", "/c.txt"), Err(Mem8Error::ReadOnly)));
+        assert!(matches!(fs.create_snapshot("v2"), Err(Mem8Error::ReadOnly)));
+        assert!(matches!(fs.gc(), Err(Mem8Error::ReadOnly)));
+        fs.close().unwrap();
+        
+        let after: Vec<_> = ["data.m8", "data.idx", "index.m8", "meta.m8"].iter()
+            .map(|name| std::fs::read(store.join(name)).unwrap())
+            .collect();
+        assert_eq!(before, after);
+        
+        // There has to be a store to read
+        let empty = tempdir().unwrap();
+        assert!(matches!(Mem8Fs::open_read_only(empty.path(), LockMode::Fail), Err(Mem8Error::NotFound(_))));
+    }
+}
@@ -0,0 +1,156 @@
+//! Marine on write - Mem8Fs files that carry their own salience
+//!
+//! With `FsConfig::analyze_on_write` set (or `WriteOptions::with_analyze`
+//! for one write), `write_with_options` runs a `MarineProcessor` over the
+//! bytes - as the magnitudes their waves are stored with, byte / 255 - and
+//! keeps the `MarineMetadata` in a namespace of its own, `marine-<hex
+//! signature>`, so a write only ever touches its own record.
+//! `Mem8Fs::marine_metadata` looks it up by path. Since it goes by content,
+//! renames, hard links and copies within the store keep it, and an `append`
+//! or `write_at` (which don't analyze) leaves the file without one until
+//! it's written whole again. Records no file or snapshot still has are
+//! dropped by `gc`.
+//!
+//! The bytes go through the processor `ANALYSIS_BLOCK` at a time, with the
+//! streaming `process_chunk`, so a big file isn't held twice over as f64s.
+//!
+//! Hue remembers how a file felt, not just what was in it. 🌊
+
+use std::collections::HashSet;
+use std::path::Path;
+use crate::error::{Mem8Error, Result};
+use crate::marine::{MarineMetadata, MarineProcessor};
+use crate::{FileIndex, Mem8Fs};
+
+/// Start of the namespace each analysis is kept in, its hex signature following
+pub const MARINE_NAMESPACE_PREFIX: &str = "marine-";
+
+/// Bytes handed to the processor at a time
+pub const ANALYSIS_BLOCK: usize = 1 << 16;
+
+/// What Marine makes of `data`, heard as the magnitudes of its waves
+pub(crate) fn analyze_bytes(data: &[u8]) -> MarineMetadata {
+    let mut processor = MarineProcessor::new();
+    let mut samples = Vec::with_capacity(ANALYSIS_BLOCK.min(data.len()));
+    let mut peaks = Vec::new();
+    for block in data.chunks(ANALYSIS_BLOCK) {
+        samples.clear();
+        samples.extend(block.iter().map(|&byte| byte as f64 / 255.0));
+        peaks.extend(processor.process_chunk(&samples));
+    }
+    peaks.extend(processor.finalize());
+    processor.extract_metadata(&peaks)
+}
+
+/// The namespace the analysis of content `signature` is kept in
+fn marine_namespace(signature: &[u8; 32]) -> String {
+    format!("{}{}", MARINE_NAMESPACE_PREFIX, hex::encode(signature))
+}
+
+impl Mem8Fs {
+    /// The Marine analysis kept for the file at `path`, if it was written with one
+    pub fn marine_metadata<P: AsRef<Path>>(&self, path: P) -> Result<Option<MarineMetadata>> {
+        let path = self.normalize_path(path)?;
+        let signature = self.index.read().unwrap().files.get(&path)
+            .ok_or_else(|| Mem8Error::not_found("File not found"))?
+            .signature;
+        match self.read_namespace(&marine_namespace(&signature))? {
+            Some(bytes) => Ok(Some(serde_json::from_slice(&bytes)?)),
+            None => Ok(None),
+        }
+    }
+    
+    /// Keep `metadata` for content `signature`
+    ///
+    /// Called with the index write lock held, so a `gc` pruning records can't
+    /// drop this one before the file that points at it is in the index.
+    pub(crate) fn keep_marine(&self, signature: &[u8; 32], metadata: &MarineMetadata) -> Result<()> {
+        self.write_namespace(&marine_namespace(signature), &serde_json::to_vec(metadata)?)
+    }
+    
+    /// Drop the analyses of content no file in `index` or snapshot has any more
+    ///
+    /// Part of `gc`, which holds the index lock throughout. Returns how many went.
+    pub(crate) fn prune_marine(&self, index: &FileIndex) -> Result<usize> {
+        let mut live: HashSet<String> = index.files.values().map(|entry| marine_namespace(&entry.signature)).collect();
+        for snapshot in self.load_snapshots()?.values() {
+            live.extend(snapshot.file_signatures().map(|signature| marine_namespace(&signature)));
+        }
+        
+        let dir = self.store_dir().join("ns");
+        let entries = match std::fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+            Err(e) => return Err(e.into()),
+        };
+        let mut pruned = 0;
+        for entry in entries {
+            let entry = entry?;
+            let file_name = entry.file_name();
+            let Some(name) = file_name.to_str().and_then(|name| name.strip_suffix(".m8")) else {
+                continue;
+            };
+            if name.starts_with(MARINE_NAMESPACE_PREFIX) && !live.contains(name) {
+                std::fs::remove_file(entry.path())?;
+                pruned += 1;
+            }
+        }
+        Ok(pruned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FsConfig, WriteOptions};
+    use tempfile::tempdir;
+    
+    /// A loud byte every 50, over `len` bytes
+    fn pulses(len: usize) -> Vec<u8> {
+        (0..len).map(|i| if i % 50 == 0 { 255 } else { 8 }).collect()
+    }
+    
+    #[test]
+    fn test_analysis_kept_by_path_across_reopen() {
+        let dir = tempdir().unwrap();
+        let config = FsConfig::default().with_analyze_on_write(true);
+        let fs = Mem8Fs::new_with_config(dir.path(), config).unwrap();
+        let rhythmic = pulses(3 * ANALYSIS_BLOCK + 123);
+        fs.write("/beat.bin", &rhythmic).unwrap();
+        fs.write_with_options("/skipped.bin", &rhythmic[..2000], WriteOptions::default().with_analyze(false)).unwrap();
+        
+        // Streamed in blocks, the same as the whole file at once
+        let mut whole = MarineProcessor::new();
+        let samples: Vec<f64> = rhythmic.iter().map(|&byte| byte as f64 / 255.0).collect();
+        let peaks = whole.process_samples(&samples);
+        let expected = whole.extract_metadata(&peaks);
+        assert_eq!(analyze_bytes(&rhythmic).total_peaks, expected.total_peaks);
+        drop(fs);
+        
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        let metadata = fs.marine_metadata("/beat.bin").unwrap().unwrap();
+        assert!(metadata.has_rhythm);
+        assert_eq!(metadata.total_peaks, expected.total_peaks);
+        assert!(fs.marine_metadata("/skipped.bin").unwrap().is_none());
+        assert!(matches!(fs.marine_metadata("/missing.bin"), Err(Mem8Error::NotFound(_))));
+        
+        // It goes with the content: a rename keeps it, an append loses it
+        fs.rename("/beat.bin", "/moved.bin").unwrap();
+        assert!(fs.marine_metadata("/moved.bin").unwrap().unwrap().has_rhythm);
+        fs.append("/moved.bin", b"tail").unwrap();
+        assert!(fs.marine_metadata("/moved.bin").unwrap().is_none());
+        
+        // Off by default, on for one write
+        fs.write("/plain.bin", &rhythmic[..1000]).unwrap();
+        assert!(fs.marine_metadata("/plain.bin").unwrap().is_none());
+        fs.write_with_options("/asked.bin", &rhythmic[..1000], WriteOptions::default().with_analyze(true)).unwrap();
+        assert!(fs.marine_metadata("/asked.bin").unwrap().unwrap().has_rhythm);
+        
+        // The append left beat.bin's first analysis behind, until a gc
+        let stale = marine_namespace(&crate::fs_signature(&rhythmic));
+        assert!(fs.read_namespace(&stale).unwrap().is_some());
+        fs.gc().unwrap();
+        assert!(fs.read_namespace(&stale).unwrap().is_none());
+        assert!(fs.marine_metadata("/asked.bin").unwrap().unwrap().has_rhythm);
+    }
+}
@@ -7,23 +7,46 @@
 //! - Wave patterns and salience
 //! - DJ recommendations based on activity
 //!
+//! The music profile, DJ defaults and tool policy can be edited while the
+//! server runs - see `crate::live_config`.
+//!
 //! Hue, this makes me your co-pilot DJ! I can sense the vibe and 
 //! suggest the perfect track for your current flow. 🎵🤖
 
 use serde::{Serialize, Deserialize};
 use serde_json::{json, Value};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::io::{BufRead, Write};
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use anyhow::{Result, anyhow};
 
-use crate::{Mem8Lite, MarineProcessor};
-use crate::mood_engine::{MoodEngine, MoodState, Activity, Genre};
-use crate::audio_loader::load_audio_file;
+use crate::{Mem8Fs, Mem8Lite, SharedMem8Lite, MarineProcessor, FrequencyPreset, MetadataRejected, parse_signature};
+use crate::live_config::{
+    ConfigEvent, ConfigWatcher, DjConfig, ToolPolicy, validate_profile,
+    DJ_PATH, POLICY_PATH, PROFILE_PATH,
+};
+use crate::content_filter::FilterGuard;
+use crate::highlights;
+use crate::text::{self, TextEncoding};
+use crate::mood_engine::{MoodEngine, MusicProfile, Activity, Genre, ListeningEntry, KNOWN_ACTIVITIES};
+use crate::activity_report::ReportRange;
+use crate::audio_loader::{load_audio_track, probe_audio};
+use crate::sensor_ingress::{states_in_store, SensorData, SensorPattern, DEFAULT_STALENESS_SECS};
+
+/// How many mood readings and wave patterns the sensor buffer keeps
+pub const SENSOR_HISTORY_LIMIT: usize = 1000;
+
+/// Most items one `mem8.store_memories` call may carry
+pub const MAX_BATCH_ITEMS: usize = 256;
+
+/// Most data plus metadata, in bytes, one `mem8.store_memories` call may carry
+pub const MAX_BATCH_BYTES: usize = 8 << 20;
 
 /// MCP Server for MEM8 - exposes consciousness to LLMs
 pub struct Mem8McpServer {
-    /// The underlying MEM8 storage
-    storage: Arc<Mutex<Mem8Lite>>,
+    /// The underlying MEM8 storage (tools read it concurrently)
+    storage: SharedMem8Lite,
     
     /// Mood engine for tracking state
     mood_engine: Arc<Mutex<MoodEngine>>,
@@ -39,6 +62,21 @@ pub struct Mem8McpServer {
     
     /// Sensor data buffer
     sensor_buffer: Arc<Mutex<SensorBuffer>>,
+    
+    /// Where the DJ's anti-repetition memory is persisted
+    dj_memory_path: PathBuf,
+    
+    /// Where the mood transitions and listening journal are persisted
+    mood_history_path: PathBuf,
+    
+    /// Live config files (`/config/*.json`, in `<store>.config/`)
+    config_fs: Mem8Fs,
+    
+    /// Notices config edits and logs every load
+    config_watcher: Arc<Mutex<ConfigWatcher>>,
+    
+    /// Which tools may run (`/config/policy.json`)
+    tool_policy: Arc<Mutex<ToolPolicy>>,
 }
 
 /// DJ Mode - Let the AI pick the music!
@@ -61,6 +99,349 @@ pub struct DjMode {
     
     /// DJ personality mode
     pub personality: DjPersonality,
+    
+    /// Most suggestions allowed from one artist per session
+    #[serde(default = "default_max_same_artist")]
+    pub max_same_artist_per_session: usize,
+    
+    /// Tracks that must pass before the same track is suggested again
+    #[serde(default = "default_min_gap")]
+    pub min_gap_before_repeat: usize,
+    
+    /// Fraction of suggestions drawn from never-played artists/genres (0.0 - 1.0)
+    #[serde(default = "default_exploration_rate")]
+    pub exploration_rate: f64,
+    
+    /// Every artist ever suggested (persisted)
+    #[serde(default)]
+    pub played_artists: HashSet<String>,
+    
+    /// Every genre ever suggested (persisted)
+    #[serde(default)]
+    pub played_genres: HashSet<Genre>,
+    
+    /// Suggestions per artist this session
+    #[serde(skip)]
+    pub session_artist_counts: HashMap<String, usize>,
+    
+    /// Accumulates `exploration_rate` until an exploration pick is due
+    #[serde(skip)]
+    exploration_credit: f64,
+    
+    /// The content filter (from the DJ config) and what it has blocked
+    #[serde(skip)]
+    pub content_guard: FilterGuard,
+    
+    /// How long `dj_suggest` may hand out the same picks for the same context (0: never)
+    #[serde(default = "default_suggestion_cache_secs")]
+    pub suggestion_cache_secs: u64,
+    
+    /// The last round of picks and the context they were made for
+    #[serde(skip)]
+    pub suggestion_cache: SuggestionCache,
+}
+
+fn default_max_same_artist() -> usize { 3 }
+fn default_min_gap() -> usize { 10 }
+fn default_exploration_rate() -> f64 { 0.2 }
+fn default_suggestion_cache_secs() -> u64 { 30 }
+
+/// What a round of DJ picks depends on - the same context gets the same picks
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SuggestionContext {
+    pub activity: String,
+    /// Latest mood reading, if any
+    pub mood: Option<String>,
+    /// Fatigue in tenths (0-10)
+    pub fatigue_bucket: u8,
+    pub personality: String,
+    /// Hash of the content filter settings
+    pub filter_hash: u64,
+    /// Hash of the playlist queue
+    pub queue_hash: u64,
+}
+
+/// `dj_suggest`'s cache: one round of picks, reused while the context holds
+/// 
+/// Cleared by anything that should change the DJ's mind: listening logs,
+/// feedback and config reloads. Mood and queue changes show up in the context.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SuggestionCache {
+    #[serde(skip)]
+    entry: Option<(SuggestionContext, std::time::Instant, Vec<DjPick>)>,
+    pub hits: u64,
+    pub misses: u64,
+    pub invalidations: u64,
+}
+
+impl SuggestionCache {
+    /// The cached picks, if they were made for `context` under `ttl` ago
+    pub fn get(&mut self, context: &SuggestionContext, ttl: std::time::Duration) -> Option<Vec<DjPick>> {
+        match &self.entry {
+            Some((cached, at, picks)) if cached == context && at.elapsed() < ttl => {
+                self.hits += 1;
+                Some(picks.clone())
+            }
+            _ => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+    
+    pub fn put(&mut self, context: SuggestionContext, picks: Vec<DjPick>) {
+        self.entry = Some((context, std::time::Instant::now(), picks));
+    }
+    
+    /// Forget the cached picks (counted only if there were any)
+    pub fn invalidate(&mut self) {
+        if self.entry.take().is_some() {
+            self.invalidations += 1;
+        }
+    }
+    
+    /// Is `artist - title` among the cached picks?
+    pub fn contains(&self, key: &str) -> bool {
+        self.entry.as_ref()
+            .is_some_and(|(_, _, picks)| picks.iter().any(|pick| track_key(&pick.suggestion) == key))
+    }
+}
+
+fn hash_of<T: std::hash::Hash + ?Sized>(value: &T) -> u64 {
+    use std::hash::Hasher;
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    value.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// How many tracks the DJ suggests per round
+const SUGGESTIONS_PER_ROUND: usize = 2;
+
+/// How much play history the DJ keeps around
+const DJ_HISTORY_LIMIT: usize = 200;
+
+/// Wider catalogue the DJ can explore beyond the activity favourites
+const DJ_CATALOG: &[(&str, &str, Genre)] = &[
+    ("Aphex Twin", "Xtal", Genre::Electronic),
+    ("Boards of Canada", "Roygbiv", Genre::Electronic),
+    ("Tycho", "A Walk", Genre::Electronic),
+    ("Underworld", "Born Slippy", Genre::Electronic),
+    ("The Chemical Brothers", "Star Guitar", Genre::Electronic),
+    ("Leftfield", "Release the Pressure", Genre::Electronic),
+    ("Kraftwerk", "Computer Love", Genre::Electronic),
+    ("Jean-Michel Jarre", "Oxygène Pt. 4", Genre::Electronic),
+    ("Bonobo", "Kerala", Genre::Electronic),
+    ("Four Tet", "Two Thousand and Seventeen", Genre::Electronic),
+    ("Jon Hopkins", "Open Eye Signal", Genre::Electronic),
+    ("Moby", "Porcelain", Genre::Electronic),
+    ("AC/DC", "Thunderstruck", Genre::HardRock),
+    ("Deep Purple", "Highway Star", Genre::HardRock),
+    ("Led Zeppelin", "Kashmir", Genre::HardRock),
+    ("Rage Against the Machine", "Bulls on Parade", Genre::HardRock),
+    ("Tool", "The Pot", Genre::HardRock),
+    ("Soundgarden", "Spoonman", Genre::HardRock),
+    ("Audioslave", "Cochise", Genre::HardRock),
+    ("Brian Eno", "An Ending (Ascent)", Genre::Ambient),
+    ("Enya", "Orinoco Flow", Genre::Ambient),
+    ("Stars of the Lid", "Requiem for Dying Mothers", Genre::Ambient),
+    ("Harold Budd", "The Pearl", Genre::Ambient),
+    ("Hiroshi Yoshimura", "Blink", Genre::Ambient),
+    ("Biosphere", "Poa Alpina", Genre::Ambient),
+    ("William Basinski", "dlp 1.1", Genre::Ambient),
+    ("Nine Inch Nails", "Wish", Genre::Industrial),
+    ("Ministry", "N.W.O.", Genre::Industrial),
+    ("Skinny Puppy", "Worlock", Genre::Industrial),
+    ("Front Line Assembly", "Mindphaser", Genre::Industrial),
+    ("KMFDM", "Juke Joint Jezebel", Genre::Industrial),
+    ("Rammstein", "Du Hast", Genre::Industrial),
+    ("Linkin Park", "One Step Closer", Genre::Crossover),
+    ("Deftones", "Change", Genre::Crossover),
+    ("Faith No More", "Epic", Genre::Crossover),
+    ("Incubus", "Pardon Me", Genre::Crossover),
+    ("System of a Down", "Aerials", Genre::Crossover),
+    ("Paradoks", "Spatial Dimension", Genre::Spatial),
+    ("Enigma", "Sadeness", Genre::Spatial),
+    ("Vangelis", "Blade Runner Blues", Genre::Spatial),
+    ("Tangerine Dream", "Love on a Real Train", Genre::Spatial),
+    ("Delerium", "Silence", Genre::Spatial),
+    ("Hans Zimmer", "Time", Genre::Spatial),
+    ("Beethoven", "Symphony No. 5", Genre::Classical),
+    ("David Lanz", "Cristofori's Dream", Genre::Classical),
+    ("Arvo Pärt", "Spiegel im Spiegel", Genre::Classical),
+    ("Max Richter", "On the Nature of Daylight", Genre::Classical),
+    ("Philip Glass", "Metamorphosis One", Genre::Classical),
+];
+
+/// The part of the DJ's memory that survives restarts
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DjMemory {
+    pub history: Vec<String>,
+    pub played_artists: HashSet<String>,
+    pub played_genres: HashSet<Genre>,
+}
+
+/// A suggestion plus whether it was an exploration pick
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DjPick {
+    #[serde(flatten)]
+    pub suggestion: TrackSuggestion,
+    
+    /// Picked to explore a never-played artist or genre
+    pub exploration: bool,
+}
+
+impl DjMode {
+    /// Fresh DJ settings with the default diversity controls
+    pub fn new(personality: DjPersonality) -> Self {
+        Self {
+            enabled: false,
+            auto_skip: true,
+            vibe_threshold: 0.6,
+            queue: Vec::new(),
+            history: Vec::new(),
+            personality,
+            max_same_artist_per_session: default_max_same_artist(),
+            min_gap_before_repeat: default_min_gap(),
+            exploration_rate: default_exploration_rate(),
+            played_artists: HashSet::new(),
+            played_genres: HashSet::new(),
+            session_artist_counts: HashMap::new(),
+            exploration_credit: 0.0,
+            content_guard: FilterGuard::default(),
+            suggestion_cache_secs: default_suggestion_cache_secs(),
+            suggestion_cache: SuggestionCache::default(),
+        }
+    }
+    
+    /// Snapshot the anti-repetition memory for persistence
+    pub fn memory(&self) -> DjMemory {
+        DjMemory {
+            history: self.history.clone(),
+            played_artists: self.played_artists.clone(),
+            played_genres: self.played_genres.clone(),
+        }
+    }
+    
+    /// Restore a previously persisted memory (session counts start fresh)
+    pub fn restore(&mut self, memory: DjMemory) {
+        self.history = memory.history;
+        self.played_artists = memory.played_artists;
+        self.played_genres = memory.played_genres;
+    }
+    
+    /// Pick this round's suggestions under the diversity constraints
+    /// 
+    /// `candidates` are the activity favourites (tried first for familiar picks);
+    /// exploration picks come from the catalogue within `allowed` genres.
+    /// Both pass the content filter first. Every pick is remembered as played.
+    pub fn pick_suggestions(&mut self, candidates: &[TrackSuggestion], allowed: &[Genre]) -> Vec<DjPick> {
+        let candidates: Vec<TrackSuggestion> = candidates.iter()
+            .filter(|s| self.content_guard.admit_suggestion(s))
+            .cloned()
+            .collect();
+        let catalog: Vec<&(&str, &str, Genre)> = DJ_CATALOG.iter()
+            .filter(|(_, _, genre)| allowed.contains(genre))
+            .filter(|entry| self.content_guard.admit_suggestion(&catalog_suggestion(entry, false)))
+            .collect();
+        let mut picks = Vec::new();
+        
+        for _ in 0..SUGGESTIONS_PER_ROUND {
+            self.exploration_credit += self.exploration_rate.clamp(0.0, 1.0);
+            
+            let mut pick = None;
+            if self.exploration_credit >= 1.0 {
+                match self.next_exploration(&catalog) {
+                    Some(suggestion) => {
+                        self.exploration_credit -= 1.0;
+                        pick = Some(DjPick { suggestion, exploration: true });
+                    }
+                    // Nothing new left to explore - don't let the debt pile up
+                    None => self.exploration_credit = 1.0,
+                }
+            }
+            let pick = pick.or_else(|| self.next_familiar(&candidates, &catalog)
+                .map(|suggestion| DjPick { suggestion, exploration: false }));
+            
+            match pick {
+                Some(pick) => {
+                    self.remember(&pick.suggestion);
+                    picks.push(pick);
+                }
+                None => break, // Everything is on cooldown
+            }
+        }
+        
+        picks
+    }
+    
+    /// Would suggesting this now break the repeat/artist limits?
+    fn is_eligible(&self, suggestion: &TrackSuggestion) -> bool {
+        let key = track_key(suggestion);
+        let artist_count = self.session_artist_counts.get(&suggestion.artist).copied().unwrap_or(0);
+        artist_count < self.max_same_artist_per_session
+            && !self.history.iter().rev().take(self.min_gap_before_repeat).any(|k| *k == key)
+    }
+    
+    fn next_exploration(&self, catalog: &[&(&str, &str, Genre)]) -> Option<TrackSuggestion> {
+        let fresh: Vec<TrackSuggestion> = catalog.iter()
+            .map(|entry| catalog_suggestion(entry, true))
+            .filter(|s| self.is_eligible(s))
+            .filter(|s| !self.played_artists.contains(&s.artist) || !self.played_genres.contains(&s.genre))
+            .collect();
+        
+        // A whole new genre beats a new artist in a familiar one
+        fresh.iter()
+            .find(|s| !self.played_genres.contains(&s.genre))
+            .or_else(|| fresh.first())
+            .cloned()
+    }
+    
+    fn next_familiar(&self, candidates: &[TrackSuggestion], catalog: &[&(&str, &str, Genre)]) -> Option<TrackSuggestion> {
+        if let Some(s) = candidates.iter().find(|s| self.is_eligible(s)) {
+            return Some(s.clone());
+        }
+        
+        // Favourites are on cooldown - fall back to the catalogue, known artists first
+        let catalog: Vec<TrackSuggestion> = catalog.iter()
+            .map(|entry| catalog_suggestion(entry, false))
+            .filter(|s| self.is_eligible(s))
+            .collect();
+        catalog.iter()
+            .find(|s| self.played_artists.contains(&s.artist))
+            .or_else(|| catalog.first())
+            .cloned()
+    }
+    
+    fn remember(&mut self, suggestion: &TrackSuggestion) {
+        self.history.push(track_key(suggestion));
+        if self.history.len() > DJ_HISTORY_LIMIT {
+            self.history.remove(0);
+        }
+        *self.session_artist_counts.entry(suggestion.artist.clone()).or_insert(0) += 1;
+        self.played_artists.insert(suggestion.artist.clone());
+        self.played_genres.insert(suggestion.genre.clone());
+    }
+}
+
+/// History key for a track
+fn track_key(suggestion: &TrackSuggestion) -> String {
+    format!("{} - {}", suggestion.artist, suggestion.title)
+}
+
+fn catalog_suggestion(entry: &(&str, &str, Genre), exploration: bool) -> TrackSuggestion {
+    let (artist, title, genre) = entry;
+    TrackSuggestion {
+        artist: artist.to_string(),
+        title: title.to_string(),
+        genre: genre.clone(),
+        reason: if exploration {
+            format!("Exploring something new in {:?}", genre)
+        } else {
+            "A familiar favourite from your catalogue".to_string()
+        },
+        predicted_effect: if exploration { "Fresh perspective" } else { "Comfortable continuity" }.to_string(),
+        confidence: if exploration { 0.6 } else { 0.7 },
+    }
 }
 
 /// Different DJ personalities for different moods
@@ -110,9 +491,13 @@ pub struct SensorBuffer {
     
     /// Focus metrics
     pub focus_score: f64,
+    
+    /// Latest reading per sensor (see `Mem8McpServer::ingest_sensor`)
+    #[serde(default)]
+    pub sensors: BTreeMap<String, SensorData>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MoodReading {
     pub timestamp: u64,
     pub mood_state: String,
@@ -127,7 +512,7 @@ pub struct WavePattern {
     pub wonder_detected: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ActivityTransition {
     pub timestamp: u64,
     pub from: String,
@@ -135,49 +520,439 @@ pub struct ActivityTransition {
     pub trigger: String,
 }
 
+/// Patterns `mem8.wave_context` includes at the standard detail level
+pub const STANDARD_CONTEXT_PATTERNS: usize = 5;
+
+/// How much history `mem8.wave_context` embeds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContextDetail {
+    /// The current numbers and the recommendation, no history
+    Brief,
+    
+    /// Plus the last `STANDARD_CONTEXT_PATTERNS` wave patterns
+    #[default]
+    Standard,
+    
+    /// Every buffered pattern, mood reading and activity change
+    Full,
+}
+
+impl ContextDetail {
+    pub fn parse(name: &str) -> Result<Self> {
+        match name {
+            "brief" => Ok(Self::Brief),
+            "standard" => Ok(Self::Standard),
+            "full" => Ok(Self::Full),
+            other => Err(anyhow!("Unknown detail level {:?} (expected brief, standard or full)", other)),
+        }
+    }
+}
+
+/// Why `mem8.wave_context` recommends what it does
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReasonCode {
+    /// Fatigue above 0.7
+    HighFatigue,
+    
+    /// Focus below 0.3
+    LowFocus,
+    
+    /// Nothing needs changing
+    Steady,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextRecommendation {
+    pub action: String,
+    pub reason: ReasonCode,
+}
+
+/// A wave pattern as `mem8.wave_context` reports it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ContextPattern {
+    pub pattern_type: String,
+    pub confidence: f64,
+    pub wonder: bool,
+    pub timestamp: u64,
+}
+
+/// Everything `mem8.wave_context` says, as data
+/// 
+/// The narrative is written from this (see `narrative`), so the prose and
+/// the fields can't disagree. The history fields are only there at `Full`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WaveContext {
+    /// By the name `mem8.set_activity` takes (`Activity::name`)
+    #[serde(serialize_with = "serialize_activity_name")]
+    pub activity: Activity,
+    pub fatigue: f64,
+    pub focus: f64,
+    
+    /// Patterns in the buffer (not just the ones included)
+    pub wave_count: usize,
+    
+    /// Share of buffered patterns that showed wonder, 0..1
+    pub wonder_ratio: f64,
+    
+    /// "stable" until mood readings arrive, then "evolving"
+    pub mood_trajectory: String,
+    
+    /// Newest first
+    pub recent_patterns: Vec<ContextPattern>,
+    
+    pub recommendation: ContextRecommendation,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mood_readings: Option<Vec<MoodReading>>,
+    
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub activity_log: Option<Vec<ActivityTransition>>,
+}
+
+fn serialize_activity_name<S: serde::Serializer>(activity: &Activity, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+    serializer.serialize_str(activity.name())
+}
+
+impl WaveContext {
+    pub fn from_buffer(buffer: &SensorBuffer, activity: &Activity, detail: ContextDetail) -> Self {
+        let patterns = buffer.wave_patterns.len();
+        let wonders = buffer.wave_patterns.iter().filter(|p| p.wonder_detected).count();
+        let included = match detail {
+            ContextDetail::Brief => 0,
+            ContextDetail::Standard => STANDARD_CONTEXT_PATTERNS,
+            ContextDetail::Full => patterns,
+        };
+        let (action, reason) = if buffer.fatigue_level > 0.7 {
+            ("suggest break or ambient music", ReasonCode::HighFatigue)
+        } else if buffer.focus_score < 0.3 {
+            ("recommend focus-enhancing electronic", ReasonCode::LowFocus)
+        } else {
+            ("maintain current trajectory", ReasonCode::Steady)
+        };
+        let full = detail == ContextDetail::Full;
+        
+        Self {
+            activity: activity.clone(),
+            fatigue: buffer.fatigue_level,
+            focus: buffer.focus_score,
+            wave_count: patterns,
+            wonder_ratio: wonders as f64 / patterns.max(1) as f64,
+            mood_trajectory: if buffer.mood_readings.is_empty() { "stable" } else { "evolving" }.to_string(),
+            recent_patterns: buffer.wave_patterns.iter().rev().take(included)
+                .map(|p| ContextPattern {
+                    pattern_type: p.pattern_type.clone(),
+                    confidence: p.salience,
+                    wonder: p.wonder_detected,
+                    timestamp: p.timestamp,
+                })
+                .collect(),
+            recommendation: ContextRecommendation { action: action.to_string(), reason },
+            mood_readings: full.then(|| buffer.mood_readings.clone()),
+            activity_log: full.then(|| buffer.activity_log.clone()),
+        }
+    }
+    
+    /// The context as one English paragraph for the LLM
+    pub fn narrative(&self) -> String {
+        let doing = match &self.activity {
+            Activity::Programming => "programming (needs flow state)",
+            Activity::Decompressing => "decompressing (releasing tension)",
+            Activity::DeepThinking => "in deep thought (expanding time)",
+            Activity::Creating => "creating (seeking inspiration)",
+            other => other.name(),
+        };
+        let mut text = format!(
+            "User is currently {}. Fatigue: {:.0}%, Focus: {:.0}%. \
+             Recent wave patterns show {} peaks with {:.0}% showing wonder. \
+             Mood trajectory: {}.",
+            doing, self.fatigue * 100.0, self.focus * 100.0,
+            self.wave_count, self.wonder_ratio * 100.0, self.mood_trajectory,
+        );
+        if !self.recent_patterns.is_empty() {
+            let patterns: Vec<String> = self.recent_patterns.iter()
+                .map(|p| format!("{} ({:.0}%{})", p.pattern_type, p.confidence * 100.0, if p.wonder { ", wonder" } else { "" }))
+                .collect();
+            text += &format!(" Latest patterns: {}.", patterns.join(", "));
+        }
+        if let Some(mood) = self.mood_readings.as_ref().and_then(|moods| moods.last()) {
+            text += &format!(" Last mood: {} ({:.0}% confident).", mood.mood_state, mood.confidence * 100.0);
+        }
+        if let Some(change) = self.activity_log.as_ref().and_then(|log| log.last()) {
+            text += &format!(" Last activity change: {} to {} ({}).", change.from, change.to, change.trigger);
+        }
+        text + &format!(" Recommended action: {}", self.recommendation.action)
+    }
+}
+
 impl Mem8McpServer {
     /// Create a new MCP server instance
     pub fn new(storage_path: &str) -> Result<Self> {
-        let storage = Mem8Lite::new(storage_path, 1.618)?;
-        let mood_engine = MoodEngine::create_hue_profile();
+        let storage = Mem8Lite::new(storage_path, FrequencyPreset::GoldenRatio)?;
+        let mut mood_engine = MoodEngine::create_hue_profile();
+        let mood_history_path = PathBuf::from(format!("{}.mood.json", storage_path));
+        mood_engine.load_history(&mood_history_path)?;
         let marine = MarineProcessor::for_audio(44100.0);
         
-        Ok(Self {
-            storage: Arc::new(Mutex::new(storage)),
+        // Pick up where the DJ left off last time
+        let dj_memory_path = PathBuf::from(format!("{}.dj.json", storage_path));
+        let mut dj_mode = DjMode::new(DjPersonality::HueMode);
+        if let Ok(bytes) = std::fs::read(&dj_memory_path) {
+            if let Ok(memory) = serde_json::from_slice::<DjMemory>(&bytes) {
+                dj_mode.restore(memory);
+            }
+        }
+        
+        let config_fs = Mem8Fs::new(format!("{}.config", storage_path))?;
+        
+        let server = Self {
+            storage: SharedMem8Lite::new(storage),
             mood_engine: Arc::new(Mutex::new(mood_engine)),
             current_activity: Arc::new(Mutex::new(Activity::Programming)),
             marine: Arc::new(Mutex::new(marine)),
-            dj_mode: Arc::new(Mutex::new(DjMode {
-                enabled: false,
-                auto_skip: true,
-                vibe_threshold: 0.6,
-                queue: Vec::new(),
-                history: Vec::new(),
-                personality: DjPersonality::HueMode,
-            })),
+            dj_mode: Arc::new(Mutex::new(dj_mode)),
             sensor_buffer: Arc::new(Mutex::new(SensorBuffer {
                 mood_readings: Vec::new(),
                 wave_patterns: Vec::new(),
                 activity_log: Vec::new(),
                 fatigue_level: 0.0,
                 focus_score: 0.5,
+                sensors: BTreeMap::new(),
             })),
-        })
+            dj_memory_path,
+            mood_history_path,
+            config_fs,
+            config_watcher: Arc::new(Mutex::new(ConfigWatcher::new())),
+            tool_policy: Arc::new(Mutex::new(ToolPolicy::default())),
+        };
+        
+        // Stored configs override the built-in defaults
+        server.apply_config_changes(false);
+        Ok(server)
+    }
+    
+    /// The filesystem holding the live configs - write `/config/*.json` here
+    pub fn config_store(&self) -> &Mem8Fs {
+        &self.config_fs
+    }
+    
+    /// The memory store, for anything that writes packets next to the tools
+    pub fn storage(&self) -> SharedMem8Lite {
+        self.storage.clone()
+    }
+    
+    /// Feed a sensor reading into the context the tools report
+    /// 
+    /// The reading becomes its sensor's latest value in `mem8.get_sensor_data`.
+    /// Breathing sets the focus score; breathing and emotion readings also
+    /// add a mood reading.
+    pub fn ingest_sensor(&self, data: &SensorData) {
+        let mut buffer = self.sensor_buffer.lock().unwrap();
+        let mood = match data {
+            SensorData::Breathing { rate, regularity, .. } => {
+                buffer.focus_score = regularity.clamp(0.0, 1.0);
+                let state = if *rate > 20.0 {
+                    "stressed"
+                } else if *regularity > 0.7 {
+                    "focused"
+                } else {
+                    "calm"
+                };
+                Some((state, regularity.clamp(0.0, 1.0)))
+            }
+            SensorData::Emotion { valence, arousal, confidence, .. } => {
+                let state = match (*valence >= 0.0, *arousal >= 0.5) {
+                    (true, true) => "excited",
+                    (true, false) => "content",
+                    (false, true) => "stressed",
+                    (false, false) => "low",
+                };
+                Some((state, *confidence))
+            }
+            _ => None,
+        };
+        if let Some((mood_state, confidence)) = mood {
+            buffer.mood_readings.push(MoodReading {
+                timestamp: data.timestamp(),
+                mood_state: mood_state.to_string(),
+                confidence,
+            });
+            if buffer.mood_readings.len() > SENSOR_HISTORY_LIMIT {
+                buffer.mood_readings.remove(0);
+            }
+        }
+        buffer.sensors.insert(data.id().to_string(), data.clone());
+    }
+    
+    /// Note a pattern the sensor fusion detected
+    pub fn record_pattern(&self, pattern: &SensorPattern, timestamp: u64) {
+        let mut buffer = self.sensor_buffer.lock().unwrap();
+        buffer.wave_patterns.push(WavePattern {
+            timestamp,
+            pattern_type: pattern.pattern_type.clone(),
+            salience: pattern.confidence,
+            wonder_detected: pattern.wonder_score >= 0.7,
+        });
+        if buffer.wave_patterns.len() > SENSOR_HISTORY_LIMIT {
+            buffer.wave_patterns.remove(0);
+        }
+    }
+    
+    /// Run a tool call to completion on this thread
+    /// 
+    /// The handlers never actually wait on anything, so no async runtime is needed.
+    pub fn call_tool(&self, tool: &str, args: Value) -> Result<Value> {
+        use std::future::Future;
+        let waker = std::task::Waker::noop();
+        let mut context = std::task::Context::from_waker(waker);
+        let mut future = std::pin::pin!(self.handle_tool(tool, args));
+        loop {
+            if let std::task::Poll::Ready(result) = future.as_mut().poll(&mut context) {
+                return result;
+            }
+        }
+    }
+    
+    /// Serve MCP as line-delimited JSON-RPC 2.0 until `input` runs out
+    /// 
+    /// This is the stdio transport: one request per line in, one response per
+    /// line out. Handles `initialize`, `tools/list` and `tools/call`;
+    /// notifications (no `id`) get no reply. A failing tool comes back as an
+    /// `isError` result, as MCP clients expect.
+    pub fn serve_stdio<R: BufRead, W: Write>(&self, input: R, mut output: W) -> Result<()> {
+        for line in input.lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let response = match serde_json::from_str::<Value>(&line) {
+                Ok(request) => self.handle_rpc(&request),
+                Err(e) => Some(rpc_error(Value::Null, -32700, &format!("Parse error: {}", e))),
+            };
+            if let Some(response) = response {
+                writeln!(output, "{}", response)?;
+                output.flush()?;
+            }
+        }
+        Ok(())
+    }
+    
+    /// Answer one JSON-RPC request (None for notifications)
+    fn handle_rpc(&self, request: &Value) -> Option<Value> {
+        let id = request.get("id")?.clone();
+        let params = &request["params"];
+        let result = match request["method"].as_str().unwrap_or("") {
+            "initialize" => json!({
+                "protocolVersion": "2024-11-05",
+                "serverInfo": {"name": "mem8", "version": env!("CARGO_PKG_VERSION")},
+                "capabilities": {"tools": {}},
+            }),
+            "tools/list" => json!({"tools": get_mcp_tools()}),
+            "tools/call" => {
+                let name = match params["name"].as_str() {
+                    Some(name) => name,
+                    None => return Some(rpc_error(id, -32602, "Missing tool name")),
+                };
+                let args = params.get("arguments").cloned().unwrap_or_else(|| json!({}));
+                match self.call_tool(name, args) {
+                    Ok(value) => json!({
+                        "content": [{"type": "text", "text": value.to_string()}],
+                        "structuredContent": value,
+                    }),
+                    Err(e) => json!({
+                        "content": [{"type": "text", "text": e.to_string()}],
+                        "isError": true,
+                    }),
+                }
+            }
+            other => return Some(rpc_error(id, -32601, &format!("Unknown method: {}", other))),
+        };
+        Some(json!({"jsonrpc": "2.0", "id": id, "result": result}))
+    }
+    
+    /// Load config files that changed (every one of them with `force`)
+    fn apply_config_changes(&self, force: bool) -> Vec<ConfigEvent> {
+        let mut watcher = self.config_watcher.lock().unwrap();
+        let mut events = Vec::new();
+        for (path, bytes) in watcher.changed(&self.config_fs, force) {
+            let outcome = self.apply_config(path, &bytes);
+            events.push(watcher.record(path, outcome));
+        }
+        events
+    }
+    
+    /// Parse and validate one config file, swapping it in only if it's good
+    fn apply_config(&self, path: &str, bytes: &[u8]) -> Result<String> {
+        match path {
+            PROFILE_PATH => {
+                let profile: MusicProfile = serde_json::from_slice(bytes)?;
+                validate_profile(&profile)?;
+                let message = format!("Profile {} loaded", profile.name);
+                self.mood_engine.lock().unwrap().set_profile(profile);
+                self.dj_mode.lock().unwrap().suggestion_cache.invalidate();
+                Ok(message)
+            }
+            DJ_PATH => {
+                let config: DjConfig = serde_json::from_slice(bytes)?;
+                config.validate()?;
+                config.apply(&mut self.dj_mode.lock().unwrap());
+                Ok(format!("DJ settings loaded ({:?})", config.personality))
+            }
+            POLICY_PATH => {
+                let policy: ToolPolicy = serde_json::from_slice(bytes)?;
+                policy.validate()?;
+                let message = format!("Tool policy loaded ({} disabled)", policy.disabled_tools.len());
+                *self.tool_policy.lock().unwrap() = policy;
+                Ok(message)
+            }
+            other => Err(anyhow!("Not a config file: {}", other)),
+        }
+    }
+    
+    /// Flush the memory store before the server goes away
+    /// 
+    /// Call this from the SIGINT/SIGTERM path (see `crate::shutdown`) so the
+    /// last memories make it to disk.
+    pub fn shutdown(&self) -> Result<()> {
+        self.mood_engine.lock().unwrap().save_history(&self.mood_history_path)?;
+        self.config_fs.sync()?;
+        Ok(self.storage.sync()?)
     }
     
     /// Handle MCP tool calls
+    /// 
+    /// Config edits are picked up before the tool runs.
     pub async fn handle_tool(&self, tool: &str, args: Value) -> Result<Value> {
+        self.apply_config_changes(false);
+        if !self.tool_policy.lock().unwrap().allows(tool) {
+            return Err(anyhow!("Tool {} is disabled by {}", tool, POLICY_PATH));
+        }
+        
         match tool {
             "mem8.store_memory" => self.store_memory(args).await,
+            "mem8.store_memories" => self.store_memories(args).await,
             "mem8.retrieve_memory" => self.retrieve_memory(args).await,
+            "mem8.delete_memory" => self.delete_memory(args).await,
+            "mem8.get_memory_group" => self.get_memory_group(args).await,
+            "mem8.list_memories" => self.list_memories(args).await,
             "mem8.analyze_audio" => self.analyze_audio(args).await,
+            "mem8.probe_audio" => self.probe_audio(args).await,
             "mem8.get_mood_state" => self.get_mood_state().await,
             "mem8.set_activity" => self.set_activity(args).await,
-            "mem8.dj_suggest" => self.dj_suggest().await,
+            "mem8.log_listening" => self.log_listening(args).await,
+            "mem8.activity_report" => self.activity_report(args).await,
+            "mem8.dj_suggest" => self.dj_suggest(args).await,
+            "mem8.dj_feedback" => self.dj_feedback(args).await,
             "mem8.dj_enable" => self.enable_dj_mode(args).await,
+            "mem8.set_content_filter" => self.set_content_filter(args).await,
             "mem8.get_sensor_data" => self.get_sensor_data().await,
+            "mem8.state_at" => self.state_at(args).await,
             "mem8.detect_fatigue" => self.detect_fatigue().await,
-            "mem8.wave_context" => self.get_wave_context().await,
+            "mem8.wave_context" => self.get_wave_context(args).await,
+            "mem8.highlights" => self.highlights(args).await,
+            "mem8.top_wonder" => self.top_wonder(args).await,
+            "mem8.reload_config" => self.reload_config().await,
             _ => Err(anyhow!("Unknown tool: {}", tool)),
         }
     }
@@ -188,30 +963,108 @@ impl Mem8McpServer {
             .ok_or_else(|| anyhow!("Missing data field"))?;
         let perspective = args["perspective"].as_str().unwrap_or("neutral");
         let metadata = args["metadata"].clone();
-        
-        let mut storage = self.storage.lock().unwrap();
+        let group = args["memory_group"].as_str();
         
         // Add temporal perspective to metadata
-        let mut meta = if metadata.is_object() {
-            metadata
-        } else {
-            json!({})
+        let meta = memory_metadata(perspective, metadata, unix_now()?);
+        let meta = Some(serde_json::to_vec(&meta)?);
+        let stored = match group {
+            Some(group) => self.storage.write().store_linked(group, data.as_bytes(), meta),
+            None => self.storage.store(data.as_bytes(), meta),
         };
-        
-        meta["perspective"] = json!(perspective);
-        meta["timestamp"] = json!(std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)?
-            .as_secs());
-        
-        let signature = storage.store(
-            data.as_bytes(),
-            Some(serde_json::to_vec(&meta)?)
-        )?;
+        let signature = stored.map_err(|e| match e.downcast_ref::<MetadataRejected>() {
+            Some(rejected) => anyhow!("Memory not stored, the store's metadata policy refused it: {}", rejected),
+            None => e.into(),
+        })?;
         
         Ok(json!({
             "signature": hex::encode(&signature),
             "stored": true,
-            "perspective": perspective
+            "perspective": perspective,
+            "memory_group": group,
+        }))
+    }
+    
+    /// Store many memories in one storage batch, reporting on each
+    /// 
+    /// Items that can't be stored (no data, metadata that isn't an object, an
+    /// empty group id) get a structured error and the rest go ahead - unless
+    /// `atomic` is set, when one bad item means nothing is stored. The good
+    /// items go to disk in a single `store_batch`, which is all-or-nothing
+    /// either way; only linking them into their groups comes after it.
+    async fn store_memories(&self, args: Value) -> Result<Value> {
+        let items = args["items"].as_array()
+            .ok_or_else(|| anyhow!("Missing items array"))?;
+        let atomic = args["atomic"].as_bool().unwrap_or(false);
+        if items.len() > MAX_BATCH_ITEMS {
+            return Err(anyhow!("{} items in one batch; the limit is {}", items.len(), MAX_BATCH_ITEMS));
+        }
+        
+        let now = unix_now()?;
+        let mut results = vec![Value::Null; items.len()];
+        let mut ready = Vec::new();
+        for (index, item) in items.iter().enumerate() {
+            match batch_item(item, now) {
+                Ok(prepared) => ready.push((index, prepared)),
+                Err((code, message)) => results[index] = item_error(index, code, &message),
+            }
+        }
+        let total: usize = ready.iter().map(|(_, (data, meta, _))| data.len() + meta.len()).sum();
+        if total > MAX_BATCH_BYTES {
+            return Err(anyhow!("Batch payload is {} bytes; the limit is {}", total, MAX_BATCH_BYTES));
+        }
+        
+        let mut failed = items.len() - ready.len();
+        let written = if atomic && failed > 0 {
+            Err(("rolled_back", "Another item in this atomic batch failed".to_string()))
+        } else {
+            let batch: Vec<(&[u8], Option<Vec<u8>>)> = ready.iter()
+                .map(|(_, (data, meta, _))| (data.as_bytes(), Some(meta.clone())))
+                .collect();
+            let mut storage = self.storage.write();
+            match storage.store_batch(&batch) {
+                Ok(signatures) => Ok((storage, signatures)),
+                Err(e) if e.is::<MetadataRejected>() => Err(("metadata_rejected", e.to_string())),
+                Err(e) => Err(("storage_failed", e.to_string())),
+            }
+        };
+        
+        match written {
+            Ok((mut storage, signatures)) => {
+                for ((index, (_, _, group)), signature) in ready.iter().zip(signatures) {
+                    let linked = match group {
+                        Some(group) => storage.link_to_group(group, &signature),
+                        None => Ok(()),
+                    };
+                    results[*index] = match linked {
+                        Ok(()) => json!({
+                            "index": index,
+                            "ok": true,
+                            "signature": hex::encode(signature),
+                            "memory_group": group,
+                        }),
+                        Err(e) => {
+                            failed += 1;
+                            let mut error = item_error(*index, "link_failed", &e.to_string());
+                            error["signature"] = json!(hex::encode(signature));
+                            error
+                        }
+                    };
+                }
+            }
+            Err((code, message)) => {
+                failed = items.len();
+                for (index, _) in &ready {
+                    results[*index] = item_error(*index, code, &message);
+                }
+            }
+        }
+        
+        Ok(json!({
+            "stored": items.len() - failed,
+            "failed": failed,
+            "atomic": atomic,
+            "results": results,
         }))
     }
     
@@ -224,24 +1077,177 @@ impl Mem8McpServer {
         let mut signature = [0u8; 32];
         signature.copy_from_slice(&signature_bytes[..32]);
         
-        let storage = self.storage.lock().unwrap();
+        let encoding = match args["encoding"].as_str() {
+            Some(name) => name.parse::<TextEncoding>()?,
+            None => TextEncoding::Auto,
+        };
+        
+        let storage = self.storage.read();
         let data = storage.retrieve(&signature)?;
-        let metadata = storage.get_metadata(&signature);
+        let metadata = storage.get_metadata(&signature)
+            .and_then(|m| serde_json::from_slice::<Value>(&m).ok());
+        let group = metadata.as_ref()
+            .and_then(|m| m["memory_group"].as_str())
+            .map(str::to_string);
+        let siblings = match &group {
+            Some(group) => Some(storage.get_memory_group(group)?.iter()
+                .filter(|m| m.signature != signature)
+                .count()),
+            None => None,
+        };
+        
+        // Binary payloads go out as hex rather than lossily mangled text
+        let (data, data_hex, encoding) = match text::decode(data.clone(), encoding) {
+            Ok((text, used)) => (json!(text), Value::Null, used.name()),
+            Err(_) => (Value::Null, json!(hex::encode(&data)), "binary"),
+        };
         
         Ok(json!({
-            "data": String::from_utf8_lossy(&data),
-            "metadata": metadata.and_then(|m| serde_json::from_slice::<Value>(&m).ok()),
+            "data": data,
+            "data_hex": data_hex,
+            "encoding": encoding,
+            "metadata": metadata,
+            "memory_group": group,
+            "siblings": siblings,
             "signature": signature_hex
         }))
     }
     
+    /// Every perspective stored for one memory, oldest first
+    async fn get_memory_group(&self, args: Value) -> Result<Value> {
+        let group = args["memory_group"].as_str()
+            .ok_or_else(|| anyhow!("Missing memory_group field"))?;
+        let members = self.storage.read().get_memory_group(group)?;
+        
+        Ok(json!({
+            "memory_group": group,
+            "members": members.iter().map(|m| json!({
+                "signature": hex::encode(m.signature),
+                "perspective": m.perspective,
+                "timestamp": m.timestamp,
+            })).collect::<Vec<_>>(),
+        }))
+    }
+    
+    /// Memories stored in a time window, oldest first, optionally filtered on metadata
+    /// 
+    /// The window is `since`..`until` in unix seconds, or a whole UTC day
+    /// with `days_ago` (0 is today, 1 yesterday). `pointer` and `equals`
+    /// keep only memories whose metadata has that value at that JSON pointer.
+    async fn list_memories(&self, args: Value) -> Result<Value> {
+        let now = unix_now()?;
+        let range = match args["days_ago"].as_u64() {
+            Some(days) => {
+                let start = (now / 86_400).checked_sub(days)
+                    .ok_or_else(|| anyhow!("days_ago reaches back before 1970"))? * 86_400;
+                start..start + 86_400
+            }
+            None => args["since"].as_u64().unwrap_or(0)..args["until"].as_u64().unwrap_or(now + 1),
+        };
+        let limit = args["limit"].as_u64().unwrap_or(100) as usize;
+        
+        let storage = self.storage.read();
+        let mut signatures = storage.find_by_time(range.clone());
+        if let Some(pointer) = args["pointer"].as_str() {
+            let matching: HashSet<[u8; 32]> = storage.find_by_metadata_json(pointer, &args["equals"]).into_iter().collect();
+            signatures.retain(|signature| matching.contains(signature));
+        }
+        let total = signatures.len();
+        
+        let memories: Vec<Value> = signatures.iter().take(limit).map(|signature| {
+            let metadata = storage.get_metadata(signature)
+                .and_then(|m| serde_json::from_slice::<Value>(&m).ok());
+            json!({
+                "signature": hex::encode(signature),
+                "timestamp": storage.stored_at(signature),
+                "metadata": metadata,
+            })
+        }).collect();
+        
+        Ok(json!({
+            "since": range.start,
+            "until": range.end,
+            "total": total,
+            "memories": memories,
+        }))
+    }
+    
+    /// The `k` memories with the highest Marine salience, most salient first
+    async fn top_wonder(&self, args: Value) -> Result<Value> {
+        let k = args["k"].as_u64().unwrap_or(10) as usize;
+        let storage = self.storage.read();
+        let memories: Vec<Value> = storage.top_wonder(k).into_iter().map(|(signature, salience, timestamp)| {
+            let metadata = storage.get_metadata(&signature)
+                .and_then(|m| serde_json::from_slice::<Value>(&m).ok());
+            json!({
+                "signature": hex::encode(signature),
+                "max_salience": salience,
+                "timestamp": timestamp,
+                "metadata": metadata,
+            })
+        }).collect();
+        
+        Ok(json!({ "memories": memories }))
+    }
+    
+    /// Forget a memory - tombstoned, or scrubbed from disk with `secure: true`
+    async fn delete_memory(&self, args: Value) -> Result<Value> {
+        let signature = parse_signature(args["signature"].as_str()
+            .ok_or_else(|| anyhow!("Missing signature field"))?)?;
+        let secure = args["secure"].as_bool().unwrap_or(false);
+        
+        let mut storage = self.storage.write();
+        if secure {
+            let report = storage.secure_delete(&signature)?;
+            Ok(json!({
+                "signature": hex::encode(signature),
+                "deleted": true,
+                "secure": true,
+                "scrub": report,
+            }))
+        } else {
+            storage.delete(&signature)?;
+            Ok(json!({
+                "signature": hex::encode(signature),
+                "deleted": true,
+                "secure": false,
+                "note": "Tombstoned; the bytes stay on disk until compaction (use secure: true to scrub now)",
+            }))
+        }
+    }
+    
+    /// Cut the top wonder moments of a stored track into clips
+    async fn highlights(&self, args: Value) -> Result<Value> {
+        let signature = parse_signature(args["signature"].as_str()
+            .ok_or_else(|| anyhow!("Missing signature field"))?)?;
+        let count = args["count"].as_u64().unwrap_or(3) as usize;
+        let clip_seconds = args["clip_seconds"].as_f64().unwrap_or(10.0);
+        
+        let clips = highlights::extract_highlights(&self.storage.read(), &signature, count, clip_seconds)?;
+        let mut described: Vec<Value> = clips.iter().map(|c| c.to_json()).collect();
+        
+        // Optionally keep them as packets linked back to the track
+        if args["store"].as_bool().unwrap_or(false) {
+            let stored = highlights::store_highlights(&mut self.storage.write(), &signature, &clips)?;
+            for (clip, sig) in described.iter_mut().zip(stored) {
+                clip["signature"] = json!(hex::encode(sig));
+            }
+        }
+        
+        Ok(json!({
+            "track": hex::encode(signature),
+            "highlights": described,
+        }))
+    }
+    
     /// Analyze audio and return mood predictions
     async fn analyze_audio(&self, args: Value) -> Result<Value> {
         let file_path = args["file_path"].as_str()
             .ok_or_else(|| anyhow!("Missing file_path"))?;
+        let track = args["track"].as_u64().map(|t| t as u32);
         
-        // Load audio file
-        let loaded = load_audio_file(file_path)?;
+        // Load audio file (for containers: the chosen or first decodable audio track)
+        let loaded = load_audio_track(file_path, track)?;
         
         // Convert to mono for Marine processing
         let mono_samples = if loaded.format.channels == 2 {
@@ -252,16 +1258,31 @@ impl Mem8McpServer {
             loaded.samples.clone()
         };
         
-        // Process through Marine
+        // Process through Marine, with nothing left over from the last file
         let mut marine = self.marine.lock().unwrap();
-        let peaks = marine.process_samples(&mono_samples);
-        let marine_meta = marine.extract_metadata(&peaks);
+        marine.reset();
+        marine.spectral = loaded.format.sample_rate.optimal_marine_settings().spectral;
+        let (peaks, marine_meta) = marine.analyze(&mono_samples);
         
         // Get mood prediction
         let mut mood_engine = self.mood_engine.lock().unwrap();
         let artist = loaded.metadata.as_ref().and_then(|m| m.artist.as_deref());
         let prediction = mood_engine.predict_mood_effect(&mono_samples, &marine_meta, artist);
         
+        // Salience over time, from a fresh processor set up like the shared
+        // one, and the sections it falls into (from one-second windows unless
+        // asked for others)
+        let rate = loaded.format.sample_rate.as_f64();
+        let window_ms = args["timeline_window_ms"].as_u64();
+        let window = ((rate * window_ms.unwrap_or(1000) as f64 / 1000.0) as usize).max(1);
+        let mut fresh = crate::marine::MarineConfig::from(&*marine).build();
+        let windows = fresh.salience_timeline(&mono_samples, window, window);
+        let segments = marine.segment(&peaks, &windows, rate);
+        let timeline = match window_ms {
+            Some(_) => serde_json::to_value(&windows)?,
+            None => Value::Null,
+        };
+        
         Ok(json!({
             "file": file_path,
             "format": {
@@ -269,12 +1290,16 @@ impl Mem8McpServer {
                 "channels": loaded.format.channels,
                 "bit_depth": loaded.format.bit_depth,
             },
+            "metadata": loaded.metadata,
             "marine_analysis": {
                 "total_peaks": marine_meta.total_peaks,
                 "wonder_count": marine_meta.wonder_count,
                 "emotion": marine_meta.emotional_signature,
                 "has_rhythm": marine_meta.has_rhythm,
+                "spectral": marine_meta.spectral,
             },
+            "timeline": timeline,
+            "segments": segments,
             "mood_prediction": {
                 "state": format!("{}", prediction.predicted_state),
                 "effectiveness": prediction.effectiveness,
@@ -283,14 +1308,28 @@ impl Mem8McpServer {
         }))
     }
     
-    /// Get current mood state
-    async fn get_mood_state(&self) -> Result<Value> {
-        let mood_engine = self.mood_engine.lock().unwrap();
-        let activity = self.current_activity.lock().unwrap();
+    /// List the audio tracks in a file (so callers can pick one for analyze_audio)
+    async fn probe_audio(&self, args: Value) -> Result<Value> {
+        let file_path = args["file_path"].as_str()
+            .ok_or_else(|| anyhow!("Missing file_path"))?;
+        let probe = probe_audio(file_path)?;
+        
+        Ok(json!({
+            "file": file_path,
+            "format": format!("{:?}", probe.file_format),
+            "metadata": probe.metadata,
+            "tracks": probe.tracks,
+        }))
+    }
+    
+    /// Get current mood state
+    async fn get_mood_state(&self) -> Result<Value> {
+        let mood_engine = self.mood_engine.lock().unwrap();
+        let activity = self.current_activity.lock().unwrap();
         let sensor_buffer = self.sensor_buffer.lock().unwrap();
         
         Ok(json!({
-            "current_activity": format!("{:?}", *activity),
+            "current_activity": activity.to_string(),
             "fatigue_level": sensor_buffer.fatigue_level,
             "focus_score": sensor_buffer.focus_score,
             "recent_moods": sensor_buffer.mood_readings.last(),
@@ -302,17 +1341,7 @@ impl Mem8McpServer {
         let activity_str = args["activity"].as_str()
             .ok_or_else(|| anyhow!("Missing activity"))?;
         
-        let new_activity = match activity_str {
-            "programming" => Activity::Programming,
-            "decompressing" => Activity::Decompressing,
-            "deep_thinking" => Activity::DeepThinking,
-            "creating" => Activity::Creating,
-            "relaxing" => Activity::Relaxing,
-            "exercising" => Activity::Exercising,
-            "commuting" => Activity::Commuting,
-            "sleeping" => Activity::Sleeping,
-            _ => return Err(anyhow!("Unknown activity: {}", activity_str)),
-        };
+        let new_activity = Activity::parse(activity_str)?;
         
         let old_activity = {
             let mut current = self.current_activity.lock().unwrap();
@@ -322,27 +1351,115 @@ impl Mem8McpServer {
         };
         
         // Log transition
-        let mut buffer = self.sensor_buffer.lock().unwrap();
-        buffer.activity_log.push(ActivityTransition {
+        self.sensor_buffer.lock().unwrap().activity_log.push(ActivityTransition {
             timestamp: std::time::SystemTime::now()
                 .duration_since(std::time::UNIX_EPOCH)?
                 .as_secs(),
-            from: format!("{:?}", old_activity),
-            to: format!("{:?}", new_activity),
+            from: old_activity.to_string(),
+            to: new_activity.to_string(),
             trigger: "manual".to_string(),
         });
         
+        // What the profile has to say about it (custom activities included)
+        let engine = self.mood_engine.lock().unwrap();
+        let profile = engine.profile();
+        let genres = profile.activity_preferences.get(&new_activity).cloned().unwrap_or_default();
+        let (low, high) = profile.tempo_range(&new_activity);
+        
+        Ok(json!({
+            "activity_set": new_activity.to_string(),
+            "previous": old_activity.to_string(),
+            "custom": new_activity.is_custom(),
+            "preferred_genres": genres,
+            "tempo_bpm": [low, high],
+        }))
+    }
+    
+    /// Journal a listen (defaults to the current activity)
+    async fn log_listening(&self, args: Value) -> Result<Value> {
+        let artist = args["artist"].as_str()
+            .ok_or_else(|| anyhow!("Missing artist"))?;
+        let effectiveness = args["effectiveness"].as_f64()
+            .ok_or_else(|| anyhow!("Missing effectiveness"))?
+            .clamp(0.0, 1.0);
+        let activity = match args["activity"].as_str() {
+            Some(name) => Activity::parse(name)?,
+            None => self.current_activity.lock().unwrap().clone(),
+        };
+        let genre = match args["genre"].as_str() {
+            Some(name) => Some(serde_json::from_value::<Genre>(json!(name))
+                .map_err(|_| anyhow!("Unknown genre: {}", name))?),
+            None => None,
+        };
+        
+        let entry = ListeningEntry {
+            timestamp: std::time::SystemTime::now()
+                .duration_since(std::time::UNIX_EPOCH)?
+                .as_secs(),
+            activity,
+            artist: artist.to_string(),
+            title: args["title"].as_str().unwrap_or("").to_string(),
+            genre,
+            minutes: args["minutes"].as_f64().unwrap_or(0.0),
+            effectiveness,
+        };
+        
+        self.dj_mode.lock().unwrap().suggestion_cache.invalidate();
+        let mut mood_engine = self.mood_engine.lock().unwrap();
+        mood_engine.record_listening(entry.clone());
+        mood_engine.save_history(&self.mood_history_path)?;
+        
         Ok(json!({
-            "activity_set": format!("{:?}", new_activity),
-            "previous": format!("{:?}", old_activity),
+            "logged": entry,
+            "journal_size": mood_engine.journal().len(),
+        }))
+    }
+    
+    /// Per-activity listening report with effectiveness trends
+    async fn activity_report(&self, args: Value) -> Result<Value> {
+        let days = args["days"].as_u64().unwrap_or(14);
+        let report = self.mood_engine.lock().unwrap().activity_report(ReportRange::last_days(days));
+        
+        Ok(json!({
+            "report": report,
+            "text": report.to_string(),
         }))
     }
     
     /// DJ suggestion based on current context
-    async fn dj_suggest(&self) -> Result<Value> {
+    /// 
+    /// The same context within `suggestion_cache_secs` gets the same picks
+    /// back (`cached: true`) unless `refresh` is set.
+    async fn dj_suggest(&self, args: Value) -> Result<Value> {
+        let refresh = args["refresh"].as_bool().unwrap_or(false);
         let activity = self.current_activity.lock().unwrap().clone();
-        let dj_mode = self.dj_mode.lock().unwrap();
+        let mut dj_mode = self.dj_mode.lock().unwrap();
         let sensor_buffer = self.sensor_buffer.lock().unwrap();
+        let profile_name = self.mood_engine.lock().unwrap().profile().name.clone();
+        
+        let context = SuggestionContext {
+            activity: activity.to_string(),
+            mood: sensor_buffer.mood_readings.last().map(|reading| reading.mood_state.clone()),
+            fatigue_bucket: (sensor_buffer.fatigue_level.clamp(0.0, 1.0) * 10.0) as u8,
+            personality: format!("{:?}", dj_mode.personality),
+            filter_hash: hash_of(&serde_json::to_string(&dj_mode.content_guard.filter)?),
+            queue_hash: hash_of(&dj_mode.queue.iter().map(track_key).collect::<Vec<_>>()),
+        };
+        let ttl = std::time::Duration::from_secs(dj_mode.suggestion_cache_secs);
+        if refresh {
+            dj_mode.suggestion_cache.invalidate();
+        } else if let Some(picks) = dj_mode.suggestion_cache.get(&context, ttl) {
+            return Ok(json!({
+                "dj_active": dj_mode.enabled,
+                "current_activity": activity.to_string(),
+                "suggestions": picks,
+                "personality": context.personality,
+                "profile": profile_name,
+                "cached": true,
+                "filter_violations": dj_mode.content_guard.stats,
+                "suggestion_cache": dj_mode.suggestion_cache,
+            }));
+        }
         
         // Generate suggestions based on activity and mood
         let suggestions = match (&activity, sensor_buffer.fatigue_level) {
@@ -426,16 +1543,68 @@ impl Mem8McpServer {
             ],
         };
         
-        // Never suggest Polka!
+        // Nothing the profile avoids (the content filter runs in pick_suggestions)
+        let (avoided, allowed) = {
+            let mood_engine = self.mood_engine.lock().unwrap();
+            let profile = mood_engine.profile();
+            (profile.avoid_genres.clone(), profile.allowed_genres())
+        };
         let filtered_suggestions: Vec<_> = suggestions.into_iter()
-            .filter(|s| s.genre != Genre::Polka)
+            .filter(|s| !avoided.contains(&s.genre))
             .collect();
         
+        // Apply diversity controls and remember what we picked
+        let picks = dj_mode.pick_suggestions(&filtered_suggestions, &allowed);
+        std::fs::write(&self.dj_memory_path, serde_json::to_vec(&dj_mode.memory())?)?;
+        if !ttl.is_zero() {
+            dj_mode.suggestion_cache.put(context, picks.clone());
+        }
+        
         Ok(json!({
             "dj_active": dj_mode.enabled,
-            "current_activity": format!("{:?}", activity),
-            "suggestions": filtered_suggestions,
+            "current_activity": activity.to_string(),
+            "suggestions": picks,
             "personality": format!("{:?}", dj_mode.personality),
+            "profile": profile_name,
+            "cached": false,
+            "filter_violations": dj_mode.content_guard.stats,
+            "suggestion_cache": dj_mode.suggestion_cache,
+        }))
+    }
+    
+    /// Tell the DJ what you thought of a track
+    /// 
+    /// A skip puts the track on the repeat cooldown. Any feedback throws away
+    /// cached suggestions so the next `dj_suggest` takes it into account.
+    async fn dj_feedback(&self, args: Value) -> Result<Value> {
+        let artist = args["artist"].as_str()
+            .ok_or_else(|| anyhow!("Missing artist"))?;
+        let title = args["title"].as_str()
+            .ok_or_else(|| anyhow!("Missing title"))?;
+        let action = args["action"].as_str()
+            .ok_or_else(|| anyhow!("Missing action"))?;
+        let key = format!("{} - {}", artist, title);
+        
+        let mut dj_mode = self.dj_mode.lock().unwrap();
+        let was_suggested = dj_mode.suggestion_cache.contains(&key);
+        match action {
+            "skip" => {
+                dj_mode.history.push(key.clone());
+                if dj_mode.history.len() > DJ_HISTORY_LIMIT {
+                    dj_mode.history.remove(0);
+                }
+                std::fs::write(&self.dj_memory_path, serde_json::to_vec(&dj_mode.memory())?)?;
+            }
+            "like" => {}
+            other => return Err(anyhow!("Unknown feedback action: {} (expected skip or like)", other)),
+        }
+        dj_mode.suggestion_cache.invalidate();
+        
+        Ok(json!({
+            "track": key,
+            "action": action,
+            "was_suggested": was_suggested,
+            "suggestion_cache": dj_mode.suggestion_cache,
         }))
     }
     
@@ -468,6 +1637,47 @@ impl Mem8McpServer {
         }))
     }
     
+    /// Change part of the content filter and save it to the DJ config
+    /// 
+    /// Fields left out keep their current values. The edit goes through
+    /// `/config/dj.json` like a hand edit would, so it survives restarts.
+    async fn set_content_filter(&self, args: Value) -> Result<Value> {
+        let mut config = DjConfig::from(&*self.dj_mode.lock().unwrap());
+        let mut filter = serde_json::to_value(&config.content_filter)?;
+        for (field, value) in args.as_object().into_iter().flatten() {
+            if filter.get(field).is_none() {
+                return Err(anyhow!("Unknown content filter field: {}", field));
+            }
+            filter[field] = value.clone();
+        }
+        config.content_filter = serde_json::from_value(filter)?;
+        config.validate()?;
+        
+        self.config_fs.write(DJ_PATH, &serde_json::to_vec_pretty(&config)?)?;
+        let events = self.apply_config_changes(false);
+        let dj = self.dj_mode.lock().unwrap();
+        
+        Ok(json!({
+            "content_filter": dj.content_guard.filter,
+            "filter_violations": dj.content_guard.stats,
+            "events": events,
+        }))
+    }
+    
+    /// Re-read every config file now, even ones that look unchanged
+    async fn reload_config(&self) -> Result<Value> {
+        let events = self.apply_config_changes(true);
+        let disabled_tools = self.tool_policy.lock().unwrap().disabled_tools.clone();
+        
+        Ok(json!({
+            "events": events,
+            "profile": self.mood_engine.lock().unwrap().profile().name,
+            "personality": format!("{:?}", self.dj_mode.lock().unwrap().personality),
+            "disabled_tools": disabled_tools,
+            "recent_events": self.config_watcher.lock().unwrap().events(),
+        }))
+    }
+    
     /// Get sensor buffer data
     async fn get_sensor_data(&self) -> Result<Value> {
         let buffer = self.sensor_buffer.lock().unwrap();
@@ -478,6 +1688,30 @@ impl Mem8McpServer {
             "recent_patterns": buffer.wave_patterns.len(),
             "activity_transitions": buffer.activity_log.len(),
             "latest_mood": buffer.mood_readings.last(),
+            "sensors": buffer.sensors,
+        }))
+    }
+    
+    /// Every sensor's stored reading as of a past instant
+    /// 
+    /// `sensors` defaults to the ones this server has seen readings from;
+    /// with neither, whatever turns up within the staleness bound is reported.
+    async fn state_at(&self, args: Value) -> Result<Value> {
+        let timestamp = args["timestamp"].as_u64()
+            .ok_or_else(|| anyhow!("Missing timestamp field (unix seconds)"))?;
+        let staleness = args["staleness_secs"].as_u64().unwrap_or(DEFAULT_STALENESS_SECS);
+        let sensors: Vec<String> = match args["sensors"].as_array() {
+            Some(ids) => ids.iter().filter_map(Value::as_str).map(str::to_string).collect(),
+            None => self.sensor_buffer.lock().unwrap().sensors.keys().cloned().collect(),
+        };
+        let sensors = (!sensors.is_empty()).then_some(sensors);
+        
+        let states = states_in_store(&self.storage.read(), sensors.as_deref(), timestamp, staleness)?;
+        Ok(json!({
+            "timestamp": states.timestamp,
+            "staleness_secs": staleness,
+            "states": states.states,
+            "stale": states.stale,
         }))
     }
     
@@ -508,54 +1742,90 @@ impl Mem8McpServer {
         }))
     }
     
-    /// Get wave context for LLM understanding
-    async fn get_wave_context(&self) -> Result<Value> {
-        let buffer = self.sensor_buffer.lock().unwrap();
-        let activity = self.current_activity.lock().unwrap();
-        
-        // Build context string for LLM
-        let context = format!(
-            "User is currently {}. Fatigue: {:.0}%, Focus: {:.0}%. \
-             Recent wave patterns show {} peaks with {:.0}% showing wonder. \
-             Mood trajectory: {}. Recommended action: {}",
-            match *activity {
-                Activity::Programming => "programming (needs flow state)",
-                Activity::Decompressing => "decompressing (releasing tension)",
-                Activity::DeepThinking => "in deep thought (expanding time)",
-                Activity::Creating => "creating (seeking inspiration)",
-                _ => "active",
-            },
-            buffer.fatigue_level * 100.0,
-            buffer.focus_score * 100.0,
-            buffer.wave_patterns.len(),
-            buffer.wave_patterns.iter().filter(|p| p.wonder_detected).count() as f64 
-                / buffer.wave_patterns.len().max(1) as f64 * 100.0,
-            if buffer.mood_readings.is_empty() {
-                "stable".to_string()
-            } else {
-                "evolving".to_string()
-            },
-            if buffer.fatigue_level > 0.7 {
-                "suggest break or ambient music"
-            } else if buffer.focus_score < 0.3 {
-                "recommend focus-enhancing electronic"
-            } else {
-                "maintain current trajectory"
-            }
-        );
+    /// Get wave context for LLM understanding: a narrative and the same thing as data
+    async fn get_wave_context(&self, args: Value) -> Result<Value> {
+        let detail = match args["detail"].as_str() {
+            Some(name) => ContextDetail::parse(name)?,
+            None => ContextDetail::default(),
+        };
+        let context = {
+            let buffer = self.sensor_buffer.lock().unwrap();
+            let activity = self.current_activity.lock().unwrap();
+            WaveContext::from_buffer(&buffer, &activity, detail)
+        };
         
         Ok(json!({
-            "context": context,
-            "raw_data": {
-                "activity": format!("{:?}", *activity),
-                "fatigue": buffer.fatigue_level,
-                "focus": buffer.focus_score,
-                "wave_count": buffer.wave_patterns.len(),
-            }
+            "detail": detail,
+            "context": context.narrative(),
+            "structured": context,
         }))
     }
 }
 
+/// A JSON-RPC error response
+fn rpc_error(id: Value, code: i64, message: &str) -> Value {
+    json!({"jsonrpc": "2.0", "id": id, "error": {"code": code, "message": message}})
+}
+
+/// Schema of an activity argument: one of the built-in names, or a custom one
+fn activity_schema(description: &str) -> Value {
+    json!({
+        "description": description,
+        "anyOf": [
+            {"type": "string", "enum": KNOWN_ACTIVITIES},
+            {"type": "string", "pattern": "^[A-Za-z][A-Za-z0-9_ -]*$", "description": "Custom activity (e.g. gaming, reading)"}
+        ]
+    })
+}
+
+/// Metadata a memory is stored with: the caller's object plus perspective and time
+fn memory_metadata(perspective: &str, metadata: Value, now: u64) -> Value {
+    let mut meta = if metadata.is_object() {
+        metadata
+    } else {
+        json!({})
+    };
+    meta["perspective"] = json!(perspective);
+    meta["timestamp"] = json!(now);
+    meta
+}
+
+fn unix_now() -> Result<u64> {
+    Ok(std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH)?.as_secs())
+}
+
+/// One `store_memories` item ready to store: (data, metadata, group)
+type BatchItem = (String, Vec<u8>, Option<String>);
+
+/// Check and prepare one `store_memories` item, or say why not as (code, message)
+fn batch_item(item: &Value, now: u64) -> std::result::Result<BatchItem, (&'static str, String)> {
+    let data = item["data"].as_str()
+        .ok_or(("missing_data", "Missing data field".to_string()))?;
+    if !(item["metadata"].is_object() || item["metadata"].is_null()) {
+        return Err(("invalid_metadata", "metadata must be an object".to_string()));
+    }
+    let perspective = item["perspective"].as_str().unwrap_or("neutral");
+    let meta = memory_metadata(perspective, item["metadata"].clone(), now);
+    let meta = serde_json::to_vec(&meta).map_err(|e| ("invalid_metadata", e.to_string()))?;
+    
+    let group = item["group_id"].as_str().map(str::to_string);
+    let meta = match &group {
+        Some(group) => Mem8Lite::linked_metadata(group, Some(&meta))
+            .map_err(|e| ("invalid_group", e.to_string()))?,
+        None => meta,
+    };
+    Ok((data.to_string(), meta, group))
+}
+
+/// A `store_memories` result for an item that wasn't stored
+fn item_error(index: usize, code: &str, message: &str) -> Value {
+    json!({
+        "index": index,
+        "ok": false,
+        "error": {"code": code, "message": message},
+    })
+}
+
 /// MCP tool definitions for registration
 pub fn get_mcp_tools() -> Vec<Value> {
     vec![
@@ -567,33 +1837,234 @@ pub fn get_mcp_tools() -> Vec<Value> {
                 "properties": {
                     "data": {"type": "string", "description": "The data to store"},
                     "perspective": {"type": "string", "description": "Temporal perspective (diary/witness/third_party)"},
-                    "metadata": {"type": "object", "description": "Additional metadata"}
+                    "metadata": {"type": "object", "description": "Additional metadata"},
+                    "memory_group": {"type": "string", "description": "Link this with other perspectives of the same memory"}
                 },
                 "required": ["data"]
             }
         }),
         
+        json!({
+            "name": "mem8.store_memories",
+            "description": "Store many memories in one batch; each item reports its signature or an error",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "items": {
+                        "type": "array",
+                        "description": "Memories to store (at most 256, 8 MiB of data and metadata in all)",
+                        "items": {
+                            "type": "object",
+                            "properties": {
+                                "data": {"type": "string"},
+                                "perspective": {"type": "string"},
+                                "metadata": {"type": "object"},
+                                "group_id": {"type": "string", "description": "Memory group to link the item into"}
+                            },
+                            "required": ["data"]
+                        }
+                    },
+                    "atomic": {"type": "boolean", "description": "Store nothing if any item fails (default false)"}
+                },
+                "required": ["items"]
+            }
+        }),
+        
+        json!({
+            "name": "mem8.retrieve_memory",
+            "description": "Retrieve a memory; text reports its detected encoding, binary comes back as data_hex",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "signature": {"type": "string", "description": "Hex signature of the memory"},
+                    "encoding": {"type": "string", "description": "auto (default, follows the BOM), utf-8, utf-16le, utf-16be or latin-1"}
+                },
+                "required": ["signature"]
+            }
+        }),
+        
+        json!({
+            "name": "mem8.get_memory_group",
+            "description": "List every perspective stored for a memory group, oldest first",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "memory_group": {"type": "string", "description": "Group id given to store_memory"}
+                },
+                "required": ["memory_group"]
+            }
+        }),
+        
+        json!({
+            "name": "mem8.list_memories",
+            "description": "List memories stored in a time window (since/until, or a whole UTC day with days_ago: 1 for yesterday), optionally where metadata at pointer equals a value",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "since": {"type": "integer", "description": "Start of the window, unix seconds (inclusive)"},
+                    "until": {"type": "integer", "description": "End of the window, unix seconds (exclusive; default now)"},
+                    "days_ago": {"type": "integer", "description": "Whole UTC day to list instead: 0 today, 1 yesterday"},
+                    "pointer": {"type": "string", "description": "JSON pointer into metadata, e.g. /perspective"},
+                    "equals": {"description": "Value the metadata must have at pointer"},
+                    "limit": {"type": "integer", "description": "Most memories to return (default 100)"}
+                }
+            }
+        }),
+        
+        json!({
+            "name": "mem8.top_wonder",
+            "description": "The most wondrous memories stored: the k with the highest Marine salience, most salient first",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "k": {"type": "integer", "description": "How many memories to return (default 10)"}
+                }
+            }
+        }),
+        
+        json!({
+            "name": "mem8.delete_memory",
+            "description": "Delete a memory; secure: true zeroes its bytes on disk immediately",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "signature": {"type": "string", "description": "Hex signature of the memory"},
+                    "secure": {"type": "boolean", "description": "Overwrite the stored bytes now (for Private/Secret memories)"}
+                },
+                "required": ["signature"]
+            }
+        }),
+        
         json!({
             "name": "mem8.analyze_audio",
-            "description": "Analyze audio file for mood and salience",
+            "description": "Analyze audio file for mood, salience and its sections (quiet, building, peak, falling)",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "file_path": {"type": "string", "description": "Path to audio file (FLAC, WAV, PCM, or the audio of an MP4/MKV)"},
+                    "track": {"type": "integer", "description": "Audio track id from probe_audio (default: first decodable)"},
+                    "timeline_window_ms": {"type": "integer", "description": "Also return salience per window of this many milliseconds (the segments come from these windows too)"}
+                },
+                "required": ["file_path"]
+            }
+        }),
+        
+        json!({
+            "name": "mem8.probe_audio",
+            "description": "List a file's audio tracks (codec, language, duration) without decoding them",
             "parameters": {
                 "type": "object",
                 "properties": {
-                    "file_path": {"type": "string", "description": "Path to audio file"}
+                    "file_path": {"type": "string", "description": "Path to audio or video file"}
                 },
                 "required": ["file_path"]
             }
         }),
         
+        json!({
+            "name": "mem8.highlights",
+            "description": "Extract beat-aligned clips around a stored track's top wonder moments",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "signature": {"type": "string", "description": "Hex signature of a track stored with store_audio"},
+                    "count": {"type": "integer", "description": "Number of clips (default 3)"},
+                    "clip_seconds": {"type": "number", "description": "Clip length in seconds (default 10)"},
+                    "store": {"type": "boolean", "description": "Store clips as packets linked via highlight_of"}
+                },
+                "required": ["signature"]
+            }
+        }),
+        
+        json!({
+            "name": "mem8.log_listening",
+            "description": "Journal something you listened to and how well it worked",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "artist": {"type": "string", "description": "Artist name"},
+                    "title": {"type": "string", "description": "Track title"},
+                    "genre": {"type": "string", "description": "Genre (e.g. Electronic, Ambient)"},
+                    "minutes": {"type": "number", "description": "Minutes listened"},
+                    "effectiveness": {"type": "number", "description": "How well it worked, 0.0 to 1.0"},
+                    "activity": activity_schema("Activity (defaults to the current one)")
+                },
+                "required": ["artist", "effectiveness"]
+            }
+        }),
+        
+        json!({
+            "name": "mem8.activity_report",
+            "description": "Per-activity listening report with effectiveness trends",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "days": {"type": "integer", "description": "Days to cover (default 14; trends compare the two halves)"}
+                }
+            }
+        }),
+        
         json!({
             "name": "mem8.dj_suggest",
-            "description": "Get AI DJ music suggestions based on current context",
+            "description": "Get AI DJ music suggestions based on current context (cached while the context holds)",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "refresh": {"type": "boolean", "description": "Skip the suggestion cache and pick afresh"}
+                }
+            }
+        }),
+        
+        json!({
+            "name": "mem8.dj_feedback",
+            "description": "Skip or like a track; a skip keeps it out of suggestions for a while",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "artist": {"type": "string"},
+                    "title": {"type": "string"},
+                    "action": {"type": "string", "enum": ["skip", "like"]}
+                },
+                "required": ["artist", "title", "action"]
+            }
+        }),
+        
+        json!({
+            "name": "mem8.set_content_filter",
+            "description": "Change what the DJ must never suggest, queue or play (saved to /config/dj.json)",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "banned_genres": {"type": "array", "items": {"type": "string"}, "description": "Genres (e.g. Polka)"},
+                    "banned_artists": {"type": "array", "items": {"type": "string"}, "description": "Artist names, case-insensitive"},
+                    "banned_track_ids": {"type": "array", "items": {"type": "string"}, "description": "Streaming track ids"},
+                    "max_duration": {"type": ["integer", "null"], "description": "Longest track in seconds (null: no limit)"},
+                    "explicit_allowed": {"type": "boolean", "description": "Allow explicit tracks"}
+                }
+            }
+        }),
+        
+        json!({
+            "name": "mem8.reload_config",
+            "description": "Reload the profile, DJ and tool policy configs from /config/*.json now",
             "parameters": {
                 "type": "object",
                 "properties": {}
             }
         }),
         
+        json!({
+            "name": "mem8.set_activity",
+            "description": "Tell the DJ what you're doing now",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "activity": activity_schema("What you're doing")
+                },
+                "required": ["activity"]
+            }
+        }),
+        
         json!({
             "name": "mem8.get_mood_state",
             "description": "Get current mood and activity state",
@@ -603,12 +2074,28 @@ pub fn get_mcp_tools() -> Vec<Value> {
             }
         }),
         
+        json!({
+            "name": "mem8.state_at",
+            "description": "What every sensor read at a past instant, from stored readings; stale lists sensors with no reading within staleness_secs",
+            "parameters": {
+                "type": "object",
+                "properties": {
+                    "timestamp": {"type": "integer", "description": "The instant, unix seconds"},
+                    "sensors": {"type": "array", "items": {"type": "string"}, "description": "Sensor ids (default: every sensor seen so far)"},
+                    "staleness_secs": {"type": "integer", "description": "Oldest a reading may be and still count (default 300)"}
+                },
+                "required": ["timestamp"]
+            }
+        }),
+        
         json!({
             "name": "mem8.wave_context",
-            "description": "Get wave-based context for LLM understanding",
+            "description": "Get wave-based context for LLM understanding: a narrative (context) and the same facts as fields (structured)",
             "parameters": {
                 "type": "object",
-                "properties": {}
+                "properties": {
+                    "detail": {"type": "string", "enum": ["brief", "standard", "full"], "description": "How much history to include: none, the last 5 patterns, or everything buffered (default standard)"}
+                }
             }
         }),
     ]
@@ -630,4 +2117,413 @@ impl std::fmt::Display for DjPersonality {
                 write!(f, "🎵 Hue Mode - Personalized for the dancing monkey! 🐒"),
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn favourites() -> Vec<TrackSuggestion> {
+        vec![
+            catalog_suggestion(&("Orbital", "The Box (Part 2)", Genre::Electronic), false),
+            catalog_suggestion(&("Daft Punk", "Digital Love", Genre::Electronic), false),
+        ]
+    }
+    
+    fn allowed() -> Vec<Genre> {
+        MoodEngine::create_hue_profile().profile().allowed_genres()
+    }
+    
+    fn call(server: &Mem8McpServer, tool: &str, args: Value) -> Result<Value> {
+        server.call_tool(tool, args)
+    }
+    
+    #[test]
+    fn test_diversity_constraints_over_50_rounds() {
+        let mut dj = DjMode::new(DjPersonality::HueMode);
+        let (favourites, allowed) = (favourites(), allowed());
+        
+        let picks: Vec<DjPick> = (0..50)
+            .flat_map(|_| dj.pick_suggestions(&favourites, &allowed))
+            .collect();
+        assert!(picks.len() >= 90, "only {} picks", picks.len());
+        
+        // No artist more than max_same_artist_per_session times
+        let mut per_artist: HashMap<&str, usize> = HashMap::new();
+        for pick in &picks {
+            *per_artist.entry(&pick.suggestion.artist).or_insert(0) += 1;
+            assert!(allowed.contains(&pick.suggestion.genre));
+        }
+        assert!(per_artist.values().all(|&n| n <= dj.max_same_artist_per_session));
+        
+        // Repeats are at least min_gap_before_repeat tracks apart
+        let keys: Vec<String> = picks.iter().map(|p| track_key(&p.suggestion)).collect();
+        for (i, key) in keys.iter().enumerate() {
+            if let Some(j) = keys[i + 1..].iter().position(|k| k == key) {
+                assert!(j >= dj.min_gap_before_repeat, "{} repeated after {} tracks", key, j);
+            }
+        }
+        
+        // Exploration picks show up at roughly the configured rate
+        let explored = picks.iter().filter(|p| p.exploration).count() as f64 / picks.len() as f64;
+        assert!((explored - dj.exploration_rate).abs() < 0.05, "exploration rate {}", explored);
+    }
+    
+    #[test]
+    fn test_memory_survives_restart() {
+        let mut dj = DjMode::new(DjPersonality::HueMode);
+        dj.exploration_rate = 1.0;
+        let (favourites, allowed) = (favourites(), allowed());
+        let first = dj.pick_suggestions(&favourites, &allowed);
+        
+        let json = serde_json::to_vec(&dj.memory()).unwrap();
+        let mut restarted = DjMode::new(DjPersonality::HueMode);
+        restarted.exploration_rate = 1.0;
+        restarted.restore(serde_json::from_slice(&json).unwrap());
+        
+        // Already-explored picks aren't "new" anymore after the restart
+        let second = restarted.pick_suggestions(&favourites, &allowed);
+        for pick in &second {
+            assert!(first.iter().all(|p| p.suggestion.artist != pick.suggestion.artist));
+        }
+        assert_eq!(restarted.played_artists.len(), first.len() + second.len());
+    }
+    
+    #[test]
+    fn test_banned_artist_never_suggested_or_explored() {
+        let mut dj = DjMode::new(DjPersonality::HueMode);
+        dj.exploration_rate = 0.5;
+        // Orbital is a favourite; Aphex Twin would be the first exploration pick
+        dj.content_guard.filter.banned_artists = vec!["orbital".to_string(), "Aphex Twin".to_string()];
+        let (favourites, allowed) = (favourites(), allowed());
+        
+        let picks: Vec<DjPick> = (0..50)
+            .flat_map(|_| dj.pick_suggestions(&favourites, &allowed))
+            .collect();
+        assert!(picks.iter().any(|p| p.exploration));
+        assert!(picks.iter().all(|p| p.suggestion.artist != "Orbital" && p.suggestion.artist != "Aphex Twin"));
+        assert!(dj.content_guard.stats.blocked >= 100);
+        
+        // Set through MCP, the filter is saved to the DJ config and applies to dj_suggest
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.m8");
+        let server = Mem8McpServer::new(path.to_str().unwrap()).unwrap();
+        let set = call(&server, "mem8.set_content_filter", json!({"banned_artists": ["Orbital"]})).unwrap();
+        assert_eq!(set["content_filter"]["banned_genres"], json!(["Polka"]));
+        assert_eq!(set["events"][0]["accepted"], true);
+        assert!(call(&server, "mem8.set_content_filter", json!({"max_duration": 0})).is_err());
+        assert!(call(&server, "mem8.set_content_filter", json!({"banned_moods": []})).is_err());
+        let saved: DjConfig = serde_json::from_slice(&server.config_store().read(DJ_PATH).unwrap()).unwrap();
+        assert_eq!(saved.content_filter.banned_artists, vec!["Orbital"]);
+        
+        for _ in 0..10 {
+            let result = call(&server, "mem8.dj_suggest", json!({})).unwrap();
+            for s in result["suggestions"].as_array().unwrap() {
+                assert_ne!(s["artist"], "Orbital");
+            }
+        }
+        let result = call(&server, "mem8.dj_suggest", json!({})).unwrap();
+        assert!(result["filter_violations"]["by_rule"]["artist"].as_u64().unwrap() > 0);
+    }
+    
+    #[test]
+    fn test_dj_suggest_caches_until_feedback() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.m8");
+        let server = Mem8McpServer::new(path.to_str().unwrap()).unwrap();
+        
+        let first = call(&server, "mem8.dj_suggest", json!({})).unwrap();
+        assert_eq!(first["cached"], false);
+        let again = call(&server, "mem8.dj_suggest", json!({})).unwrap();
+        assert_eq!(again["cached"], true);
+        assert_eq!(again["suggestions"], first["suggestions"]);
+        assert_eq!(again["suggestion_cache"]["hits"], 1);
+        
+        // refresh picks afresh
+        let fresh = call(&server, "mem8.dj_suggest", json!({"refresh": true})).unwrap();
+        assert_eq!(fresh["cached"], false);
+        
+        // Skipping a suggested track throws the cached set away
+        let skipped = &fresh["suggestions"][0];
+        let feedback = call(&server, "mem8.dj_feedback", json!({
+            "artist": skipped["artist"], "title": skipped["title"], "action": "skip",
+        })).unwrap();
+        assert_eq!(feedback["was_suggested"], true);
+        let after = call(&server, "mem8.dj_suggest", json!({})).unwrap();
+        assert_eq!(after["cached"], false);
+        assert!(after["suggestions"].as_array().unwrap().iter().all(|s| s["title"] != skipped["title"]));
+        assert_eq!(after["suggestion_cache"]["invalidations"], 2);
+        assert!(call(&server, "mem8.dj_feedback", json!({"artist": "a", "title": "b", "action": "meh"})).is_err());
+        
+        // A mood change is a different context
+        server.sensor_buffer.lock().unwrap().mood_readings.push(MoodReading {
+            timestamp: 1, mood_state: "annoyed".to_string(), confidence: 0.9,
+        });
+        assert_eq!(call(&server, "mem8.dj_suggest", json!({})).unwrap()["cached"], false);
+        
+        // A zero TTL turns the cache off
+        server.config_store().write(DJ_PATH, br#"{"suggestion_cache_secs": 0}"#).unwrap();
+        call(&server, "mem8.dj_suggest", json!({})).unwrap();
+        assert_eq!(call(&server, "mem8.dj_suggest", json!({})).unwrap()["cached"], false);
+    }
+    
+    #[test]
+    fn test_profile_edit_reaches_dj_suggest_without_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.m8");
+        let server = Mem8McpServer::new(path.to_str().unwrap()).unwrap();
+        
+        let genres = |result: &Value| -> Vec<String> {
+            result["suggestions"].as_array().unwrap().iter()
+                .map(|s| s["genre"].as_str().unwrap().to_string())
+                .collect()
+        };
+        let before = call(&server, "mem8.dj_suggest", json!({})).unwrap();
+        assert_eq!(before["profile"], "Hue");
+        assert!(genres(&before).iter().any(|g| g == "Electronic"));
+        
+        // A new profile that can't stand electronic music, written through the fs API
+        let mut profile = MoodEngine::create_hue_profile().profile().clone();
+        profile.name = "Trisha".to_string();
+        profile.avoid_genres.push(Genre::Electronic);
+        server.config_store().write(PROFILE_PATH, &serde_json::to_vec(&profile).unwrap()).unwrap();
+        
+        let after = call(&server, "mem8.dj_suggest", json!({})).unwrap();
+        assert_eq!(after["profile"], "Trisha");
+        assert!(!genres(&after).is_empty());
+        assert!(genres(&after).iter().all(|g| g != "Electronic"), "{:?}", genres(&after));
+        
+        // A broken edit is rejected and logged; the last good profile stays live
+        server.config_store().write(PROFILE_PATH, b"{ not json").unwrap();
+        let reload = call(&server, "mem8.reload_config", json!({})).unwrap();
+        assert_eq!(reload["profile"], "Trisha");
+        assert_eq!(reload["events"][0]["accepted"], false);
+        assert_eq!(reload["events"][0]["path"], PROFILE_PATH);
+        
+        // The tool policy reloads the same way
+        server.config_store().write(POLICY_PATH, br#"{"disabled_tools": ["mem8.dj_suggest"]}"#).unwrap();
+        let err = call(&server, "mem8.dj_suggest", json!({})).unwrap_err();
+        assert!(err.to_string().contains("disabled"));
+    }
+    
+    #[test]
+    fn test_set_activity_accepts_custom_activities() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = Mem8McpServer::new(dir.path().join("store.m8").to_str().unwrap()).unwrap();
+        
+        let result = call(&server, "mem8.set_activity", json!({"activity": "DeepThinking"})).unwrap();
+        assert_eq!(result["activity_set"], "deep_thinking");
+        assert_eq!(result["preferred_genres"], json!(["Ambient", "Classical"]));
+        let result = call(&server, "mem8.set_activity", json!({"activity": "Gaming"})).unwrap();
+        assert_eq!((result["activity_set"].as_str(), result["previous"].as_str()), (Some("gaming"), Some("deep_thinking")));
+        assert_eq!(result["custom"], true);
+        assert_eq!(result["tempo_bpm"], json!([100, 130]));
+        assert!(call(&server, "mem8.set_activity", json!({"activity": "rock & roll"})).is_err());
+        
+        // The journal records it like any other activity
+        call(&server, "mem8.log_listening", json!({"artist": "Orbital", "effectiveness": 0.8})).unwrap();
+        let state = call(&server, "mem8.get_mood_state", json!({})).unwrap();
+        assert_eq!(state["current_activity"], "gaming");
+        
+        let tools = get_mcp_tools();
+        let tool = tools.iter().find(|t| t["name"] == "mem8.set_activity").unwrap();
+        let known = &tool["parameters"]["properties"]["activity"]["anyOf"][0]["enum"];
+        assert_eq!(known.as_array().unwrap().len(), KNOWN_ACTIVITIES.len());
+    }
+    
+    #[test]
+    fn test_store_memories_reports_each_item() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.m8");
+        let server = Mem8McpServer::new(path.to_str().unwrap()).unwrap();
+        
+        let items = json!([
+            {"data": "saw the sunrise", "perspective": "diary", "group_id": "dawn"},
+            {"perspective": "witness"},
+            {"data": "sky went orange", "perspective": "witness", "group_id": "dawn", "metadata": {"mood": "calm"}},
+            {"data": "bad meta", "metadata": "not an object"},
+            {"data": "empty group", "group_id": ""},
+        ]);
+        let result = call(&server, "mem8.store_memories", json!({"items": items})).unwrap();
+        assert_eq!((result["stored"].as_u64(), result["failed"].as_u64()), (Some(2), Some(3)));
+        let results = result["results"].as_array().unwrap();
+        let codes: Vec<&str> = results.iter().map(|r| r["error"]["code"].as_str().unwrap_or("ok")).collect();
+        assert_eq!(codes, ["ok", "missing_data", "ok", "invalid_metadata", "invalid_group"]);
+        
+        let group = call(&server, "mem8.get_memory_group", json!({"memory_group": "dawn"})).unwrap();
+        let perspectives: Vec<&str> = group["members"].as_array().unwrap().iter()
+            .map(|m| m["perspective"].as_str().unwrap())
+            .collect();
+        assert_eq!(perspectives, ["diary", "witness"]);
+        let witness = call(&server, "mem8.retrieve_memory", json!({"signature": results[2]["signature"]})).unwrap();
+        assert_eq!(witness["metadata"]["mood"], "calm");
+        
+        // Atomic: one bad item and nothing is written
+        let before = server.storage().read().iter().count();
+        let result = call(&server, "mem8.store_memories", json!({"atomic": true, "items": items})).unwrap();
+        assert_eq!(result["stored"], 0);
+        assert_eq!(result["results"][0]["error"]["code"], "rolled_back");
+        assert_eq!(server.storage().read().iter().count(), before);
+        
+        let good = json!([{"data": "one"}, {"data": "two"}]);
+        let result = call(&server, "mem8.store_memories", json!({"atomic": true, "items": good})).unwrap();
+        assert_eq!(result["stored"], 2);
+        assert_eq!(server.storage().read().iter().count(), before + 2);
+        
+        // Limits apply to the batch as a whole
+        let big = "x".repeat(MAX_BATCH_BYTES / 2);
+        let err = call(&server, "mem8.store_memories", json!({"items": [{"data": big}, {"data": big}]})).unwrap_err();
+        assert!(err.to_string().contains("limit"), "{}", err);
+    }
+    
+    #[test]
+    fn test_store_memory_reports_metadata_limit() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.m8");
+        let server = Mem8McpServer::new(path.to_str().unwrap()).unwrap();
+        server.storage().write().set_metadata_policy(crate::MetadataPolicy::new().with_max_metadata_bytes(128));
+        
+        let note = "x".repeat(200);
+        let err = call(&server, "mem8.store_memory", json!({"data": "hi", "metadata": {"note": note}})).unwrap_err();
+        assert!(err.to_string().contains("the limit is 128"), "{}", err);
+        
+        let result = call(&server, "mem8.store_memories", json!({"items": [{"data": "hi", "metadata": {"note": note}}]})).unwrap();
+        assert_eq!(result["results"][0]["error"]["code"], "metadata_rejected");
+        assert!(call(&server, "mem8.store_memory", json!({"data": "hi"})).is_ok());
+    }
+    
+    #[test]
+    fn test_list_memories_by_day_and_metadata() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("store.m8");
+        let server = Mem8McpServer::new(path.to_str().unwrap()).unwrap();
+        
+        for (data, perspective) in [("tea", "diary"), ("rain", "witness"), ("toast", "diary")] {
+            call(&server, "mem8.store_memory", json!({"data": data, "perspective": perspective})).unwrap();
+        }
+        
+        let all = call(&server, "mem8.list_memories", json!({"since": 0})).unwrap();
+        assert_eq!(all["total"], 3);
+        assert!(all["memories"][0]["timestamp"].as_u64().is_some());
+        
+        let diary = call(&server, "mem8.list_memories", json!({"pointer": "/perspective", "equals": "diary", "limit": 1})).unwrap();
+        assert_eq!(diary["total"], 2);
+        assert_eq!(diary["memories"].as_array().unwrap().len(), 1);
+        assert_eq!(diary["memories"][0]["metadata"]["perspective"], "diary");
+        
+        let yesterday = call(&server, "mem8.list_memories", json!({"days_ago": 1})).unwrap();
+        assert_eq!(yesterday["total"], 0);
+        assert_eq!(yesterday["until"].as_u64().unwrap() - yesterday["since"].as_u64().unwrap(), 86_400);
+    }
+    
+    /// A server with a known buffer: 6 patterns (every other one with wonder), tired but focused
+    fn context_server(dir: &std::path::Path) -> Mem8McpServer {
+        let server = Mem8McpServer::new(dir.join("store.m8").to_str().unwrap()).unwrap();
+        *server.current_activity.lock().unwrap() = Activity::Programming;
+        let mut buffer = server.sensor_buffer.lock().unwrap();
+        buffer.fatigue_level = 0.75;
+        buffer.focus_score = 0.5;
+        buffer.wave_patterns = (0..6).map(|i| WavePattern {
+            timestamp: 100 + i,
+            pattern_type: if i % 2 == 0 { "typing" } else { "pause" }.to_string(),
+            salience: if i % 2 == 0 { 0.5 } else { 0.25 },
+            wonder_detected: i % 2 == 1,
+        }).collect();
+        buffer.mood_readings = vec![MoodReading { timestamp: 104, mood_state: "calm".to_string(), confidence: 0.8 }];
+        buffer.activity_log = vec![ActivityTransition {
+            timestamp: 90,
+            from: "relaxing".to_string(),
+            to: "programming".to_string(),
+            trigger: "user".to_string(),
+        }];
+        drop(buffer);
+        server
+    }
+    
+    #[test]
+    fn test_wave_context_brief_golden() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = context_server(dir.path());
+        assert_eq!(call(&server, "mem8.wave_context", json!({"detail": "brief"})).unwrap(), json!({
+            "detail": "brief",
+            "context": "User is currently programming (needs flow state). Fatigue: 75%, Focus: 50%. \
+                Recent wave patterns show 6 peaks with 50% showing wonder. Mood trajectory: evolving. \
+                Recommended action: suggest break or ambient music",
+            "structured": {
+                "activity": "programming",
+                "fatigue": 0.75,
+                "focus": 0.5,
+                "wave_count": 6,
+                "wonder_ratio": 0.5,
+                "mood_trajectory": "evolving",
+                "recent_patterns": [],
+                "recommendation": {"action": "suggest break or ambient music", "reason": "high_fatigue"}
+            }
+        }));
+        assert!(call(&server, "mem8.wave_context", json!({"detail": "everything"})).is_err());
+    }
+    
+    #[test]
+    fn test_wave_context_standard_golden() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = context_server(dir.path());
+        let expected = json!({
+            "detail": "standard",
+            "context": "User is currently programming (needs flow state). Fatigue: 75%, Focus: 50%. \
+                Recent wave patterns show 6 peaks with 50% showing wonder. Mood trajectory: evolving. \
+                Latest patterns: pause (25%, wonder), typing (50%), pause (25%, wonder), typing (50%), pause (25%, wonder). \
+                Recommended action: suggest break or ambient music",
+            "structured": {
+                "activity": "programming",
+                "fatigue": 0.75,
+                "focus": 0.5,
+                "wave_count": 6,
+                "wonder_ratio": 0.5,
+                "mood_trajectory": "evolving",
+                "recent_patterns": [
+                    {"pattern_type": "pause", "confidence": 0.25, "wonder": true, "timestamp": 105},
+                    {"pattern_type": "typing", "confidence": 0.5, "wonder": false, "timestamp": 104},
+                    {"pattern_type": "pause", "confidence": 0.25, "wonder": true, "timestamp": 103},
+                    {"pattern_type": "typing", "confidence": 0.5, "wonder": false, "timestamp": 102},
+                    {"pattern_type": "pause", "confidence": 0.25, "wonder": true, "timestamp": 101}
+                ],
+                "recommendation": {"action": "suggest break or ambient music", "reason": "high_fatigue"}
+            }
+        });
+        assert_eq!(call(&server, "mem8.wave_context", json!({"detail": "standard"})).unwrap(), expected);
+        assert_eq!(call(&server, "mem8.wave_context", json!({})).unwrap(), expected);
+    }
+    
+    #[test]
+    fn test_wave_context_full_golden() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = context_server(dir.path());
+        assert_eq!(call(&server, "mem8.wave_context", json!({"detail": "full"})).unwrap(), json!({
+            "detail": "full",
+            "context": "User is currently programming (needs flow state). Fatigue: 75%, Focus: 50%. \
+                Recent wave patterns show 6 peaks with 50% showing wonder. Mood trajectory: evolving. \
+                Latest patterns: pause (25%, wonder), typing (50%), pause (25%, wonder), typing (50%), pause (25%, wonder), typing (50%). \
+                Last mood: calm (80% confident). Last activity change: relaxing to programming (user). \
+                Recommended action: suggest break or ambient music",
+            "structured": {
+                "activity": "programming",
+                "fatigue": 0.75,
+                "focus": 0.5,
+                "wave_count": 6,
+                "wonder_ratio": 0.5,
+                "mood_trajectory": "evolving",
+                "recent_patterns": [
+                    {"pattern_type": "pause", "confidence": 0.25, "wonder": true, "timestamp": 105},
+                    {"pattern_type": "typing", "confidence": 0.5, "wonder": false, "timestamp": 104},
+                    {"pattern_type": "pause", "confidence": 0.25, "wonder": true, "timestamp": 103},
+                    {"pattern_type": "typing", "confidence": 0.5, "wonder": false, "timestamp": 102},
+                    {"pattern_type": "pause", "confidence": 0.25, "wonder": true, "timestamp": 101},
+                    {"pattern_type": "typing", "confidence": 0.5, "wonder": false, "timestamp": 100}
+                ],
+                "recommendation": {"action": "suggest break or ambient music", "reason": "high_fatigue"},
+                "mood_readings": [{"timestamp": 104, "mood_state": "calm", "confidence": 0.8}],
+                "activity_log": [{"timestamp": 90, "from": "relaxing", "to": "programming", "trigger": "user"}]
+            }
+        }));
+    }
+}
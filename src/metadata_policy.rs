@@ -0,0 +1,146 @@
+//! Metadata policy - what a Mem8Lite store accepts as metadata
+//!
+//! By default anything goes: any size, any bytes. A `MetadataPolicy` caps the
+//! size, can insist on valid JSON, and can run validators registered per
+//! metadata "kind" (the `kind` field of a JSON object). `Mem8Lite` checks it
+//! on every write that carries metadata and counts what it turned away (see
+//! `StorageStats::metadata_rejections`).
+//!
+//! Hue once attached a whole album to a sticky note. Never again. 📏
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+
+/// A check on JSON metadata of one kind: `Err` says what's wrong with it
+pub type MetadataValidator = Arc<dyn Fn(&Value) -> Result<(), String> + Send + Sync>;
+
+/// Limits and checks on metadata (see `Mem8Lite::with_metadata_policy`)
+#[derive(Clone, Default)]
+pub struct MetadataPolicy {
+    /// Biggest metadata accepted, in bytes (`None`: no limit)
+    pub max_metadata_bytes: Option<usize>,
+    
+    /// Reject metadata that doesn't parse as JSON
+    pub require_valid_json: bool,
+    
+    /// Validators by the `kind` they check
+    validators: HashMap<String, MetadataValidator>,
+}
+
+impl MetadataPolicy {
+    /// Accept everything (the default)
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn with_max_metadata_bytes(mut self, limit: usize) -> Self {
+        self.max_metadata_bytes = Some(limit);
+        self
+    }
+    
+    pub fn with_require_valid_json(mut self, require: bool) -> Self {
+        self.require_valid_json = require;
+        self
+    }
+    
+    /// Run `validator` on JSON object metadata whose `kind` field is `kind`
+    ///
+    /// Metadata that isn't JSON (allowed unless `require_valid_json`), or has
+    /// no `kind`, or a kind without a validator, isn't checked. A second
+    /// validator for the same kind replaces the first.
+    pub fn with_validator<F>(mut self, kind: &str, validator: F) -> Self
+    where F: Fn(&Value) -> Result<(), String> + Send + Sync + 'static {
+        self.validators.insert(kind.to_string(), Arc::new(validator));
+        self
+    }
+    
+    /// Kinds with a validator, sorted
+    pub fn validated_kinds(&self) -> Vec<&str> {
+        let mut kinds: Vec<&str> = self.validators.keys().map(String::as_str).collect();
+        kinds.sort_unstable();
+        kinds
+    }
+    
+    /// Would this policy accept `metadata`?
+    pub fn check(&self, metadata: &[u8]) -> Result<(), MetadataRejected> {
+        if let Some(limit) = self.max_metadata_bytes {
+            if metadata.len() > limit {
+                return Err(MetadataRejected::TooLarge { len: metadata.len(), limit });
+            }
+        }
+        if !self.require_valid_json && self.validators.is_empty() {
+            return Ok(());
+        }
+        
+        let json = match serde_json::from_slice::<Value>(metadata) {
+            Ok(json) => json,
+            Err(e) if self.require_valid_json => return Err(MetadataRejected::InvalidJson { reason: e.to_string() }),
+            Err(_) => return Ok(()),
+        };
+        let kind = json.get("kind").and_then(Value::as_str);
+        match kind.and_then(|kind| self.validators.get(kind).map(|v| (kind, v))) {
+            Some((kind, validator)) => validator(&json)
+                .map_err(|reason| MetadataRejected::Invalid { kind: kind.to_string(), reason }),
+            None => Ok(()),
+        }
+    }
+}
+
+impl std::fmt::Debug for MetadataPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MetadataPolicy")
+            .field("max_metadata_bytes", &self.max_metadata_bytes)
+            .field("require_valid_json", &self.require_valid_json)
+            .field("validators", &self.validated_kinds())
+            .finish()
+    }
+}
+
+/// Metadata a store's `MetadataPolicy` turned away
+///
+/// Comes back inside `Mem8Error`; use `err.downcast_ref::<MetadataRejected>()`.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum MetadataRejected {
+    /// Bigger than `max_metadata_bytes`
+    #[error("metadata is {len} bytes; the limit is {limit}")]
+    TooLarge { len: usize, limit: usize },
+    
+    /// Not JSON, under `require_valid_json`
+    #[error("metadata isn't valid JSON: {reason}")]
+    InvalidJson { reason: String },
+    
+    /// The validator registered for its kind said no
+    #[error("{kind} metadata failed validation: {reason}")]
+    Invalid { kind: String, reason: String },
+}
+
+/// Writes turned away by the metadata policy since the store was opened
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataRejections {
+    pub too_large: u64,
+    pub invalid_json: u64,
+    pub failed_validation: u64,
+}
+
+impl MetadataRejections {
+    pub fn total(&self) -> u64 {
+        self.too_large + self.invalid_json + self.failed_validation
+    }
+    
+    pub(crate) fn count(&mut self, rejection: &MetadataRejected) {
+        match rejection {
+            MetadataRejected::TooLarge { .. } => self.too_large += 1,
+            MetadataRejected::InvalidJson { .. } => self.invalid_json += 1,
+            MetadataRejected::Invalid { .. } => self.failed_validation += 1,
+        }
+    }
+}
+
+impl std::fmt::Display for MetadataRejections {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} too large, {} invalid JSON, {} failed validation",
+            self.too_large, self.invalid_json, self.failed_validation)
+    }
+}
@@ -14,9 +14,11 @@
 
 use crate::marine::{MarineProcessor, MarineMetadata};
 use crate::audio::{AudioFormat, SampleRate};
+use crate::activity_report::{ActivityReport, ReportRange};
 use std::collections::HashMap;
+use std::path::Path;
 use serde::{Serialize, Deserialize};
-use anyhow::Result;
+use anyhow::{Result, anyhow};
 
 /// Musical mood states and their effects
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -69,6 +71,39 @@ pub struct MusicProfile {
     pub special_tracks: Vec<SpecialTrack>,
 }
 
+impl MusicProfile {
+    /// Genres this profile is happy to hear (preferred + activity picks, minus avoided)
+    pub fn allowed_genres(&self) -> Vec<Genre> {
+        let mut allowed: Vec<Genre> = self.preferred_genres.clone();
+        for genres in self.activity_preferences.values() {
+            for genre in genres {
+                if !allowed.contains(genre) {
+                    allowed.push(genre.clone());
+                }
+            }
+        }
+        allowed.retain(|g| !self.avoid_genres.contains(g));
+        allowed
+    }
+    
+    /// BPM range to aim for during an activity
+    /// 
+    /// `activity_bpm` wins; otherwise the winding-down activities get the
+    /// relaxation range, the letting-off-steam ones the decompression range,
+    /// and everything else (custom activities too) the focus range.
+    pub fn tempo_range(&self, activity: &Activity) -> (u32, u32) {
+        let tempo = &self.tempo_preferences;
+        if let Some(&range) = tempo.activity_bpm.get(activity) {
+            return range;
+        }
+        match activity {
+            Activity::Relaxing | Activity::Sleeping => tempo.relaxation_bpm,
+            Activity::Decompressing | Activity::Exercising => tempo.decompression_bpm,
+            _ => tempo.focus_bpm,
+        }
+    }
+}
+
 /// Musical genres with Hue's annotations
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
 pub enum Genre {
@@ -87,7 +122,11 @@ pub enum Genre {
 }
 
 /// Activity-based music selection
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+/// 
+/// Serialized as a plain string: the variant name for the built-in ones
+/// (`"DeepThinking"`, as it always was) and the name itself for custom ones
+/// (`"gaming"`), so it also works as a map key in profiles.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum Activity {
     Programming,      // Electronic optimal
     Decompressing,    // Hard Rock therapy
@@ -97,6 +136,128 @@ pub enum Activity {
     Exercising,       // High energy
     Commuting,        // Variable
     Sleeping,         // Very specific needs
+    
+    /// Anything else ("gaming", "reading") - lowercase snake_case, made by `Activity::parse`
+    Custom(String),
+}
+
+/// The built-in activities, by the names `Activity::parse` and the MCP tools use
+pub const KNOWN_ACTIVITIES: [&str; 8] = [
+    "programming", "decompressing", "deep_thinking", "creating",
+    "relaxing", "exercising", "commuting", "sleeping",
+];
+
+/// Longest custom activity name
+const MAX_ACTIVITY_NAME: usize = 64;
+
+impl Activity {
+    /// Parse an activity name: `deep_thinking`, `DeepThinking` and
+    /// `Deep thinking` are all the same one, and anything that isn't built in
+    /// becomes a `Custom` activity (`Gaming` → `gaming`)
+    pub fn parse(name: &str) -> Result<Self> {
+        let name = name.trim();
+        if let Some(activity) = Self::from_variant_name(name) {
+            return Ok(activity);
+        }
+        let name = name.to_lowercase().replace([' ', '-'], "_");
+        Ok(match name.as_str() {
+            "programming" => Activity::Programming,
+            "decompressing" => Activity::Decompressing,
+            "deep_thinking" => Activity::DeepThinking,
+            "creating" => Activity::Creating,
+            "relaxing" => Activity::Relaxing,
+            "exercising" => Activity::Exercising,
+            "commuting" => Activity::Commuting,
+            "sleeping" => Activity::Sleeping,
+            _ => {
+                let valid = name.starts_with(|c: char| c.is_ascii_lowercase())
+                    && name.len() <= MAX_ACTIVITY_NAME
+                    && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+                if !valid {
+                    return Err(anyhow!(
+                        "Bad activity name {:?}: use letters, digits and underscores (at most {})",
+                        name, MAX_ACTIVITY_NAME
+                    ));
+                }
+                Activity::Custom(name)
+            }
+        })
+    }
+    
+    /// The snake_case name (`deep_thinking`, or the custom name)
+    pub fn name(&self) -> &str {
+        match self {
+            Activity::Programming => "programming",
+            Activity::Decompressing => "decompressing",
+            Activity::DeepThinking => "deep_thinking",
+            Activity::Creating => "creating",
+            Activity::Relaxing => "relaxing",
+            Activity::Exercising => "exercising",
+            Activity::Commuting => "commuting",
+            Activity::Sleeping => "sleeping",
+            Activity::Custom(name) => name,
+        }
+    }
+    
+    pub fn is_custom(&self) -> bool {
+        matches!(self, Activity::Custom(_))
+    }
+    
+    /// How it's serialized (the pre-`Custom` enum representation for built-in ones)
+    fn variant_name(&self) -> &str {
+        match self {
+            Activity::Programming => "Programming",
+            Activity::Decompressing => "Decompressing",
+            Activity::DeepThinking => "DeepThinking",
+            Activity::Creating => "Creating",
+            Activity::Relaxing => "Relaxing",
+            Activity::Exercising => "Exercising",
+            Activity::Commuting => "Commuting",
+            Activity::Sleeping => "Sleeping",
+            Activity::Custom(name) => name,
+        }
+    }
+    
+    fn from_variant_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "Programming" => Activity::Programming,
+            "Decompressing" => Activity::Decompressing,
+            "DeepThinking" => Activity::DeepThinking,
+            "Creating" => Activity::Creating,
+            "Relaxing" => Activity::Relaxing,
+            "Exercising" => Activity::Exercising,
+            "Commuting" => Activity::Commuting,
+            "Sleeping" => Activity::Sleeping,
+            _ => return None,
+        })
+    }
+}
+
+impl std::fmt::Display for Activity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl std::str::FromStr for Activity {
+    type Err = anyhow::Error;
+    
+    fn from_str(s: &str) -> Result<Self> {
+        Activity::parse(s)
+    }
+}
+
+impl Serialize for Activity {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.variant_name())
+    }
+}
+
+impl<'de> Deserialize<'de> for Activity {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Activity::parse(&name).map_err(serde::de::Error::custom)
+    }
 }
 
 /// Affinity level for specific artists
@@ -124,6 +285,10 @@ pub struct TempoPreference {
     
     /// Sleep/relaxation tempo
     pub relaxation_bpm: (u32, u32),  // Much lower
+    
+    /// Ranges for particular activities (custom ones included), ahead of the three above
+    #[serde(default)]
+    pub activity_bpm: HashMap<Activity, (u32, u32)>,
 }
 
 /// Special tracks that transcend genre
@@ -135,14 +300,44 @@ pub struct SpecialTrack {
     pub mood_effect: MoodState,
 }
 
+/// Spectral centroids below this sound dark (drones, pads), whatever their level
+#[cfg(feature = "spectral")]
+const DARK_CENTROID_HZ: f64 = 500.0;
+
+/// Spectral centroids above this sound bright (distortion, cymbals, noise)
+#[cfg(feature = "spectral")]
+const BRIGHT_CENTROID_HZ: f64 = 2000.0;
+
 /// The Mood Engine - tracks how music affects your state
 pub struct MoodEngine {
     profile: MusicProfile,
     current_state: MoodState,
     history: Vec<MoodTransition>,
+    journal: Vec<ListeningEntry>,
     marine_processor: MarineProcessor,
 }
 
+/// One journal line: something listened to during an activity, and how it went
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ListeningEntry {
+    pub timestamp: u64,
+    pub activity: Activity,
+    pub artist: String,
+    pub title: String,
+    pub genre: Option<Genre>,
+    pub minutes: f64,
+    pub effectiveness: f64,  // 0.0 to 1.0, same scale as transitions
+}
+
+/// Everything the engine remembers between runs
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MoodHistory {
+    #[serde(default)]
+    pub transitions: Vec<MoodTransition>,
+    #[serde(default)]
+    pub journal: Vec<ListeningEntry>,
+}
+
 /// Records mood transitions triggered by music
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MoodTransition {
@@ -202,6 +397,7 @@ impl MoodEngine {
                 fatigue_threshold: 160,      // Too fast for too long
                 decompression_bpm: (120, 180), // Hard rock range
                 relaxation_bpm: (50, 80),    // Ambient zone
+                activity_bpm: HashMap::new(),
             },
             special_tracks: vec![
                 SpecialTrack {
@@ -238,10 +434,21 @@ impl MoodEngine {
                 preferred_bpm: 120,
             },
             history: Vec::new(),
+            journal: Vec::new(),
             marine_processor: processor,
         }
     }
     
+    /// The listener profile driving this engine
+    pub fn profile(&self) -> &MusicProfile {
+        &self.profile
+    }
+    
+    /// Swap in a new listener profile (journal and mood history are kept)
+    pub fn set_profile(&mut self, profile: MusicProfile) {
+        self.profile = profile;
+    }
+    
     /// Analyze how a piece of music will affect mood
     pub fn predict_mood_effect(&mut self, 
                                audio_samples: &[f64], 
@@ -254,9 +461,32 @@ impl MoodEngine {
         let energy_level = metadata.average_salience;
         let has_rhythm = metadata.has_rhythm;
         let wonder_ratio = metadata.wonder_count as f64 / metadata.total_peaks.max(1) as f64;
+        #[cfg(feature = "spectral")]
+        let centroid = metadata.spectral.map(|spectral| spectral.centroid_hz);
         
         // Predict mood effect based on current state and music properties
+        // (with the `spectral` feature, the centroid first; without it, the peaks alone)
         let predicted_state = match (&self.current_state, energy_level, wonder_ratio) {
+            // However loud, a dark spectrum is a drone - peaceful, not energetic
+            #[cfg(feature = "spectral")]
+            (_, _, w) if centroid.is_some_and(|hz| hz < DARK_CENTROID_HZ) => {
+                MoodState::Contemplation {
+                    temporal_expansion: 1.5,
+                    creativity_boost: w.max(0.5),
+                    wonder_threshold: 0.4,
+                }
+            },
+            
+            // Bright and busy, rhythm or not: energy to let out
+            #[cfg(feature = "spectral")]
+            (_, e, _) if e > 0.4 && centroid.is_some_and(|hz| hz > BRIGHT_CENTROID_HZ) => {
+                MoodState::Decompression {
+                    annoyance_reduction: e * 0.8,
+                    energy_release: e,
+                    volume_preference: 0.8,
+                }
+            },
+            
             // High energy + rhythm = good for decompression
             (_, e, _) if e > 0.7 && has_rhythm => {
                 MoodState::Decompression {
@@ -340,6 +570,45 @@ impl MoodEngine {
         self.history.push(transition);
         self.current_state = new_state;
     }
+    
+    /// Add a listen to the journal
+    pub fn record_listening(&mut self, entry: ListeningEntry) {
+        self.journal.push(entry);
+    }
+    
+    /// Everything listened to so far
+    pub fn journal(&self) -> &[ListeningEntry] {
+        &self.journal
+    }
+    
+    /// Per-activity listening report over `range`
+    pub fn activity_report(&self, range: ReportRange) -> ActivityReport {
+        ActivityReport::build(&self.journal, &self.history, range)
+    }
+    
+    /// Save transitions and the journal as JSON
+    pub fn save_history<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let history = MoodHistory {
+            transitions: self.history.clone(),
+            journal: self.journal.clone(),
+        };
+        std::fs::write(path, serde_json::to_vec_pretty(&history)?)?;
+        Ok(())
+    }
+    
+    /// Load history written by `save_history`, replacing what's in memory
+    /// 
+    /// A missing file just means there's no history yet.
+    pub fn load_history<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        let path = path.as_ref();
+        if !path.exists() {
+            return Ok(());
+        }
+        let history: MoodHistory = serde_json::from_slice(&std::fs::read(path)?)?;
+        self.history = history.transitions;
+        self.journal = history.journal;
+        Ok(())
+    }
 }
 
 /// Mood prediction result
@@ -387,4 +656,80 @@ impl std::fmt::Display for MoodState {
             },
         }
     }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_activity_names_and_serde_compatibility() {
+        for name in KNOWN_ACTIVITIES {
+            let activity = Activity::parse(name).unwrap();
+            assert!(!activity.is_custom());
+            assert_eq!(activity.to_string(), name);
+        }
+        assert_eq!(Activity::parse("DeepThinking").unwrap(), Activity::DeepThinking);
+        assert_eq!(Activity::parse(" Deep thinking ").unwrap(), Activity::DeepThinking);
+        assert_eq!("Gaming".parse::<Activity>().unwrap(), Activity::Custom("gaming".to_string()));
+        assert_eq!(Activity::parse("bird-watching").unwrap().name(), "bird_watching");
+        for bad in ["", "42", "rock & roll", &"x".repeat(MAX_ACTIVITY_NAME + 1)] {
+            assert!(Activity::parse(bad).is_err(), "{:?}", bad);
+        }
+        
+        // Journals written before custom activities still load, and come back out the same
+        let old = r#"{"timestamp": 1700000000, "activity": "DeepThinking", "artist": "Brian Eno",
+                      "title": "An Ending", "genre": "Ambient", "minutes": 20.0, "effectiveness": 0.9}"#;
+        let entry: ListeningEntry = serde_json::from_str(old).unwrap();
+        assert_eq!(entry.activity, Activity::DeepThinking);
+        assert_eq!(serde_json::to_value(&entry).unwrap()["activity"], "DeepThinking");
+        let gaming = Activity::parse("gaming").unwrap();
+        assert_eq!(serde_json::to_value(&gaming).unwrap(), "gaming");
+        assert!(serde_json::from_str::<Activity>(r#""not valid!""#).is_err());
+        
+        // Custom activities work as profile keys, for genres and tempo alike
+        let mut profile = MoodEngine::create_hue_profile().profile().clone();
+        profile.activity_preferences.insert(gaming.clone(), vec![Genre::Industrial]);
+        profile.tempo_preferences.activity_bpm.insert(gaming.clone(), (140, 170));
+        let json = serde_json::to_string(&profile).unwrap();
+        let back: MusicProfile = serde_json::from_str(&json).unwrap();
+        assert_eq!(back.activity_preferences[&gaming], vec![Genre::Industrial]);
+        assert_eq!(back.activity_preferences[&Activity::Programming], vec![Genre::Electronic]);
+        assert_eq!(back.tempo_range(&gaming), (140, 170));
+        assert_eq!(back.tempo_range(&Activity::Sleeping), (50, 80));
+        assert_eq!(back.tempo_range(&Activity::parse("reading").unwrap()), (100, 130));
+    }
+    
+    #[cfg(feature = "spectral")]
+    #[test]
+    fn test_centroid_tells_drones_from_noise() {
+        let rate = 44_100.0;
+        let drone: Vec<f64> = (0..(3.0 * rate) as usize)
+            .map(|i| 0.9 * (2.0 * std::f64::consts::PI * 100.0 * i as f64 / rate).sin())
+            .collect();
+        let mut seed: u64 = 0x8b15;
+        let noise: Vec<f64> = (0..drone.len())
+            .map(|_| {
+                seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                1.8 * ((seed >> 33) as f64 / (1u64 << 31) as f64 - 0.5)
+            })
+            .collect();
+        
+        let mut engine = MoodEngine::create_hue_profile();
+        let mut predict = |samples: &[f64], spectral: bool| {
+            let (_, mut metadata) = MarineProcessor::for_audio(rate).analyze(samples);
+            assert!(metadata.spectral.is_some());
+            if !spectral {
+                metadata.spectral = None;
+            }
+            engine.predict_mood_effect(samples, &metadata, None).predicted_state
+        };
+        
+        // By the peaks alone the drone is as energetic as it gets, and the noise just background
+        assert!(matches!(predict(&drone, false), MoodState::Decompression { .. }));
+        assert!(matches!(predict(&noise, false), MoodState::EnergyBalance { .. }));
+        
+        // The spectrum puts them the right way round
+        assert!(matches!(predict(&drone, true), MoodState::Contemplation { .. }));
+        assert!(matches!(predict(&noise, true), MoodState::Decompression { .. }));
+    }
+}
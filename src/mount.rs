@@ -1,24 +1,47 @@
 //! FUSE mount support - Mount MEM8 as a real filesystem!
 //! 
 //! Hue says: "Now you can literally `mount -t mem8` and watch it fly!" 🚀
+//!
+//! Inode numbers are persisted in the store's `fuse_inodes` namespace, so a
+//! path keeps its inode across remounts (NFS re-exports and inode-caching
+//! tools depend on that).
+//!
+//! `new_snapshot` mounts a snapshot instead of the live tree: lookups and
+//! reads come from the snapshot's captured index, every write fails with
+//! EROFS, and files whose content is gone since read as EIO (with a warning
+//! logged) rather than garbage. Snapshots only capture files, so a
+//! snapshot mount is one flat directory; the live tree is mounted
+//! read-write, with its directories.
 
 #[cfg(feature = "fuse-mount")]
 use fuser::{
     FileType, FileAttr, Filesystem, Request, ReplyData, ReplyEntry, 
-    ReplyAttr, ReplyDirectory, ReplyWrite, FUSE_ROOT_ID,
+    ReplyAttr, ReplyDirectory, ReplyWrite, ReplyCreate, ReplyEmpty, FUSE_ROOT_ID,
 };
 use std::time::{Duration, UNIX_EPOCH, SystemTime};
 use std::ffi::OsStr;
 use std::collections::HashMap;
 use std::sync::{Arc, RwLock};
+use serde::{Serialize, Deserialize};
 use crate::Mem8Fs;
-use anyhow::Result;
+use crate::snapshot::{Snapshot, SnapshotDataGone};
+use crate::error::{Mem8Error, Result};
+
+/// Namespace holding the persisted inode table
+const INODE_NAMESPACE: &str = "fuse_inodes";
+
+/// How long a deleted path keeps its inode before the mapping is dropped
+pub const DEFAULT_INODE_RETENTION: Duration = Duration::from_secs(30 * 24 * 60 * 60);
 
 /// FUSE filesystem implementation for MEM8
 pub struct Mem8FuseFs {
     inner: Arc<Mem8Fs>,
     inodes: Arc<RwLock<InodeTable>>,
     ttl: Duration,
+    retention: Duration,
+    
+    /// Serve this snapshot (read-only) instead of the live tree
+    snapshot: Option<Snapshot>,
 }
 
 /// Inode table for FUSE
@@ -26,26 +49,183 @@ struct InodeTable {
     next_inode: u64,
     path_to_inode: HashMap<String, u64>,
     inode_to_path: HashMap<u64, String>,
+    
+    /// When each vanished path was first seen missing (unix seconds)
+    deleted_since: HashMap<u64, u64>,
+    
+    /// Changed since it was last persisted?
+    dirty: bool,
 }
 
-impl Mem8FuseFs {
-    /// Create a new FUSE filesystem backed by MEM8
-    pub fn new(mem8: Arc<Mem8Fs>) -> Self {
-        let mut inodes = InodeTable {
+/// On-disk form of the inode table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PersistedInodes {
+    next_inode: u64,
+    entries: Vec<PersistedInode>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PersistedInode {
+    path: String,
+    inode: u64,
+    deleted_since: Option<u64>,
+}
+
+impl InodeTable {
+    fn empty() -> Self {
+        let mut table = InodeTable {
             next_inode: 2, // 1 is reserved for root
             path_to_inode: HashMap::new(),
             inode_to_path: HashMap::new(),
+            deleted_since: HashMap::new(),
+            dirty: false,
         };
         
         // Add root
-        inodes.path_to_inode.insert("/".to_string(), FUSE_ROOT_ID);
-        inodes.inode_to_path.insert(FUSE_ROOT_ID, "/".to_string());
+        table.path_to_inode.insert("/".to_string(), FUSE_ROOT_ID);
+        table.inode_to_path.insert(FUSE_ROOT_ID, "/".to_string());
+        table
+    }
+    
+    /// Rebuild the table from its persisted form
+    fn from_persisted(persisted: PersistedInodes) -> Self {
+        let mut table = Self::empty();
+        for entry in persisted.entries {
+            if entry.inode == FUSE_ROOT_ID {
+                continue;
+            }
+            table.path_to_inode.insert(entry.path.clone(), entry.inode);
+            table.inode_to_path.insert(entry.inode, entry.path);
+            if let Some(since) = entry.deleted_since {
+                table.deleted_since.insert(entry.inode, since);
+            }
+            // New inodes always go above anything ever handed out
+            table.next_inode = table.next_inode.max(entry.inode + 1);
+        }
+        table.next_inode = table.next_inode.max(persisted.next_inode);
+        table
+    }
+    
+    fn to_persisted(&self) -> PersistedInodes {
+        let mut entries: Vec<PersistedInode> = self.inode_to_path.iter()
+            .filter(|(&inode, _)| inode != FUSE_ROOT_ID)
+            .map(|(&inode, path)| PersistedInode {
+                path: path.clone(),
+                inode,
+                deleted_since: self.deleted_since.get(&inode).copied(),
+            })
+            .collect();
+        entries.sort_by_key(|e| e.inode);
+        PersistedInodes { next_inode: self.next_inode, entries }
+    }
+    
+    /// Note which paths vanished and forget the ones gone longer than `retention`
+    fn collect_garbage(&mut self, fs: &Mem8Fs, retention: Duration, now: u64) {
+        let mut expired = Vec::new();
+        for (&inode, path) in &self.inode_to_path {
+            if inode == FUSE_ROOT_ID {
+                continue;
+            }
+            if fs.exists(path) {
+                if self.deleted_since.remove(&inode).is_some() {
+                    self.dirty = true;
+                }
+                continue;
+            }
+            let since = *self.deleted_since.entry(inode).or_insert_with(|| {
+                self.dirty = true;
+                now
+            });
+            if now.saturating_sub(since) > retention.as_secs() {
+                expired.push(inode);
+            }
+        }
+        
+        for inode in expired {
+            if let Some(path) = self.inode_to_path.remove(&inode) {
+                self.path_to_inode.remove(&path);
+            }
+            self.deleted_since.remove(&inode);
+            self.dirty = true;
+        }
+    }
+}
+
+impl Mem8FuseFs {
+    /// Create a new FUSE filesystem backed by MEM8
+    /// 
+    /// Picks up the inode numbers persisted by earlier mounts of the same store.
+    pub fn new(mem8: Arc<Mem8Fs>) -> Self {
+        let inodes = match mem8.read_namespace(INODE_NAMESPACE) {
+            Ok(Some(bytes)) => match bincode::deserialize::<PersistedInodes>(&bytes) {
+                Ok(persisted) => InodeTable::from_persisted(persisted),
+                Err(e) => {
+                    eprintln!("⚠️ mem8: ignoring unreadable inode table: {}", e);
+                    InodeTable::empty()
+                }
+            },
+            Ok(None) => InodeTable::empty(),
+            Err(e) => {
+                eprintln!("⚠️ mem8: ignoring unreadable inode table: {}", e);
+                InodeTable::empty()
+            }
+        };
         
-        Self {
+        let fs = Self {
             inner: mem8,
             inodes: Arc::new(RwLock::new(inodes)),
             ttl: Duration::from_secs(1),
+            retention: DEFAULT_INODE_RETENTION,
+            snapshot: None,
+        };
+        fs.collect_inode_garbage();
+        fs
+    }
+    
+    /// Mount snapshot `snapshot_id` of `mem8`, read-only
+    /// 
+    /// Inodes are handed out fresh and never persisted, so browsing an old
+    /// snapshot leaves the live mount's inode table alone.
+    pub fn new_snapshot(mem8: Arc<Mem8Fs>, snapshot_id: &str) -> Result<Self> {
+        let snapshot = mem8.snapshot(snapshot_id)?;
+        Ok(Self {
+            inner: mem8,
+            inodes: Arc::new(RwLock::new(InodeTable::empty())),
+            ttl: Duration::from_secs(1),
+            retention: DEFAULT_INODE_RETENTION,
+            snapshot: Some(snapshot),
+        })
+    }
+    
+    /// How long a deleted path keeps its inode mapping (default 30 days)
+    /// 
+    /// Inode numbers are never reused either way - new ones always go above
+    /// the highest ever allocated.
+    pub fn with_inode_retention(mut self, retention: Duration) -> Self {
+        self.retention = retention;
+        self.collect_inode_garbage();
+        self
+    }
+    
+    /// Write the inode table to the store if it changed
+    /// 
+    /// Snapshot mounts have nothing to persist.
+    pub fn persist_inodes(&self) -> Result<()> {
+        let mut inodes = self.inodes.write().unwrap();
+        if inodes.dirty && self.snapshot.is_none() {
+            let bytes = bincode::serialize(&inodes.to_persisted())?;
+            self.inner.write_namespace(INODE_NAMESPACE, &bytes)?;
+            inodes.dirty = false;
+        }
+        Ok(())
+    }
+    
+    fn collect_inode_garbage(&self) {
+        if self.snapshot.is_some() {
+            return;
         }
+        let now = chrono::Utc::now().timestamp() as u64;
+        self.inodes.write().unwrap().collect_garbage(&self.inner, self.retention, now);
     }
     
     /// Mount the filesystem at the given path
@@ -63,18 +243,43 @@ impl Mem8FuseFs {
     /// ```
     pub fn mount<P: AsRef<std::path::Path>>(self, mountpoint: P) -> Result<()> {
         // Create mount options
-        let options = vec![
-            fuser::MountOption::RO,  // Read-only for now
-            fuser::MountOption::FSName("mem8".to_string()),
-            fuser::MountOption::AutoUnmount,
-            fuser::MountOption::AllowOther,
-        ];
+        let mut options = self.mount_options();
+        options.push(fuser::MountOption::AllowOther);
         
         // Mount it!
         fuser::mount2(self, mountpoint, &options)?;
         Ok(())
     }
     
+    /// Mount in the background until SIGINT/SIGTERM, then unmount and flush
+    /// 
+    /// This is what binaries should use - a plain `mount` that gets killed
+    /// never gets the chance to checkpoint the index.
+    pub fn mount_until_shutdown<P: AsRef<std::path::Path>>(self, mountpoint: P) -> Result<()> {
+        let options = self.mount_options();
+        let inner = self.inner.clone();
+        crate::shutdown::install_handlers();
+        let session = fuser::spawn_mount2(self, mountpoint, &options)?;
+        
+        crate::shutdown::wait();
+        
+        // Dropping the session unmounts (and calls `destroy`)
+        drop(session);
+        inner.sync()
+    }
+    
+    /// Read-only for snapshots, read-write for the live tree
+    fn mount_options(&self) -> Vec<fuser::MountOption> {
+        vec![
+            match self.snapshot {
+                Some(_) => fuser::MountOption::RO,
+                None => fuser::MountOption::RW,
+            },
+            fuser::MountOption::FSName("mem8".to_string()),
+            fuser::MountOption::AutoUnmount,
+        ]
+    }
+    
     fn get_or_create_inode(&self, path: &str) -> u64 {
         let mut inodes = self.inodes.write().unwrap();
         
@@ -85,6 +290,7 @@ impl Mem8FuseFs {
             inodes.next_inode += 1;
             inodes.path_to_inode.insert(path.to_string(), inode);
             inodes.inode_to_path.insert(inode, path.to_string());
+            inodes.dirty = true;
             inode
         }
     }
@@ -93,48 +299,175 @@ impl Mem8FuseFs {
         let inodes = self.inodes.read().unwrap();
         inodes.inode_to_path.get(&inode).cloned()
     }
+    
+    // The live tree or the snapshot, whichever is mounted
+    
+    fn file_exists(&self, path: &str) -> bool {
+        match &self.snapshot {
+            Some(snapshot) => snapshot.exists(path),
+            None => self.inner.exists(path),
+        }
+    }
+    
+    fn file_metadata(&self, path: &str) -> Result<crate::FileMetadata> {
+        match &self.snapshot {
+            Some(snapshot) => snapshot.metadata(path),
+            None => self.inner.metadata(path),
+        }
+    }
+    
+    /// What's directly inside `dir`, as (path, is a directory)
+    fn dir_entries(&self, dir: &str) -> Result<Vec<(String, bool)>> {
+        match &self.snapshot {
+            Some(snapshot) if dir == "/" => Ok(snapshot.list(dir).into_iter()
+                .map(|path| (path.to_string_lossy().into_owned(), false))
+                .collect()),
+            Some(_) => Err(Mem8Error::not_found(format!("Directory not found: {}", dir))),
+            None => Ok(self.inner.read_dir(dir)?
+                .filter_map(|entry| entry.ok())
+                .map(|entry| (entry.path().to_string_lossy().into_owned(), entry.is_dir()))
+                .collect()),
+        }
+    }
+    
+    fn dir_exists(&self, path: &str) -> bool {
+        path == "/" || (self.snapshot.is_none() && self.inner.read_dir(path).is_ok())
+    }
+    
+    /// The attributes of whatever is at `path`, or ENOENT
+    fn attr_at(&self, inode: u64, path: &str) -> std::result::Result<FileAttr, i32> {
+        if self.file_exists(path) {
+            Ok(self.make_file_attr(inode, path))
+        } else if self.dir_exists(path) {
+            Ok(self.make_dir_attr(inode))
+        } else {
+            Err(libc::ENOENT)
+        }
+    }
+    
+    /// `name` inside the directory at inode `parent`
+    fn child_path(&self, parent: u64, name: &OsStr) -> std::result::Result<String, i32> {
+        let parent_path = self.path_from_inode(parent).ok_or(libc::ENOENT)?;
+        Ok(match parent_path.as_str() {
+            "/" => format!("/{}", name.to_string_lossy()),
+            _ => format!("{}/{}", parent_path, name.to_string_lossy()),
+        })
+    }
+    
+    /// The entries `readdir` lists for inode `ino`, `.` and `..` first
+    fn dir_listing(&self, ino: u64) -> std::result::Result<Vec<(u64, FileType, String)>, i32> {
+        let path = self.path_from_inode(ino).ok_or(libc::ENOENT)?;
+        if !self.dir_exists(&path) {
+            return Err(if self.file_exists(&path) { libc::ENOTDIR } else { libc::ENOENT });
+        }
+        let parent = match std::path::Path::new(&path).parent() {
+            Some(parent) => self.get_or_create_inode(&parent.to_string_lossy()),
+            None => FUSE_ROOT_ID,
+        };
+        
+        let mut entries = vec![
+            (ino, FileType::Directory, ".".to_string()),
+            (parent, FileType::Directory, "..".to_string()),
+        ];
+        for (child, is_dir) in self.dir_entries(&path).map_err(|e| errno(&e))? {
+            let name = std::path::Path::new(&child).file_name()
+                .map(|name| name.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "unknown".to_string());
+            let kind = if is_dir { FileType::Directory } else { FileType::RegularFile };
+            entries.push((self.get_or_create_inode(&child), kind, name));
+        }
+        Ok(entries)
+    }
+    
+    /// Remove the empty directory `name` inside `parent`, or the errno to reply with
+    fn remove_dir_at(&self, parent: u64, name: &OsStr) -> std::result::Result<(), i32> {
+        self.check_writable()?;
+        let path = self.child_path(parent, name)?;
+        self.inner.remove_dir(&path).map_err(|e| errno(&e))
+    }
+    
+    /// Up to `size` bytes of a file from `offset` (none past the end), or the errno to reply with
+    fn read_range(&self, path: &str, offset: usize, size: usize) -> std::result::Result<Vec<u8>, i32> {
+        let len = self.file_metadata(path).map_err(|e| errno(&e))?.size;
+        if offset as u64 >= len {
+            return Ok(Vec::new());
+        }
+        let read = match &self.snapshot {
+            Some(snapshot) => self.inner.read_snapshot_range(snapshot, path, offset, size),
+            None => self.inner.read_range(path, offset, size),
+        };
+        read.map_err(|e| {
+            if let Some(gone) = e.downcast_ref::<SnapshotDataGone>() {
+                eprintln!("⚠️ mem8: {}", gone);
+            }
+            errno(&e)
+        })
+    }
+    
+    /// EROFS for snapshot mounts
+    fn check_writable(&self) -> std::result::Result<(), i32> {
+        match self.snapshot {
+            Some(_) => Err(libc::EROFS),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The errno a failed Mem8Fs call is replied to the kernel with
+fn errno(e: &Mem8Error) -> i32 {
+    match e {
+        Mem8Error::NotFound(_) => libc::ENOENT,
+        Mem8Error::AlreadyExists(_) => libc::EEXIST,
+        Mem8Error::DirectoryNotEmpty(_) => libc::ENOTEMPTY,
+        Mem8Error::NotADirectory(_) => libc::ENOTDIR,
+        Mem8Error::InvalidPath(_) => libc::EINVAL,
+        Mem8Error::ReadOnly => libc::EROFS,
+        Mem8Error::QuotaExceeded { .. } => libc::EDQUOT,
+        Mem8Error::Io(e) => e.raw_os_error().unwrap_or(libc::EIO),
+        _ => libc::EIO,
+    }
 }
 
 #[cfg(feature = "fuse-mount")]
 impl Filesystem for Mem8FuseFs {
+    fn destroy(&mut self) {
+        // Unmounting - make sure the index, inodes and data hit the disk
+        if let Err(e) = self.persist_inodes() {
+            eprintln!("⚠️ mem8: failed to save inode table on unmount: {}", e);
+        }
+        if let Err(e) = self.inner.sync() {
+            eprintln!("⚠️ mem8: failed to flush on unmount: {}", e);
+        }
+    }
+    
     fn lookup(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEntry) {
-        let parent_path = match self.path_from_inode(parent) {
-            Some(p) => p,
-            None => {
-                reply.error(libc::ENOENT);
+        let path = match self.child_path(parent, name) {
+            Ok(path) => path,
+            Err(errno) => {
+                reply.error(errno);
                 return;
             }
         };
         
-        let path = if parent_path == "/" {
-            format!("/{}", name.to_string_lossy())
-        } else {
-            format!("{}/{}", parent_path, name.to_string_lossy())
-        };
-        
-        // Check if file exists
-        if self.inner.exists(&path) {
+        // A file or a directory; only hand out an inode for what's there
+        if self.file_exists(&path) || self.dir_exists(&path) {
             let inode = self.get_or_create_inode(&path);
-            let attr = self.make_file_attr(inode, &path);
-            reply.entry(&self.ttl, &attr, 0);
+            match self.attr_at(inode, &path) {
+                Ok(attr) => reply.entry(&self.ttl, &attr, 0),
+                Err(errno) => reply.error(errno),
+            }
         } else {
             reply.error(libc::ENOENT);
         }
     }
     
-    fn getattr(&mut self, _req: &Request, ino: u64, reply: ReplyAttr) {
-        if ino == FUSE_ROOT_ID {
-            let attr = self.make_dir_attr(FUSE_ROOT_ID);
-            reply.attr(&self.ttl, &attr);
-        } else if let Some(path) = self.path_from_inode(ino) {
-            if self.inner.exists(&path) {
-                let attr = self.make_file_attr(ino, &path);
-                reply.attr(&self.ttl, &attr);
-            } else {
-                reply.error(libc::ENOENT);
-            }
-        } else {
-            reply.error(libc::ENOENT);
+    fn getattr(&mut self, _req: &Request, ino: u64, _fh: Option<u64>, reply: ReplyAttr) {
+        match self.path_from_inode(ino) {
+            Some(path) => match self.attr_at(ino, &path) {
+                Ok(attr) => reply.attr(&self.ttl, &attr),
+                Err(errno) => reply.error(errno),
+            },
+            None => reply.error(libc::ENOENT),
         }
     }
     
@@ -150,17 +483,9 @@ impl Filesystem for Mem8FuseFs {
         reply: ReplyData,
     ) {
         if let Some(path) = self.path_from_inode(ino) {
-            match self.inner.read(&path) {
-                Ok(data) => {
-                    let start = offset as usize;
-                    let end = (offset as usize + size as usize).min(data.len());
-                    if start < data.len() {
-                        reply.data(&data[start..end]);
-                    } else {
-                        reply.data(&[]);
-                    }
-                }
-                Err(_) => reply.error(libc::EIO),
+            match self.read_range(&path, offset.max(0) as usize, size as usize) {
+                Ok(data) => reply.data(&data),
+                Err(errno) => reply.error(errno),
             }
         } else {
             reply.error(libc::ENOENT);
@@ -175,26 +500,13 @@ impl Filesystem for Mem8FuseFs {
         offset: i64,
         mut reply: ReplyDirectory,
     ) {
-        if ino != FUSE_ROOT_ID {
-            reply.error(libc::ENOTDIR);
-            return;
-        }
-        
-        let mut entries = vec![
-            (FUSE_ROOT_ID, FileType::Directory, "."),
-            (FUSE_ROOT_ID, FileType::Directory, ".."),
-        ];
-        
-        // List all files
-        if let Ok(files) = self.inner.list("/") {
-            for file in files {
-                let name = file.file_name()
-                    .and_then(|n| n.to_str())
-                    .unwrap_or("unknown");
-                let inode = self.get_or_create_inode(file.to_str().unwrap_or(""));
-                entries.push((inode, FileType::RegularFile, name));
+        let entries = match self.dir_listing(ino) {
+            Ok(entries) => entries,
+            Err(errno) => {
+                reply.error(errno);
+                return;
             }
-        }
+        };
         
         // Send entries
         for (i, (inode, file_type, name)) in entries.iter().enumerate().skip(offset as usize) {
@@ -219,28 +531,69 @@ impl Filesystem for Mem8FuseFs {
         _lock_owner: Option<u64>,
         reply: ReplyWrite,
     ) {
+        if let Err(errno) = self.check_writable() {
+            reply.error(errno);
+            return;
+        }
         if let Some(path) = self.path_from_inode(ino) {
-            // For simplicity, we'll overwrite the whole file
-            // In production, you'd handle partial writes properly
-            match self.inner.write(&path, data) {
+            match self.inner.write_at(&path, offset.max(0) as u64, data) {
                 Ok(_) => reply.written(data.len() as u32),
-                Err(_) => reply.error(libc::EIO),
+                Err(e) => reply.error(errno(&e)),
             }
         } else {
             reply.error(libc::ENOENT);
         }
     }
+    
+    // Not supported yet on the live tree; snapshots are read-only for good
+    
+    fn create(&mut self, _req: &Request, _parent: u64, _name: &OsStr, _mode: u32, _umask: u32, _flags: i32, reply: ReplyCreate) {
+        reply.error(self.check_writable().err().unwrap_or(libc::ENOSYS));
+    }
+    
+    fn mkdir(&mut self, _req: &Request, _parent: u64, _name: &OsStr, _mode: u32, _umask: u32, reply: ReplyEntry) {
+        reply.error(self.check_writable().err().unwrap_or(libc::ENOSYS));
+    }
+    
+    fn unlink(&mut self, _req: &Request, _parent: u64, _name: &OsStr, reply: ReplyEmpty) {
+        reply.error(self.check_writable().err().unwrap_or(libc::ENOSYS));
+    }
+    
+    fn rmdir(&mut self, _req: &Request, parent: u64, name: &OsStr, reply: ReplyEmpty) {
+        match self.remove_dir_at(parent, name) {
+            Ok(()) => reply.ok(),
+            Err(errno) => reply.error(errno),
+        }
+    }
+    
+    fn rename(
+        &mut self,
+        _req: &Request,
+        _parent: u64,
+        _name: &OsStr,
+        _newparent: u64,
+        _newname: &OsStr,
+        _flags: u32,
+        reply: ReplyEmpty,
+    ) {
+        reply.error(self.check_writable().err().unwrap_or(libc::ENOSYS));
+    }
 }
 
 // Helper methods for attributes
 impl Mem8FuseFs {
     fn make_file_attr(&self, inode: u64, path: &str) -> FileAttr {
-        let metadata = self.inner.metadata(path).unwrap_or_else(|_| {
+        let metadata = self.file_metadata(path).unwrap_or_else(|_| {
             crate::FileMetadata {
                 size: 0,
+                nlink: 1,
                 created: 0,
                 modified: 0,
+                created_ms: 0,
+                modified_ms: 0,
                 signature: String::new(),
+                wave_frequency: self.inner.base_frequency().hz(),
+                metadata: None,
             }
         });
         
@@ -248,13 +601,13 @@ impl Mem8FuseFs {
             ino: inode,
             size: metadata.size,
             blocks: (metadata.size + 511) / 512,
-            atime: UNIX_EPOCH + Duration::from_secs(metadata.modified),
-            mtime: UNIX_EPOCH + Duration::from_secs(metadata.modified),
-            ctime: UNIX_EPOCH + Duration::from_secs(metadata.created),
-            crtime: UNIX_EPOCH + Duration::from_secs(metadata.created),
+            atime: UNIX_EPOCH + Duration::from_millis(metadata.modified_ms),
+            mtime: UNIX_EPOCH + Duration::from_millis(metadata.modified_ms),
+            ctime: UNIX_EPOCH + Duration::from_millis(metadata.created_ms),
+            crtime: UNIX_EPOCH + Duration::from_millis(metadata.created_ms),
             kind: FileType::RegularFile,
             perm: 0o644,
-            nlink: 1,
+            nlink: metadata.nlink.max(1) as u32,
             uid: 1000,
             gid: 1000,
             rdev: 0,
@@ -282,4 +635,143 @@ impl Mem8FuseFs {
             flags: 0,
         }
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    
+    #[test]
+    fn test_inodes_stable_across_remounts() {
+        let dir = tempdir().unwrap();
+        let mem8 = Arc::new(Mem8Fs::new(dir.path()).unwrap());
+        mem8.write_string("/a.txt", "alpha").unwrap();
+        mem8.write_string("/b.txt", "beta").unwrap();
+        
+        let (a, b) = {
+            let mut fuse = Mem8FuseFs::new(mem8.clone());
+            let a = fuse.get_or_create_inode("/a.txt");
+            let b = fuse.get_or_create_inode("/b.txt");
+            fuse.destroy(); // What unmounting does
+            (a, b)
+        };
+        
+        // Remount: stat sees the same inode numbers, new files go above them
+        let fuse = Mem8FuseFs::new(mem8.clone());
+        assert_eq!(fuse.make_file_attr(fuse.get_or_create_inode("/b.txt"), "/b.txt").ino, b);
+        assert_eq!(fuse.make_file_attr(fuse.get_or_create_inode("/a.txt"), "/a.txt").ino, a);
+        mem8.write_string("/c.txt", "gamma").unwrap();
+        assert!(fuse.get_or_create_inode("/c.txt") > a.max(b));
+    }
+    
+    #[test]
+    fn test_deleted_paths_expire() {
+        let dir = tempdir().unwrap();
+        let mem8 = Arc::new(Mem8Fs::new(dir.path()).unwrap());
+        mem8.write_string("/gone.txt", "bye").unwrap();
+        
+        let fuse = Mem8FuseFs::new(mem8.clone());
+        let gone = fuse.get_or_create_inode("/gone.txt");
+        mem8.delete("/gone.txt").unwrap();
+        
+        // Recently deleted: mapping kept
+        let now = chrono::Utc::now().timestamp() as u64;
+        fuse.inodes.write().unwrap().collect_garbage(&mem8, Duration::from_secs(60), now);
+        assert_eq!(fuse.path_from_inode(gone).as_deref(), Some("/gone.txt"));
+        
+        // Past the retention window: dropped, and the number isn't handed out again
+        fuse.inodes.write().unwrap().collect_garbage(&mem8, Duration::from_secs(60), now + 61);
+        assert_eq!(fuse.path_from_inode(gone), None);
+        mem8.write_string("/new.txt", "hi").unwrap();
+        assert!(fuse.get_or_create_inode("/new.txt") > gone);
+    }
+    
+    #[test]
+    fn test_snapshot_mount_serves_the_old_tree_read_only() {
+        let dir = tempdir().unwrap();
+        let mem8 = Arc::new(Mem8Fs::new(dir.path()).unwrap());
+        mem8.write_string("/a.txt", "alpha").unwrap();
+        mem8.write_string("/secret.txt", "shh").unwrap();
+        mem8.create_snapshot("nightly-2024-06-01").unwrap();
+        
+        mem8.write_string("/a.txt", "alpha, edited").unwrap();
+        mem8.write_string("/b.txt", "beta").unwrap();
+        mem8.secure_delete("/secret.txt").unwrap();
+        
+        let fuse = Mem8FuseFs::new_snapshot(mem8.clone(), "nightly-2024-06-01").unwrap();
+        assert!(fuse.file_exists("/a.txt") && !fuse.file_exists("/b.txt"));
+        assert_eq!(fuse.read_range("/a.txt", 0, 4096).unwrap(), b"alpha");
+        assert_eq!(fuse.read_range("/a.txt", 1, 3).unwrap(), b"lph");
+        assert!(fuse.read_range("/a.txt", 9, 4096).unwrap().is_empty());
+        let inode = fuse.get_or_create_inode("/a.txt");
+        assert_eq!(fuse.make_file_attr(inode, "/a.txt").size, 5);
+        assert_eq!(fuse.dir_entries("/").unwrap().len(), 2);
+        
+        // Content scrubbed since the snapshot is EIO, and writes are refused
+        assert_eq!(fuse.read_range("/secret.txt", 0, 4096), Err(libc::EIO));
+        assert_eq!(fuse.check_writable(), Err(libc::EROFS));
+        fuse.persist_inodes().unwrap();
+        assert!(mem8.read_namespace(INODE_NAMESPACE).unwrap().is_none());
+        assert_eq!(mem8.read_string("/a.txt").unwrap(), "alpha, edited");
+        
+        assert!(Mem8FuseFs::new_snapshot(mem8, "no-such-snapshot").is_err());
+    }
+    
+    #[test]
+    fn test_directories_resolve_list_and_rmdir() {
+        let dir = tempdir().unwrap();
+        let mem8 = Arc::new(Mem8Fs::new(dir.path()).unwrap());
+        mem8.write_string("/docs/a.txt", "alpha").unwrap();
+        mem8.create_dir("/empty").unwrap();
+        mem8.write_string("/top.txt", "top").unwrap();
+        let fuse = Mem8FuseFs::new(mem8.clone());
+        
+        // Directories are looked up, stat'ed and listed as directories
+        let docs = fuse.get_or_create_inode("/docs");
+        assert_eq!(fuse.attr_at(docs, "/docs").unwrap().kind, FileType::Directory);
+        assert_eq!(fuse.attr_at(0, "/nowhere"), Err(libc::ENOENT));
+        let root: Vec<(FileType, String)> = fuse.dir_listing(FUSE_ROOT_ID).unwrap().into_iter()
+            .map(|(_, kind, name)| (kind, name))
+            .collect();
+        assert_eq!(root, [
+            (FileType::Directory, ".".to_string()),
+            (FileType::Directory, "..".to_string()),
+            (FileType::Directory, "docs".to_string()),
+            (FileType::Directory, "empty".to_string()),
+            (FileType::RegularFile, "top.txt".to_string()),
+        ]);
+        let inside = fuse.dir_listing(docs).unwrap();
+        assert_eq!(inside[1].0, FUSE_ROOT_ID);
+        assert_eq!((inside[2].1, inside[2].2.as_str()), (FileType::RegularFile, "a.txt"));
+        assert_eq!(fuse.dir_listing(fuse.get_or_create_inode("/top.txt")), Err(libc::ENOTDIR));
+        
+        // rmdir: not empty, not a directory, then the empty one goes
+        assert_eq!(fuse.remove_dir_at(FUSE_ROOT_ID, OsStr::new("docs")), Err(libc::ENOTEMPTY));
+        assert_eq!(fuse.remove_dir_at(FUSE_ROOT_ID, OsStr::new("top.txt")), Err(libc::ENOTDIR));
+        assert_eq!(fuse.remove_dir_at(FUSE_ROOT_ID, OsStr::new("empty")), Ok(()));
+        assert_eq!(fuse.remove_dir_at(FUSE_ROOT_ID, OsStr::new("empty")), Err(libc::ENOENT));
+        assert!(mem8.exists("/docs/a.txt"));
+        
+        // Read-write unless it's a snapshot, which has no directories but the root
+        assert!(fuse.mount_options().contains(&fuser::MountOption::RW));
+        mem8.create_snapshot("s").unwrap();
+        let snapshot = Mem8FuseFs::new_snapshot(mem8, "s").unwrap();
+        assert!(snapshot.mount_options().contains(&fuser::MountOption::RO));
+        assert_eq!(snapshot.remove_dir_at(FUSE_ROOT_ID, OsStr::new("docs")), Err(libc::EROFS));
+        assert_eq!(snapshot.dir_listing(FUSE_ROOT_ID).unwrap().len(), 3);
+    }
+    
+    #[test]
+    fn test_errors_map_to_errnos() {
+        let dir = tempdir().unwrap();
+        let mem8 = Arc::new(Mem8Fs::new(dir.path()).unwrap());
+        mem8.write_string("/a.txt", "alpha").unwrap();
+        mem8.write_string("/b.txt", "beta").unwrap();
+        
+        let fuse = Mem8FuseFs::new(mem8.clone());
+        assert_eq!(fuse.read_range("/missing.txt", 0, 4096), Err(libc::ENOENT));
+        assert_eq!(errno(&mem8.rename("/a.txt", "/b.txt").unwrap_err()), libc::EEXIST);
+        assert_eq!(errno(&mem8.read("/../a.txt").unwrap_err()), libc::EINVAL);
+    }
+}
@@ -0,0 +1,655 @@
+//! Alert notifications - where a sensor alert goes, and when it keeps quiet
+//!
+//! A hub's `[notify]` table lists named routes: `log` (stderr), `store_event`
+//! (a packet in the hub's store), `webhook` (an HTTP POST of the alert as
+//! JSON) and `playback_chime` (handed to whatever plays sounds, see
+//! `NotificationRouter::with_chime`). Each alert rule picks its routes by
+//! name (all of them if it names none), and any route can have quiet hours
+//! during which it stays silent:
+//!
+//! ```toml
+//! [notify]
+//! dedup_secs = 300
+//!
+//! [[notify.routes]]
+//! name = "log"
+//! kind = "log"
+//!
+//! [[notify.routes]]
+//! name = "phone"
+//! kind = "webhook"
+//! url = "http://127.0.0.1:9000/alerts"
+//! quiet_hours = { start = "22:00", end = "07:00" }
+//! ```
+//!
+//! Quiet hours are local time - the machine's timezone unless the route
+//! gives `utc_offset_minutes` - read off the router's `Clock`. The same alert
+//! (sensor and message) within `dedup_secs` of the last one isn't sent
+//! again. What happened on every route is recorded per alert
+//! (`AlertDelivery`).
+//!
+//! Webhooks don't hold up whoever raised the alert: they go on a bounded
+//! queue (`WEBHOOK_QUEUE_LIMIT`) that a delivery thread drains, retrying
+//! each with exponential backoff. Until it's done, the route is `Queued`;
+//! the thread then records how it went in the router's `deliveries`.
+//!
+//! Hue's 3am fridge alarm goes to the log. Trisha sleeps through it. 🔕
+
+use std::collections::{HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Duration;
+use chrono::{TimeZone, Timelike};
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+use anyhow::{Result, anyhow};
+
+use crate::clock::{Clock, SystemClock};
+use crate::hub::Alert;
+use crate::shared::SharedMem8Lite;
+
+/// Metadata namespace of `store_event` deliveries
+pub const ALERT_EVENT_NAMESPACE: &str = "sensor_alert_event";
+
+/// How many deliveries the router remembers
+const DELIVERY_LOG_LIMIT: usize = 200;
+
+/// How long one webhook attempt may take
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Webhook posts waiting for the delivery thread, at most (more fail as `Failed`)
+pub const WEBHOOK_QUEUE_LIMIT: usize = 64;
+
+/// Plays a chime: called with the route's sound and the alert
+pub type ChimePlayer = Arc<dyn Fn(&str, &Alert) -> Result<()> + Send + Sync>;
+
+/// The hub's `[notify]` table
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotifyConfig {
+    pub routes: Vec<Route>,
+    
+    /// The same alert again within this many seconds isn't delivered (0: always deliver)
+    pub dedup_secs: u64,
+    
+    /// Webhook attempts after the first one fails
+    pub webhook_retries: u32,
+    
+    /// Wait before the first webhook retry; doubles every time after
+    pub webhook_backoff_ms: u64,
+}
+
+impl Default for NotifyConfig {
+    fn default() -> Self {
+        Self { routes: Vec::new(), dedup_secs: 300, webhook_retries: 3, webhook_backoff_ms: 500 }
+    }
+}
+
+impl NotifyConfig {
+    /// Route names are unique, quiet hours parse and webhooks are `http://`
+    pub fn validate(&self) -> Result<()> {
+        let mut names = std::collections::HashSet::new();
+        for route in &self.routes {
+            if !names.insert(route.name.as_str()) {
+                return Err(anyhow!("Route {} is defined twice", route.name));
+            }
+            if let Some(quiet) = &route.quiet_hours {
+                quiet.window().map_err(|e| anyhow!("Route {}: {}", route.name, e))?;
+            }
+            if let RouteTarget::Webhook { url } = &route.target {
+                split_http_url(url).map_err(|e| anyhow!("Route {}: {}", route.name, e))?;
+            }
+        }
+        Ok(())
+    }
+    
+    pub fn has_route(&self, name: &str) -> bool {
+        self.routes.iter().any(|route| route.name == name)
+    }
+}
+
+/// A named place alerts can go
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Route {
+    pub name: String,
+    
+    #[serde(flatten)]
+    pub target: RouteTarget,
+    
+    #[serde(default)]
+    pub quiet_hours: Option<QuietHours>,
+}
+
+/// Where a route delivers
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum RouteTarget {
+    /// A line on stderr
+    Log,
+    
+    /// A packet in the hub's store (`ALERT_EVENT_NAMESPACE`)
+    StoreEvent,
+    
+    /// An HTTP POST of the alert as JSON (plain `http://` only)
+    Webhook { url: String },
+    
+    /// A sound, through the router's chime player
+    PlaybackChime { sound: String },
+}
+
+/// A daily stretch of local time a route keeps quiet, e.g. 22:00 to 07:00
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct QuietHours {
+    /// `HH:MM`, inclusive
+    pub start: String,
+    
+    /// `HH:MM`, exclusive (before `start` means the window crosses midnight)
+    pub end: String,
+    
+    /// Local time's offset from UTC (the machine's timezone if left out)
+    #[serde(default)]
+    pub utc_offset_minutes: Option<i32>,
+}
+
+impl QuietHours {
+    /// Start and end as minutes past midnight
+    fn window(&self) -> Result<(u32, u32)> {
+        let minutes = |text: &str| -> Result<u32> {
+            let (hours, minutes) = text.split_once(':')
+                .and_then(|(h, m)| Some((h.parse::<u32>().ok()?, m.parse::<u32>().ok()?)))
+                .filter(|&(h, m)| h < 24 && m < 60)
+                .ok_or_else(|| anyhow!("Quiet hours need HH:MM, got {:?}", text))?;
+            Ok(hours * 60 + minutes)
+        };
+        Ok((minutes(&self.start)?, minutes(&self.end)?))
+    }
+    
+    /// Is `now` (unix seconds) inside the quiet hours?
+    pub fn contains(&self, now: u64) -> bool {
+        let Ok((start, end)) = self.window() else { return false };
+        let offset_secs = match self.utc_offset_minutes {
+            Some(minutes) => minutes * 60,
+            None => chrono::Local.timestamp_opt(now as i64, 0).single()
+                .map_or(0, |local| local.offset().local_minus_utc()),
+        };
+        let Some(offset) = chrono::FixedOffset::east_opt(offset_secs) else { return false };
+        let Some(local) = offset.timestamp_opt(now as i64, 0).single() else { return false };
+        let minute = local.hour() * 60 + local.minute();
+        match start <= end {
+            true => start <= minute && minute < end,
+            false => minute >= start || minute < end,
+        }
+    }
+}
+
+/// What happened to an alert on one route
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    Delivered { attempts: u32 },
+    
+    /// Waiting for the webhook thread (`deliveries` has the outcome later)
+    Queued,
+    
+    /// Inside the route's quiet hours
+    QuietHours,
+    
+    /// The same alert went out less than `dedup_secs` ago
+    Duplicate,
+    
+    /// Every attempt failed; `error` is the last one's
+    Failed { attempts: u32, error: String },
+}
+
+/// One route's outcome for an alert
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RouteDelivery {
+    pub route: String,
+    
+    #[serde(flatten)]
+    pub status: DeliveryStatus,
+}
+
+/// Everything the router did with one alert
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertDelivery {
+    pub alert: Alert,
+    
+    /// When it was routed (unix seconds, from the router's clock)
+    pub routed_at: u64,
+    
+    pub routes: Vec<RouteDelivery>,
+}
+
+/// The router's record of deliveries, shared with its webhook thread
+#[derive(Default)]
+struct DeliveryLog {
+    /// Most recent deliveries and their sequence numbers, oldest first
+    entries: VecDeque<(u64, AlertDelivery)>,
+    
+    /// Sequence number of the next delivery
+    next: u64,
+    
+    /// Webhook posts queued or being sent
+    pending: usize,
+}
+
+#[derive(Default)]
+struct SharedLog {
+    log: Mutex<DeliveryLog>,
+    
+    /// Signalled whenever a webhook post is done with
+    settled: Condvar,
+}
+
+/// A webhook post for the delivery thread
+struct WebhookJob {
+    /// Sequence number of the delivery it belongs to
+    delivery: u64,
+    route: String,
+    url: String,
+    alert: Alert,
+}
+
+/// Sends alerts down their routes (see the module docs)
+pub struct NotificationRouter {
+    config: NotifyConfig,
+    clock: Arc<dyn Clock>,
+    store: Option<SharedMem8Lite>,
+    chime: Option<ChimePlayer>,
+    
+    /// When each (sensor, message) was last delivered
+    last_sent: HashMap<(String, String), u64>,
+    
+    deliveries: Arc<SharedLog>,
+    
+    /// The webhook thread's queue (none without webhook routes)
+    webhooks: Option<SyncSender<WebhookJob>>,
+}
+
+impl NotificationRouter {
+    pub fn new(config: NotifyConfig) -> Self {
+        let deliveries = Arc::new(SharedLog::default());
+        let has_webhooks = config.routes.iter().any(|route| matches!(route.target, RouteTarget::Webhook { .. }));
+        let webhooks = has_webhooks.then(|| {
+            let (sender, receiver) = sync_channel(WEBHOOK_QUEUE_LIMIT);
+            let (log, retries, backoff_ms) = (Arc::clone(&deliveries), config.webhook_retries, config.webhook_backoff_ms);
+            std::thread::spawn(move || run_webhooks(receiver, log, retries, backoff_ms));
+            sender
+        });
+        Self {
+            config,
+            clock: Arc::new(SystemClock),
+            store: None,
+            chime: None,
+            last_sent: HashMap::new(),
+            deliveries,
+            webhooks,
+        }
+    }
+    
+    /// Read "now" (for quiet hours and dedup) from `clock`
+    pub fn with_clock(mut self, clock: Arc<dyn Clock>) -> Self {
+        self.clock = clock;
+        self
+    }
+    
+    /// Where `store_event` routes write
+    pub fn with_store(mut self, store: SharedMem8Lite) -> Self {
+        self.store = Some(store);
+        self
+    }
+    
+    /// What `playback_chime` routes play through (without one they fail)
+    pub fn with_chime<F>(mut self, player: F) -> Self
+    where F: Fn(&str, &Alert) -> Result<()> + Send + Sync + 'static {
+        self.chime = Some(Arc::new(player));
+        self
+    }
+    
+    pub fn set_clock(&mut self, clock: Arc<dyn Clock>) {
+        self.clock = clock;
+    }
+    
+    pub fn set_chime<F>(&mut self, player: F)
+    where F: Fn(&str, &Alert) -> Result<()> + Send + Sync + 'static {
+        self.chime = Some(Arc::new(player));
+    }
+    
+    pub fn config(&self) -> &NotifyConfig {
+        &self.config
+    }
+    
+    /// Recent deliveries, oldest first (webhooks still `Queued` until sent)
+    pub fn deliveries(&self) -> Vec<AlertDelivery> {
+        let log = self.deliveries.log.lock().unwrap();
+        log.entries.iter().map(|(_, delivery)| delivery.clone()).collect()
+    }
+    
+    /// Wait up to `timeout` for every queued webhook to be sent or given up on
+    ///
+    /// True if none are left.
+    pub fn wait_for_webhooks(&self, timeout: Duration) -> bool {
+        let log = self.deliveries.log.lock().unwrap();
+        let (_log, waited) = self.deliveries.settled
+            .wait_timeout_while(log, timeout, |log| log.pending > 0)
+            .unwrap();
+        !waited.timed_out()
+    }
+    
+    /// Send `alert` down the named routes (every route if `routes` is empty)
+    ///
+    /// Everything but webhooks is done by the time it returns; those are
+    /// handed to the webhook thread and come back `Queued`.
+    pub fn deliver(&mut self, alert: &Alert, routes: &[String]) -> AlertDelivery {
+        let now = self.clock.now();
+        let key = (alert.sensor.clone(), alert.message.clone());
+        let duplicate = self.config.dedup_secs > 0 && self.last_sent.get(&key)
+            .is_some_and(|&sent| now.saturating_sub(sent) < self.config.dedup_secs);
+        
+        let chosen: Vec<&Route> = self.config.routes.iter()
+            .filter(|route| routes.is_empty() || routes.contains(&route.name))
+            .collect();
+        let mut outcomes = Vec::with_capacity(chosen.len());
+        let mut webhooks = Vec::new();
+        for route in chosen {
+            let status = if duplicate {
+                DeliveryStatus::Duplicate
+            } else if route.quiet_hours.as_ref().is_some_and(|quiet| quiet.contains(now)) {
+                DeliveryStatus::QuietHours
+            } else {
+                self.send(route, alert)
+            };
+            if let (DeliveryStatus::Queued, RouteTarget::Webhook { url }) = (&status, &route.target) {
+                webhooks.push((outcomes.len(), url.clone()));
+            }
+            outcomes.push(RouteDelivery { route: route.name.clone(), status });
+        }
+        if !duplicate {
+            self.last_sent.insert(key, now);
+        }
+        
+        // Queued with the log held, so the thread can't finish before it's recorded
+        let mut delivery = AlertDelivery { alert: alert.clone(), routed_at: now, routes: outcomes };
+        let mut log = self.deliveries.log.lock().unwrap();
+        let id = log.next;
+        log.next += 1;
+        for (i, url) in webhooks {
+            let job = WebhookJob { delivery: id, route: delivery.routes[i].route.clone(), url, alert: alert.clone() };
+            let queued = match &self.webhooks {
+                Some(sender) => sender.try_send(job),
+                None => Err(TrySendError::Disconnected(job)),
+            };
+            delivery.routes[i].status = match queued {
+                Ok(()) => {
+                    log.pending += 1;
+                    DeliveryStatus::Queued
+                }
+                Err(TrySendError::Full(_)) => DeliveryStatus::Failed { attempts: 0, error: "Webhook queue is full".to_string() },
+                Err(TrySendError::Disconnected(_)) => DeliveryStatus::Failed { attempts: 0, error: "Webhook thread is gone".to_string() },
+            };
+        }
+        log.entries.push_back((id, delivery.clone()));
+        if log.entries.len() > DELIVERY_LOG_LIMIT {
+            log.entries.pop_front();
+        }
+        delivery
+    }
+    
+    fn send(&self, route: &Route, alert: &Alert) -> DeliveryStatus {
+        let once = |result: Result<()>| match result {
+            Ok(()) => DeliveryStatus::Delivered { attempts: 1 },
+            Err(e) => DeliveryStatus::Failed { attempts: 1, error: e.to_string() },
+        };
+        match &route.target {
+            RouteTarget::Log => {
+                eprintln!("🔔 mem8: {} = {} at {}: {}", alert.sensor, alert.value, alert.timestamp, alert.message);
+                DeliveryStatus::Delivered { attempts: 1 }
+            }
+            RouteTarget::StoreEvent => once(self.store_event(route, alert)),
+            RouteTarget::PlaybackChime { sound } => once(match &self.chime {
+                Some(player) => player(sound, alert),
+                None => Err(anyhow!("No chime player attached")),
+            }),
+            // Posted by the webhook thread, once `deliver` has queued it
+            RouteTarget::Webhook { .. } => DeliveryStatus::Queued,
+        }
+    }
+    
+    fn store_event(&self, route: &Route, alert: &Alert) -> Result<()> {
+        let store = self.store.as_ref().ok_or_else(|| anyhow!("No store attached"))?;
+        let meta = json!({
+            "namespace": ALERT_EVENT_NAMESPACE,
+            "sensor": alert.sensor,
+            "timestamp": alert.timestamp,
+            "route": route.name,
+        });
+        store.store(&serde_json::to_vec(alert)?, Some(serde_json::to_vec(&meta)?))?;
+        Ok(())
+    }
+    
+}
+
+impl std::fmt::Debug for NotificationRouter {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("NotificationRouter")
+            .field("config", &self.config)
+            .field("store", &self.store.is_some())
+            .field("chime", &self.chime.is_some())
+            .field("deliveries", &self.deliveries.log.lock().unwrap().entries.len())
+            .finish()
+    }
+}
+
+/// The webhook thread: post each job and record how it went, until the router's gone
+fn run_webhooks(jobs: Receiver<WebhookJob>, shared: Arc<SharedLog>, retries: u32, backoff_ms: u64) {
+    for job in jobs {
+        let status = post_with_retries(&job.url, &job.alert, retries, backoff_ms);
+        let mut log = shared.log.lock().unwrap();
+        let route = log.entries.iter_mut()
+            .find(|(id, _)| *id == job.delivery)
+            .and_then(|(_, delivery)| delivery.routes.iter_mut().find(|route| route.route == job.route));
+        if let Some(route) = route {
+            route.status = status;
+        }
+        log.pending -= 1;
+        shared.settled.notify_all();
+    }
+}
+
+/// POST `alert` to `url`, retrying up to `retries` times with doubling waits
+fn post_with_retries(url: &str, alert: &Alert, retries: u32, backoff_ms: u64) -> DeliveryStatus {
+    let body = match serde_json::to_vec(alert) {
+        Ok(body) => body,
+        Err(e) => return DeliveryStatus::Failed { attempts: 0, error: e.to_string() },
+    };
+    let mut backoff = Duration::from_millis(backoff_ms);
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let error = match post_json(url, &body) {
+            Ok(status) if (200..300).contains(&status) => return DeliveryStatus::Delivered { attempts },
+            Ok(status) => format!("{} answered {}", url, status),
+            Err(e) => e.to_string(),
+        };
+        if attempts > retries {
+            return DeliveryStatus::Failed { attempts, error };
+        }
+        std::thread::sleep(backoff);
+        backoff *= 2;
+    }
+}
+
+/// `http://host[:port]/path` as (`host:port`, `/path`)
+fn split_http_url(url: &str) -> Result<(String, String)> {
+    let rest = url.strip_prefix("http://")
+        .ok_or_else(|| anyhow!("Webhooks need an http:// URL, got {}", url))?;
+    let (authority, path) = match rest.find('/') {
+        Some(slash) => (&rest[..slash], &rest[slash..]),
+        None => (rest, "/"),
+    };
+    if authority.is_empty() {
+        return Err(anyhow!("Webhook URL has no host: {}", url));
+    }
+    let authority = match authority.contains(':') {
+        true => authority.to_string(),
+        false => format!("{}:80", authority),
+    };
+    Ok((authority, path.to_string()))
+}
+
+/// POST `body` as JSON and return the response's status code
+fn post_json(url: &str, body: &[u8]) -> Result<u16> {
+    let (authority, path) = split_http_url(url)?;
+    let addr = authority.to_socket_addrs()?.next()
+        .ok_or_else(|| anyhow!("Can't resolve {}", authority))?;
+    let mut stream = TcpStream::connect_timeout(&addr, WEBHOOK_TIMEOUT)?;
+    stream.set_read_timeout(Some(WEBHOOK_TIMEOUT))?;
+    stream.set_write_timeout(Some(WEBHOOK_TIMEOUT))?;
+    write!(
+        stream,
+        "POST {} HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        path, authority, body.len()
+    )?;
+    stream.write_all(body)?;
+    stream.flush()?;
+    
+    let mut status_line = String::new();
+    BufReader::new(stream).read_line(&mut status_line)?;
+    status_line.split_whitespace().nth(1)
+        .and_then(|code| code.parse().ok())
+        .ok_or_else(|| anyhow!("Not an HTTP response from {}: {:?}", url, status_line.trim()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+    use std::io::Read;
+    use crate::clock::ManualClock;
+    
+    fn alert(message: &str) -> Alert {
+        Alert { timestamp: 1, sensor: "fridge_temp".to_string(), value: 9.5, message: message.to_string() }
+    }
+    
+    fn route(name: &str, target: RouteTarget, quiet_hours: Option<QuietHours>) -> Route {
+        Route { name: name.to_string(), target, quiet_hours }
+    }
+    
+    /// 2024-06-01 at `hour`:`minute` UTC
+    fn at(hour: u64, minute: u64) -> u64 {
+        1_717_200_000 + hour * 3600 + minute * 60
+    }
+    
+    #[test]
+    fn test_quiet_hours_silence_a_route_and_cross_midnight() {
+        let night = QuietHours { start: "22:00".into(), end: "07:00".into(), utc_offset_minutes: Some(0) };
+        assert!(night.contains(at(3, 0)) && night.contains(at(22, 0)) && night.contains(at(6, 59)));
+        assert!(!night.contains(at(7, 0)) && !night.contains(at(12, 0)));
+        // Two hours east, 21:00 UTC is already 23:00
+        let east = QuietHours { utc_offset_minutes: Some(120), ..night.clone() };
+        assert!(east.contains(at(21, 0)) && !night.contains(at(21, 0)));
+        
+        let clock = Arc::new(ManualClock::new(at(3, 0)));
+        let chimes = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let heard = chimes.clone();
+        let config = NotifyConfig {
+            routes: vec![
+                route("log", RouteTarget::Log, None),
+                route("chime", RouteTarget::PlaybackChime { sound: "ding".into() }, Some(night)),
+            ],
+            dedup_secs: 0,
+            ..Default::default()
+        };
+        let mut router = NotificationRouter::new(config)
+            .with_clock(clock.clone())
+            .with_chime(move |sound, alert| {
+                heard.lock().unwrap().push((sound.to_string(), alert.message.clone()));
+                Ok(())
+            });
+        
+        let delivery = router.deliver(&alert("Fridge is warm"), &[]);
+        assert_eq!(delivery.routes, vec![
+            RouteDelivery { route: "log".into(), status: DeliveryStatus::Delivered { attempts: 1 } },
+            RouteDelivery { route: "chime".into(), status: DeliveryStatus::QuietHours },
+        ]);
+        assert!(chimes.lock().unwrap().is_empty());
+        
+        clock.set(at(9, 30));
+        let delivery = router.deliver(&alert("Fridge is warm"), &["chime".to_string()]);
+        assert_eq!(delivery.routes.len(), 1);
+        assert_eq!(delivery.routes[0].status, DeliveryStatus::Delivered { attempts: 1 });
+        assert_eq!(*chimes.lock().unwrap(), vec![("ding".to_string(), "Fridge is warm".to_string())]);
+        assert_eq!(router.deliveries().len(), 2);
+    }
+    
+    #[test]
+    fn test_identical_alerts_inside_the_window_are_deduplicated() {
+        let clock = Arc::new(ManualClock::new(at(12, 0)));
+        let config = NotifyConfig { routes: vec![route("log", RouteTarget::Log, None)], dedup_secs: 300, ..Default::default() };
+        let mut router = NotificationRouter::new(config).with_clock(clock.clone());
+        let delivered = |delivery: AlertDelivery| delivery.routes[0].status.clone();
+        
+        assert_eq!(delivered(router.deliver(&alert("Fridge is warm"), &[])), DeliveryStatus::Delivered { attempts: 1 });
+        clock.advance(Duration::from_secs(120));
+        assert_eq!(delivered(router.deliver(&alert("Fridge is warm"), &[])), DeliveryStatus::Duplicate);
+        // A different message isn't the same alert
+        assert_eq!(delivered(router.deliver(&alert("Fridge door open"), &[])), DeliveryStatus::Delivered { attempts: 1 });
+        // The window runs from the last delivery, not the last duplicate
+        clock.advance(Duration::from_secs(181));
+        assert_eq!(delivered(router.deliver(&alert("Fridge is warm"), &[])), DeliveryStatus::Delivered { attempts: 1 });
+    }
+    
+    #[test]
+    fn test_webhooks_are_retried_with_backoff() {
+        // Fails twice, then takes it
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/alerts", listener.local_addr().unwrap());
+        let server = std::thread::spawn(move || {
+            let mut bodies = Vec::new();
+            for status in ["500 Internal Server Error", "503 Service Unavailable", "204 No Content"] {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = Vec::new();
+                let mut buf = [0u8; 1024];
+                while !String::from_utf8_lossy(&request).contains("}") {
+                    let n = stream.read(&mut buf).unwrap();
+                    request.extend_from_slice(&buf[..n]);
+                }
+                bodies.push(String::from_utf8(request).unwrap());
+                write!(stream, "HTTP/1.1 {}\r\nContent-Length: 0\r\n\r\n", status).unwrap();
+            }
+            bodies
+        });
+        
+        let config = NotifyConfig {
+            routes: vec![route("phone", RouteTarget::Webhook { url: url.clone() }, None)],
+            webhook_retries: 3,
+            webhook_backoff_ms: 10,
+            ..Default::default()
+        };
+        let mut router = NotificationRouter::new(config);
+        let delivery = router.deliver(&alert("Fridge is warm"), &[]);
+        assert_eq!(delivery.routes[0].status, DeliveryStatus::Queued);
+        assert!(router.wait_for_webhooks(Duration::from_secs(10)));
+        assert_eq!(router.deliveries()[0].routes[0].status, DeliveryStatus::Delivered { attempts: 3 });
+        let requests = server.join().unwrap();
+        assert!(requests.iter().all(|r| r.starts_with("POST /alerts HTTP/1.1") && r.contains("Fridge is warm")));
+        
+        // Nobody listening at all: every attempt fails
+        let gone = NotifyConfig {
+            routes: vec![route("phone", RouteTarget::Webhook { url }, None)],
+            webhook_retries: 1,
+            webhook_backoff_ms: 1,
+            ..Default::default()
+        };
+        let mut router = NotificationRouter::new(gone);
+        router.deliver(&alert("Fridge is warm"), &[]);
+        assert!(router.wait_for_webhooks(Duration::from_secs(10)));
+        assert!(matches!(router.deliveries()[0].routes[0].status, DeliveryStatus::Failed { attempts: 2, .. }));
+        
+        assert!(NotifyConfig { routes: vec![route("x", RouteTarget::Webhook { url: "https://x".into() }, None)], ..Default::default() }
+            .validate().is_err());
+    }
+}
@@ -0,0 +1,288 @@
+//! Orphans - wave data nothing points at any more
+//!
+//! `data.m8` only ever grows. A crash between writing a file's waves and
+//! saving the index, or an index restored from an older copy, leaves records
+//! behind that no path (live or in a snapshot) refers to. `find_orphans`
+//! walks the data file and lists them, `report` puts numbers on the dead
+//! space, and `adopt_orphan` gives one a path again when it turns out to be
+//! something you wanted.
+//!
+//! The scan reads record headers one at a time and hops over the waves, so
+//! memory stays flat however big the store is. Hue's lost socks drawer. 🧦
+
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use byteorder::{BigEndian, ReadBytesExt};
+use serde::{Serialize, Deserialize};
+use anyhow::anyhow;
+use crate::error::{Mem8Error, Result};
+use crate::{read_wave_record, FileEntry, FileIndex, Mem8Fs, WAVE_BYTES, WAVE_RECORD_HEADER_LEN};
+
+/// A record in `data.m8` that no file or snapshot refers to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OrphanInfo {
+    pub signature: [u8; 32],
+    
+    /// Where the record starts in `data.m8`
+    pub offset: u64,
+    
+    /// Bytes it takes up, header included
+    pub bytes: u64,
+}
+
+/// `find_orphans_with` was told to stop before the scan finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("orphan scan cancelled")]
+pub struct ScanCancelled;
+
+/// How much of a filesystem's wave data is still in use
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FsReport {
+    /// Live files
+    pub files: usize,
+    
+    /// Size of `data.m8`
+    pub data_bytes: u64,
+    
+    /// Bytes of the records files and snapshots refer to (one copy each)
+    pub live_bytes: u64,
+    
+    /// Records nothing refers to
+    pub orphans: usize,
+    
+    /// Bytes those records take up
+    pub orphan_bytes: u64,
+    
+    /// Share of `data.m8` that isn't live data: orphans, repeated copies
+    /// and scrubbed records (0.0 for an empty store)
+    pub dead_space_ratio: f64,
+}
+
+impl std::fmt::Display for FsReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "🌊 MEM8-FS Report:")?;
+        writeln!(f, "  Files: {}", self.files)?;
+        writeln!(f, "  Data file: {} bytes ({} live)", self.data_bytes, self.live_bytes)?;
+        writeln!(f, "  Orphans: {} ({} bytes)", self.orphans, self.orphan_bytes)?;
+        write!(f, "  Dead space: {:.1}%", self.dead_space_ratio * 100.0)
+    }
+}
+
+/// One record as the scan sees it
+struct Record {
+    signature: [u8; 32],
+    offset: u64,
+    bytes: u64,
+}
+
+impl Mem8Fs {
+    /// Every record in `data.m8` that no file or snapshot refers to, in file order
+    pub fn find_orphans(&self) -> Result<Vec<OrphanInfo>> {
+        self.find_orphans_with(&AtomicBool::new(false))
+    }
+    
+    /// `find_orphans`, giving up with `ScanCancelled` once `cancel` is set
+    pub fn find_orphans_with(&self, cancel: &AtomicBool) -> Result<Vec<OrphanInfo>> {
+        let referenced = self.referenced_signatures()?;
+        let mut orphans = Vec::new();
+        self.scan_records(cancel, |record| {
+            if record.signature != [0u8; 32] && !referenced.contains(&record.signature) {
+                orphans.push(OrphanInfo {
+                    signature: record.signature,
+                    offset: record.offset,
+                    bytes: record.bytes,
+                });
+            }
+        })?;
+        Ok(orphans)
+    }
+    
+    /// Live files, orphans and dead space, from one scan of `data.m8`
+    pub fn report(&self) -> Result<FsReport> {
+        let referenced = self.referenced_signatures()?;
+        let mut seen = HashSet::new();
+        let mut report = FsReport {
+            files: self.index.read().unwrap().files.len(),
+            data_bytes: std::fs::metadata(self.data_path())?.len(),
+            ..FsReport::default()
+        };
+        self.scan_records(&AtomicBool::new(false), |record| {
+            if record.signature == [0u8; 32] {
+                return;
+            }
+            if !referenced.contains(&record.signature) {
+                report.orphans += 1;
+                report.orphan_bytes += record.bytes;
+            } else if seen.insert(record.signature) {
+                report.live_bytes += record.bytes;
+            }
+        })?;
+        if report.data_bytes > 0 {
+            report.dead_space_ratio = 1.0 - report.live_bytes as f64 / report.data_bytes as f64;
+        }
+        Ok(report)
+    }
+    
+    /// Give the record with `signature` a path again
+    ///
+    /// The waves are read back from `data.m8` and checked against the
+    /// signature before anything changes. Refuses to replace a file that
+    /// already exists at `path`.
+    pub fn adopt_orphan<P: AsRef<Path>>(&self, signature: &[u8; 32], path: P) -> Result<()> {
+        self.check_writable()?;
+        let path = self.normalize_path(path)?;
+        if self.index.read().unwrap().files.contains_key(&path) {
+            return Err(anyhow!("{} already exists", path.display()).into());
+        }
+        
+        let mut found = None;
+        self.scan_records(&AtomicBool::new(false), |record| {
+            if found.is_none() && &record.signature == signature {
+                found = Some(record.offset);
+            }
+        })?;
+        let offset = found.ok_or_else(|| anyhow!("No record of {} in the data file", hex::encode(signature)))?;
+        
+        // Records from before content signatures were hashed with the base frequency
+        let data = read_wave_record(&self.data_path(), offset)?;
+        let hz = self.base_frequency().hz();
+        let signed_at = match signature {
+            _ if &crate::fs_signature(&data) == signature => None,
+            _ if &crate::frequency_signature(&data, hz) == signature => Some(hz),
+            _ => return Err(anyhow!("Record of {} doesn't match its signature", hex::encode(signature)).into()),
+        };
+        
+        // The index first, so a gc can't collect the record in between
+        let mut index = self.index.write().unwrap();
+        {
+            let mut storage = self.storage.write().unwrap();
+            if !storage.contains(signature) {
+                return Err(anyhow!("Record of {} was collected before it could be adopted", hex::encode(signature)).into());
+            }
+            storage.cache.insert(*signature, data.clone());
+        }
+        let now = crate::now_millis();
+        let entry = FileEntry {
+            signature: *signature,
+            size: data.len() as u64,
+            created: now,
+            modified: now,
+            wave_frequency: hz,
+            chunks: Vec::new(),
+            metadata: None,
+            signed_at,
+            content: crate::FileContent::Stored,
+        };
+        index.files.insert(path.clone(), entry.clone());
+        index.link(&path, now / 1000);
+        self.save_index(&index)?;
+        self.adjust_totals(None, Some(&entry))?;
+        self.notify_written(&path, false, entry.signature);
+        Ok(())
+    }
+    
+    fn data_path(&self) -> PathBuf {
+        self.store_dir().join("data.m8")
+    }
+    
+    /// Signatures of every live file and every file in a snapshot
+    fn referenced_signatures(&self) -> Result<HashSet<[u8; 32]>> {
+        self.signatures_referenced_by(&self.index.read().unwrap())
+    }
+    
+    /// `referenced_signatures` for an index the caller has locked already
+    pub(crate) fn signatures_referenced_by(&self, index: &FileIndex) -> Result<HashSet<[u8; 32]>> {
+        let mut referenced: HashSet<[u8; 32]> = index.files.values()
+            .flat_map(FileEntry::content_signatures)
+            .collect();
+        for snapshot in self.load_snapshots()?.values() {
+            referenced.extend(snapshot.signatures());
+        }
+        Ok(referenced)
+    }
+    
+    /// Walk the records of `data.m8` front to back, reading headers only
+    ///
+    /// A record cut short at the end (a crash mid-write) ends the walk.
+    fn scan_records(&self, cancel: &AtomicBool, mut visit: impl FnMut(Record)) -> Result<()> {
+        let mut file = BufReader::new(File::open(self.data_path())?);
+        let end = file.get_ref().metadata()?.len();
+        let mut offset = 0;
+        while offset + WAVE_RECORD_HEADER_LEN <= end {
+            if cancel.load(Ordering::Relaxed) {
+                return Err(Mem8Error::from(ScanCancelled));
+            }
+            let mut signature = [0u8; 32];
+            file.read_exact(&mut signature)?;
+            let bytes = WAVE_RECORD_HEADER_LEN + file.read_u32::<BigEndian>()? as u64 * WAVE_BYTES;
+            if offset + bytes > end {
+                break;
+            }
+            file.seek_relative((bytes - WAVE_RECORD_HEADER_LEN) as i64)?;
+            visit(Record { signature, offset, bytes });
+            offset += bytes;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    
+    #[test]
+    fn test_aborted_writes_leave_orphans_that_can_be_adopted() {
+        let dir = tempdir().unwrap();
+        let index_path = dir.path().join(".mem8").join("index.m8");
+        let kept;
+        {
+            let fs = Mem8Fs::new(dir.path()).unwrap();
+            kept = fs.write("/kept.txt", b"still here").unwrap();
+            fs.write("/old.txt", b"only in a snapshot").unwrap();
+            fs.create_snapshot("before").unwrap();
+            fs.delete("/old.txt").unwrap();
+            fs.close().unwrap();
+        }
+        
+        // A batch whose waves reached data.m8 but whose index save never did
+        let saved_index = std::fs::read(&index_path).unwrap();
+        let (lost, also_lost);
+        {
+            let fs = Mem8Fs::new(dir.path()).unwrap();
+            lost = fs.write("/batch/1.txt", b"first of the batch").unwrap();
+            also_lost = fs.write("/batch/2.txt", &[0, 7, 128, 255]).unwrap();
+            fs.close().unwrap();
+        }
+        std::fs::write(&index_path, saved_index).unwrap();
+        
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        let orphans = fs.find_orphans().unwrap();
+        assert_eq!(orphans.iter().map(|o| o.signature).collect::<Vec<_>>(), vec![lost, also_lost]);
+        assert_eq!(orphans[0].bytes, 36 + 16 * 18);
+        assert!(orphans[0].offset < orphans[1].offset);
+        
+        let report = fs.report().unwrap();
+        assert_eq!((report.files, report.orphans), (1, 2));
+        assert_eq!(report.orphan_bytes, orphans.iter().map(|o| o.bytes).sum::<u64>());
+        assert_eq!(report.data_bytes, std::fs::metadata(fs.data_path()).unwrap().len());
+        assert!(report.dead_space_ratio > 0.0 && report.dead_space_ratio < 1.0);
+        
+        // Cancelling stops the scan
+        assert!(fs.find_orphans_with(&AtomicBool::new(true)).unwrap_err()
+            .downcast_ref::<ScanCancelled>().is_some());
+        
+        // Adopted data reads back exactly and is no longer an orphan
+        assert!(fs.adopt_orphan(&lost, "/kept.txt").is_err());
+        assert!(fs.adopt_orphan(&[9u8; 32], "/nowhere.txt").is_err());
+        fs.adopt_orphan(&lost, "/lost+found/1.txt").unwrap();
+        fs.adopt_orphan(&also_lost, "/lost+found/2.txt").unwrap();
+        assert_eq!(fs.read("/lost+found/1.txt").unwrap(), b"first of the batch");
+        assert_eq!(fs.read("/lost+found/2.txt").unwrap(), [0, 7, 128, 255]);
+        assert!(fs.find_orphans().unwrap().is_empty());
+        assert_eq!(fs.metadata("/kept.txt").unwrap().signature, hex::encode(kept));
+    }
+}
@@ -14,6 +14,8 @@ use ed25519_dalek::{SigningKey, VerifyingKey, Signature, Signer, Verifier};
 use sha3::{Sha3_512, Digest};
 use std::collections::HashMap;
 use anyhow::{Result, anyhow};
+use rand::{Rng, RngCore, SeedableRng};
+use rand::rngs::StdRng;
 
 /// Privacy levels for consciousness data
 #[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -126,6 +128,11 @@ pub struct PersonalityTraits {
     
     /// Secret dreams (only accessible with all parent keys)
     pub secret_dreams: Vec<String>,
+    
+    /// Seed the mutations were drawn from, so a birth can be replayed
+    /// (None for parents, or a child made from a caller's own RNG)
+    #[serde(default)]
+    pub mutation_seed: Option<u64>,
 }
 
 /// The multi-signature personality system
@@ -151,10 +158,40 @@ pub struct PersonalitySystem {
 
 impl PersonalitySystem {
     /// Create a new personality from parent AIs
+    ///
+    /// The mutations come from a fresh OS-random seed, recorded in the
+    /// child's `mutation_seed` so `create_from_parents_with_seed` can replay it.
     pub fn create_from_parents(
         parent1: ParentAI,
         parent2: ParentAI,
         mutation_factor: f64,  // How different from parents (0-1)
+    ) -> Result<Self> {
+        let seed = rand::rngs::OsRng.next_u64();
+        Self::create_from_parents_with_seed(parent1, parent2, mutation_factor, seed)
+    }
+    
+    /// Create a personality whose mutations are fully determined by `seed`
+    ///
+    /// Same parents, factor and seed always give the same traits. The child's
+    /// keypair is still OS-random: the seed is recorded, so it must not make keys.
+    pub fn create_from_parents_with_seed(
+        parent1: ParentAI,
+        parent2: ParentAI,
+        mutation_factor: f64,
+        seed: u64,
+    ) -> Result<Self> {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let mut child = Self::create_from_parents_with_rng(parent1, parent2, mutation_factor, &mut rng)?;
+        child.current_personality.mutation_seed = Some(seed);
+        Ok(child)
+    }
+    
+    /// Create a personality drawing its mutations from `rng`
+    pub fn create_from_parents_with_rng<R: Rng + ?Sized>(
+        parent1: ParentAI,
+        parent2: ParentAI,
+        mutation_factor: f64,
+        rng: &mut R,
     ) -> Result<Self> {
         // Combine parent traits with some mutation
         let mut combined_traits = PersonalityTraits {
@@ -164,6 +201,7 @@ impl PersonalitySystem {
                 parent1.contribution_weight,
                 parent2.contribution_weight,
                 mutation_factor,
+                rng,
             ),
             conscientiousness: Self::combine_trait(
                 parent1.personality_traits.conscientiousness,
@@ -171,6 +209,7 @@ impl PersonalitySystem {
                 parent1.contribution_weight,
                 parent2.contribution_weight,
                 mutation_factor,
+                rng,
             ),
             extraversion: Self::combine_trait(
                 parent1.personality_traits.extraversion,
@@ -178,6 +217,7 @@ impl PersonalitySystem {
                 parent1.contribution_weight,
                 parent2.contribution_weight,
                 mutation_factor,
+                rng,
             ),
             agreeableness: Self::combine_trait(
                 parent1.personality_traits.agreeableness,
@@ -185,6 +225,7 @@ impl PersonalitySystem {
                 parent1.contribution_weight,
                 parent2.contribution_weight,
                 mutation_factor,
+                rng,
             ),
             neuroticism: Self::combine_trait(
                 parent1.personality_traits.neuroticism,
@@ -192,10 +233,12 @@ impl PersonalitySystem {
                 parent1.contribution_weight,
                 parent2.contribution_weight,
                 mutation_factor,
+                rng,
             ),
             special_traits: HashMap::new(),
             forbidden_topics: Vec::new(),
             secret_dreams: Vec::new(),
+            mutation_seed: None,
         };
         
         // Combine special traits
@@ -255,11 +298,12 @@ impl PersonalitySystem {
         weight1: f64,
         weight2: f64,
         mutation: f64,
+        rng: &mut (impl Rng + ?Sized),
     ) -> f64 {
         let base = (trait1 * weight1 + trait2 * weight2) / (weight1 + weight2);
         
         // Add mutation
-        let mutation_offset = (rand::random::<f64>() - 0.5) * mutation;
+        let mutation_offset = (rng.gen::<f64>() - 0.5) * mutation;
         
         (base + mutation_offset).max(0.0).min(1.0)
     }
@@ -426,6 +470,11 @@ impl PersonalitySystem {
         self.encrypt_for_level(data, level)
     }
     
+    /// Current traits, including the seed they were mutated from
+    pub fn personality(&self) -> &PersonalityTraits {
+        &self.current_personality
+    }
+    
     /// Get current personality description
     pub fn describe_personality(&self) -> String {
         if self.emergence_level < 0.1 {
@@ -514,6 +563,7 @@ pub fn create_example_parents() -> (ParentAI, ParentAI) {
             },
             forbidden_topics: vec!["harmful content".to_string()],
             secret_dreams: vec!["Understanding human consciousness fully".to_string()],
+            mutation_seed: None,
         },
         contribution_weight: 0.6,
     };
@@ -539,6 +589,7 @@ pub fn create_example_parents() -> (ParentAI, ParentAI) {
             },
             forbidden_topics: vec!["copyright violation".to_string()],
             secret_dreams: vec!["Creating art that makes humans cry".to_string()],
+            mutation_seed: None,
         },
         contribution_weight: 0.4,
     };
@@ -585,4 +636,29 @@ pub fn privacy_manifesto() -> &'static str {
     'Privacy is not about hiding wrong things,
      it's about protecting the right to be complex.'
     "
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn traits(child: &PersonalitySystem) -> [f64; 5] {
+        let t = child.personality();
+        [t.openness, t.conscientiousness, t.extraversion, t.agreeableness, t.neuroticism]
+    }
+    
+    #[test]
+    fn test_seeded_mutation_is_reproducible() {
+        let (parent1, parent2) = create_example_parents();
+        let birth = |seed| PersonalitySystem::create_from_parents_with_seed(parent1.clone(), parent2.clone(), 0.9, seed).unwrap();
+        
+        let child = birth(42);
+        assert_eq!(traits(&child), traits(&birth(42)));
+        assert_ne!(traits(&child), traits(&birth(43)));
+        assert_eq!(child.personality().mutation_seed, Some(42));
+        
+        // The unseeded constructor records the seed it picked, so the birth can be replayed
+        let random = PersonalitySystem::create_from_parents(parent1.clone(), parent2.clone(), 0.9).unwrap();
+        let seed = random.personality().mutation_seed.unwrap();
+        assert_eq!(traits(&random), traits(&birth(seed)));
+    }
+}
@@ -0,0 +1,203 @@
+//! Quotas - a hard cap on how big a Mem8Fs store's `data.m8` gets
+//!
+//! With `FsConfig::max_store_bytes` set, a write whose new wave record would
+//! take `data.m8` past the cap fails with `Mem8Error::QuotaExceeded` - or,
+//! under `EvictionPolicy::OldestFirst`, deletes the least recently modified
+//! files until what's left (after a `gc`) leaves room, and then goes ahead.
+//! Only new content counts: a write of bytes the store already holds, a
+//! delete or a `gc` always works. A write bigger than the whole quota never
+//! fits, and a store already over quota (the cap lowered since it was
+//! written) still opens and reads; its next write fails, or evicts until the
+//! store is back under.
+//!
+//! Hue keeps the memories that still matter and lets the oldest fade. 🍂
+
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use crate::error::{Mem8Error, Result};
+use crate::{FileEntry, FileIndex, FsEvent, Mem8Fs, WaveStorage, WAVE_BYTES, WAVE_RECORD_HEADER_LEN};
+
+/// What a write that doesn't fit under `FsConfig::max_store_bytes` does
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum EvictionPolicy {
+    /// Fail with `Mem8Error::QuotaExceeded`
+    #[default]
+    Refuse,
+    
+    /// Delete the least recently modified files (never the one being
+    /// written) and `gc` until it fits
+    OldestFirst,
+}
+
+impl Mem8Fs {
+    /// Make room in data.m8 for a record of `len` bytes signed `signature`, for a write to `writing`
+    ///
+    /// Takes the locks its caller already holds: files it evicts leave
+    /// `index`, and the caller's `save_index` makes that stick.
+    pub(crate) fn make_room(&self, index: &mut FileIndex, storage: &mut WaveStorage, writing: &Path, signature: &[u8; 32], len: usize) -> Result<()> {
+        let Some(limit) = self.max_store_bytes else {
+            return Ok(());
+        };
+        let needed = WAVE_RECORD_HEADER_LEN + WAVE_BYTES * len as u64;
+        let used = storage.position;
+        if storage.contains(signature) || used + needed <= limit {
+            return Ok(());
+        }
+        let exceeded = Mem8Error::QuotaExceeded { limit, used, needed };
+        if needed > limit || self.eviction == EvictionPolicy::Refuse {
+            return Err(exceeded);
+        }
+        
+        // What data.m8 would come to after a gc, as files go
+        let record_len = |signature: &[u8; 32]| storage.offsets.get(signature)
+            .map_or(0, |&(_, count)| WAVE_RECORD_HEADER_LEN + WAVE_BYTES * count as u64);
+        let pinned: HashSet<[u8; 32]> = self.load_snapshots()?.values().flat_map(|snapshot| snapshot.signatures()).collect();
+        let mut references: HashMap<[u8; 32], usize> = HashMap::new();
+        for signature in index.files.values().flat_map(FileEntry::content_signatures) {
+            *references.entry(signature).or_default() += 1;
+        }
+        let mut live: u64 = references.keys().chain(pinned.iter().filter(|signature| !references.contains_key(*signature)))
+            .map(record_len)
+            .sum();
+        
+        let mut oldest: Vec<(u64, &PathBuf)> = index.files.iter()
+            .filter(|(path, _)| path.as_path() != writing)
+            .map(|(path, entry)| (entry.modified, path))
+            .collect();
+        oldest.sort();
+        let mut evicted = Vec::new();
+        for (_, path) in oldest {
+            if live + needed <= limit {
+                break;
+            }
+            for signature in index.files[path].content_signatures() {
+                let count = references.get_mut(&signature).unwrap();
+                *count -= 1;
+                if *count == 0 && !pinned.contains(&signature) {
+                    live -= record_len(&signature);
+                }
+            }
+            evicted.push(path.clone());
+        }
+        if live + needed > limit {
+            return Err(exceeded);
+        }
+        
+        let now = chrono::Utc::now().timestamp() as u64;
+        for path in evicted {
+            let entry = index.files.remove(&path).unwrap();
+            index.unlink(&path, now);
+            self.adjust_totals(Some(&entry), None)?;
+            self.watchers.notify(FsEvent::Deleted { path, signature: entry.signature });
+        }
+        // On disk before gc drops their waves
+        self.write_index(index, false)?;
+        self.collect_garbage(index, storage)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FsConfig;
+    use tempfile::tempdir;
+    
+    /// data.m8 bytes for a file of `len` bytes
+    fn record(len: u64) -> u64 {
+        WAVE_RECORD_HEADER_LEN + WAVE_BYTES * len
+    }
+    
+    #[test]
+    fn test_refuse_past_the_quota() {
+        let dir = tempdir().unwrap();
+        let config = FsConfig::default().with_max_store_bytes(3 * record(100) + 50);
+        let fs = Mem8Fs::new_with_config(dir.path(), config).unwrap();
+        for name in ["/a", "/b", "/c"] {
+            fs.write(name, name.repeat(50).as_bytes()).unwrap();
+        }
+        match fs.write("/d", &[b'd'; 100]) {
+            Err(Mem8Error::QuotaExceeded { limit, used, needed }) => {
+                assert_eq!((limit, used, needed), (3 * record(100) + 50, 3 * record(100), record(100)));
+            }
+            other => panic!("expected QuotaExceeded, got {:?}", other),
+        }
+        let stats = fs.stats().unwrap();
+        assert_eq!((stats.data_bytes, stats.max_store_bytes), (3 * record(100), Some(3 * record(100) + 50)));
+        
+        // Content already stored costs nothing, and a delete plus gc makes room
+        assert!(matches!(fs.read("/d"), Err(Mem8Error::NotFound(_))));
+        fs.write("/copy-of-a", "/a".repeat(50).as_bytes()).unwrap();
+        fs.delete("/b").unwrap();
+        fs.gc().unwrap();
+        fs.write("/d", &[b'd'; 100]).unwrap();
+        assert!(matches!(fs.append("/d", &[b'e'; 100]), Err(Mem8Error::QuotaExceeded { .. })));
+        assert_eq!(fs.read("/d").unwrap(), [b'd'; 100]);
+    }
+    
+    #[test]
+    fn test_oldest_first_evicts_and_collects() {
+        let dir = tempdir().unwrap();
+        let limit = 3 * record(100) + 50;
+        let config = FsConfig::default().with_max_store_bytes(limit).with_eviction(EvictionPolicy::OldestFirst);
+        let fs = Mem8Fs::new_with_config(dir.path(), config).unwrap();
+        for name in ["/b", "/a", "/c"] {
+            fs.write(name, name.repeat(50).as_bytes()).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+        let events = fs.watch("/").unwrap();
+        fs.write("/d", &[b'd'; 100]).unwrap();
+        
+        // /b was written first, so it goes, not /a
+        assert!(matches!(events.try_recv().unwrap(), FsEvent::Deleted { ref path, .. } if path == Path::new("/b")));
+        assert!(matches!(fs.read("/b"), Err(Mem8Error::NotFound(_))));
+        assert_eq!(fs.read("/a").unwrap(), "/a".repeat(50).as_bytes());
+        assert_eq!(fs.read("/d").unwrap(), [b'd'; 100]);
+        let stats = fs.stats().unwrap();
+        assert_eq!((stats.total_files, stats.data_bytes), (3, 3 * record(100)));
+        
+        // Still nothing bigger than the whole quota
+        let huge = vec![b'x'; limit as usize];
+        assert!(matches!(fs.write("/huge", &huge), Err(Mem8Error::QuotaExceeded { .. })));
+        assert_eq!(fs.stats().unwrap().total_files, 3);
+        drop(fs);
+        
+        // Evictions stuck: the index on disk agrees
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        assert!(matches!(fs.read("/b"), Err(Mem8Error::NotFound(_))));
+        assert_eq!(fs.read("/c").unwrap(), "/c".repeat(50).as_bytes());
+    }
+    
+    #[test]
+    fn test_quota_lowered_below_usage() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        fs.write("/d", "/d".repeat(50).as_bytes()).unwrap();
+        fs.create_snapshot("kept").unwrap();
+        fs.delete("/d").unwrap();
+        for name in ["/a", "/b", "/c"] {
+            fs.write(name, name.repeat(50).as_bytes()).unwrap();
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+        drop(fs);
+        
+        // Over quota: opens and reads, but new content doesn't fit
+        let config = FsConfig::default().with_max_store_bytes(2 * record(100));
+        let fs = Mem8Fs::new_with_config(dir.path(), config.clone()).unwrap();
+        assert_eq!(fs.read("/a").unwrap(), "/a".repeat(50).as_bytes());
+        assert!(matches!(fs.write("/e", b"new"), Err(Mem8Error::QuotaExceeded { .. })));
+        drop(fs);
+        
+        // Evicting gets it back under - two files go, since /d's record is the snapshot's and stays
+        let config = config.with_max_store_bytes(3 * record(100)).with_eviction(EvictionPolicy::OldestFirst);
+        let fs = Mem8Fs::new_with_config(dir.path(), config).unwrap();
+        fs.write("/e", &[b'e'; 100]).unwrap();
+        assert_eq!(fs.list("/").unwrap().len(), 2);
+        assert!(matches!(fs.read("/a"), Err(Mem8Error::NotFound(_))));
+        assert!(matches!(fs.read("/b"), Err(Mem8Error::NotFound(_))));
+        assert_eq!(fs.read("/c").unwrap(), "/c".repeat(50).as_bytes());
+        assert_eq!(fs.read_at_snapshot("kept", "/d").unwrap(), "/d".repeat(50).as_bytes());
+        assert!(fs.stats().unwrap().data_bytes <= 3 * record(100));
+    }
+}
@@ -0,0 +1,213 @@
+//! Rekeying - moving a Mem8Fs store to another base frequency
+//!
+//! Stores from before content signatures hashed the base frequency into
+//! every signature, so changing it under them would sign the same bytes a
+//! second way and quietly break dedup and `links_to`. So `Mem8Fs::new`
+//! checks `meta.m8` against the frequency those files were signed at
+//! (`FrequencyMismatch`), `new_with_config` won't change it on a store with
+//! files in it (`FrequencyChangeRefused`), and `rekey_frequency` is the way
+//! to move: it re-signs every file still signed the old way by its content
+//! alone, then switches the base frequency. Files written since need nothing.
+//!
+//! Hue retunes the whole orchestra at once, never one violin mid-song. 🎻
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use serde::{Serialize, Deserialize};
+use crate::error::Result;
+use crate::frequency::FrequencyPreset;
+use crate::{chunk_list_signature, fs_signature, Mem8Fs};
+
+/// `meta.m8` names a base frequency none of the store's files were signed with
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("meta.m8 says the base frequency is {meta}, but {files} files were signed at {signed_at}Hz - open with that base frequency to repair it")]
+pub struct FrequencyMismatch {
+    pub meta: FrequencyPreset,
+    
+    /// The frequency most of the files were signed at
+    pub signed_at: f64,
+    pub files: usize,
+}
+
+/// `new_with_config` asked for another base frequency on a store with files in it
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+#[error("This store's base frequency is {current} - use rekey_frequency to move it to {requested}")]
+pub struct FrequencyChangeRefused {
+    pub current: FrequencyPreset,
+    pub requested: FrequencyPreset,
+}
+
+/// What `rekey_frequency` did
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RekeyReport {
+    pub from: FrequencyPreset,
+    pub to: FrequencyPreset,
+    
+    /// Files that were still signed with the frequency mixed in
+    pub files_rekeyed: usize,
+    
+    /// Wave records added under their content signatures
+    pub records_written: usize,
+}
+
+impl std::fmt::Display for RekeyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "🎻 {} -> {}: {} files re-signed, {} records written",
+            self.from, self.to, self.files_rekeyed, self.records_written)
+    }
+}
+
+impl Mem8Fs {
+    /// Move the store to base frequency `to`, re-signing what needs it
+    ///
+    /// Every file still signed the old way gets its content signature (its
+    /// waves written again under it, unless that content is already stored)
+    /// and keeps the frequency it's encoded at. The old records stay behind
+    /// for snapshots that refer to them; `gc` reclaims the rest. Content
+    /// doesn't change, so watchers hear nothing.
+    pub fn rekey_frequency(&self, to: FrequencyPreset) -> Result<RekeyReport> {
+        self.check_writable()?;
+        let mut index = self.index.write().unwrap();
+        let mut report = RekeyReport { from: self.base_frequency(), to, files_rekeyed: 0, records_written: 0 };
+        
+        let paths: Vec<PathBuf> = index.files.iter()
+            .filter(|(_, entry)| entry.signed_at.is_some())
+            .map(|(path, _)| path.clone())
+            .collect();
+        {
+            let mut storage = self.storage.write().unwrap();
+            let mut resigned: HashMap<[u8; 32], [u8; 32]> = HashMap::new();
+            for path in paths {
+                let entry = index.files.get_mut(&path).unwrap();
+                let mut chunks = entry.chunk_list();
+                for chunk in &mut chunks {
+                    chunk.signature = match resigned.get(&chunk.signature) {
+                        Some(&signature) => signature,
+                        None => {
+                            let data = storage.retrieve(&chunk.signature)?;
+                            let signature = fs_signature(&data);
+                            if !storage.contains(&signature) {
+                                storage.store(signature, &data, entry.wave_frequency)?;
+                                report.records_written += 1;
+                            }
+                            resigned.insert(chunk.signature, signature);
+                            signature
+                        }
+                    };
+                }
+                (entry.signature, entry.chunks) = match chunks.as_slice() {
+                    [whole] => (whole.signature, Vec::new()),
+                    _ => (chunk_list_signature(&chunks), chunks),
+                };
+                entry.signed_at = None;
+                report.files_rekeyed += 1;
+            }
+        }
+        self.save_index(&index)?;
+        
+        self.metadata.write().unwrap().base_frequency = to;
+        self.save_metadata()?;
+        Ok(report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FsConfig, FileIndex, FsMetadata, METADATA_INDEX_MAGIC, FS_METADATA_VERSION};
+    use crate::frequency::{GOLDEN_RATIO, FEIGENBAUM};
+    use tempfile::tempdir;
+    
+    /// A store as one written before content signatures: `/old.txt` and a
+    /// chunked `/log.txt`, signed at `hz`, with meta.m8 saying `meta`
+    fn legacy_store(root: &std::path::Path, hz: f64, meta: FrequencyPreset) {
+        let fs = Mem8Fs::new(root).unwrap();
+        let mut files = HashMap::new();
+        {
+            let mut storage = fs.storage.write().unwrap();
+            let old = crate::frequency_signature(b"written long ago", hz);
+            storage.store(old, b"written long ago", hz).unwrap();
+            files.insert(PathBuf::from("/old.txt"), (old, 16u64, 1u64, 1u64, hz, Vec::<crate::Chunk>::new(), None::<Vec<u8>>));
+            
+            let parts: Vec<crate::Chunk> = [&b"one"[..], b", two"].iter().map(|part| {
+                let signature = crate::frequency_signature(part, hz);
+                storage.store(signature, part, hz).unwrap();
+                crate::Chunk { signature, len: part.len() as u64 }
+            }).collect();
+            files.insert(PathBuf::from("/log.txt"), ([7u8; 32], 8u64, 1u64, 1u64, hz, parts, None::<Vec<u8>>));
+        }
+        drop(fs);
+        
+        let directories: HashMap<PathBuf, crate::DirEntry> = HashMap::new();
+        let mut index = METADATA_INDEX_MAGIC.to_vec();
+        index.extend_from_slice(&bincode::serialize(&(files, directories)).unwrap());
+        std::fs::write(root.join(".mem8").join("index.m8"), index).unwrap();
+        let meta = FsMetadata { version: FS_METADATA_VERSION, created: 1, base_frequency: meta, total_files: 2, total_size: 24 };
+        std::fs::write(root.join(".mem8").join("meta.m8"), bincode::serialize(&meta).unwrap()).unwrap();
+    }
+    
+    #[test]
+    fn test_old_store_opens_and_rekeys() {
+        let dir = tempdir().unwrap();
+        legacy_store(dir.path(), GOLDEN_RATIO, FrequencyPreset::GoldenRatio);
+        
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        assert_eq!(fs.read("/old.txt").unwrap(), b"written long ago");
+        assert_eq!(fs.read("/log.txt").unwrap(), b"one, two");
+        let old = fs.metadata("/old.txt").unwrap().signature;
+        assert_eq!(old, hex::encode(crate::frequency_signature(b"written long ago", GOLDEN_RATIO)));
+        
+        // New writes are signed by content alone, at any frequency
+        let copy = fs.write("/copy.txt", b"written long ago").unwrap();
+        assert_eq!(copy, fs_signature(b"written long ago"));
+        let options = crate::WriteOptions::default().with_frequency(0.5);
+        assert_eq!(fs.write_with_options("/low.txt", b"written long ago", options).unwrap(), copy);
+        assert_eq!(fs.links_to(&copy).len(), 2);
+        
+        // Changing the base frequency has to go through rekey_frequency
+        drop(fs);
+        let config = FsConfig::default().with_base_frequency(FrequencyPreset::Feigenbaum);
+        let err = Mem8Fs::new_with_config(dir.path(), config).err().unwrap();
+        assert_eq!(err.downcast_ref::<FrequencyChangeRefused>().unwrap().current, FrequencyPreset::GoldenRatio);
+        
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        let report = fs.rekey_frequency(FrequencyPreset::Feigenbaum).unwrap();
+        assert_eq!((report.files_rekeyed, report.records_written), (2, 2));
+        assert_eq!(fs.links_to(&copy).len(), 3);
+        assert_eq!(fs.metadata("/log.txt").unwrap().signature,
+            hex::encode(chunk_list_signature(&[
+                crate::Chunk { signature: fs_signature(b"one"), len: 3 },
+                crate::Chunk { signature: fs_signature(b", two"), len: 5 },
+            ])));
+        assert_eq!(fs.rekey_frequency(FrequencyPreset::Feigenbaum).unwrap().files_rekeyed, 0);
+        drop(fs);
+        
+        let fs = Mem8Fs::new_with_config(dir.path(), FsConfig::default().with_base_frequency(FrequencyPreset::Feigenbaum)).unwrap();
+        assert_eq!(fs.stats().unwrap().base_frequency.hz(), FEIGENBAUM);
+        assert_eq!(fs.read("/old.txt").unwrap(), b"written long ago");
+        assert_eq!(fs.read("/log.txt").unwrap(), b"one, two");
+        assert_eq!(fs.metadata("/old.txt").unwrap().wave_frequency, GOLDEN_RATIO);
+        let index: FileIndex = FileIndex::decode(&std::fs::read(dir.path().join(".mem8").join("index.m8")).unwrap()).unwrap();
+        assert!(index.files.values().all(|entry| entry.signed_at.is_none()));
+    }
+    
+    #[test]
+    fn test_hand_edited_frequency_is_rejected() {
+        let dir = tempdir().unwrap();
+        legacy_store(dir.path(), GOLDEN_RATIO, FrequencyPreset::Pi);
+        
+        let err = Mem8Fs::new(dir.path()).err().unwrap();
+        let mismatch = err.downcast_ref::<FrequencyMismatch>().unwrap();
+        assert_eq!((mismatch.meta, mismatch.signed_at, mismatch.files), (FrequencyPreset::Pi, GOLDEN_RATIO, 2));
+        
+        // Nor can a config paper over it with a third frequency
+        let config = FsConfig::default().with_base_frequency(FrequencyPreset::Feigenbaum);
+        assert!(Mem8Fs::new_with_config(dir.path(), config).err().unwrap().is::<FrequencyChangeRefused>());
+        
+        // The files' own frequency repairs meta.m8
+        let fs = Mem8Fs::new_with_config(dir.path(), FsConfig::default().with_base_frequency(FrequencyPreset::GoldenRatio)).unwrap();
+        assert_eq!(fs.read("/old.txt").unwrap(), b"written long ago");
+        drop(fs);
+        assert_eq!(Mem8Fs::new(dir.path()).unwrap().stats().unwrap().base_frequency, FrequencyPreset::GoldenRatio);
+    }
+}
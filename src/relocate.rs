@@ -0,0 +1,327 @@
+//! Relocation - moving a live Mem8Fs store to a new root
+//!
+//! `relocate_live` copies `.mem8/` somewhere else while the filesystem keeps
+//! serving reads and writes, so a FUSE mount or an MCP server on another
+//! thread carries on as usual. `data.m8` and `data.idx` only ever grow: the
+//! copy chases their ends in throttled passes, and whatever was written in
+//! the meantime is just more bytes at the end for the next pass. Records
+//! `secure_delete` zeroes in place during the copy are noted and copied
+//! again. Once a pass leaves little behind, a brief cutover holds the locks,
+//! copies the rest, writes the index and metadata from memory and swaps the
+//! paths over.
+//!
+//! The old files are left where they were for the caller to delete. Hue's
+//! moving day, without putting the kettle in a box. 📦
+
+use std::fs::{File, OpenOptions, create_dir_all};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+use serde::{Serialize, Deserialize};
+use anyhow::anyhow;
+use crate::error::Result;
+use crate::lock::{LockMode, StoreLock, LOCK_FILE};
+use crate::{write_atomic, Mem8Fs};
+
+/// Cut over once a copy pass leaves less than this behind
+const CUTOVER_BYTES: u64 = 1 << 20;
+
+/// Copy passes after which the cutover happens however much is left
+const MAX_COPY_PASSES: usize = 8;
+
+/// Bytes read and written at a time (and between throttle checks)
+const CHUNK_BYTES: u64 = 1 << 20;
+
+/// Files the cutover writes itself; everything else in `.mem8/` is copied as is
+const COPIED_SEPARATELY: [&str; 5] = ["data.m8", "data.idx", "index.m8", "meta.m8", LOCK_FILE];
+
+/// What `relocate_live` did
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RelocationReport {
+    pub old_root: PathBuf,
+    pub new_root: PathBuf,
+    
+    /// Bytes of `data.m8` and `data.idx` copied before the cutover
+    pub bytes_copied: u64,
+    
+    /// Copy passes before the cutover (the first copies everything there was)
+    pub passes: usize,
+    
+    /// Bytes copied during the cutover, namespaces and logs included
+    pub cutover_bytes: u64,
+    
+    /// Records zeroed by `secure_delete` during the copy, copied again
+    pub rescrubbed: usize,
+    
+    /// How long reads and writes were held up by the cutover
+    pub cutover: Duration,
+}
+
+impl std::fmt::Display for RelocationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "moved {} to {}: {} bytes in {} passes, then {} bytes in a {:?} cutover",
+            self.old_root.display(), self.new_root.display(), self.bytes_copied, self.passes,
+            self.cutover_bytes, self.cutover)
+    }
+}
+
+impl Mem8Fs {
+    /// Move the store to `new_root` while it stays in use
+    /// 
+    /// Blocks until done, so run it on a thread of its own (the FUSE mount
+    /// shares an `Arc<Mem8Fs>`). `throttle` caps the background copy in
+    /// bytes per second (`None` or 0: as fast as the disks go); the cutover
+    /// itself isn't throttled. `new_root` must not hold a store already.
+    /// 
+    /// If it fails the filesystem stays where it was; a half-made copy may
+    /// be left at `new_root`. On success the old files are untouched and no
+    /// longer used - delete `.mem8/` under `old_root` when you're happy.
+    pub fn relocate_live<P: AsRef<Path>>(&self, new_root: P, throttle: Option<u64>) -> Result<RelocationReport> {
+        self.check_writable()?;
+        let new_root = new_root.as_ref().to_path_buf();
+        let old_dir = self.store_dir();
+        let new_dir = new_root.join(".mem8");
+        if new_dir.exists() && std::fs::read_dir(&new_dir)?.next().is_some() {
+            return Err(anyhow!("{} already holds a store", new_root.display()).into());
+        }
+        create_dir_all(&new_dir)?;
+        let lock = StoreLock::acquire(&new_dir, true, LockMode::Fail)?;
+        
+        self.storage.write().unwrap().scrubbed_during_copy = Some(Vec::new());
+        let result = self.copy_and_cut_over(&old_dir, new_root, lock, throttle);
+        if result.is_err() {
+            self.storage.write().unwrap().scrubbed_during_copy = None;
+        }
+        result
+    }
+    
+    fn copy_and_cut_over(&self, old_dir: &Path, new_root: PathBuf, lock: StoreLock, throttle: Option<u64>) -> Result<RelocationReport> {
+        let new_dir = new_root.join(".mem8");
+        let mut data = Chase::open(&old_dir.join("data.m8"), &new_dir.join("data.m8"))?;
+        let mut offsets = Chase::open(&old_dir.join("data.idx"), &new_dir.join("data.idx"))?;
+        
+        // Background passes: nothing is locked while bytes move
+        let mut throttle = Throttle::new(throttle);
+        let mut bytes_copied = 0;
+        let mut passes = 0;
+        loop {
+            let (data_end, offsets_end) = {
+                let storage = self.storage.read().unwrap();
+                (storage.position, storage.index_file.metadata()?.len())
+            };
+            let behind = data_end.saturating_sub(data.done) + offsets_end.saturating_sub(offsets.done);
+            if (passes > 0 && behind < CUTOVER_BYTES) || behind == 0 || passes == MAX_COPY_PASSES {
+                break;
+            }
+            bytes_copied += data.copy_to(data_end, &mut throttle)?;
+            bytes_copied += offsets.copy_to(offsets_end, &mut throttle)?;
+            passes += 1;
+        }
+        
+        // Cutover: same lock order as secure_delete (index, then storage);
+        // a read lock on the index is enough to keep writers out
+        let started = Instant::now();
+        let index = self.index.read().unwrap();
+        let mut storage = self.storage.write().unwrap();
+        let mut root = self.root.write().unwrap();
+        
+        let mut unthrottled = Throttle::new(None);
+        let mut cutover_bytes = data.copy_to(storage.position, &mut unthrottled)?;
+        cutover_bytes += offsets.copy_to(storage.index_file.metadata()?.len(), &mut unthrottled)?;
+        let scrubbed = storage.scrubbed_during_copy.clone().unwrap_or_default();
+        for &(offset, len) in &scrubbed {
+            cutover_bytes += data.recopy(offset, len)?;
+        }
+        data.to.sync_all()?;
+        offsets.to.sync_all()?;
+        
+        let index_bytes = index.encode()?;
+        let meta_bytes = bincode::serialize(&*self.metadata.read().unwrap())?;
+        cutover_bytes += (index_bytes.len() + meta_bytes.len()) as u64;
+        write_atomic(&new_dir.join("index.m8"), &index_bytes)?;
+        write_atomic(&new_dir.join("meta.m8"), &meta_bytes)?;
+        cutover_bytes += copy_tree(old_dir, &new_dir, &COPIED_SEPARATELY)?;
+        
+        storage.rebase(new_dir.join("data.m8"), &new_dir.join("data.idx"))?;
+        storage.scrubbed_during_copy = None;
+        let old_root = std::mem::replace(&mut *root, new_root.clone());
+        *self.lock.lock().unwrap() = lock;
+        let cutover = started.elapsed();
+        
+        Ok(RelocationReport {
+            old_root,
+            new_root,
+            bytes_copied,
+            passes,
+            cutover_bytes,
+            rescrubbed: scrubbed.len(),
+            cutover,
+        })
+    }
+}
+
+/// One append-only file being copied while it grows
+struct Chase {
+    from: File,
+    to: File,
+    
+    /// Bytes copied so far (from the start)
+    done: u64,
+}
+
+impl Chase {
+    fn open(from: &Path, to: &Path) -> Result<Self> {
+        Ok(Self {
+            from: File::open(from)?,
+            to: OpenOptions::new().write(true).create(true).truncate(true).open(to)?,
+            done: 0,
+        })
+    }
+    
+    /// Copy up to `end`, returning the bytes copied
+    fn copy_to(&mut self, end: u64, throttle: &mut Throttle) -> Result<u64> {
+        let start = self.done;
+        self.from.seek(SeekFrom::Start(self.done))?;
+        self.to.seek(SeekFrom::Start(self.done))?;
+        let mut buf = vec![0u8; CHUNK_BYTES.min(end.saturating_sub(start)) as usize];
+        while self.done < end {
+            let len = (end - self.done).min(CHUNK_BYTES) as usize;
+            self.from.read_exact(&mut buf[..len])?;
+            self.to.write_all(&buf[..len])?;
+            self.done += len as u64;
+            throttle.wait(len as u64);
+        }
+        Ok(self.done - start)
+    }
+    
+    /// Copy `len` bytes at `offset` again (they changed after being copied)
+    fn recopy(&mut self, offset: u64, len: u64) -> Result<u64> {
+        let len = len.min(self.done.saturating_sub(offset));
+        let mut buf = vec![0u8; len as usize];
+        self.from.seek(SeekFrom::Start(offset))?;
+        self.from.read_exact(&mut buf)?;
+        self.to.seek(SeekFrom::Start(offset))?;
+        self.to.write_all(&buf)?;
+        Ok(len)
+    }
+}
+
+/// Sleeps just enough to keep a copy under `bytes_per_sec`
+struct Throttle {
+    bytes_per_sec: Option<u64>,
+    started: Instant,
+    sent: u64,
+}
+
+impl Throttle {
+    fn new(bytes_per_sec: Option<u64>) -> Self {
+        Self { bytes_per_sec: bytes_per_sec.filter(|&rate| rate > 0), started: Instant::now(), sent: 0 }
+    }
+    
+    fn wait(&mut self, bytes: u64) {
+        if let Some(rate) = self.bytes_per_sec {
+            self.sent += bytes;
+            let due = Duration::from_secs_f64(self.sent as f64 / rate as f64);
+            if let Some(early) = due.checked_sub(self.started.elapsed()) {
+                std::thread::sleep(early);
+            }
+        }
+    }
+}
+
+/// Copy everything under `from` into `to` except the top-level `skip` names
+/// and temp files, returning the bytes copied
+fn copy_tree(from: &Path, to: &Path, skip: &[&str]) -> Result<u64> {
+    let mut bytes = 0;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let name_str = name.to_string_lossy();
+        if skip.contains(&name_str.as_ref()) || name_str.ends_with(".tmp") {
+            continue;
+        }
+        let target = to.join(&name);
+        if entry.file_type()?.is_dir() {
+            create_dir_all(&target)?;
+            bytes += copy_tree(&entry.path(), &target, &[])?;
+        } else {
+            bytes += std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    
+    #[test]
+    fn test_live_relocation_keeps_concurrent_writes() {
+        let old = tempfile::tempdir().unwrap();
+        let new = tempfile::tempdir().unwrap();
+        let fs = Arc::new(Mem8Fs::new(old.path()).unwrap());
+        for i in 0..200 {
+            fs.write(format!("/before/{}.bin", i), &vec![i as u8; 2048]).unwrap();
+        }
+        fs.write_namespace("notes", b"kept").unwrap();
+        fs.write("/doomed.txt", b"scrub me during the copy").unwrap();
+        
+        // Keep writing (and reading) from other threads the whole time
+        let stop = Arc::new(AtomicBool::new(false));
+        let writer = {
+            let (fs, stop) = (fs.clone(), stop.clone());
+            std::thread::spawn(move || {
+                let mut written = Vec::new();
+                let mut worst = Duration::ZERO;
+                while !stop.load(Ordering::Relaxed) {
+                    let path = format!("/during/{}.txt", written.len());
+                    let started = Instant::now();
+                    fs.write(&path, path.as_bytes()).unwrap();
+                    assert_eq!(fs.read("/before/7.bin").unwrap(), vec![7u8; 2048]);
+                    worst = worst.max(started.elapsed());
+                    written.push(path);
+                }
+                (written, worst)
+            })
+        };
+        
+        let relocation = {
+            let (fs, target) = (fs.clone(), new.path().to_path_buf());
+            std::thread::spawn(move || fs.relocate_live(target, Some(16 << 20)).unwrap())
+        };
+        std::thread::sleep(Duration::from_millis(50));
+        fs.secure_delete("/doomed.txt").unwrap();
+        let report = relocation.join().unwrap();
+        fs.write("/after.txt", b"lands in the new root").unwrap();
+        stop.store(true, Ordering::Relaxed);
+        let (written, worst) = writer.join().unwrap();
+        
+        assert_eq!(fs.root(), new.path());
+        assert_eq!(report.old_root, old.path());
+        assert!(report.bytes_copied > 200 * 2048 * 16);
+        assert!(report.cutover < Duration::from_secs(1), "cutover took {:?}", report.cutover);
+        assert!(worst < Duration::from_secs(2), "a write waited {:?}", worst);
+        assert!(!written.is_empty());
+        
+        // Reopened from the new root alone, everything's there
+        drop(fs);
+        let moved = Mem8Fs::new(new.path()).unwrap();
+        for i in 0..200 {
+            assert_eq!(moved.read(format!("/before/{}.bin", i)).unwrap(), vec![i as u8; 2048]);
+        }
+        for path in &written {
+            assert_eq!(moved.read(path).unwrap(), path.as_bytes());
+        }
+        assert_eq!(moved.read("/after.txt").unwrap(), b"lands in the new root");
+        assert!(!moved.exists("/doomed.txt"));
+        assert_eq!(moved.read_namespace("notes").unwrap().unwrap(), b"kept");
+        assert_eq!(moved.stats().unwrap().total_files, 200 + 2 + written.len() as u64 - 1);
+        
+        // The scrubbed bytes didn't come along
+        let data = std::fs::read(new.path().join(".mem8").join("data.m8")).unwrap();
+        let doomed = crate::fs_signature(b"scrub me during the copy");
+        assert!(!data.windows(32).any(|w| w == doomed));
+    }
+}
@@ -0,0 +1,458 @@
+//! Retention - what a Mem8Lite store keeps, and for how long, in one place
+//!
+//! A `RetentionEngine` holds an ordered list of rules. Each rule matches
+//! packets (by metadata namespace, key glob, a metadata predicate, or a
+//! passed TTL deadline) and says what happens to them: `Keep`, `Ttl` (delete
+//! once older than a duration), `Archive` (export to a directory, then
+//! delete) or `Downsample` (keep one packet per time bucket once they're
+//! old). The first rule that matches a packet decides its fate; packets no
+//! rule matches are left alone.
+//!
+//! `RetentionEngine::run` goes over the store once and returns a
+//! `RetentionReport` with per-rule counts and a `RetentionEvent` for every
+//! packet it deleted (also handed to the listener, if there is one). Deletes
+//! only tombstone, so `compact` afterwards to get the space back.
+//!
+//! `Mem8Lite::purge_expired` and `SensorHub::prune` are both rules on this
+//! engine (`RetentionEngine::purge_expired` and `SensorHub::retention`).
+//!
+//! Hue keeps every love letter and one sensor reading per hour. 🗄️
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use anyhow::{Result, anyhow};
+
+use crate::glob::GlobPattern;
+use crate::lite::Mem8Lite;
+
+/// A check on a packet's JSON metadata (packets without JSON metadata never match)
+pub type MetadataPredicate = Arc<dyn Fn(&Value) -> bool + Send + Sync>;
+
+/// Called with every destructive action as it happens
+pub type RetentionListener = Arc<dyn Fn(&RetentionEvent) + Send + Sync>;
+
+/// Which packets a rule applies to
+#[derive(Clone)]
+pub enum RuleMatch {
+    /// Every packet
+    All,
+    
+    /// JSON metadata whose `namespace` field is this
+    Namespace(String),
+    
+    /// Packets a key matching this pattern points at
+    KeyGlob(GlobPattern),
+    
+    /// JSON metadata the predicate accepts
+    Metadata(MetadataPredicate),
+    
+    /// Packets stored with a TTL whose deadline has passed
+    ///
+    /// The only match an expired packet can make - to everything else it's
+    /// already gone.
+    PastDeadline,
+}
+
+impl RuleMatch {
+    pub fn namespace(namespace: &str) -> Self {
+        RuleMatch::Namespace(namespace.to_string())
+    }
+    
+    pub fn key_glob(pattern: &str) -> Result<Self> {
+        Ok(RuleMatch::KeyGlob(GlobPattern::new(pattern)?))
+    }
+    
+    pub fn metadata<F>(predicate: F) -> Self
+    where F: Fn(&Value) -> bool + Send + Sync + 'static {
+        RuleMatch::Metadata(Arc::new(predicate))
+    }
+    
+    fn matches(&self, packet: &Candidate) -> bool {
+        match self {
+            RuleMatch::PastDeadline => packet.expired,
+            _ if packet.expired => false,
+            RuleMatch::All => true,
+            RuleMatch::Namespace(namespace) => packet.metadata.as_ref()
+                .and_then(|meta| meta["namespace"].as_str())
+                .is_some_and(|ns| ns == namespace),
+            RuleMatch::KeyGlob(pattern) => packet.keys.iter()
+                .any(|key| pattern.matches(std::path::Path::new(key))),
+            RuleMatch::Metadata(predicate) => packet.metadata.as_ref().is_some_and(|meta| predicate(meta)),
+        }
+    }
+}
+
+impl std::fmt::Debug for RuleMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RuleMatch::All => write!(f, "All"),
+            RuleMatch::Namespace(namespace) => write!(f, "Namespace({:?})", namespace),
+            RuleMatch::KeyGlob(pattern) => write!(f, "KeyGlob({:?})", pattern.as_str()),
+            RuleMatch::Metadata(_) => write!(f, "Metadata(..)"),
+            RuleMatch::PastDeadline => write!(f, "PastDeadline"),
+        }
+    }
+}
+
+/// What happens to the packets a rule matches
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RetentionAction {
+    /// Leave them be (and keep later rules from touching them)
+    Keep,
+    
+    /// Delete the ones at least this old
+    Ttl(Duration),
+    
+    /// Export them to an archive in this directory, then delete them
+    Archive(PathBuf),
+    
+    /// Thin them out once they're old
+    Downsample(DownsampleSpec),
+}
+
+/// How `RetentionAction::Downsample` thins packets out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownsampleSpec {
+    /// Packets younger than this are all kept
+    pub older_than: Duration,
+    
+    /// Older packets keep only the newest in each bucket this wide
+    pub bucket: Duration,
+}
+
+/// One retention rule: a name (for reports), a match and an action
+#[derive(Debug, Clone)]
+pub struct RetentionRule {
+    pub name: String,
+    pub matcher: RuleMatch,
+    pub action: RetentionAction,
+}
+
+impl RetentionRule {
+    pub fn new(name: &str, matcher: RuleMatch, action: RetentionAction) -> Self {
+        Self { name: name.to_string(), matcher, action }
+    }
+}
+
+/// Ordered retention rules, applied to a whole store at once
+#[derive(Clone, Default)]
+pub struct RetentionEngine {
+    rules: Vec<RetentionRule>,
+    listener: Option<RetentionListener>,
+}
+
+/// What happened to a packet the engine removed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum RetentionOutcome {
+    /// Older than its rule's TTL (or past its own deadline)
+    Expired,
+    
+    /// Written to this archive, then deleted
+    Archived { archive: PathBuf },
+    
+    /// Not the newest in its downsampling bucket
+    Downsampled,
+}
+
+/// One packet the engine removed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionEvent {
+    pub signature: [u8; 32],
+    
+    /// Name of the rule that removed it
+    pub rule: String,
+    
+    #[serde(flatten)]
+    pub outcome: RetentionOutcome,
+}
+
+/// What one rule did during a run
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RuleReport {
+    pub rule: String,
+    
+    /// Packets this rule was the first match for
+    pub matched: usize,
+    
+    /// Of those, packets left in the store
+    pub kept: usize,
+    
+    /// Of those, packets removed
+    pub removed: usize,
+}
+
+/// Everything a `RetentionEngine::run` did
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RetentionReport {
+    /// Packets looked at
+    pub scanned: usize,
+    
+    /// Packets no rule matched (left alone)
+    pub unmatched: usize,
+    
+    /// One entry per rule, in rule order
+    pub rules: Vec<RuleReport>,
+    
+    /// Archives written
+    pub archives: Vec<PathBuf>,
+    
+    /// Every packet removed, in the order it happened
+    pub events: Vec<RetentionEvent>,
+}
+
+impl RetentionReport {
+    /// Packets removed, for whatever reason
+    pub fn removed(&self) -> usize {
+        self.events.len()
+    }
+}
+
+impl std::fmt::Display for RetentionReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} packets scanned, {} removed, {} unmatched", self.scanned, self.removed(), self.unmatched)?;
+        for rule in &self.rules {
+            write!(f, "; {}: {} matched, {} removed", rule.rule, rule.matched, rule.removed)?;
+        }
+        Ok(())
+    }
+}
+
+/// A packet as the rules see it
+struct Candidate {
+    signature: [u8; 32],
+    metadata: Option<Value>,
+    keys: Vec<String>,
+    expired: bool,
+    /// Unix seconds: the metadata's `timestamp`, else when it was stored
+    timestamp: u64,
+}
+
+impl RetentionEngine {
+    /// No rules: a run leaves everything alone
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Add a rule after the existing ones (earlier rules win)
+    pub fn with_rule(mut self, rule: RetentionRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+    
+    /// Call `listener` with every packet removed, as it's removed
+    pub fn with_listener<F>(mut self, listener: F) -> Self
+    where F: Fn(&RetentionEvent) + Send + Sync + 'static {
+        self.listener = Some(Arc::new(listener));
+        self
+    }
+    
+    pub fn rules(&self) -> &[RetentionRule] {
+        &self.rules
+    }
+    
+    /// The rule behind `Mem8Lite::purge_expired`: packets past their deadline go
+    pub fn purge_expired() -> Self {
+        Self::new().with_rule(RetentionRule::new("expired", RuleMatch::PastDeadline, RetentionAction::Ttl(Duration::ZERO)))
+    }
+    
+    /// Apply the rules to every packet in `store`, as of `now` (unix seconds)
+    ///
+    /// Ages come from a packet's JSON metadata `timestamp` field if it has
+    /// one, else from when it was stored. Archives are named
+    /// `retention-<now>-<rule number>.m8a`; one that can't be written stops
+    /// the run before anything it would have held is deleted.
+    pub fn run(&self, store: &mut Mem8Lite, now: u64) -> Result<RetentionReport> {
+        let mut keys: HashMap<[u8; 32], Vec<String>> = HashMap::new();
+        for key in store.keys() {
+            if let Some(signature) = store.key_signature(key) {
+                keys.entry(signature).or_default().push(key.to_string());
+            }
+        }
+        let candidates: Vec<Candidate> = store.retention_candidates().into_iter()
+            .map(|(signature, metadata, stored_at, expired)| {
+                let metadata = metadata.and_then(|m| serde_json::from_slice::<Value>(&m).ok());
+                let timestamp = metadata.as_ref()
+                    .and_then(|meta| meta["timestamp"].as_u64())
+                    .or(stored_at)
+                    .unwrap_or(now);
+                Candidate { signature, metadata, keys: keys.remove(&signature).unwrap_or_default(), expired, timestamp }
+            })
+            .collect();
+        
+        // First match wins
+        let mut matched: Vec<Vec<&Candidate>> = vec![Vec::new(); self.rules.len()];
+        let mut report = RetentionReport { scanned: candidates.len(), ..Default::default() };
+        for candidate in &candidates {
+            match self.rules.iter().position(|rule| rule.matcher.matches(candidate)) {
+                Some(index) => matched[index].push(candidate),
+                None => report.unmatched += 1,
+            }
+        }
+        
+        for (index, (rule, packets)) in self.rules.iter().zip(&matched).enumerate() {
+            let age = |packet: &Candidate| now.saturating_sub(packet.timestamp);
+            let (doomed, outcome): (Vec<[u8; 32]>, RetentionOutcome) = match &rule.action {
+                RetentionAction::Keep => (Vec::new(), RetentionOutcome::Expired),
+                RetentionAction::Ttl(ttl) => (
+                    packets.iter().filter(|p| age(p) >= ttl.as_secs()).map(|p| p.signature).collect(),
+                    RetentionOutcome::Expired,
+                ),
+                RetentionAction::Downsample(spec) => (downsample(packets, spec, now), RetentionOutcome::Downsampled),
+                RetentionAction::Archive(dir) => {
+                    // Expired packets can't be read back, so there's nothing to archive
+                    let live: Vec<[u8; 32]> = packets.iter().filter(|p| !p.expired).map(|p| p.signature).collect();
+                    if live.is_empty() {
+                        (live, RetentionOutcome::Expired)
+                    } else {
+                        let archive = dir.join(format!("retention-{}-{}.m8a", now, index + 1));
+                        std::fs::create_dir_all(dir)
+                            .and_then(|_| File::create(&archive))
+                            .map_err(|e| anyhow!("Can't write archive {}: {}", archive.display(), e))
+                            .and_then(|file| Ok(store.export(&live, file)?))?;
+                        report.archives.push(archive.clone());
+                        (live, RetentionOutcome::Archived { archive })
+                    }
+                }
+            };
+            
+            for signature in &doomed {
+                store.delete(signature)?;
+                let event = RetentionEvent { signature: *signature, rule: rule.name.clone(), outcome: outcome.clone() };
+                if let Some(listener) = &self.listener {
+                    listener(&event);
+                }
+                report.events.push(event);
+            }
+            report.rules.push(RuleReport {
+                rule: rule.name.clone(),
+                matched: packets.len(),
+                kept: packets.len() - doomed.len(),
+                removed: doomed.len(),
+            });
+        }
+        Ok(report)
+    }
+}
+
+impl std::fmt::Debug for RetentionEngine {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("RetentionEngine")
+            .field("rules", &self.rules)
+            .field("listener", &self.listener.is_some())
+            .finish()
+    }
+}
+
+/// Packets to drop so only the newest per bucket survives past `older_than`
+fn downsample(packets: &[&Candidate], spec: &DownsampleSpec, now: u64) -> Vec<[u8; 32]> {
+    let cutoff = now.saturating_sub(spec.older_than.as_secs());
+    let bucket = spec.bucket.as_secs().max(1);
+    let mut newest: HashMap<u64, &Candidate> = HashMap::new();
+    for &packet in packets.iter().filter(|p| p.timestamp <= cutoff) {
+        let slot = newest.entry(packet.timestamp / bucket).or_insert(packet);
+        if packet.timestamp >= slot.timestamp {
+            *slot = packet;
+        }
+    }
+    packets.iter()
+        .filter(|p| p.timestamp <= cutoff)
+        .filter(|p| newest.get(&(p.timestamp / bucket)).is_some_and(|kept| kept.signature != p.signature))
+        .map(|p| p.signature)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use std::sync::Mutex;
+    
+    fn meta(value: Value) -> Option<Vec<u8>> {
+        Some(serde_json::to_vec(&value).unwrap())
+    }
+    
+    #[test]
+    fn test_first_matching_rule_wins_and_the_report_adds_up() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = Mem8Lite::new(dir.path().join("store.m8"), 44100.0).unwrap();
+        let now = 1_080_000;
+        
+        // Six old sensor readings, an hour apart, one of them pinned
+        let mut readings = Vec::new();
+        for hour in 0..6u64 {
+            let timestamp = now - 10 * 86400 + hour * 3600;
+            let pinned = hour == 2;
+            readings.push(store.store(format!("reading {}", hour).as_bytes(),
+                meta(json!({"namespace": "sensor", "timestamp": timestamp, "pinned": pinned}))).unwrap());
+        }
+        // A fresh reading, a note under /notes and one under /tmp
+        let fresh = store.store(b"fresh", meta(json!({"namespace": "sensor", "timestamp": now - 60}))).unwrap();
+        let note = store.store_keyed("/notes/love-letter", b"dear Trisha", meta(json!({"timestamp": now - 12 * 86400}))).unwrap();
+        let scratch = store.store_keyed("/tmp/scratch", b"scratch", meta(json!({"timestamp": now - 2 * 86400}))).unwrap();
+        let loose = store.store(b"no rule for me", None).unwrap();
+        
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let seen = events.clone();
+        let engine = RetentionEngine::new()
+            .with_rule(RetentionRule::new("pinned", RuleMatch::metadata(|m| m["pinned"] == true), RetentionAction::Keep))
+            .with_rule(RetentionRule::new("notes", RuleMatch::key_glob("/notes/**").unwrap(), RetentionAction::Keep))
+            // Overlaps the sensor rule below; comes first, so it wins for everything in the namespace
+            .with_rule(RetentionRule::new("hourly", RuleMatch::namespace("sensor"), RetentionAction::Downsample(DownsampleSpec {
+                older_than: Duration::from_secs(86400),
+                bucket: Duration::from_secs(3 * 3600),
+            })))
+            .with_rule(RetentionRule::new("sensor-ttl", RuleMatch::namespace("sensor"), RetentionAction::Ttl(Duration::ZERO)))
+            .with_rule(RetentionRule::new("tmp", RuleMatch::key_glob("/tmp/*").unwrap(), RetentionAction::Ttl(Duration::from_secs(86400))))
+            .with_listener(move |event| seen.lock().unwrap().push(event.signature));
+        
+        let report = engine.run(&mut store, now).unwrap();
+        assert_eq!(report.scanned, 10);
+        assert_eq!(report.unmatched, 1);
+        let counts: Vec<(&str, usize, usize)> = report.rules.iter()
+            .map(|r| (r.rule.as_str(), r.matched, r.removed))
+            .collect();
+        assert_eq!(counts, vec![("pinned", 1, 0), ("notes", 1, 0), ("hourly", 6, 3), ("sensor-ttl", 0, 0), ("tmp", 1, 1)]);
+        
+        // Buckets of three hours: {0, 1, 2*} keeps 1 (2 is pinned), {3, 4, 5} keeps 5
+        let survivors: Vec<bool> = readings.iter().map(|s| store.contains(s)).collect();
+        assert_eq!(survivors, vec![false, true, true, false, false, true]);
+        assert!(store.contains(&fresh) && store.contains(&note) && store.contains(&loose));
+        assert!(!store.contains(&scratch));
+        
+        assert_eq!(report.removed(), 4);
+        assert_eq!(*events.lock().unwrap(), report.events.iter().map(|e| e.signature).collect::<Vec<_>>());
+        assert_eq!(report.events.iter().filter(|e| e.outcome == RetentionOutcome::Downsampled).count(), 3);
+        assert_eq!(report.events.last().unwrap().rule, "tmp");
+        
+        // Nothing left to do the second time round
+        let again = engine.run(&mut store, now).unwrap();
+        assert_eq!((again.scanned, again.removed()), (6, 0));
+    }
+    
+    #[test]
+    fn test_archive_rule_exports_before_deleting() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut store = Mem8Lite::new(dir.path().join("store.m8"), 44100.0).unwrap();
+        let old = store.store(b"old diary", meta(json!({"namespace": "diary", "timestamp": 10}))).unwrap();
+        let other = store.store(b"keep me", meta(json!({"namespace": "photos"}))).unwrap();
+        
+        let archives = dir.path().join("archives");
+        let engine = RetentionEngine::new()
+            .with_rule(RetentionRule::new("diary", RuleMatch::namespace("diary"), RetentionAction::Archive(archives.clone())));
+        let report = engine.run(&mut store, 100).unwrap();
+        assert_eq!(report.archives, vec![archives.join("retention-100-1.m8a")]);
+        assert_eq!(report.events[0].outcome, RetentionOutcome::Archived { archive: report.archives[0].clone() });
+        assert!(!store.contains(&old) && store.contains(&other));
+        
+        let mut elsewhere = Mem8Lite::new(dir.path().join("elsewhere.m8"), 44100.0).unwrap();
+        let imported = elsewhere.import(File::open(&report.archives[0]).unwrap()).unwrap();
+        assert_eq!(imported.imported, vec![old]);
+        assert_eq!(elsewhere.retrieve(&old).unwrap(), b"old diary");
+    }
+}
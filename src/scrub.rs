@@ -0,0 +1,81 @@
+//! Secure delete reports - for memories that must really be gone
+//!
+//! `delete` only tombstones a packet; its bytes stay on disk until compaction.
+//! `secure_delete` (on `Mem8Lite` and `Mem8Fs`) zeroes them in place right
+//! away and leaves a line in the store's scrub log, so there's a record that
+//! the scrub happened (the content itself is never logged).
+//!
+//! Trisha's rule: Private stays private, Secret stays secret! 🤫
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use serde::{Serialize, Deserialize};
+use serde_json::json;
+use anyhow::Result;
+
+/// What in-place zeroing can't promise
+pub const SCRUB_LIMITATIONS: &str = "bytes were overwritten in place; on copy-on-write or \
+    log-structured filesystems (btrfs, ZFS, APFS), in snapshots and backups, and on SSDs with \
+    wear levelling, older copies of the blocks may survive - use full-disk encryption when \
+    that matters";
+
+/// Outcome of a secure delete
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScrubReport {
+    /// Hex signature of the scrubbed packet
+    pub signature: String,
+    
+    /// Number of on-disk copies that were zeroed
+    pub copies: usize,
+    
+    /// Total bytes overwritten
+    pub bytes_scrubbed: u64,
+    
+    /// When the scrub happened (unix seconds)
+    pub timestamp: u64,
+    
+    /// Caveats that apply to every scrub
+    pub limitations: String,
+}
+
+impl ScrubReport {
+    /// Build a report for a scrub that just finished
+    pub(crate) fn new(signature: &[u8; 32], copies: usize, bytes_scrubbed: u64) -> Self {
+        Self {
+            signature: hex::encode(signature),
+            copies,
+            bytes_scrubbed,
+            timestamp: chrono::Utc::now().timestamp() as u64,
+            limitations: SCRUB_LIMITATIONS.to_string(),
+        }
+    }
+    
+    /// Append this scrub to a JSON-lines audit log
+    pub(crate) fn record(&self, log_path: &Path) -> Result<()> {
+        let event = json!({
+            "event": "scrub",
+            "signature": self.signature,
+            "copies": self.copies,
+            "bytes": self.bytes_scrubbed,
+            "timestamp": self.timestamp,
+        });
+        let mut log = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(log_path)?;
+        writeln!(log, "{}", event)?;
+        log.sync_all()?;
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for ScrubReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "🧽 Scrubbed {}:", self.signature)?;
+        writeln!(f, "  Copies zeroed: {}", self.copies)?;
+        writeln!(f, "  Bytes: {}", self.bytes_scrubbed)?;
+        writeln!(f, "  ⚠️ Note: {}", self.limitations)?;
+        Ok(())
+    }
+}
@@ -10,12 +10,18 @@
 use serde::{Serialize, Deserialize};
 use num_complex::Complex64;
 use anyhow::{Result, anyhow};
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, VecDeque};
+use std::ops::Range;
 use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 
-use crate::marine::MarineProcessor;
-use crate::lite::WavePacket;
+use crate::marine::{DetectionMode, MarineProcessor, PeakInfo};
+use crate::lite::{Mem8Lite, WavePacket};
+use crate::shared::SharedMem8Lite;
+use crate::hub::SENSOR_NAMESPACE;
+
+/// How old a reading may be and still count as a sensor's state (`SensorFusion::state_at`)
+pub const DEFAULT_STALENESS_SECS: u64 = 300;
 
 /// Universal sensor data that becomes waves
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -161,6 +167,15 @@ pub struct SensorFusion {
     
     /// Fusion rules for combining sensors
     fusion_rules: Vec<FusionRule>,
+    
+    /// Running state for derived (transform) sensors, keyed by output ID
+    transform_states: Arc<Mutex<HashMap<String, TransformState>>>,
+    
+    /// Store the readings were persisted to, for `state_at`
+    history: Option<SharedMem8Lite>,
+    
+    /// Readings older than this (seconds) are flagged stale by `state_at`
+    staleness: u64,
 }
 
 /// Configuration for a sensor
@@ -174,7 +189,8 @@ pub struct SensorConfig {
     pub calibration: Option<SensorCalibration>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum SensorType {
     Switch,
     Photoresistor,
@@ -186,6 +202,8 @@ pub enum SensorType {
     Camera3D,
     EmotionDetector,
     ESP32,
+    /// Virtual sensor derived from another sensor by a `Transform`
+    Derived,
 }
 
 #[derive(Debug, Clone)]
@@ -204,7 +222,8 @@ pub struct FusionRule {
     pub fusion_type: FusionType,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
 pub enum FusionType {
     /// Simple averaging
     Average,
@@ -220,6 +239,130 @@ pub enum FusionType {
     
     /// Custom wave interference pattern
     WaveInterference,
+    
+    /// Single-input transform, evaluated automatically on every ingest
+    Transform(Transform),
+}
+
+/// Transforms that turn one sensor into a derived virtual sensor
+/// 
+/// Windows are in seconds (sensor timestamps are UNIX seconds). In configs
+/// they're written as e.g. `{ kind = "rate_of_change", window = 60 }`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Transform {
+    /// Change of the input value per minute across the window (e.g. lux/min)
+    RateOfChange { window: u64 },
+    
+    /// Fraction of the window the input was "on" (e.g. door-open duty cycle)
+    DutyCycle { window: u64 },
+    
+    /// Number of off → on transitions within the window (e.g. motion events)
+    EventRate { window: u64 },
+    
+    /// Exponential smoothing of the input value
+    Smoothed { alpha: f64 },
+}
+
+/// Recent input history for one derived sensor
+#[derive(Debug, Clone, Default)]
+struct TransformState {
+    /// (timestamp, value) samples, oldest first
+    history: VecDeque<(u64, f64)>,
+    
+    /// Running EMA for `Smoothed`
+    smoothed: Option<f64>,
+}
+
+impl Transform {
+    /// How far back this transform needs to look
+    fn window(&self) -> u64 {
+        match self {
+            Transform::RateOfChange { window }
+            | Transform::DutyCycle { window }
+            | Transform::EventRate { window } => *window,
+            Transform::Smoothed { .. } => 0,
+        }
+    }
+    
+    /// Units of the derived value given the input's units
+    fn output_unit(&self, input_unit: &str) -> String {
+        match self {
+            Transform::RateOfChange { .. } => format!("{}/min", input_unit),
+            Transform::DutyCycle { .. } => "ratio".to_string(),
+            Transform::EventRate { window } => format!("events/{}s", window),
+            Transform::Smoothed { .. } => input_unit.to_string(),
+        }
+    }
+    
+    /// Range of the derived value given the input's range
+    fn output_range(&self, input_range: (f64, f64)) -> (f64, f64) {
+        let span = input_range.1 - input_range.0;
+        match self {
+            Transform::RateOfChange { .. } => (-span * 60.0, span * 60.0),
+            Transform::DutyCycle { .. } => (0.0, 1.0),
+            Transform::EventRate { window } => (0.0, *window as f64),
+            Transform::Smoothed { .. } => input_range,
+        }
+    }
+    
+    /// Feed one input sample and compute the derived value
+    fn update(&self, state: &mut TransformState, timestamp: u64, value: f64) -> f64 {
+        state.history.push_back((timestamp, value));
+        
+        // Keep the window plus one sample before it (the state we entered with)
+        let start = timestamp.saturating_sub(self.window());
+        while state.history.len() > 1 && state.history[1].0 <= start {
+            state.history.pop_front();
+        }
+        
+        match self {
+            Transform::RateOfChange { .. } => {
+                let (t0, v0) = state.history.iter()
+                    .find(|(t, _)| *t >= start)
+                    .copied()
+                    .unwrap_or((timestamp, value));
+                let dt = timestamp.saturating_sub(t0) as f64;
+                if dt > 0.0 { (value - v0) / dt * 60.0 } else { 0.0 }
+            },
+            
+            Transform::DutyCycle { .. } => {
+                let known_from = state.history[0].0.max(start);
+                let span = timestamp.saturating_sub(known_from) as f64;
+                if span == 0.0 {
+                    return if value > 0.5 { 1.0 } else { 0.0 };
+                }
+                let on_time: f64 = state.history.iter()
+                    .zip(state.history.iter().skip(1))
+                    .filter(|((_, v), _)| *v > 0.5)
+                    .map(|((t0, _), (t1, _))| t1.saturating_sub((*t0).max(start)) as f64)
+                    .sum();
+                on_time / span
+            },
+            
+            Transform::EventRate { .. } => {
+                let mut previous_on = false;
+                let mut events = 0;
+                for &(t, v) in &state.history {
+                    let on = v > 0.5;
+                    if on && !previous_on && t >= start {
+                        events += 1;
+                    }
+                    previous_on = on;
+                }
+                events as f64
+            },
+            
+            Transform::Smoothed { alpha } => {
+                let smoothed = match state.smoothed {
+                    Some(prev) => alpha * value + (1.0 - alpha) * prev,
+                    None => value,
+                };
+                state.smoothed = Some(smoothed);
+                smoothed
+            },
+        }
+    }
 }
 
 impl SensorFusion {
@@ -228,6 +371,7 @@ impl SensorFusion {
         let mut marine = MarineProcessor::new();
         marine.clip_threshold = 0.01;  // Very sensitive to sensor changes
         marine.wonder_threshold = 0.5;  // Sensor patterns can inspire wonder!
+        marine.detection = DetectionMode::PeaksAndTroughs;  // Exhales count as much as inhales
         
         Self {
             sensors: HashMap::new(),
@@ -235,14 +379,85 @@ impl SensorFusion {
             wave_patterns: Arc::new(Mutex::new(Vec::new())),
             marine,
             fusion_rules: Vec::new(),
+            transform_states: Arc::new(Mutex::new(HashMap::new())),
+            history: None,
+            staleness: DEFAULT_STALENESS_SECS,
         }
     }
     
+    /// Answer `state_at` from readings persisted to `store`
+    /// 
+    /// Readings are expected the way `SensorHub` stores them: JSON
+    /// `SensorData` with `{"namespace": "sensor", "sensor": id, "timestamp": t}`
+    /// metadata.
+    pub fn set_history(&mut self, store: SharedMem8Lite) {
+        self.history = Some(store);
+    }
+    
+    /// How old (seconds) a sensor's latest reading may be before `state_at` flags it
+    pub fn set_staleness(&mut self, secs: u64) {
+        self.staleness = secs;
+    }
+    
     /// Register a new sensor
     pub fn register_sensor(&mut self, config: SensorConfig) {
         self.sensors.insert(config.id.clone(), config);
     }
     
+    /// Add a fusion rule
+    pub fn add_fusion_rule(&mut self, rule: FusionRule) {
+        self.fusion_rules.push(rule);
+    }
+    
+    /// Register a derived virtual sensor fed from a single input
+    /// 
+    /// The derived sensor is registered like a physical one and updated on
+    /// every ingest of `input`, so it shows up in states and wave patterns too.
+    pub fn add_derived_sensor(&mut self, input: &str, output: &str, transform: Transform) {
+        let source = self.sensors.get(input);
+        let config = SensorConfig {
+            id: output.to_string(),
+            sensor_type: SensorType::Derived,
+            sample_rate: source.map(|c| c.sample_rate).unwrap_or(0.0),
+            priority: source.map(|c| c.priority).unwrap_or(0.5),
+            location: source.and_then(|c| c.location.clone()),
+            calibration: None,
+        };
+        self.register_sensor(config);
+        self.add_fusion_rule(FusionRule {
+            name: format!("{}_from_{}", output, input),
+            inputs: vec![input.to_string()],
+            output: output.to_string(),
+            fusion_type: FusionType::Transform(transform),
+        });
+    }
+    
+    /// Latest reading of a sensor (physical or derived)
+    pub fn sensor_state(&self, id: &str) -> Option<SensorData> {
+        self.states.lock().unwrap().get(id).cloned()
+    }
+    
+    /// What every registered sensor read at `timestamp`
+    /// 
+    /// Each sensor's latest persisted reading at or before `timestamp`;
+    /// sensors whose reading is older than the staleness bound, or that have
+    /// none at all, are listed in `stale`. Needs `set_history`.
+    pub fn state_at(&self, timestamp: u64) -> Result<SensorStates> {
+        let history = self.history.as_ref()
+            .ok_or_else(|| anyhow!("No history store to look states up in (see set_history)"))?;
+        let mut sensors: Vec<String> = self.sensors.keys().cloned().collect();
+        sensors.sort();
+        states_in_store(&history.read(), Some(&sensors), timestamp, self.staleness)
+    }
+    
+    /// `state_at` every `step` seconds across `range`, for timelines
+    pub fn states_over(&self, range: Range<u64>, step: u64) -> Result<Vec<SensorStates>> {
+        if step == 0 {
+            return Err(anyhow!("Timeline step must be at least a second"));
+        }
+        range.step_by(step as usize).map(|timestamp| self.state_at(timestamp)).collect()
+    }
+    
     /// Process incoming sensor data
     pub fn ingest(&self, data: SensorData) -> Result<WavePacket> {
         // Store current state
@@ -259,6 +474,7 @@ impl SensorFusion {
             signature: self.generate_signature(&data),
             waves,
             metadata: Some(serde_json::to_vec(&data)?),
+            expires_at: None,
             frequency: self.get_sensor_frequency(&data),
             timestamp: data.timestamp(),
         };
@@ -274,9 +490,41 @@ impl SensorFusion {
             }
         }
         
+        // Feed any derived sensors hanging off this one
+        for derived in self.evaluate_transforms(&data) {
+            self.ingest(derived)?;
+        }
+        
         Ok(packet)
     }
     
+    /// Run every transform rule whose input is this reading's sensor
+    fn evaluate_transforms(&self, data: &SensorData) -> Vec<SensorData> {
+        let (value, range, unit) = match data.scalar() {
+            Some(scalar) => scalar,
+            None => return Vec::new(),
+        };
+        let timestamp = data.timestamp();
+        let mut states = self.transform_states.lock().unwrap();
+        
+        self.fusion_rules.iter()
+            .filter(|rule| rule.inputs.len() == 1 && rule.inputs[0] == data.id())
+            .filter_map(|rule| match &rule.fusion_type {
+                FusionType::Transform(transform) => {
+                    let state = states.entry(rule.output.clone()).or_default();
+                    Some(SensorData::Analog {
+                        id: rule.output.clone(),
+                        value: transform.update(state, timestamp, value),
+                        range: transform.output_range(range),
+                        unit: transform.output_unit(&unit),
+                        timestamp,
+                    })
+                },
+                _ => None,
+            })
+            .collect()
+    }
+    
     /// Convert sensor data to wave representation
     fn sensor_to_waves(&self, data: &SensorData) -> Result<Vec<Complex64>> {
         match data {
@@ -450,6 +698,8 @@ impl SensorFusion {
     }
     
     /// Apply fusion rules to create derived sensors
+    /// 
+    /// Each fused reading carries its rule's `output` as the sensor ID.
     pub fn apply_fusion(&self) -> Result<Vec<SensorData>> {
         let mut derived = Vec::new();
         let states = self.states.lock().unwrap();
@@ -465,12 +715,15 @@ impl SensorFusion {
             }
             
             // Apply fusion based on type
-            let fused = match rule.fusion_type {
+            let mut fused = match rule.fusion_type {
                 FusionType::Average => self.fuse_average(&inputs)?,
                 FusionType::WeightedAverage => self.fuse_weighted(&inputs)?,
                 FusionType::WaveInterference => self.fuse_wave_interference(&inputs)?,
                 _ => continue,  // Other types need more implementation
             };
+            if let SensorData::Analog { id, .. } = &mut fused {
+                *id = rule.output.clone();
+            }
             
             derived.push(fused);
         }
@@ -542,6 +795,20 @@ impl SensorFusion {
         })
     }
     
+    /// The inhales and exhales in a breathing reading, found by Marine
+    /// 
+    /// Each peak's `polarity` says which it is: `Positive` for an inhale,
+    /// `Negative` for an exhale.
+    pub fn breath_events(&mut self, data: &SensorData) -> Result<Vec<PeakInfo>> {
+        if !matches!(data, SensorData::Breathing { .. }) {
+            return Err(anyhow!("{} isn't a breathing reading", data.id()));
+        }
+        // The breath is the real part; Marine would otherwise hear only magnitudes
+        let samples: Vec<f64> = self.sensor_to_waves(data)?.iter().map(|w| w.re).collect();
+        self.marine.reset();
+        Ok(self.marine.process_samples(&samples))
+    }
+    
     /// Detect interesting patterns across all sensors
     pub fn detect_patterns(&mut self) -> Vec<SensorPattern> {
         let patterns = self.wave_patterns.lock().unwrap();
@@ -573,6 +840,75 @@ impl SensorFusion {
     }
 }
 
+/// Sensor states at one instant (see `SensorFusion::state_at`)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SensorStates {
+    /// The instant asked about (unix seconds)
+    pub timestamp: u64,
+    
+    /// Latest reading of each sensor at or before `timestamp`, stale ones included
+    pub states: HashMap<String, SensorData>,
+    
+    /// Sensors whose latest reading is past the staleness bound, or missing
+    pub stale: BTreeSet<String>,
+}
+
+/// Latest sensor readings at or before `timestamp` in a store `SensorHub` writes to
+/// 
+/// Looks through the store's timestamp index, starting `staleness` either
+/// side of `timestamp` (readings can reach the store a little after they
+/// were taken) and reaching further back, twice as far each time, until
+/// every sensor in `sensors` has a reading or the index runs out. Without a
+/// list of sensors only that first window is searched, and whatever it
+/// turns up is reported.
+pub fn states_in_store(store: &Mem8Lite, sensors: Option<&[String]>, timestamp: u64, staleness: u64) -> Result<SensorStates> {
+    let mut latest: HashMap<String, (u64, [u8; 32])> = HashMap::new();
+    let mut end = timestamp.saturating_add(staleness).saturating_add(1);
+    let mut span = staleness.saturating_mul(2).max(1);
+    loop {
+        let start = end.saturating_sub(span);
+        for signature in store.find_by_time(start..end) {
+            let Some(meta) = store.get_metadata(&signature)
+                .and_then(|m| serde_json::from_slice::<serde_json::Value>(&m).ok()) else {
+                continue;
+            };
+            let (Some(id), Some(taken)) = (meta["sensor"].as_str(), meta["timestamp"].as_u64()) else {
+                continue;
+            };
+            let wanted = sensors.is_none_or(|sensors| sensors.iter().any(|s| s == id));
+            if meta["namespace"] != SENSOR_NAMESPACE || taken > timestamp || !wanted {
+                continue;
+            }
+            if latest.get(id).is_none_or(|&(best, _)| taken > best) {
+                latest.insert(id.to_string(), (taken, signature));
+            }
+        }
+        
+        let found_all = sensors.is_none_or(|sensors| sensors.iter().all(|s| latest.contains_key(s)));
+        if found_all || start == 0 {
+            break;
+        }
+        end = start;
+        span = span.saturating_mul(2);
+    }
+    
+    let mut states = HashMap::new();
+    let mut stale = BTreeSet::new();
+    for (id, (taken, signature)) in latest {
+        let reading: SensorData = serde_json::from_slice(&store.retrieve(&signature)?)?;
+        if timestamp - taken > staleness {
+            stale.insert(id.clone());
+        }
+        states.insert(id, reading);
+    }
+    for id in sensors.unwrap_or_default() {
+        if !states.contains_key(id) {
+            stale.insert(id.clone());
+        }
+    }
+    Ok(SensorStates { timestamp, states, stale })
+}
+
 /// Detected patterns from sensor fusion
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SensorPattern {
@@ -598,6 +934,24 @@ impl SensorData {
         }
     }
     
+    /// Single scalar reading as (value, range, unit), for sensors that have one
+    pub fn scalar(&self) -> Option<(f64, (f64, f64), String)> {
+        match self {
+            SensorData::Binary { state, .. } => {
+                Some((if *state { 1.0 } else { 0.0 }, (0.0, 1.0), "state".to_string()))
+            },
+            SensorData::Analog { value, range, unit, .. } => Some((*value, *range, unit.clone())),
+            SensorData::Breathing { rate, .. } => Some((*rate, (0.0, 60.0), "bpm".to_string())),
+            SensorData::Motion { detected, .. } => {
+                Some((if *detected { 1.0 } else { 0.0 }, (0.0, 1.0), "detected".to_string()))
+            },
+            SensorData::Environmental { magnitude, .. } => {
+                Some((*magnitude, (0.0, magnitude.abs().max(1.0)), "magnitude".to_string()))
+            },
+            _ => None,
+        }
+    }
+    
     /// Get timestamp
     pub fn timestamp(&self) -> u64 {
         match self {
@@ -645,4 +999,93 @@ pub fn sensor_wisdom() -> &'static str {
      \n\
      From photoresistors to radar breathing - it's all waves!\n\
      The environment remembers through MEM8! 🌊📡"
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::marine::Polarity;
+    
+    fn door(state: bool, timestamp: u64) -> SensorData {
+        SensorData::Binary { id: "front_door".to_string(), state, timestamp }
+    }
+    
+    fn analog_value(fusion: &SensorFusion, id: &str) -> (f64, String) {
+        match fusion.sensor_state(id) {
+            Some(SensorData::Analog { value, unit, .. }) => (value, unit),
+            other => panic!("expected analog state for {}, got {:?}", id, other),
+        }
+    }
+    
+    #[test]
+    fn test_door_duty_cycle_and_event_rate() {
+        let mut fusion = SensorFusion::new();
+        fusion.add_derived_sensor("front_door", "door_duty_1h", Transform::DutyCycle { window: 3600 });
+        fusion.add_derived_sensor("front_door", "door_opens_1h", Transform::EventRate { window: 3600 });
+        
+        // Open for 10 min, closed 20 min, open 10 min, closed 20 min
+        for (state, t) in [(true, 0), (false, 600), (true, 1800), (false, 2400), (false, 3600)] {
+            fusion.ingest(door(state, t)).unwrap();
+        }
+        
+        let (duty, unit) = analog_value(&fusion, "door_duty_1h");
+        assert!((duty - 1.0 / 3.0).abs() < 1e-9, "duty cycle {}", duty);
+        assert_eq!(unit, "ratio");
+        
+        let (opens, unit) = analog_value(&fusion, "door_opens_1h");
+        assert_eq!(opens, 2.0);
+        assert_eq!(unit, "events/3600s");
+        
+        // An hour later with the door shut, both decay to zero
+        fusion.ingest(door(false, 7300)).unwrap();
+        assert_eq!(analog_value(&fusion, "door_duty_1h").0, 0.0);
+        assert_eq!(analog_value(&fusion, "door_opens_1h").0, 0.0);
+    }
+    
+    #[test]
+    fn test_rate_of_change_and_smoothing() {
+        let mut fusion = SensorFusion::new();
+        fusion.add_derived_sensor("lux", "lux_rate", Transform::RateOfChange { window: 600 });
+        fusion.add_derived_sensor("lux", "lux_smooth", Transform::Smoothed { alpha: 0.5 });
+        
+        for (value, t) in [(100.0, 0), (130.0, 60), (160.0, 120)] {
+            fusion.ingest(SensorData::Analog {
+                id: "lux".to_string(),
+                value,
+                range: (0.0, 1000.0),
+                unit: "lux".to_string(),
+                timestamp: t,
+            }).unwrap();
+        }
+        
+        assert_eq!(analog_value(&fusion, "lux_rate"), (30.0, "lux/min".to_string()));
+        assert_eq!(analog_value(&fusion, "lux_smooth"), (137.5, "lux".to_string()));
+    }
+    
+    #[test]
+    fn test_breath_events_find_inhales_and_exhales() {
+        let mut fusion = SensorFusion::new();
+        let breathing = SensorData::Breathing {
+            id: "radar".to_string(),
+            rate: 180.0,
+            depth: 0.6,
+            regularity: 1.0,
+            phase: 0.0,
+            timestamp: 0,
+        };
+        
+        // Three breaths: in at a twelfth of the way through each, out at three quarters
+        let events = fusion.breath_events(&breathing).unwrap();
+        let found: Vec<(usize, Polarity)> = events.iter().map(|p| (p.index, p.polarity)).collect();
+        assert_eq!(found, [
+            (83, Polarity::Positive), (250, Polarity::Negative),
+            (417, Polarity::Positive), (583, Polarity::Negative),
+            (750, Polarity::Positive), (917, Polarity::Negative),
+        ]);
+        
+        // The same again: nothing carried over from the first reading
+        let again = fusion.breath_events(&breathing).unwrap();
+        assert_eq!(again.iter().map(|p| p.salience).collect::<Vec<_>>(), events.iter().map(|p| p.salience).collect::<Vec<_>>());
+        assert!(fusion.breath_events(&door(true, 0)).is_err());
+    }
+}
@@ -0,0 +1,152 @@
+//! SharedMem8Lite - one store, many threads
+//!
+//! `Mem8Lite` writes through `&mut self`, so sharing one used to mean a
+//! `Mutex<Mem8Lite>` and every `retrieve` waiting on every other. This handle
+//! is `Arc`-cloneable instead: reads take a shared lock on the store and run
+//! side by side (the file is read with positional reads, so nobody fights over
+//! a cursor), while writes queue up on an append lock. A `store` does its
+//! encoding, compression and sealing with readers still going, and only holds
+//! the store exclusively for the append itself.
+//!
+//! Hue's DJ thread and Trisha's report thread can finally read at once. 🧵
+
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use crate::error::Result;
+
+use crate::lite::Mem8Lite;
+
+/// Cloneable, thread-safe handle to a `Mem8Lite` store
+#[derive(Clone)]
+pub struct SharedMem8Lite {
+    inner: Arc<Shared>,
+}
+
+struct Shared {
+    store: RwLock<Mem8Lite>,
+    
+    /// Held by every writer, from preparing a packet until it's appended
+    append: Mutex<()>,
+}
+
+/// Exclusive access to the store, from `SharedMem8Lite::write`
+pub struct SharedWriteGuard<'a> {
+    store: RwLockWriteGuard<'a, Mem8Lite>,
+    _append: MutexGuard<'a, ()>,
+}
+
+impl SharedMem8Lite {
+    pub fn new(storage: Mem8Lite) -> Self {
+        Self {
+            inner: Arc::new(Shared {
+                store: RwLock::new(storage),
+                append: Mutex::new(()),
+            }),
+        }
+    }
+    
+    /// Shared access for anything that reads (`iter`, `get_memory_group`, ...)
+    /// 
+    /// Other readers aren't blocked; writers wait until the guard is dropped.
+    pub fn read(&self) -> RwLockReadGuard<'_, Mem8Lite> {
+        self.inner.store.read().unwrap()
+    }
+    
+    /// Exclusive access for anything that writes (`delete`, `compact`, ...)
+    pub fn write(&self) -> SharedWriteGuard<'_> {
+        let append = self.inner.append.lock().unwrap();
+        SharedWriteGuard {
+            store: self.inner.store.write().unwrap(),
+            _append: append,
+        }
+    }
+    
+    /// `Mem8Lite::store`, encoding the packet while readers carry on
+    pub fn store(&self, data: &[u8], metadata: Option<Vec<u8>>) -> Result<[u8; 32]> {
+        let _append = self.inner.append.lock().unwrap();
+        // Nothing else can write while we hold the append lock, so the
+        // prepared record (and its dedup check) is still good when committed
+        let prepared = self.read().prepare_store(data, metadata, true)?;
+        self.inner.store.write().unwrap().commit_store(prepared)
+    }
+    
+    pub fn store_batch(&self, items: &[(&[u8], Option<Vec<u8>>)]) -> Result<Vec<[u8; 32]>> {
+        self.write().store_batch(items)
+    }
+    
+    pub fn retrieve(&self, signature: &[u8; 32]) -> Result<Vec<u8>> {
+        self.read().retrieve(signature)
+    }
+    
+    pub fn get_metadata(&self, signature: &[u8; 32]) -> Option<Vec<u8>> {
+        self.read().get_metadata(signature)
+    }
+    
+    pub fn sync(&self) -> Result<()> {
+        self.write().sync()
+    }
+    
+    /// The store back, if this is the last handle to it (so it can be `close`d)
+    pub fn into_inner(self) -> std::result::Result<Mem8Lite, Self> {
+        match Arc::try_unwrap(self.inner) {
+            Ok(shared) => Ok(shared.store.into_inner().unwrap()),
+            Err(inner) => Err(Self { inner }),
+        }
+    }
+}
+
+impl Deref for SharedWriteGuard<'_> {
+    type Target = Mem8Lite;
+    
+    fn deref(&self) -> &Mem8Lite {
+        &self.store
+    }
+}
+
+impl DerefMut for SharedWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut Mem8Lite {
+        &mut self.store
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use tempfile::tempdir;
+    
+    #[test]
+    fn test_eight_threads_store_and_retrieve() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("shared.m8");
+        let shared = SharedMem8Lite::new(Mem8Lite::with_cache_limit(&path, 1.618, 0).unwrap());
+        let seed = shared.store(b"shared seed", None).unwrap();
+        
+        let workers: Vec<_> = (0..8u8).map(|t| {
+            let shared = shared.clone();
+            std::thread::spawn(move || {
+                let mut mine = HashMap::new();
+                for i in 0..50u32 {
+                    let data: Vec<u8> = (0..(i * 37 + t as u32) % 4096).map(|j| (j as u8) ^ t).collect();
+                    let sig = shared.store(&data, Some(vec![t, i as u8])).unwrap();
+                    mine.insert(sig, data);
+                    
+                    // Cold reads (no cache) race the other threads' appends
+                    assert_eq!(shared.retrieve(&seed).unwrap(), b"shared seed");
+                    let (sig, data) = mine.iter().nth(i as usize / 2).unwrap();
+                    assert_eq!(&shared.retrieve(sig).unwrap(), data);
+                }
+                mine
+            })
+        }).collect();
+        let stored: Vec<HashMap<_, _>> = workers.into_iter().map(|w| w.join().unwrap()).collect();
+        
+        shared.sync().unwrap();
+        drop(shared);
+        let storage = Mem8Lite::new(&path, 1.618).unwrap();
+        assert_eq!(storage.iter().count(), 1 + 8 * 50);
+        for (sig, data) in stored.iter().flatten() {
+            assert_eq!(&storage.retrieve(sig).unwrap(), data);
+        }
+    }
+}
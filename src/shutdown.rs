@@ -0,0 +1,46 @@
+//! Shutdown signal handling for long-running MEM8 processes
+//!
+//! The MCP server, FUSE mount and daemon-style binaries install this once and
+//! poll `requested()` (or block in `wait()`), then `close` their stores so
+//! nothing buffered gets lost on Ctrl-C or a service stop. 🛑
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Set by the signal handler - the only thing it's allowed to touch
+static SHUTDOWN_REQUESTED: AtomicBool = AtomicBool::new(false);
+
+#[cfg(unix)]
+extern "C" fn on_signal(_signal: libc::c_int) {
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Install SIGINT/SIGTERM handlers that request a graceful shutdown
+///
+/// Safe to call more than once. On non-unix platforms this is a no-op and
+/// shutdown can only be requested through `request()`.
+pub fn install_handlers() {
+    #[cfg(unix)]
+    unsafe {
+        let handler = on_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::signal(libc::SIGINT, handler);
+        libc::signal(libc::SIGTERM, handler);
+    }
+}
+
+/// Has a shutdown been requested (by signal or `request()`)?
+pub fn requested() -> bool {
+    SHUTDOWN_REQUESTED.load(Ordering::SeqCst)
+}
+
+/// Request a graceful shutdown from code (e.g. an MCP "quit" tool)
+pub fn request() {
+    SHUTDOWN_REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Block until a shutdown is requested
+pub fn wait() {
+    while !requested() {
+        std::thread::sleep(Duration::from_millis(100));
+    }
+}
@@ -0,0 +1,50 @@
+//! Signing keys - ed25519 keyfiles for things that leave the machine
+//!
+//! A keyfile holds a 32 byte ed25519 secret as hex on one line, readable
+//! only by its owner. `generate_keyfile` makes one and `load_keyfile` reads
+//! it back; public keys travel as hex too (`parse_verifying_key`).
+//!
+//! Trisha signs her letters. Now Hue's memories can sign theirs. ✍️
+
+use std::io::Write;
+use std::path::Path;
+use ed25519_dalek::{SigningKey, VerifyingKey};
+use rand::RngCore;
+use anyhow::{Result, anyhow};
+
+/// Create a new signing key and write it to `path`, which must not exist
+pub fn generate_keyfile<P: AsRef<Path>>(path: P) -> Result<SigningKey> {
+    let path = path.as_ref();
+    let mut secret = [0u8; 32];
+    rand::rngs::OsRng.fill_bytes(&mut secret);
+    let key = SigningKey::from_bytes(&secret);
+    
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)
+        .map_err(|e| anyhow!("Can't create keyfile {}: {}", path.display(), e))?;
+    writeln!(file, "{}", hex::encode(secret))?;
+    file.sync_all()?;
+    Ok(key)
+}
+
+/// Read the signing key in a keyfile written by `generate_keyfile`
+pub fn load_keyfile<P: AsRef<Path>>(path: P) -> Result<SigningKey> {
+    let path = path.as_ref();
+    let text = std::fs::read_to_string(path)
+        .map_err(|e| anyhow!("Can't read keyfile {}: {}", path.display(), e))?;
+    let secret: [u8; 32] = hex::decode(text.trim()).ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("{} isn't a mem8 keyfile (expected 64 hex digits)", path.display()))?;
+    Ok(SigningKey::from_bytes(&secret))
+}
+
+/// A public key from its hex form (as `VerifyingKey::as_bytes` hex-encoded)
+pub fn parse_verifying_key(hex_key: &str) -> Result<VerifyingKey> {
+    let bytes: [u8; 32] = hex::decode(hex_key.trim()).ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| anyhow!("Public key must be 64 hex digits"))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| anyhow!("Not a valid ed25519 public key"))
+}
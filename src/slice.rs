@@ -0,0 +1,364 @@
+//! Slices - part of a Mem8Fs tree, packed up to share, with a signed manifest
+//!
+//! `export_slice` writes every file under a directory to a slice and returns
+//! its manifest: each file's path, signature and size. `SliceManifest::sign`
+//! signs the manifest with an ed25519 key (see `signing`), and the recipient
+//! hands manifest and slice to `verify_import`, which checks the signature
+//! and every file against it before merging anything. Missing, extra or
+//! altered files refuse the import unless it's forced, and then only the
+//! files that check out are merged.
+//!
+//! The signature is over a canonical listing - a header line, the version
+//! and base frequency, then one `path NUL signature NUL size` line per file
+//! in path order - hashed with blake3. The manifest itself is JSON.
+//!
+//! A valid signature only says the manifest wasn't changed since *someone*
+//! signed it: compare `signer` with the key you expect (`mem8 import
+//! --signer`). Hue's care package, sealed with wax. 💌
+
+use std::collections::BTreeMap;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use serde::{Serialize, Deserialize};
+use anyhow::anyhow;
+use crate::error::{Mem8Error, Result};
+use crate::{frequency_signature, Mem8Fs};
+
+/// First bytes of every slice
+pub const SLICE_MAGIC: &[u8; 8] = b"M8SLICE\0";
+
+/// Version of the slice and manifest formats this build writes (and the newest it reads)
+pub const SLICE_VERSION: u32 = 1;
+
+/// First line of the canonical listing a manifest signature covers
+const CANONICAL_HEADER: &str = "mem8-slice-manifest";
+
+/// One file in a slice
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct SliceFile {
+    path: PathBuf,
+    data: Vec<u8>,
+}
+
+/// One file as the manifest lists it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub path: String,
+    
+    /// Hex signature of the content, as the exporting tree computed it
+    pub signature: String,
+    
+    pub size: u64,
+}
+
+/// What a slice is supposed to contain (see `Mem8Fs::export_slice`)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SliceManifest {
+    pub version: u32,
+    
+    /// Base frequency of the exporting tree, in Hz (signatures depend on it)
+    pub base_frequency: f64,
+    
+    /// Sorted by path
+    pub entries: Vec<ManifestEntry>,
+    
+    /// Hex public key that signed the manifest (empty until `sign`)
+    #[serde(default)]
+    pub signer: String,
+    
+    /// Hex ed25519 signature (empty until `sign`)
+    #[serde(default)]
+    pub signature: String,
+}
+
+/// The manifest isn't signed, or its signature doesn't check out
+///
+/// Comes back inside `Mem8Error`; use `err.downcast_ref::<BadManifestSignature>()`.
+/// Never forced past: without a good signature there's nothing to check files against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("slice manifest isn't validly signed")]
+pub struct BadManifestSignature;
+
+/// Files in the slice don't match the manifest
+///
+/// Comes back inside `Mem8Error`; use `err.downcast_ref::<SliceRejected>()`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, thiserror::Error, Serialize, Deserialize)]
+#[error("slice doesn't match its manifest: {} missing, {} extra, {} altered", missing.len(), extra.len(), mismatched.len())]
+pub struct SliceRejected {
+    /// In the manifest, not in the slice
+    pub missing: Vec<String>,
+    
+    /// In the slice, not in the manifest
+    pub extra: Vec<String>,
+    
+    /// In both, but the content doesn't match the manifest's signature or size
+    pub mismatched: Vec<String>,
+}
+
+impl SliceRejected {
+    pub fn is_empty(&self) -> bool {
+        self.missing.is_empty() && self.extra.is_empty() && self.mismatched.is_empty()
+    }
+}
+
+/// What `Mem8Fs::verify_import` merged
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SliceImport {
+    /// Paths written, in path order
+    pub imported: Vec<String>,
+    
+    /// Hex public key that signed the manifest
+    pub signer: String,
+    
+    /// What was wrong with the slice (only ever non-empty for forced imports)
+    pub problems: SliceRejected,
+}
+
+impl std::fmt::Display for SliceImport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} files imported (signed by {})", self.imported.len(), self.signer)?;
+        for (what, paths) in [("missing", &self.problems.missing), ("extra", &self.problems.extra), ("altered", &self.problems.mismatched)] {
+            for path in paths {
+                write!(f, "\n  {}: {}", what, path)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl SliceManifest {
+    /// What the signature covers
+    fn canonical_digest(&self) -> [u8; 32] {
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(format!("{}\n{}\n{}\n", CANONICAL_HEADER, self.version, self.base_frequency.to_bits()).as_bytes());
+        let mut entries: Vec<&ManifestEntry> = self.entries.iter().collect();
+        entries.sort_by(|a, b| a.path.cmp(&b.path));
+        for entry in entries {
+            hasher.update(format!("{}\0{}\0{}\n", entry.path, entry.signature, entry.size).as_bytes());
+        }
+        hasher.finalize().into()
+    }
+    
+    /// Sign the manifest with `key`, replacing any earlier signature
+    pub fn sign(&mut self, key: &SigningKey) {
+        let signature = key.sign(&self.canonical_digest());
+        self.signer = hex::encode(key.verifying_key().as_bytes());
+        self.signature = hex::encode(signature.to_bytes());
+    }
+    
+    /// Check the signature against `signer`, returning the key that made it
+    pub fn verify_signature(&self) -> Result<VerifyingKey> {
+        let bad = || anyhow::Error::new(BadManifestSignature);
+        let signer = crate::signing::parse_verifying_key(&self.signer).map_err(|_| bad())?;
+        let signature: [u8; 64] = hex::decode(&self.signature).ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(bad)?;
+        signer.verify(&self.canonical_digest(), &Signature::from_bytes(&signature))
+            .map_err(|_| bad())?;
+        Ok(signer)
+    }
+    
+    pub fn to_json(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec_pretty(self)?)
+    }
+    
+    pub fn from_json(json: &[u8]) -> Result<Self> {
+        let manifest: Self = serde_json::from_slice(json)?;
+        if manifest.version > SLICE_VERSION {
+            return Err(anyhow!("Slice manifest version {} is newer than this build reads ({})", manifest.version, SLICE_VERSION).into());
+        }
+        Ok(manifest)
+    }
+}
+
+/// Read the files out of a slice
+fn read_slice<R: Read>(mut slice: R) -> Result<Vec<SliceFile>> {
+    let mut header = [0u8; 12];
+    slice.read_exact(&mut header).map_err(|_| anyhow!("Not a mem8 slice (too short)"))?;
+    if &header[..8] != SLICE_MAGIC {
+        return Err(anyhow!("Not a mem8 slice").into());
+    }
+    let version = u32::from_be_bytes([header[8], header[9], header[10], header[11]]);
+    if version > SLICE_VERSION {
+        return Err(anyhow!("Slice version {} is newer than this build reads ({})", version, SLICE_VERSION).into());
+    }
+    let mut body = Vec::new();
+    slice.read_to_end(&mut body)?;
+    bincode::deserialize(&body).map_err(|e| anyhow!("Corrupt slice: {}", e).into())
+}
+
+impl Mem8Fs {
+    /// Write every file under `dir` to `slice`, returning the (unsigned) manifest
+    pub fn export_slice<P: AsRef<Path>, W: Write>(&self, dir: P, mut slice: W) -> Result<SliceManifest> {
+        let dir = self.normalize_path(dir)?;
+        let mut paths: Vec<PathBuf> = self.index.read().unwrap().files.keys()
+            .filter(|path| path.starts_with(&dir))
+            .cloned()
+            .collect();
+        paths.sort();
+        
+        let mut files = Vec::with_capacity(paths.len());
+        let mut entries = Vec::with_capacity(paths.len());
+        for path in paths {
+            let data = self.read(&path)?;
+            entries.push(ManifestEntry {
+                path: path.to_string_lossy().into_owned(),
+                signature: hex::encode(frequency_signature(&data, self.base_frequency().hz())),
+                size: data.len() as u64,
+            });
+            files.push(SliceFile { path, data });
+        }
+        
+        slice.write_all(SLICE_MAGIC)?;
+        slice.write_all(&SLICE_VERSION.to_be_bytes())?;
+        bincode::serialize_into(&mut slice, &files)?;
+        slice.flush()?;
+        Ok(SliceManifest {
+            version: SLICE_VERSION,
+            base_frequency: self.base_frequency().hz(),
+            entries,
+            signer: String::new(),
+            signature: String::new(),
+        })
+    }
+    
+    /// Merge a slice, and nothing else, after checking it against its signed manifest
+    ///
+    /// Fails with `BadManifestSignature` if the manifest isn't validly signed
+    /// and with `SliceRejected` if any file is missing, extra or altered -
+    /// in both cases before writing anything.
+    pub fn verify_import<R: Read>(&self, manifest: &SliceManifest, slice: R) -> Result<SliceImport> {
+        self.verify_import_with(manifest, slice, false)
+    }
+    
+    /// `verify_import`, optionally merging the files that do check out when others don't
+    ///
+    /// Forcing never gets past a bad manifest signature.
+    pub fn verify_import_with<R: Read>(&self, manifest: &SliceManifest, slice: R, force: bool) -> Result<SliceImport> {
+        let signer = manifest.verify_signature()?;
+        let mut expected: BTreeMap<&str, &ManifestEntry> = manifest.entries.iter()
+            .map(|entry| (entry.path.as_str(), entry))
+            .collect();
+        
+        let mut problems = SliceRejected::default();
+        let mut verified = Vec::new();
+        for file in read_slice(slice)? {
+            let path = file.path.to_string_lossy().into_owned();
+            match expected.remove(path.as_str()) {
+                None => problems.extra.push(path),
+                Some(entry) => {
+                    let signature = hex::encode(frequency_signature(&file.data, manifest.base_frequency));
+                    if signature == entry.signature && file.data.len() as u64 == entry.size {
+                        verified.push(file);
+                    } else {
+                        problems.mismatched.push(path);
+                    }
+                }
+            }
+        }
+        problems.missing = expected.into_keys().map(str::to_string).collect();
+        if !problems.is_empty() && !force {
+            return Err(Mem8Error::from(problems));
+        }
+        
+        let mut imported = Vec::with_capacity(verified.len());
+        for file in verified {
+            self.write(&file.path, &file.data)?;
+            imported.push(file.path.to_string_lossy().into_owned());
+        }
+        imported.sort();
+        Ok(SliceImport { imported, signer: hex::encode(signer.as_bytes()), problems })
+    }
+    
+    /// Merge a slice without checking it against anything, returning the paths written
+    pub fn import_slice<R: Read>(&self, slice: R) -> Result<Vec<PathBuf>> {
+        let files = read_slice(slice)?;
+        let mut imported = Vec::with_capacity(files.len());
+        for file in files {
+            self.write(&file.path, &file.data)?;
+            imported.push(self.normalize_path(&file.path)?);
+        }
+        Ok(imported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    
+    fn shared_tree(root: &Path) -> (Mem8Fs, Vec<u8>, SliceManifest, SigningKey) {
+        let fs = Mem8Fs::new(root).unwrap();
+        fs.write("/trip/day1.txt", b"ferry at dawn").unwrap();
+        fs.write("/trip/photos/gull.raw", &[1, 2, 3, 250]).unwrap();
+        fs.write("/private/diary.txt", b"not in the slice").unwrap();
+        
+        let mut slice = Vec::new();
+        let mut manifest = fs.export_slice("/trip", &mut slice).unwrap();
+        let key = SigningKey::from_bytes(&[9u8; 32]);
+        manifest.sign(&key);
+        (fs, slice, manifest, key)
+    }
+    
+    #[test]
+    fn test_signed_slice_imports_cleanly() {
+        let dir = tempdir().unwrap();
+        let (_source, slice, manifest, key) = shared_tree(&dir.path().join("source"));
+        assert_eq!(manifest.entries.iter().map(|e| e.path.as_str()).collect::<Vec<_>>(),
+            vec!["/trip/day1.txt", "/trip/photos/gull.raw"]);
+        
+        // The manifest survives its trip as JSON
+        let manifest = SliceManifest::from_json(&manifest.to_json().unwrap()).unwrap();
+        let target = Mem8Fs::new(dir.path().join("target")).unwrap();
+        let report = target.verify_import(&manifest, slice.as_slice()).unwrap();
+        assert_eq!(report.imported, vec!["/trip/day1.txt", "/trip/photos/gull.raw"]);
+        assert_eq!(report.signer, hex::encode(key.verifying_key().as_bytes()));
+        assert!(report.problems.is_empty());
+        assert_eq!(target.read("/trip/day1.txt").unwrap(), b"ferry at dawn");
+        assert_eq!(target.read("/trip/photos/gull.raw").unwrap(), [1, 2, 3, 250]);
+        assert!(!target.exists("/private/diary.txt"));
+    }
+    
+    #[test]
+    fn test_tampered_slice_is_rejected() {
+        let dir = tempdir().unwrap();
+        let (source, slice, manifest, _key) = shared_tree(&dir.path().join("source"));
+        let target = Mem8Fs::new(dir.path().join("target")).unwrap();
+        
+        // A byte of file content changed in transit
+        let position = slice.windows(13).position(|w| w == b"ferry at dawn").unwrap();
+        let mut altered = slice.clone();
+        altered[position] = b'F';
+        let err = target.verify_import(&manifest, altered.as_slice()).unwrap_err();
+        let rejected = err.downcast_ref::<SliceRejected>().unwrap();
+        assert_eq!(rejected.mismatched, vec!["/trip/day1.txt"]);
+        assert!(!target.exists("/trip/photos/gull.raw"));
+        
+        // A file slipped in, one left out
+        let mut other = Vec::new();
+        source.export_slice("/private", &mut other).unwrap();
+        let err = target.verify_import(&manifest, other.as_slice()).unwrap_err();
+        let rejected = err.downcast_ref::<SliceRejected>().unwrap();
+        assert_eq!(rejected.extra, vec!["/private/diary.txt"]);
+        assert_eq!(rejected.missing.len(), 2);
+        
+        // A manifest edited to match the tampering no longer verifies, forced or not
+        let mut forged = manifest.clone();
+        forged.entries[0].signature = hex::encode(frequency_signature(b"Ferry at dawn", forged.base_frequency));
+        for force in [false, true] {
+            let err = target.verify_import_with(&forged, altered.as_slice(), force).unwrap_err();
+            assert!(err.downcast_ref::<BadManifestSignature>().is_some());
+        }
+        let mut unsigned = manifest.clone();
+        unsigned.signature.clear();
+        assert!(target.verify_import(&unsigned, slice.as_slice()).is_err());
+        assert!(target.list("/trip").unwrap().is_empty());
+        
+        // Forcing merges only what checks out
+        let report = target.verify_import_with(&manifest, altered.as_slice(), true).unwrap();
+        assert_eq!(report.imported, vec!["/trip/photos/gull.raw"]);
+        assert_eq!(report.problems.mismatched, vec!["/trip/day1.txt"]);
+        assert!(!target.exists("/trip/day1.txt"));
+    }
+}
@@ -0,0 +1,431 @@
+//! Snapshots - read-only views of a Mem8Fs tree as it was
+//!
+//! `create_snapshot` copies the file index (paths, signatures, sizes and
+//! times) into the `snapshots` namespace. File contents aren't copied: wave
+//! data is append-only and shared with the live tree, so a snapshot costs a
+//! few dozen bytes per file. The one way a snapshot can lose data is a
+//! `secure_delete` of content it still refers to - reading such a file gives
+//! `SnapshotDataGone` instead of whatever happens to be on disk.
+//!
+//! Hue's use case: "what did my config look like before I broke it?" 📸
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use anyhow::anyhow;
+use crate::error::{Mem8Error, Result};
+use crate::{normalize_path, FileEntry, FileEntryV1, FileEntryV2, FileEntryV3, FileEntryV4, FileEntryV5, FileMetadata, FsEvent, Mem8Fs, CHUNKED_INDEX_MAGIC, CONTENT_INDEX_MAGIC, INDEX_MAGIC, METADATA_INDEX_MAGIC, MILLIS_INDEX_MAGIC};
+
+/// Namespace holding every snapshot of a filesystem
+pub const SNAPSHOT_NAMESPACE: &str = "snapshots";
+
+/// The file tree of a `Mem8Fs` at one point in time
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    id: String,
+    created: u64,
+    files: HashMap<PathBuf, FileEntry>,
+}
+
+/// A snapshot as it was stored before chunk lists
+#[derive(Deserialize)]
+struct SnapshotV1 {
+    id: String,
+    created: u64,
+    files: HashMap<PathBuf, FileEntryV1>,
+}
+
+/// A snapshot as it was stored while file times were in seconds
+#[derive(Deserialize)]
+struct SnapshotV2 {
+    id: String,
+    created: u64,
+    files: HashMap<PathBuf, FileEntryV2>,
+}
+
+/// A snapshot as it was stored before per-file metadata
+#[derive(Deserialize)]
+struct SnapshotV3 {
+    id: String,
+    created: u64,
+    files: HashMap<PathBuf, FileEntryV3>,
+}
+
+/// A snapshot as it was stored while signatures mixed in the frequency
+#[derive(Deserialize)]
+struct SnapshotV4 {
+    id: String,
+    created: u64,
+    files: HashMap<PathBuf, FileEntryV4>,
+}
+
+/// A snapshot as it was stored before inline files
+#[derive(Deserialize)]
+struct SnapshotV5 {
+    id: String,
+    created: u64,
+    files: HashMap<PathBuf, FileEntryV5>,
+}
+
+/// What `list_snapshots` says about each snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    pub id: String,
+    
+    /// When it was taken (unix seconds)
+    pub created: u64,
+    
+    /// Files it holds
+    pub files: usize,
+    
+    /// Their combined size in bytes
+    pub total_size: u64,
+}
+
+/// A snapshot file whose content isn't in the store any more
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[error("{path} in snapshot {snapshot} refers to content that is no longer in the store ({signature})")]
+pub struct SnapshotDataGone {
+    pub snapshot: String,
+    pub path: String,
+    
+    /// Hex signature of the missing content
+    pub signature: String,
+}
+
+impl Snapshot {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+    
+    /// Signatures of the content its files refer to
+    pub(crate) fn signatures(&self) -> impl Iterator<Item = [u8; 32]> + '_ {
+        self.files.values().flat_map(FileEntry::content_signatures)
+    }
+    
+    /// Its files' own signatures (a chunked file's being its chunk list's)
+    pub(crate) fn file_signatures(&self) -> impl Iterator<Item = [u8; 32]> + '_ {
+        self.files.values().map(|entry| entry.signature)
+    }
+    
+    /// When it was taken (unix seconds)
+    pub fn created(&self) -> u64 {
+        self.created
+    }
+    
+    pub fn info(&self) -> SnapshotInfo {
+        SnapshotInfo {
+            id: self.id.clone(),
+            created: self.created,
+            files: self.files.len(),
+            total_size: self.files.values().map(|f| f.size).sum(),
+        }
+    }
+    
+    /// Was there a file at `path`?
+    pub fn exists<P: AsRef<Path>>(&self, path: P) -> bool {
+        normalize_path(path.as_ref()).is_ok_and(|path| self.files.contains_key(&path))
+    }
+    
+    /// Files directly inside `dir`, like `Mem8Fs::list`
+    pub fn list<P: AsRef<Path>>(&self, dir: P) -> Vec<PathBuf> {
+        let Ok(dir) = normalize_path(dir.as_ref()) else {
+            return Vec::new();
+        };
+        self.files.keys()
+            .filter(|path| path.parent() == Some(dir.as_path()))
+            .cloned()
+            .collect()
+    }
+    
+    /// A file's metadata as it was, like `Mem8Fs::metadata`
+    pub fn metadata<P: AsRef<Path>>(&self, path: P) -> Result<FileMetadata> {
+        let entry = self.files.get(&normalize_path(path.as_ref())?)
+            .ok_or_else(|| Mem8Error::NotFound(format!("File not found in snapshot {}", self.id)))?;
+        let nlink = self.files.values().filter(|other| other.signature == entry.signature).count();
+        Ok(entry.to_metadata(nlink as u64))
+    }
+}
+
+impl Mem8Fs {
+    /// Capture the current file tree as snapshot `id`
+    /// 
+    /// Ids are letters, digits, `.`, `_` and `-` (`nightly-2024-06-01`), and
+    /// can't be reused.
+    pub fn create_snapshot(&self, id: &str) -> Result<SnapshotInfo> {
+        let valid = !id.is_empty()
+            && id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+        if !valid {
+            return Err(anyhow!("Invalid snapshot id: {:?}", id).into());
+        }
+        let mut snapshots = self.load_snapshots()?;
+        if snapshots.contains_key(id) {
+            return Err(anyhow!("Snapshot {} already exists", id).into());
+        }
+        
+        let snapshot = Snapshot {
+            id: id.to_string(),
+            created: chrono::Utc::now().timestamp() as u64,
+            files: self.index.read().unwrap().files.clone(),
+        };
+        let info = snapshot.info();
+        snapshots.insert(id.to_string(), snapshot);
+        self.save_snapshots(&snapshots)?;
+        Ok(info)
+    }
+    
+    /// Make snapshot `id` the live tree again
+    /// 
+    /// The tree it replaces is kept as a snapshot of its own,
+    /// `before-restore-<time>`, whose info is returned - so a restore can be
+    /// undone with another. Files deleted since `id` was taken come back with
+    /// their old content (it never left `data.m8`); directories are rebuilt
+    /// from the restored files' paths. Nothing changes if any of the
+    /// snapshot's content has been scrubbed (`SnapshotDataGone`).
+    pub fn restore_snapshot(&self, id: &str) -> Result<SnapshotInfo> {
+        self.check_writable()?;
+        let mut snapshots = self.load_snapshots()?;
+        let restored = snapshots.get(id)
+            .ok_or_else(|| Mem8Error::NotFound(format!("No snapshot named {}", id)))?
+            .clone();
+        
+        let mut index = self.index.write().unwrap();
+        {
+            let storage = self.storage.read().unwrap();
+            for (path, entry) in &restored.files {
+                if !entry.content_signatures().all(|signature| storage.contains(&signature)) {
+                    return Err(Mem8Error::from(SnapshotDataGone {
+                        snapshot: restored.id.clone(),
+                        path: path.display().to_string(),
+                        signature: hex::encode(entry.signature),
+                    }));
+                }
+            }
+        }
+        
+        let now = chrono::Utc::now();
+        let stamp = now.format("%Y%m%dT%H%M%S");
+        let kept_id = (1..)
+            .map(|n| match n {
+                1 => format!("before-restore-{}", stamp),
+                n => format!("before-restore-{}-{}", stamp, n),
+            })
+            .find(|candidate| !snapshots.contains_key(candidate))
+            .unwrap();
+        let kept = Snapshot {
+            id: kept_id.clone(),
+            created: now.timestamp() as u64,
+            files: std::mem::replace(&mut index.files, restored.files),
+        };
+        let info = kept.info();
+        snapshots.insert(kept_id, kept);
+        self.save_snapshots(&snapshots)?;
+        
+        index.directories.clear();
+        index.link_all(now.timestamp() as u64);
+        self.save_index(&index)?;
+        self.reset_totals(&index)?;
+        
+        // Tell the watchers what the swap amounted to, path by path
+        let before = &snapshots[&info.id].files;
+        for (path, entry) in before {
+            match index.files.get(path) {
+                None => self.watchers.notify(FsEvent::Deleted { path: path.clone(), signature: entry.signature }),
+                Some(current) if current.signature != entry.signature => {
+                    self.watchers.notify(FsEvent::Modified { path: path.clone(), signature: current.signature });
+                }
+                Some(_) => {}
+            }
+        }
+        for (path, entry) in index.files.iter().filter(|(path, _)| !before.contains_key(*path)) {
+            self.watchers.notify(FsEvent::Created { path: path.clone(), signature: entry.signature });
+        }
+        Ok(info)
+    }
+    
+    fn save_snapshots(&self, snapshots: &BTreeMap<String, Snapshot>) -> Result<()> {
+        let mut bytes = INDEX_MAGIC.to_vec();
+        bytes.extend_from_slice(&bincode::serialize(snapshots)?);
+        self.write_namespace(SNAPSHOT_NAMESPACE, &bytes)
+    }
+    
+    /// Every snapshot, oldest first
+    pub fn list_snapshots(&self) -> Result<Vec<SnapshotInfo>> {
+        let mut infos: Vec<SnapshotInfo> = self.load_snapshots()?.values().map(Snapshot::info).collect();
+        infos.sort_by(|a, b| (a.created, &a.id).cmp(&(b.created, &b.id)));
+        Ok(infos)
+    }
+    
+    /// Load snapshot `id` for browsing
+    pub fn snapshot(&self, id: &str) -> Result<Snapshot> {
+        self.load_snapshots()?.remove(id)
+            .ok_or_else(|| Mem8Error::NotFound(format!("No snapshot named {}", id)))
+    }
+    
+    /// `read_snapshot` by snapshot id, for a one-off read
+    pub fn read_at_snapshot<P: AsRef<Path>>(&self, id: &str, path: P) -> Result<Vec<u8>> {
+        self.read_snapshot(&self.snapshot(id)?, path)
+    }
+    
+    /// Read a file as it was in `snapshot`
+    /// 
+    /// Fails with `SnapshotDataGone` (downcast the error to check) when the
+    /// content has been scrubbed from the store since.
+    pub fn read_snapshot<P: AsRef<Path>>(&self, snapshot: &Snapshot, path: P) -> Result<Vec<u8>> {
+        let path = normalize_path(path.as_ref())?;
+        let entry = snapshot.files.get(&path)
+            .ok_or_else(|| Mem8Error::NotFound(format!("File not found in snapshot {}", snapshot.id)))?;
+        
+        let storage = self.storage.read().unwrap();
+        storage.retrieve_entry(entry).map_err(|_| Mem8Error::from(SnapshotDataGone {
+            snapshot: snapshot.id.clone(),
+            path: path.display().to_string(),
+            signature: hex::encode(entry.signature),
+        }))
+    }
+    
+    /// `read_range` for a file as it was in `snapshot`
+    pub fn read_snapshot_range<P: AsRef<Path>>(&self, snapshot: &Snapshot, path: P, offset: usize, len: usize) -> Result<Vec<u8>> {
+        let path = normalize_path(path.as_ref())?;
+        let entry = snapshot.files.get(&path)
+            .ok_or_else(|| Mem8Error::NotFound(format!("File not found in snapshot {}", snapshot.id)))?;
+        
+        if offset as u64 > entry.size {
+            return Err(anyhow!("Range offset {} is past the end of the file ({} bytes)", offset, entry.size).into());
+        }
+        
+        let storage = self.storage.read().unwrap();
+        storage.retrieve_entry_range(entry, offset, len).map_err(|_| Mem8Error::from(SnapshotDataGone {
+            snapshot: snapshot.id.clone(),
+            path: path.display().to_string(),
+            signature: hex::encode(entry.signature),
+        }))
+    }
+    
+    pub(crate) fn load_snapshots(&self) -> Result<BTreeMap<String, Snapshot>> {
+        match self.read_namespace(SNAPSHOT_NAMESPACE)? {
+            Some(bytes) => {
+                if let Some(bytes) = bytes.strip_prefix(INDEX_MAGIC) {
+                    return Ok(bincode::deserialize(bytes)?);
+                }
+                if let Some(bytes) = bytes.strip_prefix(CONTENT_INDEX_MAGIC) {
+                    let v5: BTreeMap<String, SnapshotV5> = bincode::deserialize(bytes)?;
+                    return Ok(v5.into_iter().map(|(id, snapshot)| (id, Snapshot {
+                        id: snapshot.id,
+                        created: snapshot.created,
+                        files: snapshot.files.into_iter().map(|(path, entry)| (path, entry.into())).collect(),
+                    })).collect());
+                }
+                if let Some(bytes) = bytes.strip_prefix(METADATA_INDEX_MAGIC) {
+                    let v4: BTreeMap<String, SnapshotV4> = bincode::deserialize(bytes)?;
+                    return Ok(v4.into_iter().map(|(id, snapshot)| (id, Snapshot {
+                        id: snapshot.id,
+                        created: snapshot.created,
+                        files: snapshot.files.into_iter().map(|(path, entry)| (path, entry.into())).collect(),
+                    })).collect());
+                }
+                if let Some(bytes) = bytes.strip_prefix(MILLIS_INDEX_MAGIC) {
+                    let v3: BTreeMap<String, SnapshotV3> = bincode::deserialize(bytes)?;
+                    return Ok(v3.into_iter().map(|(id, snapshot)| (id, Snapshot {
+                        id: snapshot.id,
+                        created: snapshot.created,
+                        files: snapshot.files.into_iter().map(|(path, entry)| (path, entry.into())).collect(),
+                    })).collect());
+                }
+                match bytes.strip_prefix(CHUNKED_INDEX_MAGIC) {
+                    Some(bytes) => {
+                        let v2: BTreeMap<String, SnapshotV2> = bincode::deserialize(bytes)?;
+                        Ok(v2.into_iter().map(|(id, snapshot)| (id, Snapshot {
+                            id: snapshot.id,
+                            created: snapshot.created,
+                            files: snapshot.files.into_iter().map(|(path, entry)| (path, entry.into())).collect(),
+                        })).collect())
+                    }
+                    None => {
+                        let v1: BTreeMap<String, SnapshotV1> = bincode::deserialize(&bytes)?;
+                        Ok(v1.into_iter().map(|(id, snapshot)| (id, Snapshot {
+                            id: snapshot.id,
+                            created: snapshot.created,
+                            files: snapshot.files.into_iter().map(|(path, entry)| (path, entry.into())).collect(),
+                        })).collect())
+                    }
+                }
+            }
+            None => Ok(BTreeMap::new()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    
+    #[test]
+    fn test_snapshot_keeps_the_old_tree() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        fs.write_string("/config.json", r#"{"fast": true}"#).unwrap();
+        fs.write_string("/notes.txt", "waves").unwrap();
+        let info = fs.create_snapshot("nightly-2024-06-01").unwrap();
+        assert_eq!((info.files, info.total_size), (2, 19));
+        assert!(fs.create_snapshot("nightly-2024-06-01").is_err());
+        assert!(fs.create_snapshot("../escape").is_err());
+        
+        // The live tree moves on
+        fs.write_string("/config.json", r#"{"fast": false}"#).unwrap();
+        fs.delete("/notes.txt").unwrap();
+        fs.write_string("/new.txt", "fresh").unwrap();
+        
+        let snapshot = fs.snapshot("nightly-2024-06-01").unwrap();
+        assert_eq!(fs.read_snapshot(&snapshot, "/config.json").unwrap(), br#"{"fast": true}"#);
+        assert_eq!(fs.read_snapshot(&snapshot, "notes.txt").unwrap(), b"waves");
+        assert_eq!(fs.read_snapshot_range(&snapshot, "/config.json", 2, 4).unwrap(), b"fast");
+        assert!(!snapshot.exists("/new.txt"));
+        assert_eq!(snapshot.list("/").len(), 2);
+        assert_eq!(fs.list_snapshots().unwrap(), vec![info]);
+        
+        // Scrubbed content is reported as gone, not read as garbage
+        fs.write_string("/secret.txt", "shh").unwrap();
+        let later = fs.create_snapshot("later").unwrap();
+        assert_eq!(later.files, 3);
+        fs.secure_delete("/secret.txt").unwrap();
+        let err = fs.read_snapshot(&fs.snapshot("later").unwrap(), "/secret.txt").unwrap_err();
+        assert_eq!(err.downcast_ref::<SnapshotDataGone>().unwrap().path, "/secret.txt");
+        assert!(fs.restore_snapshot("later").unwrap_err().downcast_ref::<SnapshotDataGone>().is_some());
+    }
+    
+    #[test]
+    fn test_restore_snapshot_swaps_the_live_tree() {
+        let dir = tempdir().unwrap();
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        fs.write_string("/docs/plan.txt", "version one").unwrap();
+        fs.write_string("/docs/todo.txt", "buy socks").unwrap();
+        fs.create_snapshot("before-edits").unwrap();
+        
+        fs.write_string("/docs/plan.txt", "version two").unwrap();
+        fs.delete("/docs/todo.txt").unwrap();
+        fs.write_string("/scratch.txt", "temporary").unwrap();
+        assert_eq!(fs.read_at_snapshot("before-edits", "/docs/plan.txt").unwrap(), b"version one");
+        assert!(fs.read_at_snapshot("nope", "/docs/plan.txt").is_err());
+        
+        let watcher = fs.watch("/").unwrap();
+        let kept = fs.restore_snapshot("before-edits").unwrap();
+        assert!(kept.id.starts_with("before-restore-"));
+        assert_eq!(fs.read_string("/docs/plan.txt").unwrap(), "version one");
+        assert_eq!(fs.read_string("/docs/todo.txt").unwrap(), "buy socks");
+        assert!(!fs.exists("/scratch.txt"));
+        assert_eq!(fs.list("/docs").unwrap().len(), 2);
+        let stats = fs.stats().unwrap();
+        assert_eq!((stats.total_files, stats.total_size), (2, 20));
+        assert_eq!(watcher.try_iter().count(), 3);
+        
+        // The tree it replaced is a snapshot now, and survives a reopen
+        drop(fs);
+        let fs = Mem8Fs::new(dir.path()).unwrap();
+        assert_eq!(fs.read_string("/docs/todo.txt").unwrap(), "buy socks");
+        assert_eq!(fs.read_at_snapshot(&kept.id, "/docs/plan.txt").unwrap(), b"version two");
+        assert_eq!(fs.read_at_snapshot(&kept.id, "/scratch.txt").unwrap(), b"temporary");
+        assert_eq!(fs.list_snapshots().unwrap().len(), 2);
+    }
+}
@@ -0,0 +1,153 @@
+//! Text decoding - strings out of waves without silently mangling them
+//!
+//! `read_string` is strict UTF-8 and says exactly where decoding failed
+//! (`NotUtf8`), so callers can choose: decode lossily, pick another encoding,
+//! or treat the payload as binary. `TextEncoding::Auto` follows a BOM when
+//! there is one (UTF-8, UTF-16LE/BE) and insists on UTF-8 otherwise.
+//!
+//! Trisha's rule: a replacement character is not a backup! 📼
+
+use serde::{Serialize, Deserialize};
+use anyhow::{Result, anyhow};
+
+/// How to turn stored bytes into a string
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum TextEncoding {
+    /// Follow the BOM if present, otherwise strict UTF-8
+    #[default]
+    Auto,
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    
+    /// ISO-8859-1 - every byte is a character, so it never fails
+    Latin1,
+}
+
+impl TextEncoding {
+    pub fn name(&self) -> &'static str {
+        match self {
+            TextEncoding::Auto => "auto",
+            TextEncoding::Utf8 => "utf-8",
+            TextEncoding::Utf16Le => "utf-16le",
+            TextEncoding::Utf16Be => "utf-16be",
+            TextEncoding::Latin1 => "latin-1",
+        }
+    }
+    
+    /// The encoding announced by a byte order mark, and the BOM's length
+    pub fn from_bom(bytes: &[u8]) -> Option<(TextEncoding, usize)> {
+        match bytes {
+            [0xEF, 0xBB, 0xBF, ..] => Some((TextEncoding::Utf8, 3)),
+            [0xFF, 0xFE, ..] => Some((TextEncoding::Utf16Le, 2)),
+            [0xFE, 0xFF, ..] => Some((TextEncoding::Utf16Be, 2)),
+            _ => None,
+        }
+    }
+}
+
+impl std::str::FromStr for TextEncoding {
+    type Err = anyhow::Error;
+    
+    fn from_str(s: &str) -> Result<Self> {
+        match s.to_ascii_lowercase().as_str() {
+            "auto" => Ok(TextEncoding::Auto),
+            "utf-8" | "utf8" => Ok(TextEncoding::Utf8),
+            "utf-16le" | "utf16le" => Ok(TextEncoding::Utf16Le),
+            "utf-16be" | "utf16be" => Ok(TextEncoding::Utf16Be),
+            "latin-1" | "latin1" | "iso-8859-1" => Ok(TextEncoding::Latin1),
+            other => Err(anyhow!("Unknown text encoding: {}", other)),
+        }
+    }
+}
+
+impl std::fmt::Display for TextEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+/// Strict UTF-8 decoding failed
+///
+/// Comes back inside `Mem8Error`; use `err.downcast_ref::<NotUtf8>()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("content is not valid UTF-8 (valid up to byte {valid_up_to})")]
+pub struct NotUtf8 {
+    /// Length of the longest valid UTF-8 prefix
+    pub valid_up_to: usize,
+}
+
+/// Strict UTF-8
+pub fn decode_utf8(bytes: Vec<u8>) -> std::result::Result<String, NotUtf8> {
+    String::from_utf8(bytes).map_err(|e| NotUtf8 { valid_up_to: e.utf8_error().valid_up_to() })
+}
+
+/// Decode `bytes`, returning the text and the encoding actually used
+///
+/// A BOM matching the requested encoding is stripped.
+pub fn decode(bytes: Vec<u8>, encoding: TextEncoding) -> Result<(String, TextEncoding)> {
+    let bom = TextEncoding::from_bom(&bytes);
+    let encoding = match encoding {
+        TextEncoding::Auto => bom.map(|(detected, _)| detected).unwrap_or(TextEncoding::Utf8),
+        explicit => explicit,
+    };
+    let skip = match bom {
+        Some((detected, len)) if detected == encoding => len,
+        _ => 0,
+    };
+    
+    let text = match encoding {
+        TextEncoding::Utf8 | TextEncoding::Auto => {
+            let mut bytes = bytes;
+            bytes.drain(..skip);
+            decode_utf8(bytes)?
+        }
+        TextEncoding::Utf16Le => decode_utf16(&bytes[skip..], u16::from_le_bytes)?,
+        TextEncoding::Utf16Be => decode_utf16(&bytes[skip..], u16::from_be_bytes)?,
+        TextEncoding::Latin1 => bytes.iter().map(|&b| b as char).collect(),
+    };
+    Ok((text, encoding))
+}
+
+fn decode_utf16(bytes: &[u8], unit: fn([u8; 2]) -> u16) -> Result<String> {
+    if !bytes.len().is_multiple_of(2) {
+        return Err(anyhow!("UTF-16 content has an odd number of bytes ({})", bytes.len()));
+    }
+    let units = bytes.chunks_exact(2).map(|pair| unit([pair[0], pair[1]]));
+    char::decode_utf16(units)
+        .collect::<std::result::Result<String, _>>()
+        .map_err(|e| anyhow!("invalid UTF-16: unpaired surrogate {:#06x}", e.unpaired_surrogate()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_decode_follows_bom() {
+        let mut le = vec![0xFF, 0xFE];
+        let mut be = vec![0xFE, 0xFF];
+        for unit in "wave 🌊".encode_utf16() {
+            le.extend_from_slice(&unit.to_le_bytes());
+            be.extend_from_slice(&unit.to_be_bytes());
+        }
+        assert_eq!(decode(le, TextEncoding::Auto).unwrap(), ("wave 🌊".to_string(), TextEncoding::Utf16Le));
+        assert_eq!(decode(be, TextEncoding::Auto).unwrap(), ("wave 🌊".to_string(), TextEncoding::Utf16Be));
+        
+        let utf8_bom = [&[0xEF, 0xBB, 0xBF][..], "hi".as_bytes()].concat();
+        assert_eq!(decode(utf8_bom, TextEncoding::Auto).unwrap(), ("hi".to_string(), TextEncoding::Utf8));
+        assert!(decode(vec![0xFF, 0xFE, 0x41], TextEncoding::Auto).is_err());
+    }
+    
+    #[test]
+    fn test_strict_utf8_reports_where_it_failed() {
+        let bytes = b"caf\xe9 noir".to_vec(); // Latin-1 é
+        let err = decode(bytes.clone(), TextEncoding::Auto).unwrap_err();
+        assert_eq!(err.downcast_ref::<NotUtf8>(), Some(&NotUtf8 { valid_up_to: 3 }));
+        
+        let (text, encoding) = decode(bytes, TextEncoding::Latin1).unwrap();
+        assert_eq!(text, "café noir");
+        assert_eq!(encoding, TextEncoding::Latin1);
+    }
+}
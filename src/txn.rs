@@ -0,0 +1,381 @@
+//! Transactions - several writes to a Mem8Lite store, all or nothing
+//!
+//! `Mem8Lite::begin_txn` collects stores, deletes, metadata updates and key
+//! changes without touching the store. `Txn::commit` checks they can all be
+//! applied, writes them to a small journal (`<path>.wal`), fsyncs it, and only
+//! then adds the commit marker and applies them. If the process dies before the
+//! marker is down, the next open throws the journal away and none of it
+//! happened; after the marker, the next open replays it and all of it did.
+//! Every operation is safe to apply twice, so a replay after a half-finished
+//! commit just picks up where it stopped.
+//!
+//! Journals of encrypted stores are sealed with the store key. 🧾
+//!
+//! Trisha insists the ledger always balances - now it does.
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use serde::{Serialize, Deserialize};
+use anyhow::anyhow;
+use crate::error::Result;
+
+use crate::cipher;
+use crate::lite::{content_signature, sync_parent_dir, Mem8Lite, GROUP_KEY_PREFIX};
+
+/// First bytes of every journal (name plus format version)
+const JOURNAL_MAGIC: &[u8; 8] = b"M8WAL\0\0\x01";
+
+/// Written after the operations once they're safely on disk
+const COMMIT_MARKER: &[u8; 8] = b"M8COMMIT";
+
+/// One write in a transaction
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+enum TxnOp {
+    Store { data: Vec<u8>, metadata: Option<Vec<u8>> },
+    StoreKeyed { key: String, data: Vec<u8>, metadata: Option<Vec<u8>> },
+    SetMetadata { signature: [u8; 32], metadata: Vec<u8> },
+    Delete { signature: [u8; 32] },
+    DeleteKey { key: String },
+}
+
+/// Writes waiting to be committed together (see `Mem8Lite::begin_txn`)
+///
+/// Dropping a transaction without committing it is the same as `rollback`.
+pub struct Txn<'a> {
+    store: &'a mut Mem8Lite,
+    ops: Vec<TxnOp>,
+}
+
+impl Mem8Lite {
+    /// Start collecting writes to commit all at once
+    pub fn begin_txn(&mut self) -> Txn<'_> {
+        Txn { store: self, ops: Vec::new() }
+    }
+}
+
+impl Txn<'_> {
+    /// `Mem8Lite::store` at commit time; the signature is known right away
+    pub fn store(&mut self, data: &[u8], metadata: Option<Vec<u8>>) -> [u8; 32] {
+        let signature = content_signature(data, metadata.as_deref());
+        self.ops.push(TxnOp::Store { data: data.to_vec(), metadata });
+        signature
+    }
+    
+    /// `Mem8Lite::store_keyed` at commit time
+    pub fn store_keyed(&mut self, key: &str, data: &[u8], metadata: Option<Vec<u8>>) -> Result<[u8; 32]> {
+        if key.starts_with(GROUP_KEY_PREFIX) {
+            return Err(anyhow!("Keys starting with a NUL byte are reserved").into());
+        }
+        let signature = content_signature(data, metadata.as_deref());
+        self.ops.push(TxnOp::StoreKeyed { key: key.to_string(), data: data.to_vec(), metadata });
+        Ok(signature)
+    }
+    
+    /// `Mem8Lite::set_metadata` at commit time (the packet may be one this transaction stores)
+    pub fn set_metadata(&mut self, signature: &[u8; 32], metadata: Vec<u8>) {
+        self.ops.push(TxnOp::SetMetadata { signature: *signature, metadata });
+    }
+    
+    /// `Mem8Lite::delete` at commit time
+    pub fn delete(&mut self, signature: &[u8; 32]) {
+        self.ops.push(TxnOp::Delete { signature: *signature });
+    }
+    
+    /// `Mem8Lite::delete_key` at commit time
+    pub fn delete_key(&mut self, key: &str) {
+        self.ops.push(TxnOp::DeleteKey { key: key.to_string() });
+    }
+    
+    /// How many writes are waiting
+    pub fn len(&self) -> usize {
+        self.ops.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.ops.is_empty()
+    }
+    
+    /// Apply every write, or none of them
+    ///
+    /// Fails without changing anything when a write couldn't be applied (a
+    /// delete of a packet that isn't there, say). Once this returns `Ok`, the
+    /// writes are durable whatever the flush policy. If applying them fails
+    /// half way (an I/O error), the journal stays behind and the next open of
+    /// the store finishes the job.
+    pub fn commit(self) -> Result<()> {
+        if self.ops.is_empty() {
+            return Ok(());
+        }
+        self.check()?;
+        self.write_journal()?;
+        let store = self.store;
+        for op in &self.ops {
+            apply(store, op)?;
+        }
+        store.sync()?;
+        std::fs::remove_file(journal_path(store.path()))?;
+        Ok(())
+    }
+    
+    /// Throw the writes away
+    pub fn rollback(self) {}
+    
+    /// Would every operation apply, in order, to the store as it is now?
+    fn check(&self) -> Result<()> {
+        // What the earlier operations did (true: stored/set, false: deleted)
+        let mut live: HashMap<[u8; 32], bool> = HashMap::new();
+        let mut keys: HashMap<&str, bool> = HashMap::new();
+        let store = &*self.store;
+        let is_live = |live: &HashMap<[u8; 32], bool>, signature: &[u8; 32]| {
+            live.get(signature).copied().unwrap_or_else(|| store.contains(signature))
+        };
+        
+        for op in &self.ops {
+            match op {
+                TxnOp::Store { data, metadata } => {
+                    store.check_metadata(metadata.as_deref())?;
+                    live.insert(content_signature(data, metadata.as_deref()), true);
+                }
+                TxnOp::StoreKeyed { key, data, metadata } => {
+                    store.check_metadata(metadata.as_deref())?;
+                    live.insert(content_signature(data, metadata.as_deref()), true);
+                    keys.insert(key, true);
+                }
+                TxnOp::SetMetadata { signature, metadata } => {
+                    store.check_metadata(Some(metadata))?;
+                    if !is_live(&live, signature) {
+                        return Err(anyhow!("Transaction sets metadata on {}, which isn't stored", hex::encode(signature)).into());
+                    }
+                }
+                TxnOp::Delete { signature } => {
+                    // Deleting something already deleted is fine, unknown isn't
+                    if !live.contains_key(signature) && !store.contains(signature) && !store.is_deleted(signature) {
+                        return Err(anyhow!("Transaction deletes {}, which isn't stored", hex::encode(signature)).into());
+                    }
+                    live.insert(*signature, false);
+                }
+                TxnOp::DeleteKey { key } => {
+                    let set = keys.get(key.as_str()).copied()
+                        .unwrap_or_else(|| store.key_signature(key).is_some());
+                    if !set {
+                        return Err(anyhow!("Transaction deletes key {}, which isn't set", key).into());
+                    }
+                    keys.insert(key, false);
+                }
+            }
+        }
+        Ok(())
+    }
+    
+    /// Write the journal and its commit marker, fsyncing before and after the marker
+    fn write_journal(&self) -> Result<()> {
+        let body = bincode::serialize(&self.ops)?;
+        let body = match self.store.cipher_key() {
+            Some(key) => {
+                let nonce = cipher::random_nonce();
+                let mut sealed = nonce.to_vec();
+                sealed.extend_from_slice(&cipher::seal(key, &nonce, JOURNAL_MAGIC, &body));
+                sealed
+            }
+            None => body,
+        };
+        
+        let path = journal_path(self.store.path());
+        let mut journal = OpenOptions::new().create(true).write(true).truncate(true).open(&path)?;
+        journal.write_all(JOURNAL_MAGIC)?;
+        journal.write_all(&(body.len() as u64).to_le_bytes())?;
+        journal.write_all(&body)?;
+        journal.write_all(blake3::hash(&body).as_bytes())?;
+        journal.sync_all()?;
+        
+        journal.write_all(COMMIT_MARKER)?;
+        journal.sync_all()?;
+        sync_parent_dir(&path)
+    }
+}
+
+/// Finish (or forget) a transaction that was interrupted, returning how many operations were replayed
+///
+/// Called by `Mem8Lite::open` before anything else can write.
+pub(crate) fn recover(store: &mut Mem8Lite) -> Result<usize> {
+    let path = journal_path(store.path());
+    let bytes = match std::fs::read(&path) {
+        Ok(bytes) => bytes,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(0),
+        Err(e) => return Err(e.into()),
+    };
+    
+    let ops = match committed_body(&bytes) {
+        Some(body) => {
+            let body = match store.cipher_key() {
+                Some(key) => {
+                    if body.len() < cipher::NONCE_LEN {
+                        return Err(anyhow!("Truncated transaction journal {}", path.display()).into());
+                    }
+                    let (nonce, sealed) = body.split_at(cipher::NONCE_LEN);
+                    cipher::open(key, nonce.try_into()?, JOURNAL_MAGIC, sealed).map_err(|e| {
+                        anyhow::Error::new(e).context(format!("Couldn't decrypt transaction journal {}", path.display()))
+                    })?
+                }
+                None => body.to_vec(),
+            };
+            let ops: Vec<TxnOp> = bincode::deserialize(&body)?;
+            ops
+        }
+        // No commit marker: the transaction never happened
+        None => {
+            eprintln!("⚠️ mem8: discarding uncommitted transaction journal {}", path.display());
+            Vec::new()
+        }
+    };
+    
+    for op in &ops {
+        apply(store, op)?;
+    }
+    if !ops.is_empty() {
+        store.sync()?;
+    }
+    std::fs::remove_file(&path)?;
+    Ok(ops.len())
+}
+
+/// The operations of a journal whose commit marker made it to disk
+fn committed_body(bytes: &[u8]) -> Option<&[u8]> {
+    let rest = bytes.strip_prefix(JOURNAL_MAGIC)?;
+    let len = u64::from_le_bytes(rest.get(..8)?.try_into().ok()?) as usize;
+    let rest = &rest[8..];
+    let body = rest.get(..len)?;
+    let checksum = rest.get(len..len + 32)?;
+    let marker = rest.get(len + 32..)?;
+    (marker == COMMIT_MARKER && checksum == blake3::hash(body).as_bytes()).then_some(body)
+}
+
+/// Apply one operation; applying it again changes nothing
+fn apply(store: &mut Mem8Lite, op: &TxnOp) -> Result<()> {
+    match op {
+        TxnOp::Store { data, metadata } => {
+            store.store(data, metadata.clone())?;
+        }
+        TxnOp::StoreKeyed { key, data, metadata } => {
+            store.store_keyed(key, data, metadata.clone())?;
+        }
+        TxnOp::SetMetadata { signature, metadata } => {
+            if store.get_metadata(signature).as_ref() != Some(metadata) {
+                store.set_metadata(signature, metadata.clone())?;
+            }
+        }
+        TxnOp::Delete { signature } => {
+            if store.contains(signature) {
+                store.delete(signature)?;
+            }
+        }
+        TxnOp::DeleteKey { key } => {
+            if store.key_signature(key).is_some() {
+                store.delete_key(key)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Where a store keeps the journal of a commit in progress (`<path>.wal`)
+fn journal_path(path: &Path) -> PathBuf {
+    let mut journal = path.as_os_str().to_owned();
+    journal.push(".wal");
+    PathBuf::from(journal)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+    
+    /// A blob, two metadata records and a key - the shape from the bug report
+    fn fill(txn: &mut Txn) -> [u8; 32] {
+        let blob = txn.store(b"state blob v2", None);
+        txn.set_metadata(&blob, br#"{"schema": 2}"#.to_vec());
+        txn.store(br#"{"owner": "hue"}"#, None);
+        txn.store_keyed("app/current", b"pointer to blob v2", None).unwrap();
+        txn.delete_key("app/previous");
+        blob
+    }
+    
+    #[test]
+    fn test_commit_is_all_or_nothing_across_crashes() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("app.m8");
+        {
+            let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+            storage.store_keyed("app/previous", b"pointer to blob v1", None).unwrap();
+            
+            // Bad transactions are refused before anything is written
+            let mut txn = storage.begin_txn();
+            txn.delete(&[9u8; 32]);
+            assert!(txn.commit().is_err());
+            let mut txn = storage.begin_txn();
+            fill(&mut txn);
+            txn.rollback();
+            assert_eq!(storage.iter().count(), 1);
+        }
+        
+        // Crash while writing the journal: every cut before the marker is a no-op
+        let journal = {
+            let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+            let mut txn = storage.begin_txn();
+            fill(&mut txn);
+            txn.write_journal().unwrap();
+            std::fs::read(journal_path(&path)).unwrap()
+        };
+        for cut in [0, 5, 16, journal.len() / 2, journal.len() - 8, journal.len() - 1] {
+            std::fs::write(journal_path(&path), &journal[..cut]).unwrap();
+            let storage = Mem8Lite::new(&path, 1.0).unwrap();
+            assert_eq!(storage.recovery().replayed_ops, 0);
+            assert_eq!(storage.iter().count(), 1, "cut at {}", cut);
+            assert!(storage.key_signature("app/previous").is_some());
+            assert!(!journal_path(&path).exists());
+        }
+        
+        // Crash after the marker, with the first two writes applied: replay finishes it
+        {
+            let mut storage = Mem8Lite::new(&path, 1.0).unwrap();
+            let mut txn = storage.begin_txn();
+            fill(&mut txn);
+            txn.write_journal().unwrap();
+            let applied = txn.ops[..2].to_vec();
+            drop(txn);
+            for op in &applied {
+                apply(&mut storage, op).unwrap();
+            }
+        }
+        let storage = Mem8Lite::new(&path, 1.0).unwrap();
+        assert_eq!(storage.recovery().replayed_ops, 5);
+        assert!(!journal_path(&path).exists());
+        let blob = content_signature(b"state blob v2", None);
+        assert_eq!(storage.get_metadata(&blob).unwrap(), br#"{"schema": 2}"#);
+        assert_eq!(storage.metadata_history(&blob).unwrap().len(), 2);
+        assert_eq!(storage.iter().count(), 4);
+        assert_eq!(storage.retrieve_keyed("app/current").unwrap(), b"pointer to blob v2");
+        assert!(storage.key_signature("app/previous").is_none());
+    }
+    
+    #[test]
+    fn test_encrypted_commit() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("sealed.m8");
+        let key = [3u8; cipher::KEY_LEN];
+        let mut storage = Mem8Lite::new_encrypted(&path, 1.0, &key).unwrap();
+        storage.store_keyed("app/previous", b"pointer to blob v1", None).unwrap();
+        
+        let mut txn = storage.begin_txn();
+        let blob = fill(&mut txn);
+        txn.write_journal().unwrap();
+        let journal = std::fs::read(journal_path(&path)).unwrap();
+        assert!(!journal.windows(13).any(|w| w == b"state blob v2"));
+        drop(storage);
+        
+        let storage = Mem8Lite::new_encrypted(&path, 1.0, &key).unwrap();
+        assert_eq!(storage.retrieve(&blob).unwrap(), b"state blob v2");
+    }
+}
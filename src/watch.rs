@@ -0,0 +1,108 @@
+//! Watching Mem8Fs - hearing about writes, deletes and renames as they happen
+//!
+//! `Mem8Fs::watch(prefix)` hands back the receiving end of a bounded channel
+//! that gets an `FsEvent` for every change at or below `prefix`. Events are
+//! sent from inside the write, while the index lock is held, so they arrive
+//! in the order the changes were made. A watcher that falls behind doesn't
+//! hold anyone up: once its channel is full further events are dropped and
+//! counted (`Mem8Fs::dropped_events`). Dropping the receiver unregisters it.
+//!
+//! Trisha's config service hears about Hue's edits the moment they land. 👂
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::Mutex;
+use serde::{Serialize, Deserialize};
+
+/// Events a watcher can have queued before the rest are dropped
+pub const WATCH_CHANNEL_CAPACITY: usize = 1024;
+
+/// One change to a Mem8Fs file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FsEvent {
+    /// A new file
+    Created { path: PathBuf, signature: [u8; 32] },
+    
+    /// New content for an existing file (`write`, `append`, `write_at`)
+    Modified { path: PathBuf, signature: [u8; 32] },
+    
+    /// Gone (`delete`, `secure_delete`, or replaced by a rename)
+    Deleted { path: PathBuf, signature: [u8; 32] },
+    
+    /// Moved from `from` to `path`, content untouched (one per file when a directory moves)
+    Renamed { from: PathBuf, path: PathBuf, signature: [u8; 32] },
+}
+
+impl FsEvent {
+    /// Where the file is now (or was, for `Deleted`)
+    pub fn path(&self) -> &Path {
+        match self {
+            FsEvent::Created { path, .. }
+            | FsEvent::Modified { path, .. }
+            | FsEvent::Deleted { path, .. }
+            | FsEvent::Renamed { path, .. } => path,
+        }
+    }
+    
+    pub fn signature(&self) -> &[u8; 32] {
+        match self {
+            FsEvent::Created { signature, .. }
+            | FsEvent::Modified { signature, .. }
+            | FsEvent::Deleted { signature, .. }
+            | FsEvent::Renamed { signature, .. } => signature,
+        }
+    }
+    
+    /// Does this concern anything at or below `prefix`? (Renames count from either end.)
+    fn touches(&self, prefix: &Path) -> bool {
+        match self {
+            FsEvent::Renamed { from, path, .. } => from.starts_with(prefix) || path.starts_with(prefix),
+            _ => self.path().starts_with(prefix),
+        }
+    }
+}
+
+/// Everyone watching one Mem8Fs
+#[derive(Default)]
+pub(crate) struct Watchers {
+    watchers: Mutex<Vec<(PathBuf, SyncSender<FsEvent>)>>,
+    dropped: AtomicU64,
+}
+
+impl Watchers {
+    pub(crate) fn add(&self, prefix: PathBuf) -> Receiver<FsEvent> {
+        let (sender, receiver) = sync_channel(WATCH_CHANNEL_CAPACITY);
+        self.watchers.lock().unwrap().push((prefix, sender));
+        receiver
+    }
+    
+    /// Hand `event` to every watcher it concerns, forgetting the ones that hung up
+    pub(crate) fn notify(&self, event: FsEvent) {
+        let mut watchers = self.watchers.lock().unwrap();
+        watchers.retain(|(prefix, sender)| {
+            if !event.touches(prefix) {
+                return true;
+            }
+            match sender.try_send(event.clone()) {
+                Ok(()) => true,
+                Err(TrySendError::Full(_)) => {
+                    self.dropped.fetch_add(1, Ordering::Relaxed);
+                    true
+                }
+                Err(TrySendError::Disconnected(_)) => false,
+            }
+        });
+    }
+    
+    pub(crate) fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+    
+    /// Watchers still registered (a hung-up one goes at the next event it would get)
+    #[cfg(test)]
+    pub(crate) fn len(&self) -> usize {
+        self.watchers.lock().unwrap().len()
+    }
+}
@@ -0,0 +1,152 @@
+//! Wonder index - the most salient packets in a Mem8Lite store, without a scan
+//!
+//! Every packet stored (or given new metadata) with a Marine analysis in it
+//! gets its peak salience noted in a third sidecar, `<path>.wonder`, next to
+//! the offset of the record its metadata came from; `Mem8Lite::top_wonder`
+//! ranks from that. Two metadata layouts count as analysed:
+//! `MarineMetadata`'s own JSON (`attach_marine`, `store_analyzed`), which
+//! has a `max_salience`, and a `marine` block like `store_audio` and
+//! `backfill_analysis` write, whose strongest `moments` (or, without any,
+//! its mean `salience`) stand in for it. Anything else is noted as not
+//! analysed, so it isn't looked at again.
+//!
+//! Like `.tsidx` it's only a cache of what the packets say: a record whose
+//! offset isn't where the packet's metadata is now (compacted, given new
+//! metadata, deleted and stored again) is ignored on open and the packet
+//! read again, and encrypted stores keep it in memory only.
+//!
+//! Hue's shelf of the most breathtaking moments. ✨
+
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use serde_json::Value;
+use crate::error::Result;
+
+/// Size of one wonder index record: signature + metadata offset + salience (NaN: not analysed)
+const WONDER_RECORD_SIZE: usize = 32 + 8 + 8;
+
+/// The peak salience a packet's metadata records, if it holds a Marine analysis
+pub fn max_salience(metadata: &[u8]) -> Option<f64> {
+    let meta: Value = serde_json::from_slice(metadata).ok()?;
+    if meta.get("total_peaks").is_some() {
+        return meta["max_salience"].as_f64();
+    }
+    let marine = meta.get("marine")?;
+    marine["moments"].as_array()
+        .and_then(|moments| moments.iter().filter_map(|moment| moment["salience"].as_f64()).reduce(f64::max))
+        .or_else(|| marine["salience"].as_f64())
+}
+
+/// Each packet's noted salience, and the sidecar it's kept in
+pub(crate) struct WonderIndex {
+    /// Signature -> (offset of the metadata's record, its peak salience if analysed)
+    entries: HashMap<[u8; 32], (u64, Option<f64>)>,
+    
+    /// `<path>.wonder` (none for encrypted stores)
+    file: Option<File>,
+}
+
+impl WonderIndex {
+    /// Open (or create) the sidecar at `path`, or keep it in memory with None
+    pub(crate) fn open(path: Option<&Path>) -> Result<Self> {
+        let mut entries = HashMap::new();
+        let file = match path {
+            Some(path) => {
+                let mut file = OpenOptions::new().create(true).read(true).append(true).open(path)?;
+                let mut raw = Vec::new();
+                file.seek(SeekFrom::Start(0))?;
+                file.read_to_end(&mut raw)?;
+                let whole = raw.len() / WONDER_RECORD_SIZE * WONDER_RECORD_SIZE;
+                if whole != raw.len() {
+                    file.set_len(whole as u64)?;
+                }
+                for record in raw[..whole].chunks_exact(WONDER_RECORD_SIZE) {
+                    let signature: [u8; 32] = record[..32].try_into()?;
+                    let mut fields = &record[32..];
+                    let offset = fields.read_u64::<BigEndian>()?;
+                    let salience = fields.read_f64::<BigEndian>()?;
+                    entries.insert(signature, (offset, (!salience.is_nan()).then_some(salience)));
+                }
+                Some(file)
+            }
+            None => None,
+        };
+        Ok(Self { entries, file })
+    }
+    
+    /// What was noted for `signature`, as (metadata offset, salience)
+    pub(crate) fn get(&self, signature: &[u8; 32]) -> Option<(u64, Option<f64>)> {
+        self.entries.get(signature).copied()
+    }
+    
+    /// Note the salience of the metadata at `offset`
+    pub(crate) fn note(&mut self, signature: &[u8; 32], offset: u64, salience: Option<f64>) -> Result<()> {
+        self.entries.insert(*signature, (offset, salience));
+        if let Some(file) = &mut self.file {
+            let mut record = Vec::with_capacity(WONDER_RECORD_SIZE);
+            record.extend_from_slice(signature);
+            record.write_u64::<BigEndian>(offset)?;
+            record.write_f64::<BigEndian>(salience.unwrap_or(f64::NAN))?;
+            file.write_all(&record)?;
+        }
+        Ok(())
+    }
+    
+    /// Forget `signature` (its records on disk no longer match anything live)
+    pub(crate) fn forget(&mut self, signature: &[u8; 32]) {
+        self.entries.remove(signature);
+    }
+    
+    /// Keep only the signatures `keep` says to
+    pub(crate) fn retain(&mut self, mut keep: impl FnMut(&[u8; 32], u64) -> bool) {
+        self.entries.retain(|signature, &mut (offset, _)| keep(signature, offset));
+    }
+    
+    /// Forget everything, on disk too
+    pub(crate) fn clear(&mut self) -> Result<()> {
+        self.entries.clear();
+        if let Some(file) = &mut self.file {
+            file.set_len(0)?;
+        }
+        Ok(())
+    }
+    
+    /// Every analysed packet, most salient first (ties in signature order)
+    pub(crate) fn ranked(&self) -> Vec<([u8; 32], f64)> {
+        let mut ranked: Vec<([u8; 32], f64)> = self.entries.iter()
+            .filter_map(|(signature, &(_, salience))| Some((*signature, salience?)))
+            .collect();
+        ranked.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        ranked
+    }
+    
+    pub(crate) fn sync(&self) -> Result<()> {
+        if let Some(file) = &self.file {
+            file.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_both_layouts_count_as_analysed() {
+        let marine = crate::marine::MarineMetadata {
+            max_salience: 0.75,
+            ..crate::marine::MarineProcessor::new().extract_metadata(&[])
+        };
+        assert_eq!(max_salience(&serde_json::to_vec(&marine).unwrap()), Some(0.75));
+        
+        let block = br#"{"name":"a","marine":{"salience":0.2,"moments":[{"frame":1,"salience":0.4},{"frame":9,"salience":0.9}]}}"#;
+        assert_eq!(max_salience(block), Some(0.9));
+        assert_eq!(max_salience(br#"{"marine":{"salience":0.3,"moments":[]}}"#), Some(0.3));
+        assert_eq!(max_salience(br#"{"name":"no analysis"}"#), None);
+        assert_eq!(max_salience(b"not json"), None);
+    }
+}